@@ -14,9 +14,29 @@ CREATE TABLE IF NOT EXISTS commands (
     exit_code INTEGER,
     tags TEXT, -- JSON array of tags
     context TEXT, -- What was happening before this command
+    canonical TEXT, -- Spelling shared by commands that only differ in quoting
+    hash TEXT, -- Content hash, the same for this command on every machine
     UNIQUE(project_path, command) ON CONFLICT REPLACE
 );
 
+-- Every individual run of a command (commands rows are deduplicated)
+CREATE TABLE IF NOT EXISTS executions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    command_id INTEGER NOT NULL,
+    executed_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    execution_time_ms INTEGER,
+    exit_code INTEGER,
+    session_id TEXT, -- Set by the shell hook, one per shell
+    hostname TEXT,
+    cwd TEXT, -- Where it ran (commands only keep the project root)
+    env TEXT, -- JSON object: capture_env variables plus the active venv/conda/node/rust
+    branch TEXT, -- Git branch checked out when it ran
+    rapid_repeat_count INTEGER DEFAULT 0, -- Identical reruns within debounce_seconds, folded in
+    interactive BOOLEAN, -- 0 when the shell wasn't interactive (a script), NULL if the hook didn't say
+    privileged BOOLEAN DEFAULT 0, -- Run through sudo or doas
+    FOREIGN KEY(command_id) REFERENCES commands(id) ON DELETE CASCADE
+);
+
 -- Command patterns (for auto-detection)
 CREATE TABLE IF NOT EXISTS command_patterns (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -70,13 +90,45 @@ CREATE TABLE IF NOT EXISTS execution_context (
     FOREIGN KEY(command_id) REFERENCES commands(id) ON DELETE CASCADE
 );
 
+-- Edits to replay when merging another machine's database (see db/oplog.rs)
+CREATE TABLE IF NOT EXISTS oplog (
+    device TEXT NOT NULL, -- The database that made the edit
+    clock INTEGER NOT NULL, -- Lamport clock, higher wins
+    hash TEXT NOT NULL, -- The command's content hash (see db/identity.rs)
+    edit TEXT NOT NULL, -- 'favorite', 'tags', 'delete'
+    value TEXT, -- '1'/'0' for favorite, JSON array for tags
+    at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY(device, clock)
+);
+
+-- Each program in a compound command (a | b && c), so a tool is found
+-- wherever in a line it ran. Plain commands have no rows here.
+CREATE TABLE IF NOT EXISTS command_stages (
+    command_id INTEGER NOT NULL,
+    position INTEGER NOT NULL, -- 0 for the first stage
+    joint TEXT, -- How it follows the one before: 'pipe', 'and', 'or', 'then'
+    program TEXT NOT NULL,
+    stage TEXT NOT NULL, -- As typed, redirections included
+    PRIMARY KEY(command_id, position),
+    FOREIGN KEY(command_id) REFERENCES commands(id) ON DELETE CASCADE
+);
+
+-- How many commands were left out and why. Only counts, never the commands
+CREATE TABLE IF NOT EXISTS dropped_recordings (
+    reason TEXT PRIMARY KEY, -- 'ignore_rule', 'sensitive', 'leading_space', 'noise', 'too_long'
+    count INTEGER NOT NULL DEFAULT 0,
+    last_at DATETIME
+);
+
 -- Indexes for performance
 CREATE INDEX IF NOT EXISTS idx_commands_project ON commands(project_path);
 CREATE INDEX IF NOT EXISTS idx_commands_timestamp ON commands(timestamp DESC);
 CREATE INDEX IF NOT EXISTS idx_commands_usage ON commands(usage_count DESC);
+CREATE INDEX IF NOT EXISTS idx_executions_command ON executions(command_id);
 CREATE INDEX IF NOT EXISTS idx_patterns_project ON command_patterns(project_path);
 CREATE INDEX IF NOT EXISTS idx_suggestions_project ON suggestions(project_path);
 CREATE INDEX IF NOT EXISTS idx_context_command ON execution_context(command_id);
+CREATE INDEX IF NOT EXISTS idx_stages_program ON command_stages(program);
 
 -- Insert default preferences
 INSERT OR IGNORE INTO preferences (key, value) VALUES
@@ -84,4 +136,7 @@ INSERT OR IGNORE INTO preferences (key, value) VALUES
     ('enable_suggestions', 'true'),
     ('enable_pattern_detection', 'true'),
     ('suggestion_threshold', '0.7'),
-    ('auto_cleanup_days', '90');
+    ('auto_cleanup_days', '90'),
+    ('session_gap_minutes', '30'),
+    ('dedup_canonical', 'true'),
+    ('notify_after_minutes', '5');
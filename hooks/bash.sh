@@ -2,29 +2,123 @@
 # Bash hook for berri-recall
 # Records every command you type automatically
 
+# One ID per shell so `berri-recall sessions` can tell terminals apart
+__berri_session="${__berri_session:-$(date +%s)-$$}"
+export BERRI_RECALL_SESSION="$__berri_session"
+
 __berri_hook() {
     local exit_code=$?
     local cmd="${__berri_last_cmd}"
+    __berri_last_cmd=""
+    __berri_at_prompt=1
+
+    # How long it took (bash 5+ has EPOCHREALTIME, older bash just skips this)
+    local duration_ms=""
+    if [[ -n "$__berri_start" && -n "$EPOCHREALTIME" ]]; then
+        local now="${EPOCHREALTIME/[.,]/}"
+        duration_ms=$(( (now - __berri_start) / 1000 ))
+    fi
+    __berri_start=""
+    __berri_typed=""
+
+    # New directory? Warm its caches and introduce it if it's new to us too
+    __berri_chpwd
 
     # Nothing to record? bail out
     [[ -z "$cmd" ]] && return 0
 
-    # Don't record berri commands (that would be weird)
-    [[ "$cmd" =~ ^berri ]] && return 0
+    # What bash's history has last, to tell ignoredups from ignorespace
+    [[ -z "$__berri_space" ]] && __berri_prev_cmd="$cmd"
+
+    # A shell berri-recall started (picker, trigger) doesn't record, and
+    # `record` skips berri-recall's own commands itself
+    [[ -n "$BERRI_RECALL_INTERNAL" ]] && return 0
 
-    # Run this in background so it doesn't slow your terminal
+    # "✓ 4m 12s — 15% faster than your median" after long commands (set
+    # summary_after_seconds to turn it on), before this run is recorded.
+    # The marker file saves starting berri-recall at all while it's off.
+    if [[ -n "$duration_ms" && "$cmd" != berri* ]] && (( duration_ms >= 1000 )) \
+        && [[ -n "$BERRI_RECALL_SUMMARY_AFTER" || -e "$HOME/.berri-recall/summary-on" ]]; then
+        berri-recall finished --command "$cmd" --exit-code "$exit_code" \
+            --duration "$duration_ms" --cwd "$PWD" 2>/dev/null
+    fi
+
+    # A script sourcing the hook isn't you at a prompt
+    local mode=--interactive
+    [[ $- == *i* ]] || mode=--non-interactive
+
+    # Fire and forget: backgrounded inside a subshell, so it's never one of
+    # this shell's jobs and the prompt doesn't wait for (or report on) it
     (
         berri-recall record \
             --command "$cmd" \
             --exit-code "$exit_code" \
+            ${duration_ms:+--duration "$duration_ms"} \
             --cwd "$PWD" \
-            &> /dev/null
-    ) &
+            --session "$__berri_session" \
+            "$mode" \
+            ${__berri_space:+--leading-space} \
+            ${HISTIGNORE:+--histignore "$HISTIGNORE"} \
+            < /dev/null &> /dev/null &
+    )
+}
+
+# First prompt in a directory (bash has no chpwd, so compare with the last one)
+__berri_chpwd() {
+    [[ "$PWD" == "$__berri_last_pwd" ]] && return 0
+    __berri_last_pwd="$PWD"
+
+    [[ -n "$BERRI_RECALL_INTERNAL" ]] && return 0
+
+    # A project with nothing recorded yet gets a short intro, once
+    # (`berri-recall config set hello false` turns it off). The files it
+    # leaves once it's off, or done with this directory, save starting it.
+    if [[ ! -e "$HOME/.berri-recall/hello-off" \
+        && ! -e "$HOME/.berri-recall/hello/${PWD//\//%}" ]]; then
+        berri-recall hello --once --cwd "$PWD" 2>/dev/null
+    fi
+
+    # Get its suggestions into the prompt cache in the background
+    ( berri-recall warm --cwd "$PWD" < /dev/null &> /dev/null & )
 }
 
 # Grab the command before it runs
 __berri_preexec() {
+    # DEBUG also fires for PROMPT_COMMAND itself, which isn't the user's command
+    [[ "$PROMPT_COMMAND" == *"$BASH_COMMAND"* ]] && return 0
+
+    # First command after the prompt
+    if [[ -n "$__berri_at_prompt" ]]; then
+        __berri_at_prompt=""
+        __berri_typed=1
+
+        # BASH_COMMAND has no leading space, but with ignorespace bash leaves
+        # such a line out of history, so HISTCMD doesn't move. A repeat under
+        # ignoredups doesn't move it either, hence the comparison.
+        __berri_space=""
+        if [[ "$HISTCONTROL" == *ignorespace* || "$HISTCONTROL" == *ignoreboth* ]] \
+            && [[ "$HISTCMD" == "$__berri_histcmd" && "$BASH_COMMAND" != "$__berri_prev_cmd" ]]; then
+            __berri_space=1
+        fi
+        __berri_histcmd="$HISTCMD"
+    elif [[ -z "$__berri_typed" ]]; then
+        # Not typed at a prompt (the rest of .bashrc, for one)
+        return 0
+    fi
+
     __berri_last_cmd="$BASH_COMMAND"
+
+    # First DEBUG trap after the prompt is when the command actually starts
+    if [[ -z "$__berri_start" && -n "$EPOCHREALTIME" ]]; then
+        # "usually ~4m 30s" before slow commands (opt in with `export BERRI_RECALL_ETA=1`)
+        if [[ -n "$BERRI_RECALL_ETA" && "$BASH_COMMAND" != berri* ]]; then
+            local eta
+            eta=$(berri-recall eta --min-seconds 30 --command "$BASH_COMMAND" --cwd "$PWD" 2>/dev/null) \
+                && echo "⏱  $eta"
+        fi
+
+        __berri_start="${EPOCHREALTIME/[.,]/}"
+    fi
 }
 
 # Set everything up
@@ -32,7 +126,7 @@ if [[ -z "$__berri_installed" ]]; then
     export __berri_installed=1
 
     # Newer bash (4.4+) has better command capture
-    if [[ ${BASH_VERSINFO[0]} -ge 4 ]] && [[ ${BASH_VERSINFO[1]} -ge 4 ]]; then
+    if (( BASH_VERSINFO[0] > 4 || (BASH_VERSINFO[0] == 4 && BASH_VERSINFO[1] >= 4) )); then
         trap '__berri_preexec' DEBUG
     else
         # Older bash needs to use history
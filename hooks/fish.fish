@@ -1,8 +1,22 @@
 # Fish hook for berri-recall
 # Records commands automatically
 
+# One ID per shell so `berri-recall sessions` can tell terminals apart
+set -q __berri_session; or set -g __berri_session (date +%s)-$fish_pid
+set -gx BERRI_RECALL_SESSION $__berri_session
+
+# "usually ~4m 30s" before slow commands (opt in with `set -gx BERRI_RECALL_ETA 1`)
+function __berri_preexec --on-event fish_preexec
+    if set -q BERRI_RECALL_ETA; and not string match -q "berri*" -- $argv[1]
+        set -l eta (berri-recall eta --min-seconds 30 --command "$argv[1]" --cwd "$PWD" 2>/dev/null)
+        and echo "⏱  $eta"
+    end
+end
+
 function __berri_postexec --on-event fish_postexec
     set -l exit_code $status
+    # fish times every command for us
+    set -l duration_ms $CMD_DURATION
     set -l cmd $argv[1]
 
     # Skip if no command
@@ -10,17 +24,107 @@ function __berri_postexec --on-event fish_postexec
         return 0
     end
 
-    # Don't record berri commands
-    if string match -q "berri*" -- $cmd
+    # A shell berri-recall started (picker, trigger) doesn't record, and
+    # `record` skips berri-recall's own commands itself
+    if set -q BERRI_RECALL_INTERNAL
         return 0
     end
 
-    # Background job so it doesn't block
-    fish -c "berri-recall record \
-        --command '$cmd' \
+    # "✓ 4m 12s — 15% faster than your median" after long commands (set
+    # summary_after_seconds to turn it on), before this run is recorded.
+    # The marker file saves starting berri-recall at all while it's off.
+    if test "$duration_ms" -ge 1000; and not string match -q "berri*" -- $cmd
+        if set -q BERRI_RECALL_SUMMARY_AFTER; or test -e ~/.berri-recall/summary-on
+            berri-recall finished --command "$cmd" --exit-code $exit_code \
+                --duration $duration_ms --cwd "$PWD" 2>/dev/null
+        end
+    end
+
+    # Typed with a leading space: the ignorespace habit says don't keep it
+    set -l space_flag
+    if string match -q -- ' *' $cmd
+        set space_flag --leading-space
+    end
+
+    # A script sourcing the hook isn't you at a prompt
+    set -l mode_flag --interactive
+    if not status is-interactive
+        set mode_flag --non-interactive
+    end
+
+    # Background job so it doesn't block. Arguments go straight to the binary:
+    # pasting them into a `fish -c` string would run whatever quotes they hold
+    berri-recall record $space_flag $mode_flag \
+        --command "$cmd" \
         --exit-code $exit_code \
-        --cwd '$PWD' \
-        &> /dev/null" &
+        --duration $duration_ms \
+        --cwd "$PWD" \
+        --session "$__berri_session" \
+        &> /dev/null &
+    disown
+end
+
+# Runs on every directory change
+function __berri_chpwd --on-variable PWD
+    if set -q BERRI_RECALL_INTERNAL
+        return 0
+    end
+
+    # A project with nothing recorded yet gets a short intro, once
+    # (`berri-recall config set hello false` turns it off). The files it
+    # leaves once it's off, or done with this directory, save starting it.
+    set -l greeted ~/.berri-recall/hello/(string replace -a / % -- $PWD)
+    if not test -e ~/.berri-recall/hello-off; and not test -e $greeted
+        berri-recall hello --once --cwd "$PWD" 2>/dev/null
+    end
+
+    # Get its suggestions into the prompt cache in the background
+    berri-recall warm --cwd "$PWD" &> /dev/null &
+    disown
+end
+
+# Runs on Enter, before the command does
+# - expands `!!r <alias>` into the stored command
+# - expands `!r:3` (3rd entry of `berri-recall recent`) and `!r:fail` (last failure)
+# - optionally asks before running something destructive you've never run here
+#   (turn it on with `set -gx BERRI_RECALL_CONFIRM 1`)
+function __berri_accept_line
+    set -l cmd (commandline)
+
+    # Only spawn berri-recall when the line is actually ours
+    if string match -q -- '!!r *' "$cmd"; or string match -q -- '*!r:*' "$cmd"
+        set -l expanded (berri-recall expand --command "$cmd" --cwd "$PWD" 2>&1)
+
+        if test $status -ne 0
+            echo
+            printf '%s\n' $expanded
+            commandline -f repaint
+            return
+        end
+
+        commandline -r -- "$expanded"
+        set cmd "$expanded"
+    end
+
+    if set -q BERRI_RECALL_CONFIRM; and test -n "$cmd"
+        set -l warning (berri-recall check --command "$cmd" --cwd "$PWD" 2>&1)
+
+        if test $status -eq 2
+            echo
+            printf '%s\n' $warning
+            read -l -P "Run it anyway? [y/N] " answer
+            if not string match -qi 'y*' -- "$answer"
+                commandline -f repaint
+                return
+            end
+        end
+    end
+
+    commandline -f execute
 end
 
+# Enter goes through us first (expansion is free unless you type !!r)
+bind \r __berri_accept_line
+bind \n __berri_accept_line
+
 set -g __berri_installed 1
@@ -3,42 +3,119 @@
 
 if (-not $global:__berri_installed) {
     $global:__berri_installed = $true
-    $global:__berri_last_cmd = ""
+    # Last history entry recorded, so an empty Enter doesn't record it again
+    $global:__berri_last_id = (Get-History -Count 1).Id
+    # One ID per shell so `berri-recall sessions` can tell terminals apart
+    $global:__berri_session = "$([DateTimeOffset]::Now.ToUnixTimeSeconds())-$PID"
+    $env:BERRI_RECALL_SESSION = $global:__berri_session
 
-    $ExecutionContext.InvokeCommand.PreCommandLookupAction = {
-        param($CommandName, $CommandLookupEventArgs)
-        $global:__berri_last_cmd = $CommandName
-    }
+    # Thread jobs start in milliseconds, Start-Job spins up a whole new
+    # PowerShell (only Windows PowerShell 5.1 without the module falls back)
+    $global:__berri_thread_jobs = [bool](Get-Command Start-ThreadJob -ErrorAction SilentlyContinue)
 
     function global:__berri_hook {
-        $exit_code = $LASTEXITCODE
-        if ($null -eq $exit_code) { $exit_code = 0 }
+        param($success, $native_code)
 
-        $cmd = (Get-History -Count 1).CommandLine
+        $entry = Get-History -Count 1
+        if ($null -eq $entry -or $entry.Id -eq $global:__berri_last_id) {
+            return
+        }
+        $global:__berri_last_id = $entry.Id
 
+        # CommandLine keeps every line of a multi-line command
+        $cmd = $entry.CommandLine
         if ([string]::IsNullOrWhiteSpace($cmd)) {
             return
         }
 
-        # Don't record berri commands
-        if ($cmd -match "^berri") {
+        # A shell berri-recall started (picker, trigger) doesn't record, and
+        # `record` skips berri-recall's own commands itself
+        if ($env:BERRI_RECALL_INTERNAL) {
             return
         }
 
-        # Background job so it doesn't block
-        Start-Job -ScriptBlock {
-            param($command, $exitCode, $workingDir)
+        # $? covers cmdlets, $LASTEXITCODE only native programs (and it
+        # keeps the last one's code forever, so only trust it on failure)
+        $exit_code = 0
+        if (-not $success) {
+            $exit_code = if ($native_code) { $native_code } else { 1 }
+        }
+
+        $duration_ms = [int]($entry.EndExecutionTime - $entry.StartExecutionTime).TotalMilliseconds
+
+        # Quotes and newlines don't survive being passed to a native program
+        # on every PowerShell version, base64 does
+        $encoded = [Convert]::ToBase64String([Text.Encoding]::UTF8.GetBytes($cmd))
+
+        # "✓ 4m 12s — 15% faster than your median" after long commands (set
+        # summary_after_seconds to turn it on), before this run is recorded.
+        # The marker file saves starting berri-recall at all while it's off.
+        if ($duration_ms -ge 1000 -and $cmd -notlike 'berri*' -and
+            ($env:BERRI_RECALL_SUMMARY_AFTER -or
+                (Test-Path (Join-Path $HOME '.berri-recall/summary-on')))) {
+            & berri-recall finished --command-base64 $encoded --exit-code $exit_code `
+                --duration $duration_ms --cwd $PWD.ProviderPath 2>$null
+        }
+
+        $record = {
+            param($encoded, $exitCode, $duration, $workingDir, $session)
             & berri-recall record `
-                --command $command `
+                --command-base64 $encoded `
                 --exit-code $exitCode `
+                --duration $duration `
                 --cwd $workingDir `
+                --session $session `
                 2>&1 | Out-Null
-        } -ArgumentList $cmd, $exit_code, $PWD | Out-Null
+        }
+        $job_args = $encoded, $exit_code, $duration_ms, $PWD.ProviderPath, $global:__berri_session
+
+        # Background job so it doesn't block
+        if ($global:__berri_thread_jobs) {
+            Start-ThreadJob -Name __berri_record -ScriptBlock $record -ArgumentList $job_args | Out-Null
+        } else {
+            Start-Job -Name __berri_record -ScriptBlock $record -ArgumentList $job_args | Out-Null
+        }
+
+        # Finished jobs would pile up in Get-Job otherwise
+        Get-Job -Name __berri_record -ErrorAction SilentlyContinue |
+            Where-Object { $_.State -in 'Completed', 'Failed' } |
+            Remove-Job -ErrorAction SilentlyContinue
     }
 
     $originalPrompt = $function:prompt
     function global:prompt {
-        __berri_hook
+        # Has to come first: anything else run here resets both
+        $success = $?
+        $native_code = $global:LASTEXITCODE
+
+        __berri_hook $success $native_code
+
+        # New directory: a project with nothing recorded yet gets a short
+        # intro, once (`berri-recall config set hello false` turns it off),
+        # and its suggestions go into the prompt cache in the background
+        if ($PWD.ProviderPath -ne $global:__berri_last_pwd) {
+            $global:__berri_last_pwd = $PWD.ProviderPath
+            if (-not $env:BERRI_RECALL_INTERNAL) {
+                # The files it leaves once it's off, or done with this
+                # directory, save starting it
+                $greeted = Join-Path $HOME ('.berri-recall/hello/' +
+                    ($PWD.ProviderPath -replace '[\\/:]', '%'))
+                if (-not (Test-Path (Join-Path $HOME '.berri-recall/hello-off')) -and
+                    -not (Test-Path -LiteralPath $greeted)) {
+                    & berri-recall hello --once --cwd $PWD.ProviderPath 2>$null
+                }
+
+                $warm = { param($workingDir) & berri-recall warm --cwd $workingDir 2>&1 | Out-Null }
+                if ($global:__berri_thread_jobs) {
+                    Start-ThreadJob -Name __berri_record -ScriptBlock $warm -ArgumentList $PWD.ProviderPath | Out-Null
+                } else {
+                    Start-Job -Name __berri_record -ScriptBlock $warm -ArgumentList $PWD.ProviderPath | Out-Null
+                }
+            }
+        }
+
+        # Leave it as it was for the user's own prompt
+        $global:LASTEXITCODE = $native_code
         & $originalPrompt
     }
 
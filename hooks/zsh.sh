@@ -2,9 +2,26 @@
 # Zsh hook for berri-recall
 # Records commands automatically
 
+# EPOCHREALTIME for timing commands
+zmodload zsh/datetime 2>/dev/null
+
+# One ID per shell so `berri-recall sessions` can tell terminals apart
+typeset -g __berri_session="${__berri_session:-${EPOCHSECONDS:-$(date +%s)}-$$}"
+export BERRI_RECALL_SESSION="$__berri_session"
+
 # Called right before a command runs
 __berri_preexec() {
     typeset -g __berri_last_cmd="$1"
+
+    # "usually ~4m 30s" before slow commands (opt in with `export BERRI_RECALL_ETA=1`)
+    if [[ -n "$BERRI_RECALL_ETA" && "$1" != berri* ]]; then
+        local eta
+        eta=$(berri-recall eta --min-seconds 30 --command "$1" --cwd "$PWD" 2>/dev/null) \
+            && print -r -- "⏱  $eta"
+    fi
+
+    # Started after the lookup so it doesn't count towards the duration
+    typeset -g __berri_start="$EPOCHREALTIME"
 }
 
 # Called right after a command finishes
@@ -13,21 +30,112 @@ __berri_precmd() {
 
     [[ -z "$__berri_last_cmd" ]] && return 0
 
-    # Don't record berri commands
-    [[ "$__berri_last_cmd" =~ ^berri ]] && return 0
+    # Integer typeset truncates the float for us
+    local -i duration_ms=0
+    if [[ -n "$__berri_start" && -n "$EPOCHREALTIME" ]]; then
+        duration_ms=$(( (EPOCHREALTIME - __berri_start) * 1000 ))
+    fi
+
+    # A shell berri-recall started (picker, trigger) doesn't record, and
+    # `record` skips berri-recall's own commands itself
+    [[ -n "$BERRI_RECALL_INTERNAL" ]] && return 0
 
-    # Run silently in background without job notification
-    {
-        berri-recall record \
-            --command "$__berri_last_cmd" \
-            --exit-code "$exit_code" \
-            --cwd "$PWD" \
-            &> /dev/null
-    } &!
+    # "✓ 4m 12s — 15% faster than your median" after long commands (set
+    # summary_after_seconds to turn it on). Before recording, so the median
+    # is of the runs before this one. The marker file saves starting
+    # berri-recall at all while it's off.
+    if (( duration_ms >= 1000 )) && [[ "$__berri_last_cmd" != berri* ]] \
+        && [[ -n "$BERRI_RECALL_SUMMARY_AFTER" || -e "$HOME/.berri-recall/summary-on" ]]; then
+        berri-recall finished --command "$__berri_last_cmd" --exit-code "$exit_code" \
+            --duration "$duration_ms" --cwd "$PWD" 2>/dev/null
+    fi
+
+    # Typed with a leading space: the ignorespace habit says don't keep it
+    local __berri_space=""
+    [[ "$__berri_last_cmd" == " "* ]] && __berri_space=1
+
+    # A script sourcing the hook isn't you at a prompt
+    local __berri_mode=--interactive
+    [[ -o interactive ]] || __berri_mode=--non-interactive
+
+    # What zsh keeps out of its history stays out of ours
+    local -a __berri_ignore=()
+    [[ -n "$HISTORY_IGNORE" ]] && __berri_ignore=(--history-ignore "$HISTORY_IGNORE")
+
+    # Fire and forget: &! disowns it, so the prompt never waits and there's
+    # no job notification
+    berri-recall record \
+        --command "$__berri_last_cmd" \
+        --exit-code "$exit_code" \
+        --duration "$duration_ms" \
+        --cwd "$PWD" \
+        --session "$__berri_session" \
+        "$__berri_mode" \
+        ${__berri_space:+--leading-space} \
+        "${__berri_ignore[@]}" \
+        < /dev/null &> /dev/null &!
 
     __berri_last_cmd=""
 }
 
+# First prompt in a directory: a project with nothing recorded yet gets a
+# short intro, once (`berri-recall config set hello false` turns it off)
+__berri_hello() {
+    [[ "$PWD" == "$__berri_last_pwd" ]] && return 0
+    typeset -g __berri_last_pwd="$PWD"
+
+    [[ -n "$BERRI_RECALL_INTERNAL" ]] && return 0
+    # Files berri-recall leaves once it's off, or done with this directory
+    [[ -e "$HOME/.berri-recall/hello-off" ]] && return 0
+    [[ -e "$HOME/.berri-recall/hello/${PWD//\//%}" ]] && return 0
+    berri-recall hello --once --cwd "$PWD" 2>/dev/null
+}
+
+# Right after a cd: get the new project's suggestions into the prompt cache
+# before the next prompt, in the background
+__berri_chpwd() {
+    [[ -n "$BERRI_RECALL_INTERNAL" ]] && return 0
+    berri-recall warm --cwd "$PWD" < /dev/null &> /dev/null &!
+}
+
+# Runs on Enter, before the command does
+# - expands `!!r <alias>` into the stored command
+# - expands `!r:3` (3rd entry of `berri-recall recent`) and `!r:fail` (last failure)
+# - optionally asks before running something destructive you've never run here
+#   (turn it on with `export BERRI_RECALL_CONFIRM=1`)
+__berri_accept_line() {
+    # Only spawn berri-recall when the line is actually ours
+    if [[ "$BUFFER" == '!!r '* || "$BUFFER" == *'!r:'* ]]; then
+        local expanded
+        expanded=$(berri-recall expand --command "$BUFFER" --cwd "$PWD" 2>&1)
+
+        if [[ $? -ne 0 ]]; then
+            # Don't let zsh's own ! expansion run something else instead
+            zle -M "${expanded:-berri-recall: nothing to expand}"
+            return 0
+        fi
+        BUFFER="$expanded"
+    fi
+
+    if [[ -n "$BERRI_RECALL_CONFIRM" && -n "$BUFFER" ]]; then
+        local warning
+        warning=$(berri-recall check --command "$BUFFER" --cwd "$PWD" 2>&1)
+
+        if [[ $? -eq 2 ]]; then
+            zle -I
+            print -r -- "$warning"
+            if ! read -q "?Run it anyway? [y/N] "; then
+                print
+                zle reset-prompt
+                return 0
+            fi
+            print
+        fi
+    fi
+
+    zle .accept-line
+}
+
 # Install everything (zsh has native hook support which is nice)
 if [[ -z "$__berri_installed" ]]; then
     typeset -g __berri_installed=1
@@ -41,4 +149,15 @@ if [[ -z "$__berri_installed" ]]; then
     if [[ -z "${precmd_functions[(r)__berri_precmd]}" ]]; then
         precmd_functions+=(__berri_precmd)
     fi
+    if [[ -z "${precmd_functions[(r)__berri_hello]}" ]]; then
+        precmd_functions+=(__berri_hello)
+    fi
+
+    # chpwd runs on every directory change
+    if [[ -z "${chpwd_functions[(r)__berri_chpwd]}" ]]; then
+        chpwd_functions+=(__berri_chpwd)
+    fi
+
+    # Enter goes through us first (expansion is free unless you type !!r)
+    zle -N accept-line __berri_accept_line
 fi
@@ -0,0 +1,9 @@
+// Hands the target triple and profile to `berri-recall --version --build-info`
+
+fn main() {
+    for var in ["TARGET", "PROFILE"] {
+        let value = std::env::var(var).unwrap_or_else(|_| "unknown".to_string());
+        println!("cargo:rustc-env=BERRI_RECALL_{}={}", var, value);
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
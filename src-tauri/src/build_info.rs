@@ -0,0 +1,77 @@
+//! What this binary was built as
+//!
+//! `--version --build-info` prints it, so a bug report says which target and
+//! features were in play (a static musl build behaves differently from the
+//! desktop one: no libgit2 from the system, no clipboard daemon).
+
+/// Target triple the binary was compiled for
+pub const TARGET: &str = env!("BERRI_RECALL_TARGET");
+
+/// `debug` or `release`
+pub const PROFILE: &str = env!("BERRI_RECALL_PROFILE");
+
+/// Cargo features compiled in
+pub fn features() -> Vec<&'static str> {
+    [
+        ("intelligence", cfg!(feature = "intelligence")),
+        ("git", cfg!(feature = "git")),
+        ("fuzzy", cfg!(feature = "fuzzy")),
+        ("clipboard", cfg!(feature = "clipboard")),
+        ("notifications", cfg!(feature = "notifications")),
+        ("static", cfg!(feature = "static")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+/// Whether the C runtime is linked in (musl) or loaded from the system
+pub fn is_static() -> bool {
+    cfg!(target_feature = "crt-static")
+}
+
+/// The `--build-info` report
+pub fn render() -> String {
+    let features = features();
+    let libgit2 = if !cfg!(feature = "git") {
+        "not included"
+    } else if cfg!(feature = "static") {
+        "vendored"
+    } else {
+        "system, or vendored when not found"
+    };
+
+    format!(
+        "berri-recall v{}\n\
+         target:   {}\n\
+         profile:  {}\n\
+         linking:  {}\n\
+         features: {}\n\
+         sqlite:   bundled\n\
+         libgit2:  {}\n",
+        env!("CARGO_PKG_VERSION"),
+        TARGET,
+        PROFILE,
+        if is_static() { "static" } else { "dynamic" },
+        if features.is_empty() {
+            "none".to_string()
+        } else {
+            features.join(", ")
+        },
+        libgit2
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let info = render();
+        assert!(info.starts_with(&format!("berri-recall v{}\n", env!("CARGO_PKG_VERSION"))));
+        assert!(info.contains(&format!("target:   {}\n", TARGET)));
+        assert_ne!(TARGET, "unknown");
+        assert_eq!(features().contains(&"fuzzy"), cfg!(feature = "fuzzy"));
+    }
+}
@@ -0,0 +1,207 @@
+//! Cheatsheets from your own history
+//!
+//! `tldr <tool>` lays out the ways you actually run a tool, most used first,
+//! in the shape of a tldr page: your note for each line if you wrote one,
+//! otherwise how often you've run it. If a tldr client has the real page
+//! cached locally, its examples follow yours.
+
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Where notes on cheatsheet lines are kept, followed by the line
+pub const NOTE_PREFIX: &str = "tldr.note:";
+
+/// One way you run the tool
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// With quoted arguments folded to `{{text}}`
+    pub command: String,
+    pub uses: i64,
+    pub note: Option<String>,
+}
+
+/// An example from a tldr page
+#[derive(Debug, Clone, PartialEq)]
+pub struct Example {
+    pub description: String,
+    pub command: String,
+}
+
+/// The line a command is filed under: quoted arguments (commit messages,
+/// grep patterns) vary run to run, so they become `{{text}}`
+pub fn fold(command: &str) -> String {
+    static QUOTED: OnceLock<Regex> = OnceLock::new();
+    let quoted = QUOTED.get_or_init(|| Regex::new(r#""[^"]*"|'[^']*'"#).expect("valid regex"));
+    quoted.replace_all(command.trim(), "{{text}}").into_owned()
+}
+
+/// Merge `(command, uses)` pairs into entries, most used first
+///
+/// Commands that fold to the same line (the same one in two projects, or
+/// with a different message) count together.
+pub fn collect(commands: &[(String, i64)], limit: usize) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for (command, uses) in commands {
+        let line = fold(command);
+        match entries.iter_mut().find(|e| e.command == line) {
+            Some(entry) => entry.uses += uses,
+            None => entries.push(Entry {
+                command: line,
+                uses: *uses,
+                note: None,
+            }),
+        }
+    }
+    // Stable, so ties keep the order they came in
+    entries.sort_by_key(|e| std::cmp::Reverse(e.uses));
+    entries.truncate(limit);
+    entries
+}
+
+/// The tool's page from a local tldr cache, if any client has one
+///
+/// Checks `TLDR_CACHE_DIR`, then where tealdeer, the Python and Node
+/// clients keep theirs.
+pub fn find_page(tool: &str) -> Option<PathBuf> {
+    let mut roots: Vec<PathBuf> = Vec::new();
+    if let Some(dir) = std::env::var_os("TLDR_CACHE_DIR") {
+        roots.push(PathBuf::from(dir));
+    }
+    if let Some(cache) = dirs::cache_dir() {
+        roots.push(cache.join("tealdeer").join("tldr-pages"));
+        roots.push(cache.join("tldr"));
+    }
+    if let Some(home) = dirs::home_dir() {
+        roots.push(home.join(".tldr").join("cache"));
+    }
+
+    let platform = match std::env::consts::OS {
+        "macos" => "osx",
+        "windows" => "windows",
+        _ => "linux",
+    };
+    roots.iter().find_map(|root| page_in(root, tool, platform))
+}
+
+fn page_in(root: &Path, tool: &str, platform: &str) -> Option<PathBuf> {
+    let file = format!("{}.md", tool);
+    for pages in ["pages.en", "pages"] {
+        for section in [platform, "common"] {
+            let path = root.join(pages).join(section).join(&file);
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+/// The examples on a tldr page: a `- description:` line, then the command
+/// in backticks
+pub fn parse_page(text: &str) -> Vec<Example> {
+    let mut examples = Vec::new();
+    let mut description: Option<String> = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("- ") {
+            description = Some(rest.trim_end_matches(':').to_string());
+        } else if let Some(command) = line
+            .strip_prefix('`')
+            .and_then(|rest| rest.strip_suffix('`'))
+        {
+            if let Some(description) = description.take() {
+                examples.push(Example {
+                    description,
+                    command: command.to_string(),
+                });
+            }
+        }
+    }
+    examples
+}
+
+/// The cheatsheet, yours first then the page's examples you don't already
+/// have
+pub fn render(tool: &str, entries: &[Entry], examples: &[Example]) -> String {
+    let mut out = format!("# {}\n\n", tool);
+    if !entries.is_empty() {
+        let total: i64 = entries.iter().map(|e| e.uses).sum();
+        out.push_str(&format!(
+            "> How you use {}: your {} most-run, {} runs between them.\n",
+            tool,
+            entries.len(),
+            total
+        ));
+    }
+    for entry in entries {
+        let description = match &entry.note {
+            Some(note) => note.clone(),
+            None if entry.uses == 1 => "Run once".to_string(),
+            None => format!("Run {} times", entry.uses),
+        };
+        out.push_str(&format!("\n- {}:\n\n`{}`\n", description, entry.command));
+    }
+
+    let extra: Vec<&Example> = examples
+        .iter()
+        .filter(|ex| !entries.iter().any(|e| e.command == ex.command))
+        .collect();
+    if !extra.is_empty() {
+        out.push_str("\n> From the tldr pages:\n");
+        for example in extra {
+            out.push_str(&format!(
+                "\n- {}:\n\n`{}`\n",
+                example.description, example.command
+            ));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cheatsheet() {
+        let commands = vec![
+            ("git status".to_string(), 3),
+            ("git commit -m \"fix login\"".to_string(), 2),
+            ("git commit -m 'typo'".to_string(), 2),
+            ("git status".to_string(), 1),
+            ("git push".to_string(), 1),
+        ];
+        let mut entries = collect(&commands, 2);
+        assert_eq!(
+            entries,
+            vec![
+                Entry {
+                    command: "git status".to_string(),
+                    uses: 4,
+                    note: None,
+                },
+                Entry {
+                    command: "git commit -m {{text}}".to_string(),
+                    uses: 4,
+                    note: None,
+                },
+            ]
+        );
+        entries[1].note = Some("Commit with a message".to_string());
+
+        let page = "# git\n\n> Distributed version control.\n\n\
+                    - Check the status:\n\n`git status`\n\n\
+                    - Show the log:\n\n`git log`\n";
+        let examples = parse_page(page);
+        assert_eq!(examples.len(), 2);
+        assert_eq!(examples[1].description, "Show the log");
+
+        let sheet = render("git", &entries, &examples);
+        assert!(sheet.starts_with("# git\n"));
+        assert!(sheet.contains("- Run 4 times:\n\n`git status`"));
+        assert!(sheet.contains("- Commit with a message:\n\n`git commit -m {{text}}`"));
+        // The page's `git status` is already there
+        assert!(!sheet.contains("Check the status"));
+        assert!(sheet.contains("> From the tldr pages:\n\n- Show the log:\n\n`git log`"));
+    }
+}
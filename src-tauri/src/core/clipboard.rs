@@ -0,0 +1,100 @@
+//! Clipboard support
+//!
+//! Tries arboard first, then whatever clipboard tool the system has. On Linux
+//! the external tools go first: they keep serving the clipboard after we exit,
+//! while arboard's X11/Wayland selection disappears with the process.
+
+use crate::error::{RecallError, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// External tools, tried in order (program, args)
+#[cfg(target_os = "linux")]
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    // WSL
+    ("clip.exe", &[]),
+];
+
+#[cfg(target_os = "macos")]
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[("pbcopy", &[])];
+
+#[cfg(windows)]
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[
+    ("clip", &[]),
+    ("powershell", &["-NoProfile", "-Command", "$input | Set-Clipboard"]),
+];
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[];
+
+/// Put text on the system clipboard
+///
+/// # Returns
+/// * `Ok(&str)` - Name of whatever did the copying
+/// * `Err(RecallError)` - Nothing on this system could
+pub fn copy(text: &str) -> Result<&'static str> {
+    if !cfg!(target_os = "linux") && copy_with_arboard(text) {
+        return Ok("arboard");
+    }
+
+    for (program, args) in CLIPBOARD_TOOLS {
+        if copy_with_tool(program, args, text) {
+            return Ok(program);
+        }
+    }
+
+    if cfg!(target_os = "linux") && copy_with_arboard(text) {
+        return Ok("arboard");
+    }
+
+    Err(RecallError::Generic(
+        "No clipboard available (install wl-clipboard, xclip or xsel)".to_string(),
+    ))
+}
+
+#[cfg(feature = "clipboard")]
+fn copy_with_arboard(text: &str) -> bool {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .is_ok()
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_with_arboard(_text: &str) -> bool {
+    false
+}
+
+fn copy_with_tool(program: &str, args: &[&str], text: &str) -> bool {
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    let mut child = match child {
+        Ok(c) => c,
+        Err(_) => return false, // not installed
+    };
+
+    let written = child
+        .stdin
+        .take()
+        .map(|mut stdin| stdin.write_all(text.as_bytes()).is_ok())
+        .unwrap_or(false);
+
+    matches!(child.wait(), Ok(status) if status.success()) && written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_tool_is_skipped() {
+        assert!(!copy_with_tool("berri-recall-no-such-clipboard", &[], "ls"));
+    }
+}
@@ -0,0 +1,315 @@
+//! Made-up history to try things on
+//!
+//! `demo` fills a throwaway database with weeks of believable work: a few
+//! projects, shell sessions, builds that fail and get fixed, test suites
+//! that are sometimes slow, typos. It's all drawn from a seed, so
+//! `demo --seed 42` gives the same history every time, which also makes it
+//! a way to reproduce a slow query with a few hundred thousand runs.
+
+use crate::error::Result;
+use chrono::{DateTime, Datelike, Duration, Utc};
+use std::path::{Path, PathBuf};
+
+/// One step of a workflow: the command, how long it usually takes (ms) and
+/// how often it fails
+type Step = (&'static str, u32, f64);
+
+/// A made-up project: its directory name, the file that marks it as a
+/// project, and the workflows run in it
+struct Project {
+    name: &'static str,
+    marker: &'static str,
+    workflows: &'static [&'static [Step]],
+}
+
+const PROJECTS: &[Project] = &[
+    Project {
+        name: "storefront",
+        marker: "package.json",
+        workflows: &[
+            &[
+                ("git pull", 1_200, 0.02),
+                ("npm install", 18_000, 0.05),
+                ("npm run dev", 4_000, 0.0),
+            ],
+            &[
+                ("npm test", 9_000, 0.2),
+                ("npm run lint", 3_000, 0.1),
+                ("git add .", 80, 0.0),
+                ("git commit -m {message}", 150, 0.0),
+                ("git push", 2_000, 0.05),
+            ],
+            &[
+                ("docker compose up -d", 6_000, 0.05),
+                ("docker compose logs -f api", 30_000, 0.0),
+            ],
+        ],
+    },
+    Project {
+        name: "ledger",
+        marker: "Cargo.toml",
+        workflows: &[
+            &[
+                ("cargo build", 25_000, 0.25),
+                ("cargo test", 40_000, 0.15),
+                ("cargo clippy -- -D warnings", 12_000, 0.1),
+            ],
+            &[
+                ("git status", 60, 0.0),
+                ("git diff", 90, 0.0),
+                ("git add -p", 20_000, 0.0),
+                ("git commit -m {message}", 150, 0.0),
+            ],
+            &[("cargo run --release -- --port 8080", 60_000, 0.1)],
+        ],
+    },
+    Project {
+        name: "notebooks",
+        marker: "requirements.txt",
+        workflows: &[
+            &[
+                ("source .venv/bin/activate", 40, 0.0),
+                ("pip install -r requirements.txt", 15_000, 0.1),
+            ],
+            &[
+                ("python -m pytest -x", 20_000, 0.3),
+                ("python scripts/train.py", 300_000, 0.1),
+            ],
+            &[("jupyter lab", 600_000, 0.0)],
+        ],
+    },
+    Project {
+        name: "infra",
+        marker: ".project",
+        workflows: &[
+            &[
+                ("terraform fmt", 300, 0.0),
+                ("terraform plan -out plan.tfplan", 45_000, 0.15),
+                ("terraform apply plan.tfplan", 120_000, 0.1),
+            ],
+            &[
+                ("kubectl get pods -n staging", 800, 0.05),
+                ("kubectl logs deploy/api -n staging --tail 100", 1_000, 0.05),
+                ("kubectl rollout restart deploy/api -n staging", 1_500, 0.05),
+            ],
+        ],
+    },
+];
+
+/// Typed anywhere, between the workflows
+const ODDS_AND_ENDS: &[Step] = &[
+    ("ls -la", 20, 0.0),
+    ("cd ..", 5, 0.0),
+    ("grep -rn TODO .", 400, 0.1),
+    ("docker ps", 300, 0.0),
+    ("htop", 90_000, 0.0),
+    ("code .", 500, 0.0),
+    ("git log --oneline -10", 80, 0.0),
+    ("gti status", 5, 1.0),
+];
+
+const MESSAGES: &[&str] = &[
+    "fix flaky test",
+    "wip",
+    "bump deps",
+    "handle empty input",
+    "address review comments",
+    "add retry to the client",
+];
+
+const BRANCHES: &[&str] = &["feature/search", "fix/timeouts", "chore/deps"];
+
+/// One made-up run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub command: String,
+    pub project_path: PathBuf,
+    pub session_id: String,
+    pub branch: String,
+    /// When it finished
+    pub executed_at: DateTime<Utc>,
+    pub duration_ms: i32,
+    pub exit_code: i32,
+}
+
+/// splitmix64: small, fast and the same everywhere, which is all this needs
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// In `0..n`
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n.max(1)
+    }
+
+    /// In `0.0..1.0`
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn chance(&mut self, p: f64) -> bool {
+        self.unit() < p
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+}
+
+/// Create the projects' directories under `root`, each with the file that
+/// makes it a project, so you can `cd` into one and see its history
+pub fn create_projects(root: &Path) -> Result<()> {
+    for project in PROJECTS {
+        let dir = root.join(project.name);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(project.marker), "")?;
+    }
+    Ok(())
+}
+
+/// `count` runs from `seed`, oldest first, the last one finishing in the
+/// day before `now`; the projects are directories under `root`
+pub fn generate(seed: u64, count: usize, root: &Path, now: DateTime<Utc>) -> Vec<Run> {
+    let mut rng = Rng::new(seed);
+    let mut runs: Vec<Run> = Vec::with_capacity(count);
+    let mut day = DateTime::<Utc>::UNIX_EPOCH;
+    let mut sessions = 0;
+
+    while runs.len() < count {
+        // Fewer sessions at the weekend
+        let weekend = day.weekday().number_from_monday() >= 6;
+        let today = if weekend {
+            rng.below(2)
+        } else {
+            2 + rng.below(3)
+        };
+        let mut at = day + Duration::hours(8) + Duration::minutes(rng.below(60) as i64);
+
+        for _ in 0..today {
+            sessions += 1;
+            let session_id = format!("demo-{}-{}", seed, sessions);
+            // Most of the work happens in the first couple of projects
+            let projects = PROJECTS.len() as u64;
+            let project = &PROJECTS[rng.below(projects).min(rng.below(projects)) as usize];
+            let project_path = root.join(project.name);
+            let branch = if rng.chance(0.6) {
+                "main"
+            } else {
+                *rng.pick(BRANCHES)
+            };
+
+            for _ in 0..2 + rng.below(5) {
+                let workflow: &[Step] = if rng.chance(0.2) {
+                    std::slice::from_ref(rng.pick(ODDS_AND_ENDS))
+                } else {
+                    rng.pick::<&[Step]>(project.workflows)
+                };
+                for step in workflow {
+                    let (step, mut failure_rate) = (*step, step.2);
+                    // A failure gets fixed and run again, most of the time
+                    loop {
+                        at += Duration::seconds(2 + rng.below(40) as i64);
+                        let failed = rng.chance(failure_rate);
+                        let duration_ms = duration(&mut rng, step.1, failed);
+                        at += Duration::milliseconds(duration_ms as i64);
+                        runs.push(Run {
+                            command: step.0.replace("{message}", &quoted(&mut rng)),
+                            project_path: project_path.clone(),
+                            session_id: session_id.clone(),
+                            branch: branch.to_string(),
+                            executed_at: at,
+                            duration_ms,
+                            exit_code: if failed { exit_code(step.0) } else { 0 },
+                        });
+                        if !failed || failure_rate >= 1.0 || !rng.chance(0.7) {
+                            break;
+                        }
+                        failure_rate /= 2.0;
+                        at += Duration::seconds(30 + rng.below(300) as i64);
+                    }
+                }
+                at += Duration::minutes(1 + rng.below(15) as i64);
+            }
+            at += Duration::minutes(30 + rng.below(120) as i64);
+        }
+        day += Duration::days(1);
+    }
+
+    // Moved by whole days, so weekends and working hours stay where they were
+    runs.truncate(count);
+    if let Some(last) = runs.last().map(|r| r.executed_at) {
+        let shift = Duration::days((now - last).num_days());
+        for run in &mut runs {
+            run.executed_at += shift;
+        }
+    }
+    runs
+}
+
+/// Around `typical`, now and then three times as long; a failure gives up
+/// part of the way through
+fn duration(rng: &mut Rng, typical: u32, failed: bool) -> i32 {
+    let mut factor = 0.6 + rng.unit() * 0.8;
+    if rng.chance(0.05) {
+        factor *= 3.0;
+    }
+    if failed {
+        factor *= 0.5;
+    }
+    (typical as f64 * factor).max(1.0) as i32
+}
+
+fn exit_code(command: &str) -> i32 {
+    match command.split_whitespace().next() {
+        Some("cargo") => 101,
+        Some("gti") => 127,
+        _ => 1,
+    }
+}
+
+fn quoted(rng: &mut Rng) -> String {
+    format!("\"{}\"", rng.pick(MESSAGES))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let root = Path::new("/tmp/demo");
+        let now = Utc::now();
+        let runs = generate(42, 500, root, now);
+        assert_eq!(runs.len(), 500);
+        assert_eq!(runs, generate(42, 500, root, now));
+        assert_ne!(runs, generate(7, 500, root, now));
+
+        assert!(runs
+            .windows(2)
+            .all(|w| w[0].executed_at <= w[1].executed_at));
+        assert!(runs[499].executed_at <= now && runs[499].executed_at > now - Duration::days(1));
+        assert!(runs.iter().all(|r| r.project_path.starts_with(root)));
+
+        let failed = runs.iter().filter(|r| r.exit_code != 0).count();
+        assert!(failed > 10 && failed < 250, "{} failures", failed);
+        let projects: std::collections::HashSet<_> = runs.iter().map(|r| &r.project_path).collect();
+        assert!(projects.len() > 1);
+        let sessions: std::collections::HashSet<_> = runs.iter().map(|r| &r.session_id).collect();
+        assert!(sessions.len() > 5);
+        assert!(runs
+            .iter()
+            .any(|r| r.command.starts_with("git commit -m \"")));
+    }
+}
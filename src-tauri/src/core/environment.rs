@@ -0,0 +1,321 @@
+//! Environment variables kept with each run
+//!
+//! Only the variables named in the `capture_env` setting are read, and only
+//! their values are stored (as a small JSON object on the execution row), so
+//! `search --env AWS_PROFILE=prod` can find what ran against prod. Names
+//! that look like they hold secrets are refused outright.
+//!
+//! The toolchain active at the time goes in the same object under lowercase
+//! keys (`venv`, `conda`, `node`, `rust`), so `search --env venv=.venv` works
+//! too and suggestions can notice when the usual virtualenv isn't active.
+
+use crate::build_info;
+use crate::error::{RecallError, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Parts of a name that mean the value is a credential
+const SECRET_WORDS: &[&str] = &["TOKEN", "SECRET", "PASSWORD", "PASSWD", "KEY", "CREDENTIAL"];
+
+/// Check a variable name before it goes on the allowlist
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(RecallError::Config(format!(
+            "'{}' isn't an environment variable name",
+            name
+        )));
+    }
+
+    let upper = name.to_uppercase();
+    if let Some(word) = SECRET_WORDS.iter().find(|w| upper.contains(*w)) {
+        return Err(RecallError::Config(format!(
+            "{} looks like it holds a secret ({}), it won't be recorded",
+            name, word
+        )));
+    }
+    Ok(())
+}
+
+/// The allowlisted variables that are set and the toolchain active in `cwd`,
+/// as a JSON object
+///
+/// `None` when there's nothing to keep, so most runs store nothing.
+pub fn capture(names: &[String], cwd: &Path, project: &Path) -> Option<String> {
+    let lookup = |name: &str| std::env::var(name).ok();
+    let mut values = allowlisted(names, lookup);
+    values.extend(toolchain_from(cwd, project, lookup));
+
+    if values.is_empty() {
+        None
+    } else {
+        serde_json::to_string(&values).ok()
+    }
+}
+
+fn allowlisted(
+    names: &[String],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> BTreeMap<String, String> {
+    names
+        .iter()
+        .filter(|name| validate_name(name).is_ok())
+        .filter_map(|name| Some((name.clone(), lookup(name)?)))
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+}
+
+/// Python env, node version and rust toolchain override active in `cwd`
+///
+/// A virtualenv inside the project is kept relative to it (`.venv`), one
+/// elsewhere by its directory name.
+pub fn toolchain(cwd: &Path, project: &Path) -> BTreeMap<String, String> {
+    toolchain_from(cwd, project, |name| std::env::var(name).ok())
+}
+
+fn toolchain_from(
+    cwd: &Path,
+    project: &Path,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> BTreeMap<String, String> {
+    let mut found = BTreeMap::new();
+
+    if let Some(venv) = lookup("VIRTUAL_ENV").filter(|v| !v.is_empty()) {
+        let path = PathBuf::from(&venv);
+        let name = match path.strip_prefix(project) {
+            Ok(relative) if !relative.as_os_str().is_empty() => {
+                Some(relative.to_string_lossy().into_owned())
+            }
+            _ => path.file_name().map(|n| n.to_string_lossy().into_owned()),
+        };
+        if let Some(name) = name {
+            found.insert("venv".to_string(), name);
+        }
+    }
+
+    if let Some(conda) = lookup("CONDA_DEFAULT_ENV").filter(|v| !v.is_empty()) {
+        found.insert("conda".to_string(), conda);
+    }
+
+    // nvm puts ~/.nvm/versions/node/v20.11.0/bin on the PATH
+    if let Some(version) = lookup("NVM_BIN").and_then(|bin| {
+        let version = Path::new(&bin).parent()?.file_name()?.to_str()?.to_string();
+        version.starts_with('v').then_some(version)
+    }) {
+        found.insert("node".to_string(), version);
+    }
+
+    let rustup_home = lookup("RUSTUP_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".rustup")));
+    if let Some(rust) = lookup("RUSTUP_TOOLCHAIN")
+        .filter(|v| !v.is_empty())
+        .or_else(|| rust_override(cwd, rustup_home.as_deref()))
+    {
+        found.insert("rust".to_string(), short_toolchain(&rust));
+    }
+
+    found
+}
+
+/// The toolchain rustup would pick for `cwd` when it isn't the default
+///
+/// Same order rustup uses: the closest directory wins, and in one directory
+/// a `rustup override` beats a toolchain file.
+fn rust_override(cwd: &Path, rustup_home: Option<&Path>) -> Option<String> {
+    let overrides = rustup_home
+        .and_then(|home| std::fs::read_to_string(home.join("settings.toml")).ok())
+        .and_then(|text| text.parse::<toml_edit::DocumentMut>().ok());
+    let overrides = overrides.as_ref().and_then(|doc| doc.get("overrides"));
+
+    for dir in cwd.ancestors() {
+        if let Some(toolchain) = overrides
+            .and_then(|table| table.get(dir.to_str()?))
+            .and_then(|item| item.as_str())
+        {
+            return Some(toolchain.to_string());
+        }
+        if let Some(toolchain) = toolchain_file(dir) {
+            return Some(toolchain);
+        }
+    }
+    None
+}
+
+/// `rust-toolchain.toml`, or the older `rust-toolchain` (TOML or a bare name)
+fn toolchain_file(dir: &Path) -> Option<String> {
+    for name in ["rust-toolchain.toml", "rust-toolchain"] {
+        let Ok(text) = std::fs::read_to_string(dir.join(name)) else {
+            continue;
+        };
+        if let Ok(doc) = text.parse::<toml_edit::DocumentMut>() {
+            if let Some(channel) = doc
+                .get("toolchain")
+                .and_then(|t| t.get("channel"))
+                .and_then(|c| c.as_str())
+            {
+                return Some(channel.to_string());
+            }
+        }
+        if name == "rust-toolchain" {
+            let line = text.lines().next().unwrap_or("").trim();
+            if !line.is_empty() && !line.starts_with('[') {
+                return Some(line.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// `nightly-x86_64-unknown-linux-gnu` is just `nightly` on this machine
+fn short_toolchain(name: &str) -> String {
+    name.strip_suffix(&format!("-{}", build_info::TARGET))
+        .unwrap_or(name)
+        .to_string()
+}
+
+/// `--env NAME=value`, or `--env NAME` for any value
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvFilter {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl FromStr for EnvFilter {
+    type Err = RecallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (s, None),
+        };
+        if name.is_empty() {
+            return Err(RecallError::Config(
+                "--env needs NAME=value or NAME".to_string(),
+            ));
+        }
+        validate_name(name)?;
+
+        Ok(Self {
+            name: name.to_string(),
+            value,
+        })
+    }
+}
+
+impl EnvFilter {
+    /// Whether a run's captured variables (a JSON object) pass
+    pub fn matches(&self, env: Option<&str>) -> bool {
+        let Some(env) = env.and_then(|e| serde_json::from_str::<Value>(e).ok()) else {
+            return false;
+        };
+        match (env.get(&self.name), &self.value) {
+            (Some(found), Some(value)) => found.as_str() == Some(value.as_str()),
+            (Some(found), None) => !found.is_null(),
+            (None, _) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("AWS_PROFILE").is_ok());
+        assert!(validate_name("KUBECONFIG").is_ok());
+        assert!(validate_name("2FAST").is_err());
+        assert!(validate_name("NODE-ENV").is_err());
+        assert!(validate_name("GITHUB_TOKEN").is_err());
+        assert!(validate_name("aws_secret_access_key").is_err());
+    }
+
+    #[test]
+    fn test_capture() {
+        let names: Vec<String> = ["NODE_ENV", "AWS_PROFILE", "UNSET", "API_KEY"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let lookup = |name: &str| match name {
+            "AWS_PROFILE" => Some("prod".to_string()),
+            "NODE_ENV" => Some("test".to_string()),
+            "API_KEY" => Some("hunter2".to_string()),
+            _ => None,
+        };
+
+        let captured = allowlisted(&names, lookup);
+        assert_eq!(
+            serde_json::to_string(&captured).unwrap(),
+            r#"{"AWS_PROFILE":"prod","NODE_ENV":"test"}"#
+        );
+        assert!(allowlisted(&names[2..3], lookup).is_empty());
+
+        let filter: EnvFilter = "AWS_PROFILE=prod".parse().unwrap();
+        assert_eq!(filter.value.as_deref(), Some("prod"));
+        assert_eq!("KUBECONFIG".parse::<EnvFilter>().unwrap().value, None);
+        assert!("=prod".parse::<EnvFilter>().is_err());
+
+        let env = Some(r#"{"AWS_PROFILE":"prod","NODE_ENV":"test"}"#);
+        assert!(filter.matches(env));
+        assert!(!filter.matches(Some(r#"{"AWS_PROFILE":"dev"}"#)));
+        assert!(!filter.matches(None));
+        assert!("NODE_ENV".parse::<EnvFilter>().unwrap().matches(env));
+        assert!(!"KUBECONFIG".parse::<EnvFilter>().unwrap().matches(env));
+    }
+
+    #[test]
+    fn test_toolchain() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("api");
+        let crate_dir = project.join("worker");
+        std::fs::create_dir_all(&crate_dir).unwrap();
+        std::fs::write(
+            crate_dir.join("rust-toolchain.toml"),
+            "[toolchain]\nchannel = \"1.75\"\n",
+        )
+        .unwrap();
+
+        let venv = project.join(".venv").to_string_lossy().into_owned();
+        let home = dir.path().join("rustup").to_string_lossy().into_owned();
+        let lookup = |name: &str| match name {
+            "VIRTUAL_ENV" => Some(venv.clone()),
+            "NVM_BIN" => Some("/home/me/.nvm/versions/node/v20.11.0/bin".to_string()),
+            "RUSTUP_HOME" => Some(home.clone()),
+            _ => None,
+        };
+
+        let found = toolchain_from(&crate_dir, &project, lookup);
+        assert_eq!(found["venv"], ".venv");
+        assert_eq!(found["node"], "v20.11.0");
+        assert_eq!(found["rust"], "1.75");
+        assert!(!found.contains_key("conda"));
+
+        // A virtualenv elsewhere goes by its name, and `rustup override` wins
+        std::fs::create_dir_all(dir.path().join("rustup")).unwrap();
+        std::fs::write(
+            dir.path().join("rustup/settings.toml"),
+            format!(
+                "[overrides]\n{:?} = \"nightly-{}\"\n",
+                crate_dir.to_str().unwrap(),
+                build_info::TARGET
+            ),
+        )
+        .unwrap();
+        let lookup = |name: &str| match name {
+            "VIRTUAL_ENV" => Some("/home/me/.cache/pypoetry/virtualenvs/api-x1-py3.12".to_string()),
+            "RUSTUP_HOME" => Some(home.clone()),
+            _ => None,
+        };
+        let found = toolchain_from(&crate_dir, &project, lookup);
+        assert_eq!(found["venv"], "api-x1-py3.12");
+        assert_eq!(found["rust"], "nightly");
+        assert!(!toolchain_from(&project, &project, lookup).contains_key("rust"));
+    }
+}
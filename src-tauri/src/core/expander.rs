@@ -0,0 +1,255 @@
+//! Alias and history expansion for the pre-execution hook
+//!
+//! Turns `!!r deploy staging` into the stored `deploy` alias and `!r:3` /
+//! `!r:fail` into commands from this project's history before the shell runs
+//! them. Runs on every Enter, so anything that isn't ours bails out before
+//! touching the database.
+
+use crate::db::Database;
+use crate::error::{RecallError, Result};
+use regex::Regex;
+use std::sync::Arc;
+
+/// Token that marks a line for expansion
+pub const EXPAND_PREFIX: &str = "!!r";
+
+/// Start of a history token (`!r:3`, `!r:fail`)
+pub const RECALL_TOKEN: &str = "!r:";
+
+/// Full history token: a position from `recent` or the last failure
+const RECALL_TOKEN_PATTERN: &str = r"!r:(\d+|fail)\b";
+
+/// Placeholder for "all remaining arguments" in alias templates
+const REST_PLACEHOLDER: &str = "{@}";
+
+/// Expands berri-recall aliases and history tokens
+pub struct Expander {
+    db: Arc<Database>,
+    token: Regex,
+}
+
+impl Expander {
+    /// Create a new expander
+    pub fn new(db: Arc<Database>) -> Self {
+        Self {
+            db,
+            token: Regex::new(RECALL_TOKEN_PATTERN).expect("recall token pattern is valid"),
+        }
+    }
+
+    /// Cheap check for whether a line could need expanding
+    ///
+    /// Used to skip the database entirely for normal commands.
+    pub fn is_expandable(line: &str) -> bool {
+        Self::parse(line).is_some() || line.contains(RECALL_TOKEN)
+    }
+
+    /// Split a line into alias name and arguments
+    ///
+    /// Returns None for anything that doesn't start with the `!!r` token.
+    pub fn parse(line: &str) -> Option<(&str, Vec<&str>)> {
+        let mut parts = line.split_whitespace();
+        if parts.next()? != EXPAND_PREFIX {
+            return None;
+        }
+
+        let name = parts.next()?;
+        Some((name, parts.collect()))
+    }
+
+    /// Fill an alias template with arguments
+    ///
+    /// `{1}`, `{2}`... take positional arguments and `{@}` takes whatever is
+    /// left. Arguments no placeholder asked for are appended to the end.
+    pub fn apply_template(template: &str, args: &[&str]) -> String {
+        let mut result = template.to_string();
+        let mut used = vec![false; args.len()];
+
+        for (i, arg) in args.iter().enumerate() {
+            let placeholder = format!("{{{}}}", i + 1);
+            if result.contains(&placeholder) {
+                result = result.replace(&placeholder, arg);
+                used[i] = true;
+            }
+        }
+
+        let rest: Vec<&str> = args
+            .iter()
+            .zip(&used)
+            .filter(|(_, used)| !**used)
+            .map(|(arg, _)| *arg)
+            .collect();
+
+        if result.contains(REST_PLACEHOLDER) {
+            result.replace(REST_PLACEHOLDER, &rest.join(" "))
+        } else if rest.is_empty() {
+            result
+        } else {
+            format!("{} {}", result, rest.join(" "))
+        }
+    }
+
+    /// Expand a line typed at the prompt
+    ///
+    /// # Returns
+    /// * `Ok(Some(String))` - The command to run instead
+    /// * `Ok(None)` - Not an expansion line, leave it alone
+    /// * `Err(RecallError::CommandNotFound)` - Looked like one but no such alias
+    ///   or history entry
+    pub async fn expand(&self, line: &str, project_path: Option<&str>) -> Result<Option<String>> {
+        if let Some((name, args)) = Self::parse(line) {
+            // Project aliases shadow global ones
+            let aliases = self.db.get_aliases(project_path).await?;
+            let alias = aliases
+                .iter()
+                .filter(|a| a.alias == name)
+                .max_by_key(|a| a.project_path.is_some())
+                .ok_or_else(|| RecallError::CommandNotFound(name.to_string()))?;
+
+            return Ok(Some(Self::apply_template(&alias.command, &args)));
+        }
+
+        if !self.token.is_match(line) {
+            return Ok(None);
+        }
+
+        // Resolve each token in turn; tokens can sit anywhere (`sudo !r:fail`)
+        let mut expanded = String::with_capacity(line.len());
+        let mut last = 0;
+
+        for caps in self.token.captures_iter(line) {
+            let whole = caps.get(0).expect("group 0 always matches");
+            let replacement = self.resolve_token(&caps[1], project_path).await?;
+
+            expanded.push_str(&line[last..whole.start()]);
+            expanded.push_str(&replacement);
+            last = whole.end();
+        }
+        expanded.push_str(&line[last..]);
+
+        Ok(Some(expanded))
+    }
+
+    /// Look up what a single `!r:` token points at
+    async fn resolve_token(&self, token: &str, project_path: Option<&str>) -> Result<String> {
+        let not_found = || RecallError::CommandNotFound(format!("{}{}", RECALL_TOKEN, token));
+
+        if token == "fail" {
+            return self
+                .db
+                .get_last_failed_execution(project_path)
+                .await?
+                .map(|e| e.command)
+                .ok_or_else(not_found);
+        }
+
+        // Same numbering as `berri-recall recent`
+        let position: usize = token.parse().map_err(|_| not_found())?;
+        if position == 0 {
+            return Err(not_found());
+        }
+
+        self.db
+            .get_recent_commands(project_path, position as i64)
+            .await?
+            .into_iter()
+            .nth(position - 1)
+            .map(|c| c.command)
+            .ok_or_else(not_found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Expander::parse("!!r deploy"), Some(("deploy", vec![])));
+        assert_eq!(
+            Expander::parse("!!r deploy staging eu"),
+            Some(("deploy", vec!["staging", "eu"]))
+        );
+        assert_eq!(Expander::parse("!!r"), None);
+        assert_eq!(Expander::parse("git status"), None);
+        assert_eq!(Expander::parse("!!rdeploy"), None);
+    }
+
+    #[test]
+    fn test_apply_template() {
+        assert_eq!(
+            Expander::apply_template("kubectl apply -f k8s/{1}.yaml", &["staging"]),
+            "kubectl apply -f k8s/staging.yaml"
+        );
+        assert_eq!(
+            Expander::apply_template("cargo test", &["--", "--nocapture"]),
+            "cargo test -- --nocapture"
+        );
+        assert_eq!(
+            Expander::apply_template("ssh {1} -- {@}", &["box", "uptime", "-p"]),
+            "ssh box -- uptime -p"
+        );
+        assert_eq!(Expander::apply_template("make", &[]), "make");
+    }
+
+    #[tokio::test]
+    async fn test_expand() {
+        let db = Arc::new(Database::new_test().await.unwrap());
+        db.create_alias(
+            "deploy".to_string(),
+            "./deploy.sh {1}".to_string(),
+            Some("/test".to_string()),
+        )
+        .await
+        .unwrap();
+        let expander = Expander::new(db);
+
+        let expanded = expander.expand("!!r deploy prod", Some("/test")).await.unwrap();
+        assert_eq!(expanded, Some("./deploy.sh prod".to_string()));
+
+        assert_eq!(expander.expand("ls -la", Some("/test")).await.unwrap(), None);
+
+        match expander.expand("!!r nope", Some("/test")).await {
+            Err(RecallError::CommandNotFound(name)) => assert_eq!(name, "nope"),
+            other => panic!("Expected CommandNotFound, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_is_expandable() {
+        assert!(Expander::is_expandable("!!r deploy"));
+        assert!(Expander::is_expandable("sudo !r:fail"));
+        assert!(!Expander::is_expandable("git status"));
+    }
+
+    #[tokio::test]
+    async fn test_expand_history_tokens() {
+        use crate::db::CommandInput;
+
+        let db = Arc::new(Database::new_test().await.unwrap());
+        for (cmd, code) in [("make build", 2), ("npm test", 0), ("git status", 0)] {
+            db.record_command(CommandInput {
+                project_path: "/test".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(code),
+                context: None,
+            })
+            .await
+            .unwrap();
+        }
+        let expander = Expander::new(db);
+
+        let third = expander.expand("!r:3", Some("/test")).await.unwrap();
+        assert_eq!(third, Some("make build".to_string()));
+
+        let failed = expander.expand("sudo !r:fail", Some("/test")).await.unwrap();
+        assert_eq!(failed, Some("sudo make build".to_string()));
+
+        let both = expander.expand("!r:1 && !r:2", Some("/test")).await.unwrap();
+        assert_eq!(both, Some("git status && npm test".to_string()));
+
+        assert!(expander.expand("!r:9", Some("/test")).await.is_err());
+        assert!(expander.expand("!r:0", Some("/test")).await.is_err());
+    }
+}
@@ -0,0 +1,259 @@
+//! Commands the user never wants recorded
+//!
+//! Shell globs matched against the whole command, the same way bash's
+//! HISTIGNORE works: `man *` skips every man page, `*--help` every help
+//! screen. Rules live in the preferences table as `record.ignore:<glob>`.
+
+use crate::db::Database;
+use crate::error::{RecallError, Result};
+use regex::Regex;
+
+const IGNORE_PREFIX: &str = "record.ignore:";
+
+/// Turn a shell glob into an anchored regex
+///
+/// `*` is any run of characters, `?` a single one, `[...]` a character class
+/// (`[!...]` negated) and `\` escapes the next character.
+pub fn glob_to_regex(glob: &str) -> Option<Regex> {
+    translate(glob, false)
+}
+
+/// Same as `glob_to_regex`, plus zsh's `(a|b)` alternatives
+///
+/// That's what `HISTORY_IGNORE` usually holds: `(ls|cd|pwd|exit)`.
+pub fn zsh_pattern_to_regex(pattern: &str) -> Option<Regex> {
+    translate(pattern, true)
+}
+
+fn translate(glob: &str, alternatives: bool) -> Option<Regex> {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            '\\' => {
+                let escaped = chars.next()?;
+                pattern.push_str(&regex::escape(&escaped.to_string()));
+            }
+            '[' => {
+                let mut class = String::new();
+                if matches!(chars.peek(), Some('!') | Some('^')) {
+                    chars.next();
+                    class.push('^');
+                }
+                loop {
+                    match chars.next()? {
+                        ']' => break,
+                        c @ ('\\' | '[' | '^') => {
+                            class.push('\\');
+                            class.push(c);
+                        }
+                        c => class.push(c),
+                    }
+                }
+                pattern.push('[');
+                pattern.push_str(&class);
+                pattern.push(']');
+            }
+            '(' if alternatives => pattern.push_str("(?:"),
+            '|' | ')' if alternatives => pattern.push(c),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    pattern.push('$');
+    // Commands can span lines
+    Regex::new(&format!("(?s){}", pattern)).ok()
+}
+
+/// Split bash's `HISTIGNORE` into its globs
+///
+/// Globs are separated by `:` (`\:` is a literal one). `&` stands for "same
+/// as the previous line", which is ignoredups again: repeats are what usage
+/// counts are made of, so it's dropped.
+pub fn split_histignore(value: &str) -> Vec<String> {
+    let mut globs = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(':') => current.push(':'),
+                Some(next) => {
+                    current.push('\\');
+                    current.push(next);
+                }
+                None => current.push('\\'),
+            },
+            ':' => globs.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    globs.push(current);
+
+    globs
+        .into_iter()
+        .filter(|glob| !glob.is_empty() && glob != "&")
+        .collect()
+}
+
+/// Loaded ignore globs
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<(String, Regex)>,
+}
+
+impl IgnoreRules {
+    /// Rules from a list of globs, skipping any that don't parse
+    pub fn from_globs<I, S>(globs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let rules = globs
+            .into_iter()
+            .map(Into::into)
+            .filter_map(|glob| glob_to_regex(&glob).map(|regex| (glob, regex)))
+            .collect();
+
+        Self { rules }
+    }
+
+    /// Rules from bash's `HISTIGNORE`
+    pub fn from_histignore(value: &str) -> Self {
+        Self::from_globs(split_histignore(value))
+    }
+
+    /// The rule from zsh's `HISTORY_IGNORE`, a single pattern
+    pub fn from_history_ignore(value: &str) -> Self {
+        let rules = zsh_pattern_to_regex(value)
+            .filter(|_| !value.is_empty())
+            .map(|regex| vec![(value.to_string(), regex)])
+            .unwrap_or_default();
+
+        Self { rules }
+    }
+
+    /// Add `other`'s rules after these
+    pub fn extend(&mut self, other: IgnoreRules) {
+        self.rules.extend(other.rules);
+    }
+
+    /// Load every rule from the preferences table
+    pub async fn load(db: &Database) -> Result<Self> {
+        let globs = db
+            .get_preferences_with_prefix(IGNORE_PREFIX)
+            .await?
+            .into_iter()
+            .map(|pref| pref.key[IGNORE_PREFIX.len()..].to_string());
+
+        Ok(Self::from_globs(globs))
+    }
+
+    /// The globs, in the order they were loaded
+    pub fn globs(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|(glob, _)| glob.as_str())
+    }
+
+    /// The first glob `command` matches
+    pub fn matching(&self, command: &str) -> Option<&str> {
+        let command = command.trim();
+        self.rules
+            .iter()
+            .find(|(_, regex)| regex.is_match(command))
+            .map(|(glob, _)| glob.as_str())
+    }
+
+    /// Stop recording commands matching `glob`
+    pub async fn add(db: &Database, glob: &str) -> Result<()> {
+        if glob.trim().is_empty() || glob_to_regex(glob).is_none() {
+            return Err(RecallError::Config(format!("Not a valid glob: '{}'", glob)));
+        }
+
+        db.set_preference(format!("{}{}", IGNORE_PREFIX, glob), "true".to_string())
+            .await
+    }
+
+    /// Drop a rule
+    ///
+    /// # Returns
+    /// * `Ok(true)` - It existed and was removed
+    pub async fn remove(db: &Database, glob: &str) -> Result<bool> {
+        db.delete_preference(&format!("{}{}", IGNORE_PREFIX, glob))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_to_regex() {
+        let man = glob_to_regex("man *").unwrap();
+        assert!(man.is_match("man git-rebase"));
+        assert!(!man.is_match("command man ls"));
+
+        let help = glob_to_regex("*--help").unwrap();
+        assert!(help.is_match("cargo build --help"));
+        assert!(!help.is_match("cargo build --help | less"));
+
+        let class = glob_to_regex("[bf]g").unwrap();
+        assert!(class.is_match("fg"));
+        assert!(!class.is_match("jg"));
+        assert!(glob_to_regex("[!a-z]?").unwrap().is_match("1x"));
+
+        // Regex characters are just characters
+        assert!(glob_to_regex("ls (1).txt").unwrap().is_match("ls (1).txt"));
+        assert!(glob_to_regex(r"echo \*").unwrap().is_match("echo *"));
+        assert!(!glob_to_regex(r"echo \*").unwrap().is_match("echo hi"));
+
+        assert!(glob_to_regex("[abc").is_none());
+    }
+
+    #[test]
+    fn test_shell_history_settings() {
+        assert_eq!(
+            split_histignore(r"ls:[bf]g:exit:&:echo a\:b: *"),
+            vec!["ls", "[bf]g", "exit", "echo a:b", " *"]
+        );
+
+        let bash = IgnoreRules::from_histignore("ls:history*:&");
+        assert!(bash.matching("history | grep ssh").is_some());
+        assert!(bash.matching("ls").is_some());
+        assert!(bash.matching("ls -la").is_none());
+
+        let zsh = IgnoreRules::from_history_ignore("(ls|cd|pwd|exit|cd ..|man *)");
+        assert!(zsh.matching("cd ..").is_some());
+        assert!(zsh.matching("man git").is_some());
+        assert!(zsh.matching("cd src").is_none());
+        assert!(zsh.matching("lsof").is_none());
+
+        let mut rules = IgnoreRules::default();
+        rules.extend(bash);
+        rules.extend(zsh);
+        assert_eq!(rules.globs().count(), 3);
+        assert_eq!(IgnoreRules::from_history_ignore("").globs().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_rules_roundtrip() {
+        let db = Database::new_test().await.unwrap();
+
+        IgnoreRules::add(&db, "man *").await.unwrap();
+        IgnoreRules::add(&db, "*--help").await.unwrap();
+        assert!(IgnoreRules::add(&db, "[oops").await.is_err());
+
+        let rules = IgnoreRules::load(&db).await.unwrap();
+        assert_eq!(rules.matching("man tar"), Some("man *"));
+        assert_eq!(rules.matching("docker run --help"), Some("*--help"));
+        assert_eq!(rules.matching("make"), None);
+
+        assert!(IgnoreRules::remove(&db, "man *").await.unwrap());
+        let rules = IgnoreRules::load(&db).await.unwrap();
+        assert_eq!(rules.globs().collect::<Vec<_>>(), vec!["*--help"]);
+    }
+}
@@ -0,0 +1,653 @@
+//! Tables for `recent` and `search`
+//!
+//! Pick the columns, pick the order, and get rows lined up under a header.
+//! The command column takes whatever width the others leave, so a long
+//! command gets cut short instead of wrapping the table.
+//!
+//! `--porcelain` rows are for scripts instead: tab-separated raw values
+//! (UTC timestamps, milliseconds, exit codes, full paths), no header, no
+//! numbering, nothing cut short, and the same fields in every version.
+
+use crate::core::searcher;
+use crate::db::Command;
+use crate::error::{RecallError, Result};
+use crate::intelligence::performance::format_duration;
+use crate::ui::{self, Glyph, Style};
+use chrono::{DateTime, Local, Utc};
+use std::str::FromStr;
+
+/// How rows are ordered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    /// Last used first
+    Recency,
+    /// Most used first
+    Usage,
+    /// Slowest first
+    Duration,
+    /// Failures first
+    Exit,
+}
+
+impl FromStr for SortKey {
+    type Err = RecallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "recency" | "recent" | "last" => Ok(SortKey::Recency),
+            "usage" | "count" => Ok(SortKey::Usage),
+            "duration" | "time" => Ok(SortKey::Duration),
+            "exit" | "status" => Ok(SortKey::Exit),
+            other => Err(RecallError::Config(format!(
+                "Unknown sort '{}' (expected recency, usage, duration or exit)",
+                other
+            ))),
+        }
+    }
+}
+
+/// What `recent --group-by` puts rows together by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    /// The program: `git`, `cargo`, `docker`
+    Tool,
+    Project,
+    /// The shell session it last ran in
+    Session,
+}
+
+impl FromStr for GroupBy {
+    type Err = RecallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "tool" => Ok(GroupBy::Tool),
+            "project" => Ok(GroupBy::Project),
+            "session" => Ok(GroupBy::Session),
+            other => Err(RecallError::Config(format!(
+                "Unknown grouping '{}' (expected tool, project or session)",
+                other
+            ))),
+        }
+    }
+}
+
+/// How the LAST column reads
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Times {
+    /// `3h ago`, counted back from the given moment
+    Relative(DateTime<Utc>),
+    /// Full local date and time
+    Absolute,
+}
+
+/// Porcelain fields when no `--columns` are given, command last so
+/// `cut -f6-` or `awk -F'\t'` can take it whole
+pub const PORCELAIN_COLUMNS: &[Column] = &[
+    Column::Last,
+    Column::Count,
+    Column::Exit,
+    Column::Duration,
+    Column::Project,
+    Column::Cmd,
+];
+
+/// What `search` adds to a table
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Matches<'a> {
+    /// What was searched for, highlighted in each command when `color` is on
+    pub query: Option<&'a str>,
+    /// A line under each row: where it ran, when, and how it went
+    pub preview: bool,
+    /// Whether to use ANSI colors
+    pub color: bool,
+}
+
+/// What a table can show
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Cmd,
+    Count,
+    Last,
+    Exit,
+    Duration,
+    Project,
+}
+
+impl FromStr for Column {
+    type Err = RecallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "cmd" | "command" => Ok(Column::Cmd),
+            "count" | "uses" => Ok(Column::Count),
+            "last" => Ok(Column::Last),
+            "exit" | "status" => Ok(Column::Exit),
+            "duration" | "time" => Ok(Column::Duration),
+            "project" => Ok(Column::Project),
+            other => Err(RecallError::Config(format!(
+                "Unknown column '{}' (expected cmd, count, last, exit, duration or project)",
+                other
+            ))),
+        }
+    }
+}
+
+impl Column {
+    fn header(self) -> &'static str {
+        match self {
+            Column::Cmd => "COMMAND",
+            Column::Count => "USES",
+            Column::Last => "LAST",
+            Column::Exit => "EXIT",
+            Column::Duration => "TOOK",
+            Column::Project => "PROJECT",
+        }
+    }
+
+    /// Numbers line up on the right
+    fn right_aligned(self) -> bool {
+        matches!(self, Column::Count | Column::Duration)
+    }
+
+    fn value(self, command: &Command, times: Times) -> String {
+        match self {
+            Column::Cmd => command.command.clone(),
+            Column::Count => command.usage_count.to_string(),
+            Column::Last => match times {
+                Times::Relative(now) => relative_time(command.timestamp, now),
+                Times::Absolute => command
+                    .timestamp
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+            },
+            Column::Exit => match command.exit_code {
+                Some(0) => ui::glyph(Glyph::Ok).to_string(),
+                Some(code) => format!("{} {}", ui::glyph(Glyph::Fail), code),
+                None => String::new(),
+            },
+            Column::Duration => command
+                .execution_time_ms
+                .map(|ms| format_duration(ms.max(0) as u64))
+                .unwrap_or_default(),
+            Column::Project => command
+                .project_path
+                .rsplit(['/', '\\'])
+                .find(|s| !s.is_empty())
+                .unwrap_or(&command.project_path)
+                .to_string(),
+        }
+    }
+
+    /// The value as a script wants it
+    fn raw(self, command: &Command) -> String {
+        match self {
+            Column::Cmd => command.command.clone(),
+            Column::Count => command.usage_count.to_string(),
+            Column::Last => command
+                .timestamp
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            Column::Exit => command
+                .exit_code
+                .map(|code| code.to_string())
+                .unwrap_or_default(),
+            Column::Duration => command
+                .execution_time_ms
+                .map(|ms| ms.to_string())
+                .unwrap_or_default(),
+            Column::Project => command.project_path.clone(),
+        }
+    }
+}
+
+/// `just now`, `5m ago`, `3h ago`, `2d ago`, `3w ago`, `4mo ago`, `2y ago`
+pub fn relative_time(then: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (now - then).num_seconds().max(0);
+    let (amount, unit) = match seconds {
+        0..=59 => return "just now".to_string(),
+        60..=3_599 => (seconds / 60, "m"),
+        3_600..=86_399 => (seconds / 3_600, "h"),
+        86_400..=1_209_599 => (seconds / 86_400, "d"),
+        1_209_600..=5_183_999 => (seconds / 604_800, "w"),
+        5_184_000..=31_535_999 => (seconds / 2_592_000, "mo"),
+        _ => (seconds / 31_536_000, "y"),
+    };
+    format!("{}{} ago", amount, unit)
+}
+
+/// `cmd,count,exit` → those columns, in that order
+pub fn parse_columns(spec: &str) -> Result<Vec<Column>> {
+    let columns = spec
+        .split(',')
+        .filter(|c| !c.trim().is_empty())
+        .map(Column::from_str)
+        .collect::<Result<Vec<_>>>()?;
+
+    if columns.is_empty() {
+        return Err(RecallError::Config(
+            "--columns needs at least one column".to_string(),
+        ));
+    }
+    Ok(columns)
+}
+
+/// Reorder rows, keeping the current order among ties
+pub fn sort(commands: &mut [Command], key: SortKey) {
+    match key {
+        SortKey::Recency => commands.sort_by_key(|c| std::cmp::Reverse(c.timestamp)),
+        SortKey::Usage => commands.sort_by_key(|c| std::cmp::Reverse(c.usage_count)),
+        // Never timed goes last
+        SortKey::Duration => {
+            commands.sort_by_key(|c| std::cmp::Reverse(c.execution_time_ms.unwrap_or(-1)))
+        }
+        SortKey::Exit => commands.sort_by_key(|c| match c.exit_code {
+            Some(0) => 1,
+            Some(_) => 0,
+            None => 2,
+        }),
+    }
+}
+
+/// Numbered rows under a header
+///
+/// With a `width` the command column is cut to fit it; `None` (output going
+/// to a pipe) leaves commands whole.
+pub fn render(
+    commands: &[Command],
+    columns: &[Column],
+    times: Times,
+    width: Option<usize>,
+) -> String {
+    render_matches(commands, columns, times, width, Matches::default())
+}
+
+/// [`render`], with what matched highlighted and the preview line if asked
+pub fn render_matches(
+    commands: &[Command],
+    columns: &[Column],
+    times: Times,
+    width: Option<usize>,
+    matches: Matches,
+) -> String {
+    let cells: Vec<Vec<String>> = commands
+        .iter()
+        .map(|command| {
+            columns
+                .iter()
+                .map(|c| one_line(&c.value(command, times)))
+                .collect()
+        })
+        .collect();
+
+    let mut widths: Vec<usize> = columns.iter().map(|c| c.header().chars().count()).collect();
+    for row in &cells {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+
+    // Row number, then two spaces between columns
+    let number_width = commands.len().to_string().len().max(3);
+    if let (Some(total), Some(cmd)) = (width, columns.iter().position(|c| *c == Column::Cmd)) {
+        let others: usize = widths
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != cmd)
+            .map(|(_, w)| w + 2)
+            .sum();
+        let room = total.saturating_sub(number_width + 2 + others);
+        widths[cmd] = widths[cmd].min(room.max(columns[cmd].header().len()));
+    }
+
+    let line = |number: &str, row: Vec<String>, command: Option<&Command>| -> String {
+        let mut line = format!("{:>width$}  ", number, width = number_width);
+        let last = columns.len() - 1;
+        for (i, (cell, column)) in row.into_iter().zip(columns).enumerate() {
+            let cell = truncate(&cell, widths[i]);
+            let mut padded = if column.right_aligned() {
+                format!("{:>width$}", cell, width = widths[i])
+            } else if i == last {
+                cell.clone()
+            } else {
+                format!("{:<width$}", cell, width = widths[i])
+            };
+            if let (Column::Cmd, Some(command), Some(query), true) =
+                (column, command, matches.query, matches.color)
+            {
+                // Cut short, the `…` and anything after it isn't the command
+                let marker = ui::glyph(Glyph::Ellipsis).chars().count();
+                let visible = match cell == command.command {
+                    true => cell.chars().count(),
+                    false => cell.chars().count().saturating_sub(marker),
+                };
+                let positions = searcher::match_positions(&command.command, query);
+                padded = highlight(&padded, &positions, visible);
+            }
+            line.push_str(&padded);
+            if i != last {
+                line.push_str("  ");
+            }
+        }
+        line.trim_end().to_string() + "\n"
+    };
+
+    let mut table = line(
+        "#",
+        columns.iter().map(|c| c.header().to_string()).collect(),
+        None,
+    );
+    for (i, (row, command)) in cells.into_iter().zip(commands).enumerate() {
+        table.push_str(&line(&format!("{}.", i + 1), row, Some(command)));
+        if matches.preview {
+            let preview = format!("{}  {}", " ".repeat(number_width), preview(command, times));
+            if matches.color {
+                table.push_str(&ui::paint_always(Style::Dim, &preview));
+            } else {
+                table.push_str(&preview);
+            }
+            table.push('\n');
+        }
+    }
+    table
+}
+
+/// `~/work/api · 3h ago · exit 101 · took 4.2s`, leaving out what isn't known
+fn preview(command: &Command, times: Times) -> String {
+    let mut parts = vec![command.project_path.clone(), Column::Last.value(command, times)];
+    if let Some(code) = command.exit_code {
+        parts.push(format!("exit {}", code));
+    }
+    if let Some(ms) = command.execution_time_ms {
+        parts.push(format!("took {}", format_duration(ms.max(0) as u64)));
+    }
+    ui::joined(&parts, Glyph::Dot)
+}
+
+/// Color the characters at `positions`, up to `visible`
+fn highlight(text: &str, positions: &[usize], visible: usize) -> String {
+    let mut out = String::new();
+    let mut run = String::new();
+    for (i, c) in text.chars().enumerate() {
+        if i < visible && positions.contains(&i) {
+            run.push(c);
+            continue;
+        }
+        if !run.is_empty() {
+            out.push_str(&ui::paint_always(Style::Match, &run));
+            run.clear();
+        }
+        out.push(c);
+    }
+    if !run.is_empty() {
+        out.push_str(&ui::paint_always(Style::Match, &run));
+    }
+    out
+}
+
+/// Put each group's rows together, groups in the order they first appear
+///
+/// Rows keep their order within a group. Returns each group's name and how
+/// many rows it has, in order.
+pub fn group(commands: &mut [Command], key: impl Fn(&Command) -> String) -> Vec<(String, usize)> {
+    let mut groups: Vec<(String, usize)> = Vec::new();
+    for command in commands.iter() {
+        let name = key(command);
+        match groups.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, size)) => *size += 1,
+            None => groups.push((name, 1)),
+        }
+    }
+    commands.sort_by_cached_key(|command| {
+        let name = key(command);
+        groups.iter().position(|(n, _)| *n == name)
+    });
+    groups
+}
+
+/// [`render`] with a heading over each group, rows numbered straight through
+///
+/// `commands` are in group order, as [`group`] leaves them.
+pub fn render_grouped(
+    commands: &[Command],
+    groups: &[(String, usize)],
+    columns: &[Column],
+    times: Times,
+    width: Option<usize>,
+) -> String {
+    let table = render(commands, columns, times, width);
+    let mut lines = table.lines();
+    let mut out = lines.next().unwrap_or_default().to_string() + "\n";
+
+    let mut start = 0;
+    for (name, size) in groups {
+        let rows = &commands[start..start + size];
+        let uses: i64 = rows.iter().map(|c| c.usage_count as i64).sum();
+        out.push_str(&format!(
+            "\n{} ({} {}, {} {})\n",
+            name,
+            size,
+            if *size == 1 { "command" } else { "commands" },
+            uses,
+            if uses == 1 { "use" } else { "uses" }
+        ));
+        for line in lines.by_ref().take(*size) {
+            out.push_str(line);
+            out.push('\n');
+        }
+        start += size;
+    }
+    out
+}
+
+/// One tab-separated line per command, fields in `columns` order
+pub fn render_porcelain(commands: &[Command], columns: &[Column]) -> String {
+    let mut out = String::new();
+    for command in commands {
+        let fields: Vec<String> = columns.iter().map(|c| field(&c.raw(command))).collect();
+        out.push_str(&fields.join("\t"));
+        out.push('\n');
+    }
+    out
+}
+
+/// A value made safe for a tab-separated line: backslashes, tabs and
+/// newlines written as `\\`, `\t` and `\n`
+pub fn field(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// First line only, marked when there was more
+fn one_line(text: &str) -> String {
+    match text.split_once('\n') {
+        Some((first, _)) => format!("{} {}", first, ui::glyph(Glyph::Ellipsis)),
+        None => text.to_string(),
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let marker = ui::glyph(Glyph::Ellipsis);
+    let kept: String = text
+        .chars()
+        .take(width.saturating_sub(marker.chars().count()))
+        .collect();
+    kept + marker
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::parse_timestamp;
+
+    fn command(text: &str, usage_count: i32, exit_code: Option<i32>, ms: Option<i32>) -> Command {
+        Command {
+            id: 0,
+            project_path: "/work/api".to_string(),
+            command: text.to_string(),
+            timestamp: parse_timestamp("2026-01-05 10:00:00").unwrap(),
+            is_fav: false,
+            usage_count,
+            execution_time_ms: ms,
+            exit_code,
+            tags: None,
+            context: None,
+        }
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut commands = vec![
+            command("cargo build", 3, Some(0), Some(40_000)),
+            command("cargo test", 9, Some(101), None),
+            command("git status", 20, None, Some(30)),
+        ];
+
+        sort(&mut commands, SortKey::Usage);
+        assert_eq!(commands[0].command, "git status");
+        sort(&mut commands, SortKey::Duration);
+        assert_eq!(commands[0].command, "cargo build");
+        assert_eq!(commands[2].command, "cargo test");
+        sort(&mut commands, SortKey::Exit);
+        assert_eq!(commands[0].command, "cargo test");
+
+        assert!("size".parse::<SortKey>().is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let commands = vec![
+            command(
+                "docker compose up -d --build --remove-orphans",
+                12,
+                Some(0),
+                None,
+            ),
+            command("make", 3, Some(2), Some(1500)),
+        ];
+        let columns = parse_columns("cmd,count,exit").unwrap();
+
+        let table = render(&commands, &columns, Times::Absolute, None);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines[0],
+            "  #  COMMAND                                        USES  EXIT"
+        );
+        assert_eq!(
+            lines[1],
+            " 1.  docker compose up -d --build --remove-orphans    12  ✓"
+        );
+        assert_eq!(
+            lines[2],
+            " 2.  make                                              3  ✗ 2"
+        );
+
+        // 5 for the number, 4 + 2 each for USES and EXIT: 13 left for the command
+        let narrow = render(&commands, &columns, Times::Absolute, Some(30));
+        assert!(narrow.contains(" 1.  docker compo…    12  ✓\n"));
+
+        assert!(parse_columns("cmd,size").is_err());
+    }
+
+    #[test]
+    fn test_render_matches() {
+        let commands = vec![command("npm run build", 4, Some(1), Some(2500))];
+        let matches = Matches {
+            query: Some("run"),
+            preview: true,
+            color: true,
+        };
+
+        let table = render_matches(&commands, &[Column::Cmd], Times::Absolute, None, matches);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[1], " 1.  npm \x1b[1;33mrun\x1b[0m build");
+        assert!(lines[2].starts_with("\x1b[2m     /work/api · "));
+        assert!(lines[2].ends_with(" · exit 1 · took 2.5s\x1b[0m"));
+
+        // Cut short, the `…` isn't lit even where the match ran on
+        let narrow = render_matches(
+            &commands,
+            &[Column::Cmd],
+            Times::Absolute,
+            Some(15),
+            Matches {
+                query: Some("run build"),
+                ..matches
+            },
+        );
+        assert!(narrow.contains(" 1.  npm \x1b[1;33mrun b\x1b[0m…\n"));
+
+        let plain = Matches {
+            color: false,
+            ..matches
+        };
+        let table = render_matches(&commands, &[Column::Cmd], Times::Absolute, None, plain);
+        assert!(!table.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_group() {
+        let mut commands = vec![
+            command("git status", 5, None, None),
+            command("cargo test", 2, None, None),
+            command("git push", 1, None, None),
+        ];
+        let groups = group(&mut commands, |c| {
+            c.command.split_whitespace().next().unwrap_or("").to_string()
+        });
+        assert_eq!(
+            groups,
+            vec![("git".to_string(), 2), ("cargo".to_string(), 1)]
+        );
+
+        let table = render_grouped(&commands, &groups, &[Column::Cmd], Times::Absolute, None);
+        assert_eq!(
+            table,
+            "  #  COMMAND\n\ngit (2 commands, 6 uses)\n 1.  git status\n 2.  git push\n\n\
+             cargo (1 command, 2 uses)\n 3.  cargo test\n"
+        );
+        assert!("branch".parse::<GroupBy>().is_err());
+    }
+
+    #[test]
+    fn test_render_porcelain() {
+        let commands = vec![
+            command("make", 3, Some(2), Some(1500)),
+            command("printf 'a\tb'\necho \\done", 1, None, None),
+        ];
+
+        let rows = render_porcelain(&commands, PORCELAIN_COLUMNS);
+        let lines: Vec<&str> = rows.lines().collect();
+        assert_eq!(lines[0], "2026-01-05T10:00:00Z\t3\t2\t1500\t/work/api\tmake");
+        assert_eq!(
+            lines[1],
+            "2026-01-05T10:00:00Z\t1\t\t\t/work/api\tprintf 'a\\tb'\\necho \\\\done"
+        );
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn test_relative_time() {
+        let now = parse_timestamp("2026-03-01 12:00:00").unwrap();
+        let ago = |stamp: &str| relative_time(parse_timestamp(stamp).unwrap(), now);
+
+        assert_eq!(ago("2026-03-01 11:59:30"), "just now");
+        assert_eq!(ago("2026-03-01 11:15:00"), "45m ago");
+        assert_eq!(ago("2026-03-01 09:00:00"), "3h ago");
+        assert_eq!(ago("2026-02-27 12:00:00"), "2d ago");
+        assert_eq!(ago("2026-02-01 12:00:00"), "4w ago");
+        assert_eq!(ago("2025-10-01 12:00:00"), "5mo ago");
+        assert_eq!(ago("2024-01-01 12:00:00"), "2y ago");
+        // Clock skew between machines shouldn't read "-5m ago"
+        assert_eq!(ago("2026-03-01 12:05:00"), "just now");
+
+        let commands = vec![command("make", 1, None, None)];
+        let table = render(&commands, &[Column::Last], Times::Relative(now), None);
+        assert!(table.contains(" 1.  7w ago\n"));
+    }
+}
@@ -0,0 +1,124 @@
+//! Files the shell hooks look at before starting berri-recall
+//!
+//! Testing whether a file exists costs a shell next to nothing; starting the
+//! binary and opening the database after every command doesn't. So for
+//! commands the hooks would run in the foreground and that usually have
+//! nothing to do, the binary leaves a file saying when it's worth calling.
+//!
+//! `~/.berri-recall/summary-on` exists while `summary_after_seconds` is on,
+//! and `~/.berri-recall/hello-off` while `hello` is off. `config set`/`unset`
+//! write them, and every `record` and `finished` puts them back in line with
+//! the settings, so an edit to `config.toml` catches up one command later.
+//!
+//! `~/.berri-recall/hello/` holds a file for every directory `hello --once`
+//! has already made up its mind about, named after the path with its
+//! separators swapped for `%`, which the hooks can spell without a subshell.
+
+use crate::error::Result;
+use std::path::{Path, PathBuf};
+
+/// Marker files under one directory
+#[derive(Debug, Clone)]
+pub struct Markers {
+    dir: PathBuf,
+}
+
+impl Markers {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// `~/.berri-recall`
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".berri-recall"))
+    }
+
+    /// Exists while `finished` has a summary line to print
+    pub fn summary_path(&self) -> PathBuf {
+        self.dir.join("summary-on")
+    }
+
+    /// Create or remove the summary marker
+    pub fn set_summary(&self, on: bool) -> Result<()> {
+        set(&self.summary_path(), on)
+    }
+
+    /// Exists while `hello` is turned off
+    pub fn hello_off_path(&self) -> PathBuf {
+        self.dir.join("hello-off")
+    }
+
+    /// Create or remove the marker for `hello` being off
+    pub fn set_hello(&self, on: bool) -> Result<()> {
+        set(&self.hello_off_path(), !on)
+    }
+
+    /// Exists once `hello --once` has nothing more to say in `cwd`
+    pub fn greeted_path(&self, cwd: &str) -> PathBuf {
+        self.dir.join("hello").join(cwd.replace(['/', '\\', ':'], "%"))
+    }
+
+    /// Spare the hooks starting `hello --once` in `cwd` again
+    pub fn mark_greeted(&self, cwd: &str) -> Result<()> {
+        set(&self.greeted_path(cwd), true)
+    }
+}
+
+// An empty file that exists or doesn't
+fn set(path: &Path, on: bool) -> Result<()> {
+    match (on, path.exists()) {
+        (true, false) => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, "")?;
+        }
+        (false, true) => std::fs::remove_file(path)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_marker() {
+        let dir = tempfile::tempdir().unwrap();
+        let markers = Markers::new(dir.path().join("nested"));
+
+        markers.set_summary(false).unwrap();
+        assert!(!markers.summary_path().exists());
+
+        markers.set_summary(true).unwrap();
+        markers.set_summary(true).unwrap();
+        assert!(markers.summary_path().exists());
+
+        markers.set_summary(false).unwrap();
+        assert!(!markers.summary_path().exists());
+    }
+
+    #[test]
+    fn test_hello_markers() {
+        let dir = tempfile::tempdir().unwrap();
+        let markers = Markers::new(dir.path());
+
+        markers.set_hello(false).unwrap();
+        assert!(markers.hello_off_path().exists());
+        markers.set_hello(true).unwrap();
+        assert!(!markers.hello_off_path().exists());
+
+        // Spelled the way the hooks spell it
+        let path = markers.greeted_path("/home/me/src/app");
+        assert_eq!(path, dir.path().join("hello").join("%home%me%src%app"));
+        let path = markers.greeted_path("C:\\Users\\me");
+        assert_eq!(path, dir.path().join("hello").join("C%%Users%me"));
+
+        markers.mark_greeted("/home/me/src/app").unwrap();
+        assert!(markers.greeted_path("/home/me/src/app").exists());
+        assert!(!markers.greeted_path("/home/me/src").exists());
+    }
+}
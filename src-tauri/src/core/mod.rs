@@ -1,14 +1,39 @@
-/// Core functionality modules
-///
-/// Contains the main business logic for command recording,
-/// retrieval, searching, and project detection.
+//! Core functionality modules
+//!
+//! Contains the main business logic for command recording,
+//! retrieval, searching, and project detection.
 
+pub mod cheatsheet;
+pub mod demo;
+pub mod clipboard;
+pub mod environment;
+pub mod expander;
+pub mod ignore_rules;
+pub mod listing;
+pub mod markers;
+pub mod notifier;
+pub mod orphans;
+pub mod pipeline;
 pub mod project_detector;
+pub mod prompt;
 pub mod recorder;
+pub mod redactor;
 pub mod retriever;
+pub mod runner;
+pub mod safety;
+pub mod scriptify;
 pub mod searcher;
+pub mod signals;
+pub mod spool;
+pub mod sweep;
+pub mod tasks;
+pub mod timeline;
+pub mod triggers;
 
+pub use expander::Expander;
 pub use project_detector::ProjectDetector;
 pub use recorder::Recorder;
+pub use redactor::Redactor;
 pub use retriever::Retriever;
 pub use searcher::Searcher;
+pub use spool::Spool;
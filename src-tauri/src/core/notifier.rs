@@ -0,0 +1,25 @@
+//! Desktop notifications
+//!
+//! Used to say a long command finished, so you can stop watching the terminal.
+
+use crate::error::{RecallError, Result};
+
+/// Show a desktop notification
+///
+/// # Returns
+/// * `Err(RecallError)` - No notification service (SSH session, headless box...)
+#[cfg(feature = "notifications")]
+pub fn notify(summary: &str, body: &str) -> Result<()> {
+    notify_rust::Notification::new()
+        .appname("berri-recall")
+        .summary(summary)
+        .body(body)
+        .show()
+        .map(|_| ())
+        .map_err(|e| RecallError::Generic(format!("Couldn't show a notification: {}", e)))
+}
+
+#[cfg(not(feature = "notifications"))]
+pub fn notify(_summary: &str, _body: &str) -> Result<()> {
+    Err(RecallError::Generic("Built without notifications".to_string()))
+}
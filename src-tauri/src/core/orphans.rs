@@ -0,0 +1,68 @@
+//! History under paths that are gone
+//!
+//! Commands are filed under the project root found when they ran. Move or
+//! rename a project (or have detection pick the wrong directory) and its
+//! history sits under a path that no longer exists. `orphans` lists those
+//! and moves them to a project that's still there.
+
+use crate::core::Searcher;
+use std::path::Path;
+
+/// Most typos tolerated between an orphan's path and a live project's
+const MAX_DISTANCE: usize = 3;
+
+/// Whether the history filed under `project_path` has lost its directory
+pub fn is_orphan(project_path: &str) -> bool {
+    !Path::new(project_path).exists()
+}
+
+/// The live project `orphan` most likely became
+///
+/// One with the same directory name (it moved), otherwise one whose path is
+/// a typo or two away (renamed, or recorded with a misspelled path). `live`
+/// is in order of preference, most recently active first.
+pub fn likely_home<'a>(orphan: &str, live: &'a [String]) -> Option<&'a str> {
+    let closest = |candidates: Vec<&'a String>| {
+        candidates
+            .into_iter()
+            .map(|path| (Searcher::edit_distance(orphan, path), path.as_str()))
+            .min_by_key(|(distance, _)| *distance)
+    };
+
+    let same_name = live.iter().filter(|path| dir_name(path) == dir_name(orphan)).collect();
+    if let Some((_, path)) = closest(same_name) {
+        return Some(path);
+    }
+
+    closest(live.iter().filter(|path| path.as_str() != orphan).collect())
+        .filter(|(distance, _)| *distance <= MAX_DISTANCE)
+        .map(|(_, path)| path)
+}
+
+fn dir_name(path: &str) -> Option<&str> {
+    path.trim_end_matches(['/', '\\']).rsplit(['/', '\\']).next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_likely_home() {
+        let live = vec![
+            "/home/me/work/api".to_string(),
+            "/home/me/berri-recall".to_string(),
+            "/home/me/web".to_string(),
+        ];
+
+        // Moved
+        assert_eq!(likely_home("/home/me/old/api", &live), Some("/home/me/work/api"));
+        // Renamed by a typo
+        assert_eq!(likely_home("/home/me/berri-recal", &live), Some("/home/me/berri-recall"));
+        // Nothing like it
+        assert_eq!(likely_home("/tmp/scratch", &live), None);
+
+        assert!(is_orphan("/this/path/does/not/exist/anywhere"));
+        assert!(!is_orphan(env!("CARGO_MANIFEST_DIR")));
+    }
+}
@@ -0,0 +1,299 @@
+//! Compound commands, split into the programs they run
+//!
+//! `kubectl get pods -o json | jq '.items[]' > pods.txt && wc -l pods.txt`
+//! runs three programs. Recording indexes each stage of a line like that on
+//! its own, so `flags jq` finds the jq in the middle of it and `suggest
+//! --pipe` can finish a pipeline from its first stage.
+//!
+//! The split follows shell quoting: a `|` in quotes, after a backslash or
+//! inside `$(...)` doesn't end a stage.
+
+use crate::core::recorder::{is_assignment, WRAPPERS};
+
+/// What joins a stage to the one before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Joint {
+    /// `|` (or `|&`)
+    Pipe,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `;`, `&` or a newline
+    Then,
+}
+
+impl Joint {
+    /// As stored in `command_stages.joint`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Joint::Pipe => "pipe",
+            Joint::And => "and",
+            Joint::Or => "or",
+            Joint::Then => "then",
+        }
+    }
+}
+
+/// One program in a compound command
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stage {
+    /// `None` for the first stage
+    pub joint: Option<Joint>,
+    /// As typed, redirections included
+    pub text: String,
+    /// Past `VAR=value` and wrappers like `sudo`, without its directory
+    pub program: String,
+    /// The words after the program, redirections left out
+    pub args: Vec<String>,
+}
+
+impl Stage {
+    /// Flags it was given: `-r`, `--raw-output`, `--arg=x` as `--arg`
+    pub fn flags(&self) -> Vec<&str> {
+        self.args
+            .iter()
+            .map(String::as_str)
+            .take_while(|arg| *arg != "--")
+            .filter(|arg| arg.len() > 1 && arg.starts_with('-'))
+            .filter(|arg| !arg[1..].starts_with(|c: char| c.is_ascii_digit()))
+            .map(|arg| arg.split_once('=').map_or(arg, |(flag, _)| flag))
+            .collect()
+    }
+}
+
+/// The stages of `command`, in order
+///
+/// A plain command is one stage. Empty stages (`a ;; b`, a trailing `&`)
+/// are left out.
+pub fn stages(command: &str) -> Vec<Stage> {
+    let mut stages = Vec::new();
+    let mut text = String::new();
+    let mut joint = None;
+    let mut chars = command.chars().peekable();
+    let mut quote: Option<char> = None;
+    let mut depth = 0usize;
+
+    let mut finish = |text: &mut String, joint: Option<Joint>| {
+        if let Some(stage) = stage(text.trim(), joint) {
+            stages.push(stage);
+        }
+        text.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                text.push(c);
+                if let Some(next) = chars.next() {
+                    text.push(next);
+                }
+                continue;
+            }
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            (None, '|' | '&' | ';' | '\n') if depth == 0 => {
+                let next = chars.peek().copied();
+                let found = match (c, next) {
+                    ('|', Some('|')) => Some(Joint::Or),
+                    ('|', _) => Some(Joint::Pipe),
+                    ('&', Some('&')) => Some(Joint::And),
+                    // `2>&1`, `&>log`: a redirection, not a separator
+                    ('&', Some('>')) => None,
+                    ('&', _) if text.ends_with(['>', '<']) => None,
+                    _ => Some(Joint::Then),
+                };
+                if let Some(found) = found {
+                    if matches!((c, next), ('|', Some('|' | '&')) | ('&', Some('&'))) {
+                        chars.next();
+                    }
+                    finish(&mut text, joint);
+                    joint = Some(found);
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        text.push(c);
+    }
+    finish(&mut text, joint);
+
+    stages
+}
+
+/// How often each flag of `program` was used, most used first
+///
+/// `commands` are (command, uses) pairs. Every stage running `program`
+/// counts, wherever it sits in the line, and a flag counts once per command.
+pub fn flag_uses(program: &str, commands: &[(String, i64)]) -> Vec<(String, i64)> {
+    let mut uses: Vec<(String, i64)> = Vec::new();
+    for (command, count) in commands {
+        let mut seen: Vec<&str> = Vec::new();
+        let stages = stages(command);
+        for stage in stages.iter().filter(|s| s.program == program) {
+            for flag in stage.flags() {
+                if seen.contains(&flag) {
+                    continue;
+                }
+                seen.push(flag);
+                match uses.iter_mut().find(|(f, _)| f == flag) {
+                    Some((_, total)) => *total += count,
+                    None => uses.push((flag.to_string(), *count)),
+                }
+            }
+        }
+    }
+    // Stable, so ties keep the order they were first seen in
+    uses.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    uses
+}
+
+/// A redirection operator, with or without its target attached: `>`,
+/// `2>>log`, `2>&1`, `&>/dev/null`, `<input`
+fn redirection(word: &str) -> Option<&str> {
+    let rest = word.trim_start_matches(|c: char| c.is_ascii_digit());
+    let rest = rest.strip_prefix('&').unwrap_or(rest);
+    let target = rest
+        .strip_prefix(">>")
+        .or_else(|| rest.strip_prefix("<<"))
+        .or_else(|| rest.strip_prefix('>'))
+        .or_else(|| rest.strip_prefix('<'))?;
+    Some(target.strip_prefix('&').unwrap_or(target))
+}
+
+/// Split on whitespace outside quotes, keeping the quotes
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                continue;
+            }
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                word.push(c);
+                word.extend(chars.next());
+                continue;
+            }
+            _ => {}
+        }
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn stage(text: &str, joint: Option<Joint>) -> Option<Stage> {
+    let mut kept = Vec::new();
+    let mut split = words(text).into_iter();
+    while let Some(word) = split.next() {
+        match redirection(&word) {
+            // The target is the next word
+            Some("") => {
+                split.next();
+            }
+            Some(_) => {}
+            None => kept.push(word),
+        }
+    }
+
+    let mut kept = kept
+        .into_iter()
+        .skip_while(|word| is_assignment(word) || WRAPPERS.contains(&word.as_str()));
+    let program = kept.next()?;
+    let program = program.rsplit('/').next().unwrap_or(&program).to_string();
+
+    Some(Stage {
+        joint,
+        text: text.to_string(),
+        program,
+        args: kept.collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn programs(command: &str) -> Vec<(Option<Joint>, String)> {
+        stages(command)
+            .into_iter()
+            .map(|s| (s.joint, s.program))
+            .collect()
+    }
+
+    #[test]
+    fn test_stages() {
+        let line = "kubectl get pods -o json | jq -r '.items[] | .name' 2>/dev/null \
+                    > pods.txt && wc -l pods.txt || echo 'none; really'";
+        assert_eq!(
+            programs(line),
+            vec![
+                (None, "kubectl".to_string()),
+                (Some(Joint::Pipe), "jq".to_string()),
+                (Some(Joint::And), "wc".to_string()),
+                (Some(Joint::Or), "echo".to_string()),
+            ]
+        );
+        let split = stages(line);
+        assert_eq!(
+            split[1].text,
+            "jq -r '.items[] | .name' 2>/dev/null > pods.txt"
+        );
+        assert_eq!(split[1].args, vec!["-r", "'.items[] | .name'"]);
+        assert_eq!(split[1].flags(), vec!["-r"]);
+        assert_eq!(split[0].flags(), vec!["-o"]);
+
+        // Redirections and subshells don't split
+        assert_eq!(stages("make 2>&1 &> build.log").len(), 1);
+        assert_eq!(stages("echo $(ls | wc -l)").len(), 1);
+        assert_eq!(stages(r"echo a\|b").len(), 1);
+        assert_eq!(
+            programs("sleep 5 & sudo /usr/bin/apt update; FOO=1 make"),
+            vec![
+                (None, "sleep".to_string()),
+                (Some(Joint::Then), "apt".to_string()),
+                (Some(Joint::Then), "make".to_string()),
+            ]
+        );
+        assert!(stages("  ").is_empty());
+        assert_eq!(
+            stages("cat log |grep error&&echo done ;ls")
+                .iter()
+                .map(|s| s.text.as_str())
+                .collect::<Vec<_>>(),
+            vec!["cat log", "grep error", "echo done", "ls"]
+        );
+        let grep = &stages("grep --color=auto -n -- -v file")[0];
+        assert_eq!(grep.flags(), vec!["--color", "-n"]);
+
+        let commands = vec![
+            ("curl -s api | jq -r .name".to_string(), 5),
+            ("jq -c . data.json | jq -r .id".to_string(), 2),
+            ("jq --arg=x . f".to_string(), 1),
+        ];
+        assert_eq!(
+            flag_uses("jq", &commands),
+            vec![
+                ("-r".to_string(), 7),
+                ("-c".to_string(), 2),
+                ("--arg".to_string(), 1),
+            ]
+        );
+    }
+}
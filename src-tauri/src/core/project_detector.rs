@@ -1,10 +1,40 @@
-/// Project root detection logic
-///
-/// Detects the root directory of a project by looking for common markers
-/// like .git, package.json, Cargo.toml, etc.
+//! Project root detection logic
+//!
+//! Detects the root directory of a project by looking for common markers
+//! like .git, package.json, Cargo.toml, etc.
+//!
+//! Paths are normalized before the walk so one repo is always one project:
+//! symlinks and Windows junctions are resolved (unless
+//! `BERRI_RECALL_RESOLVE_SYMLINKS=0`), `C:foo` drive-relative paths are made
+//! absolute, and the `\\?\` prefix Windows puts on canonical paths is dropped.
+//!
+//! A git worktree is the same project as the checkout it was added from. A
+//! submodule is a project of its own unless `BERRI_RECALL_SUBMODULES=parent`.
+//!
+//! Recording detects the project on every command, so `detect_cached`
+//! remembers where the walk for markers ended up ([`RootCache`]).
 
 use crate::error::Result;
-use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Set to `0` to keep symlinked directories as their own projects
+pub const RESOLVE_SYMLINKS_ENV: &str = "BERRI_RECALL_RESOLVE_SYMLINKS";
+
+/// Set to `parent` to count a submodule as part of the repo around it
+pub const SUBMODULES_ENV: &str = "BERRI_RECALL_SUBMODULES";
+
+/// Which project a git submodule's commands belong to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SubmoduleMode {
+    /// The submodule is its own project
+    #[default]
+    Own,
+    /// It's part of the repo that contains it
+    Parent,
+}
 
 /// Project root detection markers
 const PROJECT_MARKERS: &[&str] = &[
@@ -20,6 +50,125 @@ const PROJECT_MARKERS: &[&str] = &[
     ".project",
 ];
 
+/// Directories `RootCache` remembers, the ones found longest ago go first
+const MAX_CACHED_DIRS: usize = 256;
+
+/// A walk is only remembered once the directories it looked in have been
+/// left alone this long: a marker added in the same tick of a coarse clock
+/// wouldn't change a directory's modification time
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// Where the walk for markers ended, and what it looked at on the way
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CachedRoot {
+    pub root: PathBuf,
+    /// Every directory the walk looked in, with its modification time
+    /// (nanoseconds since the epoch)
+    pub looked_in: Vec<(PathBuf, u64)>,
+    /// Unix seconds
+    pub found_at: i64,
+}
+
+/// Walks for markers remembered between runs, by the directory they started in
+///
+/// Adding or removing a marker changes the modification time of the
+/// directory it's in, so an entry holds as long as none of the directories
+/// it looked in have changed. Checking that is one `stat` per level, the
+/// walk is one per marker per level.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RootCache {
+    pub dirs: HashMap<String, CachedRoot>,
+}
+
+impl RootCache {
+    /// Where the cache lives
+    pub fn path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".berri-recall").join("project-roots.json"))
+    }
+
+    /// Read the cache (a missing or broken file is an empty cache)
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the cache, via a temp file so nobody reads half of it
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        std::fs::write(&tmp, serde_json::to_string(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// The root remembered for `dir`, if nothing it looked at has changed
+    pub fn root(&self, dir: &Path) -> Option<PathBuf> {
+        let cached = self.dirs.get(dir.to_str()?)?;
+        cached
+            .looked_in
+            .iter()
+            .all(|(dir, modified)| modified_nanos(dir) == Some(*modified))
+            .then(|| cached.root.clone())
+    }
+
+    /// Remember a walk from `dir` that ended at `root`
+    ///
+    /// Returns whether it was kept: not when a directory it looked in
+    /// changed too recently to trust, or is gone.
+    pub fn insert(
+        &mut self,
+        dir: &Path,
+        root: &Path,
+        looked_in: &[PathBuf],
+        now: SystemTime,
+    ) -> bool {
+        let Some(key) = dir.to_str() else {
+            return false;
+        };
+        let settled = (now - SETTLE).duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut stamps = Vec::with_capacity(looked_in.len());
+        for dir in looked_in {
+            match modified_nanos(dir) {
+                Some(modified) if u128::from(modified) < settled.as_nanos() => {
+                    stamps.push((dir.clone(), modified))
+                }
+                _ => return false,
+            }
+        }
+
+        if self.dirs.len() >= MAX_CACHED_DIRS && !self.dirs.contains_key(key) {
+            let oldest = self
+                .dirs
+                .iter()
+                .min_by_key(|(_, cached)| cached.found_at)
+                .map(|(dir, _)| dir.clone());
+            if let Some(oldest) = oldest {
+                self.dirs.remove(&oldest);
+            }
+        }
+        let found_at = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+        self.dirs.insert(
+            key.to_string(),
+            CachedRoot {
+                root: root.to_path_buf(),
+                looked_in: stamps,
+                found_at,
+            },
+        );
+        true
+    }
+}
+
+/// When `dir` last changed, `None` if it can't be read
+fn modified_nanos(dir: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(dir).ok()?.modified().ok()?;
+    u64::try_from(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos()).ok()
+}
+
 /// Handles project root detection
 pub struct ProjectDetector;
 
@@ -37,7 +186,7 @@ impl ProjectDetector {
     ///
     /// # Examples
     /// ```no_run
-    /// use recall_cli_lib::core::ProjectDetector;
+    /// use berri_recall_lib::core::ProjectDetector;
     /// use std::env;
     ///
     /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
@@ -48,24 +197,82 @@ impl ProjectDetector {
     /// # }
     /// ```
     pub fn detect<P: AsRef<Path>>(start_path: P) -> Result<PathBuf> {
-        let start_path = start_path.as_ref();
+        Self::detect_with(start_path, resolve_symlinks())
+    }
 
-        // Ensure the path is absolute
-        let absolute_path = if start_path.is_absolute() {
-            start_path.to_path_buf()
-        } else {
-            std::env::current_dir()?.join(start_path)
+    /// `detect`, choosing whether symlinks and junctions are followed
+    pub fn detect_with<P: AsRef<Path>>(start_path: P, resolve_symlinks: bool) -> Result<PathBuf> {
+        Self::detect_in(start_path, resolve_symlinks, submodule_mode())
+    }
+
+    /// `detect_with`, also choosing where submodules belong
+    pub fn detect_in<P: AsRef<Path>>(
+        start_path: P,
+        resolve_symlinks: bool,
+        submodules: SubmoduleMode,
+    ) -> Result<PathBuf> {
+        let absolute_path = normalize_path(start_path.as_ref(), resolve_symlinks)?;
+        let root = Self::find_root(absolute_path);
+        Self::resolve_linked_checkout(root, resolve_symlinks, submodules)
+    }
+
+    /// `detect`, remembering the walk in the cache file in your home
+    ///
+    /// The same answer as `detect`, quicker when you're in a directory it's
+    /// seen before. Worktrees and submodules are still worked out each time.
+    pub fn detect_cached<P: AsRef<Path>>(start_path: P) -> Result<PathBuf> {
+        let Some(path) = RootCache::path() else {
+            return Self::detect(start_path);
+        };
+        let mut cache = RootCache::load(&path);
+        let (root, changed) = Self::detect_in_cache(start_path, &mut cache, SystemTime::now())?;
+        if changed {
+            // Only ever a shortcut, the answer is right without it
+            let _ = cache.save(&path);
+        }
+        Ok(root)
+    }
+
+    /// `detect` through `cache`, and whether the cache took a new entry
+    pub fn detect_in_cache<P: AsRef<Path>>(
+        start_path: P,
+        cache: &mut RootCache,
+        now: SystemTime,
+    ) -> Result<(PathBuf, bool)> {
+        let resolve_symlinks = resolve_symlinks();
+        let absolute_path = normalize_path(start_path.as_ref(), resolve_symlinks)?;
+        let (root, changed) = match cache.root(&absolute_path) {
+            Some(root) => (root, false),
+            None => {
+                let (root, looked_in) = Self::walk(&absolute_path);
+                let changed = cache.insert(&absolute_path, &root, &looked_in, now);
+                (root, changed)
+            }
         };
+        let root = Self::resolve_linked_checkout(root, resolve_symlinks, submodule_mode())?;
+        Ok((root, changed))
+    }
+
+    /// Nearest directory with a marker, or `absolute_path` itself
+    fn find_root(absolute_path: PathBuf) -> PathBuf {
+        Self::walk(&absolute_path).0
+    }
+
+    /// `find_root`, along with every directory it looked in
+    fn walk(absolute_path: &Path) -> (PathBuf, Vec<PathBuf>) {
+        let mut looked_in = Vec::new();
 
         // Walk up the directory tree
-        let mut current = absolute_path.as_path();
+        let mut current = absolute_path;
 
         loop {
+            looked_in.push(current.to_path_buf());
+
             // Check for project markers
             for marker in PROJECT_MARKERS {
                 let marker_path = current.join(marker);
                 if marker_path.exists() {
-                    return Ok(current.to_path_buf());
+                    return (current.to_path_buf(), looked_in);
                 }
             }
 
@@ -75,12 +282,59 @@ impl ProjectDetector {
                 None => {
                     // Reached filesystem root without finding markers
                     // Fall back to the original directory
-                    return Ok(absolute_path);
+                    return (absolute_path.to_path_buf(), looked_in);
                 }
             }
         }
     }
 
+    /// Map a root inside a worktree or submodule to the project it belongs to
+    ///
+    /// Both have a `.git` file instead of a directory. A worktree's git dir
+    /// has a `commondir` leading back to the main repo, and the root becomes
+    /// the same spot in the main checkout. A submodule's lives under the
+    /// outer repo's `.git/modules`.
+    fn resolve_linked_checkout(
+        root: PathBuf,
+        resolve_symlinks: bool,
+        submodules: SubmoduleMode,
+    ) -> Result<PathBuf> {
+        let Some(top) = root.ancestors().find(|dir| dir.join(".git").exists()) else {
+            return Ok(root);
+        };
+        let dot_git = top.join(".git");
+        if !dot_git.is_file() {
+            return Ok(root);
+        }
+        let Some(git_dir) = read_gitdir(&dot_git) else {
+            return Ok(root);
+        };
+        // `join("")` would leave a trailing separator
+        let within = |base: PathBuf| match root.strip_prefix(top) {
+            Ok(rest) if !rest.as_os_str().is_empty() => base.join(rest),
+            _ => base,
+        };
+
+        if let Ok(common) = std::fs::read_to_string(git_dir.join("commondir")) {
+            let common = normalize_path(&git_dir.join(common.trim()), resolve_symlinks)?;
+            // A bare repo has no main checkout, it's the identity itself
+            let main = match common.file_name() {
+                Some(name) if name == ".git" => common.parent().map(Path::to_path_buf),
+                _ => Some(common.clone()),
+            };
+            return Ok(within(main.unwrap_or(common)));
+        }
+
+        let in_modules = git_dir.components().any(|c| c.as_os_str() == "modules");
+        if submodules == SubmoduleMode::Parent && in_modules {
+            if let Some(outer) = top.parent() {
+                return Self::detect_in(outer, resolve_symlinks, submodules);
+            }
+        }
+
+        Ok(root)
+    }
+
     /// Check if a path is inside a project
     ///
     /// Returns true if the path has any project markers in its hierarchy.
@@ -104,6 +358,56 @@ impl ProjectDetector {
         path.as_ref().join(".git").exists()
     }
 
+    /// The branch checked out in the repo containing `path`
+    ///
+    /// Reads `HEAD` directly rather than running git, recording does this on
+    /// every command. Worktrees and submodules (a `.git` file pointing at the
+    /// real git dir) work too. `None` outside a repo or on a detached HEAD.
+    pub fn git_branch<P: AsRef<Path>>(path: P) -> Option<String> {
+        let dot_git = path
+            .as_ref()
+            .ancestors()
+            .map(|dir| dir.join(".git"))
+            .find(|dot_git| dot_git.exists())?;
+
+        let git_dir = if dot_git.is_file() {
+            read_gitdir(&dot_git)?
+        } else {
+            dot_git
+        };
+
+        let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+        head.trim()
+            .strip_prefix("ref: refs/heads/")
+            .map(String::from)
+    }
+
+    /// What kind of project `path` is, from the files in it
+    ///
+    /// A repo can be several at once (a Rust backend with a Node frontend).
+    pub fn kinds<P: AsRef<Path>>(path: P) -> Vec<&'static str> {
+        const KINDS: &[(&str, &str)] = &[
+            ("Cargo.toml", "Rust"),
+            ("package.json", "Node"),
+            ("pyproject.toml", "Python"),
+            ("requirements.txt", "Python"),
+            ("setup.py", "Python"),
+            ("go.mod", "Go"),
+            ("pom.xml", "Java"),
+            ("build.gradle", "Java"),
+            ("Gemfile", "Ruby"),
+            ("composer.json", "PHP"),
+        ];
+
+        let mut kinds = Vec::new();
+        for (file, kind) in KINDS {
+            if path.as_ref().join(file).exists() && !kinds.contains(kind) {
+                kinds.push(*kind);
+            }
+        }
+        kinds
+    }
+
     /// Get all project markers found in a path
     ///
     /// Useful for debugging or displaying project type information.
@@ -121,6 +425,139 @@ impl ProjectDetector {
     }
 }
 
+/// Where a `.git` file says the git dir is (`gitdir: ../.git/modules/lib`)
+fn read_gitdir(dot_git: &Path) -> Option<PathBuf> {
+    let pointer = std::fs::read_to_string(dot_git).ok()?;
+    let target = pointer.trim().strip_prefix("gitdir:")?.trim();
+    Some(dot_git.parent()?.join(target))
+}
+
+/// What `SUBMODULES_ENV` asks for, a submodule of its own by default
+pub fn submodule_mode() -> SubmoduleMode {
+    match std::env::var(SUBMODULES_ENV).as_deref() {
+        Ok("parent") => SubmoduleMode::Parent,
+        _ => SubmoduleMode::Own,
+    }
+}
+
+/// Whether `RESOLVE_SYMLINKS_ENV` leaves symlink resolution on (the default)
+pub fn resolve_symlinks() -> bool {
+    !matches!(
+        std::env::var(RESOLVE_SYMLINKS_ENV).as_deref(),
+        Ok("0") | Ok("false") | Ok("no") | Ok("off")
+    )
+}
+
+/// One spelling per directory
+///
+/// Absolute, `.` and `..` folded away, and with `resolve_symlinks` every link
+/// and junction followed. On Windows the result also has the casing that's on
+/// disk either way, so `c:\Src\App` and `C:\src\app` agree.
+pub fn normalize_path(path: &Path, resolve_symlinks: bool) -> Result<PathBuf> {
+    // Handles `C:foo` (relative to C:'s own current directory) as well
+    let absolute = std::path::absolute(path)?;
+
+    if resolve_symlinks {
+        if let Ok(canonical) = std::fs::canonicalize(&absolute) {
+            return Ok(strip_verbatim(&canonical));
+        }
+    }
+
+    Ok(true_case(&lexical_normalize(&absolute)))
+}
+
+/// Fold `.` and `..` without touching the filesystem
+fn lexical_normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Drop the `\\?\` prefix `canonicalize` gives every path on Windows
+///
+/// `\\?\C:\src` becomes `C:\src` and `\\?\UNC\server\share` becomes
+/// `\\server\share`, which is what the shell (and every earlier recording)
+/// calls them.
+pub fn strip_verbatim(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    if let Some(unc) = text.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{}", unc))
+    } else if let Some(local) = text.strip_prefix(r"\\?\") {
+        // Only when it's a plain drive path, `\\?\Volume{..}` has no other name
+        if local.as_bytes().get(1) == Some(&b':') {
+            PathBuf::from(local)
+        } else {
+            path.to_path_buf()
+        }
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// The on-disk casing of every component that exists
+#[cfg(windows)]
+fn true_case(path: &Path) -> PathBuf {
+    use std::path::Prefix;
+
+    let mut cased = PathBuf::new();
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            match component {
+                // Drive letters are upper case however they were typed
+                Component::Prefix(prefix) if matches!(prefix.kind(), Prefix::Disk(_)) => {
+                    cased.push(prefix.as_os_str().to_string_lossy().to_uppercase())
+                }
+                other => cased.push(other),
+            }
+            continue;
+        };
+
+        let on_disk = std::fs::read_dir(&cased).ok().and_then(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name())
+                .find(|entry| {
+                    entry.to_string_lossy().to_lowercase() == name.to_string_lossy().to_lowercase()
+                })
+        });
+        cased.push(on_disk.as_deref().unwrap_or(name));
+    }
+    cased
+}
+
+#[cfg(not(windows))]
+fn true_case(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+/// Whether two recorded project paths are the same directory
+///
+/// Case doesn't matter on Windows, and neither does `/` vs `\`.
+pub fn paths_equal(a: &str, b: &str) -> bool {
+    same_path(a, b, cfg!(windows))
+}
+
+fn same_path(a: &str, b: &str, case_insensitive: bool) -> bool {
+    if !case_insensitive {
+        return a == b;
+    }
+
+    let fold = |path: &str| {
+        path.replace('/', "\\")
+            .trim_end_matches('\\')
+            .to_lowercase()
+    };
+    fold(a) == fold(b)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,7 +576,7 @@ mod tests {
 
         // Should detect from subdirectory
         let detected = ProjectDetector::detect(&sub_dir).unwrap();
-        assert_eq!(detected, project_dir);
+        assert_eq!(detected, project_dir.canonicalize().unwrap());
     }
 
     #[test]
@@ -149,7 +586,73 @@ mod tests {
         fs::write(temp.path().join("package.json"), "{}").unwrap();
 
         let detected = ProjectDetector::detect(temp.path()).unwrap();
-        assert_eq!(detected, temp.path());
+        assert_eq!(detected, temp.path().canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_git_branch() {
+        let temp = TempDir::new().unwrap();
+        let repo = temp.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::write(repo.join(".git/HEAD"), "ref: refs/heads/feat/schema\n").unwrap();
+        assert_eq!(
+            ProjectDetector::git_branch(repo.join("src")).as_deref(),
+            Some("feat/schema")
+        );
+
+        // A worktree's .git is a file pointing at its own git dir
+        let worktree = temp.path().join("wt");
+        let git_dir = repo.join(".git/worktrees/wt");
+        fs::create_dir_all(&git_dir).unwrap();
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/hotfix\n").unwrap();
+        fs::write(worktree.join(".git"), format!("gitdir: {}\n", git_dir.display())).unwrap();
+        assert_eq!(ProjectDetector::git_branch(&worktree).as_deref(), Some("hotfix"));
+
+        fs::write(repo.join(".git/HEAD"), "4b825dc642cb6eb9a060e54bf8d69288fbee4904\n").unwrap();
+        assert_eq!(ProjectDetector::git_branch(&repo), None);
+    }
+
+    #[test]
+    fn test_worktree_is_main_repo() {
+        let temp = TempDir::new().unwrap();
+        let repo = temp.path().join("repo");
+        let git_dir = repo.join(".git/worktrees/wt");
+        fs::create_dir_all(&git_dir).unwrap();
+        fs::write(git_dir.join("commondir"), "../..\n").unwrap();
+
+        let worktree = temp.path().join("wt");
+        fs::create_dir_all(worktree.join("crates/cli/src")).unwrap();
+        fs::write(worktree.join("crates/cli/Cargo.toml"), "").unwrap();
+        fs::write(worktree.join(".git"), format!("gitdir: {}\n", git_dir.display())).unwrap();
+
+        let repo = repo.canonicalize().unwrap();
+        assert_eq!(ProjectDetector::detect(&worktree).unwrap().as_os_str(), repo.as_os_str());
+        assert_eq!(
+            ProjectDetector::detect(worktree.join("crates/cli/src")).unwrap(),
+            repo.join("crates/cli")
+        );
+    }
+
+    #[test]
+    fn test_submodule_modes() {
+        let temp = TempDir::new().unwrap();
+        let outer = temp.path().join("outer");
+        fs::create_dir_all(outer.join(".git/modules/lib")).unwrap();
+        fs::create_dir_all(outer.join("lib/src")).unwrap();
+        fs::write(outer.join("lib/.git"), "gitdir: ../.git/modules/lib\n").unwrap();
+
+        let outer = outer.canonicalize().unwrap();
+        let src = outer.join("lib/src");
+        assert_eq!(
+            ProjectDetector::detect_in(&src, true, SubmoduleMode::Own).unwrap(),
+            outer.join("lib")
+        );
+        assert_eq!(
+            ProjectDetector::detect_in(&src, true, SubmoduleMode::Parent).unwrap(),
+            outer
+        );
     }
 
     #[test]
@@ -160,7 +663,85 @@ mod tests {
 
         // Should fall back to the original directory
         let detected = ProjectDetector::detect(&sub_dir).unwrap();
-        assert_eq!(detected, sub_dir);
+        assert_eq!(detected, sub_dir.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_root_cache() {
+        let temp = TempDir::new().unwrap();
+        let project = temp.path().canonicalize().unwrap().join("api");
+        let deep = project.join("src").join("handlers");
+        fs::create_dir_all(&deep).unwrap();
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+        let mut cache = RootCache::default();
+
+        // Everything was just created, too fresh to remember
+        let now = SystemTime::now();
+        let (root, changed) = ProjectDetector::detect_in_cache(&deep, &mut cache, now).unwrap();
+        assert_eq!((root, changed), (project.clone(), false));
+
+        let later = now + Duration::from_secs(10);
+        let (root, changed) = ProjectDetector::detect_in_cache(&deep, &mut cache, later).unwrap();
+        assert_eq!((root, changed), (project.clone(), true));
+        assert_eq!(cache.dirs[deep.to_str().unwrap()].looked_in.len(), 3);
+        assert_eq!(cache.root(&deep), Some(project.clone()));
+        let (_, changed) = ProjectDetector::detect_in_cache(&deep, &mut cache, later).unwrap();
+        assert!(!changed);
+
+        // A new marker on the way up changes its directory
+        fs::write(project.join("src").join("package.json"), "{}").unwrap();
+        assert_eq!(cache.root(&deep), None);
+        let (root, _) = ProjectDetector::detect_in_cache(&deep, &mut cache, later).unwrap();
+        assert_eq!(root, project.join("src"));
+
+        // Round trips through the file
+        let path = temp.path().join("project-roots.json");
+        cache.save(&path).unwrap();
+        assert_eq!(RootCache::load(&path).dirs, cache.dirs);
+        assert!(RootCache::load(&temp.path().join("missing.json")).dirs.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_project() {
+        let temp = TempDir::new().unwrap();
+        let real = temp.path().join("src").join("app");
+        fs::create_dir_all(real.join(".git")).unwrap();
+        fs::create_dir(real.join("lib")).unwrap();
+        let link = temp.path().join("app-link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        // Through the link it's the same project...
+        let real = real.canonicalize().unwrap();
+        assert_eq!(
+            ProjectDetector::detect_with(link.join("lib"), true).unwrap(),
+            real
+        );
+        assert_eq!(
+            ProjectDetector::detect_with(real.join("lib/../lib/."), true).unwrap(),
+            real
+        );
+        // ...unless links are kept as they are
+        assert_eq!(ProjectDetector::detect_with(&link, false).unwrap(), link);
+    }
+
+    #[test]
+    fn test_windows_path_spellings() {
+        assert_eq!(
+            strip_verbatim(Path::new(r"\\?\C:\src\app")),
+            PathBuf::from(r"C:\src\app")
+        );
+        assert_eq!(
+            strip_verbatim(Path::new(r"\\?\UNC\build01\share\app")),
+            PathBuf::from(r"\\build01\share\app")
+        );
+        let volume = Path::new(r"\\?\Volume{0a1b}\app");
+        assert_eq!(strip_verbatim(volume), volume);
+
+        assert!(same_path(r"C:\Src\App", "c:/src/app/", true));
+        assert!(same_path(r"\\build01\Share", r"\\BUILD01\share", true));
+        assert!(!same_path(r"C:\src\app", r"C:\src\api", true));
+        assert!(!same_path("/src/App", "/src/app", false));
     }
 
     #[test]
@@ -0,0 +1,224 @@
+//! Prompt segment
+//!
+//! Prompts redraw after every command, so the segment can't touch the
+//! database. `suggest` and `analyze` write each project's pending
+//! suggestions to a small JSON file and `prompt-segment` only reads that.
+//! The hooks run `warm` in the background on `cd`, so a project you just
+//! entered has them before the next prompt.
+
+use crate::error::Result;
+use crate::ui::{self, Glyph};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Cached suggestions older than this are left out of the prompt
+pub const MAX_AGE_HOURS: i64 = 12;
+
+/// `warm` leaves a project alone when its entry is newer than this
+pub const WARM_AFTER_MINUTES: i64 = 10;
+
+/// Longest command shown in the prompt before it gets cut
+const MAX_SEGMENT_CHARS: usize = 40;
+
+/// What the segment shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SegmentFormat {
+    /// Best pending suggestion: "💡 cargo test"
+    Top,
+    /// How many are pending: "💡3"
+    Count,
+}
+
+impl std::str::FromStr for SegmentFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "top" => Ok(SegmentFormat::Top),
+            "count" => Ok(SegmentFormat::Count),
+            other => Err(format!("Unknown format '{}' (expected top or count)", other)),
+        }
+    }
+}
+
+/// Pending suggestions for one project
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PromptEntry {
+    /// Best first
+    pub pending: Vec<String>,
+    /// Unix seconds
+    pub updated_at: i64,
+}
+
+/// Every project's pending suggestions, keyed by project root
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PromptCache {
+    pub projects: HashMap<String, PromptEntry>,
+}
+
+impl PromptCache {
+    /// Where the cache lives
+    pub fn path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".berri-recall").join("prompt-cache.json"))
+    }
+
+    /// Read the cache (a missing or broken file is an empty cache)
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the cache, via a temp file so a prompt never reads half of it
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Replace a project's pending suggestions
+    pub fn set(&mut self, project: &str, pending: Vec<String>, now: i64) {
+        self.projects.insert(
+            project.to_string(),
+            PromptEntry {
+                pending,
+                updated_at: now,
+            },
+        );
+    }
+
+    /// Whether a project's entry was written in the last `minutes`
+    pub fn is_fresh(&self, project: &str, minutes: i64, now: i64) -> bool {
+        self.projects
+            .get(project)
+            .is_some_and(|entry| now - entry.updated_at < minutes * 60)
+    }
+
+    /// Drop a suggestion that was taken or dismissed
+    ///
+    /// # Returns
+    /// * `true` - Something was removed
+    pub fn remove(&mut self, project: &str, command: &str) -> bool {
+        match self.projects.get_mut(project) {
+            Some(entry) => {
+                let before = entry.pending.len();
+                entry.pending.retain(|c| c != command);
+                entry.pending.len() != before
+            }
+            None => false,
+        }
+    }
+
+    /// The prompt text for a project, None when there's nothing worth showing
+    pub fn segment(&self, project: &str, format: SegmentFormat, now: i64) -> Option<String> {
+        let entry = self.projects.get(project)?;
+        if now - entry.updated_at > MAX_AGE_HOURS * 3600 || entry.pending.is_empty() {
+            return None;
+        }
+
+        let icon = if ui::theme().emoji { "💡" } else { "hint:" };
+        match format {
+            SegmentFormat::Count => Some(format!("{}{}", icon, entry.pending.len())),
+            SegmentFormat::Top => {
+                let top = &entry.pending[0];
+                let shown = if top.chars().count() > MAX_SEGMENT_CHARS {
+                    let marker = ui::glyph(Glyph::Ellipsis);
+                    let kept = MAX_SEGMENT_CHARS.saturating_sub(marker.chars().count());
+                    let cut: String = top.chars().take(kept).collect();
+                    format!("{}{}", cut, marker)
+                } else {
+                    top.clone()
+                };
+                Some(format!("{} {}", icon, shown))
+            }
+        }
+    }
+}
+
+/// Config for a prompt framework, ready to paste
+pub fn init_snippet(framework: &str) -> Option<&'static str> {
+    match framework {
+        "starship" => Some(
+            r#"# ~/.config/starship.toml
+[custom.berri_recall]
+command = "berri-recall prompt-segment"
+when = true
+shell = ["sh"]
+format = "[$output]($style) "
+style = "dimmed yellow"
+"#,
+        ),
+        "p10k" | "powerlevel10k" => Some(
+            r#"# ~/.p10k.zsh: add berri_recall to POWERLEVEL9K_RIGHT_PROMPT_ELEMENTS, then
+function prompt_berri_recall() {
+  local segment
+  segment=$(berri-recall prompt-segment 2>/dev/null) || return
+  [[ -n $segment ]] && p10k segment -f 3 -t "$segment"
+}
+"#,
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment() {
+        let mut cache = PromptCache::default();
+        cache.set(
+            "/work/api",
+            vec!["cargo test".to_string(), "git push".to_string()],
+            1_000,
+        );
+
+        assert_eq!(
+            cache.segment("/work/api", SegmentFormat::Top, 1_060).as_deref(),
+            Some("💡 cargo test")
+        );
+        assert_eq!(
+            cache.segment("/work/api", SegmentFormat::Count, 1_060).as_deref(),
+            Some("💡2")
+        );
+        assert!(cache.segment("/work/web", SegmentFormat::Top, 1_060).is_none());
+
+        assert!(cache.is_fresh("/work/api", WARM_AFTER_MINUTES, 1_060));
+        assert!(!cache.is_fresh("/work/api", WARM_AFTER_MINUTES, 1_000 + 3600));
+        assert!(!cache.is_fresh("/work/web", WARM_AFTER_MINUTES, 1_060));
+
+        // Stale suggestions stay out of the prompt
+        let later = 1_000 + MAX_AGE_HOURS * 3600 + 1;
+        assert!(cache.segment("/work/api", SegmentFormat::Top, later).is_none());
+
+        assert!(cache.remove("/work/api", "cargo test"));
+        assert_eq!(
+            cache.segment("/work/api", SegmentFormat::Top, 1_060).as_deref(),
+            Some("💡 git push")
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prompt-cache.json");
+
+        let mut cache = PromptCache::default();
+        cache.set("/work/api", vec!["x".repeat(60)], 1_000);
+        cache.save(&path).unwrap();
+
+        let loaded = PromptCache::load(&path);
+        let segment = loaded.segment("/work/api", SegmentFormat::Top, 1_000).unwrap();
+        assert_eq!(segment.chars().count(), "💡 ".chars().count() + MAX_SEGMENT_CHARS);
+        assert!(segment.ends_with('…'));
+
+        assert!(PromptCache::load(&dir.path().join("missing.json")).projects.is_empty());
+    }
+}
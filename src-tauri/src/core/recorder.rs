@@ -2,7 +2,9 @@
 //
 // Filters out sensitive stuff like passwords and API keys
 
-use crate::db::{CommandInput, Database};
+use crate::core::ignore_rules::IgnoreRules;
+use crate::core::project_detector::paths_equal;
+use crate::db::{CommandInput, Config, Database, ExecutionMeta};
 use crate::error::{RecallError, Result};
 use regex::Regex;
 use std::sync::Arc;
@@ -25,25 +27,129 @@ const SENSITIVE_PATTERNS: &[&str] = &[
     r"-p\s+\S+", // -p with a password right after it
 ];
 
+// berri-recall's own commands. Recording these would just teach it that you
+// like running berri-recall. Only the binary's own name: a `berri` of your
+// own is somebody else's program.
+const SELF_PREFIXES: &[&str] = &["berri-recall"];
+
+// Extra prefixes to treat as berri-recall, comma separated (your `br` alias)
+pub const SELF_PREFIXES_ENV: &str = "BERRI_RECALL_SELF_PREFIXES";
+
+// Set for everything berri-recall runs itself (triggers, plugins, picked
+// commands). Hooks in a shell started from there don't record.
+pub const INTERNAL_ENV: &str = "BERRI_RECALL_INTERNAL";
+
+// Words that run the next word as the actual program
+pub(crate) const WRAPPERS: &[&str] = &["command", "doas", "exec", "env", "nohup", "sudo", "time"];
+
+// Not worth remembering on their own
+const NOISE: &[&str] = &["ls", "cd", "pwd", "exit", "clear", "history", "recall"];
+
+/// Why a command was left out, for the tallies `status` shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dropped {
+    /// One of the user's globs, or the shell's HISTIGNORE
+    IgnoreRule,
+    /// Typed with a leading space
+    LeadingSpace,
+    /// `ls`, `cd` and the like, or a single character
+    Noise,
+    /// Looked like it held a password or token
+    Sensitive,
+    TooLong,
+}
+
+impl Dropped {
+    pub const ALL: [Dropped; 5] = [
+        Dropped::IgnoreRule,
+        Dropped::Sensitive,
+        Dropped::LeadingSpace,
+        Dropped::Noise,
+        Dropped::TooLong,
+    ];
+
+    /// As stored in `dropped_recordings.reason`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Dropped::IgnoreRule => "ignore_rule",
+            Dropped::LeadingSpace => "leading_space",
+            Dropped::Noise => "noise",
+            Dropped::Sensitive => "sensitive",
+            Dropped::TooLong => "too_long",
+        }
+    }
+
+    /// What `status` calls it
+    pub fn label(self) -> &'static str {
+        match self {
+            Dropped::IgnoreRule => "Ignore rules",
+            Dropped::LeadingSpace => "Leading space",
+            Dropped::Noise => "ls, cd and co.",
+            Dropped::Sensitive => "Sensitive",
+            Dropped::TooLong => "Too long",
+        }
+    }
+}
+
+fn sensitive_patterns() -> Vec<Regex> {
+    SENSITIVE_PATTERNS
+        .iter()
+        .filter_map(|pattern| Regex::new(pattern).ok())
+        .collect()
+}
+
 pub struct Recorder {
     db: Arc<Database>,
     sensitive_regex: Vec<Regex>,
+    meta: ExecutionMeta,
+    self_prefixes: Vec<String>,
+    internal: bool,
+    ignore_rules: IgnoreRules,
+    config: Option<Config>,
 }
 
 impl Recorder {
     pub fn new(db: Arc<Database>) -> Self {
         // Build all the regex patterns once so we don't recompile them every time
-        let sensitive_regex = SENSITIVE_PATTERNS
-            .iter()
-            .filter_map(|pattern| Regex::new(pattern).ok())
-            .collect();
-
         Self {
             db,
-            sensitive_regex,
+            sensitive_regex: sensitive_patterns(),
+            meta: ExecutionMeta::default(),
+            self_prefixes: self_prefixes(),
+            internal: std::env::var_os(INTERNAL_ENV).is_some(),
+            ignore_rules: IgnoreRules::default(),
+            config: None,
         }
     }
 
+    // Would record turn this down? Doesn't need a database, so commands can be
+    // vetted before they're spooled to disk.
+    pub fn refuses(command: &str) -> bool {
+        let command = Self::sanitize_command(command);
+        let lowercase = command.to_lowercase();
+        command.is_empty()
+            || command.len() > MAX_COMMAND_LENGTH
+            || sensitive_patterns().iter().any(|regex| regex.is_match(&lowercase))
+    }
+
+    // The user's "never record these" globs
+    pub fn with_ignore_rules(mut self, rules: IgnoreRules) -> Self {
+        self.ignore_rules = rules;
+        self
+    }
+
+    // Settings already loaded, so recording doesn't read them all again
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    // Session, host and directory to store with every run from here on
+    pub fn with_meta(mut self, meta: ExecutionMeta) -> Self {
+        self.meta = meta;
+        self
+    }
+
     // Main recording function. Checks if the command is safe, cleans it up, saves it.
     pub async fn record(
         &self,
@@ -53,14 +159,47 @@ impl Recorder {
         exit_code: Option<i32>,
         context: Option<String>,
     ) -> Result<i64> {
+        self.record_with_meta(
+            command,
+            project_path,
+            execution_time_ms,
+            exit_code,
+            context,
+            &self.meta,
+        )
+        .await
+    }
+
+    // Same as record, for a run whose session/time isn't this recorder's (spooled ones)
+    pub async fn record_with_meta(
+        &self,
+        command: &str,
+        project_path: &str,
+        execution_time_ms: Option<i32>,
+        exit_code: Option<i32>,
+        context: Option<String>,
+        meta: &ExecutionMeta,
+    ) -> Result<i64> {
+        // Clean up any weird characters first, so an escape sequence can't
+        // hide a secret from the checks
+        let sanitized = Self::sanitize_command(command);
+
         // Make sure it's safe to record
-        self.validate_command(command)?;
+        if let Err(e) = self.validate_command(&sanitized) {
+            let dropped = match e {
+                RecallError::SensitiveData => Some(Dropped::Sensitive),
+                RecallError::CommandTooLong(_) => Some(Dropped::TooLong),
+                _ => None,
+            };
+            if let Some(dropped) = dropped {
+                let _ = self.db.count_dropped(dropped.as_str()).await;
+            }
+            return Err(e);
+        }
 
-        // Clean up any weird characters
-        let sanitized = self.sanitize_command(command);
 
         let input = CommandInput {
-            project_path: project_path.to_string(),
+            project_path: self.recorded_spelling(project_path).await?,
             command: sanitized,
             execution_time_ms,
             exit_code,
@@ -68,11 +207,28 @@ impl Recorder {
         };
 
         // Shove it in the database
-        let id = self.db.record_command(input).await?;
+        let id = match &self.config {
+            Some(config) => self.db.record_command_using(input, meta, config).await?,
+            None => self.db.record_command_with(input, meta).await?,
+        };
 
         Ok(id)
     }
 
+    // The way a project was first recorded, when Windows would call it the
+    // same directory (`C:\Src\app` and `c:\src\app`)
+    async fn recorded_spelling(&self, project_path: &str) -> Result<String> {
+        if !cfg!(windows) {
+            return Ok(project_path.to_string());
+        }
+
+        let known = self.db.get_project_paths().await?;
+        Ok(known
+            .into_iter()
+            .find(|known| paths_equal(known, project_path))
+            .unwrap_or_else(|| project_path.to_string()))
+    }
+
     // Check if this command is safe to record (not empty, not huge, no passwords)
     fn validate_command(&self, command: &str) -> Result<()> {
         let trimmed = command.trim();
@@ -95,16 +251,26 @@ impl Recorder {
 
     /// Sanitize a command string
     ///
-    /// - Removes null bytes
-    /// - Trims whitespace
-    /// - Normalizes whitespace (multiple spaces to single)
-    fn sanitize_command(&self, command: &str) -> String {
-        command
-            .replace('\0', "") // Remove null bytes
-            .trim() // Trim edges
-            .split_whitespace() // Split on whitespace
-            .collect::<Vec<_>>() // Collect parts
-            .join(" ") // Join with single space
+    /// - Removes terminal escape sequences (colours, cursor moves, window
+    ///   titles, bracketed paste markers) pasted in along with a command
+    /// - Removes other control characters, null bytes included, but keeps
+    ///   newlines and tabs: heredocs and multi-line PowerShell blocks need them
+    /// - Trims whitespace, around the command and at the end of each line
+    /// - Normalizes spacing (runs of spaces to a single one)
+    pub fn sanitize_command(command: &str) -> String {
+        let lines: Vec<String> = strip_controls(command)
+            .split('\n')
+            .map(|line| {
+                let mut tidy = String::with_capacity(line.len());
+                for c in line.chars() {
+                    if !(c == ' ' && tidy.ends_with(' ')) {
+                        tidy.push(c);
+                    }
+                }
+                tidy.trim_end_matches(' ').to_string()
+            })
+            .collect();
+        lines.join("\n").trim().to_string()
     }
 
     /// Check if command contains sensitive data
@@ -124,26 +290,70 @@ impl Recorder {
     /// - Very short commands (single char)
     /// - Common navigation commands
     /// - History commands
+    /// - berri-recall itself, however it was spelled, and anything run from
+    ///   inside it
+    /// - Commands typed with a leading space (the shells' ignorespace habit)
+    /// - Anything matching one of the user's ignore globs
     pub fn should_ignore(&self, command: &str) -> bool {
+        self.is_own(command.trim()) || self.drop_reason(command).is_some()
+    }
+
+    /// Why `should_ignore` leaves `command` out, unless it's berri-recall's
+    /// own (those aren't counted as dropped)
+    pub fn drop_reason(&self, command: &str) -> Option<Dropped> {
         let trimmed = command.trim();
+        if command.starts_with(' ') {
+            Some(Dropped::LeadingSpace)
+        } else if trimmed.len() < 2 || NOISE.contains(&trimmed) {
+            Some(Dropped::Noise)
+        } else if self.ignore_rules.matching(trimmed).is_some() {
+            Some(Dropped::IgnoreRule)
+        } else {
+            None
+        }
+    }
 
-        // Too short
-        if trimmed.len() < 2 {
-            return true;
+    /// Add `command` to the dropped tallies, if it's being left out and
+    /// isn't berri-recall's own. Only the reason is kept, never the command.
+    pub async fn count_dropped(&self, command: &str) -> Result<()> {
+        if self.is_own(command.trim()) {
+            return Ok(());
+        }
+        match self.drop_reason(command) {
+            Some(dropped) => self.db.count_dropped(dropped.as_str()).await,
+            None => Ok(()),
         }
+    }
+
+    fn is_own(&self, command: &str) -> bool {
+        self.internal || self.is_self_invocation(command)
+    }
 
-        // Ignore list
-        let ignore_list = [
-            "ls",
-            "cd",
-            "pwd",
-            "exit",
-            "clear",
-            "history",
-            "recall",
-        ];
+    /// Whether `command` runs berri-recall
+    ///
+    /// Looks past `VAR=value`, wrappers like `sudo` and `command`, and the
+    /// directory the program was run from.
+    fn is_self_invocation(&self, command: &str) -> bool {
+        let words: Vec<&str> = command
+            .split_whitespace()
+            .skip_while(|word| is_assignment(word) || WRAPPERS.contains(word))
+            .collect();
+        let Some((program, args)) = words.split_first() else {
+            return false;
+        };
 
-        ignore_list.contains(&trimmed)
+        let program = program.rsplit(['/', '\\']).next().unwrap_or(program);
+        let program = program.strip_suffix(".exe").unwrap_or(program);
+        let normalized = std::iter::once(program)
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        self.self_prefixes.iter().any(|prefix| {
+            normalized
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+        })
     }
 
     /// Batch record multiple commands
@@ -169,15 +379,148 @@ impl Recorder {
     }
 }
 
+fn self_prefixes() -> Vec<String> {
+    let extra = std::env::var(SELF_PREFIXES_ENV).unwrap_or_default();
+
+    SELF_PREFIXES
+        .iter()
+        .map(|prefix| prefix.to_string())
+        .chain(
+            extra
+                .split(',')
+                .map(|prefix| prefix.trim().to_string())
+                .filter(|prefix| !prefix.is_empty()),
+        )
+        .collect()
+}
+
+// FOO=bar in front of a command
+pub(crate) fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// `command` without escape sequences or control characters other than
+/// newlines and tabs. Line endings become `\n`, other whitespace a space.
+fn strip_controls(command: &str) -> String {
+    let mut stripped = String::with_capacity(command.len());
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                Some('[') => skip_csi(&mut chars),
+                // OSC (window titles, hyperlinks), DCS, SOS, PM and APC
+                Some(']' | 'P' | 'X' | '^' | '_') => skip_string(&mut chars),
+                // `ESC ( B` and friends: intermediates, then a final character
+                Some(' '..='/') => {
+                    while chars.next_if(|c| (' '..='/').contains(c)).is_some() {}
+                    chars.next();
+                }
+                // Two characters, like `ESC c`
+                _ => {}
+            },
+            // The same, as single 8-bit characters
+            '\u{9b}' => skip_csi(&mut chars),
+            '\u{90}' | '\u{98}' | '\u{9d}' | '\u{9e}' | '\u{9f}' => skip_string(&mut chars),
+            '\n' | '\t' => stripped.push(c),
+            // `\r\n` is one line ending, a lone `\r` an old Mac one
+            '\r' => {
+                if chars.peek() != Some(&'\n') {
+                    stripped.push('\n');
+                }
+            }
+            c if c.is_whitespace() => stripped.push(' '),
+            c if c.is_control() => {}
+            c => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+/// The rest of a CSI sequence (`ESC [ 31 m`, `ESC [ 200 ~`): parameters and
+/// intermediates, then a final character
+fn skip_csi(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.next_if(|c| (' '..='?').contains(c)).is_some() {}
+    chars.next_if(|c| ('@'..='~').contains(c));
+}
+
+/// The rest of a control string, up to BEL or the string terminator
+fn skip_string(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\x07' | '\u{9c}' => break,
+            '\x1b' if chars.next_if_eq(&'\\').is_some() => break,
+            _ => {}
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    /// What ends up at a prompt: pasted escapes, odd unicode, control
+    /// characters and lines far past any limit
+    fn nasty_inputs() -> Vec<String> {
+        let mut inputs: Vec<String> = [
+            "echo héllo wörld 🎉",
+            "ls \u{202e}fdp.exe",
+            "echo \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}",
+            "\u{feff}npm test",
+            "ls \x1b[31mred\x1b[0m",
+            "\x1b[200~git status\x1b[201~",
+            "printf '\x1b]0;title\x07'",
+            "a\0b",
+            "\0",
+            "\0\0 \0",
+            "\t\n\r",
+            " \u{00a0} \u{3000}",
+            "cat <<EOF\nline\nEOF",
+            "rm -rf build\r",
+            "\x07\x08\x7f",
+            "curl -H 'Authorization: Bearer \u{0301}x'",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        inputs.push("x".repeat(64 * 1024));
+        inputs.push("é".repeat(32 * 1024));
+        inputs.push(format!("echo {}", "\0".repeat(MAX_COMMAND_LENGTH)));
+        inputs
+    }
 
     async fn create_test_recorder() -> Recorder {
         let db = Database::new_test().await.unwrap();
         Recorder::new(Arc::new(db))
     }
 
+    /// Control characters other than the newlines and tabs commands keep
+    fn is_stray_control(c: char) -> bool {
+        c.is_control() && c != '\n' && c != '\t'
+    }
+
+    #[tokio::test]
+    async fn test_record_multiline_command() {
+        let recorder = create_test_recorder().await;
+
+        let id = recorder
+            .record("cd build &&\r\n  make   all", "/test/project", None, None, None)
+            .await
+            .unwrap();
+
+        let stored = recorder.db.get_command_by_id(id).await.unwrap().unwrap();
+        assert_eq!(stored.command, "cd build &&\n make all");
+        let (stages,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM command_stages WHERE command_id = ?")
+                .bind(id)
+                .fetch_one(recorder.db.pool())
+                .await
+                .unwrap();
+        assert_eq!(stages, 2);
+    }
+
     #[tokio::test]
     async fn test_record_valid_command() {
         let recorder = create_test_recorder().await;
@@ -224,16 +567,28 @@ mod tests {
         }
     }
 
-    #[tokio::test]
-    async fn test_sanitize_command() {
-        let db = Database::new_test().await.unwrap();
-        let recorder = Recorder::new(Arc::new(db));
-
-        let sanitized = recorder.sanitize_command("  npm    test   ");
+    #[test]
+    fn test_sanitize_command() {
+        let sanitized = Recorder::sanitize_command("  npm    test   ");
         assert_eq!(sanitized, "npm test");
 
-        let sanitized = recorder.sanitize_command("cmd\0with\0nulls");
+        let sanitized = Recorder::sanitize_command("cmd\0with\0nulls");
         assert!(!sanitized.contains('\0'));
+
+        // Pasted from a terminal: colours, bracketed paste, a window title
+        let sanitized = Recorder::sanitize_command("\x1b[200~git \x1b[1;31mstatus\x1b[0m\x1b[201~");
+        assert_eq!(sanitized, "git status");
+        let sanitized = Recorder::sanitize_command("\x1b]0;build\x07make\x1b]8;;url\x1b\\ all");
+        assert_eq!(sanitized, "make all");
+        assert_eq!(Recorder::sanitize_command("\x1b(Bls\x1bc\u{9b}2J -la"), "ls -la");
+        assert_eq!(Recorder::sanitize_command("echo\ta\r\nb\x08\x7f"), "echo\ta\nb");
+
+        // Multi-line commands keep their lines
+        let heredoc = "cat <<EOF > notes.txt  \r\n\tfirst   line\r\nEOF\n";
+        assert_eq!(
+            Recorder::sanitize_command(heredoc),
+            "cat <<EOF > notes.txt\n\tfirst line\nEOF"
+        );
     }
 
     #[tokio::test]
@@ -246,6 +601,80 @@ mod tests {
         assert!(!recorder.should_ignore("npm test"));
     }
 
+    #[tokio::test]
+    async fn test_ignores_itself() {
+        let mut recorder = create_test_recorder().await;
+        recorder.self_prefixes.push("br".to_string());
+
+        // Picking, suggesting, recording by hand
+        assert!(recorder.should_ignore("berri-recall pick"));
+        assert!(recorder.should_ignore("berri-recall suggest --snooze 12 7d"));
+        assert!(recorder.should_ignore("berri-recall record npm test"));
+        assert!(recorder.should_ignore("/usr/local/bin/berri-recall recent"));
+        assert!(recorder.should_ignore("BERRI_RECALL_ETA=1 command berri-recall eta make"));
+        assert!(recorder.should_ignore("sudo berri-recall uninstall"));
+        assert!(recorder.should_ignore("br pick"));
+
+        // Only whole words count
+        assert!(!recorder.should_ignore("./berries.sh"));
+        assert!(!recorder.should_ignore("berri deploy"));
+        assert!(!recorder.should_ignore("brew install jq"));
+        assert!(!recorder.should_ignore("echo berri-recall"));
+
+        // Typed with a leading space, or matching the user's globs
+        assert!(recorder.should_ignore(" git push --force"));
+        recorder = recorder.with_ignore_rules(IgnoreRules::from_globs(["man *"]));
+        assert!(recorder.should_ignore("man tar"));
+        assert!(!recorder.should_ignore("git push --force"));
+
+        // A command run from the picker, or a shell it opened
+        recorder.internal = true;
+        assert!(recorder.should_ignore("npm test"));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_tallies() {
+        let db = Arc::new(Database::new_test().await.unwrap());
+        let recorder = Recorder::new(Arc::clone(&db))
+            .with_ignore_rules(IgnoreRules::from_globs(["man *"]));
+
+        assert_eq!(recorder.drop_reason("man tar"), Some(Dropped::IgnoreRule));
+        assert_eq!(recorder.drop_reason(" make"), Some(Dropped::LeadingSpace));
+        assert_eq!(recorder.drop_reason("pwd"), Some(Dropped::Noise));
+        assert_eq!(recorder.drop_reason("npm test"), None);
+        // berri-recall's own commands are left out without counting
+        assert_eq!(recorder.drop_reason("berri-recall recent"), None);
+
+        for command in ["man tar", "man ls", " make", "berri-recall recent", "npm test"] {
+            recorder.count_dropped(command).await.unwrap();
+        }
+        let _ = recorder.record("curl --token=abc", "/test", None, None, None).await;
+
+        let counts = db.dropped_counts().await.unwrap();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts["ignore_rule"], 2);
+        assert_eq!(counts["leading_space"], 1);
+        assert_eq!(counts["sensitive"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_record_with_loaded_config() {
+        let db = Arc::new(Database::new_test().await.unwrap());
+        db.set_preference("strip_sudo".to_string(), "true".to_string())
+            .await
+            .unwrap();
+        let recorder = Recorder::new(Arc::clone(&db))
+            .with_config(Config::load(&db).await.unwrap());
+
+        // The settings it was handed win over a change made since
+        db.set_preference("strip_sudo".to_string(), "false".to_string())
+            .await
+            .unwrap();
+        let id = recorder.record("sudo apt update", "/test", None, None, None).await.unwrap();
+        let command = db.get_command_by_id(id).await.unwrap().unwrap();
+        assert_eq!(command.command, "apt update");
+    }
+
     #[tokio::test]
     async fn test_contains_sensitive_data() {
         let db = Database::new_test().await.unwrap();
@@ -288,4 +717,71 @@ mod tests {
 
         assert!(id > 0);
     }
+
+    #[tokio::test]
+    async fn test_nasty_inputs() {
+        let recorder = create_test_recorder().await;
+        for input in nasty_inputs() {
+            let _ = recorder.should_ignore(&input);
+            let _ = Recorder::refuses(&input);
+            if let Ok(id) = recorder.record(&input, "/test", None, None, None).await {
+                let stored = recorder.db.get_command_by_id(id).await.unwrap().unwrap();
+                assert!(!stored.command.is_empty(), "{:?}", input);
+                assert!(!stored.command.chars().any(is_stray_control), "{:?}", input);
+                assert!(stored.command.len() <= MAX_COMMAND_LENGTH, "{:?}", input);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn sanitize_is_tidy(command in any::<String>()) {
+            let sanitized = Recorder::sanitize_command(&command);
+            prop_assert_eq!(sanitized.trim(), sanitized.as_str());
+            prop_assert!(!sanitized.contains("  "));
+            prop_assert!(sanitized.len() <= command.len());
+            // Running it again changes nothing
+            prop_assert_eq!(Recorder::sanitize_command(&sanitized), sanitized.clone());
+            prop_assert!(!sanitized.chars().any(is_stray_control));
+        }
+
+        // Without control characters, only the spacing changes
+        #[test]
+        fn sanitize_keeps_words(command in "[^\\p{Cc}]*") {
+            let words: Vec<&str> = command.split_whitespace().collect();
+            let sanitized = Recorder::sanitize_command(&command);
+            prop_assert_eq!(sanitized.split_whitespace().collect::<Vec<_>>(), words);
+        }
+
+        // ...and lines stay lines
+        #[test]
+        fn sanitize_keeps_lines(
+            lines in prop::collection::vec("[a-z]{1,8}( [a-z]{1,8}){0,3}", 1..5),
+        ) {
+            let command = lines.join("\n");
+            prop_assert_eq!(Recorder::sanitize_command(&command), command);
+        }
+
+        #[test]
+        fn refuses_never_panics(command in any::<String>()) {
+            let _ = Recorder::refuses(&command);
+        }
+
+        // A secret anywhere in the line, in any case, keeps the line out
+        #[test]
+        fn refuses_secrets(
+            before in "[a-z ./]{0,30}",
+            after in "[a-z ./]{0,30}",
+            flag in prop::sample::select(vec!["--password=", "--token ", "TOKEN=", "Api_Key="]),
+            secret in "[A-Za-z0-9]{1,24}",
+        ) {
+            let command = format!("{} {}{} {}", before, flag, secret, after);
+            prop_assert!(Recorder::refuses(&command));
+        }
+
+        #[test]
+        fn refuses_long_lines(extra in 1usize..64 * 1024) {
+            prop_assert!(Recorder::refuses(&"a".repeat(MAX_COMMAND_LENGTH + extra)));
+        }
+    }
 }
@@ -1,6 +1,6 @@
-/// Command retriever
-///
-/// Handles fetching commands from database with various filters.
+//! Command retriever
+//!
+//! Handles fetching commands from database with various filters.
 
 use crate::db::{Command, Database};
 use crate::error::Result;
@@ -50,7 +50,6 @@ impl Retriever {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::Recorder;
     use crate::db::CommandInput;
 
     async fn setup() -> (Retriever, Arc<Database>) {
@@ -94,6 +93,6 @@ mod tests {
             .unwrap();
 
         let is_fav = retriever.toggle_favorite(id).await.unwrap();
-        assert_eq!(is_fav, true);
+        assert!(is_fav);
     }
 }
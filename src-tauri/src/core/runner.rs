@@ -0,0 +1,126 @@
+//! Running a recorded command again
+//!
+//! Each command gets a fresh `$SHELL -c` (`cmd /C` on Windows) with the
+//! terminal attached, so prompts and colors work like they did the first
+//! time. A shell doesn't outlive its command, so `cd` is followed by the
+//! caller through [`cd_target`].
+
+use crate::core::recorder::INTERNAL_ENV;
+use crate::error::Result;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+/// How a run went, in the shape `record` takes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outcome {
+    pub exit_code: i32,
+    pub duration_ms: i32,
+}
+
+/// Run `command` in `cwd` and wait for it
+pub fn run(command: &str, cwd: &Path) -> Result<Outcome> {
+    let mut shell = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let program = std::env::var("SHELL")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "sh".to_string());
+        let mut sh = Command::new(program);
+        sh.arg("-c");
+        sh
+    };
+
+    let start = Instant::now();
+    // The caller records the command itself, anything nested shouldn't
+    let status = shell
+        .arg(command)
+        .current_dir(cwd)
+        .env(INTERNAL_ENV, "1")
+        .status()?;
+    let duration_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+    Ok(Outcome {
+        exit_code: exit_code(&status),
+        duration_ms,
+    })
+}
+
+/// Killed by a signal reads as 128 + signal, like the shell reports it
+fn exit_code(status: &std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Where a plain `cd` moves to, `None` for anything else
+///
+/// Only a lone `cd` counts: in `cd web && npm i` the directory change ends
+/// with that command's shell.
+pub fn cd_target(command: &str, cwd: &Path) -> Option<PathBuf> {
+    let command = command.trim();
+    if command.contains(['&', ';', '|', '\n', '$', '`']) {
+        return None;
+    }
+
+    let mut words = command.split_whitespace();
+    if words.next() != Some("cd") {
+        return None;
+    }
+    let target = words.next();
+    if words.next().is_some() {
+        return None;
+    }
+
+    let home = dirs::home_dir();
+    let target = match target.map(|t| t.trim_matches(|c| c == '"' || c == '\'')) {
+        None | Some("~") => home?,
+        Some(t) => match t.strip_prefix("~/") {
+            Some(rest) => home?.join(rest),
+            None => cwd.join(t),
+        },
+    };
+
+    target.is_dir().then_some(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cd_target() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("web")).unwrap();
+
+        assert_eq!(
+            cd_target("cd web", dir.path()),
+            Some(dir.path().join("web"))
+        );
+        assert_eq!(
+            cd_target("cd 'web'", dir.path()),
+            Some(dir.path().join("web"))
+        );
+        assert_eq!(cd_target("cd missing", dir.path()), None);
+        assert_eq!(cd_target("cd web && npm i", dir.path()), None);
+        assert_eq!(cd_target("cargo build", dir.path()), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let outcome = run("touch ran && exit 3", dir.path()).unwrap();
+
+        assert_eq!(outcome.exit_code, 3);
+        assert!(dir.path().join("ran").exists());
+    }
+}
@@ -0,0 +1,267 @@
+//! A second look before running a command again
+//!
+//! `replay` runs what was typed weeks ago, maybe from another directory or
+//! on another branch. Before it does, [`lint`] looks for the ways that goes
+//! badly: `rm` aimed at `/`, `~` or a variable that might be empty, deleting
+//! or overwriting with no `-i` to ask first, and force pushes to a protected
+//! branch. A command with warnings only runs once you confirm it again.
+//!
+//! Protected branches are `main`, `master`, git's `init.defaultBranch` and
+//! any `berri-recall.protectedBranch` in git config:
+//! `git config --add berri-recall.protectedBranch release`.
+
+use crate::core::pipeline::{self, Stage};
+use crate::core::ProjectDetector;
+use std::path::Path;
+use std::process::Command;
+
+/// Branches that are always protected
+const PROTECTED: &[&str] = &["main", "master"];
+
+/// Short flags that make `rm`, `mv` or `cp` ask first (or, for `-n`, never
+/// overwrite), alone or in a cluster like `-rI`
+const ASKING_FLAGS: &[char] = &['i', 'I', 'n'];
+
+/// What the repo a command runs in says about force pushes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Repo {
+    /// Checked out, for a push that doesn't name a branch
+    pub branch: Option<String>,
+    pub protected: Vec<String>,
+}
+
+impl Repo {
+    /// The repo containing `cwd`, with its git config read
+    pub fn detect(cwd: &Path) -> Self {
+        let mut protected: Vec<String> = PROTECTED.iter().map(|b| b.to_string()).collect();
+        // Keys come back lowercased, whatever case they were set in
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(cwd)
+            .args([
+                "config",
+                "--get-regexp",
+                r"^(init\.defaultbranch|berri-recall\.protectedbranch)$",
+            ])
+            .output();
+        if let Some(output) = output.ok().filter(|o| o.status.success()) {
+            for line in String::from_utf8_lossy(&output.stdout).lines() {
+                if let Some((_, branch)) = line.split_once(' ') {
+                    if !branch.is_empty() && !protected.iter().any(|b| b == branch) {
+                        protected.push(branch.to_string());
+                    }
+                }
+            }
+        }
+
+        Self {
+            branch: ProjectDetector::git_branch(cwd),
+            protected,
+        }
+    }
+}
+
+/// What might go wrong running `command`, one line each; empty when
+/// nothing looks risky
+pub fn lint(command: &str, repo: &Repo) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for stage in pipeline::stages(command) {
+        match stage.program.as_str() {
+            "rm" => lint_rm(&stage, &mut warnings),
+            "mv" | "cp" => lint_overwrite(&stage, &mut warnings),
+            "git" => lint_push(&stage, repo, &mut warnings),
+            _ => {}
+        }
+    }
+    warnings
+}
+
+fn lint_rm(stage: &Stage, warnings: &mut Vec<String>) {
+    let found = warnings.len();
+    for target in targets(stage) {
+        if let Some(variable) = empty_variable(&target) {
+            warnings.push(format!(
+                "`{}`: if ${} is empty or unset, rm starts from /",
+                target, variable
+            ));
+        } else if sweeping(&target) {
+            warnings.push(format!("`rm` aimed at `{}`", target));
+        }
+    }
+    if warnings.len() > found || asks(stage) {
+        return;
+    }
+
+    let recursive = stage.flags().iter().any(|flag| {
+        *flag == "--recursive" || (!flag.starts_with("--") && flag.contains(['r', 'R']))
+    });
+    if recursive {
+        warnings.push("`rm -r` deletes without asking: -I asks first".to_string());
+    } else if targets(stage).iter().any(|t| globbed(t)) {
+        warnings.push("`rm` on a glob deletes without asking: -i asks first".to_string());
+    }
+}
+
+fn lint_overwrite(stage: &Stage, warnings: &mut Vec<String>) {
+    if !asks(stage) && targets(stage).iter().any(|t| globbed(t)) {
+        warnings.push(format!(
+            "`{}` on a glob overwrites without asking: -i asks first",
+            stage.program
+        ));
+    }
+}
+
+fn lint_push(stage: &Stage, repo: &Repo, warnings: &mut Vec<String>) {
+    // Past global options, `-C dir` and `-c key=value` taking a value
+    let mut args = stage.args.iter();
+    let mut subcommand = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-C" | "-c" => {
+                args.next();
+            }
+            arg if arg.starts_with('-') => {}
+            arg => {
+                subcommand = Some(arg);
+                break;
+            }
+        }
+    }
+    if subcommand != Some("push") {
+        return;
+    }
+
+    let args: Vec<&str> = args.map(String::as_str).collect();
+    let force = args.iter().any(|arg| {
+        arg.starts_with("--force")
+            || (arg.starts_with('-') && !arg.starts_with("--") && arg.contains('f'))
+    });
+    let positional = args.iter().filter(|arg| !arg.starts_with('-')).skip(1);
+
+    let mut pushed: Vec<(String, bool)> = Vec::new();
+    for refspec in positional {
+        let (forced, refspec) = match refspec.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (force, *refspec),
+        };
+        let branch = refspec.rsplit(':').next().unwrap_or(refspec);
+        let branch = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+        let branch = match branch {
+            "HEAD" => repo.branch.clone(),
+            branch => Some(branch.to_string()),
+        };
+        pushed.extend(branch.map(|b| (b, forced)));
+    }
+    if pushed.is_empty() {
+        pushed.extend(repo.branch.clone().map(|b| (b, force)));
+    }
+
+    for (branch, forced) in pushed {
+        if forced && repo.protected.contains(&branch) {
+            warnings.push(format!("force-pushes to `{}`, a protected branch", branch));
+        }
+    }
+}
+
+/// The words after the flags, quotes dropped
+fn targets(stage: &Stage) -> Vec<String> {
+    let mut after_dashes = false;
+    let mut targets = Vec::new();
+    for arg in &stage.args {
+        if !after_dashes && arg == "--" {
+            after_dashes = true;
+        } else if after_dashes || !arg.starts_with('-') || arg == "-" {
+            targets.push(arg.replace(['"', '\''], ""));
+        }
+    }
+    targets
+}
+
+fn asks(stage: &Stage) -> bool {
+    stage.flags().iter().any(|flag| match flag.strip_prefix("--") {
+        Some(long) => matches!(long, "interactive" | "no-clobber"),
+        None => flag.contains(ASKING_FLAGS),
+    })
+}
+
+fn globbed(target: &str) -> bool {
+    target.contains(['*', '?'])
+}
+
+/// `/`, `~`, `.`, `*`, a top-level directory, or everything in one of them
+fn sweeping(target: &str) -> bool {
+    let base = target.trim_end_matches('*').trim_end_matches('/');
+    match base {
+        "" | "~" | "." | ".." | "$HOME" | "${HOME}" => true,
+        base => base
+            .strip_prefix('/')
+            .is_some_and(|rest| !rest.is_empty() && !rest.contains('/')),
+    }
+}
+
+/// The variable in `$DIR/...` or `${DIR}/...`, which is `/...` when it's
+/// empty; `${DIR:?}` fails instead, so it's left alone
+fn empty_variable(target: &str) -> Option<&str> {
+    let rest = target.strip_prefix('$')?;
+    let (name, rest) = match rest.strip_prefix('{') {
+        Some(braced) => braced.split_once('}')?,
+        None => rest.split_at(
+            rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len()),
+        ),
+    };
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    (valid && name != "HOME" && rest.starts_with('/')).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let repo = Repo {
+            branch: Some("main".to_string()),
+            protected: vec!["main".to_string(), "release".to_string()],
+        };
+        let warned = |command: &str| lint(command, &repo);
+
+        assert!(warned("rm -rf /")[0].contains("aimed at `/`"));
+        assert!(warned("sudo rm -rf ~/*")[0].contains("`~/*`"));
+        assert!(warned("cd build && rm -rf *").len() == 1);
+        assert!(warned("rm -rf /usr")[0].contains("`/usr`"));
+        assert!(warned("rm -rf \"$BUILD_DIR\"/*")[0].contains("$BUILD_DIR is empty"));
+        assert!(warned("rm -rf ${OUT}/cache")[0].contains("$OUT"));
+        assert_eq!(warned("rm -rf ${OUT:?}/cache").len(), 1);
+        assert!(warned("rm -r target")[0].contains("-I asks first"));
+        assert!(warned("rm *.log")[0].contains("on a glob"));
+        assert!(warned("rm -i *.log").is_empty());
+        assert!(warned("rm -rI target").is_empty());
+        assert!(warned("rm notes.txt").is_empty());
+        assert!(warned("rm -rf /usr/local/lib/foo").len() == 1);
+        assert!(warned("mv *.txt done/")[0].starts_with("`mv`"));
+        assert!(warned("cp -n *.txt done/").is_empty());
+
+        // Force pushes, named or not
+        assert_eq!(
+            warned("git push --force"),
+            vec!["force-pushes to `main`, a protected branch"]
+        );
+        assert_eq!(warned("git push -uf origin release").len(), 1);
+        assert_eq!(warned("git push origin +HEAD:refs/heads/release").len(), 1);
+        assert_eq!(
+            warned("git -C app push --force-with-lease origin main").len(),
+            1
+        );
+        assert!(warned("git push origin main").is_empty());
+        assert!(warned("git push -f origin feature").is_empty());
+        assert!(warned("git commit -m 'push -f main'").is_empty());
+
+        let feature = Repo {
+            branch: Some("feature".to_string()),
+            ..repo.clone()
+        };
+        assert!(lint("git push -f", &feature).is_empty());
+        assert!(lint("git push", &Repo::default()).is_empty());
+    }
+}
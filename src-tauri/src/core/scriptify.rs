@@ -0,0 +1,301 @@
+//! Turning a run of commands into something you can run again
+//!
+//! A detected sequence (or commands picked from history) becomes a bash
+//! script, a justfile recipe or a Makefile target. Arguments that obviously
+//! change from one run to the next (versions, commit hashes, ticket IDs,
+//! commit messages) become parameters instead of being baked in.
+
+use crate::error::{RecallError, Result};
+use regex::Regex;
+use std::str::FromStr;
+
+/// What to generate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptFormat {
+    Shell,
+    Just,
+    Make,
+}
+
+impl FromStr for ScriptFormat {
+    type Err = RecallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "sh" | "bash" | "shell" => Ok(ScriptFormat::Shell),
+            "just" | "justfile" => Ok(ScriptFormat::Just),
+            "make" | "makefile" => Ok(ScriptFormat::Make),
+            other => Err(RecallError::Config(format!(
+                "Unknown script format '{}' (expected sh, just or make)",
+                other
+            ))),
+        }
+    }
+}
+
+/// A value that changes between runs, pulled out as a parameter
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    /// What it was when the commands were recorded
+    pub example: String,
+}
+
+/// Commands ready to render, with their parameters
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub name: String,
+    /// Where the commands came from, for the header
+    pub provenance: String,
+    pub params: Vec<Param>,
+    /// With `{{NAME}}` where a parameter goes
+    lines: Vec<String>,
+}
+
+/// Kinds of values that change from run to run: (name, pattern)
+///
+/// The pattern's first group is the value; quoted messages keep their quotes.
+const VARIABLE_ARGS: &[(&str, &str)] = &[
+    // `-m`, and `-am` and friends
+    ("MESSAGE", r#"(?:\s-[a-z]*m|--message)\s+"([^"]+)""#),
+    ("MESSAGE", r"(?:\s-[a-z]*m|--message)\s+'([^']+)'"),
+    (
+        "VERSION",
+        r"(?:^|[\s=@:/])(v?\d+\.\d+(?:\.\d+)?(?:-[0-9A-Za-z.]+)?)(?:$|\s)",
+    ),
+    ("TICKET", r"(?:^|[\s/_-])([A-Z][A-Z0-9]+-\d+)\b"),
+    ("COMMIT", r"(?:^|\s)([0-9a-f]{7,40})(?:$|\s)"),
+];
+
+impl Script {
+    /// Build a script from commands in the order they should run
+    pub fn new(name: &str, provenance: &str, commands: &[String]) -> Self {
+        let patterns: Vec<(&str, Regex)> = VARIABLE_ARGS
+            .iter()
+            .map(|(name, pattern)| {
+                (
+                    *name,
+                    Regex::new(pattern).expect("argument pattern is valid"),
+                )
+            })
+            .collect();
+
+        let mut params: Vec<Param> = Vec::new();
+        let mut lines = Vec::new();
+
+        for command in commands {
+            let mut line = command.clone();
+            for (kind, regex) in &patterns {
+                // Replace from the back so earlier offsets stay valid
+                let found: Vec<(usize, usize, String)> = regex
+                    .captures_iter(&line)
+                    .filter_map(|c| c.get(1))
+                    // A hash needs a digit and a letter, or it's just a word or number
+                    .filter(|m| {
+                        *kind != "COMMIT"
+                            || (m.as_str().chars().any(|c| c.is_ascii_digit())
+                                && m.as_str().chars().any(|c| c.is_ascii_alphabetic()))
+                    })
+                    .map(|m| (m.start(), m.end(), m.as_str().to_string()))
+                    .collect();
+
+                for (start, end, value) in found.into_iter().rev() {
+                    let name = match params.iter().find(|p| p.example == value) {
+                        Some(param) => param.name.clone(),
+                        None => {
+                            let taken = params.iter().filter(|p| p.name.starts_with(kind)).count();
+                            let name = match taken {
+                                0 => kind.to_string(),
+                                n => format!("{}_{}", kind, n + 1),
+                            };
+                            params.push(Param {
+                                name: name.clone(),
+                                example: value,
+                            });
+                            name
+                        }
+                    };
+                    line.replace_range(start..end, &format!("{{{{{}}}}}", name));
+                }
+            }
+            lines.push(line);
+        }
+
+        Self {
+            name: name.to_string(),
+            provenance: provenance.to_string(),
+            params,
+            lines,
+        }
+    }
+
+    pub fn render(&self, format: ScriptFormat) -> String {
+        match format {
+            ScriptFormat::Shell => self.render_shell(),
+            ScriptFormat::Just => self.render_just(),
+            ScriptFormat::Make => self.render_make(),
+        }
+    }
+
+    fn header(&self, comment: &str) -> String {
+        let mut header = format!("{} {}\n", comment, self.provenance);
+        for param in &self.params {
+            header.push_str(&format!(
+                "{} {} was '{}' when recorded\n",
+                comment, param.name, param.example
+            ));
+        }
+        header
+    }
+
+    /// Lines with every `{{NAME}}` swapped for `reference(NAME)`
+    fn body(
+        &self,
+        escape: impl Fn(&str) -> String,
+        reference: impl Fn(&str) -> String,
+    ) -> Vec<String> {
+        self.lines
+            .iter()
+            .map(|line| {
+                let mut line = escape(line);
+                for param in &self.params {
+                    line = line.replace(
+                        &escape(&format!("{{{{{}}}}}", param.name)),
+                        &reference(&param.name),
+                    );
+                }
+                line
+            })
+            .collect()
+    }
+
+    fn render_shell(&self) -> String {
+        let mut script = String::from("#!/usr/bin/env bash\n");
+        script.push_str(&self.header("#"));
+        script.push_str("set -euo pipefail\n\n");
+
+        if !self.params.is_empty() {
+            let usage: Vec<&str> = self.params.iter().map(|p| p.name.as_str()).collect();
+            for (i, param) in self.params.iter().enumerate() {
+                script.push_str(&format!(
+                    "{}=\"${{{}:?usage: $0 {}}}\"\n",
+                    param.name,
+                    i + 1,
+                    usage.join(" ")
+                ));
+            }
+            script.push('\n');
+        }
+
+        for line in self.body(|l| l.to_string(), |name| format!("${{{}}}", name)) {
+            script.push_str(&line);
+            script.push('\n');
+        }
+        script
+    }
+
+    fn render_just(&self) -> String {
+        let mut recipe = self.header("#");
+        let params: Vec<&str> = self.params.iter().map(|p| p.name.as_str()).collect();
+        recipe.push_str(&format!(
+            "{}{}:\n",
+            self.name,
+            params.iter().map(|p| format!(" {}", p)).collect::<String>()
+        ));
+        // One shell for the whole recipe, so a `cd` carries over
+        recipe.push_str("    #!/usr/bin/env bash\n    set -euo pipefail\n");
+
+        let escape = |line: &str| line.replace("{{", "{{ \"{{\" }}");
+        for line in self.body(escape, |name| format!("{{{{{}}}}}", name)) {
+            recipe.push_str(&format!("    {}\n", line));
+        }
+        recipe
+    }
+
+    fn render_make(&self) -> String {
+        let mut makefile = self.header("#");
+        makefile.push_str("SHELL := bash\n.SHELLFLAGS := -euo pipefail -c\n.ONESHELL:\n\n");
+
+        makefile.push_str(&format!(".PHONY: {}\n{}:\n", self.name, self.name));
+        let usage: Vec<String> = self
+            .params
+            .iter()
+            .map(|p| format!("{}=...", p.name))
+            .collect();
+        for param in &self.params {
+            makefile.push_str(&format!(
+                "\t@test -n \"$({})\" || {{ echo 'usage: make {} {}'; exit 1; }}\n",
+                param.name,
+                self.name,
+                usage.join(" ")
+            ));
+        }
+
+        // Make expands `$`, the shell should get it as typed
+        for line in self.body(|l| l.replace('$', "$$"), |name| format!("$({})", name)) {
+            makefile.push_str(&format!("\t{}\n", line));
+        }
+        makefile
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release() -> Script {
+        let commands = vec![
+            "git checkout -b release/1.4.2".to_string(),
+            "cargo set-version 1.4.2".to_string(),
+            r#"git commit -am "Release 1.4.2 for ENG-318""#.to_string(),
+            "git cherry-pick 3f9c2ab".to_string(),
+            "echo $HOME".to_string(),
+        ];
+        Script::new(
+            "release",
+            "From pattern #7 (seen 5 times in /work/api)",
+            &commands,
+        )
+    }
+
+    #[test]
+    fn test_finds_variable_args() {
+        let script = release();
+        let names: Vec<(&str, &str)> = script
+            .params
+            .iter()
+            .map(|p| (p.name.as_str(), p.example.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("VERSION", "1.4.2"),
+                ("MESSAGE", "Release 1.4.2 for ENG-318"),
+                ("COMMIT", "3f9c2ab"),
+            ]
+        );
+        assert_eq!(script.lines[0], "git checkout -b release/{{VERSION}}");
+        assert_eq!(script.lines[2], r#"git commit -am "{{MESSAGE}}""#);
+    }
+
+    #[test]
+    fn test_render() {
+        let script = release();
+
+        let shell = script.render(ScriptFormat::Shell);
+        assert!(shell.starts_with("#!/usr/bin/env bash\n# From pattern #7"));
+        assert!(shell.contains("set -euo pipefail"));
+        assert!(shell.contains(r#"VERSION="${1:?usage: $0 VERSION MESSAGE COMMIT}""#));
+        assert!(shell.contains("cargo set-version ${VERSION}\n"));
+
+        let just = script.render(ScriptFormat::Just);
+        assert!(just.contains("release VERSION MESSAGE COMMIT:\n"));
+        assert!(just.contains("    git cherry-pick {{COMMIT}}\n"));
+
+        let make = script.render(ScriptFormat::Make);
+        assert!(make.contains("\tgit checkout -b release/$(VERSION)\n"));
+        assert!(make.contains("\techo $$HOME\n"));
+
+        assert!("zsh".parse::<ScriptFormat>().is_err());
+    }
+}
@@ -1,16 +1,22 @@
-/// Command searcher with fuzzy matching
-///
-/// Provides fuzzy search capabilities for finding commands.
+//! Command searcher with fuzzy matching
+//!
+//! Provides fuzzy search capabilities for finding commands.
 
 use crate::db::{Database, SearchResult};
 use crate::error::Result;
+#[cfg(feature = "fuzzy")]
 use fuzzy_matcher::skim::SkimMatcherV2;
+#[cfg(feature = "fuzzy")]
 use fuzzy_matcher::FuzzyMatcher;
 use std::sync::Arc;
 
+/// Score given to matches that only hit through the typo fallback
+const TYPO_MATCH_SCORE: f64 = 1.0;
+
 /// Handles command searching with fuzzy matching
 pub struct Searcher {
     db: Arc<Database>,
+    #[cfg(feature = "fuzzy")]
     matcher: SkimMatcherV2,
 }
 
@@ -19,6 +25,7 @@ impl Searcher {
     pub fn new(db: Arc<Database>) -> Self {
         Self {
             db,
+            #[cfg(feature = "fuzzy")]
             matcher: SkimMatcherV2::default(),
         }
     }
@@ -45,12 +52,15 @@ impl Searcher {
         let mut results: Vec<SearchResult> = commands
             .into_iter()
             .filter_map(|cmd| {
-                self.matcher
+                let score = self
                     .fuzzy_match(&cmd.command, query)
-                    .map(|score| SearchResult {
-                        command: cmd,
-                        score: score as f64,
-                    })
+                    .map(|score| score as f64)
+                    .or_else(|| Self::typo_match(&cmd.command, query).then_some(TYPO_MATCH_SCORE))?;
+
+                Some(SearchResult {
+                    command: cmd,
+                    score,
+                })
             })
             .collect();
 
@@ -63,6 +73,67 @@ impl Searcher {
         Ok(results)
     }
 
+    #[cfg(feature = "fuzzy")]
+    fn fuzzy_match(&self, command: &str, query: &str) -> Option<i64> {
+        self.matcher.fuzzy_match(command, query)
+    }
+
+    /// Case-insensitive substring match, earlier and tighter scoring higher
+    #[cfg(not(feature = "fuzzy"))]
+    fn fuzzy_match(&self, command: &str, query: &str) -> Option<i64> {
+        let command = command.to_lowercase();
+        let query = query.to_lowercase();
+        let start = command.find(&query)?;
+        // Never below the typo fallback
+        Some((1000 + query.len() as i64 * 10 - start as i64 - command.len() as i64).max(2))
+    }
+
+    /// Check if every query word is one typo away from a word in the command
+    ///
+    /// Fuzzy matching needs the letters in order, so swapped letters ("nmp")
+    /// never match. This catches those without matching everything.
+    fn typo_match(command: &str, query: &str) -> bool {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let mut query_words = query.split_whitespace().peekable();
+
+        if query_words.peek().is_none() {
+            return false;
+        }
+
+        query_words.all(|q| {
+            q.chars().count() >= 3 && words.iter().any(|w| Self::edit_distance(w, q) <= 1)
+        })
+    }
+
+    /// Optimal string alignment distance (Levenshtein plus adjacent swaps)
+    pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+
+        for (i, row) in d.iter_mut().enumerate() {
+            row[0] = i;
+        }
+        for (j, cell) in d[0].iter_mut().enumerate() {
+            *cell = j;
+        }
+
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let cost = usize::from(a[i - 1] != b[j - 1]);
+                d[i][j] = (d[i - 1][j] + 1)
+                    .min(d[i][j - 1] + 1)
+                    .min(d[i - 1][j - 1] + cost);
+
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+                }
+            }
+        }
+
+        d[a.len()][b.len()]
+    }
+
     /// Search by tags
     pub async fn search_by_tags(
         &self,
@@ -87,6 +158,51 @@ impl Searcher {
     }
 }
 
+/// Which characters of `command` (by char index) `query` matched, for
+/// highlighting
+///
+/// Wherever the whole query appears, that's what matched; otherwise the
+/// letters the fuzzy matcher picked out.
+pub fn match_positions(command: &str, query: &str) -> Vec<usize> {
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    // Lowercasing can change lengths outside ASCII, so compare char by char
+    let lower: Vec<char> = command
+        .chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect();
+
+    let mut positions = Vec::new();
+    let mut i = 0;
+    while i + query.len() <= lower.len() {
+        if lower[i..i + query.len()] == query[..] {
+            positions.extend(i..i + query.len());
+            i += query.len();
+        } else {
+            i += 1;
+        }
+    }
+    if positions.is_empty() {
+        positions = fuzzy_positions(command, &query.iter().collect::<String>());
+    }
+    positions
+}
+
+#[cfg(feature = "fuzzy")]
+fn fuzzy_positions(command: &str, query: &str) -> Vec<usize> {
+    SkimMatcherV2::default()
+        .fuzzy_indices(command, query)
+        .map(|(_, indices)| indices)
+        .unwrap_or_default()
+}
+
+#[cfg(not(feature = "fuzzy"))]
+fn fuzzy_positions(_command: &str, _query: &str) -> Vec<usize> {
+    Vec::new()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,4 +246,19 @@ mod tests {
         let results = searcher.search("nmp", Some("/test"), 10).await.unwrap();
         assert!(!results.is_empty());
     }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(Searcher::edit_distance("npm", "nmp"), 1);
+        assert_eq!(Searcher::edit_distance("cargo", "cargo"), 0);
+        assert_eq!(Searcher::edit_distance("git", "docker"), 6);
+    }
+
+    #[test]
+    fn test_match_positions() {
+        assert_eq!(match_positions("npm test && NPM run", "npm"), vec![0, 1, 2, 12, 13, 14]);
+        assert!(match_positions("cargo build", "").is_empty());
+        #[cfg(feature = "fuzzy")]
+        assert_eq!(match_positions("cargo build", "cbd"), vec![0, 6, 10]);
+    }
 }
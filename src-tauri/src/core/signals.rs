@@ -0,0 +1,102 @@
+//! Signals for the commands that keep running
+//!
+//! `mirror --watch` runs until it's told to stop, usually from a service
+//! manager. It checks for signals between reads, so a command is never cut
+//! off halfway through being recorded:
+//!
+//! - SIGTERM, SIGINT (Ctrl-C): write out the spool, close the database, exit
+//! - SIGHUP: read the settings and ignore rules again
+//! - SIGUSR1: print how long it's been up and what it's done
+//!
+//! `lsp-like` closes the database on SIGTERM and SIGINT too, and says how
+//! long it's been up on SIGUSR1. Only Ctrl-C exists on Windows.
+
+use crate::error::Result;
+
+/// What a signal asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// SIGTERM or SIGINT
+    Stop,
+    /// SIGHUP
+    Reload,
+    /// SIGUSR1
+    Report,
+}
+
+/// The signals above, caught from when this is created
+///
+/// From then on they no longer do what they would by default (a SIGHUP
+/// doesn't end the process, even once this is dropped), so only create it
+/// where they're handled.
+pub struct Signals {
+    #[cfg(unix)]
+    term: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    int: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    hup: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    usr1: tokio::signal::unix::Signal,
+}
+
+impl Signals {
+    /// Start catching them
+    pub fn new() -> Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            Ok(Self {
+                term: signal(SignalKind::terminate())?,
+                int: signal(SignalKind::interrupt())?,
+                hup: signal(SignalKind::hangup())?,
+                usr1: signal(SignalKind::user_defined1())?,
+            })
+        }
+
+        #[cfg(not(unix))]
+        Ok(Self {})
+    }
+
+    /// The next signal to arrive
+    pub async fn recv(&mut self) -> Signal {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = self.term.recv() => Signal::Stop,
+                _ = self.int.recv() => Signal::Stop,
+                _ = self.hup.recv() => Signal::Reload,
+                _ = self.usr1.recv() => Signal::Report,
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+            Signal::Stop
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_recv() {
+        let mut signals = Signals::new().unwrap();
+        // Caught from here on, so sending them to ourselves is safe
+        for (name, expected) in [("-HUP", Signal::Reload), ("-USR1", Signal::Report)] {
+            let status = std::process::Command::new("kill")
+                .args([name, &std::process::id().to_string()])
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let received = tokio::time::timeout(std::time::Duration::from_secs(5), signals.recv())
+                .await
+                .unwrap();
+            assert_eq!(received, expected);
+        }
+    }
+}
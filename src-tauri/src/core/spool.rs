@@ -0,0 +1,249 @@
+//! Spool for commands that couldn't be recorded right away
+//!
+//! Hooks record in the background and never wait. When the database can't
+//! take a write (another shell holds the lock, the file is being restored),
+//! the command goes into `~/.berri-recall/spool/` as one small JSON file and
+//! the next successful `record` replays it with its original time.
+//!
+//! `record --fast` (or every `record`, with `BERRI_RECALL_FAST_RECORD=1`)
+//! puts every command here and nothing else: no async runtime, no database.
+//! Whatever opens the database next records them.
+//!
+//! No locks anywhere: every entry is its own file written under a temporary
+//! name and renamed into place, and an entry is claimed for replay by renaming
+//! it, which only one process can win.
+
+use crate::core::Recorder;
+use crate::db::ExecutionMeta;
+use crate::error::{RecallError, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Set to `1` to make every `record` a `record --fast`
+pub const FAST_RECORD_ENV: &str = "BERRI_RECALL_FAST_RECORD";
+
+/// Whether `FAST_RECORD_ENV` asks for it
+pub fn fast_record() -> bool {
+    std::env::var(FAST_RECORD_ENV).is_ok_and(|value| value == "1")
+}
+
+/// One command waiting to be recorded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpooledCommand {
+    pub command: String,
+    pub project_path: String,
+    pub execution_time_ms: Option<i32>,
+    pub exit_code: Option<i32>,
+    pub session_id: Option<String>,
+    pub hostname: Option<String>,
+    pub cwd: Option<String>,
+    /// `YYYY-MM-DD HH:MM:SS`, UTC, same as the database
+    pub executed_at: String,
+    #[serde(default)]
+    pub env: Option<String>,
+    #[serde(default)]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub interactive: Option<bool>,
+}
+
+impl SpooledCommand {
+    /// A command that ran just now
+    pub fn new(
+        command: &str,
+        project_path: &str,
+        execution_time_ms: Option<i32>,
+        exit_code: Option<i32>,
+        meta: &ExecutionMeta,
+    ) -> Self {
+        Self {
+            command: command.to_string(),
+            project_path: project_path.to_string(),
+            execution_time_ms,
+            exit_code,
+            session_id: meta.session_id.clone(),
+            hostname: meta.hostname.clone(),
+            cwd: meta.cwd.clone(),
+            executed_at: Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            env: meta.env.clone(),
+            branch: meta.branch.clone(),
+            interactive: meta.interactive,
+        }
+    }
+
+    fn meta(&self) -> ExecutionMeta {
+        ExecutionMeta {
+            session_id: self.session_id.clone(),
+            hostname: self.hostname.clone(),
+            cwd: self.cwd.clone(),
+            executed_at: Some(self.executed_at.clone()),
+            env: self.env.clone(),
+            branch: self.branch.clone(),
+            interactive: self.interactive,
+        }
+    }
+}
+
+pub struct Spool {
+    dir: PathBuf,
+}
+
+impl Spool {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// `~/.berri-recall/spool`
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".berri-recall").join("spool"))
+    }
+
+    /// Queue a command for later
+    pub fn push(&self, entry: &SpooledCommand) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        // Nanoseconds sort entries by when they ran, the pid keeps two shells
+        // finishing in the same instant apart
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let name = format!("{:020}-{}", nanos, std::process::id());
+
+        let tmp = self.dir.join(format!("{}.tmp", name));
+        let path = self.dir.join(format!("{}.json", name));
+        std::fs::write(&tmp, serde_json::to_vec(entry)?)?;
+        std::fs::rename(&tmp, &path)?;
+
+        Ok(path)
+    }
+
+    /// Entries waiting to be replayed, oldest first
+    pub fn pending(&self) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        let mut pending: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        pending.sort();
+        pending
+    }
+
+    /// Record every pending entry through `recorder`
+    ///
+    /// Entries the recorder refuses (sensitive, too long) are dropped, an
+    /// entry that fails to write is put back for next time.
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - How many were recorded
+    pub async fn drain(&self, recorder: &Recorder) -> Result<usize> {
+        let mut recorded = 0;
+
+        for path in self.pending() {
+            // Whoever renames it first replays it
+            let claimed = path.with_extension(format!("claimed-{}", std::process::id()));
+            if std::fs::rename(&path, &claimed).is_err() {
+                continue;
+            }
+
+            let entry: Option<SpooledCommand> = std::fs::read(&claimed)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+            let Some(entry) = entry else {
+                // Half a file from a crash, nothing to save
+                let _ = std::fs::remove_file(&claimed);
+                continue;
+            };
+
+            if recorder.should_ignore(&entry.command) {
+                let _ = std::fs::remove_file(&claimed);
+                continue;
+            }
+
+            match recorder
+                .record_with_meta(
+                    &entry.command,
+                    &entry.project_path,
+                    entry.execution_time_ms,
+                    entry.exit_code,
+                    None,
+                    &entry.meta(),
+                )
+                .await
+            {
+                Ok(_) => {
+                    recorded += 1;
+                    let _ = std::fs::remove_file(&claimed);
+                }
+                Err(e @ RecallError::Database(_)) => {
+                    let _ = std::fs::rename(&claimed, &path);
+                    return Err(e);
+                }
+                Err(_) => {
+                    let _ = std::fs::remove_file(&claimed);
+                }
+            }
+        }
+
+        Ok(recorded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_push_and_drain() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::new(dir.path());
+        let meta = ExecutionMeta {
+            session_id: Some("1700000000-41".to_string()),
+            ..Default::default()
+        };
+
+        let mut first = SpooledCommand::new("cargo build", "/work/api", Some(900), Some(0), &meta);
+        first.executed_at = "2024-05-02 14:00:00".to_string();
+        spool.push(&first).unwrap();
+        spool
+            .push(&SpooledCommand::new(
+                "cargo test",
+                "/work/api",
+                None,
+                Some(1),
+                &meta,
+            ))
+            .unwrap();
+        spool
+            .push(&SpooledCommand::new(
+                "mysql --password=hunter2",
+                "/work/api",
+                None,
+                None,
+                &meta,
+            ))
+            .unwrap();
+        assert_eq!(spool.pending().len(), 3);
+
+        let db = Arc::new(Database::new_test().await.unwrap());
+        let recorder = Recorder::new(Arc::clone(&db));
+        assert_eq!(spool.drain(&recorder).await.unwrap(), 2);
+        assert!(spool.pending().is_empty());
+
+        let runs = db.get_session_executions("1700000000-41").await.unwrap();
+        let commands: Vec<&str> = runs.iter().map(|e| e.command.as_str()).collect();
+        assert_eq!(commands, vec!["cargo build", "cargo test"]);
+        // Replayed with the time it ran, not the time it was drained
+        assert_eq!(runs[0].executed_at, "2024-05-02 14:00:00");
+    }
+}
@@ -0,0 +1,79 @@
+//! Privacy sweep
+//!
+//! `sweep` walks history one entry at a time (keep, redact, edit or delete),
+//! the review to do before syncing or sharing it. With `--risky` it only
+//! walks entries that score here, worst first.
+
+use crate::core::Redactor;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Why an entry might not be fit to share
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Risk {
+    pub score: u32,
+    pub reasons: Vec<&'static str>,
+}
+
+// (pattern, weight, reason), checked on top of the redactor's own rules
+const SIGNALS: &[(&str, u32, &str)] = &[
+    // Long runs of base64/hex: keys, tokens, hashes of something private
+    (r"[A-Za-z0-9+/_=-]{32,}", 2, "long token"),
+    (r"[\w.+-]+@[\w-]+\.[\w.]+", 1, "email address"),
+    (r"\b\d{1,3}(?:\.\d{1,3}){3}\b", 1, "IP address"),
+    (r"(?:^|\s)(?:ssh|scp|sftp|rsync)\s", 1, "remote host"),
+    (r"(?:^|\s)export\s+\w+=", 1, "sets a variable"),
+    (r"(?i)\b(?:prod|production)\b", 1, "mentions production"),
+];
+
+/// Score a command, higher is riskier
+///
+/// Anything the redactor would mask counts most.
+pub fn assess(command: &str, redactor: &Redactor) -> Risk {
+    static COMPILED: OnceLock<Vec<(Regex, u32, &'static str)>> = OnceLock::new();
+    let signals = COMPILED.get_or_init(|| {
+        SIGNALS
+            .iter()
+            .filter_map(|(pattern, weight, reason)| {
+                Regex::new(pattern)
+                    .ok()
+                    .map(|regex| (regex, *weight, *reason))
+            })
+            .collect()
+    });
+
+    let mut risk = Risk::default();
+    if redactor.redact(command) != command {
+        risk.score += 3;
+        risk.reasons.push("looks like a secret");
+    }
+    for (regex, weight, reason) in signals {
+        if regex.is_match(command) {
+            risk.score += weight;
+            risk.reasons.push(reason);
+        }
+    }
+    risk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assess() {
+        let redactor = Redactor::new();
+
+        let risk = assess("mysql -h 10.0.0.5 --password=hunter2", &redactor);
+        assert_eq!(risk.score, 4);
+        assert_eq!(risk.reasons, vec!["looks like a secret", "IP address"]);
+
+        let risk = assess("ssh deploy@prod.example.com", &redactor);
+        assert_eq!(
+            risk.reasons,
+            vec!["email address", "remote host", "mentions production"]
+        );
+
+        assert_eq!(assess("cargo test -p core", &redactor), Risk::default());
+    }
+}
@@ -0,0 +1,175 @@
+//! Task runner targets a project defines
+//!
+//! `hello` lists them for a project you haven't run anything in yet: npm
+//! scripts, Makefile targets, justfile recipes and cargo aliases, each as the
+//! command that runs it.
+
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// One runnable target
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    /// `npm`, `make`, `just` or `cargo`
+    pub runner: &'static str,
+    pub name: String,
+    /// What to type to run it
+    pub command: String,
+}
+
+/// Every target defined in `dir`, grouped by runner
+pub fn detect(dir: &Path) -> Vec<Task> {
+    let mut tasks = npm_scripts(dir);
+    tasks.extend(make_targets(dir));
+    tasks.extend(just_recipes(dir));
+    tasks.extend(cargo_aliases(dir));
+    tasks
+}
+
+fn task(runner: &'static str, name: &str, command: String) -> Task {
+    Task {
+        runner,
+        name: name.to_string(),
+        command,
+    }
+}
+
+/// `scripts` in package.json, run with whichever package manager has a lockfile
+fn npm_scripts(dir: &Path) -> Vec<Task> {
+    let Ok(text) = std::fs::read_to_string(dir.join("package.json")) else {
+        return Vec::new();
+    };
+    let Ok(package) = serde_json::from_str::<serde_json::Value>(&text) else {
+        return Vec::new();
+    };
+    let Some(scripts) = package.get("scripts").and_then(|s| s.as_object()) else {
+        return Vec::new();
+    };
+
+    let run = if dir.join("pnpm-lock.yaml").exists() {
+        "pnpm"
+    } else if dir.join("yarn.lock").exists() {
+        "yarn"
+    } else {
+        "npm run"
+    };
+    scripts
+        .keys()
+        .map(|name| task("npm", name, format!("{} {}", run, name)))
+        .collect()
+}
+
+/// Explicit targets in the Makefile, not patterns or special targets
+fn make_targets(dir: &Path) -> Vec<Task> {
+    static TARGET: OnceLock<Regex> = OnceLock::new();
+    let target = TARGET.get_or_init(|| {
+        Regex::new(r"^([A-Za-z0-9][A-Za-z0-9_./-]*)\s*:([^=]|$)").expect("valid regex")
+    });
+
+    let Some(text) = ["GNUmakefile", "makefile", "Makefile"]
+        .iter()
+        .find_map(|name| std::fs::read_to_string(dir.join(name)).ok())
+    else {
+        return Vec::new();
+    };
+
+    let mut tasks: Vec<Task> = Vec::new();
+    for line in text.lines() {
+        if let Some(name) = target.captures(line).map(|c| c[1].to_string()) {
+            if !tasks.iter().any(|t| t.name == name) {
+                tasks.push(task("make", &name, format!("make {}", name)));
+            }
+        }
+    }
+    tasks
+}
+
+/// Recipes in the justfile, skipping `_private` ones
+fn just_recipes(dir: &Path) -> Vec<Task> {
+    static RECIPE: OnceLock<Regex> = OnceLock::new();
+    let recipe = RECIPE.get_or_init(|| {
+        Regex::new(r"^@?([A-Za-z][A-Za-z0-9_-]*)(\s+[^:=]*)?:([^=]|$)").expect("valid regex")
+    });
+
+    let Some(text) = ["justfile", "Justfile", ".justfile"]
+        .iter()
+        .find_map(|name| std::fs::read_to_string(dir.join(name)).ok())
+    else {
+        return Vec::new();
+    };
+
+    text.lines()
+        .filter_map(|line| recipe.captures(line).map(|c| c[1].to_string()))
+        .map(|name| task("just", &name, format!("just {}", name)))
+        .collect()
+}
+
+/// `[alias]` entries in .cargo/config.toml
+fn cargo_aliases(dir: &Path) -> Vec<Task> {
+    let Some(text) = ["config.toml", "config"]
+        .iter()
+        .find_map(|name| std::fs::read_to_string(dir.join(".cargo").join(name)).ok())
+    else {
+        return Vec::new();
+    };
+    let Ok(config) = text.parse::<toml_edit::DocumentMut>() else {
+        return Vec::new();
+    };
+    let Some(aliases) = config.get("alias").and_then(|a| a.as_table_like()) else {
+        return Vec::new();
+    };
+
+    aliases
+        .iter()
+        .map(|(name, _)| task("cargo", name, format!("cargo {}", name)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_detect() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{"scripts": {"dev": "vite", "test": "vitest"}}"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("yarn.lock"), "").unwrap();
+        fs::write(
+            dir.path().join("Makefile"),
+            "VERSION := 1.0\n.PHONY: build\nbuild: deps\n\tcargo build\n%.o: %.c\n\
+             deps:\n\techo\nbuild:\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("justfile"),
+            "set shell := [\"bash\"]\n@release version:\n  echo\n_helper:\n  echo\n",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join(".cargo")).unwrap();
+        fs::write(
+            dir.path().join(".cargo/config.toml"),
+            "[alias]\nxtask = \"run -p xtask --\"\n",
+        )
+        .unwrap();
+
+        let commands: Vec<String> = detect(dir.path()).into_iter().map(|t| t.command).collect();
+        assert_eq!(
+            commands,
+            vec![
+                "yarn dev",
+                "yarn test",
+                "make build",
+                "make deps",
+                "just release",
+                "cargo xtask",
+            ]
+        );
+        assert!(detect(&dir.path().join("missing")).is_empty());
+    }
+}
@@ -0,0 +1,320 @@
+//! Timeline export
+//!
+//! Everything that ran in a time window, oldest first, with where it ran,
+//! how it ended and how long it took. Meant for incident reviews, so the
+//! markdown output can be pasted straight into a postmortem.
+
+use crate::db::{parse_timestamp, Execution};
+use crate::error::{RecallError, Result};
+use crate::intelligence::performance::format_duration;
+use crate::ui::{self, Glyph};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use std::str::FromStr;
+
+/// Timeline output formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimelineFormat {
+    Text,
+    Markdown,
+    Json,
+}
+
+impl FromStr for TimelineFormat {
+    type Err = RecallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" | "txt" => Ok(TimelineFormat::Text),
+            "md" | "markdown" => Ok(TimelineFormat::Markdown),
+            "json" => Ok(TimelineFormat::Json),
+            other => Err(RecallError::Config(format!(
+                "Unknown timeline format '{}' (expected text, md or json)",
+                other
+            ))),
+        }
+    }
+}
+
+/// One run plus whatever is worth pointing out about it
+#[derive(Debug, Clone)]
+pub struct TimelineEntry {
+    pub execution: Execution,
+    /// "failed", "destructive: ..." and the like
+    pub notes: Vec<String>,
+}
+
+/// Parse a local time given on the command line
+///
+/// Takes `2024-05-02 14:00`, `2024-05-02T14:00:30`, `2024-05-02` or just
+/// `16:00` (on `default_date`). A bare date means midnight, or the end of
+/// that day when `end_of_day` is set so `--to 2024-05-02` includes all of it.
+pub fn parse_local_time(
+    input: &str,
+    default_date: NaiveDate,
+    end_of_day: bool,
+) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+
+    let naive = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(input, "%Y-%m-%d")
+            .ok()
+            .map(|date| {
+                if end_of_day {
+                    date.and_hms_opt(23, 59, 59).unwrap()
+                } else {
+                    date.and_hms_opt(0, 0, 0).unwrap()
+                }
+            })
+    })
+    .or_else(|| {
+        ["%H:%M:%S", "%H:%M"]
+            .iter()
+            .find_map(|format| NaiveTime::parse_from_str(input, format).ok())
+            .map(|time| default_date.and_time(time))
+    })?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Render a timeline
+///
+/// # Arguments
+/// * `format` - Output format
+/// * `from`, `to` - The window, used for the heading
+/// * `entries` - Runs, oldest first
+pub fn render(
+    format: TimelineFormat,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    entries: &[TimelineEntry],
+) -> String {
+    match format {
+        TimelineFormat::Json => render_json(entries),
+        TimelineFormat::Text | TimelineFormat::Markdown => {
+            let from = from.with_timezone(&Local);
+            let to = to.with_timezone(&Local);
+            // Within one day the date only needs saying once, in the heading
+            let one_day = from.date_naive() == to.date_naive();
+            let time_format = if one_day {
+                "%H:%M:%S"
+            } else {
+                "%m-%d %H:%M:%S"
+            };
+            let heading = format!(
+                "Timeline {} {} {}",
+                from.format("%Y-%m-%d %H:%M"),
+                ui::glyph(Glyph::Arrow),
+                if one_day {
+                    to.format("%H:%M").to_string()
+                } else {
+                    to.format("%Y-%m-%d %H:%M").to_string()
+                }
+            );
+
+            if format == TimelineFormat::Markdown {
+                render_markdown(&heading, time_format, entries)
+            } else {
+                render_text(&heading, time_format, entries)
+            }
+        }
+    }
+}
+
+fn local(executed_at: &str, format: &str) -> String {
+    parse_timestamp(executed_at)
+        .map(|t| t.with_timezone(&Local).format(format).to_string())
+        .unwrap_or(executed_at.to_string())
+}
+
+fn directory(execution: &Execution) -> &str {
+    execution.cwd.as_deref().unwrap_or(&execution.project_path)
+}
+
+fn duration(execution: &Execution) -> String {
+    execution
+        .execution_time_ms
+        .map(|ms| format_duration(ms.max(0) as u64))
+        .unwrap_or_default()
+}
+
+fn render_text(heading: &str, time_format: &str, entries: &[TimelineEntry]) -> String {
+    let mut out = format!(
+        "{}, {} commands\n{}\n",
+        heading,
+        entries.len(),
+        "=".repeat(60)
+    );
+
+    let mut current_dir: Option<&str> = None;
+    for entry in entries {
+        let execution = &entry.execution;
+        if current_dir != Some(directory(execution)) {
+            out.push_str(&format!("  in {}\n", directory(execution)));
+            current_dir = Some(directory(execution));
+        }
+
+        let status = match execution.exit_code {
+            Some(0) => ui::glyph(Glyph::Ok).to_string(),
+            Some(code) => format!("{} {}", ui::glyph(Glyph::Fail), code),
+            None => " ".to_string(),
+        };
+        let took = duration(execution);
+
+        out.push_str(&format!(
+            "{}  {:<5} {}",
+            local(&execution.executed_at, time_format),
+            status,
+            execution.command
+        ));
+        if !took.is_empty() {
+            out.push_str(&format!("  ({})", took));
+        }
+        for note in &entry.notes {
+            out.push_str(&format!("  [{}]", note));
+        }
+        out.push('\n');
+    }
+
+    out.push_str(&"=".repeat(60));
+    out.push('\n');
+    out
+}
+
+fn render_markdown(heading: &str, time_format: &str, entries: &[TimelineEntry]) -> String {
+    // Pipes would end the cell early
+    let cell = |value: &str| value.replace('|', "\\|");
+
+    let mut out = format!("## {}\n\n", heading);
+    out.push_str("| Time | Command | Directory | Exit | Duration | Notes |\n");
+    out.push_str("|------|---------|-----------|------|----------|-------|\n");
+
+    for entry in entries {
+        let execution = &entry.execution;
+        out.push_str(&format!(
+            "| {} | `{}` | {} | {} | {} | {} |\n",
+            local(&execution.executed_at, time_format),
+            cell(&execution.command.replace('`', "'")),
+            cell(directory(execution)),
+            execution
+                .exit_code
+                .map(|c| c.to_string())
+                .unwrap_or_default(),
+            duration(execution),
+            cell(&entry.notes.join(", "))
+        ));
+    }
+
+    out
+}
+
+fn render_json(entries: &[TimelineEntry]) -> String {
+    let rows: Vec<serde_json::Value> = entries
+        .iter()
+        .map(|entry| {
+            let execution = &entry.execution;
+            serde_json::json!({
+                "executed_at": parse_timestamp(&execution.executed_at)
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or(execution.executed_at.clone()),
+                "command": execution.command,
+                "cwd": directory(execution),
+                "project": execution.project_path,
+                "exit_code": execution.exit_code,
+                "duration_ms": execution.execution_time_ms,
+                "session_id": execution.session_id,
+                "hostname": execution.hostname,
+                "notes": entry.notes,
+            })
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&rows).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, exit_code: i32, notes: &[&str]) -> TimelineEntry {
+        TimelineEntry {
+            execution: Execution {
+                id: 1,
+                command_id: 1,
+                project_path: "/work/api".to_string(),
+                command: command.to_string(),
+                executed_at: "2024-05-02 14:05:00".to_string(),
+                execution_time_ms: Some(1500),
+                exit_code: Some(exit_code),
+                session_id: None,
+                hostname: None,
+                cwd: Some("/work/api/deploy".to_string()),
+                env: None,
+                branch: None,
+                interactive: None,
+                privileged: None,
+                rapid_repeat_count: None,
+            },
+            notes: notes.iter().map(|n| n.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_local_time() {
+        let day = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let at = |input: &str, end: bool| {
+            parse_local_time(input, day, end).map(|t| t.with_timezone(&Local).naive_local())
+        };
+
+        assert_eq!(
+            at("2024-05-02 14:00", false),
+            Some(day.and_hms_opt(14, 0, 0).unwrap())
+        );
+        assert_eq!(at("16:00", false), Some(day.and_hms_opt(16, 0, 0).unwrap()));
+        assert_eq!(
+            at("2024-05-02", true),
+            Some(day.and_hms_opt(23, 59, 59).unwrap())
+        );
+        assert_eq!(
+            at("2024-05-02", false),
+            Some(day.and_hms_opt(0, 0, 0).unwrap())
+        );
+        assert!(at("yesterday-ish", false).is_none());
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let from = parse_local_time("2024-05-02 14:00", NaiveDate::MIN, false).unwrap();
+        let to = parse_local_time("2024-05-02 16:00", NaiveDate::MIN, false).unwrap();
+        let entries = vec![
+            entry("kubectl get pods | grep api", 0, &[]),
+            entry(
+                "kubectl delete pod api-1",
+                1,
+                &["failed", "destructive: deletes resources"],
+            ),
+        ];
+
+        let md = render(TimelineFormat::Markdown, from, to, &entries);
+        assert!(md.starts_with("## Timeline 2024-05-02 14:00 → 16:00"));
+        assert!(md.contains("`kubectl get pods \\| grep api`"));
+        assert!(
+            md.contains("| /work/api/deploy | 1 | 1.5s | failed, destructive: deletes resources |")
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(TimelineFormat::Json, from, to, &entries)).unwrap();
+        assert_eq!(json[1]["notes"][0], "failed");
+        assert_eq!(json[0]["cwd"], "/work/api/deploy");
+    }
+}
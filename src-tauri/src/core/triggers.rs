@@ -0,0 +1,344 @@
+//! Event triggers
+//!
+//! Run a script or POST a webhook when something happens: a command gets
+//! recorded, a destructive command runs, a new pattern shows up, or a
+//! suggestion gets taken. Triggers are stored as preferences
+//! (`trigger.<event>:<script or url>`) and fire in the background so they
+//! never slow the shell down.
+
+use crate::core::recorder::INTERNAL_ENV;
+use crate::db::Database;
+use crate::error::{RecallError, Result};
+use regex::Regex;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// Preference key prefix for triggers
+pub const TRIGGER_PREFIX: &str = "trigger.";
+
+/// Seconds a webhook gets before curl gives up
+const WEBHOOK_TIMEOUT_SECS: &str = "5";
+
+/// Things a trigger can fire on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    CommandRecorded,
+    DangerousCommand,
+    PatternFound,
+    SuggestionAccepted,
+}
+
+impl Event {
+    pub const ALL: [Event; 4] = [
+        Event::CommandRecorded,
+        Event::DangerousCommand,
+        Event::PatternFound,
+        Event::SuggestionAccepted,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Event::CommandRecorded => "command_recorded",
+            Event::DangerousCommand => "dangerous_command",
+            Event::PatternFound => "pattern_found",
+            Event::SuggestionAccepted => "suggestion_accepted",
+        }
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Event {
+    type Err = RecallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let normalized = s.trim().to_lowercase().replace('-', "_");
+        Event::ALL
+            .into_iter()
+            .find(|e| e.as_str() == normalized)
+            .ok_or_else(|| {
+                RecallError::Config(format!(
+                    "Unknown event '{}' (expected one of: {})",
+                    s,
+                    Event::ALL.map(|e| e.as_str()).join(", ")
+                ))
+            })
+    }
+}
+
+/// A script or webhook attached to an event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub event: Event,
+    /// Shell command, or an http(s) URL to POST to
+    pub target: String,
+    /// Payload with `{{field}}` placeholders (None for the default JSON object)
+    pub template: Option<String>,
+    /// Only fire when the command matches this regex
+    pub matches: Option<String>,
+}
+
+impl Trigger {
+    pub fn is_webhook(&self) -> bool {
+        self.target.starts_with("http://") || self.target.starts_with("https://")
+    }
+
+    fn key(&self) -> String {
+        format!("{}{}:{}", TRIGGER_PREFIX, self.event, self.target)
+    }
+
+    /// Whether this trigger cares about an event for `command`
+    fn wants(&self, event: Event, command: Option<&str>) -> bool {
+        if self.event != event {
+            return false;
+        }
+
+        match (&self.matches, command) {
+            (Some(pattern), Some(command)) => Regex::new(pattern)
+                .map(|r| r.is_match(command))
+                .unwrap_or(false),
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+}
+
+/// Build the payload sent for an event
+///
+/// Without a template it's a JSON object with the event name and every
+/// field. With one, `{{event}}` and `{{<field>}}` are replaced by the values,
+/// JSON-escaped so they can sit inside a JSON string.
+pub fn render(template: Option<&str>, event: Event, fields: &[(&str, String)]) -> String {
+    match template {
+        None => {
+            let mut payload = serde_json::Map::new();
+            payload.insert("event".to_string(), event.as_str().into());
+            for (name, value) in fields {
+                payload.insert(name.to_string(), value.clone().into());
+            }
+            serde_json::Value::Object(payload).to_string()
+        }
+        Some(template) => {
+            let escape = |value: &str| {
+                let quoted = serde_json::Value::from(value).to_string();
+                quoted[1..quoted.len() - 1].to_string()
+            };
+
+            let mut rendered = template.replace("{{event}}", event.as_str());
+            for (name, value) in fields {
+                rendered = rendered.replace(&format!("{{{{{}}}}}", name), &escape(value));
+            }
+            rendered
+        }
+    }
+}
+
+/// Every configured trigger
+#[derive(Debug, Default)]
+pub struct Triggers {
+    pub triggers: Vec<Trigger>,
+}
+
+impl Triggers {
+    /// Read the triggers out of preferences
+    pub async fn load(db: &Database) -> Result<Self> {
+        let mut triggers = Vec::new();
+
+        for pref in db.get_preferences_with_prefix(TRIGGER_PREFIX).await? {
+            let rest = &pref.key[TRIGGER_PREFIX.len()..];
+            let Some((event, target)) = rest.split_once(':') else {
+                continue;
+            };
+            let Ok(event) = event.parse::<Event>() else {
+                continue;
+            };
+
+            let options: serde_json::Value =
+                serde_json::from_str(&pref.value).unwrap_or(serde_json::json!({}));
+            let option = |name: &str| options.get(name).and_then(|v| v.as_str()).map(String::from);
+
+            triggers.push(Trigger {
+                event,
+                target: target.to_string(),
+                template: option("template"),
+                matches: option("match"),
+            });
+        }
+
+        Ok(Self { triggers })
+    }
+
+    /// Save a trigger (same event and target replaces the old one)
+    pub async fn add(db: &Database, trigger: &Trigger) -> Result<()> {
+        if let Some(pattern) = &trigger.matches {
+            Regex::new(pattern)
+                .map_err(|e| RecallError::Config(format!("Invalid --match regex: {}", e)))?;
+        }
+
+        let options = serde_json::json!({
+            "template": trigger.template,
+            "match": trigger.matches,
+        });
+        db.set_preference(trigger.key(), options.to_string()).await
+    }
+
+    /// Remove a trigger
+    ///
+    /// # Returns
+    /// * `Ok(true)` - It existed and was removed
+    pub async fn remove(db: &Database, event: Event, target: &str) -> Result<bool> {
+        db.delete_preference(&format!("{}{}:{}", TRIGGER_PREFIX, event, target))
+            .await
+    }
+
+    /// Whether any trigger listens for `event`
+    pub fn listens_for(&self, event: Event) -> bool {
+        self.triggers.iter().any(|t| t.event == event)
+    }
+
+    /// Fire every trigger for `event`
+    ///
+    /// `command` is what `--match` filters are checked against. Scripts get
+    /// the payload on stdin plus `BERRI_EVENT` and `BERRI_<FIELD>` variables,
+    /// webhooks get it as a JSON POST. Nothing is waited on and failures are
+    /// ignored, a broken webhook shouldn't break recording.
+    pub fn fire(&self, event: Event, command: Option<&str>, fields: &[(&str, String)]) {
+        for trigger in self.triggers.iter().filter(|t| t.wants(event, command)) {
+            let payload = render(trigger.template.as_deref(), event, fields);
+
+            let mut process = if trigger.is_webhook() {
+                let mut curl = Command::new("curl");
+                curl.args([
+                    "-sS",
+                    "-m",
+                    WEBHOOK_TIMEOUT_SECS,
+                    "-X",
+                    "POST",
+                    "-H",
+                    "Content-Type: application/json",
+                    "--data-binary",
+                    "@-",
+                    &trigger.target,
+                ]);
+                curl
+            } else {
+                let mut shell = if cfg!(windows) {
+                    let mut cmd = Command::new("cmd");
+                    cmd.arg("/C");
+                    cmd
+                } else {
+                    let mut sh = Command::new("sh");
+                    sh.arg("-c");
+                    sh
+                };
+                shell
+                    .arg(&trigger.target)
+                    .env("BERRI_EVENT", event.as_str())
+                    .env(INTERNAL_ENV, "1");
+                for (name, value) in fields {
+                    shell.env(format!("BERRI_{}", name.to_uppercase()), value);
+                }
+                shell
+            };
+
+            let spawned = process
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+
+            if let Ok(mut child) = spawned {
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = stdin.write_all(payload.as_bytes());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> Vec<(&'static str, String)> {
+        vec![
+            ("command", "kubectl delete pod \"api\"".to_string()),
+            ("project", "/work/prod".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_render_default_payload() {
+        let payload: serde_json::Value =
+            serde_json::from_str(&render(None, Event::DangerousCommand, &fields())).unwrap();
+
+        assert_eq!(payload["event"], "dangerous_command");
+        assert_eq!(payload["command"], "kubectl delete pod \"api\"");
+        assert_eq!(payload["project"], "/work/prod");
+    }
+
+    #[test]
+    fn test_render_template_escapes_values() {
+        let rendered = render(
+            Some(r#"{"text": "{{event}}: {{command}} in {{project}}"}"#),
+            Event::CommandRecorded,
+            &fields(),
+        );
+
+        let payload: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(
+            payload["text"],
+            "command_recorded: kubectl delete pod \"api\" in /work/prod"
+        );
+    }
+
+    #[test]
+    fn test_trigger_match_filter() {
+        let trigger = Trigger {
+            event: Event::CommandRecorded,
+            target: "https://hooks.example.com/x".to_string(),
+            template: None,
+            matches: Some(r"--context\s+prod".to_string()),
+        };
+
+        assert!(trigger.is_webhook());
+        assert!(trigger.wants(Event::CommandRecorded, Some("kubectl --context prod get pods")));
+        assert!(!trigger.wants(Event::CommandRecorded, Some("kubectl --context dev get pods")));
+        assert!(!trigger.wants(Event::PatternFound, Some("kubectl --context prod get pods")));
+    }
+
+    #[test]
+    fn test_parse_event() {
+        assert_eq!("pattern-found".parse::<Event>().unwrap(), Event::PatternFound);
+        assert_eq!(
+            "suggestion_accepted".parse::<Event>().unwrap(),
+            Event::SuggestionAccepted
+        );
+        assert!("sometimes".parse::<Event>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_add_load_remove() {
+        let db = Database::new_test().await.unwrap();
+        let trigger = Trigger {
+            event: Event::DangerousCommand,
+            target: "notify-send 'careful'".to_string(),
+            template: Some("{{command}}".to_string()),
+            matches: None,
+        };
+
+        Triggers::add(&db, &trigger).await.unwrap();
+        let loaded = Triggers::load(&db).await.unwrap();
+        assert_eq!(loaded.triggers, vec![trigger.clone()]);
+        assert!(loaded.listens_for(Event::DangerousCommand));
+        assert!(!loaded.listens_for(Event::PatternFound));
+
+        assert!(Triggers::remove(&db, trigger.event, &trigger.target).await.unwrap());
+        assert!(Triggers::load(&db).await.unwrap().triggers.is_empty());
+    }
+}
@@ -0,0 +1,320 @@
+//! Exporting everything, and importing it back
+//!
+//! `export --everything` writes one JSON document: a manifest (what wrote
+//! it, the schema version, the settings in effect and how many rows each
+//! table had) followed by every table as column names plus rows. Nothing is
+//! left out, so it doubles as a way to move to another machine:
+//! `import --everything` on a fresh install gives back the same database.
+
+use crate::db::{Config, Database};
+use crate::error::{RecallError, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Marks a file as one of ours
+pub const ARCHIVE_FORMAT: &str = "berri-recall-archive";
+
+/// Bumped when tables change in a way an older import couldn't read
+///
+/// Columns added later don't need a bump: each table lists its own columns,
+/// and an import fills in only the ones both sides have.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Every table, parents before the tables that point at them
+const TABLES: &[&str] = &[
+    "commands",
+    "command_stages",
+    "executions",
+    "command_patterns",
+    "suggestions",
+    "preferences",
+    "aliases",
+    "execution_context",
+    "oplog",
+    "dropped_recordings",
+];
+
+/// What's in an archive and where it came from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub schema_version: u32,
+    pub app_version: String,
+    pub exported_at: String,
+    /// Every setting as it was resolved (from the file, environment or table)
+    pub config: BTreeMap<String, String>,
+    /// The text of `config.toml`, if there was one
+    pub config_file: Option<String>,
+    /// Rows per table
+    pub tables: BTreeMap<String, usize>,
+}
+
+/// One table's columns and rows, values in column order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// A full export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Archive {
+    pub manifest: Manifest,
+    pub tables: BTreeMap<String, Table>,
+}
+
+impl Archive {
+    /// Read an archive, refusing anything we didn't write or can't read
+    pub fn parse(text: &str) -> Result<Self> {
+        let archive: Archive = serde_json::from_str(text)
+            .map_err(|e| RecallError::Config(format!("Not a berri-recall archive: {}", e)))?;
+
+        if archive.manifest.format != ARCHIVE_FORMAT {
+            return Err(RecallError::Config(format!(
+                "Not a berri-recall archive (format is '{}')",
+                archive.manifest.format
+            )));
+        }
+        if archive.manifest.schema_version > SCHEMA_VERSION {
+            return Err(RecallError::Config(format!(
+                "This archive is schema version {} from berri-recall v{}, this build reads up \
+                 to {}. Upgrade first.",
+                archive.manifest.schema_version, archive.manifest.app_version, SCHEMA_VERSION
+            )));
+        }
+        Ok(archive)
+    }
+}
+
+/// What an import did
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportStats {
+    /// Rows per table
+    pub rows: BTreeMap<String, usize>,
+    /// Columns in the archive this database doesn't have, as `table.column`
+    pub skipped_columns: Vec<String>,
+}
+
+async fn columns(db: &Database, table: &str) -> Result<Vec<String>> {
+    let columns: Vec<(String,)> =
+        sqlx::query_as(&format!("SELECT name FROM pragma_table_info('{}')", table))
+            .fetch_all(db.pool())
+            .await?;
+
+    Ok(columns.into_iter().map(|(name,)| name).collect())
+}
+
+impl Database {
+    /// Every table and the settings in effect, as one archive
+    pub async fn export_everything(&self) -> Result<Archive> {
+        let mut tables = BTreeMap::new();
+        for table in TABLES {
+            let columns = columns(self, table).await?;
+            // SQLite turns each row into JSON itself, keeping integers,
+            // reals, text and NULL apart
+            let rows: Vec<(String,)> = sqlx::query_as(&format!(
+                "SELECT json_array({}) FROM {} ORDER BY rowid",
+                columns.join(", "),
+                table
+            ))
+            .fetch_all(self.pool())
+            .await?;
+            let rows = rows
+                .iter()
+                .map(|(row,)| serde_json::from_str(row))
+                .collect::<std::result::Result<Vec<Vec<Value>>, _>>()?;
+
+            tables.insert(table.to_string(), Table { columns, rows });
+        }
+
+        let config = Config::load(self).await?;
+        let config_file = config
+            .file
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok());
+
+        Ok(Archive {
+            manifest: Manifest {
+                format: ARCHIVE_FORMAT.to_string(),
+                schema_version: SCHEMA_VERSION,
+                app_version: env!("CARGO_PKG_VERSION").to_string(),
+                exported_at: Utc::now().to_rfc3339(),
+                config: config
+                    .list()
+                    .iter()
+                    .map(|s| (s.key.name.to_string(), s.value.clone()))
+                    .collect(),
+                config_file,
+                tables: tables
+                    .iter()
+                    .map(|(name, t)| (name.clone(), t.rows.len()))
+                    .collect(),
+            },
+            tables,
+        })
+    }
+
+    /// Load an archive, keeping every id, in one transaction
+    ///
+    /// With `replace` everything here is deleted first. Without it rows are
+    /// added alongside what's here, a row with the same key replacing ours
+    /// (the seeded preferences, say); `merge` is the way to combine two
+    /// histories.
+    pub async fn import_everything(&self, archive: &Archive, replace: bool) -> Result<ImportStats> {
+        let mut ours = BTreeMap::new();
+        for table in TABLES {
+            ours.insert(*table, columns(self, table).await?);
+        }
+
+        let mut stats = ImportStats::default();
+        let mut tx = self.pool().begin().await?;
+
+        if replace {
+            for table in TABLES.iter().rev() {
+                sqlx::query(&format!("DELETE FROM {}", table))
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        for table in TABLES {
+            let Some(data) = archive.tables.get(*table) else {
+                continue;
+            };
+            let ours = &ours[table];
+            let keep: Vec<usize> = (0..data.columns.len())
+                .filter(|&i| ours.contains(&data.columns[i]))
+                .collect();
+            stats.skipped_columns.extend(
+                data.columns
+                    .iter()
+                    .filter(|c| !ours.contains(c))
+                    .map(|c| format!("{}.{}", table, c)),
+            );
+            if keep.is_empty() {
+                continue;
+            }
+
+            let sql = format!(
+                "INSERT OR REPLACE INTO {} ({}) VALUES ({})",
+                table,
+                keep.iter()
+                    .map(|&i| data.columns[i].as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                vec!["?"; keep.len()].join(", ")
+            );
+            for row in &data.rows {
+                let mut query = sqlx::query(&sql);
+                for &i in &keep {
+                    query = match row.get(i).unwrap_or(&Value::Null) {
+                        Value::Null => query.bind(None::<String>),
+                        Value::Bool(b) => query.bind(*b),
+                        Value::Number(n) => match n.as_i64() {
+                            Some(n) => query.bind(n),
+                            None => query.bind(n.as_f64()),
+                        },
+                        Value::String(s) => query.bind(s.clone()),
+                        other => query.bind(other.to_string()),
+                    };
+                }
+                query.execute(&mut *tx).await?;
+            }
+            stats.rows.insert(table.to_string(), data.rows.len());
+        }
+
+        tx.commit().await?;
+        // Archives from before content hashes and the stage index
+        self.backfill_hash().await?;
+        self.backfill_stages().await?;
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{CommandInput, ExecutionMeta};
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().join("old.db")).await.unwrap();
+        let id = db
+            .record_command_with(
+                CommandInput {
+                    project_path: "/work/api".to_string(),
+                    command: "cargo test".to_string(),
+                    execution_time_ms: Some(1500),
+                    exit_code: Some(0),
+                    context: None,
+                },
+                &ExecutionMeta {
+                    branch: Some("main".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        db.toggle_favorite(id).await.unwrap();
+        db.create_alias("t".to_string(), "cargo test".to_string(), None)
+            .await
+            .unwrap();
+        db.set_preference("hello".to_string(), "false".to_string())
+            .await
+            .unwrap();
+
+        let archive = db.export_everything().await.unwrap();
+        assert_eq!(archive.manifest.tables["commands"], 1);
+        assert_eq!(archive.manifest.config["hello"], "false");
+
+        let text = serde_json::to_string(&archive).unwrap();
+        let fresh = Database::new(dir.path().join("new.db")).await.unwrap();
+        let stats = fresh
+            .import_everything(&Archive::parse(&text).unwrap(), false)
+            .await
+            .unwrap();
+        assert_eq!(stats.rows["executions"], 1);
+        assert!(stats.skipped_columns.is_empty());
+
+        // Same rows, ids and all
+        let again = fresh.export_everything().await.unwrap();
+        assert_eq!(
+            serde_json::to_value(&again.tables).unwrap(),
+            serde_json::to_value(&archive.tables).unwrap()
+        );
+
+        let mut newer = archive.clone();
+        newer.manifest.schema_version = SCHEMA_VERSION + 1;
+        assert!(Archive::parse(&serde_json::to_string(&newer).unwrap()).is_err());
+        assert!(Archive::parse("{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import_keeps_stages() {
+        let db = Database::new_test().await.unwrap();
+        db.record_command(CommandInput {
+            project_path: "/work/api".to_string(),
+            command: "cat app.log | grep ERROR".to_string(),
+            execution_time_ms: None,
+            exit_code: Some(0),
+            context: None,
+        })
+        .await
+        .unwrap();
+        let archive = db.export_everything().await.unwrap();
+        assert_eq!(archive.manifest.tables["command_stages"], 2);
+
+        // Written out, or rebuilt for an archive from before the index
+        let mut older = archive.clone();
+        older.tables.remove("command_stages");
+        for archive in [archive, older] {
+            let fresh = Database::new_test().await.unwrap();
+            fresh.import_everything(&archive, false).await.unwrap();
+            let uses = fresh.stage_uses("grep", None).await.unwrap();
+            assert_eq!(uses, vec![("grep ERROR".to_string(), 1)]);
+        }
+    }
+}
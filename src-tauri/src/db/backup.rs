@@ -0,0 +1,193 @@
+//! Database backups and recovery
+//!
+//! A copy of the database is taken at most once a day into
+//! `~/.berri-recall/backups/`, keeping the last `KEEP_BACKUPS`. When the
+//! database file turns out to be corrupted it can be swapped for the latest
+//! copy; the broken file is kept next to it rather than deleted.
+
+use crate::db::lock::{self, Lock};
+use crate::db::Database;
+use crate::error::{RecallError, Result};
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How many daily backups to keep
+pub const KEEP_BACKUPS: usize = 7;
+
+/// A new backup is taken once the latest is older than this
+const BACKUP_EVERY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// SQLITE_CORRUPT and SQLITE_NOTADB
+const CORRUPTION_CODES: [i64; 2] = [11, 26];
+
+/// Where backups of the database at `db_path` go
+pub fn backup_dir(db_path: &Path) -> PathBuf {
+    db_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("backups")
+}
+
+/// Every backup in `dir`, newest first
+pub fn list_backups(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "db"))
+        .collect();
+    // Names carry the time they were taken, so they sort by age
+    backups.sort();
+    backups.reverse();
+    backups
+}
+
+/// Whether an error means the database file itself is damaged
+pub fn is_corruption(error: &RecallError) -> bool {
+    match error {
+        RecallError::Database(sqlx::Error::Database(e)) => e
+            .code()
+            .and_then(|code| code.parse::<i64>().ok())
+            // Extended result codes keep the primary code in the low byte
+            .is_some_and(|code| CORRUPTION_CODES.contains(&(code & 0xff))),
+        _ => false,
+    }
+}
+
+impl Database {
+    /// Copy the database to `path`
+    ///
+    /// Uses `VACUUM INTO`, so the copy is consistent even while the shell
+    /// hooks keep recording.
+    pub async fn backup_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        sqlx::query("VACUUM INTO ?")
+            .bind(path.to_string_lossy().to_string())
+            .execute(self.pool())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Take a backup if the latest one is more than a day old
+    ///
+    /// # Returns
+    /// * `Ok(Some(path))` - A backup was taken
+    /// * `Ok(None)` - The latest one is recent enough, or another process
+    ///   is busy with the database
+    pub async fn backup_if_due(&self) -> Result<Option<PathBuf>> {
+        let dir = backup_dir(self.path());
+        let backups = list_backups(&dir);
+
+        let fresh = backups
+            .first()
+            .and_then(|latest| latest.metadata().ok()?.modified().ok())
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age < BACKUP_EVERY);
+        if fresh {
+            return Ok(None);
+        }
+        // Someone else is taking it, or doing something bigger
+        let Ok(_lock) = Lock::try_acquire(
+            &lock::lock_path(self.path(), lock::MAINTENANCE),
+            "backing up the database",
+        ) else {
+            return Ok(None);
+        };
+
+        let path = dir.join(format!(
+            "commands-{}.db",
+            Utc::now().format("%Y%m%d-%H%M%S")
+        ));
+        self.backup_to(&path).await?;
+
+        for old in list_backups(&dir).into_iter().skip(KEEP_BACKUPS) {
+            let _ = std::fs::remove_file(old);
+        }
+
+        Ok(Some(path))
+    }
+}
+
+/// Put `backup` in place of the database at `db_path`
+///
+/// The damaged file is renamed to `<name>.corrupt-<time>` so nothing is lost.
+///
+/// # Returns
+/// * `Ok(PathBuf)` - Where the damaged file was moved
+pub fn restore_backup(db_path: &Path, backup: &Path) -> Result<PathBuf> {
+    let kept = db_path.with_extension(format!("db.corrupt-{}", Utc::now().format("%Y%m%d-%H%M%S")));
+
+    if db_path.exists() {
+        std::fs::rename(db_path, &kept)?;
+    }
+    // Journal files belong to the broken database, not the backup
+    for suffix in ["-wal", "-shm", "-journal"] {
+        let mut sidecar = db_path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        let _ = std::fs::remove_file(PathBuf::from(sidecar));
+    }
+
+    std::fs::copy(backup, db_path)?;
+
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CommandInput;
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("commands.db");
+
+        let db = Database::new(&db_path).await.unwrap();
+        db.record_command(CommandInput {
+            project_path: "/test".to_string(),
+            command: "make".to_string(),
+            execution_time_ms: None,
+            exit_code: Some(0),
+            context: None,
+        })
+        .await
+        .unwrap();
+
+        let backup = db.backup_if_due().await.unwrap().unwrap();
+        // One a day is plenty
+        assert!(db.backup_if_due().await.unwrap().is_none());
+        db.close().await;
+
+        // Trash the database
+        std::fs::write(&db_path, b"this is not a database, not anymore").unwrap();
+        let error = Database::new(&db_path).await.err().unwrap();
+        assert!(is_corruption(&error));
+
+        assert_eq!(list_backups(&backup_dir(&db_path)), vec![backup.clone()]);
+        let kept = restore_backup(&db_path, &backup).unwrap();
+        assert!(kept.exists());
+
+        let db = Database::new(&db_path).await.unwrap();
+        assert!(db
+            .find_command("make", Some("/test"))
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_other_errors_are_not_corruption() {
+        assert!(!is_corruption(&RecallError::Config("nope".to_string())));
+        assert!(!is_corruption(&RecallError::Database(
+            sqlx::Error::RowNotFound
+        )));
+    }
+}
@@ -0,0 +1,207 @@
+//! Canonical form of a command
+//!
+//! `git commit -m "fix"`, `git commit -m 'fix'` and `git  commit -m fix `
+//! run exactly the same thing. The canonical form spells them all the same
+//! way, so recording can fold them into one row instead of three.
+//!
+//! Only quoting that can't change meaning is normalized: anything with
+//! expansions, globs or operators is kept as typed.
+
+/// Programs that run the rest of the line as root (or another user)
+const PRIVILEGE_PROGRAMS: &[&str] = &["sudo", "doas"];
+
+/// Their options that take a value: `sudo -u deploy`, `doas -C doas.conf`
+const PRIVILEGE_VALUE_FLAGS: &[&str] = &[
+    "-C", "-D", "-g", "-h", "-p", "-r", "-T", "-t", "-U", "-u",
+];
+
+/// Characters that never need quoting
+fn is_plain(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_-./:=@,+%^".contains(c)
+}
+
+/// Characters that mean something to the shell when left unquoted
+fn is_special_unquoted(c: char) -> bool {
+    "$`\\*?[]{}()<>|&;~!#".contains(c)
+}
+
+/// One word as typed, plus what it means if that's unambiguous
+struct Word {
+    raw: String,
+    /// None once something shell-interpreted shows up
+    literal: Option<String>,
+}
+
+/// The canonical spelling of `command`
+///
+/// Words are split like the shell would, literal words are written unquoted
+/// when they can be and single-quoted otherwise, and everything is joined
+/// with single spaces.
+pub fn canonical_command(command: &str) -> String {
+    split_words(command)
+        .into_iter()
+        .map(|word| match word.literal {
+            Some(literal) if !literal.is_empty() && literal.chars().all(is_plain) => literal,
+            Some(literal) => format!("'{}'", literal.replace('\'', r"'\''")),
+            None => word.raw,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// What `command` runs once a leading `sudo` or `doas` and its options are
+/// taken off
+///
+/// `None` when it starts with neither. A shell (`sudo -i`, `sudo -s`) comes
+/// back as `Some("")`.
+pub fn strip_privilege(command: &str) -> Option<&str> {
+    fn next_word(text: &str) -> (&str, &str) {
+        let text = text.trim_start();
+        text.split_at(text.find(char::is_whitespace).unwrap_or(text.len()))
+    }
+
+    let (program, mut rest) = next_word(command);
+    if !PRIVILEGE_PROGRAMS.contains(&program) {
+        return None;
+    }
+    loop {
+        let (word, after) = next_word(rest);
+        if word == "--" {
+            return Some(after.trim());
+        }
+        if !word.starts_with('-') {
+            return Some(rest.trim());
+        }
+        rest = after;
+        if PRIVILEGE_VALUE_FLAGS.contains(&word) {
+            rest = next_word(rest).1;
+        }
+    }
+}
+
+fn split_words(command: &str) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut current: Option<Word> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            words.extend(current.take());
+            continue;
+        }
+
+        let word = current.get_or_insert_with(|| Word {
+            raw: String::new(),
+            literal: Some(String::new()),
+        });
+
+        match c {
+            '\'' => {
+                word.raw.push(c);
+                let mut text = String::new();
+                for c in chars.by_ref() {
+                    word.raw.push(c);
+                    if c == '\'' {
+                        break;
+                    }
+                    text.push(c);
+                }
+                if let Some(literal) = word.literal.as_mut() {
+                    literal.push_str(&text);
+                }
+            }
+            '"' => {
+                word.raw.push(c);
+                let mut text = String::new();
+                let mut expands = false;
+                while let Some(c) = chars.next() {
+                    word.raw.push(c);
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            expands = true;
+                            if let Some(next) = chars.next() {
+                                word.raw.push(next);
+                            }
+                        }
+                        '$' | '`' => expands = true,
+                        _ => text.push(c),
+                    }
+                }
+                if expands {
+                    word.literal = None;
+                } else if let Some(literal) = word.literal.as_mut() {
+                    literal.push_str(&text);
+                }
+            }
+            c if is_special_unquoted(c) => {
+                word.raw.push(c);
+                // A backslash escapes the next character, whitespace included
+                if c == '\\' {
+                    if let Some(next) = chars.next() {
+                        word.raw.push(next);
+                    }
+                }
+                word.literal = None;
+            }
+            c => {
+                word.raw.push(c);
+                if let Some(literal) = word.literal.as_mut() {
+                    literal.push(c);
+                }
+            }
+        }
+    }
+
+    words.extend(current);
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quoting_styles_match() {
+        let canonical = canonical_command("git commit -m 'fix build'");
+        assert_eq!(canonical, "git commit -m 'fix build'");
+        assert_eq!(canonical_command(r#"git commit -m "fix build""#), canonical);
+        assert_eq!(canonical_command(r#"git  commit -m fix" "build  "#), canonical);
+
+        assert_eq!(canonical_command(r#"npm run "test""#), "npm run test");
+        assert_eq!(canonical_command("echo ''"), "echo ''");
+    }
+
+    #[test]
+    fn test_meaning_is_kept() {
+        // Expanded vs literal
+        assert_ne!(
+            canonical_command(r#"echo "$HOME""#),
+            canonical_command("echo '$HOME'")
+        );
+        // A pipe vs a string with a pipe in it
+        assert_ne!(canonical_command("grep a|b"), canonical_command("grep 'a|b'"));
+        // Glob vs literal star
+        assert_ne!(canonical_command("ls *.rs"), canonical_command("ls '*.rs'"));
+        // One argument vs two
+        assert_ne!(
+            canonical_command("echo 'a b'"),
+            canonical_command("echo a b")
+        );
+        assert_eq!(canonical_command("ls   *.rs "), "ls *.rs");
+    }
+
+    #[test]
+    fn test_strip_privilege() {
+        assert_eq!(strip_privilege("sudo apt update"), Some("apt update"));
+        assert_eq!(strip_privilege("  doas  pkg_add htop"), Some("pkg_add htop"));
+        assert_eq!(
+            strip_privilege("sudo -E -u deploy systemctl restart app"),
+            Some("systemctl restart app")
+        );
+        assert_eq!(strip_privilege("sudo -- -weird-name"), Some("-weird-name"));
+        assert_eq!(strip_privilege("sudo -i"), Some(""));
+        assert_eq!(strip_privilege("sudoku"), None);
+        assert_eq!(strip_privilege("echo sudo"), None);
+    }
+}
@@ -0,0 +1,341 @@
+//! Cold storage for old runs
+//!
+//! Every run is a row in `executions`, so the database keeps growing and the
+//! queries the prompt waits on slow down with it. `archive` moves runs older
+//! than a few months out to one gzipped file per month next to the database
+//! (`archive/runs-2024-05.jsonl.gz`, a JSON object per line), along with the
+//! commands that have no runs left. `search --include-archived` still finds
+//! them there.
+//!
+//! A file only ever grows by another gzip member appended to its end, so an
+//! archive that stops halfway leaves every file readable. Runs are deleted
+//! once they're written; if that never happens they're written again next
+//! time, and reading skips the copies.
+
+use crate::db::{format_timestamp, parse_timestamp, Command, Database};
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// IDs per `DELETE ... IN (...)`, well under SQLite's limit on parameters
+const DELETE_CHUNK: usize = 500;
+
+/// One archived run, with the command it belongs to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct ColdRun {
+    pub id: i64,
+    pub command_id: i64,
+    pub project_path: String,
+    pub command: String,
+    pub executed_at: String,
+    pub execution_time_ms: Option<i32>,
+    pub exit_code: Option<i32>,
+    pub session_id: Option<String>,
+    pub hostname: Option<String>,
+    pub cwd: Option<String>,
+    /// Captured environment variables, a JSON object
+    pub env: Option<String>,
+    pub branch: Option<String>,
+    pub interactive: Option<bool>,
+    pub privileged: Option<bool>,
+}
+
+/// What `move_to_cold` moved
+#[derive(Debug, Default)]
+pub struct ColdStats {
+    pub runs: usize,
+    /// Commands that had no runs left
+    pub commands: usize,
+    /// `YYYY-MM`, oldest first
+    pub months: Vec<String>,
+}
+
+/// Where the monthly files go: `archive/` next to the database
+pub fn cold_dir(db_path: &Path) -> PathBuf {
+    db_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("archive")
+}
+
+fn month_file(dir: &Path, month: &str) -> PathBuf {
+    dir.join(format!("runs-{}.jsonl.gz", month))
+}
+
+/// Every monthly file in `dir`, oldest first
+pub fn month_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("runs-") && n.ends_with(".jsonl.gz"))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// The runs in one monthly file, in the order they were written
+///
+/// A line that doesn't parse is skipped rather than losing the whole month.
+pub fn read_runs(path: &Path) -> Result<Vec<ColdRun>> {
+    let reader = BufReader::new(MultiGzDecoder::new(File::open(path)?));
+    let mut runs = Vec::new();
+    for line in reader.lines() {
+        if let Ok(run) = serde_json::from_str(&line?) {
+            runs.push(run);
+        }
+    }
+    Ok(runs)
+}
+
+/// Archived commands containing `query` (ignoring ASCII case, like
+/// `search`) in `project`, or anywhere for `None`
+///
+/// One per project and command, most used first, counting only the runs
+/// `keep` accepts. Their `id` is 0: they're not in the database.
+pub fn search_cold<F>(
+    dir: &Path,
+    query: &str,
+    project: Option<&str>,
+    keep: F,
+) -> Result<Vec<Command>>
+where
+    F: Fn(&ColdRun) -> bool,
+{
+    let query = query.to_ascii_lowercase();
+    let mut seen = HashSet::new();
+    let mut found: HashMap<(String, String), Command> = HashMap::new();
+
+    for path in month_files(dir) {
+        for run in read_runs(&path)? {
+            if !seen.insert(run.id)
+                || project.is_some_and(|p| p != run.project_path)
+                || !run.command.to_ascii_lowercase().contains(&query)
+                || !keep(&run)
+            {
+                continue;
+            }
+            let Some(at) = parse_timestamp(&run.executed_at) else {
+                continue;
+            };
+            let key = (run.project_path.clone(), run.command.clone());
+            let command = found.entry(key).or_insert_with(|| Command {
+                id: 0,
+                project_path: run.project_path.clone(),
+                command: run.command.clone(),
+                timestamp: at,
+                is_fav: false,
+                usage_count: 0,
+                execution_time_ms: None,
+                exit_code: None,
+                tags: None,
+                context: None,
+            });
+            command.usage_count += 1;
+            if at >= command.timestamp {
+                command.timestamp = at;
+                command.execution_time_ms = run.execution_time_ms;
+                command.exit_code = run.exit_code;
+            }
+        }
+    }
+
+    let mut commands: Vec<Command> = found.into_values().collect();
+    commands.sort_by(|a, b| {
+        b.usage_count
+            .cmp(&a.usage_count)
+            .then(b.timestamp.cmp(&a.timestamp))
+    });
+    Ok(commands)
+}
+
+impl Database {
+    /// Move the runs from before `before` into monthly files in `dir`
+    ///
+    /// Commands left with no runs go too, unless they're favorites or tagged.
+    /// The database is vacuumed afterwards, so the file actually shrinks.
+    pub async fn move_to_cold(&self, dir: &Path, before: DateTime<Utc>) -> Result<ColdStats> {
+        let runs: Vec<ColdRun> = sqlx::query_as(
+            r#"
+            SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                   e.execution_time_ms, e.exit_code, e.session_id, e.hostname, e.cwd,
+                   e.env, e.branch, e.interactive, e.privileged
+            FROM executions e
+            JOIN commands c ON c.id = e.command_id
+            WHERE e.executed_at < ?
+            ORDER BY e.executed_at, e.id
+            "#,
+        )
+        .bind(format_timestamp(&before))
+        .fetch_all(self.pool())
+        .await?;
+        if runs.is_empty() {
+            return Ok(ColdStats::default());
+        }
+
+        let mut months: BTreeMap<String, Vec<&ColdRun>> = BTreeMap::new();
+        for run in &runs {
+            let month = match parse_timestamp(&run.executed_at) {
+                Some(at) => at.format("%Y-%m").to_string(),
+                None => run.executed_at.chars().take(7).collect(),
+            };
+            months.entry(month).or_default().push(run);
+        }
+
+        std::fs::create_dir_all(dir)?;
+        for (month, runs) in &months {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(month_file(dir, month))?;
+            let mut gz = GzEncoder::new(file, Compression::best());
+            for run in runs {
+                serde_json::to_writer(&mut gz, run)?;
+                gz.write_all(b"\n")?;
+            }
+            gz.finish()?.sync_all()?;
+        }
+
+        // On disk, so the rows can go
+        let command_ids: Vec<i64> = runs
+            .iter()
+            .map(|r| r.command_id)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let mut commands = 0;
+        let mut tx = self.pool().begin().await?;
+        for chunk in runs.chunks(DELETE_CHUNK) {
+            let sql = format!(
+                "DELETE FROM executions WHERE id IN ({})",
+                vec!["?"; chunk.len()].join(", ")
+            );
+            let mut query = sqlx::query(&sql);
+            for run in chunk {
+                query = query.bind(run.id);
+            }
+            query.execute(&mut *tx).await?;
+        }
+        for chunk in command_ids.chunks(DELETE_CHUNK) {
+            let sql = format!(
+                r#"
+                DELETE FROM commands
+                WHERE id IN ({})
+                  AND NOT EXISTS (SELECT 1 FROM executions WHERE command_id = commands.id)
+                  AND NOT is_fav
+                  AND (tags IS NULL OR tags IN ('', '[]'))
+                "#,
+                vec!["?"; chunk.len()].join(", ")
+            );
+            let mut query = sqlx::query(&sql);
+            for id in chunk {
+                query = query.bind(id);
+            }
+            commands += query.execute(&mut *tx).await?.rows_affected() as usize;
+        }
+        tx.commit().await?;
+
+        sqlx::query("VACUUM").execute(self.pool()).await?;
+
+        Ok(ColdStats {
+            runs: runs.len(),
+            commands,
+            months: months.into_keys().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{CommandInput, ExecutionMeta};
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn test_move_to_cold() {
+        let db = Database::new_test().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let now = Utc::now();
+        let run = |command: &str, days_ago: i64| {
+            let input = CommandInput {
+                project_path: "/work/api".to_string(),
+                command: command.to_string(),
+                execution_time_ms: Some(100),
+                exit_code: Some(0),
+                context: None,
+            };
+            let meta = ExecutionMeta {
+                executed_at: Some(format_timestamp(&(now - Duration::days(days_ago)))),
+                branch: Some("main".to_string()),
+                ..Default::default()
+            };
+            (input, meta)
+        };
+        for (command, days_ago) in [
+            ("make old", 400),
+            ("make old", 380),
+            ("make both", 400),
+            ("make both", 1),
+            ("make starred", 400),
+            ("make new", 2),
+        ] {
+            let (input, meta) = run(command, days_ago);
+            db.record_command_with(input, &meta).await.unwrap();
+        }
+        let starred = db.find_command("make starred", Some("/work/api")).await.unwrap();
+        db.toggle_favorite(starred.unwrap().id).await.unwrap();
+
+        let stats = db.move_to_cold(dir.path(), now - Duration::days(365)).await.unwrap();
+        assert_eq!(stats.runs, 4);
+        assert_eq!(stats.commands, 1);
+        assert!(!stats.months.is_empty());
+        assert_eq!(db.count_executions(None).await.unwrap(), 2);
+        assert!(db.find_command("make old", None).await.unwrap().is_none());
+        assert!(db.find_command("make both", None).await.unwrap().is_some());
+        assert!(db.find_command("make starred", None).await.unwrap().is_some());
+
+        // Nothing left that old
+        let again = db.move_to_cold(dir.path(), now - Duration::days(365)).await.unwrap();
+        assert_eq!(again.runs, 0);
+
+        let found = search_cold(dir.path(), "MAKE OLD", Some("/work/api"), |_| true).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].usage_count, 2);
+        assert_eq!(found[0].exit_code, Some(0));
+        assert!(search_cold(dir.path(), "make", Some("/elsewhere"), |_| true)
+            .unwrap()
+            .is_empty());
+        let on_main = |run: &ColdRun| run.branch.as_deref() == Some("main");
+        assert_eq!(search_cold(dir.path(), "make", None, on_main).unwrap().len(), 3);
+        assert!(search_cold(dir.path(), "make", None, |_| false).unwrap().is_empty());
+
+        // Written twice (the rows weren't deleted the first time): counted once
+        let path = &month_files(dir.path())[0];
+        let runs = read_runs(path).unwrap();
+        let mut gz = GzEncoder::new(
+            OpenOptions::new().append(true).open(path).unwrap(),
+            Compression::default(),
+        );
+        for run in &runs {
+            serde_json::to_writer(&mut gz, run).unwrap();
+            gz.write_all(b"\n").unwrap();
+        }
+        gz.finish().unwrap();
+        assert_eq!(read_runs(path).unwrap().len(), runs.len() * 2);
+        let found = search_cold(dir.path(), "make old", None, |_| true).unwrap();
+        assert_eq!(found[0].usage_count, 2);
+    }
+}
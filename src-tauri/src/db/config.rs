@@ -0,0 +1,581 @@
+//! Typed settings
+//!
+//! A setting can come from four places. Highest wins:
+//!
+//! 1. An environment variable, for the few keys that have one
+//! 2. `config.toml` next to the database (`~/.berri-recall/config.toml`),
+//!    the file to keep in your dotfiles
+//! 3. The preferences table, which `berri-recall config set` writes
+//! 4. The built-in default
+//!
+//! Values are checked when they're set. A bad value (or a broken file) is
+//! skipped with a warning instead of breaking the hooks.
+
+use crate::core::environment;
+use crate::db::Database;
+use crate::error::{RecallError, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Settings file, in the same directory as the database
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// What a setting holds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Bool,
+    /// A whole number no smaller than `min`
+    Int {
+        min: i64,
+    },
+    /// Environment variable names, comma separated
+    EnvNames,
+    /// One of these words
+    Choice(&'static [&'static str]),
+}
+
+/// A setting that can be read and written
+#[derive(Debug)]
+pub struct Key {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub default: &'static str,
+    /// Environment variable that beats everything else
+    pub env: Option<&'static str>,
+    pub description: &'static str,
+}
+
+pub const KEYS: &[Key] = &[
+    Key {
+        name: "archive_after_months",
+        kind: Kind::Int { min: 1 },
+        default: "12",
+        env: None,
+        description: "How old runs are before `archive` moves them out of the database",
+    },
+    Key {
+        name: "capture_env",
+        kind: Kind::EnvNames,
+        default: "",
+        env: None,
+        description: "Environment variables stored with each run (e.g. AWS_PROFILE,NODE_ENV)",
+    },
+    Key {
+        name: "color",
+        kind: Kind::Choice(&["auto", "always", "never"]),
+        default: "auto",
+        env: None,
+        description: "Colored output (auto: only on a terminal, and not with NO_COLOR set)",
+    },
+    Key {
+        name: "dedup_canonical",
+        kind: Kind::Bool,
+        default: "true",
+        env: None,
+        description: "Count differently quoted copies of a command as one",
+    },
+    Key {
+        name: "debounce_seconds",
+        kind: Kind::Int { min: 0 },
+        default: "2",
+        env: None,
+        description: "The same command again this soon in one shell counts as a repeat (0 is off)",
+    },
+    Key {
+        name: "emoji",
+        kind: Kind::Choice(&["auto", "always", "never"]),
+        default: "auto",
+        env: None,
+        description: "Emoji in headings and the prompt segment (auto: wherever glyphs are unicode)",
+    },
+    Key {
+        name: "glyphs",
+        kind: Kind::Choice(&["auto", "unicode", "ascii"]),
+        default: "auto",
+        env: None,
+        description: "✓ and → or plain ok and -> (auto: ascii on dumb or non-UTF-8 terminals)",
+    },
+    Key {
+        name: "hello",
+        kind: Kind::Bool,
+        default: "true",
+        env: None,
+        description: "Introduce a project the first time you cd into it (berri-recall hello)",
+    },
+    Key {
+        name: "notify_after_minutes",
+        kind: Kind::Int { min: 0 },
+        default: "5",
+        env: Some("BERRI_RECALL_NOTIFY_AFTER"),
+        description: "Notify when a command runs at least this long (0 turns it off)",
+    },
+    Key {
+        name: "session_gap_minutes",
+        kind: Kind::Int { min: 1 },
+        default: "30",
+        env: None,
+        description: "A pause this long starts a new session for pattern detection",
+    },
+    Key {
+        name: "strip_sudo",
+        kind: Kind::Bool,
+        default: "false",
+        env: None,
+        description: "Record `sudo apt update` as `apt update`, marked as run with privileges",
+    },
+    Key {
+        name: "summary_after_seconds",
+        kind: Kind::Int { min: 0 },
+        default: "0",
+        env: Some("BERRI_RECALL_SUMMARY_AFTER"),
+        description: "After a command this long, print how it compares to your median (0 is off)",
+    },
+    Key {
+        name: "write_history",
+        kind: Kind::Bool,
+        default: "false",
+        env: None,
+        description: "Also add commands replay runs to your shell's own history file",
+    },
+];
+
+/// Where a setting's value came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    Default,
+    Table,
+    File,
+    Env,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Source::Default => "default",
+            Source::Table => "config set",
+            Source::File => CONFIG_FILE,
+            Source::Env => "environment",
+        };
+        f.pad(name)
+    }
+}
+
+/// A setting's current value
+#[derive(Debug, Clone)]
+pub struct Setting {
+    pub key: &'static Key,
+    pub value: String,
+    pub source: Source,
+}
+
+impl Key {
+    /// Look a setting up by name
+    pub fn find(name: &str) -> Result<&'static Key> {
+        if let Some(key) = KEYS.iter().find(|k| k.name == name) {
+            return Ok(key);
+        }
+
+        let close = KEYS
+            .iter()
+            .find(|k| k.name.contains(name) || name.contains(k.name.trim_end_matches("_minutes")));
+        Err(RecallError::Config(match close {
+            Some(key) => format!("Unknown setting '{}', did you mean '{}'?", name, key.name),
+            None => format!("Unknown setting '{}' (see berri-recall config list)", name),
+        }))
+    }
+
+    /// Check a value, returning it the way it's stored
+    pub fn normalize(&self, value: &str) -> Result<String> {
+        let value = value.trim();
+        match self.kind {
+            Kind::Bool => match value.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Ok("true".to_string()),
+                "false" | "no" | "off" | "0" => Ok("false".to_string()),
+                _ => Err(RecallError::Config(format!(
+                    "{} takes true or false, not '{}'",
+                    self.name, value
+                ))),
+            },
+            Kind::Int { min } => match i64::from_str(value) {
+                Ok(n) if n >= min => Ok(n.to_string()),
+                _ => Err(RecallError::Config(format!(
+                    "{} takes a whole number, {} or more, not '{}'",
+                    self.name, min, value
+                ))),
+            },
+            Kind::EnvNames => {
+                let names: Vec<&str> = value
+                    .split([',', ' '])
+                    .filter(|name| !name.is_empty())
+                    .collect();
+                for name in &names {
+                    environment::validate_name(name)?;
+                }
+                Ok(names.join(","))
+            }
+            Kind::Choice(choices) => {
+                let value = value.to_lowercase();
+                match choices.contains(&value.as_str()) {
+                    true => Ok(value),
+                    false => Err(RecallError::Config(format!(
+                        "{} takes {}, not '{}'",
+                        self.name,
+                        choices.join(", "),
+                        value
+                    ))),
+                }
+            }
+        }
+    }
+}
+
+/// Every setting, resolved
+#[derive(Debug, Clone)]
+pub struct Config {
+    settings: Vec<Setting>,
+    /// Where `config.toml` is looked for, `None` for an in-memory database
+    pub file: Option<PathBuf>,
+    /// Bad values and unknown keys that were skipped
+    pub warnings: Vec<String>,
+}
+
+impl Config {
+    /// Resolve every setting for this database
+    pub async fn load(db: &Database) -> Result<Self> {
+        let file = Self::file_path(db);
+        let mut warnings = Vec::new();
+
+        let from_file = match &file {
+            Some(path) if path.exists() => {
+                let text = std::fs::read_to_string(path)?;
+                // A typo in the file shouldn't stop commands being recorded
+                Self::parse_file(&text, &mut warnings).unwrap_or_else(|e| {
+                    warnings.push(format!(
+                        "{} isn't valid TOML, ignored: {}",
+                        path.display(),
+                        e
+                    ));
+                    HashMap::new()
+                })
+            }
+            _ => HashMap::new(),
+        };
+
+        let mut settings = Vec::new();
+        for key in KEYS {
+            let from_env = key.env.and_then(|var| std::env::var(var).ok());
+            let from_table = db.get_preference(key.name).await?;
+            let layers = [
+                (Source::Env, from_env),
+                (Source::File, from_file.get(key.name).cloned()),
+                (Source::Table, from_table),
+            ];
+
+            let mut setting = Setting {
+                key,
+                value: key.default.to_string(),
+                source: Source::Default,
+            };
+            for (source, value) in layers {
+                let Some(value) = value else { continue };
+                match key.normalize(&value) {
+                    Ok(value) => {
+                        // The schema seeds the defaults into the table
+                        let source = if source == Source::Table && value == key.default {
+                            Source::Default
+                        } else {
+                            source
+                        };
+                        setting = Setting { key, value, source };
+                        break;
+                    }
+                    Err(e) => warnings.push(format!("{}: {}, ignored", source, reason(e))),
+                }
+            }
+            settings.push(setting);
+        }
+
+        Ok(Self {
+            settings,
+            file,
+            warnings,
+        })
+    }
+
+    /// `config.toml` for this database, `None` when it has no directory
+    pub fn file_path(db: &Database) -> Option<PathBuf> {
+        db.path()
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(|dir| dir.join(CONFIG_FILE))
+    }
+
+    /// Top-level `key = value` pairs, values as text
+    fn parse_file(
+        text: &str,
+        warnings: &mut Vec<String>,
+    ) -> std::result::Result<HashMap<String, String>, String> {
+        let document = toml_edit::DocumentMut::from_str(text).map_err(|e| e.to_string())?;
+
+        let mut values = HashMap::new();
+        for (name, item) in document.iter() {
+            if Key::find(name).is_err() {
+                warnings.push(format!(
+                    "{}: unknown setting '{}', ignored",
+                    CONFIG_FILE, name
+                ));
+                continue;
+            }
+            let value = match item.as_value() {
+                Some(toml_edit::Value::String(s)) => s.value().clone(),
+                Some(toml_edit::Value::Integer(n)) => n.value().to_string(),
+                Some(toml_edit::Value::Boolean(b)) => b.value().to_string(),
+                Some(toml_edit::Value::Array(items)) => items
+                    .iter()
+                    .filter_map(|item| item.as_str())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                _ => {
+                    warnings.push(format!(
+                        "{}: {} should be a number, true/false, a string or a list, ignored",
+                        CONFIG_FILE, name
+                    ));
+                    continue;
+                }
+            };
+            values.insert(name.to_string(), value);
+        }
+        Ok(values)
+    }
+
+    /// Every setting, by name
+    pub fn list(&self) -> &[Setting] {
+        &self.settings
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Setting> {
+        let key = Key::find(name)?;
+        Ok(self
+            .settings
+            .iter()
+            .find(|s| s.key.name == key.name)
+            .expect("every key is resolved"))
+    }
+
+    /// Store a value in the preferences table
+    ///
+    /// Refused when `config.toml` or the environment sets the key, since
+    /// the table would never be read.
+    pub async fn set(&self, db: &Database, name: &str, value: &str) -> Result<String> {
+        let key = Key::find(name)?;
+        let value = key.normalize(value)?;
+        self.check_writable(key)?;
+
+        db.set_preference(key.name.to_string(), value.clone())
+            .await?;
+        Ok(value)
+    }
+
+    /// Go back to the default
+    ///
+    /// # Returns
+    /// * `Ok(true)` - A value was set with `config set` and is gone now
+    pub async fn unset(&self, db: &Database, name: &str) -> Result<bool> {
+        let key = Key::find(name)?;
+        self.check_writable(key)?;
+
+        let was_set = self.get(key.name)?.source == Source::Table;
+        db.delete_preference(key.name).await?;
+        Ok(was_set)
+    }
+
+    fn check_writable(&self, key: &Key) -> Result<()> {
+        match self.get(key.name)?.source {
+            Source::File => Err(RecallError::Config(format!(
+                "{} is set in {}, change it there",
+                key.name,
+                self.file
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|| CONFIG_FILE.to_string())
+            ))),
+            Source::Env => Err(RecallError::Config(format!(
+                "{} comes from ${}, unset that first",
+                key.name,
+                key.env.unwrap_or_default()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn int(&self, name: &str) -> i64 {
+        let setting = self.get(name).expect("known key");
+        setting
+            .value
+            .parse()
+            .unwrap_or_else(|_| setting.key.default.parse().expect("default is valid"))
+    }
+
+    fn bool(&self, name: &str) -> bool {
+        self.get(name).expect("known key").value == "true"
+    }
+
+    /// Months a run stays in the database before `archive` moves it out
+    pub fn archive_after_months(&self) -> i64 {
+        self.int("archive_after_months")
+    }
+
+    /// Environment variables to keep with each run
+    pub fn capture_env(&self) -> Vec<String> {
+        let value = &self.get("capture_env").expect("known key").value;
+        value
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// `auto`, `always` or `never`
+    pub fn color(&self) -> &str {
+        &self.get("color").expect("known key").value
+    }
+
+    pub fn dedup_canonical(&self) -> bool {
+        self.bool("dedup_canonical")
+    }
+
+    /// Window for folding rapid repeats of a command into the run before
+    pub fn debounce_seconds(&self) -> i64 {
+        self.int("debounce_seconds")
+    }
+
+    /// `auto`, `always` or `never`
+    pub fn emoji(&self) -> &str {
+        &self.get("emoji").expect("known key").value
+    }
+
+    /// `auto`, `unicode` or `ascii`
+    pub fn glyphs(&self) -> &str {
+        &self.get("glyphs").expect("known key").value
+    }
+
+    /// Whether the hook greets projects with no history yet
+    pub fn hello(&self) -> bool {
+        self.bool("hello")
+    }
+
+    /// 0 means never notify
+    pub fn notify_after_minutes(&self) -> i64 {
+        self.int("notify_after_minutes")
+    }
+
+    pub fn session_gap_minutes(&self) -> i64 {
+        self.int("session_gap_minutes")
+    }
+
+    /// Whether a `sudo` or `doas` prefix comes off before recording
+    pub fn strip_sudo(&self) -> bool {
+        self.bool("strip_sudo")
+    }
+
+    /// 0 means no summary line
+    pub fn summary_after_seconds(&self) -> i64 {
+        self.int("summary_after_seconds")
+    }
+
+    /// Whether commands berri-recall runs go into the shell's history file
+    pub fn write_history(&self) -> bool {
+        self.bool("write_history")
+    }
+}
+
+/// The message without the "Configuration error" in front
+fn reason(error: RecallError) -> String {
+    match error {
+        RecallError::Config(msg) => msg,
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let dedup = Key::find("dedup_canonical").unwrap();
+        assert_eq!(dedup.normalize("Off").unwrap(), "false");
+        assert!(dedup.normalize("maybe").is_err());
+
+        let gap = Key::find("session_gap_minutes").unwrap();
+        assert_eq!(gap.normalize(" 45 ").unwrap(), "45");
+        assert!(gap.normalize("0").is_err());
+        assert!(gap.normalize("soon").is_err());
+
+        let err = Key::find("session_gap").unwrap_err().to_string();
+        assert!(err.contains("did you mean 'session_gap_minutes'"));
+        assert!(Key::find("colour").is_err());
+
+        let capture = Key::find("capture_env").unwrap();
+        assert_eq!(
+            capture.normalize("AWS_PROFILE, NODE_ENV").unwrap(),
+            "AWS_PROFILE,NODE_ENV"
+        );
+        assert!(capture.normalize("AWS_PROFILE,GITHUB_TOKEN").is_err());
+
+        let color = Key::find("color").unwrap();
+        assert_eq!(color.normalize("Never").unwrap(), "never");
+        assert!(color.normalize("sometimes").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().join("commands.db")).await.unwrap();
+
+        // Seeded by the schema, so still the default
+        let config = Config::load(&db).await.unwrap();
+        assert_eq!(
+            config.get("session_gap_minutes").unwrap().source,
+            Source::Default
+        );
+        assert_eq!(config.session_gap_minutes(), 30);
+
+        config.set(&db, "session_gap_minutes", "45").await.unwrap();
+        config.set(&db, "dedup_canonical", "no").await.unwrap();
+        assert!(config.set(&db, "session_gap_minutes", "-1").await.is_err());
+        let config = Config::load(&db).await.unwrap();
+        assert_eq!(config.session_gap_minutes(), 45);
+        assert!(!config.dedup_canonical());
+        assert_eq!(
+            config.get("session_gap_minutes").unwrap().source,
+            Source::Table
+        );
+
+        // The file beats the table, a bad value falls through to it
+        std::fs::write(
+            dir.path().join(CONFIG_FILE),
+            "session_gap_minutes = 20\ndedup_canonical = \"sometimes\"\ncolour = true\n",
+        )
+        .unwrap();
+        let config = Config::load(&db).await.unwrap();
+        assert_eq!(config.session_gap_minutes(), 20);
+        assert_eq!(
+            config.get("session_gap_minutes").unwrap().source,
+            Source::File
+        );
+        assert!(!config.dedup_canonical());
+        assert_eq!(config.warnings.len(), 2);
+        assert!(config.set(&db, "session_gap_minutes", "10").await.is_err());
+
+        std::fs::write(dir.path().join(CONFIG_FILE), "session_gap_minutes = [").unwrap();
+        let config = Config::load(&db).await.unwrap();
+        assert_eq!(config.session_gap_minutes(), 45);
+        assert_eq!(config.warnings.len(), 1);
+
+        assert!(config.unset(&db, "dedup_canonical").await.unwrap());
+        let config = Config::load(&db).await.unwrap();
+        assert!(config.dedup_canonical());
+    }
+}
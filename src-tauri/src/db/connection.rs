@@ -1,17 +1,96 @@
-/// Database connection management with connection pooling
-///
-/// Provides a thread-safe connection pool to SQLite database.
+//! Database connection management with connection pooling
+//!
+//! Provides a thread-safe connection pool to SQLite database.
 
+use crate::db::lock::{self, Lock};
 use crate::error::Result;
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
 use sqlx::ConnectOptions;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Maximum number of database connections in the pool
 const MAX_CONNECTIONS: u32 = 5;
 
+/// How long to wait for another process setting up the schema
+const SCHEMA_LOCK_WAIT: Duration = Duration::from_secs(30);
+
+/// Columns added after a table first shipped: (table, column, definition)
+///
+/// `CREATE TABLE IF NOT EXISTS` leaves older databases alone, so these get
+/// added by hand when they're missing.
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("executions", "session_id", "TEXT"),
+    ("executions", "hostname", "TEXT"),
+    ("executions", "cwd", "TEXT"),
+    ("executions", "env", "TEXT"),
+    ("executions", "branch", "TEXT"),
+    ("executions", "rapid_repeat_count", "INTEGER DEFAULT 0"),
+    ("executions", "interactive", "BOOLEAN"),
+    ("executions", "privileged", "BOOLEAN DEFAULT 0"),
+    ("commands", "canonical", "TEXT"),
+    ("commands", "hash", "TEXT"),
+];
+
+/// Indexes on added columns (they can only be created once the column exists)
+const ADDED_INDEXES: &[&str] = &[
+    "CREATE INDEX IF NOT EXISTS idx_executions_session ON executions(session_id)",
+    "CREATE INDEX IF NOT EXISTS idx_commands_canonical ON commands(project_path, canonical)",
+    "CREATE UNIQUE INDEX IF NOT EXISTS idx_commands_hash ON commands(hash)",
+];
+
+/// Brings `command_patterns.pattern_type` to what `PatternType` reads, then
+/// keeps it there
+///
+/// Old spellings get renamed and anything else is dropped (patterns are
+/// re-detected on the next analyze). The triggers live here rather than in
+/// schema.sql because their bodies hold a `;`.
+const PATTERN_TYPE_MIGRATION: &[&str] = &[
+    "UPDATE command_patterns SET pattern_type = lower(trim(pattern_type))",
+    "UPDATE command_patterns SET pattern_type = 'sequence' \
+     WHERE pattern_type IN ('sequential', 'sequences')",
+    "UPDATE command_patterns SET pattern_type = replace(pattern_type, '-', '_') \
+     WHERE pattern_type IN ('time-based', 'context-based')",
+    "DELETE FROM command_patterns \
+     WHERE pattern_type NOT IN ('sequence', 'frequency', 'time_based', 'context_based')",
+    "CREATE TRIGGER IF NOT EXISTS patterns_insert_type BEFORE INSERT ON command_patterns \
+     WHEN NEW.pattern_type NOT IN ('sequence', 'frequency', 'time_based', 'context_based') \
+     BEGIN SELECT RAISE(ABORT, 'invalid pattern_type'); END",
+    "CREATE TRIGGER IF NOT EXISTS patterns_update_type \
+     BEFORE UPDATE OF pattern_type ON command_patterns \
+     WHEN NEW.pattern_type NOT IN ('sequence', 'frequency', 'time_based', 'context_based') \
+     BEGIN SELECT RAISE(ABORT, 'invalid pattern_type'); END",
+];
+
+/// Everything `initialize_schema` creates
+const SCHEMA: &str = include_str!("../../../database/schema.sql");
+
+/// What goes in `PRAGMA user_version` once the schema is in place
+///
+/// A hash of all the statements above, so changing any of them makes the
+/// next start run them again, and a database already stamped with it skips
+/// straight past them. Never 0, which is what a new database has.
+fn schema_version() -> i32 {
+    // FNV-1a, stable across builds and platforms
+    let mut hash: u32 = 0x811c_9dc5;
+    let mut feed = |text: &str| {
+        for byte in text.bytes().chain([0]) {
+            hash = (hash ^ byte as u32).wrapping_mul(0x0100_0193);
+        }
+    };
+    feed(SCHEMA);
+    for (table, column, definition) in ADDED_COLUMNS {
+        feed(table);
+        feed(column);
+        feed(definition);
+    }
+    ADDED_INDEXES.iter().for_each(|index| feed(index));
+    PATTERN_TYPE_MIGRATION.iter().for_each(|statement| feed(statement));
+    (hash >> 1).max(1) as i32
+}
+
 /// Database wrapper with connection pool
 #[derive(Clone)]
 pub struct Database {
@@ -31,7 +110,7 @@ impl Database {
     ///
     /// # Examples
     /// ```no_run
-    /// use recall_cli_lib::db::Database;
+    /// use berri_recall_lib::db::Database;
     ///
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// let db = Database::new("~/.recall/commands.db").await?;
@@ -69,6 +148,29 @@ impl Database {
         Ok(db)
     }
 
+    /// Open an existing database for reading only
+    ///
+    /// Nothing is created or migrated and no write lock is ever taken, so
+    /// this is safe to point at a database that's damaged or in use. Any
+    /// write fails with a read-only error.
+    pub async fn open_read_only<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let db_path = db_path.as_ref().to_path_buf();
+
+        let options = SqliteConnectOptions::from_str(&format!("sqlite:{}", db_path.display()))?
+            .read_only(true)
+            .disable_statement_logging();
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect_with(options)
+            .await?;
+
+        Ok(Self {
+            pool: Arc::new(pool),
+            db_path,
+        })
+    }
+
     /// Create a test database in memory
     ///
     /// Used for testing. Creates a fresh database for each test.
@@ -78,8 +180,9 @@ impl Database {
             .create_if_missing(true)
             .foreign_keys(true);
 
+        // Every connection to :memory: gets its own database, so stick to one
         let pool = SqlitePoolOptions::new()
-            .max_connections(MAX_CONNECTIONS)
+            .max_connections(1)
             .connect_with(options)
             .await?;
 
@@ -95,21 +198,97 @@ impl Database {
 
     /// Initialize database schema
     ///
-    /// Creates all required tables and indexes if they don't exist.
+    /// Creates all required tables and indexes if they don't exist. Skipped
+    /// when the database is stamped with this version's schema, which is
+    /// most of the work of starting up.
     async fn initialize_schema(&self) -> Result<()> {
-        // Read schema file
-        let schema = include_str!("../../../database/schema.sql");
+        if self.schema_is_current().await? {
+            return Ok(());
+        }
+        // Two shells opening a new or older database at once would both try
+        // to add the same columns: one does it, the other finds it done
+        let _lock = match self.db_path.to_str() {
+            Some(":memory:") => None,
+            _ => {
+                let path = lock::lock_path(&self.db_path, lock::SCHEMA);
+                let lock = Lock::acquire(&path, "updating the database", SCHEMA_LOCK_WAIT).await?;
+                if self.schema_is_current().await? {
+                    return Ok(());
+                }
+                Some(lock)
+            }
+        };
+
+        // Stages are indexed as commands come in, older ones need a pass
+        let (had_stages,): (bool,) = sqlx::query_as(
+            "SELECT COUNT(*) > 0 FROM sqlite_master \
+             WHERE type = 'table' AND name = 'command_stages'",
+        )
+        .fetch_one(self.pool.as_ref())
+        .await?;
 
         // Execute schema SQL
         // Note: SQLite doesn't support multiple statements in execute,
         // so we need to split and execute each statement
-        for statement in schema.split(';') {
+        for statement in SCHEMA.split(';') {
             let trimmed = statement.trim();
             if !trimmed.is_empty() {
                 sqlx::query(trimmed).execute(self.pool.as_ref()).await?;
             }
         }
 
+        self.add_missing_columns().await?;
+
+        if !had_stages {
+            self.backfill_stages().await?;
+        }
+
+        // PRAGMA takes no parameters, it's a number we made anyway
+        sqlx::query(&format!("PRAGMA user_version = {}", schema_version()))
+            .execute(self.pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Whether the database is stamped with this version's schema
+    async fn schema_is_current(&self) -> Result<bool> {
+        let (stamped,): (i32,) = sqlx::query_as("PRAGMA user_version")
+            .fetch_one(self.pool.as_ref())
+            .await?;
+        Ok(stamped == schema_version())
+    }
+
+    /// Bring a database created by an older version up to date
+    async fn add_missing_columns(&self) -> Result<()> {
+        for (table, column, definition) in ADDED_COLUMNS {
+            let existing: Vec<(String,)> =
+                sqlx::query_as(&format!("SELECT name FROM pragma_table_info('{}')", table))
+                    .fetch_all(self.pool.as_ref())
+                    .await?;
+
+            if !existing.iter().any(|(name,)| name == column) {
+                sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                    .execute(self.pool.as_ref())
+                    .await?;
+
+                match (*table, *column) {
+                    ("commands", "canonical") => self.backfill_canonical().await?,
+                    ("commands", "hash") => self.backfill_hash().await?,
+                    ("executions", "privileged") => self.backfill_privileged().await?,
+                    _ => {}
+                }
+            }
+        }
+
+        for index in ADDED_INDEXES {
+            sqlx::query(index).execute(self.pool.as_ref()).await?;
+        }
+
+        for statement in PATTERN_TYPE_MIGRATION {
+            sqlx::query(statement).execute(self.pool.as_ref()).await?;
+        }
+
         Ok(())
     }
 
@@ -142,7 +321,8 @@ impl Database {
                 .await?;
 
         let pattern_count: (i64,) =
-            sqlx::query_as("SELECT COUNT(*) FROM command_patterns")
+            // Sequences still being counted have no confidence yet
+            sqlx::query_as("SELECT COUNT(*) FROM command_patterns WHERE confidence_score > 0")
                 .fetch_one(self.pool.as_ref())
                 .await?;
 
@@ -151,10 +331,17 @@ impl Database {
                 .fetch_one(self.pool.as_ref())
                 .await?;
 
+        let privileged_count: (i64,) = sqlx::query_as(
+            "SELECT COUNT(DISTINCT command_id) FROM executions WHERE privileged",
+        )
+        .fetch_one(self.pool.as_ref())
+        .await?;
+
         Ok(DatabaseStats {
             total_commands: command_count.0,
             total_patterns: pattern_count.0,
             total_suggestions: suggestion_count.0,
+            privileged_commands: privileged_count.0,
             pool_size: self.pool.size(),
             idle_connections: self.pool.num_idle(),
         })
@@ -167,6 +354,8 @@ pub struct DatabaseStats {
     pub total_commands: i64,
     pub total_patterns: i64,
     pub total_suggestions: i64,
+    /// Commands run through `sudo` or `doas` at least once
+    pub privileged_commands: i64,
     pub pool_size: u32,
     pub idle_connections: usize,
 }
@@ -174,6 +363,7 @@ pub struct DatabaseStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::content_hash;
 
     #[tokio::test]
     async fn test_database_creation() {
@@ -189,6 +379,7 @@ mod tests {
         assert_eq!(stats.total_commands, 0);
         assert_eq!(stats.total_patterns, 0);
         assert_eq!(stats.total_suggestions, 0);
+        assert_eq!(stats.privileged_commands, 0);
     }
 
     #[tokio::test]
@@ -204,11 +395,168 @@ mod tests {
         let db = Database::new_test().await.unwrap();
 
         // Verify tables exist by querying them
-        let result: Result<(i64,), sqlx::Error> =
+        let result: std::result::Result<(i64,), sqlx::Error> =
             sqlx::query_as("SELECT COUNT(*) FROM commands")
                 .fetch_one(db.pool())
                 .await;
 
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_adds_missing_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.db");
+
+        // A database from before sessions and canonical forms
+        {
+            let options = SqliteConnectOptions::from_str(&format!("sqlite:{}", path.display()))
+                .unwrap()
+                .create_if_missing(true);
+            let pool = SqlitePoolOptions::new().connect_with(options).await.unwrap();
+            for statement in [
+                "CREATE TABLE commands (id INTEGER PRIMARY KEY AUTOINCREMENT, \
+                 project_path TEXT NOT NULL, command TEXT NOT NULL, \
+                 timestamp DATETIME DEFAULT CURRENT_TIMESTAMP, is_fav INTEGER DEFAULT 0, \
+                 usage_count INTEGER DEFAULT 1, execution_time_ms INTEGER, exit_code INTEGER, \
+                 tags TEXT, context TEXT, UNIQUE(project_path, command) ON CONFLICT REPLACE)",
+                "CREATE TABLE executions (id INTEGER PRIMARY KEY AUTOINCREMENT, \
+                 command_id INTEGER NOT NULL, executed_at DATETIME DEFAULT CURRENT_TIMESTAMP, \
+                 execution_time_ms INTEGER, exit_code INTEGER)",
+                "INSERT INTO commands (project_path, command) VALUES ('/test', 'npm run \"build\"')",
+            ] {
+                sqlx::query(statement).execute(&pool).await.unwrap();
+            }
+            pool.close().await;
+        }
+
+        let db = Database::new(&path).await.unwrap();
+        for (table, column, _) in ADDED_COLUMNS {
+            let columns: Vec<(String,)> =
+                sqlx::query_as(&format!("SELECT name FROM pragma_table_info('{}')", table))
+                    .fetch_all(db.pool())
+                    .await
+                    .unwrap();
+            assert!(columns.iter().any(|(name,)| name == column));
+        }
+
+        // Old rows get their canonical form so new spellings can match them
+        let (canonical,): (String,) = sqlx::query_as("SELECT canonical FROM commands")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(canonical, "npm run build");
+
+        // ...and their hash, before the unique index on it goes on
+        let (hash,): (String,) = sqlx::query_as("SELECT hash FROM commands")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(hash, content_hash("/test", "npm run \"build\""));
+    }
+
+    #[tokio::test]
+    async fn test_normalizes_pattern_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("patterns.db");
+
+        {
+            let db = Database::new(&path).await.unwrap();
+            // Triggers (and the stamp) aren't there yet in a database this old
+            sqlx::query("PRAGMA user_version = 0")
+                .execute(db.pool())
+                .await
+                .unwrap();
+            for trigger in ["patterns_insert_type", "patterns_update_type"] {
+                sqlx::query(&format!("DROP TRIGGER {}", trigger))
+                    .execute(db.pool())
+                    .await
+                    .unwrap();
+            }
+            let insert = "INSERT INTO command_patterns (pattern_type, commands) VALUES (?, '[]')";
+            for pattern_type in ["Sequential", "time-based", "frequency", "hunch"] {
+                sqlx::query(insert)
+                    .bind(pattern_type)
+                    .execute(db.pool())
+                    .await
+                    .unwrap();
+            }
+            db.close().await;
+        }
+
+        let db = Database::new(&path).await.unwrap();
+        let patterns = db.get_patterns(None).await;
+        assert!(patterns.is_ok());
+        let types: Vec<(String,)> =
+            sqlx::query_as("SELECT pattern_type FROM command_patterns ORDER BY id")
+                .fetch_all(db.pool())
+                .await
+                .unwrap();
+        let types: Vec<&str> = types.iter().map(|(t,)| t.as_str()).collect();
+        assert_eq!(types, vec!["sequence", "time_based", "frequency"]);
+
+        // And nothing else gets in from now on
+        let rejected = sqlx::query(
+            "INSERT INTO command_patterns (pattern_type, commands) VALUES ('hunch', '[]')",
+        )
+        .execute(db.pool())
+        .await;
+        assert!(rejected.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_schema_version_stamp() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stamped.db");
+        let index_count = |db: Database| async move {
+            let (count,): (i64,) = sqlx::query_as(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'idx_executions_session'",
+            )
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+            db.close().await;
+            count
+        };
+        let drop_index = |db: Database| async move {
+            sqlx::query("DROP INDEX idx_executions_session")
+                .execute(db.pool())
+                .await
+                .unwrap();
+            db.close().await;
+        };
+
+        drop_index(Database::new(&path).await.unwrap()).await;
+        // Stamped with this schema: nothing runs, so nothing comes back
+        assert_eq!(index_count(Database::new(&path).await.unwrap()).await, 0);
+
+        // An older stamp goes through it all again
+        let db = Database::new(&path).await.unwrap();
+        sqlx::query("PRAGMA user_version = 1")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        db.close().await;
+        let db = Database::new(&path).await.unwrap();
+        let (stamped,): (i32,) = sqlx::query_as("PRAGMA user_version")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(stamped, schema_version());
+        assert_eq!(index_count(db).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_setup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.db");
+
+        // Shells opening a brand new database at once: one sets it up
+        let opens: Vec<_> = (0..4)
+            .map(|_| tokio::spawn(Database::new(path.clone())))
+            .collect();
+        for open in opens {
+            open.await.unwrap().unwrap().close().await;
+        }
+    }
 }
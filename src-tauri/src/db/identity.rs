@@ -0,0 +1,113 @@
+//! Content hash: which command a row is, on any machine
+//!
+//! Row IDs are only meaningful in one database. The hash names a command by
+//! what it is instead, the project (with the home directory written as `~`,
+//! so `/home/me/work/api` and `/Users/me/work/api` agree) plus the canonical
+//! form of the command (see [`canonical`](super::canonical)), so quoting
+//! doesn't make two commands out of one. Merges, imports and the edit log
+//! match commands on it.
+//!
+//! With `dedup_canonical` off, or in history recorded before it, two
+//! spellings of one command can each have a row. The first keeps
+//! `content_hash`, the others get `spelling_hash`.
+
+use crate::db::canonical_command;
+use std::path::Path;
+
+/// The project as every machine of yours would name it
+pub fn project_identity(project_path: &str, home: Option<&Path>) -> String {
+    let path = Path::new(project_path);
+    match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.to_string_lossy().replace('\\', "/")),
+        None => project_path.to_string(),
+    }
+}
+
+/// The hash for `command` recorded in `project_path` on this machine
+pub fn content_hash(project_path: &str, command: &str) -> String {
+    content_hash_in(project_path, command, dirs::home_dir().as_deref())
+}
+
+/// The hash for a spelling of `command` kept apart from its other spellings
+pub fn spelling_hash(project_path: &str, command: &str) -> String {
+    spelling_hash_in(project_path, command, dirs::home_dir().as_deref())
+}
+
+fn content_hash_in(project_path: &str, command: &str, home: Option<&Path>) -> String {
+    let identity = project_identity(project_path, home);
+    let canonical = canonical_command(command);
+    format!(
+        "{:016x}",
+        fnv1a(&[identity.as_bytes(), b"\0", canonical.as_bytes()])
+    )
+}
+
+fn spelling_hash_in(project_path: &str, command: &str, home: Option<&Path>) -> String {
+    let identity = project_identity(project_path, home);
+    let canonical = canonical_command(command);
+    format!(
+        "{:016x}",
+        fnv1a(&[
+            identity.as_bytes(),
+            b"\0",
+            canonical.as_bytes(),
+            b"\0",
+            command.as_bytes(),
+        ])
+    )
+}
+
+/// 64-bit FNV-1a: tiny, and unlike `DefaultHasher` the same in every build
+fn fnv1a(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash() {
+        let linux = Path::new("/home/me");
+        let mac = Path::new("/Users/me");
+        assert_eq!(
+            project_identity("/home/me/work/api", Some(linux)),
+            "~/work/api"
+        );
+        assert_eq!(project_identity("/srv/api", Some(linux)), "/srv/api");
+        assert_eq!(project_identity("/home/me", Some(linux)), "~");
+
+        let hash = content_hash_in("/home/me/work/api", "cargo test", Some(linux));
+        assert_eq!(hash.len(), 16);
+        assert_eq!(
+            hash,
+            content_hash_in("/Users/me/work/api", "cargo test", Some(mac))
+        );
+        assert_ne!(
+            hash,
+            content_hash_in("/home/me/work/web", "cargo test", Some(linux))
+        );
+        assert_ne!(
+            hash,
+            content_hash_in("/home/me/work/api", "cargo build", Some(linux))
+        );
+        // Quoting doesn't make it another command, unless it's kept apart
+        let quoted = content_hash_in("/home/me/work/api", "git commit -m 'wip'", Some(linux));
+        assert_eq!(
+            quoted,
+            content_hash_in("/home/me/work/api", "git commit -m \"wip\"", Some(linux))
+        );
+        assert_ne!(
+            quoted,
+            spelling_hash_in("/home/me/work/api", "git commit -m \"wip\"", Some(linux))
+        );
+        // Known value, so a change to the hash shows up here first
+        assert_eq!(fnv1a(&[b"a"]), 0xaf63_dc4c_8601_ec8c);
+    }
+}
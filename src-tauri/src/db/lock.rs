@@ -0,0 +1,135 @@
+//! Keeping two berri-recalls off the same job
+//!
+//! Any number of shells can record at once, SQLite sees to that. A few jobs
+//! can't overlap: setting up the schema, the daily backup, and the big
+//! one-off ones (`merge`, `archive`, `import --everything`, restoring a
+//! backup). Each takes an advisory lock on a file in `locks/` next to the
+//! database first. The lock goes when the process does, so a crash never
+//! leaves one behind. The file stays, with the pid and job of whoever had it
+//! last, which is what "already running" messages quote.
+//!
+//! `mirror --watch` holds one for as long as it runs, so each history file
+//! is followed once.
+
+use crate::error::{RecallError, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// `merge`, `archive`, `import --everything`, restores and backups
+pub const MAINTENANCE: &str = "maintenance";
+
+/// Creating and migrating the schema
+pub const SCHEMA: &str = "schema";
+
+/// How often `acquire` tries again
+const RETRY_EVERY: Duration = Duration::from_millis(20);
+
+/// Where the lock called `name` lives for the database at `db_path`
+pub fn lock_path(db_path: &Path, name: &str) -> PathBuf {
+    db_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("locks")
+        .join(format!("{}.lock", name))
+}
+
+/// A held lock, released when dropped
+#[derive(Debug)]
+pub struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    /// Take the lock for `job` (like "merging"), unless someone has it
+    ///
+    /// # Returns
+    /// * `Err(RecallError::Busy)` - Another process holds it
+    pub fn try_acquire(path: &Path, job: &str) -> Result<Self> {
+        let mut file = open(path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Err(busy(&mut file, job)),
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        // Who has it, for anyone who finds it taken
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{} {}", std::process::id(), job)?;
+        Ok(Self { _file: file })
+    }
+
+    /// `try_acquire`, waiting up to `timeout` for whoever has it
+    pub async fn acquire(path: &Path, job: &str, timeout: Duration) -> Result<Self> {
+        let started = Instant::now();
+        loop {
+            match Self::try_acquire(path, job) {
+                Err(RecallError::Busy(_)) if started.elapsed() < timeout => {
+                    tokio::time::sleep(RETRY_EVERY).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn open(path: &Path) -> Result<File> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok(OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?)
+}
+
+/// "Another berri-recall (pid 4242) is already merging"
+fn busy(file: &mut File, job: &str) -> RecallError {
+    let mut holder = String::new();
+    let _ = file.read_to_string(&mut holder);
+    let holder = match holder.split_once(' ') {
+        Some((pid, theirs)) if pid.parse::<u32>().is_ok() => {
+            format!("Another berri-recall (pid {}) is already {}", pid, theirs)
+        }
+        _ => format!("Another berri-recall is already {}", job),
+    };
+    RecallError::Busy(holder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = lock_path(&dir.path().join("commands.db"), MAINTENANCE);
+        assert!(path.ends_with("locks/maintenance.lock"));
+
+        let held = Lock::try_acquire(&path, "merging").unwrap();
+        let message = match Lock::try_acquire(&path, "archiving") {
+            Err(RecallError::Busy(message)) => message,
+            other => panic!("expected busy, got {:?}", other),
+        };
+        assert_eq!(
+            message,
+            format!("Another berri-recall (pid {}) is already merging", std::process::id())
+        );
+        let waited = Lock::acquire(&path, "archiving", Duration::from_millis(50)).await;
+        assert!(matches!(waited, Err(RecallError::Busy(_))));
+
+        // Free again once it's dropped, and the next holder's name replaces it
+        drop(held);
+        let _held = Lock::acquire(&path, "archiving", Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{} archiving", std::process::id())
+        );
+    }
+}
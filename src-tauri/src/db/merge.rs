@@ -0,0 +1,631 @@
+//! Merging another berri-recall database into this one
+//!
+//! For people who used berri-recall on a few machines and want one history.
+//! The other database is opened read-only and never changed.
+
+use crate::db::models::{format_timestamp, Alias, Command, Execution};
+use crate::db::oplog::{self, LoggedEdit};
+use crate::db::queries::index_stages;
+use crate::db::{canonical_command, content_hash, spelling_hash, strip_privilege, Database};
+use crate::error::{RecallError, Result};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// What a merge did
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MergeStats {
+    /// Commands this database didn't have yet
+    pub commands_added: usize,
+    /// Commands both had, folded together
+    pub commands_merged: usize,
+    pub executions_added: usize,
+    /// Runs that were already here (merging the same file twice)
+    pub executions_skipped: usize,
+    pub aliases_added: usize,
+    /// Favorites, tags and deletes from their edit log this one didn't have
+    pub edits_added: usize,
+}
+
+/// Tags of both commands, each once, in the order first seen
+pub(crate) fn union_tags(ours: Option<&str>, theirs: Option<&str>) -> Option<String> {
+    let parse = |tags: Option<&str>| -> Vec<String> {
+        tags.and_then(|t| serde_json::from_str(t).ok())
+            .unwrap_or_default()
+    };
+
+    let mut tags = parse(ours);
+    for tag in parse(theirs) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    if tags.is_empty() {
+        None
+    } else {
+        serde_json::to_string(&tags).ok()
+    }
+}
+
+/// Where `project_path` from another machine is on this one
+///
+/// Their hash names the project relative to their home directory. The tail
+/// of their path that gives the same hash under our home is the same project
+/// here. Anything else (`/srv/api`, or no match) keeps the path it had.
+fn local_project_path(project_path: &str, command: &str, hash: &str) -> String {
+    let matches = |path: &str| {
+        content_hash(path, command) == hash || spelling_hash(path, command) == hash
+    };
+    if matches(project_path) {
+        return project_path.to_string();
+    }
+    let Some(home) = dirs::home_dir() else {
+        return project_path.to_string();
+    };
+
+    let parts: Vec<&str> = project_path.split(['/', '\\']).filter(|p| !p.is_empty()).collect();
+    for start in 0..=parts.len() {
+        let mut candidate = home.clone();
+        candidate.extend(&parts[start..]);
+        let candidate = candidate.to_string_lossy().to_string();
+        if matches(&candidate) {
+            return candidate;
+        }
+    }
+
+    project_path.to_string()
+}
+
+async fn has_column(pool: &SqlitePool, table: &str, column: &str) -> Result<bool> {
+    let columns: Vec<(String,)> =
+        sqlx::query_as(&format!("SELECT name FROM pragma_table_info('{}')", table))
+            .fetch_all(pool)
+            .await?;
+
+    Ok(columns.iter().any(|(name,)| name == column))
+}
+
+impl Database {
+    /// Merge the history in `other` into this database
+    ///
+    /// - Commands are matched on their content hash, so the same project under
+    ///   a different home directory (`/Users/me/api`, `/home/me/api`) lines up.
+    ///   New ones are filed under the project's path on this machine.
+    /// - Commands both databases know get their usage counts added up, are a
+    ///   favorite if either side starred them and keep the tags of both.
+    /// - Their `timestamp` is the earlier of the two. Duration and exit code
+    ///   come from whichever side ran it last. The next run here moves the
+    ///   timestamp forward again.
+    /// - Every run is copied over with its original time. Runs already here
+    ///   are skipped and their uses aren't added again, so merging the same
+    ///   file twice changes nothing.
+    /// - Aliases are added unless one with that name already exists here.
+    /// - Both edit logs are combined and replayed on top, so favorites, tags
+    ///   and deletes end up the same whichever side merges which (see
+    ///   [`oplog`]). Commands nobody edited keep the rules above.
+    ///
+    /// Patterns and suggestions aren't copied, `analyze` rebuilds them from
+    /// the merged history.
+    pub async fn merge_from(&self, other: &Path) -> Result<MergeStats> {
+        if !other.is_file() {
+            return Err(RecallError::Config(format!(
+                "No database at {}",
+                other.display()
+            )));
+        }
+
+        let options =
+            SqliteConnectOptions::from_str(&format!("sqlite:{}", other.display()))?.read_only(true);
+        let theirs = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+
+        let commands = sqlx::query_as::<_, Command>("SELECT * FROM commands ORDER BY id")
+            .fetch_all(&theirs)
+            .await?;
+        // Their command ID -> content hash, worked out here for databases
+        // from before it was stored
+        let mut hashes: HashMap<i64, String> = if has_column(&theirs, "commands", "hash").await? {
+            sqlx::query_as("SELECT id, hash FROM commands WHERE hash IS NOT NULL")
+                .fetch_all(&theirs)
+                .await?
+                .into_iter()
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        for command in &commands {
+            hashes
+                .entry(command.id)
+                .or_insert_with(|| content_hash(&command.project_path, &command.command));
+        }
+
+        // Older databases don't know where or in which session a run happened
+        let session_columns = if has_column(&theirs, "executions", "session_id").await? {
+            ", e.session_id, e.hostname, e.cwd"
+        } else {
+            ""
+        };
+        let env_column = if has_column(&theirs, "executions", "env").await? {
+            ", e.env"
+        } else {
+            ""
+        };
+        let branch_column = if has_column(&theirs, "executions", "branch").await? {
+            ", e.branch"
+        } else {
+            ""
+        };
+        let interactive_column = if has_column(&theirs, "executions", "interactive").await? {
+            ", e.interactive"
+        } else {
+            ""
+        };
+        let privileged_column = if has_column(&theirs, "executions", "privileged").await? {
+            ", e.privileged"
+        } else {
+            ""
+        };
+        let repeats_column = if has_column(&theirs, "executions", "rapid_repeat_count").await? {
+            ", e.rapid_repeat_count"
+        } else {
+            ""
+        };
+        let executions = sqlx::query_as::<_, Execution>(&format!(
+            r#"
+            SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                   e.execution_time_ms, e.exit_code{}{}{}{}{}{}
+            FROM executions e
+            JOIN commands c ON c.id = e.command_id
+            ORDER BY e.id
+            "#,
+            session_columns,
+            env_column,
+            branch_column,
+            interactive_column,
+            privileged_column,
+            repeats_column
+        ))
+        .fetch_all(&theirs)
+        .await?;
+
+        let aliases = sqlx::query_as::<_, Alias>("SELECT * FROM aliases")
+            .fetch_all(&theirs)
+            .await?;
+
+        // Databases from before the edit log have none to replay
+        let edits = if has_column(&theirs, "oplog", "clock").await? {
+            sqlx::query_as::<_, LoggedEdit>("SELECT * FROM oplog")
+                .fetch_all(&theirs)
+                .await?
+        } else {
+            Vec::new()
+        };
+
+        theirs.close().await;
+
+        let mut stats = MergeStats::default();
+        let mut tx = self.pool().begin().await?;
+
+        // Their command ID -> ours
+        let mut ids: HashMap<i64, i64> = HashMap::new();
+        // Our command ID -> uses theirs had, added once we know which runs were new
+        let mut their_uses: HashMap<i64, i64> = HashMap::new();
+
+        for command in &commands {
+            let hash = &hashes[&command.id];
+            let project_path = local_project_path(&command.project_path, &command.command, hash);
+            let ours = sqlx::query_as::<_, Command>(
+                "SELECT * FROM commands WHERE hash = ? OR (project_path = ? AND command = ?)",
+            )
+            .bind(hash)
+            .bind(&project_path)
+            .bind(&command.command)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            let id = match ours {
+                Some(ours) => {
+                    let theirs_newer = command.timestamp > ours.timestamp;
+                    let latest = if theirs_newer { command } else { &ours };
+                    let earliest = command.timestamp.min(ours.timestamp);
+
+                    sqlx::query(
+                        r#"
+                        UPDATE commands
+                        SET is_fav = ?, tags = ?, timestamp = ?, execution_time_ms = ?,
+                            exit_code = ?
+                        WHERE id = ?
+                        "#,
+                    )
+                    .bind(ours.is_fav || command.is_fav)
+                    .bind(union_tags(ours.tags.as_deref(), command.tags.as_deref()))
+                    .bind(format_timestamp(&earliest))
+                    .bind(latest.execution_time_ms)
+                    .bind(latest.exit_code)
+                    .bind(ours.id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                    *their_uses.entry(ours.id).or_default() += i64::from(command.usage_count);
+                    stats.commands_merged += 1;
+                    ours.id
+                }
+                None => {
+                    let (id,): (i64,) = sqlx::query_as(
+                        r#"
+                        INSERT INTO commands (project_path, command, timestamp, is_fav, usage_count,
+                                              execution_time_ms, exit_code, tags, context,
+                                              canonical, hash)
+                        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                        RETURNING id
+                        "#,
+                    )
+                    .bind(&project_path)
+                    .bind(&command.command)
+                    .bind(format_timestamp(&command.timestamp))
+                    .bind(command.is_fav)
+                    .bind(command.usage_count)
+                    .bind(command.execution_time_ms)
+                    .bind(command.exit_code)
+                    .bind(&command.tags)
+                    .bind(&command.context)
+                    .bind(canonical_command(&command.command))
+                    .bind(hash)
+                    .fetch_one(&mut *tx)
+                    .await?;
+                    index_stages(&mut tx, id, &command.command).await?;
+
+                    stats.commands_added += 1;
+                    id
+                }
+            };
+
+            ids.insert(command.id, id);
+        }
+
+        // Runs are matched on everything they carry. Identical runs are
+        // counted rather than checked one by one, so two real runs in the same
+        // second aren't mistaken for one that was merged before.
+        let mut runs: Vec<(&Execution, i64, usize)> = Vec::new();
+        let mut seen: HashMap<_, usize> = HashMap::new();
+        for execution in &executions {
+            let Some(&command_id) = ids.get(&execution.command_id) else {
+                continue;
+            };
+            let key = (
+                command_id,
+                execution.executed_at.as_str(),
+                execution.exit_code,
+                execution.execution_time_ms,
+                execution.session_id.as_deref(),
+            );
+            match seen.get(&key) {
+                Some(&index) => runs[index].2 += 1,
+                None => {
+                    seen.insert(key, runs.len());
+                    runs.push((execution, command_id, 1));
+                }
+            }
+        }
+
+        let mut skipped_runs: HashMap<i64, i64> = HashMap::new();
+        for (execution, command_id, count) in runs {
+            let (already_here,): (i64,) = sqlx::query_as(
+                r#"
+                SELECT COUNT(*) FROM executions
+                WHERE command_id = ? AND executed_at = ? AND exit_code IS ?
+                  AND execution_time_ms IS ? AND session_id IS ?
+                "#,
+            )
+            .bind(command_id)
+            .bind(&execution.executed_at)
+            .bind(execution.exit_code)
+            .bind(execution.execution_time_ms)
+            .bind(&execution.session_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            let skipped = count.min(already_here as usize);
+            stats.executions_skipped += skipped;
+            *skipped_runs.entry(command_id).or_default() += skipped as i64;
+
+            for _ in skipped..count {
+                sqlx::query(
+                    r#"
+                    INSERT INTO executions (command_id, executed_at, execution_time_ms, exit_code,
+                                            session_id, hostname, cwd, env, branch, interactive,
+                                            privileged, rapid_repeat_count)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                )
+                .bind(command_id)
+                .bind(&execution.executed_at)
+                .bind(execution.execution_time_ms)
+                .bind(execution.exit_code)
+                .bind(&execution.session_id)
+                .bind(&execution.hostname)
+                .bind(&execution.cwd)
+                .bind(&execution.env)
+                .bind(&execution.branch)
+                .bind(execution.interactive)
+                // Databases from before runs were marked only have the text to go on
+                .bind(
+                    execution
+                        .privileged
+                        .unwrap_or_else(|| strip_privilege(&execution.command).is_some()),
+                )
+                .bind(execution.rapid_repeat_count.unwrap_or(0))
+                .execute(&mut *tx)
+                .await?;
+
+                stats.executions_added += 1;
+            }
+        }
+
+        // A run that was here already was counted when it came over last time
+        for (id, uses) in their_uses {
+            let new_uses = uses - skipped_runs.get(&id).copied().unwrap_or(0);
+            if new_uses > 0 {
+                sqlx::query("UPDATE commands SET usage_count = usage_count + ? WHERE id = ?")
+                    .bind(new_uses)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        for alias in &aliases {
+            let added = sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO aliases (alias, command, project_path, created_at)
+                VALUES (?, ?, ?, ?)
+                "#,
+            )
+            .bind(&alias.alias)
+            .bind(&alias.command)
+            .bind(&alias.project_path)
+            .bind(&alias.created_at)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            stats.aliases_added += added as usize;
+        }
+
+        stats.edits_added = oplog::add_edits(&mut tx, &edits).await?;
+        oplog::replay(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{CommandInput, ExecutionMeta};
+
+    async fn record(db: &Database, project: &str, command: &str, ms: i32) -> i64 {
+        db.record_command(CommandInput {
+            project_path: project.to_string(),
+            command: command.to_string(),
+            execution_time_ms: Some(ms),
+            exit_code: Some(0),
+            context: None,
+        })
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn test_union_tags() {
+        assert_eq!(
+            union_tags(Some(r#"["git","ci"]"#), Some(r#"["ci","deploy"]"#)).as_deref(),
+            Some(r#"["git","ci","deploy"]"#)
+        );
+        assert_eq!(union_tags(None, None), None);
+    }
+
+    #[tokio::test]
+    async fn test_merge_from() {
+        let dir = tempfile::tempdir().unwrap();
+        let laptop = Database::new(dir.path().join("laptop.db")).await.unwrap();
+        let desktop_path = dir.path().join("desktop.db");
+        let desktop = Database::new(&desktop_path).await.unwrap();
+
+        record(&laptop, "/work/api", "cargo test", 900).await;
+        record(&desktop, "/work/api", "cargo test", 1200).await;
+        record(&desktop, "/work/api", "cargo test", 1200).await;
+        let starred = record(&desktop, "/work/api", "make release", 30_000).await;
+        desktop.toggle_favorite(starred).await.unwrap();
+        desktop
+            .create_alias("rel".to_string(), "make release".to_string(), None)
+            .await
+            .unwrap();
+        desktop.close().await;
+
+        let stats = laptop.merge_from(&desktop_path).await.unwrap();
+        assert_eq!(stats.commands_added, 1);
+        assert_eq!(stats.commands_merged, 1);
+        assert_eq!(stats.executions_added, 3);
+        assert_eq!(stats.aliases_added, 1);
+
+        let test = laptop
+            .find_command("cargo test", Some("/work/api"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(test.usage_count, 3);
+        let release = laptop
+            .find_command("make release", Some("/work/api"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(release.is_fav);
+
+        // Runs that came over last time aren't copied or counted again
+        let again = laptop.merge_from(&desktop_path).await.unwrap();
+        assert_eq!(again.executions_added, 0);
+        assert_eq!(again.executions_skipped, stats.executions_added);
+        for (command, uses) in [("cargo test", 3), ("make release", 1)] {
+            let found = laptop.find_command(command, Some("/work/api")).await.unwrap().unwrap();
+            assert_eq!(found.usage_count, uses, "{}", command);
+        }
+
+        let missing = dir.path().join("nope.db");
+        assert!(laptop.merge_from(&missing).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_merge_keeps_earliest_timestamp() {
+        let dir = tempfile::tempdir().unwrap();
+        let laptop = Database::new(dir.path().join("laptop.db")).await.unwrap();
+        let desktop_path = dir.path().join("desktop.db");
+        let desktop = Database::new(&desktop_path).await.unwrap();
+
+        for (db, at, ms, interactive) in [
+            (&laptop, "2024-06-01 12:00:00", 100, true),
+            (&desktop, "2023-01-01 09:00:00", 900, false),
+        ] {
+            let input = CommandInput {
+                project_path: "/work/api".to_string(),
+                command: "make".to_string(),
+                execution_time_ms: Some(ms),
+                exit_code: Some(0),
+                context: None,
+            };
+            let meta = ExecutionMeta {
+                executed_at: Some(at.to_string()),
+                interactive: Some(interactive),
+                ..Default::default()
+            };
+            db.record_command_with(input, &meta).await.unwrap();
+        }
+        sqlx::query("UPDATE executions SET rapid_repeat_count = 2")
+            .execute(desktop.pool())
+            .await
+            .unwrap();
+        desktop.close().await;
+        laptop.merge_from(&desktop_path).await.unwrap();
+
+        // The earlier timestamp, with how the last run went
+        let make = laptop.find_command("make", Some("/work/api")).await.unwrap().unwrap();
+        assert_eq!(format_timestamp(&make.timestamp), "2023-01-01 09:00:00");
+        assert_eq!(make.execution_time_ms, Some(100));
+
+        // The first run, from the other machine, came along
+        let (first,): (String,) = sqlx::query_as("SELECT MIN(executed_at) FROM executions")
+            .fetch_one(laptop.pool())
+            .await
+            .unwrap();
+        assert_eq!(first, "2023-01-01 09:00:00");
+
+        // Still marked as a run from a script, with its quick repeats
+        let (interactive, repeats): (Option<bool>, i64) = sqlx::query_as(
+            "SELECT interactive, rapid_repeat_count FROM executions WHERE executed_at = ?",
+        )
+        .bind("2023-01-01 09:00:00")
+        .fetch_one(laptop.pool())
+        .await
+        .unwrap();
+        assert_eq!(interactive, Some(false));
+        assert_eq!(repeats, 2);
+    }
+
+    #[tokio::test]
+    async fn test_merge_keeps_privileged_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let laptop = Database::new(dir.path().join("laptop.db")).await.unwrap();
+        let desktop_path = dir.path().join("desktop.db");
+        let desktop = Database::new(&desktop_path).await.unwrap();
+
+        // Filed without the sudo, so only the run says it was privileged
+        desktop
+            .set_preference("strip_sudo".to_string(), "true".to_string())
+            .await
+            .unwrap();
+        record(&desktop, "/work/api", "sudo apt update", 0).await;
+        desktop.close().await;
+        laptop.merge_from(&desktop_path).await.unwrap();
+
+        let update = laptop.find_command("apt update", Some("/work/api")).await.unwrap().unwrap();
+        assert!(laptop.privileged_command_ids().await.unwrap().contains(&update.id));
+    }
+
+    #[tokio::test]
+    async fn test_merge_from_another_home() {
+        let dir = tempfile::tempdir().unwrap();
+        let laptop = Database::new(dir.path().join("laptop.db")).await.unwrap();
+        let mac_path = dir.path().join("mac.db");
+        let mac = Database::new(&mac_path).await.unwrap();
+
+        // What the Mac would have stored: its own home, hashed as `~/work/api`
+        let here = dirs::home_dir().unwrap().join("work").join("api");
+        let here = here.to_string_lossy().to_string();
+        let id = record(&mac, "/Users/someone/work/api", "make", 100).await;
+        sqlx::query("UPDATE commands SET hash = ? WHERE id = ?")
+            .bind(content_hash(&here, "make"))
+            .bind(id)
+            .execute(mac.pool())
+            .await
+            .unwrap();
+        mac.close().await;
+
+        laptop.merge_from(&mac_path).await.unwrap();
+        // Filed under this machine's path, so running it here counts on the same row
+        record(&laptop, &here, "make", 100).await;
+        let make = laptop.find_command("make", Some(&here)).await.unwrap().unwrap();
+        assert_eq!(make.usage_count, 2);
+        assert_eq!(laptop.get_project_paths().await.unwrap(), vec![here]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_replays_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let laptop_path = dir.path().join("laptop.db");
+        let desktop_path = dir.path().join("desktop.db");
+        let laptop = Database::new(&laptop_path).await.unwrap();
+        let desktop = Database::new(&desktop_path).await.unwrap();
+
+        let test = record(&laptop, "/work/api", "cargo test", 900).await;
+        let secret = record(&laptop, "/work/api", "psql postgres://admin:pw@db", 50).await;
+        laptop.toggle_favorite(test).await.unwrap();
+        desktop.merge_from(&laptop_path).await.unwrap();
+
+        // The desktop saw the star, so unstarring there wins. Meanwhile the
+        // laptop deletes the secret the desktop already has.
+        let theirs = desktop
+            .find_command("cargo test", Some("/work/api"))
+            .await
+            .unwrap()
+            .unwrap();
+        desktop.toggle_favorite(theirs.id).await.unwrap();
+        laptop.delete_command(secret).await.unwrap();
+
+        let stats = laptop.merge_from(&desktop_path).await.unwrap();
+        assert_eq!(stats.edits_added, 1);
+        desktop.merge_from(&laptop_path).await.unwrap();
+
+        for db in [&laptop, &desktop] {
+            let test = db
+                .find_command("cargo test", Some("/work/api"))
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(!test.is_fav);
+            assert!(db
+                .find_command("psql postgres://admin:pw@db", Some("/work/api"))
+                .await
+                .unwrap()
+                .is_none());
+        }
+
+        // Nothing new the second time round
+        assert_eq!(laptop.merge_from(&desktop_path).await.unwrap().edits_added, 0);
+    }
+}
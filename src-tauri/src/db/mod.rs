@@ -1,11 +1,25 @@
-/// Database module for recall-cli
-///
-/// Handles all database operations using SQLite and sqlx.
-/// Implements connection pooling for performance.
+//! Database module for recall-cli
+//!
+//! Handles all database operations using SQLite and sqlx.
+//! Implements connection pooling for performance.
 
+pub mod archive;
+pub mod backup;
+pub mod canonical;
+pub mod cold;
+pub mod config;
 pub mod connection;
+pub mod identity;
+pub mod lock;
+pub mod merge;
 pub mod models;
+pub mod oplog;
 pub mod queries;
 
+pub use archive::Archive;
+pub use canonical::{canonical_command, strip_privilege};
+pub use config::Config;
 pub use connection::Database;
+pub use identity::{content_hash, spelling_hash};
+pub use merge::MergeStats;
 pub use models::*;
@@ -1,9 +1,37 @@
-/// Data models for database entities
-///
-/// All models map to database tables and use sqlx for type-safe queries.
+//! Data models for database entities
+//!
+//! All models map to database tables and use sqlx for type-safe queries.
 
+use chrono::{DateTime, NaiveDateTime, Utc};
+use crate::error::RecallError;
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::sqlite::{Sqlite, SqliteArgumentValue, SqliteTypeInfo, SqliteValueRef};
+use sqlx::{Decode, Encode, FromRow};
+use std::str::FromStr;
+
+/// Parse a timestamp as SQLite stores it
+///
+/// CURRENT_TIMESTAMP gives "YYYY-MM-DD HH:MM:SS" in UTC, while values written
+/// from Rust are RFC 3339. Accepts both.
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|naive| naive.and_utc())
+}
+
+/// Format a timestamp the way CURRENT_TIMESTAMP writes it
+///
+/// Keeps text comparisons in SQL (`ORDER BY timestamp`, `MAX(...)`) working
+/// for values written from Rust.
+pub fn format_timestamp(value: &DateTime<Utc>) -> String {
+    value.format("%Y-%m-%d %H:%M:%S").to_string()
+}
 
 /// Represents a recorded command
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -11,7 +39,7 @@ pub struct Command {
     pub id: i64,
     pub project_path: String,
     pub command: String,
-    pub timestamp: String, // ISO 8601 format from SQLite
+    pub timestamp: DateTime<Utc>,
     pub is_fav: bool,
     pub usage_count: i32,
     pub execution_time_ms: Option<i32>,
@@ -46,16 +74,109 @@ pub struct CommandInput {
     pub context: Option<String>,
 }
 
+/// A single run of a recorded command
+///
+/// Joined with its parent command so callers get the text without a second lookup.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Execution {
+    pub id: i64,
+    pub command_id: i64,
+    pub project_path: String,
+    pub command: String,
+    pub executed_at: String, // ISO 8601 format from SQLite
+    pub execution_time_ms: Option<i32>,
+    pub exit_code: Option<i32>,
+    #[sqlx(default)]
+    pub session_id: Option<String>,
+    #[sqlx(default)]
+    pub hostname: Option<String>,
+    #[sqlx(default)]
+    pub cwd: Option<String>,
+    /// Captured environment variables, a JSON object
+    #[sqlx(default)]
+    pub env: Option<String>,
+    /// Git branch checked out at the time
+    #[sqlx(default)]
+    pub branch: Option<String>,
+    /// `Some(false)` for runs from a script rather than a prompt
+    #[sqlx(default)]
+    pub interactive: Option<bool>,
+    /// Run with `sudo` or `doas`
+    #[sqlx(default)]
+    pub privileged: Option<bool>,
+    /// Identical runs right after this one that were folded into it
+    #[sqlx(default)]
+    pub rapid_repeat_count: Option<i64>,
+}
+
+/// Where and in which shell a command ran
+///
+/// Stored on the execution row, so every run keeps its own.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionMeta {
+    pub session_id: Option<String>,
+    pub hostname: Option<String>,
+    pub cwd: Option<String>,
+    /// When it actually ran, if that wasn't just now (`YYYY-MM-DD HH:MM:SS`, UTC)
+    pub executed_at: Option<String>,
+    /// Allowlisted environment variables, a JSON object
+    pub env: Option<String>,
+    /// Git branch checked out in `cwd`
+    pub branch: Option<String>,
+    /// Whether the shell was interactive, if the hook said
+    pub interactive: Option<bool>,
+}
+
+/// One shell session, summarized from its executions
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub hostname: Option<String>,
+    pub started_at: String,
+    pub ended_at: String,
+    pub command_count: i64,
+    /// Comma-separated project roots
+    pub projects: String,
+}
+
+/// Usage and outcome totals for one command
+///
+/// Built from the execution log, so runs recorded before it existed only count
+/// towards `usage_count`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CommandStats {
+    pub id: i64,
+    pub project_path: String,
+    pub command: String,
+    pub usage_count: i32,
+    pub runs: i64,
+    pub successes: i64,
+    pub failures: i64,
+    pub avg_execution_time_ms: Option<f64>,
+}
+
+impl CommandStats {
+    /// Share of runs with a known exit code that succeeded
+    pub fn success_rate(&self) -> Option<f64> {
+        let known = self.successes + self.failures;
+        if known == 0 {
+            None
+        } else {
+            Some(self.successes as f64 / known as f64)
+        }
+    }
+}
+
 /// Detected command pattern
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct CommandPattern {
     pub id: i64,
-    pub pattern_type: String, // 'sequence', 'frequency', 'time_based', 'context_based'
+    pub pattern_type: PatternType,
     pub commands: String,     // JSON array
     pub project_path: Option<String>,
     pub confidence_score: f64,
     pub occurrences: i32,
-    pub last_seen: String, // ISO 8601 format from SQLite
+    pub last_seen: DateTime<Utc>,
     pub metadata: Option<String>, // JSON
 }
 
@@ -75,7 +196,9 @@ impl CommandPattern {
 }
 
 /// Pattern types enum
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+///
+/// Stored in `command_patterns.pattern_type` as its `Display` form.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum PatternType {
     Sequential,
@@ -96,6 +219,46 @@ impl std::fmt::Display for PatternType {
     }
 }
 
+impl FromStr for PatternType {
+    type Err = RecallError;
+
+    fn from_str(s: &str) -> Result<Self, RecallError> {
+        match s {
+            "sequence" => Ok(PatternType::Sequential),
+            "frequency" => Ok(PatternType::Frequency),
+            "time_based" => Ok(PatternType::TimeBased),
+            "context_based" => Ok(PatternType::ContextBased),
+            other => Err(RecallError::Config(format!(
+                "Unknown pattern type '{}' \
+                 (expected sequence, frequency, time_based or context_based)",
+                other
+            ))),
+        }
+    }
+}
+
+impl sqlx::Type<Sqlite> for PatternType {
+    fn type_info() -> SqliteTypeInfo {
+        <str as sqlx::Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <str as sqlx::Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'q> Encode<'q, Sqlite> for PatternType {
+    fn encode_by_ref(&self, buf: &mut Vec<SqliteArgumentValue<'q>>) -> Result<IsNull, BoxDynError> {
+        Encode::<Sqlite>::encode(self.to_string(), buf)
+    }
+}
+
+impl<'r> Decode<'r, Sqlite> for PatternType {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(<&str as Decode<Sqlite>>::decode(value)?.parse()?)
+    }
+}
+
 /// Command suggestion
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Suggestion {
@@ -107,8 +270,8 @@ pub struct Suggestion {
     pub confidence: f64,
     pub times_accepted: i32,
     pub times_rejected: i32,
-    pub created_at: String, // ISO 8601 format from SQLite
-    pub last_suggested: Option<String>, // ISO 8601 format from SQLite
+    pub created_at: DateTime<Utc>,
+    pub last_suggested: Option<DateTime<Utc>>,
 }
 
 impl Suggestion {
@@ -179,7 +342,7 @@ mod tests {
             id: 1,
             project_path: "/test".to_string(),
             command: "ls".to_string(),
-            timestamp: "2025-11-25T00:00:00Z".to_string(),
+            timestamp: parse_timestamp("2025-11-25T00:00:00Z").unwrap(),
             is_fav: false,
             usage_count: 1,
             execution_time_ms: None,
@@ -206,16 +369,45 @@ mod tests {
             confidence: 0.8,
             times_accepted: 8,
             times_rejected: 2,
-            created_at: "2025-11-25T00:00:00Z".to_string(),
+            created_at: parse_timestamp("2025-11-25T00:00:00Z").unwrap(),
             last_suggested: None,
         };
 
         assert_eq!(suggestion.acceptance_rate(), 0.8);
     }
 
+    #[test]
+    fn test_command_stats_success_rate() {
+        let mut stats = CommandStats {
+            id: 1,
+            project_path: "/test".to_string(),
+            command: "npm test".to_string(),
+            usage_count: 4,
+            runs: 4,
+            successes: 3,
+            failures: 1,
+            avg_execution_time_ms: None,
+        };
+        assert_eq!(stats.success_rate(), Some(0.75));
+
+        stats.successes = 0;
+        stats.failures = 0;
+        assert_eq!(stats.success_rate(), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let sqlite = parse_timestamp("2025-11-25 10:30:00").unwrap();
+        let rfc = parse_timestamp("2025-11-25T10:30:00Z").unwrap();
+        assert_eq!(sqlite, rfc);
+        assert!(parse_timestamp("yesterday").is_none());
+    }
+
     #[test]
     fn test_pattern_type_display() {
         assert_eq!(PatternType::Sequential.to_string(), "sequence");
         assert_eq!(PatternType::TimeBased.to_string(), "time_based");
+        assert_eq!("time_based".parse::<PatternType>().unwrap(), PatternType::TimeBased);
+        assert!("sequential".parse::<PatternType>().is_err());
     }
 }
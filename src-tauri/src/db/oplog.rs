@@ -0,0 +1,261 @@
+//! Edit log for merging between machines
+//!
+//! Starring, tagging, deleting or rewriting a command appends an entry to
+//! `oplog`: which command (by content hash, the same on every machine), what
+//! changed, the device that did it and a Lamport clock. `merge` copies
+//! the other database's log over and replays the combined log, so the same
+//! set of edits ends in the same state whichever way round two databases are
+//! merged, and a command deleted on one machine doesn't come back from the
+//! other.
+//!
+//! For each command and kind of edit the entry with the highest
+//! `(clock, device)` wins. The clock is one more than any entry either side
+//! has seen, so an edit made after a merge beats everything it merged.
+
+use crate::db::Database;
+use crate::error::{RecallError, Result};
+use sqlx::{Sqlite, Transaction};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Where this database's device ID is kept
+const DEVICE_KEY: &str = "sync.device";
+
+/// What an edit changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Edit {
+    /// `value` is `1` or `0`
+    Favorite,
+    /// `value` is the whole tag list as JSON
+    Tags,
+    /// The command and every run up to `at` are gone
+    Delete,
+}
+
+impl Edit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Edit::Favorite => "favorite",
+            Edit::Tags => "tags",
+            Edit::Delete => "delete",
+        }
+    }
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Edit {
+    type Err = RecallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "favorite" => Ok(Edit::Favorite),
+            "tags" => Ok(Edit::Tags),
+            "delete" => Ok(Edit::Delete),
+            other => Err(RecallError::Config(format!("Unknown edit '{}'", other))),
+        }
+    }
+}
+
+/// One entry in the log
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct LoggedEdit {
+    pub device: String,
+    pub clock: i64,
+    pub hash: String,
+    pub edit: String,
+    pub value: Option<String>,
+    pub at: String,
+}
+
+impl LoggedEdit {
+    fn order(&self) -> (i64, &str) {
+        (self.clock, &self.device)
+    }
+}
+
+/// A device ID nobody else will have: the hostname plus when it was made
+fn new_device_id() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "{}-{:x}",
+        gethostname::gethostname().to_string_lossy(),
+        nanos
+    )
+}
+
+impl Database {
+    /// This database's device ID, made up the first time it's needed
+    pub async fn device_id(&self) -> Result<String> {
+        if let Some(id) = self.get_preference(DEVICE_KEY).await? {
+            return Ok(id);
+        }
+        let id = new_device_id();
+        self.set_preference(DEVICE_KEY.to_string(), id.clone())
+            .await?;
+        Ok(id)
+    }
+
+    /// Log `edit` for each of `ids`, reading the value from the row as it is now
+    ///
+    /// Call it after the change, except for deletes, which need the row still
+    /// there to know what was deleted.
+    pub(crate) async fn log_edits(&self, ids: &[i64], edit: Edit) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let device = self.device_id().await?;
+
+        let mut tx = self.pool().begin().await?;
+        for id in ids {
+            let row: Option<(Option<String>, bool, Option<String>)> =
+                sqlx::query_as("SELECT hash, is_fav, tags FROM commands WHERE id = ?")
+                    .bind(id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            let Some((Some(hash), is_fav, tags)) = row else {
+                continue;
+            };
+
+            let value = match edit {
+                Edit::Favorite => Some(if is_fav { "1" } else { "0" }.to_string()),
+                Edit::Tags => tags,
+                Edit::Delete => None,
+            };
+            sqlx::query(
+                r#"
+                INSERT INTO oplog (device, clock, hash, edit, value)
+                VALUES (?, (SELECT COALESCE(MAX(clock), 0) + 1 FROM oplog), ?, ?, ?)
+                "#,
+            )
+            .bind(&device)
+            .bind(&hash)
+            .bind(edit.as_str())
+            .bind(value)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Add `edits` to the log, skipping ones it already has
+///
+/// # Returns
+/// * `Ok(usize)` - How many were new
+pub(crate) async fn add_edits(
+    tx: &mut Transaction<'_, Sqlite>,
+    edits: &[LoggedEdit],
+) -> Result<usize> {
+    let mut added = 0;
+    for edit in edits {
+        added += sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO oplog (device, clock, hash, edit, value, at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&edit.device)
+        .bind(edit.clock)
+        .bind(&edit.hash)
+        .bind(&edit.edit)
+        .bind(&edit.value)
+        .bind(&edit.at)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected() as usize;
+    }
+    Ok(added)
+}
+
+/// Bring every command in line with the winning edits in the log
+///
+/// Deletes go first. A delete takes the runs up to when it happened, and the
+/// command with them unless it was run again since. Starring and tags only
+/// apply when they're newer than the command's last delete.
+pub(crate) async fn replay(tx: &mut Transaction<'_, Sqlite>) -> Result<()> {
+    let edits = sqlx::query_as::<_, LoggedEdit>("SELECT * FROM oplog")
+        .fetch_all(&mut **tx)
+        .await?;
+
+    let mut winners: HashMap<(&str, Edit), &LoggedEdit> = HashMap::new();
+    for logged in &edits {
+        let Ok(edit) = logged.edit.parse::<Edit>() else {
+            continue;
+        };
+        let key = (logged.hash.as_str(), edit);
+        match winners.get(&key) {
+            Some(current) if current.order() >= logged.order() => {}
+            _ => {
+                winners.insert(key, logged);
+            }
+        }
+    }
+
+    for ((hash, edit), logged) in &winners {
+        if *edit != Edit::Delete {
+            continue;
+        }
+        sqlx::query(
+            r#"
+            DELETE FROM executions WHERE executed_at <= ? AND command_id IN
+                (SELECT id FROM commands WHERE hash = ?)
+            "#,
+        )
+        .bind(&logged.at)
+        .bind(hash)
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query(
+            r#"
+            DELETE FROM commands WHERE hash = ?
+              AND NOT EXISTS (SELECT 1 FROM executions WHERE command_id = commands.id)
+            "#,
+        )
+        .bind(hash)
+        .execute(&mut **tx)
+        .await?;
+        // Run again since: it counts only the runs it still has
+        sqlx::query(
+            r#"
+            UPDATE commands
+            SET usage_count = (SELECT COUNT(*) FROM executions WHERE command_id = commands.id)
+            WHERE hash = ?
+            "#,
+        )
+        .bind(hash)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    for ((hash, edit), logged) in &winners {
+        let deleted_after = winners
+            .get(&(*hash, Edit::Delete))
+            .is_some_and(|delete| delete.order() > logged.order());
+        if deleted_after {
+            continue;
+        }
+
+        let query = match edit {
+            Edit::Favorite => sqlx::query("UPDATE commands SET is_fav = ? WHERE hash = ?")
+                .bind(logged.value.as_deref() == Some("1")),
+            Edit::Tags => {
+                sqlx::query("UPDATE commands SET tags = ? WHERE hash = ?").bind(&logged.value)
+            }
+            Edit::Delete => continue,
+        };
+        query.bind(hash).execute(&mut **tx).await?;
+    }
+
+    Ok(())
+}
@@ -1,12 +1,29 @@
-/// SQL query functions for database operations
-///
-/// All queries use sqlx for compile-time verification and type safety.
+//! SQL query functions for database operations
+//!
+//! All queries use sqlx for compile-time verification and type safety.
 
 use crate::db::models::*;
-use crate::db::Database;
+use crate::db::merge::union_tags;
+use crate::db::oplog::Edit;
+use crate::core::pipeline;
+use crate::db::{
+    canonical_command, content_hash, spelling_hash, strip_privilege, Config, Database,
+};
 use crate::error::Result;
-use chrono::Utc;
-use sqlx::Row;
+use chrono::{DateTime, Utc};
+use sqlx::{Row, Sqlite, Transaction};
+use std::collections::{HashMap, HashSet};
+
+/// Preference marking a pattern as noise, followed by its ID
+const PATTERN_DISMISSED_PREFIX: &str = "patterns.dismissed:";
+
+/// Preference marking a pattern as a workflow you follow, followed by its ID
+const PATTERN_ACCEPTED_PREFIX: &str = "patterns.accepted:";
+
+/// `?, ?, ?` for an `IN (...)` list of `n` values
+fn placeholders(n: usize) -> String {
+    vec!["?"; n].join(", ")
+}
 
 impl Database {
     /// Record a new command or increment usage count if it exists
@@ -18,27 +35,455 @@ impl Database {
     /// * `Ok(i64)` - The command ID
     /// * `Err(RecallError)` - If database operation fails
     pub async fn record_command(&self, input: CommandInput) -> Result<i64> {
-        let result = sqlx::query(
+        self.record_command_with(input, &ExecutionMeta::default()).await
+    }
+
+    /// Record a command along with the session, host and directory it ran in
+    ///
+    /// # Arguments
+    /// * `input` - Command input data
+    /// * `meta` - Stored on the execution row
+    ///
+    /// # Returns
+    /// * `Ok(i64)` - The command ID
+    /// * `Err(RecallError)` - If database operation fails
+    pub async fn record_command_with(
+        &self,
+        input: CommandInput,
+        meta: &ExecutionMeta,
+    ) -> Result<i64> {
+        let config = Config::load(self).await?;
+        self.record_command_using(input, meta, &config).await
+    }
+
+    /// Record a command with settings the caller already loaded
+    ///
+    /// # Arguments
+    /// * `input` - Command input data
+    /// * `meta` - Stored on the execution row
+    /// * `config` - Decides sudo stripping, canonical dedup and debouncing
+    ///
+    /// # Returns
+    /// * `Ok(i64)` - The command ID
+    /// * `Err(RecallError)` - If database operation fails
+    pub async fn record_command_using(
+        &self,
+        mut input: CommandInput,
+        meta: &ExecutionMeta,
+        config: &Config,
+    ) -> Result<i64> {
+        // `sudo apt update` is marked either way, and filed as `apt update` if asked
+        let unprivileged = strip_privilege(&input.command).map(str::to_string);
+        let privileged = unprivileged.is_some();
+        if let Some(command) = unprivileged.filter(|c| !c.is_empty() && config.strip_sudo()) {
+            input.command = command;
+        }
+        let canonical = canonical_command(&input.command);
+
+        let mut tx = self.pool().begin().await?;
+
+        // Same command typed with different quoting: count it on the row we already have
+        if config.dedup_canonical() {
+            let existing: Option<(String,)> = sqlx::query_as(
+                r#"
+                SELECT command FROM commands
+                WHERE project_path = ? AND canonical = ? AND command != ?
+                ORDER BY usage_count DESC
+                LIMIT 1
+                "#,
+            )
+            .bind(&input.project_path)
+            .bind(&canonical)
+            .bind(&input.command)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if let Some((command,)) = existing {
+                input.command = command;
+            }
+        }
+
+        // Up-arrow spam or a watch loop: the shell's last run was this same command
+        // moments ago, so count a repeat on that run instead of a new one
+        let debounce = config.debounce_seconds();
+        if debounce > 0 && meta.session_id.is_some() {
+            let last: Option<(i64, i64, bool)> = sqlx::query_as(
+                r#"
+                SELECT e.id, e.command_id,
+                       c.project_path = ? AND c.command = ?
+                       AND (julianday(COALESCE(?, CURRENT_TIMESTAMP)) - julianday(e.executed_at))
+                           * 86400 <= ?
+                FROM executions e
+                JOIN commands c ON c.id = e.command_id
+                WHERE e.session_id = ?
+                ORDER BY e.executed_at DESC, e.id DESC
+                LIMIT 1
+                "#,
+            )
+            .bind(&input.project_path)
+            .bind(&input.command)
+            .bind(&meta.executed_at)
+            .bind(debounce)
+            .bind(&meta.session_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if let Some((execution, id, true)) = last {
+                sqlx::query(
+                    r#"
+                    UPDATE executions SET rapid_repeat_count = rapid_repeat_count + 1
+                    WHERE id = ?
+                    "#,
+                )
+                .bind(execution)
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await?;
+                return Ok(id);
+            }
+        }
+
+        // Another spelling, kept apart with dedup_canonical off, can hold the hash already
+        let hash = content_hash(&input.project_path, &input.command);
+        let id = match upsert_command(&mut tx, &input, meta, &canonical, &hash).await {
+            Err(e) if is_hash_clash(&e) => {
+                let hash = spelling_hash(&input.project_path, &input.command);
+                upsert_command(&mut tx, &input, meta, &canonical, &hash).await?
+            }
+            result => result?,
+        };
+        if input.command.contains(['|', '&', ';', '\n']) {
+            index_stages(&mut tx, id, &input.command).await?;
+        }
+
+        // Keep every run so sequences and success rates survive the dedup above
+        sqlx::query(
             r#"
-            INSERT INTO commands (project_path, command, execution_time_ms, exit_code, context)
-            VALUES (?, ?, ?, ?, ?)
-            ON CONFLICT(project_path, command) DO UPDATE SET
-                usage_count = usage_count + 1,
-                timestamp = CURRENT_TIMESTAMP,
-                execution_time_ms = excluded.execution_time_ms,
-                exit_code = excluded.exit_code
-            RETURNING id
+            INSERT INTO executions (command_id, executed_at, execution_time_ms, exit_code,
+                                    session_id, hostname, cwd, env, branch, interactive,
+                                    privileged)
+            VALUES (?, COALESCE(?, CURRENT_TIMESTAMP), ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
-        .bind(&input.project_path)
-        .bind(&input.command)
+        .bind(id)
+        .bind(&meta.executed_at)
         .bind(input.execution_time_ms)
         .bind(input.exit_code)
-        .bind(input.context)
+        .bind(&meta.session_id)
+        .bind(&meta.hostname)
+        .bind(&meta.cwd)
+        .bind(&meta.env)
+        .bind(&meta.branch)
+        .bind(meta.interactive)
+        .bind(privileged)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    /// Fill in the content hash for commands recorded (or imported) without one
+    pub async fn backfill_hash(&self) -> Result<()> {
+        let rows: Vec<(i64, String, String)> = sqlx::query_as(
+            "SELECT id, project_path, command FROM commands WHERE hash IS NULL",
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        let mut tx = self.pool().begin().await?;
+        for (id, project_path, command) in rows {
+            let hash = free_hash(&mut tx, id, &project_path, &command).await?;
+            sqlx::query("UPDATE commands SET hash = ? WHERE id = ?")
+                .bind(hash)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Index the stages of compound commands recorded before stages were
+    pub async fn backfill_stages(&self) -> Result<()> {
+        let rows: Vec<(i64, String)> = sqlx::query_as(
+            r#"
+            SELECT id, command FROM commands
+            WHERE command LIKE '%|%' OR command LIKE '%&%' OR command LIKE '%;%'
+               OR command LIKE '%' || char(10) || '%'
+            "#,
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        let mut tx = self.pool().begin().await?;
+        for (id, command) in rows {
+            index_stages(&mut tx, id, &command).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Mark the runs of `sudo` and `doas` commands recorded before runs were marked
+    pub async fn backfill_privileged(&self) -> Result<()> {
+        let rows: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT id, command FROM commands WHERE command LIKE 'sudo%' OR command LIKE 'doas%'",
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        let mut tx = self.pool().begin().await?;
+        for (id, command) in rows {
+            if strip_privilege(&command).is_some() {
+                sqlx::query("UPDATE executions SET privileged = 1 WHERE command_id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Fill in the canonical form for commands recorded before it existed
+    pub async fn backfill_canonical(&self) -> Result<()> {
+        let rows: Vec<(i64, String)> =
+            sqlx::query_as("SELECT id, command FROM commands WHERE canonical IS NULL")
+                .fetch_all(self.pool())
+                .await?;
+
+        let mut tx = self.pool().begin().await?;
+        for (id, command) in rows {
+            sqlx::query("UPDATE commands SET canonical = ? WHERE id = ?")
+                .bind(canonical_command(&command))
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Get the execution history in the order commands were run
+    ///
+    /// Runs from scripts (the hook saw a non-interactive shell) are left out.
+    ///
+    /// # Arguments
+    /// * `project_path` - Optional project path filter (None for all projects)
+    /// * `limit` - Maximum number of executions to return (most recent ones win)
+    ///
+    /// # Returns
+    /// * `Ok(Vec<Execution>)` - Executions, oldest first
+    pub async fn get_execution_history(
+        &self,
+        project_path: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Execution>> {
+        let mut executions = if let Some(path) = project_path {
+            sqlx::query_as::<_, Execution>(
+                r#"
+                SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                       e.execution_time_ms, e.exit_code
+                FROM executions e
+                JOIN commands c ON c.id = e.command_id
+                WHERE c.project_path = ? AND e.interactive IS NOT 0
+                ORDER BY e.id DESC
+                LIMIT ?
+                "#,
+            )
+            .bind(path)
+            .bind(limit)
+            .fetch_all(self.pool())
+            .await?
+        } else {
+            sqlx::query_as::<_, Execution>(
+                r#"
+                SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                       e.execution_time_ms, e.exit_code
+                FROM executions e
+                JOIN commands c ON c.id = e.command_id
+                WHERE e.interactive IS NOT 0
+                ORDER BY e.id DESC
+                LIMIT ?
+                "#,
+            )
+            .bind(limit)
+            .fetch_all(self.pool())
+            .await?
+        };
+
+        executions.reverse();
+
+        Ok(executions)
+    }
+
+    /// List shell sessions, most recent first
+    ///
+    /// Runs recorded without a session (older hooks, manual `record`) are left out.
+    ///
+    /// # Arguments
+    /// * `limit` - Maximum number of sessions to return
+    ///
+    /// # Returns
+    /// * `Ok(Vec<SessionSummary>)` - Sessions, newest first
+    pub async fn get_sessions(&self, limit: i64) -> Result<Vec<SessionSummary>> {
+        let sessions = sqlx::query_as::<_, SessionSummary>(
+            r#"
+            SELECT e.session_id, MAX(e.hostname) AS hostname,
+                   MIN(e.executed_at) AS started_at, MAX(e.executed_at) AS ended_at,
+                   COUNT(*) AS command_count,
+                   GROUP_CONCAT(DISTINCT c.project_path) AS projects
+            FROM executions e
+            JOIN commands c ON c.id = e.command_id
+            WHERE e.session_id IS NOT NULL
+            GROUP BY e.session_id
+            ORDER BY MAX(e.id) DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(sessions)
+    }
+
+    /// Find the sessions whose ID starts with `prefix`
+    pub async fn find_sessions(&self, prefix: &str) -> Result<Vec<String>> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            r#"
+            SELECT DISTINCT session_id FROM executions
+            WHERE substr(session_id, 1, length(?1)) = ?1
+            "#,
+        )
+        .bind(prefix)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    /// Everything run in one session, oldest first
+    pub async fn get_session_executions(&self, session_id: &str) -> Result<Vec<Execution>> {
+        let executions = sqlx::query_as::<_, Execution>(
+            r#"
+            SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                   e.execution_time_ms, e.exit_code, e.session_id, e.hostname, e.cwd
+            FROM executions e
+            JOIN commands c ON c.id = e.command_id
+            WHERE e.session_id = ?
+            ORDER BY e.id
+            "#,
+        )
+        .bind(session_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(executions)
+    }
+
+    /// Everything run between two times, oldest first
+    ///
+    /// # Arguments
+    /// * `project_path` - Optional project path filter (None for all projects)
+    /// * `from` - Start of the window (inclusive)
+    /// * `to` - End of the window (inclusive)
+    ///
+    /// # Returns
+    /// * `Ok(Vec<Execution>)` - Executions, oldest first
+    pub async fn get_executions_in_range(
+        &self,
+        project_path: Option<&str>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Execution>> {
+        // executed_at is stored as 'YYYY-MM-DD HH:MM:SS' in UTC, which compares as text
+        let format = "%Y-%m-%d %H:%M:%S";
+
+        let executions = sqlx::query_as::<_, Execution>(
+            r#"
+            SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                   e.execution_time_ms, e.exit_code, e.session_id, e.hostname, e.cwd,
+                   e.interactive
+            FROM executions e
+            JOIN commands c ON c.id = e.command_id
+            WHERE (?1 IS NULL OR c.project_path = ?1)
+              AND e.executed_at >= ?2 AND e.executed_at <= ?3
+            ORDER BY e.executed_at, e.id
+            "#,
+        )
+        .bind(project_path)
+        .bind(from.format(format).to_string())
+        .bind(to.format(format).to_string())
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(executions)
+    }
+
+    /// Get the executions with an ID in `after_id+1..=up_to_id`
+    ///
+    /// Runs from scripts are left out, like in `get_execution_history`.
+    ///
+    /// # Arguments
+    /// * `project_path` - Optional project path filter (None for all projects)
+    /// * `after_id` - Only executions newer than this one
+    /// * `up_to_id` - Only executions up to and including this one
+    /// * `limit` - Maximum number of executions to return (most recent ones win)
+    ///
+    /// # Returns
+    /// * `Ok(Vec<Execution>)` - Executions, oldest first
+    pub async fn get_executions_between(
+        &self,
+        project_path: Option<&str>,
+        after_id: i64,
+        up_to_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Execution>> {
+        let mut executions = sqlx::query_as::<_, Execution>(
+            r#"
+            SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                   e.execution_time_ms, e.exit_code
+            FROM executions e
+            JOIN commands c ON c.id = e.command_id
+            WHERE (?1 IS NULL OR c.project_path = ?1)
+              AND e.id > ?2 AND e.id <= ?3 AND e.interactive IS NOT 0
+            ORDER BY e.id DESC
+            LIMIT ?4
+            "#,
+        )
+        .bind(project_path)
+        .bind(after_id)
+        .bind(up_to_id)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        executions.reverse();
+
+        Ok(executions)
+    }
+
+    /// Count every recorded run for a project (None for all projects)
+    pub async fn count_executions(&self, project_path: Option<&str>) -> Result<i64> {
+        let count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*)
+            FROM executions e
+            JOIN commands c ON c.id = e.command_id
+            WHERE ?1 IS NULL OR c.project_path = ?1
+            "#,
+        )
+        .bind(project_path)
         .fetch_one(self.pool())
         .await?;
 
-        Ok(result.get(0))
+        Ok(count.0)
     }
 
     /// Get recent commands for a project
@@ -56,7 +501,7 @@ impl Database {
     ) -> Result<Vec<Command>> {
         let commands = if let Some(path) = project_path {
             sqlx::query_as::<_, Command>(
-                "SELECT * FROM commands WHERE project_path = ? ORDER BY timestamp DESC LIMIT ?",
+                "SELECT * FROM commands WHERE project_path = ? ORDER BY timestamp DESC, id DESC LIMIT ?",
             )
             .bind(path)
             .bind(limit)
@@ -64,7 +509,7 @@ impl Database {
             .await?
         } else {
             sqlx::query_as::<_, Command>(
-                "SELECT * FROM commands ORDER BY timestamp DESC LIMIT ?",
+                "SELECT * FROM commands ORDER BY timestamp DESC, id DESC LIMIT ?",
             )
             .bind(limit)
             .fetch_all(self.pool())
@@ -74,6 +519,113 @@ impl Database {
         Ok(commands)
     }
 
+    /// Get every run with a recorded duration, oldest first
+    ///
+    /// # Arguments
+    /// * `project_path` - Optional project path filter (None for all projects)
+    pub async fn get_timed_executions(&self, project_path: Option<&str>) -> Result<Vec<Execution>> {
+        let executions = if let Some(path) = project_path {
+            sqlx::query_as::<_, Execution>(
+                r#"
+                SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                       e.execution_time_ms, e.exit_code
+                FROM executions e
+                JOIN commands c ON c.id = e.command_id
+                WHERE c.project_path = ? AND e.execution_time_ms IS NOT NULL
+                ORDER BY e.id
+                "#,
+            )
+            .bind(path)
+            .fetch_all(self.pool())
+            .await?
+        } else {
+            sqlx::query_as::<_, Execution>(
+                r#"
+                SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                       e.execution_time_ms, e.exit_code
+                FROM executions e
+                JOIN commands c ON c.id = e.command_id
+                WHERE e.execution_time_ms IS NOT NULL
+                ORDER BY e.id
+                "#,
+            )
+            .fetch_all(self.pool())
+            .await?
+        };
+
+        Ok(executions)
+    }
+
+    /// Get the most recent run that exited non-zero
+    ///
+    /// # Arguments
+    /// * `project_path` - Optional project path filter (None for all projects)
+    pub async fn get_last_failed_execution(
+        &self,
+        project_path: Option<&str>,
+    ) -> Result<Option<Execution>> {
+        let execution = if let Some(path) = project_path {
+            sqlx::query_as::<_, Execution>(
+                r#"
+                SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                       e.execution_time_ms, e.exit_code
+                FROM executions e
+                JOIN commands c ON c.id = e.command_id
+                WHERE c.project_path = ? AND e.exit_code IS NOT NULL AND e.exit_code != 0
+                ORDER BY e.id DESC
+                LIMIT 1
+                "#,
+            )
+            .bind(path)
+            .fetch_optional(self.pool())
+            .await?
+        } else {
+            sqlx::query_as::<_, Execution>(
+                r#"
+                SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                       e.execution_time_ms, e.exit_code
+                FROM executions e
+                JOIN commands c ON c.id = e.command_id
+                WHERE e.exit_code IS NOT NULL AND e.exit_code != 0
+                ORDER BY e.id DESC
+                LIMIT 1
+                "#,
+            )
+            .fetch_optional(self.pool())
+            .await?
+        };
+
+        Ok(execution)
+    }
+
+    /// Get every project with recorded commands, most recently active first
+    pub async fn get_project_paths(&self) -> Result<Vec<String>> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT project_path FROM commands GROUP BY project_path ORDER BY MAX(timestamp) DESC, project_path",
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.into_iter().map(|(path,)| path).collect())
+    }
+
+    /// Every project with how many commands it has and when one last ran,
+    /// most recently active first
+    pub async fn get_project_activity(&self) -> Result<Vec<(String, i64, String)>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT project_path, COUNT(*), MAX(timestamp)
+            FROM commands
+            GROUP BY project_path
+            ORDER BY MAX(timestamp) DESC, project_path
+            "#,
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows)
+    }
+
     /// Get most used commands for a project
     ///
     /// # Arguments
@@ -104,6 +656,44 @@ impl Database {
         Ok(commands)
     }
 
+    /// Get most used commands with success rate and average duration
+    ///
+    /// # Arguments
+    /// * `project_path` - Optional project path filter (None for all projects)
+    /// * `tool` - Optional first word to filter on (e.g. "git")
+    /// * `limit` - Maximum number of commands to return
+    pub async fn get_command_stats(
+        &self,
+        project_path: Option<&str>,
+        tool: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<CommandStats>> {
+        // NULL binds switch a filter off
+        let stats = sqlx::query_as::<_, CommandStats>(
+            r#"
+            SELECT c.id, c.project_path, c.command, c.usage_count,
+                   COUNT(e.id) AS runs,
+                   COALESCE(SUM(CASE WHEN e.exit_code = 0 THEN 1 ELSE 0 END), 0) AS successes,
+                   COALESCE(SUM(CASE WHEN e.exit_code != 0 THEN 1 ELSE 0 END), 0) AS failures,
+                   AVG(e.execution_time_ms) AS avg_execution_time_ms
+            FROM commands c
+            LEFT JOIN executions e ON e.command_id = c.id
+            WHERE (?1 IS NULL OR c.project_path = ?1)
+              AND (?2 IS NULL OR c.command = ?2 OR c.command LIKE ?2 || ' %')
+            GROUP BY c.id
+            ORDER BY c.usage_count DESC, c.id DESC
+            LIMIT ?3
+            "#,
+        )
+        .bind(project_path)
+        .bind(tool)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(stats)
+    }
+
     /// Get favorite commands
     ///
     /// # Arguments
@@ -138,6 +728,7 @@ impl Database {
         .bind(command_id)
         .fetch_one(self.pool())
         .await?;
+        self.log_edits(&[command_id], Edit::Favorite).await?;
 
         Ok(result.get(0))
     }
@@ -178,27 +769,714 @@ impl Database {
         Ok(commands)
     }
 
-    /// Get command by ID
-    pub async fn get_command_by_id(&self, id: i64) -> Result<Option<Command>> {
-        let command = sqlx::query_as::<_, Command>("SELECT * FROM commands WHERE id = ?")
-            .bind(id)
-            .fetch_optional(self.pool())
-            .await?;
-
-        Ok(command)
-    }
-
-    /// Delete a command
-    pub async fn delete_command(&self, id: i64) -> Result<()> {
-        sqlx::query("DELETE FROM commands WHERE id = ?")
-            .bind(id)
-            .execute(self.pool())
-            .await?;
+    /// IDs of commands that ran with an environment variable captured
+    ///
+    /// # Arguments
+    /// * `name` - Variable name, as listed in `capture_env`
+    /// * `value` - Exact value it had, `None` for any
+    pub async fn command_ids_with_env(
+        &self,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<HashSet<i64>> {
+        let rows: Vec<(i64,)> = sqlx::query_as(
+            r#"
+            SELECT DISTINCT command_id FROM executions
+            WHERE env IS NOT NULL
+              AND json_extract(env, '$."' || ?1 || '"') IS NOT NULL
+              AND (?2 IS NULL OR json_extract(env, '$."' || ?1 || '"') = ?2)
+            "#,
+        )
+        .bind(name)
+        .bind(value)
+        .fetch_all(self.pool())
+        .await?;
 
-        Ok(())
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    /// Whether `command` has a run on `hostname`, in `project_path` if given
+    ///
+    /// Runs recorded before hosts were aren't on any.
+    pub async fn ran_on_host(
+        &self,
+        command: &str,
+        project_path: Option<&str>,
+        hostname: &str,
+    ) -> Result<bool> {
+        let found: Option<(i64,)> = sqlx::query_as(
+            r#"
+            SELECT 1 FROM executions e
+            JOIN commands c ON c.id = e.command_id
+            WHERE c.command = ?1 AND (?2 IS NULL OR c.project_path = ?2) AND e.hostname = ?3
+            LIMIT 1
+            "#,
+        )
+        .bind(command)
+        .bind(project_path)
+        .bind(hostname)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(found.is_some())
+    }
+
+    /// IDs of commands run at least once on a git branch
+    pub async fn command_ids_on_branch(&self, branch: &str) -> Result<HashSet<i64>> {
+        let rows: Vec<(i64,)> =
+            sqlx::query_as("SELECT DISTINCT command_id FROM executions WHERE branch = ?")
+                .bind(branch)
+                .fetch_all(self.pool())
+                .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    /// IDs of commands run at least once from a prompt (`true`) or from a
+    /// script (`false`)
+    ///
+    /// Runs recorded before hooks said which count as from a prompt.
+    pub async fn command_ids_by_interactive(&self, interactive: bool) -> Result<HashSet<i64>> {
+        let rows: Vec<(i64,)> = sqlx::query_as(
+            "SELECT DISTINCT command_id FROM executions WHERE (interactive IS NOT 0) = ?",
+        )
+        .bind(interactive)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    /// IDs of commands run at least once through `sudo` or `doas`
+    pub async fn privileged_command_ids(&self) -> Result<HashSet<i64>> {
+        let rows: Vec<(i64,)> =
+            sqlx::query_as("SELECT DISTINCT command_id FROM executions WHERE privileged")
+                .fetch_all(self.pool())
+                .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    /// The stages of compound commands that run `program`, each with the
+    /// uses of the command it's in
+    pub async fn stage_uses(
+        &self,
+        program: &str,
+        project_path: Option<&str>,
+    ) -> Result<Vec<(String, i64)>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT s.stage, c.usage_count
+            FROM command_stages s
+            JOIN commands c ON c.id = s.command_id
+            WHERE s.program = ?1 AND (?2 IS NULL OR c.project_path = ?2)
+            "#,
+        )
+        .bind(program)
+        .bind(project_path)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Pipelines whose first stage is `first_stage`, most used first
+    ///
+    /// The same line in several projects counts once, with its uses added up.
+    pub async fn pipelines_from(
+        &self,
+        first_stage: &str,
+        project_path: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<(String, i64)>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT c.command, SUM(c.usage_count) AS uses
+            FROM command_stages s
+            JOIN command_stages next ON next.command_id = s.command_id AND next.position = 1
+            JOIN commands c ON c.id = s.command_id
+            WHERE s.position = 0 AND s.stage = ?1 AND next.joint = 'pipe'
+              AND (?2 IS NULL OR c.project_path = ?2)
+            GROUP BY c.command
+            ORDER BY uses DESC, MAX(c.timestamp) DESC
+            LIMIT ?3
+            "#,
+        )
+        .bind(first_stage)
+        .bind(project_path)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// The session each of `ids` last ran in, for those that have one
+    pub async fn last_sessions(&self, ids: &[i64]) -> Result<HashMap<i64, String>> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let sql = format!(
+            "SELECT command_id, session_id FROM executions \
+             WHERE session_id IS NOT NULL AND command_id IN ({}) \
+             ORDER BY executed_at, id",
+            placeholders(ids.len())
+        );
+        let mut query = sqlx::query_as::<_, (i64, String)>(&sql);
+        for id in ids {
+            query = query.bind(id);
+        }
+
+        // Later runs overwrite earlier ones
+        Ok(query.fetch_all(self.pool()).await?.into_iter().collect())
+    }
+
+    /// How often each command in a project ran on `branch`
+    ///
+    /// Returns `(command, runs on the branch, runs on any branch)`, most runs on
+    /// it first. Runs recorded outside a repo (or before branches were kept)
+    /// don't count either way.
+    pub async fn branch_usage(
+        &self,
+        project_path: &str,
+        branch: &str,
+    ) -> Result<Vec<(String, i64, i64)>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT c.command,
+                   SUM(CASE WHEN e.branch = ?2 THEN 1 ELSE 0 END) AS on_branch,
+                   COUNT(*) AS runs
+            FROM executions e
+            JOIN commands c ON c.id = e.command_id
+            WHERE c.project_path = ?1 AND e.branch IS NOT NULL
+            GROUP BY c.id
+            ORDER BY on_branch DESC, runs DESC
+            "#,
+        )
+        .bind(project_path)
+        .bind(branch)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Which value of a captured `key` each command in a project runs with
+    ///
+    /// Returns `(command, value, runs, total)` most runs first, where `total`
+    /// counts every run of the command since the value was first seen (older
+    /// runs predate capturing it).
+    pub async fn context_usage(
+        &self,
+        project_path: &str,
+        key: &str,
+    ) -> Result<Vec<(String, String, i64, i64)>> {
+        let rows = sqlx::query_as(
+            r#"
+            WITH tagged AS (
+                SELECT e.command_id,
+                       json_extract(e.env, '$."' || ?2 || '"') AS value,
+                       COUNT(*) AS runs,
+                       MIN(e.executed_at) AS since
+                FROM executions e
+                JOIN commands c ON c.id = e.command_id
+                WHERE c.project_path = ?1 AND e.env IS NOT NULL
+                GROUP BY e.command_id, value
+                HAVING value IS NOT NULL
+            )
+            SELECT c.command, t.value, t.runs,
+                   (SELECT COUNT(*) FROM executions e
+                    WHERE e.command_id = t.command_id AND e.executed_at >= t.since) AS total
+            FROM tagged t
+            JOIN commands c ON c.id = t.command_id
+            ORDER BY t.runs DESC, c.command
+            "#,
+        )
+        .bind(project_path)
+        .bind(key)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// The last successful run in a project of any of `commands`
+    ///
+    /// Extra arguments still match (`npm install` covers `npm install --force`,
+    /// not `npm installer`).
+    ///
+    /// # Returns
+    /// * `Ok(Some((command, executed_at)))` - What ran and when
+    pub async fn last_successful_run(
+        &self,
+        project_path: &str,
+        commands: &[&str],
+    ) -> Result<Option<(String, String)>> {
+        if commands.is_empty() {
+            return Ok(None);
+        }
+
+        let matches =
+            vec!["c.command = ? OR c.command LIKE ? || ' %'"; commands.len()].join(" OR ");
+        let sql = format!(
+            r#"
+            SELECT c.command, e.executed_at FROM executions e
+            JOIN commands c ON c.id = e.command_id
+            WHERE c.project_path = ?
+              AND (e.exit_code = 0 OR e.exit_code IS NULL)
+              AND ({})
+            ORDER BY e.executed_at DESC, e.id DESC
+            LIMIT 1
+            "#,
+            matches
+        );
+        let mut query = sqlx::query_as(&sql).bind(project_path);
+        for command in commands {
+            query = query.bind(*command).bind(*command);
+        }
+
+        Ok(query.fetch_optional(self.pool()).await?)
+    }
+
+    /// Get command by ID
+    pub async fn get_command_by_id(&self, id: i64) -> Result<Option<Command>> {
+        let command = sqlx::query_as::<_, Command>("SELECT * FROM commands WHERE id = ?")
+            .bind(id)
+            .fetch_optional(self.pool())
+            .await?;
+
+        Ok(command)
+    }
+
+    /// Find a command by its exact text
+    ///
+    /// Without a project filter the most used match across projects wins.
+    pub async fn find_command(
+        &self,
+        command: &str,
+        project_path: Option<&str>,
+    ) -> Result<Option<Command>> {
+        let found = if let Some(path) = project_path {
+            sqlx::query_as::<_, Command>(
+                "SELECT * FROM commands WHERE project_path = ? AND command = ?",
+            )
+            .bind(path)
+            .bind(command)
+            .fetch_optional(self.pool())
+            .await?
+        } else {
+            sqlx::query_as::<_, Command>(
+                "SELECT * FROM commands WHERE command = ? ORDER BY usage_count DESC LIMIT 1",
+            )
+            .bind(command)
+            .fetch_optional(self.pool())
+            .await?
+        };
+
+        Ok(found)
+    }
+
+    /// Delete a command
+    pub async fn delete_command(&self, id: i64) -> Result<()> {
+        self.log_edits(&[id], Edit::Delete).await?;
+        sqlx::query("DELETE FROM commands WHERE id = ?")
+            .bind(id)
+            .execute(self.pool())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete several commands at once, with their runs
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - How many existed and were deleted
+    pub async fn delete_many(&self, ids: &[i64]) -> Result<usize> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        self.log_edits(ids, Edit::Delete).await?;
+        let sql = format!("DELETE FROM commands WHERE id IN ({})", placeholders(ids.len()));
+        let mut query = sqlx::query(&sql);
+        for id in ids {
+            query = query.bind(id);
+        }
+
+        Ok(query.execute(self.pool()).await?.rows_affected() as usize)
+    }
+
+    /// Add `tag` to several commands at once
+    ///
+    /// Commands that already have it are left alone.
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - How many got the tag
+    pub async fn tag_many(&self, ids: &[i64], tag: &str) -> Result<usize> {
+        let mut tx = self.pool().begin().await?;
+        let mut tagged = Vec::new();
+
+        for id in ids {
+            let row: Option<(Option<String>,)> =
+                sqlx::query_as("SELECT tags FROM commands WHERE id = ?")
+                    .bind(id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            let Some((tags,)) = row else {
+                continue;
+            };
+
+            let mut tags: Vec<String> = tags
+                .and_then(|t| serde_json::from_str(&t).ok())
+                .unwrap_or_default();
+            if tags.iter().any(|t| t == tag) {
+                continue;
+            }
+            tags.push(tag.to_string());
+
+            sqlx::query("UPDATE commands SET tags = ? WHERE id = ?")
+                .bind(serde_json::to_string(&tags)?)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            tagged.push(*id);
+        }
+
+        tx.commit().await?;
+        self.log_edits(&tagged, Edit::Tags).await?;
+        Ok(tagged.len())
+    }
+
+    /// Favorite (or unfavorite) several commands at once
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - How many commands exist among `ids`
+    pub async fn set_favorite_many(&self, ids: &[i64], favorite: bool) -> Result<usize> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let sql = format!(
+            "UPDATE commands SET is_fav = ? WHERE id IN ({})",
+            placeholders(ids.len())
+        );
+        let mut query = sqlx::query(&sql).bind(favorite);
+        for id in ids {
+            query = query.bind(id);
+        }
+
+        let changed = query.execute(self.pool()).await?.rows_affected() as usize;
+        self.log_edits(ids, Edit::Favorite).await?;
+        Ok(changed)
+    }
+
+    /// Forget the most recent run, as if it had never been recorded
+    ///
+    /// The command row goes too when that was its only run, otherwise its
+    /// usage count and last run details roll back to the run before.
+    ///
+    /// # Arguments
+    /// * `session_id` - Only look at runs from this shell session
+    /// * `project_path` - Only look at runs in this project
+    ///
+    /// # Returns
+    /// * `Ok(Some(Execution))` - The run that was removed
+    /// * `Ok(None)` - Nothing matched
+    pub async fn undo_last_execution(
+        &self,
+        session_id: Option<&str>,
+        project_path: Option<&str>,
+    ) -> Result<Option<Execution>> {
+        let mut tx = self.pool().begin().await?;
+
+        let last = sqlx::query_as::<_, Execution>(
+            r#"
+            SELECT e.id, e.command_id, c.project_path, c.command, e.executed_at,
+                   e.execution_time_ms, e.exit_code, e.session_id, e.hostname, e.cwd
+            FROM executions e
+            JOIN commands c ON c.id = e.command_id
+            WHERE (?1 IS NULL OR e.session_id = ?1)
+              AND (?2 IS NULL OR c.project_path = ?2)
+            ORDER BY e.id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(session_id)
+        .bind(project_path)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(last) = last else {
+            return Ok(None);
+        };
+
+        sqlx::query("DELETE FROM executions WHERE id = ?")
+            .bind(last.id)
+            .execute(&mut *tx)
+            .await?;
+
+        let previous: Option<(String, Option<i32>, Option<i32>)> = sqlx::query_as(
+            r#"
+            SELECT executed_at, execution_time_ms, exit_code FROM executions
+            WHERE command_id = ?
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(last.command_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        match previous {
+            Some((executed_at, execution_time_ms, exit_code)) => {
+                sqlx::query(
+                    r#"
+                    UPDATE commands
+                    SET usage_count = MAX(usage_count - 1, 1), timestamp = ?,
+                        execution_time_ms = ?, exit_code = ?
+                    WHERE id = ?
+                    "#,
+                )
+                .bind(executed_at)
+                .bind(execution_time_ms)
+                .bind(exit_code)
+                .bind(last.command_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM commands WHERE id = ?")
+                    .bind(last.command_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(Some(last))
+    }
+
+    /// Change the text of a stored command
+    ///
+    /// If the project already has a command with the new text, the two are
+    /// folded together: runs move over, usage counts add up, and the favorite
+    /// and the tags of both are kept.
+    pub async fn rewrite_command(&self, id: i64, new_command: &str) -> Result<()> {
+        // Other machines drop the old text when they merge this
+        self.log_edits(&[id], Edit::Delete).await?;
+        let mut tx = self.pool().begin().await?;
+
+        type Fold = (i64, Option<String>, i64, bool, Option<String>, String);
+        let existing: Option<Fold> = sqlx::query_as(
+            r#"
+            SELECT other.id, other.tags, old.usage_count, old.is_fav, old.tags, old.timestamp
+            FROM commands old
+            JOIN commands other
+              ON other.project_path = old.project_path AND other.command = ? AND other.id != old.id
+            WHERE old.id = ?
+            "#,
+        )
+        .bind(new_command)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        match existing {
+            Some((other_id, other_tags, usage_count, is_fav, tags, timestamp)) => {
+                sqlx::query("UPDATE executions SET command_id = ? WHERE command_id = ?")
+                    .bind(other_id)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query(
+                    r#"
+                    UPDATE commands
+                    SET usage_count = usage_count + ?, is_fav = MAX(is_fav, ?), tags = ?,
+                        timestamp = MAX(timestamp, ?)
+                    WHERE id = ?
+                    "#,
+                )
+                .bind(usage_count)
+                .bind(is_fav)
+                .bind(union_tags(other_tags.as_deref(), tags.as_deref()))
+                .bind(timestamp)
+                .bind(other_id)
+                .execute(&mut *tx)
+                .await?;
+                sqlx::query("DELETE FROM commands WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            None => {
+                let project: Option<(String,)> =
+                    sqlx::query_as("SELECT project_path FROM commands WHERE id = ?")
+                        .bind(id)
+                        .fetch_optional(&mut *tx)
+                        .await?;
+                if let Some((project_path,)) = project {
+                    let hash = free_hash(&mut tx, id, &project_path, new_command).await?;
+                    sqlx::query(
+                        "UPDATE commands SET command = ?, canonical = ?, hash = ? WHERE id = ?",
+                    )
+                    .bind(new_command)
+                    .bind(canonical_command(new_command))
+                    .bind(hash)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                    index_stages(&mut tx, id, new_command).await?;
+                }
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Move everything recorded under project `from` to project `to`
+    ///
+    /// For history that ended up under a path that's gone (the project was
+    /// moved or renamed). Commands `to` already has are folded together like
+    /// `rewrite_command` does, keeping the favorite and the tags of both.
+    /// Aliases come along; the patterns and suggestions of `from` are dropped
+    /// and the next analyze rebuilds them.
+    ///
+    /// Nothing goes in the edit log: on another machine `from` may still be
+    /// where that project lives.
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - How many commands were moved
+    pub async fn reassign_project(&self, from: &str, to: &str) -> Result<usize> {
+        if from == to {
+            return Ok(0);
+        }
+        let mut tx = self.pool().begin().await?;
+
+        let moving: Vec<(i64, String, i64, bool, Option<String>, String)> = sqlx::query_as(
+            r#"
+            SELECT id, command, usage_count, is_fav, tags, timestamp
+            FROM commands WHERE project_path = ?
+            "#,
+        )
+        .bind(from)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for (id, command, usage_count, is_fav, tags, timestamp) in &moving {
+            let existing: Option<(i64, Option<String>)> = sqlx::query_as(
+                "SELECT id, tags FROM commands WHERE project_path = ? AND command = ?",
+            )
+            .bind(to)
+            .bind(command)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            match existing {
+                Some((other_id, other_tags)) => {
+                    sqlx::query("UPDATE executions SET command_id = ? WHERE command_id = ?")
+                        .bind(other_id)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                    sqlx::query(
+                        r#"
+                        UPDATE commands
+                        SET usage_count = usage_count + ?, is_fav = MAX(is_fav, ?), tags = ?,
+                            timestamp = MAX(timestamp, ?)
+                        WHERE id = ?
+                        "#,
+                    )
+                    .bind(usage_count)
+                    .bind(is_fav)
+                    .bind(union_tags(other_tags.as_deref(), tags.as_deref()))
+                    .bind(timestamp)
+                    .bind(other_id)
+                    .execute(&mut *tx)
+                    .await?;
+                    sqlx::query("DELETE FROM commands WHERE id = ?")
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                None => {
+                    let hash = free_hash(&mut tx, *id, to, command).await?;
+                    sqlx::query("UPDATE commands SET project_path = ?, hash = ? WHERE id = ?")
+                        .bind(to)
+                        .bind(hash)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+        }
+
+        sqlx::query("UPDATE aliases SET project_path = ? WHERE project_path = ?")
+            .bind(to)
+            .bind(from)
+            .execute(&mut *tx)
+            .await?;
+        for table in ["command_patterns", "suggestions"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE project_path = ?", table))
+                .bind(from)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(moving.len())
+    }
+
+    /// Drop patterns and suggestions built from commands `matches` picks out
+    ///
+    /// They're derived from history, so they come back (clean) on the next analyze.
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - How many rows were removed
+    pub async fn forget_derived<F>(&self, matches: F) -> Result<usize>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let patterns: Vec<(i64, String)> =
+            sqlx::query_as("SELECT id, commands FROM command_patterns")
+                .fetch_all(self.pool())
+                .await?;
+        let suggestions: Vec<(i64, String)> =
+            sqlx::query_as("SELECT id, suggested_command FROM suggestions")
+                .fetch_all(self.pool())
+                .await?;
+
+        let mut removed = 0;
+        for (id, commands) in patterns {
+            let commands: Vec<String> = serde_json::from_str(&commands).unwrap_or_default();
+            if commands.iter().any(|c| matches(c)) {
+                sqlx::query("DELETE FROM command_patterns WHERE id = ?")
+                    .bind(id)
+                    .execute(self.pool())
+                    .await?;
+                removed += 1;
+            }
+        }
+        for (id, command) in suggestions {
+            if matches(&command) {
+                sqlx::query("DELETE FROM suggestions WHERE id = ?")
+                    .bind(id)
+                    .execute(self.pool())
+                    .await?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
     }
 
     /// Store a detected pattern
+    ///
+    /// Re-detecting a pattern updates its row instead of adding another one.
     pub async fn store_pattern(
         &self,
         pattern_type: PatternType,
@@ -209,6 +1487,27 @@ impl Database {
     ) -> Result<i64> {
         let commands_json = serde_json::to_string(&commands)?;
         let metadata_json = serde_json::to_string(&metadata)?;
+        let existing: Option<(i64,)> = sqlx::query_as(
+            "SELECT id FROM command_patterns WHERE pattern_type = ? AND commands = ? AND project_path IS ? LIMIT 1",
+        )
+        .bind(pattern_type)
+        .bind(&commands_json)
+        .bind(&project_path)
+        .fetch_optional(self.pool())
+        .await?;
+
+        if let Some((id,)) = existing {
+            sqlx::query(
+                "UPDATE command_patterns SET confidence_score = ?, metadata = ?, last_seen = CURRENT_TIMESTAMP WHERE id = ?",
+            )
+            .bind(confidence)
+            .bind(metadata_json)
+            .bind(id)
+            .execute(self.pool())
+            .await?;
+
+            return Ok(id);
+        }
 
         let result = sqlx::query(
             r#"
@@ -217,7 +1516,7 @@ impl Database {
             RETURNING id
             "#,
         )
-        .bind(pattern_type.to_string())
+        .bind(pattern_type)
         .bind(commands_json)
         .bind(project_path)
         .bind(confidence)
@@ -228,19 +1527,232 @@ impl Database {
         Ok(result.get(0))
     }
 
+    /// Add newly seen sequence runs to the persisted counts
+    ///
+    /// Sequences seen for the first time get a row with no confidence yet.
+    /// The high-water mark is saved in the same transaction, so a run is
+    /// never counted twice.
+    ///
+    /// # Arguments
+    /// * `project_path` - Project the runs belong to (None for all projects)
+    /// * `counts` - (commands, exact runs, runs with a gap) for each sequence
+    /// * `high_water_key` - Preference holding the last analyzed execution ID
+    /// * `high_water` - ID of the newest execution that was counted
+    pub async fn merge_sequence_counts(
+        &self,
+        project_path: Option<&str>,
+        counts: &[(Vec<String>, i64, i64)],
+        high_water_key: &str,
+        high_water: i64,
+    ) -> Result<()> {
+        let mut tx = self.pool().begin().await?;
+
+        for (commands, exact, gapped) in counts {
+            let commands_json = serde_json::to_string(commands)?;
+
+            let existing: Option<(i64, Option<String>)> = sqlx::query_as(
+                "SELECT id, metadata FROM command_patterns WHERE pattern_type = ? AND commands = ? AND project_path IS ? LIMIT 1",
+            )
+            .bind(PatternType::Sequential)
+            .bind(&commands_json)
+            .bind(project_path)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            match existing {
+                Some((id, metadata)) => {
+                    let mut metadata: serde_json::Value = metadata
+                        .and_then(|m| serde_json::from_str(&m).ok())
+                        .unwrap_or(serde_json::json!({}));
+                    let previous = |key: &str| metadata.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
+                    let (total_exact, total_gapped) = (previous("exact") + exact, previous("gapped") + gapped);
+                    metadata["exact"] = total_exact.into();
+                    metadata["gapped"] = total_gapped.into();
+
+                    sqlx::query(
+                        r#"
+                        UPDATE command_patterns
+                        SET occurrences = ?, metadata = ?, last_seen = CURRENT_TIMESTAMP
+                        WHERE id = ?
+                        "#,
+                    )
+                    .bind(total_exact + total_gapped)
+                    .bind(serde_json::to_string(&metadata)?)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                None => {
+                    let metadata = serde_json::json!({ "exact": exact, "gapped": gapped });
+
+                    sqlx::query(
+                        r#"
+                        INSERT INTO command_patterns (pattern_type, commands, project_path, confidence_score, occurrences, metadata)
+                        VALUES (?, ?, ?, 0.0, ?, ?)
+                        "#,
+                    )
+                    .bind(PatternType::Sequential)
+                    .bind(commands_json)
+                    .bind(project_path)
+                    .bind(exact + gapped)
+                    .bind(serde_json::to_string(&metadata)?)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+            }
+        }
+
+        sqlx::query("INSERT OR REPLACE INTO preferences (key, value) VALUES (?, ?)")
+            .bind(high_water_key)
+            .bind(high_water.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Get the persisted sequence counts for one project
+    ///
+    /// # Arguments
+    /// * `project_path` - Exact project (None for the all-projects rows)
+    /// * `min_occurrences` - Skip sequences seen fewer times than this
+    pub async fn get_sequence_patterns(
+        &self,
+        project_path: Option<&str>,
+        min_occurrences: i64,
+    ) -> Result<Vec<CommandPattern>> {
+        let patterns = sqlx::query_as::<_, CommandPattern>(
+            r#"
+            SELECT * FROM command_patterns
+            WHERE pattern_type = ? AND project_path IS ? AND occurrences >= ?
+            "#,
+        )
+        .bind(PatternType::Sequential)
+        .bind(project_path)
+        .bind(min_occurrences)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(patterns)
+    }
+
+    /// Get a stored pattern by ID
+    pub async fn get_pattern_by_id(&self, id: i64) -> Result<Option<CommandPattern>> {
+        let pattern =
+            sqlx::query_as::<_, CommandPattern>("SELECT * FROM command_patterns WHERE id = ?")
+                .bind(id)
+                .fetch_optional(self.pool())
+                .await?;
+
+        Ok(pattern)
+    }
+
+    /// Update the confidence of a stored pattern
+    pub async fn update_pattern_confidence(&self, id: i64, confidence: f64) -> Result<()> {
+        sqlx::query("UPDATE command_patterns SET confidence_score = ? WHERE id = ?")
+            .bind(confidence)
+            .bind(id)
+            .execute(self.pool())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Mark a stored pattern as noise
+    ///
+    /// Detection keeps counting it, but it stays out of `get_patterns` and of
+    /// what `detect_patterns` hands to reports and suggestions.
+    pub async fn dismiss_pattern(&self, id: i64) -> Result<()> {
+        self.delete_preference(&format!("{}{}", PATTERN_ACCEPTED_PREFIX, id))
+            .await?;
+        self.set_preference(
+            format!("{}{}", PATTERN_DISMISSED_PREFIX, id),
+            Utc::now().to_rfc3339(),
+        )
+        .await
+    }
+
+    /// Mark a stored pattern as a workflow you follow on purpose
+    pub async fn accept_pattern(&self, id: i64) -> Result<()> {
+        self.delete_preference(&format!("{}{}", PATTERN_DISMISSED_PREFIX, id))
+            .await?;
+        self.set_preference(
+            format!("{}{}", PATTERN_ACCEPTED_PREFIX, id),
+            Utc::now().to_rfc3339(),
+        )
+        .await
+    }
+
+    /// The stored patterns marked as noise
+    pub async fn get_dismissed_patterns(&self) -> Result<Vec<CommandPattern>> {
+        let patterns = sqlx::query_as::<_, CommandPattern>(
+            r#"
+            SELECT * FROM command_patterns WHERE id IN
+                (SELECT CAST(substr(key, ?) AS INTEGER) FROM preferences WHERE key LIKE ?)
+            "#,
+        )
+        .bind(PATTERN_DISMISSED_PREFIX.len() as i64 + 1)
+        .bind(format!("{}%", PATTERN_DISMISSED_PREFIX))
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(patterns)
+    }
+
+    /// IDs of the patterns accepted as workflows
+    pub async fn accepted_pattern_ids(&self) -> Result<HashSet<i64>> {
+        Ok(self
+            .get_preferences_with_prefix(PATTERN_ACCEPTED_PREFIX)
+            .await?
+            .into_iter()
+            .filter_map(|p| p.key.strip_prefix(PATTERN_ACCEPTED_PREFIX)?.parse().ok())
+            .collect())
+    }
+
+    /// Drop every stored sequence for one project (None for the all-projects rows)
+    pub async fn delete_sequence_patterns(&self, project_path: Option<&str>) -> Result<()> {
+        sqlx::query("DELETE FROM command_patterns WHERE pattern_type = ? AND project_path IS ?")
+            .bind(PatternType::Sequential)
+            .bind(project_path)
+            .execute(self.pool())
+            .await?;
+
+        Ok(())
+    }
+
     /// Get patterns for a project
     pub async fn get_patterns(&self, project_path: Option<&str>) -> Result<Vec<CommandPattern>> {
         let patterns = if let Some(path) = project_path {
             sqlx::query_as::<_, CommandPattern>(
-                "SELECT * FROM command_patterns WHERE project_path = ? OR project_path IS NULL ORDER BY confidence_score DESC",
+                // Global sequences predate per-project detection and belong to some other repo
+                r#"
+                SELECT * FROM command_patterns
+                WHERE confidence_score > 0
+                  AND (project_path = ? OR (project_path IS NULL AND pattern_type != 'sequence'))
+                  AND id NOT IN (SELECT CAST(substr(key, ?) AS INTEGER) FROM preferences
+                                 WHERE key LIKE ?)
+                ORDER BY confidence_score DESC
+                "#,
             )
             .bind(path)
+            .bind(PATTERN_DISMISSED_PREFIX.len() as i64 + 1)
+            .bind(format!("{}%", PATTERN_DISMISSED_PREFIX))
             .fetch_all(self.pool())
             .await?
         } else {
             sqlx::query_as::<_, CommandPattern>(
-                "SELECT * FROM command_patterns ORDER BY confidence_score DESC",
+                r#"
+                SELECT * FROM command_patterns
+                WHERE confidence_score > 0
+                  AND id NOT IN (SELECT CAST(substr(key, ?) AS INTEGER) FROM preferences
+                                 WHERE key LIKE ?)
+                ORDER BY confidence_score DESC
+                "#,
             )
+            .bind(PATTERN_DISMISSED_PREFIX.len() as i64 + 1)
+            .bind(format!("{}%", PATTERN_DISMISSED_PREFIX))
             .fetch_all(self.pool())
             .await?
         };
@@ -275,6 +1787,46 @@ impl Database {
         Ok(result.get(0))
     }
 
+    /// Mark a recently shown suggestion as taken
+    ///
+    /// Running a command that was suggested for this project within the last
+    /// `within_minutes` counts as accepting it. Each suggestion is only
+    /// accepted once.
+    ///
+    /// # Returns
+    /// * `Ok(Some(Suggestion))` - The suggestion that was accepted
+    pub async fn accept_recent_suggestion(
+        &self,
+        project_path: &str,
+        command: &str,
+        within_minutes: i64,
+    ) -> Result<Option<Suggestion>> {
+        // Suggestions are stored against the directory they were made in,
+        // which can be anywhere inside the project
+        let suggestion = sqlx::query_as::<_, Suggestion>(
+            r#"
+            SELECT * FROM suggestions
+            WHERE suggested_command = ?1
+              AND (project_path = ?2 OR substr(project_path, 1, length(?2) + 1) = ?2 || '/')
+              AND times_accepted = 0
+              AND created_at >= datetime('now', ?3)
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(command)
+        .bind(project_path)
+        .bind(format!("-{} minutes", within_minutes))
+        .fetch_optional(self.pool())
+        .await?;
+
+        if let Some(s) = &suggestion {
+            self.record_suggestion_feedback(s.id, true).await?;
+        }
+
+        Ok(suggestion)
+    }
+
     /// Get suggestions for a context
     pub async fn get_suggestions(
         &self,
@@ -301,6 +1853,31 @@ impl Database {
         Ok(suggestions)
     }
 
+    /// Get a suggestion by ID
+    pub async fn get_suggestion_by_id(&self, id: i64) -> Result<Option<Suggestion>> {
+        let suggestion = sqlx::query_as::<_, Suggestion>("SELECT * FROM suggestions WHERE id = ?")
+            .bind(id)
+            .fetch_optional(self.pool())
+            .await?;
+
+        Ok(suggestion)
+    }
+
+    /// Get accumulated feedback for a suggested command
+    ///
+    /// # Returns
+    /// * `Ok((accepted, rejected))` - Totals across every time it was suggested
+    pub async fn get_suggestion_feedback(&self, command: &str) -> Result<(i64, i64)> {
+        let totals: (i64, i64) = sqlx::query_as(
+            "SELECT COALESCE(SUM(times_accepted), 0), COALESCE(SUM(times_rejected), 0) FROM suggestions WHERE suggested_command = ?",
+        )
+        .bind(command)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(totals)
+    }
+
     /// Record suggestion feedback
     pub async fn record_suggestion_feedback(&self, id: i64, accepted: bool) -> Result<()> {
         let now = Utc::now().to_rfc3339();
@@ -347,6 +1924,59 @@ impl Database {
         Ok(())
     }
 
+    /// Get every preference whose key starts with `prefix`
+    pub async fn get_preferences_with_prefix(&self, prefix: &str) -> Result<Vec<Preference>> {
+        let prefs = sqlx::query_as::<_, Preference>(
+            "SELECT * FROM preferences WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key",
+        )
+        .bind(prefix)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(prefs)
+    }
+
+    /// Delete a preference
+    ///
+    /// # Returns
+    /// * `Ok(true)` - The preference existed and was removed
+    pub async fn delete_preference(&self, key: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM preferences WHERE key = ?")
+            .bind(key)
+            .execute(self.pool())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// One more command left out for `reason`
+    pub async fn count_dropped(&self, reason: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO dropped_recordings (reason, count, last_at)
+            VALUES (?, 1, CURRENT_TIMESTAMP)
+            ON CONFLICT(reason) DO UPDATE SET
+                count = count + 1,
+                last_at = excluded.last_at
+            "#,
+        )
+        .bind(reason)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// How many commands were left out, by reason
+    pub async fn dropped_counts(&self) -> Result<HashMap<String, i64>> {
+        let rows: Vec<(String, i64)> =
+            sqlx::query_as("SELECT reason, count FROM dropped_recordings")
+                .fetch_all(self.pool())
+                .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+
     /// Create an alias
     pub async fn create_alias(
         &self,
@@ -364,6 +1994,19 @@ impl Database {
         Ok(())
     }
 
+    /// Delete an alias
+    ///
+    /// # Returns
+    /// * `Ok(true)` - The alias existed and was removed
+    pub async fn delete_alias(&self, alias: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM aliases WHERE alias = ?")
+            .bind(alias)
+            .execute(self.pool())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Get all aliases
     pub async fn get_aliases(&self, project_path: Option<&str>) -> Result<Vec<Alias>> {
         let aliases = if let Some(path) = project_path {
@@ -379,8 +2022,110 @@ impl Database {
                 .await?
         };
 
-        Ok(aliases)
+        Ok(aliases)
+    }
+}
+
+/// Insert a command, or count another use of it
+async fn upsert_command(
+    tx: &mut Transaction<'_, Sqlite>,
+    input: &CommandInput,
+    meta: &ExecutionMeta,
+    canonical: &str,
+    hash: &str,
+) -> std::result::Result<i64, sqlx::Error> {
+    let (id,) = sqlx::query_as(
+        r#"
+        INSERT INTO commands (project_path, command, timestamp, execution_time_ms, exit_code,
+                              context, canonical, hash)
+        VALUES (?, ?, COALESCE(?, CURRENT_TIMESTAMP), ?, ?, ?, ?, ?)
+        ON CONFLICT(project_path, command) DO UPDATE SET
+            usage_count = usage_count + 1,
+            timestamp = MAX(timestamp, excluded.timestamp),
+            execution_time_ms = excluded.execution_time_ms,
+            exit_code = excluded.exit_code
+        RETURNING id
+        "#,
+    )
+    .bind(&input.project_path)
+    .bind(&input.command)
+    .bind(&meta.executed_at)
+    .bind(input.execution_time_ms)
+    .bind(input.exit_code)
+    .bind(&input.context)
+    .bind(canonical)
+    .bind(hash)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(id)
+}
+
+/// Whether `error` is another row already holding the hash being written
+fn is_hash_clash(error: &sqlx::Error) -> bool {
+    matches!(
+        error,
+        sqlx::Error::Database(e)
+            if e.is_unique_violation() && e.message().contains("commands.hash")
+    )
+}
+
+/// The hash to store on row `id` for `command` in `project_path`
+///
+/// `content_hash`, unless another row (another spelling) has it already.
+pub(crate) async fn free_hash(
+    tx: &mut Transaction<'_, Sqlite>,
+    id: i64,
+    project_path: &str,
+    command: &str,
+) -> Result<String> {
+    let hash = content_hash(project_path, command);
+    let taken: Option<(i64,)> = sqlx::query_as("SELECT id FROM commands WHERE hash = ? AND id != ?")
+        .bind(&hash)
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+    Ok(match taken {
+        Some(_) => spelling_hash(project_path, command),
+        None => hash,
+    })
+}
+
+/// Store the stages of `command` under `id`, replacing any it had
+///
+/// Only compound commands get rows: a plain command is its own one stage.
+pub(crate) async fn index_stages(
+    tx: &mut Transaction<'_, Sqlite>,
+    id: i64,
+    command: &str,
+) -> Result<()> {
+    sqlx::query("DELETE FROM command_stages WHERE command_id = ?")
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+
+    let stages = pipeline::stages(command);
+    if stages.len() < 2 {
+        return Ok(());
+    }
+    for (position, stage) in stages.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO command_stages (command_id, position, joint, program, stage)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(id)
+        .bind(position as i64)
+        .bind(stage.joint.map(|j| j.as_str()))
+        .bind(&stage.program)
+        .bind(&stage.text)
+        .execute(&mut **tx)
+        .await?;
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -452,6 +2197,108 @@ mod tests {
         assert_eq!(recent[0].command, "command5");
     }
 
+    #[tokio::test]
+    async fn test_execution_history_keeps_every_run() {
+        let db = Database::new_test().await.unwrap();
+
+        for (cmd, code) in [("npm test", 1), ("npm install", 0), ("npm test", 0)] {
+            db.record_command(CommandInput {
+                project_path: "/test".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(code),
+                context: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let history = db.get_execution_history(Some("/test"), 10).await.unwrap();
+        let commands: Vec<_> = history.iter().map(|e| e.command.as_str()).collect();
+        assert_eq!(commands, vec!["npm test", "npm install", "npm test"]);
+        assert_eq!(history[0].exit_code, Some(1));
+
+        // Limit keeps the newest runs, still oldest first
+        let tail = db.get_execution_history(Some("/test"), 2).await.unwrap();
+        assert_eq!(tail[0].command, "npm install");
+    }
+
+    #[tokio::test]
+    async fn test_command_stats() {
+        let db = Database::new_test().await.unwrap();
+
+        for (cmd, code, ms) in [
+            ("git push", 1, 400),
+            ("git push", 0, 200),
+            ("git status", 0, 10),
+            ("gitk", 0, 50),
+            ("npm test", 0, 900),
+        ] {
+            db.record_command(CommandInput {
+                project_path: "/test".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: Some(ms),
+                exit_code: Some(code),
+                context: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let all = db.get_command_stats(Some("/test"), None, 10).await.unwrap();
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0].command, "git push");
+        assert_eq!(all[0].runs, 2);
+        assert_eq!(all[0].failures, 1);
+        assert_eq!(all[0].avg_execution_time_ms, Some(300.0));
+
+        // --tool matches the first word only, so gitk stays out
+        let git = db.get_command_stats(Some("/test"), Some("git"), 10).await.unwrap();
+        assert_eq!(git.len(), 2);
+
+        assert!(db.get_command_stats(Some("/other"), None, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_accept_recent_suggestion() {
+        let db = Database::new_test().await.unwrap();
+        let id = db
+            .store_suggestion("/work/api/src".to_string(), None, "cargo test".to_string(), None, 0.7)
+            .await
+            .unwrap();
+
+        // Other projects and other commands don't count
+        assert!(db.accept_recent_suggestion("/work/web", "cargo test", 60).await.unwrap().is_none());
+        assert!(db.accept_recent_suggestion("/work/api", "cargo build", 60).await.unwrap().is_none());
+
+        let accepted = db.accept_recent_suggestion("/work/api", "cargo test", 60).await.unwrap();
+        assert_eq!(accepted.map(|s| s.id), Some(id));
+
+        // Only once
+        assert!(db.accept_recent_suggestion("/work/api", "cargo test", 60).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_project_paths() {
+        let db = Database::new_test().await.unwrap();
+
+        for (project, cmd) in [("/a", "ls"), ("/b", "make"), ("/a", "git status")] {
+            db.record_command(CommandInput {
+                project_path: project.to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: None,
+                context: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let projects = db.get_project_paths().await.unwrap();
+        assert_eq!(projects.len(), 2);
+        assert!(projects.contains(&"/a".to_string()));
+    }
+
     #[tokio::test]
     async fn test_toggle_favorite() {
         let db = Database::new_test().await.unwrap();
@@ -468,11 +2315,87 @@ mod tests {
 
         // Toggle on
         let is_fav = db.toggle_favorite(id).await.unwrap();
-        assert_eq!(is_fav, true);
+        assert!(is_fav);
 
         // Toggle off
         let is_fav = db.toggle_favorite(id).await.unwrap();
-        assert_eq!(is_fav, false);
+        assert!(!is_fav);
+    }
+
+    #[tokio::test]
+    async fn test_typed_timestamps() {
+        let db = Database::new_test().await.unwrap();
+
+        let mut ids = Vec::new();
+        for command in ["make", "make test"] {
+            ids.push(
+                db.record_command(CommandInput {
+                    project_path: "/test".to_string(),
+                    command: command.to_string(),
+                    execution_time_ms: None,
+                    exit_code: None,
+                    context: None,
+                })
+                .await
+                .unwrap(),
+            );
+        }
+
+        // What CURRENT_TIMESTAMP writes, and what older versions wrote from Rust
+        let stored = ["2026-02-03 04:05:06", "2026-02-03T04:05:06+00:00"];
+        for (id, stored) in ids.iter().zip(stored) {
+            sqlx::query("UPDATE commands SET timestamp = ? WHERE id = ?")
+                .bind(stored)
+                .bind(id)
+                .execute(db.pool())
+                .await
+                .unwrap();
+        }
+
+        let expected = parse_timestamp("2026-02-03 04:05:06").unwrap();
+        for id in ids {
+            let command = db.get_command_by_id(id).await.unwrap().unwrap();
+            assert_eq!(command.timestamp, expected);
+
+            let json = serde_json::to_string(&command).unwrap();
+            let back: Command = serde_json::from_str(&json).unwrap();
+            assert_eq!(back.timestamp, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_operations() {
+        let db = Database::new_test().await.unwrap();
+
+        let mut ids = Vec::new();
+        for command in ["make", "make test", "make lint"] {
+            ids.push(
+                db.record_command(CommandInput {
+                    project_path: "/test".to_string(),
+                    command: command.to_string(),
+                    execution_time_ms: None,
+                    exit_code: None,
+                    context: None,
+                })
+                .await
+                .unwrap(),
+            );
+        }
+
+        assert_eq!(db.tag_many(&ids[..2], "ci").await.unwrap(), 2);
+        // Already tagged ones don't count twice, missing ones not at all
+        assert_eq!(db.tag_many(&[ids[0], ids[2], 999], "ci").await.unwrap(), 1);
+        let first = db.get_command_by_id(ids[0]).await.unwrap().unwrap();
+        assert_eq!(first.get_tags(), vec!["ci"]);
+
+        assert_eq!(db.set_favorite_many(&ids[1..], true).await.unwrap(), 2);
+        assert_eq!(db.get_favorites(Some("/test")).await.unwrap().len(), 2);
+
+        assert_eq!(db.delete_many(&[ids[0], ids[1], 999]).await.unwrap(), 2);
+        assert_eq!(db.delete_many(&[]).await.unwrap(), 0);
+        let left = db.get_recent_commands(Some("/test"), 10).await.unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].command, "make lint");
     }
 
     #[tokio::test]
@@ -495,6 +2418,25 @@ mod tests {
         assert_eq!(results.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_alias_roundtrip() {
+        let db = Database::new_test().await.unwrap();
+
+        db.create_alias("t".to_string(), "cargo test".to_string(), Some("/a".to_string()))
+            .await
+            .unwrap();
+        db.create_alias("st".to_string(), "git status".to_string(), None)
+            .await
+            .unwrap();
+
+        // Project aliases plus global ones
+        assert_eq!(db.get_aliases(Some("/a")).await.unwrap().len(), 2);
+        assert_eq!(db.get_aliases(Some("/b")).await.unwrap().len(), 1);
+
+        assert!(db.delete_alias("t").await.unwrap());
+        assert!(!db.delete_alias("t").await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_preferences() {
         let db = Database::new_test().await.unwrap();
@@ -506,4 +2448,374 @@ mod tests {
         let value = db.get_preference("test_key").await.unwrap();
         assert_eq!(value, Some("test_value".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let db = Database::new_test().await.unwrap();
+
+        let runs = [
+            (Some("1700000000-41"), "/work/api", "cargo build"),
+            (Some("1700000000-41"), "/work/web", "npm test"),
+            (None, "/work/api", "cargo test"),
+            (Some("1700009999-77"), "/work/api", "git pull"),
+        ];
+        for (session, project, cmd) in runs {
+            let meta = ExecutionMeta {
+                session_id: session.map(String::from),
+                hostname: Some("laptop".to_string()),
+                cwd: Some(format!("{}/src", project)),
+                ..Default::default()
+            };
+            let input = CommandInput {
+                project_path: project.to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            };
+            db.record_command_with(input, &meta).await.unwrap();
+        }
+
+        let sessions = db.get_sessions(10).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].session_id, "1700009999-77");
+        assert_eq!(sessions[1].command_count, 2);
+        assert_eq!(sessions[1].hostname.as_deref(), Some("laptop"));
+        assert!(sessions[1].projects.contains("/work/web"));
+
+        assert_eq!(db.find_sessions("17000000").await.unwrap(), vec!["1700000000-41"]);
+        assert_eq!(db.find_sessions("1700").await.unwrap().len(), 2);
+
+        let timeline = db.get_session_executions("1700000000-41").await.unwrap();
+        let commands: Vec<&str> = timeline.iter().map(|e| e.command.as_str()).collect();
+        assert_eq!(commands, vec!["cargo build", "npm test"]);
+        assert_eq!(timeline[1].cwd.as_deref(), Some("/work/web/src"));
+    }
+
+    #[tokio::test]
+    async fn test_command_ids_with_env() {
+        let db = Database::new_test().await.unwrap();
+
+        let runs = [
+            ("terraform apply", Some(r#"{"AWS_PROFILE":"prod"}"#)),
+            ("terraform plan", Some(r#"{"AWS_PROFILE":"dev","NODE_ENV":"test"}"#)),
+            ("ls", None),
+        ];
+        let mut ids = Vec::new();
+        for (cmd, env) in runs {
+            let meta = ExecutionMeta {
+                env: env.map(String::from),
+                ..Default::default()
+            };
+            let input = CommandInput {
+                project_path: "/work/infra".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            };
+            ids.push(db.record_command_with(input, &meta).await.unwrap());
+        }
+
+        let prod = db.command_ids_with_env("AWS_PROFILE", Some("prod")).await.unwrap();
+        assert_eq!(prod, HashSet::from([ids[0]]));
+        let any = db.command_ids_with_env("AWS_PROFILE", None).await.unwrap();
+        assert_eq!(any.len(), 2);
+        assert!(db.command_ids_with_env("KUBECONFIG", None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_undo_last_execution() {
+        let db = Database::new_test().await.unwrap();
+
+        let runs = [
+            ("shell-a", "git status"),
+            ("shell-a", "export API_TOKEN_VALUE=hunter2"),
+            ("shell-b", "make"),
+            ("shell-a", "git status"),
+        ];
+        for (session, cmd) in runs {
+            let meta = ExecutionMeta {
+                session_id: Some(session.to_string()),
+                ..Default::default()
+            };
+            let input = CommandInput {
+                project_path: "/test".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            };
+            db.record_command_with(input, &meta).await.unwrap();
+        }
+
+        // A repeat only rolls back the count
+        let undone = db.undo_last_execution(Some("shell-a"), None).await.unwrap();
+        assert_eq!(undone.unwrap().command, "git status");
+        let status = db.find_command("git status", Some("/test")).await.unwrap().unwrap();
+        assert_eq!(status.usage_count, 1);
+
+        // A one-off disappears completely, and the other shell isn't touched
+        let undone = db.undo_last_execution(Some("shell-a"), None).await.unwrap();
+        assert_eq!(undone.unwrap().command, "export API_TOKEN_VALUE=hunter2");
+        let leaked = db
+            .find_command("export API_TOKEN_VALUE=hunter2", Some("/test"))
+            .await
+            .unwrap();
+        assert!(leaked.is_none());
+        assert!(db.find_command("make", Some("/test")).await.unwrap().is_some());
+
+        assert!(db.undo_last_execution(Some("shell-c"), None).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_command_folds_duplicates() {
+        let db = Database::new_test().await.unwrap();
+
+        for cmd in ["psql --password=***", "psql --password=hunter2", "psql --password=hunter2"] {
+            db.record_command(CommandInput {
+                project_path: "/test".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let leaked = db
+            .find_command("psql --password=hunter2", Some("/test"))
+            .await
+            .unwrap()
+            .unwrap();
+        let masked = db
+            .find_command("psql --password=***", Some("/test"))
+            .await
+            .unwrap()
+            .unwrap();
+        db.toggle_favorite(leaked.id).await.unwrap();
+        db.tag_many(&[leaked.id], "db").await.unwrap();
+        db.tag_many(&[masked.id], "prod").await.unwrap();
+        db.rewrite_command(leaked.id, "psql --password=***").await.unwrap();
+
+        let commands = db.get_recent_commands(Some("/test"), 10).await.unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].usage_count, 3);
+        assert_eq!(db.count_executions(Some("/test")).await.unwrap(), 3);
+        // The star and the tags of the folded row aren't lost
+        assert!(commands[0].is_fav);
+        let tags = commands[0].tags.as_deref().unwrap_or_default();
+        assert!(tags.contains("db") && tags.contains("prod"), "{}", tags);
+    }
+
+    #[tokio::test]
+    async fn test_reassign_project() {
+        let db = Database::new_test().await.unwrap();
+        for (project, cmd) in [("/old/api", "make"), ("/old/api", "cargo test"), ("/api", "make")] {
+            db.record_command(CommandInput {
+                project_path: project.to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            })
+            .await
+            .unwrap();
+        }
+        let starred = db.find_command("make", Some("/old/api")).await.unwrap().unwrap();
+        db.toggle_favorite(starred.id).await.unwrap();
+
+        assert_eq!(db.reassign_project("/old/api", "/api").await.unwrap(), 2);
+
+        assert_eq!(db.get_project_paths().await.unwrap(), vec!["/api".to_string()]);
+        assert_eq!(db.count_executions(Some("/api")).await.unwrap(), 3);
+        // Both had `make`: one row now, with both runs and the star
+        let make = db.find_command("make", Some("/api")).await.unwrap().unwrap();
+        assert_eq!(make.usage_count, 2);
+        assert!(make.is_fav);
+        let moved = db.find_command("cargo test", Some("/api")).await.unwrap().unwrap();
+        let (hash,): (String,) = sqlx::query_as("SELECT hash FROM commands WHERE id = ?")
+            .bind(moved.id)
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(hash, content_hash("/api", "cargo test"));
+    }
+
+    #[tokio::test]
+    async fn test_canonical_dedup() {
+        let db = Database::new_test().await.unwrap();
+        let record = |cmd: &str| {
+            let db = db.clone();
+            let input = CommandInput {
+                project_path: "/test".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            };
+            async move { db.record_command(input).await.unwrap() }
+        };
+
+        let first = record("git commit -m 'wip'").await;
+        let second = record("git commit -m \"wip\"").await;
+        assert_eq!(first, second);
+        let stored = db.get_command_by_id(first).await.unwrap().unwrap();
+        assert_eq!(stored.command, "git commit -m 'wip'");
+        assert_eq!(stored.usage_count, 2);
+
+        // Turned off, every spelling gets its own row again
+        db.set_preference("dedup_canonical".to_string(), "false".to_string())
+            .await
+            .unwrap();
+        assert_ne!(record("git commit -m wip").await, first);
+    }
+
+    #[tokio::test]
+    async fn test_scripted_runs() {
+        let db = Database::new_test().await.unwrap();
+
+        for (cmd, interactive) in [
+            ("cargo build", None),
+            ("apt-get update", Some(false)),
+            ("cargo test", Some(true)),
+        ] {
+            let meta = ExecutionMeta {
+                interactive,
+                ..Default::default()
+            };
+            let input = CommandInput {
+                project_path: "/test".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            };
+            db.record_command_with(input, &meta).await.unwrap();
+        }
+
+        // Pattern detection only sees what was typed at a prompt
+        let history = db.get_execution_history(Some("/test"), 10).await.unwrap();
+        let commands: Vec<&str> = history.iter().map(|e| e.command.as_str()).collect();
+        assert_eq!(commands, vec!["cargo build", "cargo test"]);
+        assert_eq!(db.get_executions_between(None, 0, i64::MAX, 10).await.unwrap().len(), 2);
+
+        let scripted = db.command_ids_by_interactive(false).await.unwrap();
+        let apt = db.find_command("apt-get update", Some("/test")).await.unwrap().unwrap();
+        assert_eq!(scripted.into_iter().collect::<Vec<_>>(), vec![apt.id]);
+        assert_eq!(db.command_ids_by_interactive(true).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_privileged_runs() {
+        let db = Database::new_test().await.unwrap();
+        let record = |cmd: &str| {
+            let db = db.clone();
+            let input = CommandInput {
+                project_path: "/test".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            };
+            async move { db.record_command(input).await.unwrap() }
+        };
+
+        let sudo = record("sudo apt update").await;
+        let plain = record("apt update").await;
+        assert_ne!(sudo, plain);
+        assert_eq!(db.privileged_command_ids().await.unwrap(), HashSet::from([sudo]));
+
+        // Filed with the plain one, still marked
+        db.set_preference("strip_sudo".to_string(), "true".to_string())
+            .await
+            .unwrap();
+        assert_eq!(record("doas -u root apt update").await, plain);
+        assert_eq!(record("sudo -i").await, record("sudo -i").await);
+        assert_eq!(db.privileged_command_ids().await.unwrap().len(), 3);
+        assert_eq!(db.stats().await.unwrap().privileged_commands, 3);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_stages() {
+        let db = Database::new_test().await.unwrap();
+        let record = |cmd: &str| {
+            let db = db.clone();
+            let input = CommandInput {
+                project_path: "/test".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            };
+            async move { db.record_command(input).await.unwrap() }
+        };
+
+        record("kubectl get pods | grep api").await;
+        record("kubectl get pods | grep api").await;
+        let jq = record("kubectl get pods -o json | jq -r .items").await;
+        record("kubectl get pods && echo done").await;
+        record("jq . data.json").await;
+
+        let uses = db.stage_uses("jq", None).await.unwrap();
+        assert_eq!(uses, vec![("jq -r .items".to_string(), 1)]);
+
+        let pipelines = db.pipelines_from("kubectl get pods", None, 10).await.unwrap();
+        assert_eq!(
+            pipelines,
+            vec![("kubectl get pods | grep api".to_string(), 2)]
+        );
+
+        // Rewritten into a plain command, it has no stages left
+        db.rewrite_command(jq, "jq --version").await.unwrap();
+        assert!(db.stage_uses("jq", None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rapid_repeats_debounced() {
+        let db = Database::new_test().await.unwrap();
+        let record = |cmd: &str, at: &str| {
+            let db = db.clone();
+            let input = CommandInput {
+                project_path: "/test".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: Some(100),
+                exit_code: Some(0),
+                context: None,
+            };
+            let meta = ExecutionMeta {
+                session_id: Some("shell-a".to_string()),
+                executed_at: Some(at.to_string()),
+                ..Default::default()
+            };
+            async move { db.record_command_with(input, &meta).await.unwrap() }
+        };
+
+        let id = record("kubectl get pods", "2024-05-02 14:00:00").await;
+        record("kubectl get pods", "2024-05-02 14:00:01").await;
+        record("kubectl get pods", "2024-05-02 14:00:02").await;
+        let stored = db.get_command_by_id(id).await.unwrap().unwrap();
+        assert_eq!(stored.usage_count, 1);
+        assert_eq!(db.count_executions(Some("/test")).await.unwrap(), 1);
+        let (repeats,): (i64,) =
+            sqlx::query_as("SELECT rapid_repeat_count FROM executions WHERE command_id = ?")
+                .bind(id)
+                .fetch_one(db.pool())
+                .await
+                .unwrap();
+        assert_eq!(repeats, 2);
+
+        // Later, or with something else in between, it's a run of its own
+        record("kubectl get pods", "2024-05-02 14:00:10").await;
+        record("ls", "2024-05-02 14:00:11").await;
+        record("kubectl get pods", "2024-05-02 14:00:12").await;
+        assert_eq!(db.count_executions(Some("/test")).await.unwrap(), 4);
+
+        db.set_preference("debounce_seconds".to_string(), "0".to_string())
+            .await
+            .unwrap();
+        record("kubectl get pods", "2024-05-02 14:00:12").await;
+        assert_eq!(db.count_executions(Some("/test")).await.unwrap(), 5);
+    }
 }
@@ -0,0 +1,158 @@
+//! Desktop launcher output
+//!
+//! Formats command history for Raycast, Alfred and rofi/wofi so a launcher
+//! can search every project's history from anywhere. Each item carries a
+//! copy action and a run action (the command plus the directory to run it in).
+
+use crate::db::Command;
+use crate::error::{RecallError, Result};
+use serde_json::json;
+use std::str::FromStr;
+
+/// Launcher list formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LauncherFormat {
+    Raycast,
+    Alfred,
+    /// rofi script mode (also fine for wofi/dmenu, which ignore the metadata)
+    Rofi,
+}
+
+impl FromStr for LauncherFormat {
+    type Err = RecallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "raycast" => Ok(LauncherFormat::Raycast),
+            "alfred" => Ok(LauncherFormat::Alfred),
+            "rofi" | "wofi" | "dmenu" => Ok(LauncherFormat::Rofi),
+            other => Err(RecallError::Config(format!(
+                "Unknown launcher format '{}' (expected raycast, alfred or rofi)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Render commands as a launcher list
+pub fn render(format: LauncherFormat, commands: &[Command]) -> String {
+    match format {
+        LauncherFormat::Raycast => to_raycast(commands),
+        LauncherFormat::Alfred => to_alfred(commands),
+        LauncherFormat::Rofi => to_rofi(commands),
+    }
+}
+
+fn subtitle(cmd: &Command) -> String {
+    format!("{} · used {} times", project_name(&cmd.project_path), cmd.usage_count)
+}
+
+fn project_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).find(|s| !s.is_empty()).unwrap_or(path)
+}
+
+fn to_raycast(commands: &[Command]) -> String {
+    let items: Vec<_> = commands
+        .iter()
+        .map(|cmd| {
+            json!({
+                "id": cmd.id.to_string(),
+                "title": cmd.command,
+                "subtitle": subtitle(cmd),
+                "actions": [
+                    { "type": "copy", "title": "Copy Command", "content": cmd.command },
+                    { "type": "run", "title": "Run in Terminal", "command": cmd.command, "cwd": cmd.project_path },
+                ],
+            })
+        })
+        .collect();
+
+    json!({ "items": items }).to_string()
+}
+
+// Alfred Script Filter: Enter passes `arg` on, Cmd+C copies, ⌘-Enter runs
+fn to_alfred(commands: &[Command]) -> String {
+    let items: Vec<_> = commands
+        .iter()
+        .map(|cmd| {
+            json!({
+                "uid": cmd.id.to_string(),
+                "title": cmd.command,
+                "subtitle": subtitle(cmd),
+                "arg": cmd.command,
+                "text": { "copy": cmd.command, "largetype": cmd.command },
+                "variables": { "action": "copy", "cwd": cmd.project_path },
+                "mods": {
+                    "cmd": {
+                        "subtitle": format!("Run in {}", cmd.project_path),
+                        "arg": cmd.command,
+                        "variables": { "action": "run", "cwd": cmd.project_path },
+                    },
+                },
+            })
+        })
+        .collect();
+
+    json!({ "items": items }).to_string()
+}
+
+// rofi script mode: one row per line, project path rides along in ROFI_INFO
+fn to_rofi(commands: &[Command]) -> String {
+    commands
+        .iter()
+        .map(|cmd| {
+            format!(
+                "{}\0info\x1f{}\x1fmeta\x1f{}\n",
+                cmd.command.replace('\n', " "),
+                cmd.project_path,
+                project_name(&cmd.project_path)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::parse_timestamp;
+
+    fn sample() -> Vec<Command> {
+        vec![Command {
+            id: 3,
+            project_path: "/home/me/api".to_string(),
+            command: "docker compose up".to_string(),
+            timestamp: parse_timestamp("2025-11-25 10:00:00").unwrap(),
+            is_fav: false,
+            usage_count: 12,
+            execution_time_ms: None,
+            exit_code: Some(0),
+            tags: None,
+            context: None,
+        }]
+    }
+
+    #[test]
+    fn test_render_json_formats() {
+        let raycast: serde_json::Value =
+            serde_json::from_str(&render(LauncherFormat::Raycast, &sample())).unwrap();
+        assert_eq!(raycast["items"][0]["subtitle"], "api · used 12 times");
+        assert_eq!(raycast["items"][0]["actions"][1]["cwd"], "/home/me/api");
+
+        let alfred: serde_json::Value =
+            serde_json::from_str(&render(LauncherFormat::Alfred, &sample())).unwrap();
+        assert_eq!(alfred["items"][0]["arg"], "docker compose up");
+        assert_eq!(alfred["items"][0]["mods"]["cmd"]["variables"]["action"], "run");
+    }
+
+    #[test]
+    fn test_render_rofi() {
+        let rofi = render(LauncherFormat::Rofi, &sample());
+        assert_eq!(rofi, "docker compose up\0info\x1f/home/me/api\x1fmeta\x1fapi\n");
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("wofi".parse::<LauncherFormat>().unwrap(), LauncherFormat::Rofi);
+        assert!("spotlight".parse::<LauncherFormat>().is_err());
+    }
+}
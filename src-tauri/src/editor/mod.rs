@@ -0,0 +1,12 @@
+//! Editor and launcher integration
+//!
+//! A long-running JSON-RPC backend over stdio for editor extensions
+//! (VS Code, JetBrains). Speaks LSP-style `Content-Length` framing, or one
+//! JSON message per line for quick scripting. Also formats history for
+//! desktop launchers (Raycast, Alfred, rofi).
+
+pub mod launcher;
+pub mod server;
+
+pub use launcher::LauncherFormat;
+pub use server::EditorServer;
@@ -0,0 +1,465 @@
+//! JSON-RPC server for editor extensions
+//!
+//! Methods:
+//! - `initialize` - `{ rootPath?, workspaceFolders? }`, remembers the default folder
+//! - `search` - `{ query, folder?, limit? }`
+//! - `recent` - `{ folder?, limit? }`
+//! - `runInTerminal` - `{ command, folder? }`, returns where to run it
+//! - `suggestForFileType` - `{ fileType | path, folder?, limit? }`
+//! - `shutdown` / `exit`
+//!
+//! Every method that takes `folder` resolves it to a project root the same way
+//! the shell hooks do, so an editor with several workspaces gets the right
+//! history for each one.
+
+use crate::core::ProjectDetector;
+use crate::db::Database;
+use crate::error::Result;
+use crate::intelligence::ProjectType;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const DEFAULT_LIMIT: i64 = 20;
+
+// Standard JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// How the client frames its messages; replies use the same
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Framing {
+    ContentLength,
+    Line,
+}
+
+/// A failed call, turned into a JSON-RPC error object
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn invalid_params(message: &str) -> Self {
+        Self {
+            code: INVALID_PARAMS,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<crate::error::RecallError> for RpcError {
+    fn from(e: crate::error::RecallError) -> Self {
+        Self {
+            code: INTERNAL_ERROR,
+            message: e.user_message(),
+        }
+    }
+}
+
+/// Editor backend session
+pub struct EditorServer {
+    db: Arc<Database>,
+    /// Folder used when a request doesn't name one
+    default_folder: Option<String>,
+    /// folder -> project root, so each workspace only walks the tree once
+    roots: HashMap<String, Option<String>>,
+    shutting_down: bool,
+}
+
+impl EditorServer {
+    /// Create a new editor server
+    pub fn new(db: Arc<Database>) -> Self {
+        Self {
+            db,
+            default_folder: None,
+            roots: HashMap::new(),
+            shutting_down: false,
+        }
+    }
+
+    /// Serve requests until the client sends `exit` or closes the stream
+    pub async fn run<R, W>(&mut self, mut reader: R, mut writer: W) -> Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        while let Some((framing, body)) = read_message(&mut reader).await? {
+            let response = match serde_json::from_str::<Value>(&body) {
+                Ok(request) => self.handle(request).await,
+                Err(e) => Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+            };
+
+            if let Some(response) = response {
+                write_message(&mut writer, framing, &response).await?;
+            }
+
+            if self.shutting_down {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle one decoded message
+    ///
+    /// # Returns
+    /// * `Some(Value)` - The response to send back
+    /// * `None` - It was a notification (no `id`), nothing to send
+    pub async fn handle(&mut self, request: Value) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let method = match request.get("method").and_then(|m| m.as_str()) {
+            Some(m) => m.to_string(),
+            None => {
+                return Some(error_response(
+                    id.unwrap_or(Value::Null),
+                    INVALID_REQUEST,
+                    "missing method",
+                ))
+            }
+        };
+        let params = request.get("params").cloned().unwrap_or(json!({}));
+
+        let result = match method.as_str() {
+            "initialize" => Ok(self.initialize(&params)),
+            "search" => self.search(&params).await,
+            "recent" => self.recent(&params).await,
+            "runInTerminal" => self.run_in_terminal(&params),
+            "suggestForFileType" => self.suggest_for_file_type(&params).await,
+            "shutdown" => Ok(Value::Null),
+            "exit" => {
+                self.shutting_down = true;
+                Ok(Value::Null)
+            }
+            other => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("unknown method '{}'", other),
+            }),
+        };
+
+        // Notifications never get a reply, even on error
+        let id = id?;
+        Some(match result {
+            Ok(value) => json!({ "jsonrpc": "2.0", "id": id, "result": value }),
+            Err(e) => error_response(id, e.code, &e.message),
+        })
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        let folder = params
+            .get("rootPath")
+            .and_then(|p| p.as_str())
+            .or_else(|| {
+                params
+                    .pointer("/workspaceFolders/0/path")
+                    .and_then(|p| p.as_str())
+            });
+        self.default_folder = folder.map(|f| f.to_string());
+
+        json!({
+            "name": "berri-recall",
+            "version": env!("CARGO_PKG_VERSION"),
+            "methods": ["search", "recent", "runInTerminal", "suggestForFileType"],
+        })
+    }
+
+    async fn search(&mut self, params: &Value) -> std::result::Result<Value, RpcError> {
+        let query = params
+            .get("query")
+            .and_then(|q| q.as_str())
+            .ok_or_else(|| RpcError::invalid_params("search needs a query"))?;
+        let project = self.project_for(params);
+
+        let commands = self
+            .db
+            .search_commands(query, project.as_deref(), limit(params))
+            .await?;
+
+        Ok(json!({ "project": project, "commands": commands }))
+    }
+
+    async fn recent(&mut self, params: &Value) -> std::result::Result<Value, RpcError> {
+        let project = self.project_for(params);
+
+        let commands = self
+            .db
+            .get_recent_commands(project.as_deref(), limit(params))
+            .await?;
+
+        Ok(json!({ "project": project, "commands": commands }))
+    }
+
+    // The extension does the actual running, we just say where
+    fn run_in_terminal(&mut self, params: &Value) -> std::result::Result<Value, RpcError> {
+        let command = params
+            .get("command")
+            .and_then(|c| c.as_str())
+            .ok_or_else(|| RpcError::invalid_params("runInTerminal needs a command"))?;
+        let project = self.project_for(params);
+        let cwd = project.clone().or_else(|| self.folder(params));
+
+        Ok(json!({ "command": command, "cwd": cwd, "project": project }))
+    }
+
+    async fn suggest_for_file_type(
+        &mut self,
+        params: &Value,
+    ) -> std::result::Result<Value, RpcError> {
+        let path = params.get("path").and_then(|p| p.as_str());
+        let file_type = params
+            .get("fileType")
+            .and_then(|t| t.as_str())
+            .or_else(|| path.and_then(|p| Path::new(p).extension()?.to_str()))
+            .ok_or_else(|| RpcError::invalid_params("suggestForFileType needs fileType or path"))?;
+        let project = self.project_for(params);
+
+        let project_type = match ProjectType::from_file_extension(file_type) {
+            Some(t) => t,
+            None => return Ok(json!({ "project": project, "commands": [] })),
+        };
+
+        let stats = self
+            .db
+            .get_command_stats(project.as_deref(), None, -1)
+            .await?;
+        let file_name = path.and_then(|p| Path::new(p).file_name()?.to_str());
+
+        // Commands that mention this exact file first, then the rest by usage
+        let mut commands: Vec<_> = stats
+            .into_iter()
+            .filter(|s| {
+                s.command
+                    .split_whitespace()
+                    .next()
+                    .is_some_and(|tool| project_type.matches_tool(tool))
+            })
+            .collect();
+        commands.sort_by_key(|s| {
+            let mentions_file = file_name.is_some_and(|f| s.command.contains(f));
+            (!mentions_file, -s.usage_count)
+        });
+        commands.truncate(limit(params) as usize);
+
+        Ok(json!({ "project": project, "commands": commands }))
+    }
+
+    fn folder(&self, params: &Value) -> Option<String> {
+        params
+            .get("folder")
+            .and_then(|f| f.as_str())
+            .map(|f| f.to_string())
+            .or_else(|| self.default_folder.clone())
+    }
+
+    /// Resolve the request's folder to a project root
+    fn project_for(&mut self, params: &Value) -> Option<String> {
+        let folder = self.folder(params)?;
+
+        self.roots
+            .entry(folder.clone())
+            .or_insert_with(|| {
+                ProjectDetector::detect(&folder)
+                    .ok()
+                    .and_then(|p| p.to_str().map(|s| s.to_string()))
+            })
+            .clone()
+    }
+}
+
+fn limit(params: &Value) -> i64 {
+    params
+        .get("limit")
+        .and_then(|l| l.as_i64())
+        .unwrap_or(DEFAULT_LIMIT)
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// Read one message, either LSP-framed or a single line
+async fn read_message<R>(reader: &mut R) -> Result<Option<(Framing, String)>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let length = match trimmed.strip_prefix("Content-Length:") {
+            Some(len) => len.trim().parse::<usize>().ok(),
+            None => return Ok(Some((Framing::Line, trimmed.to_string()))),
+        };
+
+        // Skip any other headers up to the blank line
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(None);
+            }
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+
+        let mut body = vec![0; length.unwrap_or(0)];
+        reader.read_exact(&mut body).await?;
+        return Ok(Some((
+            Framing::ContentLength,
+            String::from_utf8_lossy(&body).into_owned(),
+        )));
+    }
+}
+
+async fn write_message<W>(writer: &mut W, framing: Framing, message: &Value) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let body = message.to_string();
+
+    match framing {
+        Framing::ContentLength => {
+            writer
+                .write_all(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes())
+                .await?
+        }
+        Framing::Line => writer.write_all(format!("{}\n", body).as_bytes()).await?,
+    }
+    writer.flush().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CommandInput;
+    use tempfile::TempDir;
+
+    async fn setup() -> (EditorServer, TempDir) {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        let root = dir.path().canonicalize().unwrap();
+
+        let db = Arc::new(Database::new_test().await.unwrap());
+        for cmd in ["cargo test", "cargo build", "npm test", "cargo test --test parser_tests"] {
+            db.record_command(CommandInput {
+                project_path: root.to_str().unwrap().to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        (EditorServer::new(db), dir)
+    }
+
+    #[tokio::test]
+    async fn test_search_resolves_workspace_folder() {
+        let (mut server, dir) = setup().await;
+        let sub = dir.path().join("src");
+        std::fs::create_dir(&sub).unwrap();
+
+        let response = server
+            .handle(json!({
+                "jsonrpc": "2.0", "id": 1, "method": "search",
+                "params": { "query": "cargo", "folder": sub.to_str().unwrap() },
+            }))
+            .await
+            .unwrap();
+
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["commands"].as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_suggest_for_file_type() {
+        let (mut server, dir) = setup().await;
+        server
+            .handle(json!({
+                "jsonrpc": "2.0", "id": 1, "method": "initialize",
+                "params": { "rootPath": dir.path().to_str().unwrap() },
+            }))
+            .await;
+
+        let response = server
+            .handle(json!({
+                "jsonrpc": "2.0", "id": 2, "method": "suggestForFileType",
+                "params": { "path": "tests/parser_tests.rs" },
+            }))
+            .await
+            .unwrap();
+
+        let commands = response["result"]["commands"].as_array().unwrap();
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[0]["command"], "cargo test --test parser_tests");
+    }
+
+    #[tokio::test]
+    async fn test_errors_and_notifications() {
+        let (mut server, _dir) = setup().await;
+
+        let response = server
+            .handle(json!({ "jsonrpc": "2.0", "id": 7, "method": "nope" }))
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+        let response = server
+            .handle(json!({ "jsonrpc": "2.0", "id": 8, "method": "search", "params": {} }))
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+
+        // No id, no reply
+        assert!(server
+            .handle(json!({ "jsonrpc": "2.0", "method": "recent" }))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_run_over_both_framings() {
+        let (mut server, _dir) = setup().await;
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#;
+        let input = format!(
+            "Content-Length: {}\r\n\r\n{}{}\n",
+            body.len(),
+            body,
+            r#"{"jsonrpc":"2.0","id":2,"method":"exit"}"#
+        );
+        let mut output = Vec::new();
+
+        server
+            .run(tokio::io::BufReader::new(input.as_bytes()), &mut output)
+            .await
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Content-Length: "));
+        assert!(output.ends_with("\"result\":null}\n"));
+    }
+}
@@ -1,7 +1,7 @@
-/// Error types for recall-cli
-///
-/// This module defines all possible errors that can occur in the application.
-/// Uses thiserror for ergonomic error handling.
+//! Error types for recall-cli
+//!
+//! This module defines all possible errors that can occur in the application.
+//! Uses thiserror for ergonomic error handling.
 
 use thiserror::Error;
 
@@ -17,9 +17,15 @@ pub enum RecallError {
     Io(#[from] std::io::Error),
 
     /// Git-related errors
+    #[cfg(feature = "git")]
     #[error("Git error: {0}")]
     Git(#[from] git2::Error),
 
+    /// Watching a file for changes failed
+    #[cfg(feature = "watch")]
+    #[error("Watch error: {0}")]
+    Watch(#[from] notify::Error),
+
     /// Command not found in history
     #[error("Command not found: {0}")]
     CommandNotFound(String),
@@ -56,6 +62,10 @@ pub enum RecallError {
     #[error("Command exceeds maximum allowed length of {0} characters")]
     CommandTooLong(usize),
 
+    /// Another process holds the lock for the same job
+    #[error("{0}")]
+    Busy(String),
+
     /// Generic error with message
     #[error("{0}")]
     Generic(String),
@@ -74,9 +84,14 @@ impl RecallError {
             RecallError::Io(e) => {
                 format!("File system error. Check permissions. Details: {}", e)
             }
+            #[cfg(feature = "git")]
             RecallError::Git(e) => {
                 format!("Git operation failed. Details: {}", e)
             }
+            #[cfg(feature = "watch")]
+            RecallError::Watch(e) => {
+                format!("Couldn't watch for changes. Details: {}", e)
+            }
             RecallError::CommandNotFound(cmd) => {
                 format!("Command '{}' not found in history", cmd)
             }
@@ -104,6 +119,7 @@ impl RecallError {
             RecallError::CommandTooLong(max) => {
                 format!("Command exceeds maximum length of {} characters", max)
             }
+            RecallError::Busy(holder) => format!("{}, try again once it's done", holder),
             RecallError::Generic(msg) => msg.clone(),
         }
     }
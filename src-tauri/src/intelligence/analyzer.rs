@@ -1,14 +1,22 @@
-/// Main analyzer orchestrator
-///
-/// Coordinates pattern detection and suggestion generation.
+//! Main analyzer orchestrator
+//!
+//! Coordinates pattern detection and suggestion generation.
 
-use crate::db::Database;
+use crate::db::{parse_timestamp, Database, Execution, PatternType};
 use crate::error::Result;
-use crate::intelligence::{PatternDetector, SuggestionEngine};
+use crate::intelligence::chains::{self, Chain, Timings};
+use crate::intelligence::{Pattern, PatternDetector, SmartSuggestion, SuggestionEngine};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use std::sync::Arc;
 
+/// How many past executions chains are timed against, and drill-downs
+/// look through
+const RECENT_HISTORY: i64 = 1000;
+
 /// Main analyzer
 pub struct Analyzer {
+    db: Arc<Database>,
     pattern_detector: PatternDetector,
     suggestion_engine: SuggestionEngine,
 }
@@ -17,14 +25,23 @@ impl Analyzer {
     /// Create a new analyzer
     pub fn new(db: Arc<Database>) -> Self {
         let pattern_detector = PatternDetector::new(Arc::clone(&db));
-        let suggestion_engine = SuggestionEngine::new(db);
+        let suggestion_engine = SuggestionEngine::new(Arc::clone(&db));
 
         Self {
+            db,
             pattern_detector,
             suggestion_engine,
         }
     }
 
+    /// Detect and store patterns only
+    ///
+    /// Suggestions depend on the shell's current context, so batch runs over
+    /// other projects stop here.
+    pub async fn analyze_patterns(&self, project_path: &str) -> Result<Vec<Pattern>> {
+        self.pattern_detector.detect_patterns(Some(project_path)).await
+    }
+
     /// Run full analysis
     ///
     /// Detects patterns and generates suggestions
@@ -35,13 +52,130 @@ impl Analyzer {
         // Generate suggestions
         let suggestions = self.suggestion_engine.generate_suggestions().await?;
 
+        let chains = self.chains(&patterns, project_path).await?;
+
         Ok(AnalysisReport {
             patterns_found: patterns.len(),
             suggestions_generated: suggestions.len(),
             patterns,
             suggestions,
+            chains,
         })
     }
+
+    /// Sequences from `patterns` worth running as one line, timed against
+    /// the recent history
+    pub async fn chains(
+        &self,
+        patterns: &[Pattern],
+        project_path: Option<&str>,
+    ) -> Result<Vec<Chain>> {
+        let runs: Vec<_> = self
+            .db
+            .get_execution_history(project_path, RECENT_HISTORY)
+            .await?
+            .into_iter()
+            .filter_map(|e| {
+                Some((
+                    self.pattern_detector.normalize_command(&e.command),
+                    parse_timestamp(&e.executed_at)?,
+                    e.execution_time_ms,
+                ))
+            })
+            .collect();
+
+        Ok(chains::chains(patterns, &Timings::from_runs(&runs)))
+    }
+
+    /// The latest runs behind `pattern`, newest first, at most `limit`
+    ///
+    /// For a sequence each entry is one run of the whole thing, step by step
+    /// (runs with something else in the middle aren't shown). For other
+    /// patterns it's one run of one of its commands.
+    pub async fn supporting_runs(
+        &self,
+        pattern: &Pattern,
+        limit: usize,
+    ) -> Result<Vec<Vec<Execution>>> {
+        let history = self
+            .db
+            .get_execution_history(pattern.project_path.as_deref(), RECENT_HISTORY)
+            .await?;
+        let normalized: Vec<String> = history
+            .iter()
+            .map(|e| self.pattern_detector.normalize_command(&e.command))
+            .collect();
+
+        Ok(find_runs(pattern, &history, &normalized, limit))
+    }
+
+    /// `report` as `analyze --output` writes it, each pattern with what's
+    /// stored about it
+    pub async fn export(
+        &self,
+        report: &AnalysisReport,
+        project_path: Option<&str>,
+    ) -> Result<ReportExport> {
+        let stored = self.db.get_patterns(project_path).await?;
+        let patterns = report
+            .patterns
+            .iter()
+            .map(|pattern| {
+                let row = stored.iter().find(|row| {
+                    row.pattern_type == pattern.pattern_type
+                        && row.get_commands() == pattern.commands
+                });
+                ExportedPattern {
+                    pattern: pattern.clone(),
+                    id: row.map(|row| row.id),
+                    last_seen: row.map(|row| row.last_seen),
+                    metadata: row.map(|row| row.get_metadata()),
+                }
+            })
+            .collect();
+
+        Ok(ReportExport {
+            generated_at: Utc::now(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            project_path: project_path.map(String::from),
+            patterns_found: report.patterns_found,
+            suggestions_generated: report.suggestions_generated,
+            patterns,
+            suggestions: report.suggestions.clone(),
+            chains: report.chains.clone(),
+        })
+    }
+}
+
+fn find_runs(
+    pattern: &Pattern,
+    history: &[Execution],
+    normalized: &[String],
+    limit: usize,
+) -> Vec<Vec<Execution>> {
+    let mut runs = Vec::new();
+    if pattern.pattern_type == PatternType::Sequential {
+        let len = pattern.commands.len();
+        let mut end = history.len();
+        while end >= len && len > 0 && runs.len() < limit {
+            if normalized[end - len..end] == pattern.commands[..] {
+                runs.push(history[end - len..end].to_vec());
+                end -= len;
+            } else {
+                end -= 1;
+            }
+        }
+    } else {
+        for (execution, command) in history.iter().zip(normalized).rev() {
+            if runs.len() == limit {
+                break;
+            }
+            if pattern.commands.contains(command) {
+                runs.push(vec![execution.clone()]);
+            }
+        }
+    }
+    runs
 }
 
 /// Analysis report
@@ -49,8 +183,37 @@ impl Analyzer {
 pub struct AnalysisReport {
     pub patterns_found: usize,
     pub suggestions_generated: usize,
-    pub patterns: Vec<crate::intelligence::Pattern>,
-    pub suggestions: Vec<crate::intelligence::SmartSuggestion>,
+    pub patterns: Vec<Pattern>,
+    pub suggestions: Vec<SmartSuggestion>,
+    /// Biggest saving first
+    pub chains: Vec<Chain>,
+}
+
+/// An analysis as JSON, for dashboards and reviews
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportExport {
+    pub generated_at: DateTime<Utc>,
+    /// The berri-recall that wrote it
+    pub version: String,
+    /// None when every project was analyzed
+    pub project_path: Option<String>,
+    pub patterns_found: usize,
+    pub suggestions_generated: usize,
+    pub patterns: Vec<ExportedPattern>,
+    pub suggestions: Vec<SmartSuggestion>,
+    pub chains: Vec<Chain>,
+}
+
+/// A pattern and, once it's been stored, its row
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedPattern {
+    #[serde(flatten)]
+    pub pattern: Pattern,
+    /// What `scriptify` and `patterns` call it
+    pub id: Option<i64>,
+    pub last_seen: Option<DateTime<Utc>>,
+    /// How it was detected
+    pub metadata: Option<serde_json::Value>,
 }
 
 #[cfg(test)]
@@ -88,4 +251,60 @@ mod tests {
         // Should find patterns and generate suggestions
         assert!(report.patterns_found > 0 || report.suggestions_generated > 0);
     }
+
+    #[tokio::test]
+    async fn test_export() {
+        let analyzer = setup().await;
+        let report = analyzer.analyze(Some("/test")).await.unwrap();
+
+        let export = analyzer.export(&report, Some("/test")).await.unwrap();
+        let json = serde_json::to_value(&export).unwrap();
+        assert_eq!(json["project_path"], "/test");
+        assert_eq!(json["patterns"].as_array().unwrap().len(), report.patterns.len());
+
+        // Sequences are stored as they're counted, so they have a row
+        let sequence = export
+            .patterns
+            .iter()
+            .find(|p| p.pattern.commands == ["git add .", "git commit -m <arg>"])
+            .unwrap();
+        assert!(sequence.id.is_some());
+        let sequence = serde_json::to_value(sequence).unwrap();
+        assert_eq!(sequence["pattern_type"], "sequential");
+        assert!(sequence["occurrences"].as_u64().unwrap() >= 3);
+    }
+
+    #[tokio::test]
+    async fn test_drill_down() {
+        let analyzer = setup().await;
+        let report = analyzer.analyze(Some("/test")).await.unwrap();
+        let export = analyzer.export(&report, Some("/test")).await.unwrap();
+        let pattern = export
+            .patterns
+            .iter()
+            .find(|p| p.pattern.commands == ["git add .", "git commit -m <arg>", "git push"])
+            .unwrap();
+
+        let runs = analyzer.supporting_runs(&pattern.pattern, 2).await.unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0][1].command, "git commit -m 'test'");
+        assert!(runs[0][0].id > runs[1][2].id);
+
+        // Dismissed: still counted, no longer reported
+        let id = pattern.id.unwrap();
+        analyzer.db.dismiss_pattern(id).await.unwrap();
+        let again = analyzer.analyze(Some("/test")).await.unwrap();
+        assert!(!again.patterns.iter().any(|p| p.commands == pattern.pattern.commands));
+        assert!(!analyzer
+            .db
+            .get_patterns(Some("/test"))
+            .await
+            .unwrap()
+            .iter()
+            .any(|p| p.id == id));
+
+        analyzer.db.accept_pattern(id).await.unwrap();
+        assert!(analyzer.db.accepted_pattern_ids().await.unwrap().contains(&id));
+        assert!(analyzer.db.get_dismissed_patterns().await.unwrap().is_empty());
+    }
 }
@@ -0,0 +1,174 @@
+//! Spots commands that can wreck things AND that you've never run here
+//!
+//! `terraform destroy` in a sandbox you tear down daily is fine.
+//! The same thing in a directory, or on a machine, where you've never run it
+//! deserves a second look.
+
+use crate::db::Database;
+use crate::error::Result;
+use regex::Regex;
+use std::sync::Arc;
+
+/// (pattern, what it does) - matched against the lowercased command
+const DESTRUCTIVE_PATTERNS: &[(&str, &str)] = &[
+    (r"\bterraform\s+destroy\b", "destroys infrastructure"),
+    (r"\bterraform\s+apply\b.*-auto-approve", "applies infrastructure changes without review"),
+    (r"\bpulumi\s+destroy\b", "destroys infrastructure"),
+    (r"\bkubectl\s+delete\b", "deletes cluster resources"),
+    (r"\bhelm\s+(uninstall|delete)\b", "removes a release"),
+    (r"\brm\s+(-[a-z]*r[a-z]*f|-[a-z]*f[a-z]*r)\b", "recursively force-deletes files"),
+    (r"\bgit\s+push\b.*(\s--force\b|\s-f\b)", "rewrites remote history"),
+    (r"\bgit\s+reset\s+--hard\b", "discards local changes"),
+    (r"\bgit\s+clean\s+-[a-z]*f", "deletes untracked files"),
+    (r"\bdrop\s+(table|database|schema)\b", "drops database objects"),
+    (r"\btruncate\s+table\b", "empties a table"),
+    (r"\bdocker\s+(system|volume)\s+prune\b", "removes docker data"),
+    (r"\bdd\s+.*\bof=", "overwrites a device or file"),
+    (r"\bmkfs(\.\w+)?\b", "formats a filesystem"),
+];
+
+/// A destructive command with no runs in this project on this host
+#[derive(Debug, Clone)]
+pub struct Anomaly {
+    pub command: String,
+    pub reason: &'static str,
+    pub project_path: String,
+    pub hostname: Option<String>,
+    /// Ran somewhere else before, just never in this project on this host
+    pub seen_elsewhere: bool,
+}
+
+/// Checks commands against the destructive patterns and their history
+pub struct AnomalyDetector {
+    db: Arc<Database>,
+    destructive: Vec<(Regex, &'static str)>,
+}
+
+impl AnomalyDetector {
+    pub fn new(db: Arc<Database>) -> Self {
+        // Compile once, same as the recorder's sensitive patterns
+        let destructive = DESTRUCTIVE_PATTERNS
+            .iter()
+            .filter_map(|(pattern, reason)| Regex::new(pattern).ok().map(|r| (r, *reason)))
+            .collect();
+
+        Self { db, destructive }
+    }
+
+    /// What makes this command dangerous, if anything
+    pub fn destructive_reason(&self, command: &str) -> Option<&'static str> {
+        let lowercase = command.to_lowercase();
+
+        self.destructive
+            .iter()
+            .find(|(regex, _)| regex.is_match(&lowercase))
+            .map(|(_, reason)| *reason)
+    }
+
+    /// Flags the command only if it's destructive and unprecedented in this
+    /// project on `hostname`
+    ///
+    /// Without a hostname, any run in the project counts.
+    pub async fn check(
+        &self,
+        command: &str,
+        project_path: &str,
+        hostname: Option<&str>,
+    ) -> Result<Option<Anomaly>> {
+        let reason = match self.destructive_reason(command) {
+            Some(reason) => reason,
+            None => return Ok(None),
+        };
+
+        let ran_here = match hostname {
+            Some(host) => self.db.ran_on_host(command, Some(project_path), host).await?,
+            None => self.db.find_command(command, Some(project_path)).await?.is_some(),
+        };
+        if ran_here {
+            return Ok(None);
+        }
+
+        let seen_elsewhere = self.db.find_command(command, None).await?.is_some();
+
+        Ok(Some(Anomaly {
+            command: command.to_string(),
+            reason,
+            project_path: project_path.to_string(),
+            hostname: hostname.map(str::to_string),
+            seen_elsewhere,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{CommandInput, ExecutionMeta};
+
+    async fn setup() -> AnomalyDetector {
+        let db = Arc::new(Database::new_test().await.unwrap());
+
+        db.record_command_with(
+            CommandInput {
+                project_path: "/sandbox".to_string(),
+                command: "terraform destroy".to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            },
+            &ExecutionMeta {
+                hostname: Some("laptop".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        AnomalyDetector::new(db)
+    }
+
+    #[tokio::test]
+    async fn test_destructive_reason() {
+        let detector = setup().await;
+
+        assert!(detector.destructive_reason("terraform destroy").is_some());
+        assert!(detector.destructive_reason("rm -rf build/").is_some());
+        assert!(detector.destructive_reason("git push --force origin main").is_some());
+        assert!(detector.destructive_reason("DROP TABLE users;").is_some());
+        assert!(detector.destructive_reason("git push origin main").is_none());
+        assert!(detector.destructive_reason("rm notes.txt").is_none());
+        assert!(detector.destructive_reason("terraform plan").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_check_unprecedented() {
+        let detector = setup().await;
+
+        // Never run in prod
+        let anomaly = detector
+            .check("terraform destroy", "/prod", None)
+            .await
+            .unwrap()
+            .expect("should flag");
+        assert!(anomaly.seen_elsewhere);
+
+        // Routine in the sandbox, on the laptop
+        for host in [None, Some("laptop")] {
+            let found = detector.check("terraform destroy", "/sandbox", host).await.unwrap();
+            assert!(found.is_none());
+        }
+
+        // The same sandbox on the build server is new ground
+        let anomaly = detector
+            .check("terraform destroy", "/sandbox", Some("ci-runner"))
+            .await
+            .unwrap()
+            .expect("should flag");
+        assert_eq!(anomaly.hostname.as_deref(), Some("ci-runner"));
+        assert!(anomaly.seen_elsewhere);
+
+        // Not destructive at all
+        let found = detector.check("terraform plan", "/prod", None).await.unwrap();
+        assert!(found.is_none());
+    }
+}
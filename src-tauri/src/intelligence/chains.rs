@@ -0,0 +1,264 @@
+//! One-liners for sequences you keep typing out step by step
+//!
+//! Chaining a sequential pattern (`a && b && c`) saves the pause between
+//! steps: reading the output, typing the next command. When the steps don't
+//! depend on each other (different tools, nothing one writes that another
+//! reads) they could also run side by side, saving all but the slowest step.
+//! Both are measured from recorded runs.
+
+use crate::db::PatternType;
+use crate::intelligence::pattern_detector::ARG_PLACEHOLDER;
+use crate::intelligence::performance::median;
+use crate::intelligence::{Pattern, PatternDetector};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A pause longer than this between two steps is stepping away, not
+/// getting round to the next one
+const MAX_STEP_GAP_MS: f64 = 5.0 * 60.0 * 1000.0;
+
+/// Flags naming a file the command writes
+const OUTPUT_FLAGS: &[&str] = &["-o", "--output", "--out", "--out-file", "--outfile"];
+
+/// A sequence worth running as one line
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Chain {
+    pub commands: Vec<String>,
+    pub occurrences: usize,
+    /// No two steps use the same tool, and none reads what another writes
+    pub independent: bool,
+    /// Median pause before each step after the first, added up
+    pub idle_ms: Option<f64>,
+    /// Median run time of each step
+    pub step_ms: Vec<Option<f64>>,
+}
+
+impl Chain {
+    /// Measure a sequence against the history
+    pub fn build(commands: &[String], occurrences: usize, timings: &Timings) -> Self {
+        let gaps: Option<Vec<f64>> = commands
+            .windows(2)
+            .map(|pair| {
+                timings
+                    .gaps
+                    .get(&(pair[0].clone(), pair[1].clone()))
+                    .and_then(|gaps| median(gaps))
+            })
+            .collect();
+
+        Self {
+            commands: commands.to_vec(),
+            occurrences,
+            independent: independent(commands),
+            idle_ms: gaps.map(|gaps| gaps.iter().sum()),
+            step_ms: commands
+                .iter()
+                .map(|c| timings.durations.get(c).and_then(|d| median(d)))
+                .collect(),
+        }
+    }
+
+    /// `a && b && c`
+    pub fn one_liner(&self) -> String {
+        self.commands.join(" && ")
+    }
+
+    /// `a & b & c & wait`, for independent chains
+    pub fn side_by_side(&self) -> String {
+        format!("{} & wait", self.commands.join(" & "))
+    }
+
+    /// What running the steps side by side saves each time: all but the
+    /// slowest step. Only for independent chains with every step timed.
+    pub fn parallel_saving_ms(&self) -> Option<f64> {
+        if !self.independent {
+            return None;
+        }
+        let steps: Vec<f64> = self.step_ms.iter().copied().collect::<Option<_>>()?;
+        let slowest = steps.iter().copied().fold(0.0, f64::max);
+        Some(steps.iter().sum::<f64>() - slowest)
+    }
+
+    /// What one run would save: the pauses, plus running side by side when
+    /// the steps are independent
+    pub fn saving_ms(&self) -> Option<f64> {
+        match (self.idle_ms, self.parallel_saving_ms()) {
+            (None, None) => None,
+            (idle, parallel) => Some(idle.unwrap_or(0.0) + parallel.unwrap_or(0.0)),
+        }
+    }
+}
+
+/// How long each command takes and how long you pause between two
+#[derive(Debug, Default)]
+pub struct Timings {
+    durations: HashMap<String, Vec<f64>>,
+    gaps: HashMap<(String, String), Vec<f64>>,
+}
+
+impl Timings {
+    /// From (normalized command, when it finished, how long it took) runs,
+    /// oldest first
+    pub fn from_runs(runs: &[(String, DateTime<Utc>, Option<i32>)]) -> Self {
+        let mut timings = Self::default();
+        for (command, _, ms) in runs {
+            if let Some(ms) = ms {
+                timings
+                    .durations
+                    .entry(command.clone())
+                    .or_default()
+                    .push(*ms as f64);
+            }
+        }
+        for pair in runs.windows(2) {
+            let (previous, finished, _) = &pair[0];
+            let (next, next_finished, next_ms) = &pair[1];
+            // Runs are stamped when they finish
+            let started = *next_finished - Duration::milliseconds(next_ms.unwrap_or(0) as i64);
+            let gap = (started - *finished).num_milliseconds().max(0) as f64;
+            if gap <= MAX_STEP_GAP_MS {
+                timings
+                    .gaps
+                    .entry((previous.clone(), next.clone()))
+                    .or_default()
+                    .push(gap);
+            }
+        }
+        timings
+    }
+}
+
+/// The sequential patterns worth chaining, biggest saving overall first
+pub fn chains(patterns: &[Pattern], timings: &Timings) -> Vec<Chain> {
+    let mut chains: Vec<Chain> = patterns
+        .iter()
+        .filter(|p| matches!(p.pattern_type, PatternType::Sequential) && p.commands.len() > 1)
+        .map(|p| Chain::build(&p.commands, p.occurrences, timings))
+        .filter(|c| c.saving_ms().is_some_and(|ms| ms > 0.0))
+        .collect();
+    let total = |c: &Chain| c.saving_ms().unwrap_or(0.0) * c.occurrences as f64;
+    chains.sort_by(|a, b| total(b).total_cmp(&total(a)));
+    chains
+}
+
+/// The steps as one alias template: `a && b`, each quoted argument the
+/// pattern stood in for becoming `{1}`, `{2}`, ...
+pub fn alias_template(commands: &[String]) -> String {
+    let line = commands.join(" && ");
+    let mut parts = line.split(ARG_PLACEHOLDER);
+    let mut template = parts.next().unwrap_or_default().to_string();
+    for (n, part) in parts.enumerate() {
+        template.push_str(&format!("{{{}}}{}", n + 1, part));
+    }
+    template
+}
+
+/// Whether the steps could run in any order, or all at once
+///
+/// A guess from the text: every step a different tool, and no step naming
+/// a file another one writes (`> out.txt`, `-o build.tar`, `tee log`).
+pub fn independent(commands: &[String]) -> bool {
+    let tools: Vec<String> = commands
+        .iter()
+        .map(|c| PatternDetector::extract_category(c))
+        .collect();
+    if tools
+        .iter()
+        .enumerate()
+        .any(|(i, tool)| tools[..i].contains(tool))
+    {
+        return false;
+    }
+
+    !commands.iter().enumerate().any(|(i, command)| {
+        outputs(command).iter().any(|output| {
+            commands
+                .iter()
+                .enumerate()
+                .any(|(j, other)| j != i && other.split_whitespace().any(|word| word == output))
+        })
+    })
+}
+
+/// Files a command writes, as far as its text says
+fn outputs(command: &str) -> Vec<String> {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let mut outputs = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        if matches!(*word, ">" | ">>" | "tee") || OUTPUT_FLAGS.contains(word) {
+            outputs.extend(words.get(i + 1).map(|w| w.to_string()));
+        } else if let Some(path) = word.strip_prefix(">>").or_else(|| word.strip_prefix('>')) {
+            outputs.push(path.to_string());
+        } else if let Some((flag, path)) = word.split_once('=') {
+            if OUTPUT_FLAGS.contains(&flag) {
+                outputs.push(path.to_string());
+            }
+        }
+    }
+    outputs.retain(|o| !o.is_empty() && !o.starts_with('&'));
+    outputs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(commands: &[&str]) -> Vec<String> {
+        commands.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_chains() {
+        assert!(independent(&strings(&["cargo fmt", "npm run lint"])));
+        assert!(!independent(&strings(&["git add .", "git commit"])));
+        assert!(!independent(&strings(&[
+            "curl -o data.json https://example.com",
+            "jq . data.json"
+        ])));
+        assert!(!independent(&strings(&[
+            "make > build.log",
+            "grep error build.log"
+        ])));
+        assert!(independent(&strings(&["make 2>&1", "npm test"])));
+
+        // cargo fmt (2s), pause 10s, npm run lint (6s); three times over
+        let start = Utc::now();
+        let mut runs = Vec::new();
+        for i in 0..3 {
+            let at = start + Duration::minutes(30 * i);
+            runs.push(("cargo fmt".to_string(), at, Some(2_000)));
+            runs.push((
+                "npm run lint".to_string(),
+                at + Duration::seconds(16),
+                Some(6_000),
+            ));
+        }
+        let timings = Timings::from_runs(&runs);
+
+        let chain = Chain::build(&strings(&["cargo fmt", "npm run lint"]), 3, &timings);
+        assert_eq!(chain.one_liner(), "cargo fmt && npm run lint");
+        assert_eq!(chain.side_by_side(), "cargo fmt & npm run lint & wait");
+        assert_eq!(chain.idle_ms, Some(10_000.0));
+        assert_eq!(chain.parallel_saving_ms(), Some(2_000.0));
+        assert_eq!(chain.saving_ms(), Some(12_000.0));
+
+        // Never seen back to back (the 30 minute gap doesn't count)
+        let reversed = Chain::build(&strings(&["npm run lint", "cargo fmt"]), 3, &timings);
+        assert_eq!(reversed.idle_ms, None);
+
+        let patterns = vec![Pattern {
+            pattern_type: PatternType::Sequential,
+            commands: strings(&["cargo fmt", "npm run lint"]),
+            confidence: 0.9,
+            occurrences: 3,
+            project_path: None,
+        }];
+        assert_eq!(chains(&patterns, &timings), vec![chain]);
+
+        assert_eq!(
+            alias_template(&strings(&["git commit -m <arg>", "git tag <arg>", "git push"])),
+            "git commit -m {1} && git tag {2} && git push"
+        );
+    }
+}
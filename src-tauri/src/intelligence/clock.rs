@@ -0,0 +1,49 @@
+//! Where the intelligence gets the time from
+//!
+//! "Monday morning" suggestions, snoozes running out and recency decay all
+//! depend on when you ask. Everything that reads the time takes a [`Clock`],
+//! so tests (and anyone embedding the library) can pin it to a Friday
+//! afternoon instead of waiting for one.
+
+use chrono::{DateTime, FixedOffset, Local, Utc};
+
+/// A source of the current time
+pub trait Clock: Send + Sync {
+    /// Now, in the local time zone: what "morning" and "Monday" go by
+    fn local(&self) -> DateTime<FixedOffset>;
+
+    fn now(&self) -> DateTime<Utc> {
+        self.local().with_timezone(&Utc)
+    }
+}
+
+/// The system's clock and time zone
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn local(&self) -> DateTime<FixedOffset> {
+        Local::now().fixed_offset()
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Always the same moment, in the offset it was given in
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub DateTime<FixedOffset>);
+
+impl FixedClock {
+    /// From an RFC 3339 time like `2026-03-02T09:30:00+01:00`
+    pub fn parse(rfc3339: &str) -> Option<Self> {
+        DateTime::parse_from_rfc3339(rfc3339).ok().map(Self)
+    }
+}
+
+impl Clock for FixedClock {
+    fn local(&self) -> DateTime<FixedOffset> {
+        self.0
+    }
+}
@@ -1,9 +1,10 @@
-/// Context detection for smart suggestions
-///
-/// Detects the current context to provide relevant command suggestions.
+//! Context detection for smart suggestions
+//!
+//! Detects the current context to provide relevant command suggestions.
 
 use crate::error::Result;
-use chrono::{Datelike, Timelike};
+use crate::intelligence::clock::{Clock, SystemClock};
+use chrono::{DateTime, Datelike, FixedOffset, Timelike};
 use std::env;
 use std::path::Path;
 
@@ -11,6 +12,8 @@ use std::path::Path;
 #[derive(Debug, Clone)]
 pub struct Context {
     pub working_directory: String,
+    /// When it was detected, in local time
+    pub now: DateTime<FixedOffset>,
     pub time_of_day: TimeOfDay,
     pub day_of_week: DayOfWeek,
     pub git_branch: Option<String>,
@@ -56,18 +59,25 @@ pub struct ContextDetector;
 impl ContextDetector {
     /// Detect current context
     pub fn detect() -> Result<Context> {
+        Self::detect_with(&SystemClock)
+    }
+
+    /// Detect current context, with the time taken from `clock`
+    pub fn detect_with(clock: &dyn Clock) -> Result<Context> {
         let working_directory = env::current_dir()?
             .to_str()
             .unwrap_or("/")
             .to_string();
 
-        let time_of_day = Self::detect_time_of_day();
-        let day_of_week = Self::detect_day_of_week();
+        let now = clock.local();
+        let time_of_day = Self::detect_time_of_day(&now);
+        let day_of_week = Self::detect_day_of_week(&now);
         let git_branch = Self::detect_git_branch();
         let project_type = Self::detect_project_type(&working_directory);
 
         Ok(Context {
             working_directory,
+            now,
             time_of_day,
             day_of_week,
             git_branch,
@@ -76,8 +86,7 @@ impl ContextDetector {
     }
 
     /// Detect time of day
-    fn detect_time_of_day() -> TimeOfDay {
-        let now = chrono::Local::now();
+    fn detect_time_of_day(now: &DateTime<FixedOffset>) -> TimeOfDay {
         let hour = now.hour();
 
         match hour {
@@ -89,8 +98,7 @@ impl ContextDetector {
     }
 
     /// Detect day of week
-    fn detect_day_of_week() -> DayOfWeek {
-        let now = chrono::Local::now();
+    fn detect_day_of_week(now: &DateTime<FixedOffset>) -> DayOfWeek {
         match now.weekday() {
             chrono::Weekday::Mon => DayOfWeek::Monday,
             chrono::Weekday::Tue => DayOfWeek::Tuesday,
@@ -107,7 +115,7 @@ impl ContextDetector {
         use std::process::Command;
 
         Command::new("git")
-            .args(&["rev-parse", "--abbrev-ref", "HEAD"])
+            .args(["rev-parse", "--abbrev-ref", "HEAD"])
             .output()
             .ok()
             .and_then(|output| {
@@ -121,7 +129,7 @@ impl ContextDetector {
     }
 
     /// Detect project type from marker files
-    fn detect_project_type(dir: &str) -> Option<ProjectType> {
+    pub fn detect_project_type(dir: &str) -> Option<ProjectType> {
         let path = Path::new(dir);
 
         if path.join("package.json").exists() {
@@ -142,6 +150,36 @@ impl ContextDetector {
     }
 }
 
+impl ProjectType {
+    /// Check if a tool (first word of a command) belongs to this kind of project
+    pub fn matches_tool(&self, tool: &str) -> bool {
+        let tools: &[&str] = match self {
+            ProjectType::Node => &["npm", "npx", "yarn", "pnpm", "node"],
+            ProjectType::Rust => &["cargo", "rustc", "rustup"],
+            ProjectType::Python => &["python", "python3", "pip", "pip3", "pytest", "poetry"],
+            ProjectType::Go => &["go"],
+            ProjectType::Java => &["mvn", "gradle", "./gradlew", "java"],
+            ProjectType::Ruby => &["bundle", "ruby", "rake", "rails"],
+            ProjectType::Other => &[],
+        };
+
+        tools.contains(&tool)
+    }
+
+    /// Guess the kind of project a source file belongs to
+    pub fn from_file_extension(extension: &str) -> Option<ProjectType> {
+        match extension.trim_start_matches('.').to_lowercase().as_str() {
+            "js" | "jsx" | "ts" | "tsx" | "mjs" | "cjs" | "vue" | "svelte" => Some(ProjectType::Node),
+            "rs" => Some(ProjectType::Rust),
+            "py" | "pyi" => Some(ProjectType::Python),
+            "go" => Some(ProjectType::Go),
+            "java" | "kt" | "kts" | "scala" => Some(ProjectType::Java),
+            "rb" | "erb" | "rake" => Some(ProjectType::Ruby),
+            _ => None,
+        }
+    }
+}
+
 impl std::fmt::Display for TimeOfDay {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -170,6 +208,7 @@ impl std::fmt::Display for DayOfWeek {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::intelligence::clock::FixedClock;
 
     #[test]
     fn test_detect_context() {
@@ -180,32 +219,48 @@ mod tests {
         assert!(!ctx.working_directory.is_empty());
     }
 
+    #[test]
+    fn test_project_type_matches_tool() {
+        assert!(ProjectType::Rust.matches_tool("cargo"));
+        assert!(ProjectType::Node.matches_tool("npm"));
+        assert!(!ProjectType::Node.matches_tool("cargo"));
+        assert!(!ProjectType::Other.matches_tool("git"));
+    }
+
+    #[test]
+    fn test_project_type_from_file_extension() {
+        assert_eq!(ProjectType::from_file_extension("rs"), Some(ProjectType::Rust));
+        assert_eq!(ProjectType::from_file_extension(".TSX"), Some(ProjectType::Node));
+        assert_eq!(ProjectType::from_file_extension("md"), None);
+    }
+
+    fn at(rfc3339: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap()
+    }
+
     #[test]
     fn test_time_of_day() {
-        let time = ContextDetector::detect_time_of_day();
-        // Just ensure it returns something valid
-        assert!(matches!(
-            time,
-            TimeOfDay::Morning
-                | TimeOfDay::Afternoon
-                | TimeOfDay::Evening
-                | TimeOfDay::Night
-        ));
+        let time_of_day = |t: &str| ContextDetector::detect_time_of_day(&at(t));
+        assert_eq!(time_of_day("2026-03-02T05:59:00+00:00"), TimeOfDay::Night);
+        assert_eq!(time_of_day("2026-03-02T06:00:00+00:00"), TimeOfDay::Morning);
+        assert_eq!(time_of_day("2026-03-02T12:00:00+00:00"), TimeOfDay::Afternoon);
+        assert_eq!(time_of_day("2026-03-02T21:59:00+00:00"), TimeOfDay::Evening);
+        // Local time, not UTC: 23:30 UTC is morning in Auckland
+        assert_eq!(time_of_day("2026-03-02T11:30:00+13:00"), TimeOfDay::Morning);
     }
 
     #[test]
     fn test_day_of_week() {
-        let day = ContextDetector::detect_day_of_week();
-        // Just ensure it returns something valid
-        assert!(matches!(
-            day,
-            DayOfWeek::Monday
-                | DayOfWeek::Tuesday
-                | DayOfWeek::Wednesday
-                | DayOfWeek::Thursday
-                | DayOfWeek::Friday
-                | DayOfWeek::Saturday
-                | DayOfWeek::Sunday
-        ));
+        let day = |t: &str| ContextDetector::detect_day_of_week(&at(t));
+        assert_eq!(day("2026-03-02T09:00:00+00:00"), DayOfWeek::Monday);
+        assert_eq!(day("2026-03-06T16:00:00+00:00"), DayOfWeek::Friday);
+        // Still Sunday evening in New York when it's Monday in UTC
+        assert_eq!(day("2026-03-01T22:00:00-05:00"), DayOfWeek::Sunday);
+
+        let clock = FixedClock::parse("2026-03-02T09:30:00+01:00").unwrap();
+        let context = ContextDetector::detect_with(&clock).unwrap();
+        assert_eq!(context.now, clock.0);
+        assert_eq!(context.day_of_week, DayOfWeek::Monday);
+        assert_eq!(context.time_of_day, TimeOfDay::Morning);
     }
 }
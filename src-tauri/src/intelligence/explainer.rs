@@ -0,0 +1,230 @@
+//! Suggestion explainer
+//!
+//! Rebuilds every input the Scorer uses for a command so users can see
+//! exactly why something gets recommended.
+
+use crate::db::Database;
+use crate::error::Result;
+use crate::intelligence::{
+    Clock, ContextDetector, Pattern, PatternDetector, ProjectType, Scorer, SystemClock,
+};
+use std::sync::Arc;
+
+/// Max supporting patterns to list
+const MAX_SUPPORTING_PATTERNS: usize = 5;
+
+/// Full scoring breakdown for one command
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub command: String,
+    pub usage_count: i32,
+    pub max_usage_count: i32,
+    pub days_since_last_use: Option<f64>,
+    /// (description, matched) for each context factor checked
+    pub context_factors: Vec<(String, bool)>,
+    pub times_accepted: i64,
+    pub times_rejected: i64,
+    pub frequency: f64,
+    pub recency: f64,
+    pub pattern_confidence: f64,
+    pub context_match: f64,
+    pub acceptance_rate: f64,
+    pub score: f64,
+    /// Sequential patterns the command shows up in, most common first
+    pub supporting_patterns: Vec<Pattern>,
+}
+
+/// Explains suggestion scores
+pub struct Explainer {
+    db: Arc<Database>,
+    pattern_detector: PatternDetector,
+    clock: Arc<dyn Clock>,
+}
+
+impl Explainer {
+    /// Create a new explainer
+    pub fn new(db: Arc<Database>) -> Self {
+        let pattern_detector = PatternDetector::new(Arc::clone(&db));
+
+        Self {
+            db,
+            pattern_detector,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Measure recency from `clock` instead of the system's
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Work out the score breakdown for a command
+    ///
+    /// # Arguments
+    /// * `command` - The command text
+    /// * `project_path` - Project to score it in (None for all projects)
+    pub async fn explain(&self, command: &str, project_path: Option<&str>) -> Result<Explanation> {
+        // Frequency: compared to the most used command in the same scope
+        let found = self.db.find_command(command, project_path).await?;
+        let usage_count = found.as_ref().map(|c| c.usage_count).unwrap_or(0);
+        let max_usage_count = self
+            .db
+            .get_most_used_commands(project_path, 1)
+            .await?
+            .first()
+            .map(|c| c.usage_count)
+            .unwrap_or(0);
+        let frequency = Scorer::calculate_frequency_weight(usage_count, max_usage_count);
+
+        // Recency: decay since the last run
+        let days_since_last_use = found
+            .as_ref()
+            .map(|c| (self.clock.now() - c.timestamp).num_seconds().max(0) as f64 / 86_400.0);
+        let recency = days_since_last_use
+            .map(Scorer::calculate_recency_weight)
+            .unwrap_or(0.0);
+
+        // Pattern: strongest sequence this command is part of
+        let normalized = self.pattern_detector.normalize_command(command);
+        let mut supporting_patterns: Vec<Pattern> = self
+            .pattern_detector
+            .detect_sequential_patterns(project_path)
+            .await?
+            .into_iter()
+            .filter(|p| p.commands.contains(&normalized))
+            .collect();
+        supporting_patterns.sort_by(|a, b| {
+            b.occurrences
+                .cmp(&a.occurrences)
+                .then(b.confidence.total_cmp(&a.confidence))
+        });
+        let pattern_confidence = supporting_patterns
+            .iter()
+            .map(|p| p.confidence)
+            .fold(0.0, f64::max);
+        supporting_patterns.truncate(MAX_SUPPORTING_PATTERNS);
+
+        // Context: has it been run here, and does it fit the project
+        let tool = command.split_whitespace().next().unwrap_or("");
+        let project_type = match project_path {
+            Some(path) => ContextDetector::detect_project_type(path),
+            None => None,
+        };
+        let context_factors = vec![
+            ("Used in this project before".to_string(), usage_count > 0),
+            (
+                match &project_type {
+                    Some(t) if *t != ProjectType::Other => format!("Fits a {:?} project", t),
+                    _ => "Fits the project type".to_string(),
+                },
+                project_type.is_some_and(|t| t.matches_tool(tool)),
+            ),
+        ];
+        let matched = context_factors.iter().filter(|(_, m)| *m).count();
+        let context_match = Scorer::calculate_context_match(matched, context_factors.len());
+
+        // Acceptance: what you did the last times it was suggested
+        let (times_accepted, times_rejected) = self.db.get_suggestion_feedback(command).await?;
+        let total = times_accepted + times_rejected;
+        let acceptance_rate = if total == 0 {
+            0.0
+        } else {
+            times_accepted as f64 / total as f64
+        };
+
+        let score = Scorer::calculate_suggestion_score(
+            frequency,
+            recency,
+            pattern_confidence,
+            context_match,
+            acceptance_rate,
+        );
+
+        Ok(Explanation {
+            command: command.to_string(),
+            usage_count,
+            max_usage_count,
+            days_since_last_use,
+            context_factors,
+            times_accepted,
+            times_rejected,
+            frequency,
+            recency,
+            pattern_confidence,
+            context_match,
+            acceptance_rate,
+            score,
+            supporting_patterns,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CommandInput;
+
+    async fn setup() -> Explainer {
+        let db = Arc::new(Database::new_test().await.unwrap());
+
+        for _ in 0..3 {
+            for cmd in &["git add .", "git commit -m 'wip'", "git push"] {
+                db.record_command(CommandInput {
+                    project_path: "/test".to_string(),
+                    command: cmd.to_string(),
+                    execution_time_ms: None,
+                    exit_code: Some(0),
+                    context: None,
+                })
+                .await
+                .unwrap();
+            }
+        }
+
+        Explainer::new(db)
+    }
+
+    #[tokio::test]
+    async fn test_explain_known_command() {
+        let explainer = setup().await;
+
+        let explanation = explainer.explain("git push", Some("/test")).await.unwrap();
+
+        assert_eq!(explanation.usage_count, 3);
+        assert_eq!(explanation.frequency, 1.0);
+        assert!(explanation.recency > 0.9);
+        assert!(explanation.pattern_confidence > 0.0);
+        assert!(!explanation.supporting_patterns.is_empty());
+        assert!(explanation.score > 0.0 && explanation.score <= 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_explain_unknown_command() {
+        let explainer = setup().await;
+
+        let explanation = explainer.explain("make deploy", Some("/test")).await.unwrap();
+
+        assert_eq!(explanation.usage_count, 0);
+        assert_eq!(explanation.frequency, 0.0);
+        assert_eq!(explanation.recency, 0.0);
+        assert!(explanation.supporting_patterns.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_recency_follows_the_clock() {
+        let month_later = (chrono::Utc::now() + chrono::Duration::days(30)).fixed_offset();
+        let explainer = setup()
+            .await
+            .with_clock(Arc::new(crate::intelligence::FixedClock(month_later)));
+
+        let explanation = explainer.explain("git push", Some("/test")).await.unwrap();
+
+        let days = explanation.days_since_last_use.unwrap();
+        assert!((days - 30.0).abs() < 0.01, "{} days", days);
+        assert!(
+            (explanation.recency - Scorer::calculate_recency_weight(days)).abs() < 1e-9
+        );
+        assert!(explanation.recency < 0.5);
+    }
+}
@@ -0,0 +1,68 @@
+//! Lockfile change detection
+//!
+//! A lockfile written after the last install you ran in a project (a pull, a
+//! branch switch, a teammate's bump) means what's installed is out of date.
+
+use chrono::{DateTime, Utc};
+use std::path::Path;
+
+/// A lockfile and the commands that bring a project in line with it
+pub struct Lockfile {
+    pub file: &'static str,
+    pub installs: &'static [&'static str],
+}
+
+/// Lockfiles worth watching
+pub const LOCKFILES: &[Lockfile] = &[
+    Lockfile {
+        file: "Cargo.lock",
+        // Any of these fetches and builds what the lockfile asks for
+        installs: &[
+            "cargo build",
+            "cargo fetch",
+            "cargo check",
+            "cargo test",
+            "cargo run",
+        ],
+    },
+    Lockfile {
+        file: "package-lock.json",
+        installs: &["npm install", "npm i", "npm ci"],
+    },
+    Lockfile {
+        file: "poetry.lock",
+        installs: &["poetry install", "poetry sync"],
+    },
+];
+
+/// Whether the file at `path` was modified after `since`
+///
+/// Compared in whole seconds, like the timestamps we record: an install that
+/// rewrote the lockfile a moment before it was recorded doesn't count.
+pub fn modified_after(path: &Path, since: DateTime<Utc>) -> bool {
+    path.metadata()
+        .and_then(|meta| meta.modified())
+        .map(|modified| DateTime::<Utc>::from(modified).timestamp() > since.timestamp())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_modified_after() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = dir.path().join("package-lock.json");
+        fs::write(&lock, "{}").unwrap();
+
+        let hour = chrono::Duration::hours(1);
+        assert!(modified_after(&lock, Utc::now() - hour));
+        assert!(!modified_after(&lock, Utc::now() + hour));
+        assert!(!modified_after(
+            &dir.path().join("poetry.lock"),
+            Utc::now() - hour
+        ));
+    }
+}
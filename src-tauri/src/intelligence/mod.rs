@@ -1,15 +1,37 @@
-/// Intelligence module
-///
-/// Handles pattern detection and smart suggestions based on command history.
+//! Intelligence module
+//!
+//! Handles pattern detection and smart suggestions based on command history.
 
 pub mod analyzer;
+pub mod anomaly_detector;
+pub mod chains;
+pub mod clock;
 pub mod context_detector;
+pub mod explainer;
+pub mod lockfiles;
 pub mod pattern_detector;
+pub mod pattern_graph;
+pub mod performance;
+pub mod plugins;
+pub mod providers;
+pub mod reliability;
 pub mod scorer;
+pub mod shortenings;
 pub mod suggestion_engine;
+pub mod suggestion_rules;
+pub mod trends;
 
 pub use analyzer::Analyzer;
+pub use anomaly_detector::{Anomaly, AnomalyDetector};
+pub use chains::Chain;
+pub use clock::{Clock, FixedClock, SystemClock};
 pub use context_detector::{Context, ContextDetector, DayOfWeek, ProjectType, TimeOfDay};
+pub use explainer::{Explainer, Explanation};
 pub use pattern_detector::{Pattern, PatternDetector};
+pub use pattern_graph::{GraphEdge, GraphFormat, PatternGraph};
+pub use performance::{PerfAnalyzer, PerfReport};
+pub use providers::SuggestionProvider;
+pub use reliability::{FlakyCommand, ReliabilityAnalyzer};
 pub use scorer::Scorer;
 pub use suggestion_engine::{SmartSuggestion, SuggestionEngine};
+pub use suggestion_rules::SuggestionRules;
@@ -3,8 +3,13 @@
 // Like when you always run "git add ." then "git commit" then "git push"
 // Or when you keep running the same 3 docker commands in order
 
-use crate::db::{Command, Database, PatternType};
+use crate::core::triggers::{Event, Triggers};
+use crate::db::{parse_timestamp, Command, Config, Database, Execution, PatternType};
 use crate::error::Result;
+use crate::intelligence::PatternGraph;
+use chrono::Duration;
+use regex::Regex;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::sync::Arc;
 
@@ -14,7 +19,37 @@ const MIN_PATTERN_OCCURRENCES: usize = 3;
 // Only save patterns we're at least 60% confident about
 const MIN_CONFIDENCE: f64 = 0.6;
 
-#[derive(Debug, Clone)]
+// How many past executions the first analysis of a project looks at
+const HISTORY_WINDOW: i64 = 1000;
+
+// Sequence lengths we look for
+const WINDOW_SIZES: [usize; 4] = [2, 3, 4, 5];
+
+// Already counted runs loaded in front of the new ones, so a sequence that
+// started before the last analysis and finished after it still counts
+const CONTEXT_RUNS: i64 = 5;
+
+// Preference holding the last execution ID each project was analyzed up to
+const HIGH_WATER_PREFIX: &str = "patterns.last_execution:";
+
+// Quoted arguments (commit messages, echo strings) vary every run
+const QUOTED_ARG_PATTERN: &str = r#"'[^']*'|"[^"]*""#;
+
+// What quoted arguments get replaced with so the runs line up
+pub(crate) const ARG_PLACEHOLDER: &str = "<arg>";
+
+// A run with one unrelated command in the middle (add -> ls -> commit) still
+// supports add -> commit, but only counts this much of an exact run
+const GAP_DISCOUNT: f64 = 0.5;
+
+// Occurrences needed for full base confidence scale with how much history a
+// project has: 3 repeats in a quiet side project mean more than 3 in your
+// main repo. One extra occurrence needed per this many runs, within the bounds.
+const RUNS_PER_REQUIRED_OCCURRENCE: f64 = 50.0;
+const MIN_REQUIRED_OCCURRENCES: f64 = 5.0;
+const MAX_REQUIRED_OCCURRENCES: f64 = 20.0;
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Pattern {
     pub pattern_type: PatternType,
     pub commands: Vec<String>,
@@ -25,11 +60,30 @@ pub struct Pattern {
 
 pub struct PatternDetector {
     db: Arc<Database>,
+    quoted_arg: Regex,
 }
 
 impl PatternDetector {
     pub fn new(db: Arc<Database>) -> Self {
-        Self { db }
+        Self {
+            db,
+            quoted_arg: Regex::new(QUOTED_ARG_PATTERN).expect("quoted arg pattern is valid"),
+        }
+    }
+
+    // Turns `git commit -m 'fix'` and `git commit -m 'wip'` into the same thing
+    pub fn normalize_command(&self, command: &str) -> String {
+        self.quoted_arg
+            .replace_all(command, ARG_PLACEHOLDER)
+            .into_owned()
+    }
+
+    // Sequential patterns plus how often each step actually follows the previous one
+    pub async fn pattern_graph(&self, project_path: Option<&str>) -> Result<PatternGraph> {
+        let sessions = self.load_history(project_path).await?;
+        let patterns = self.sequential_patterns_from(&sessions, project_path);
+
+        Ok(PatternGraph::build(&patterns, &sessions))
     }
 
     // Main function - finds all patterns in your history
@@ -44,9 +98,22 @@ impl PatternDetector {
         let frequency = self.detect_frequency_patterns(project_path).await?;
         patterns.extend(frequency);
 
-        // Only keep the good ones and save to db
+        // Ones you've called noise are still counted, just not passed on
+        let dismissed = self.db.get_dismissed_patterns().await?;
+        patterns.retain(|pattern| {
+            !dismissed.iter().any(|row| {
+                row.pattern_type == pattern.pattern_type
+                    && row.project_path == pattern.project_path
+                    && row.get_commands() == pattern.commands
+            })
+        });
+
+        // Only keep the good ones and save to db (sequences are already stored
+        // as they're counted)
         for pattern in &patterns {
-            if pattern.confidence >= MIN_CONFIDENCE {
+            if pattern.confidence >= MIN_CONFIDENCE
+                && !matches!(pattern.pattern_type, PatternType::Sequential)
+            {
                 let metadata = serde_json::json!({
                     "detected_at": chrono::Utc::now().to_rfc3339(),
                     "method": "auto"
@@ -55,7 +122,7 @@ impl PatternDetector {
                 let _ = self
                     .db
                     .store_pattern(
-                        pattern.pattern_type.clone(),
+                        pattern.pattern_type,
                         pattern.commands.clone(),
                         pattern.project_path.clone(),
                         pattern.confidence,
@@ -70,73 +137,319 @@ impl PatternDetector {
 
     /// Detect sequential patterns (commands that follow each other)
     ///
-    /// Uses sliding window algorithm to find command sequences
-    async fn detect_sequential_patterns(&self, project_path: Option<&str>) -> Result<Vec<Pattern>> {
-        let commands = self.db.get_recent_commands(project_path, 1000).await?;
+    /// Incremental: only executions recorded since the last run are read,
+    /// and their sequence counts are added to the stored ones, so the cost
+    /// grows with new commands rather than with the whole history. The first
+    /// run for a project starts from the last `HISTORY_WINDOW` executions.
+    pub async fn detect_sequential_patterns(
+        &self,
+        project_path: Option<&str>,
+    ) -> Result<Vec<Pattern>> {
+        let high_water_key = format!("{}{}", HIGH_WATER_PREFIX, project_path.unwrap_or("*"));
+        let high_water = self
+            .db
+            .get_preference(&high_water_key)
+            .await?
+            .and_then(|v| v.parse::<i64>().ok());
+
+        // No mark yet: whatever was stored came from full rescans, start over
+        if high_water.is_none() {
+            self.db.delete_sequence_patterns(project_path).await?;
+        }
 
-        if commands.len() < 3 {
-            return Ok(Vec::new());
+        let new_runs = self
+            .db
+            .get_executions_between(project_path, high_water.unwrap_or(0), i64::MAX, HISTORY_WINDOW)
+            .await?;
+
+        if let Some(newest) = new_runs.last().map(|e| e.id) {
+            let mut history = match high_water {
+                Some(mark) => {
+                    self.db
+                        .get_executions_between(project_path, 0, mark, CONTEXT_RUNS)
+                        .await?
+                }
+                None => Vec::new(),
+            };
+            let first_new = history.len();
+            history.extend(new_runs);
+
+            let sessions = self.split_sessions(&history, self.session_gap().await?);
+            let counts: Vec<(Vec<String>, i64, i64)> = self
+                .count_new_sequences(&sessions, first_new)
+                .into_iter()
+                .map(|(commands, (exact, gapped))| (commands, exact as i64, gapped as i64))
+                .collect();
+
+            self.db
+                .merge_sequence_counts(project_path, &counts, &high_water_key, newest)
+                .await?;
         }
 
+        let history_len = self.db.count_executions(project_path).await? as usize;
+        let stored = self
+            .db
+            .get_sequence_patterns(project_path, MIN_PATTERN_OCCURRENCES as i64)
+            .await?;
+
         let mut patterns = Vec::new();
-        let window_sizes = [2, 3, 4, 5]; // Different sequence lengths
+        let mut newly_found = Vec::new();
+        for row in stored {
+            let commands = row.get_commands();
+            let metadata = row.get_metadata();
+            let count = |key: &str| metadata.get(key).and_then(|v| v.as_u64());
+            let exact = count("exact").unwrap_or(row.occurrences.max(0) as u64) as usize;
+            let gapped = count("gapped").unwrap_or(0) as usize;
+
+            let support = self.sequence_support(exact, gapped);
+            if support < MIN_PATTERN_OCCURRENCES as f64 {
+                continue;
+            }
+
+            let confidence =
+                self.calculate_sequence_confidence(support, commands.len(), history_len);
+            if (confidence - row.confidence_score).abs() > f64::EPSILON {
+                self.db.update_pattern_confidence(row.id, confidence).await?;
+            }
+            if row.confidence_score < MIN_CONFIDENCE && confidence >= MIN_CONFIDENCE {
+                newly_found.push((commands.join(" → "), confidence));
+            }
 
-        for window_size in window_sizes {
-            let sequences = self.extract_sequences(&commands, window_size);
-            let pattern_candidates = self.find_frequent_sequences(sequences, window_size);
+            patterns.push(Pattern {
+                pattern_type: PatternType::Sequential,
+                commands,
+                confidence,
+                occurrences: exact + gapped,
+                project_path: project_path.map(|s| s.to_string()),
+            });
+        }
 
-            patterns.extend(pattern_candidates);
+        // Tell whoever hooked up a trigger
+        if !newly_found.is_empty() {
+            let triggers = Triggers::load(&self.db).await?;
+            for (sequence, confidence) in newly_found {
+                let fields = [
+                    ("pattern", sequence.clone()),
+                    ("confidence", format!("{:.2}", confidence)),
+                    ("project", project_path.unwrap_or_default().to_string()),
+                ];
+                triggers.fire(Event::PatternFound, Some(&sequence), &fields);
+            }
         }
 
         Ok(patterns)
     }
 
+    /// Count the sequences that end on a new command
+    ///
+    /// Everything before `first_new` (across sessions) was counted by an
+    /// earlier run and is only there so sequences can start in it.
+    fn count_new_sequences(
+        &self,
+        sessions: &[Vec<String>],
+        first_new: usize,
+    ) -> HashMap<Vec<String>, (usize, usize)> {
+        let mut counts: HashMap<Vec<String>, (usize, usize)> = HashMap::new();
+
+        for window_size in WINDOW_SIZES {
+            let mut exact_tails = Vec::new();
+            let mut gapped_tails = Vec::new();
+            let mut offset = 0;
+
+            for session in sessions {
+                let new_at = first_new.saturating_sub(offset).min(session.len());
+                exact_tails.push(session[new_at.saturating_sub(window_size - 1)..].to_vec());
+                gapped_tails.push(session[new_at.saturating_sub(window_size)..].to_vec());
+                offset += session.len();
+            }
+
+            for seq in self.extract_sequences(&exact_tails, window_size) {
+                counts.entry(seq).or_default().0 += 1;
+            }
+            for seq in self.extract_skip_grams(&gapped_tails, window_size) {
+                counts.entry(seq).or_default().1 += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// Load the normalized execution history, oldest first, split into sessions
+    async fn load_history(&self, project_path: Option<&str>) -> Result<Vec<Vec<String>>> {
+        let executions = self
+            .db
+            .get_execution_history(project_path, HISTORY_WINDOW)
+            .await?;
+
+        Ok(self.split_sessions(&executions, self.session_gap().await?))
+    }
+
+    /// How long a pause ends a session, so yesterday's last command and
+    /// today's first never count as a sequence
+    async fn session_gap(&self) -> Result<Duration> {
+        let gap_minutes = Config::load(&self.db).await?.session_gap_minutes();
+        Ok(Duration::minutes(gap_minutes))
+    }
+
+    /// Break the history wherever the user stepped away for longer than `gap`
+    fn split_sessions(&self, executions: &[Execution], gap: Duration) -> Vec<Vec<String>> {
+        let mut sessions: Vec<Vec<String>> = Vec::new();
+        let mut last_at = None;
+
+        for execution in executions {
+            let at = parse_timestamp(&execution.executed_at);
+            let new_session = match (last_at, at) {
+                (Some(prev), Some(now)) => now - prev > gap,
+                _ => sessions.is_empty(),
+            };
+
+            if new_session {
+                sessions.push(Vec::new());
+            }
+            if let Some(session) = sessions.last_mut() {
+                session.push(self.normalize_command(&execution.command));
+            }
+            last_at = at.or(last_at);
+        }
+
+        sessions
+    }
+
+    /// Run the sliding window over an already loaded history
+    fn sequential_patterns_from(&self, sessions: &[Vec<String>], project_path: Option<&str>) -> Vec<Pattern> {
+        let history_len: usize = sessions.iter().map(|s| s.len()).sum();
+        if history_len < 3 {
+            return Vec::new();
+        }
+
+        let mut patterns = Vec::new();
+
+        for window_size in WINDOW_SIZES {
+            let sequences = self.extract_sequences(sessions, window_size);
+            let gapped = self.extract_skip_grams(sessions, window_size);
+            let pattern_candidates = self.find_frequent_sequences(
+                sequences,
+                gapped,
+                window_size,
+                history_len,
+                project_path,
+            );
+
+            patterns.extend(pattern_candidates);
+        }
+
+        patterns
+    }
+
     /// Extract command sequences using sliding window
-    fn extract_sequences(&self, commands: &[Command], window_size: usize) -> Vec<Vec<String>> {
-        let mut sequences = Vec::new();
+    ///
+    /// Windows never span two sessions.
+    fn extract_sequences(&self, sessions: &[Vec<String>], window_size: usize) -> Vec<Vec<String>> {
+        sessions
+            .iter()
+            .flat_map(|session| session.windows(window_size).map(|w| w.to_vec()))
+            .collect()
+    }
+
+    /// Extract sequences with one unrelated command skipped
+    ///
+    /// Looks at windows one longer than `window_size` and drops one of the
+    /// middle commands. Runs that are already exact at the same spot are left
+    /// to `extract_sequences` so they aren't counted twice.
+    fn extract_skip_grams(&self, sessions: &[Vec<String>], window_size: usize) -> Vec<Vec<String>> {
+        let mut skip_grams = Vec::new();
+
+        for session in sessions {
+            for span in session.windows(window_size + 1) {
+                let exact = &span[..window_size];
+                let mut seen: Vec<Vec<String>> = Vec::new();
+
+                for skipped in 1..window_size {
+                    let candidate: Vec<String> = span
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| *i != skipped)
+                        .map(|(_, cmd)| cmd.clone())
+                        .collect();
+
+                    if candidate != exact && !seen.contains(&candidate) {
+                        seen.push(candidate);
+                    }
+                }
 
-        for window in commands.windows(window_size) {
-            let sequence: Vec<String> = window.iter().map(|c| c.command.clone()).collect();
-            sequences.push(sequence);
+                skip_grams.extend(seen);
+            }
         }
 
-        sequences
+        skip_grams
     }
 
     /// Find frequent sequences and calculate confidence
-    fn find_frequent_sequences(&self, sequences: Vec<Vec<String>>, window_size: usize) -> Vec<Pattern> {
-        let mut sequence_counts: HashMap<Vec<String>, usize> = HashMap::new();
+    ///
+    /// Patterns keep the project they were found in, so one repo's workflow
+    /// never shows up as a suggestion in another.
+    fn find_frequent_sequences(
+        &self,
+        sequences: Vec<Vec<String>>,
+        gapped: Vec<Vec<String>>,
+        window_size: usize,
+        history_len: usize,
+        project_path: Option<&str>,
+    ) -> Vec<Pattern> {
+        // (exact runs, runs with a gap)
+        let mut sequence_counts: HashMap<Vec<String>, (usize, usize)> = HashMap::new();
 
         // Count occurrences
         for seq in sequences {
-            *sequence_counts.entry(seq).or_insert(0) += 1;
+            sequence_counts.entry(seq).or_default().0 += 1;
+        }
+        for seq in gapped {
+            sequence_counts.entry(seq).or_default().1 += 1;
         }
 
         // Filter and create patterns
         sequence_counts
             .into_iter()
-            .filter(|(_, count)| *count >= MIN_PATTERN_OCCURRENCES)
-            .map(|(commands, occurrences)| {
-                let confidence = self.calculate_sequence_confidence(occurrences, window_size);
+            .map(|(commands, (exact, with_gap))| {
+                let support = self.sequence_support(exact, with_gap);
+                (commands, exact + with_gap, support)
+            })
+            .filter(|(_, _, support)| *support >= MIN_PATTERN_OCCURRENCES as f64)
+            .map(|(commands, occurrences, support)| {
+                let confidence =
+                    self.calculate_sequence_confidence(support, window_size, history_len);
 
                 Pattern {
                     pattern_type: PatternType::Sequential,
                     commands,
                     confidence,
                     occurrences,
-                    project_path: None,
+                    project_path: project_path.map(|s| s.to_string()),
                 }
             })
             .collect()
     }
 
+    /// Exact runs count fully, runs with a gap count for `GAP_DISCOUNT`
+    fn sequence_support(&self, exact: usize, with_gap: usize) -> f64 {
+        exact as f64 + with_gap as f64 * GAP_DISCOUNT
+    }
+
     /// Calculate confidence score for sequential patterns
     ///
     /// Confidence increases with:
-    /// - Number of occurrences
+    /// - Support (occurrences, with gapped runs discounted), relative to the
+    ///   project's command volume
     /// - Sequence length
-    fn calculate_sequence_confidence(&self, occurrences: usize, window_size: usize) -> f64 {
-        let base_confidence = (occurrences as f64 / 10.0).min(0.7);
+    fn calculate_sequence_confidence(
+        &self,
+        support: f64,
+        window_size: usize,
+        history_len: usize,
+    ) -> f64 {
+        let required = (history_len as f64 / RUNS_PER_REQUIRED_OCCURRENCE)
+            .clamp(MIN_REQUIRED_OCCURRENCES, MAX_REQUIRED_OCCURRENCES);
+        let base_confidence = (support / required * 0.7).min(0.7);
         let length_bonus = (window_size as f64 / 10.0).min(0.3);
 
         (base_confidence + length_bonus).min(1.0)
@@ -179,10 +492,10 @@ impl PatternDetector {
         let mut categories: HashMap<String, Vec<Command>> = HashMap::new();
 
         for cmd in commands {
-            let category = self.extract_category(&cmd.command);
+            let category = Self::extract_category(&cmd.command);
             categories
                 .entry(category)
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(cmd.clone());
         }
 
@@ -190,7 +503,7 @@ impl PatternDetector {
     }
 
     /// Extract category from command (first word)
-    fn extract_category(&self, command: &str) -> String {
+    pub fn extract_category(command: &str) -> String {
         command
             .split_whitespace()
             .next()
@@ -252,21 +565,183 @@ mod tests {
         assert!(!sequential.is_empty());
     }
 
+    #[test]
+    fn test_extract_category() {
+        assert_eq!(PatternDetector::extract_category("git add ."), "git");
+        assert_eq!(PatternDetector::extract_category("npm install"), "npm");
+        assert_eq!(PatternDetector::extract_category("docker ps"), "docker");
+    }
+
+    #[tokio::test]
+    async fn test_normalize_command() {
+        let detector = setup().await;
+
+        assert_eq!(
+            detector.normalize_command("git commit -m 'fix the thing'"),
+            "git commit -m <arg>"
+        );
+        assert_eq!(
+            detector.normalize_command(r#"echo "hi" > out.txt"#),
+            "echo <arg> > out.txt"
+        );
+        assert_eq!(detector.normalize_command("cargo build"), "cargo build");
+    }
+
     #[tokio::test]
-    async fn test_extract_category() {
+    async fn test_pattern_graph() {
         let detector = setup().await;
 
-        assert_eq!(detector.extract_category("git add ."), "git");
-        assert_eq!(detector.extract_category("npm install"), "npm");
-        assert_eq!(detector.extract_category("docker ps"), "docker");
+        let graph = detector.pattern_graph(Some("/test")).await.unwrap();
+
+        let edge = graph
+            .edges
+            .iter()
+            .find(|e| e.from == "git add ." && e.to == "git commit -m <arg>")
+            .expect("add -> commit edge");
+        assert_eq!(edge.count, 3);
+        assert_eq!(edge.probability, 1.0);
     }
 
     #[tokio::test]
     async fn test_confidence_calculation() {
         let detector = setup().await;
 
-        let confidence = detector.calculate_sequence_confidence(5, 3);
+        let confidence = detector.calculate_sequence_confidence(5.0, 3, 100);
         assert!(confidence >= MIN_CONFIDENCE);
         assert!(confidence <= 1.0);
+
+        // Same repeats count for less in a busy project
+        let busy = detector.calculate_sequence_confidence(5.0, 3, 1000);
+        assert!(busy < confidence);
+    }
+
+    #[tokio::test]
+    async fn test_split_sessions_at_gaps() {
+        let detector = setup().await;
+
+        let execution = |command: &str, at: &str| Execution {
+            id: 0,
+            command_id: 0,
+            project_path: "/test".to_string(),
+            command: command.to_string(),
+            executed_at: at.to_string(),
+            execution_time_ms: None,
+            exit_code: None,
+            session_id: None,
+            hostname: None,
+            cwd: None,
+            env: None,
+            branch: None,
+            interactive: None,
+            privileged: None,
+            rapid_repeat_count: None,
+        };
+        let history = vec![
+            execution("git add .", "2025-11-24 17:50:00"),
+            execution("git push", "2025-11-24 17:55:00"),
+            execution("git pull", "2025-11-25 09:00:00"),
+            execution("npm test", "2025-11-25 09:20:00"),
+        ];
+
+        let sessions = detector.split_sessions(&history, Duration::minutes(30));
+        assert_eq!(
+            sessions,
+            vec![vec!["git add .", "git push"], vec!["git pull", "npm test"]]
+        );
+
+        // push -> pull crossed the night, so it's not a sequence
+        let pairs = detector.extract_sequences(&sessions, 2);
+        assert_eq!(pairs.len(), 2);
+        assert!(!pairs.contains(&vec!["git push".to_string(), "git pull".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_skip_grams_tolerate_noise() {
+        let detector = setup().await;
+        let session = |cmds: &[&str]| cmds.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+
+        let skip_grams = detector.extract_skip_grams(&[session(&["git add .", "ls", "git commit"])], 2);
+        assert_eq!(skip_grams, vec![session(&["git add .", "git commit"])]);
+
+        // Three clean runs and two noisy ones: 3 + 2 * 0.5 support
+        let sessions = vec![
+            session(&["git add .", "git commit"]),
+            session(&["git add .", "git commit"]),
+            session(&["git add .", "git commit"]),
+            session(&["git add .", "ls", "git commit"]),
+            session(&["git add .", "git status", "git commit"]),
+        ];
+        let patterns = detector.sequential_patterns_from(&sessions, Some("/test"));
+        let add_commit = patterns
+            .iter()
+            .find(|p| p.commands == session(&["git add .", "git commit"]))
+            .expect("add -> commit survives the noise");
+        assert_eq!(add_commit.occurrences, 5);
+
+        let clean_only = detector.sequential_patterns_from(&sessions[..3], Some("/test"));
+        assert!(add_commit.confidence > clean_only[0].confidence);
+    }
+
+    #[tokio::test]
+    async fn test_sequential_patterns_keep_project() {
+        let detector = setup().await;
+
+        let patterns = detector.detect_sequential_patterns(Some("/test")).await.unwrap();
+        assert!(!patterns.is_empty());
+        assert!(patterns
+            .iter()
+            .all(|p| p.project_path.as_deref() == Some("/test")));
+
+        // Nothing from /test leaks into another project
+        let other = detector.detect_sequential_patterns(Some("/other")).await.unwrap();
+        assert!(other.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_incremental_detection() {
+        let detector = setup().await;
+        let add_commit = vec!["git add .".to_string(), "git commit -m <arg>".to_string()];
+        let occurrences = |patterns: &[Pattern]| {
+            patterns
+                .iter()
+                .find(|p| p.commands == add_commit)
+                .map(|p| p.occurrences)
+        };
+
+        let first = detector.detect_sequential_patterns(Some("/test")).await.unwrap();
+        assert_eq!(occurrences(&first), Some(3));
+
+        // Nothing new: counts stay put instead of doubling
+        let again = detector.detect_sequential_patterns(Some("/test")).await.unwrap();
+        assert_eq!(occurrences(&again), Some(3));
+        assert_eq!(again.len(), first.len());
+
+        for cmd in ["git add .", "git commit -m 'more'"] {
+            detector
+                .db
+                .record_command(CommandInput {
+                    project_path: "/test".to_string(),
+                    command: cmd.to_string(),
+                    execution_time_ms: None,
+                    exit_code: Some(0),
+                    context: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let updated = detector.detect_sequential_patterns(Some("/test")).await.unwrap();
+        assert_eq!(occurrences(&updated), Some(4));
+
+        // push -> add spans the old and new runs and still counts
+        let push_add = detector
+            .db
+            .get_sequence_patterns(Some("/test"), 1)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|p| p.get_commands() == ["git push", "git add ."])
+            .expect("push -> add is stored");
+        assert_eq!(push_add.get_metadata()["exact"], 3);
     }
 }
@@ -0,0 +1,256 @@
+//! Pattern graph export
+//!
+//! Renders sequential patterns as a directed graph so workflows can be
+//! inspected visually. Edge weights come from the real execution history.
+
+use crate::db::PatternType;
+use crate::error::{RecallError, Result};
+use crate::intelligence::Pattern;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// Output formats for the graph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for GraphFormat {
+    type Err = RecallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "dot" | "graphviz" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            other => Err(RecallError::Config(format!(
+                "Unknown graph format '{}' (expected dot or mermaid)",
+                other
+            ))),
+        }
+    }
+}
+
+/// A transition between two commands
+#[derive(Debug, Clone)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    /// How many times `to` directly followed `from`
+    pub count: usize,
+    /// Share of runs of `from` that were followed by `to` (0.0-1.0)
+    pub probability: f64,
+}
+
+/// Directed graph of command transitions
+#[derive(Debug, Clone, Default)]
+pub struct PatternGraph {
+    pub nodes: Vec<String>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl PatternGraph {
+    /// Build a graph from sequential patterns
+    ///
+    /// # Arguments
+    /// * `patterns` - Detected patterns (non-sequential ones are ignored)
+    /// * `sessions` - Normalized command history, oldest first, one Vec per session
+    pub fn build(patterns: &[Pattern], sessions: &[Vec<String>]) -> Self {
+        // Count every direct transition in the history, never across sessions
+        let mut transitions: HashMap<(&str, &str), usize> = HashMap::new();
+        let mut outgoing: HashMap<&str, usize> = HashMap::new();
+
+        for pair in sessions.iter().flat_map(|s| s.windows(2)) {
+            *transitions
+                .entry((pair[0].as_str(), pair[1].as_str()))
+                .or_default() += 1;
+            *outgoing.entry(pair[0].as_str()).or_default() += 1;
+        }
+
+        // Longest, most common sequences first so output is stable between runs
+        let mut sequential: Vec<&Pattern> = patterns
+            .iter()
+            .filter(|p| p.pattern_type == PatternType::Sequential)
+            .collect();
+        sequential.sort_by(|a, b| {
+            b.commands
+                .len()
+                .cmp(&a.commands.len())
+                .then(b.occurrences.cmp(&a.occurrences))
+                .then(a.commands.cmp(&b.commands))
+        });
+
+        let mut graph = PatternGraph::default();
+        let mut seen_nodes = HashSet::new();
+        let mut seen_edges = HashSet::new();
+
+        for pattern in sequential {
+            for cmd in &pattern.commands {
+                if seen_nodes.insert(cmd.clone()) {
+                    graph.nodes.push(cmd.clone());
+                }
+            }
+
+            for pair in pattern.commands.windows(2) {
+                let key = (pair[0].clone(), pair[1].clone());
+                if !seen_edges.insert(key) {
+                    continue;
+                }
+
+                let count = transitions
+                    .get(&(pair[0].as_str(), pair[1].as_str()))
+                    .copied()
+                    .unwrap_or(0);
+                let total = outgoing.get(pair[0].as_str()).copied().unwrap_or(0);
+                let probability = if total == 0 {
+                    0.0
+                } else {
+                    count as f64 / total as f64
+                };
+
+                graph.edges.push(GraphEdge {
+                    from: pair[0].clone(),
+                    to: pair[1].clone(),
+                    count,
+                    probability,
+                });
+            }
+        }
+
+        graph
+    }
+
+    /// Check if there's anything to draw
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Render in the requested format
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Mermaid => self.to_mermaid(),
+        }
+    }
+
+    /// Render as Graphviz DOT (pipe into `dot -Tsvg`)
+    pub fn to_dot(&self) -> String {
+        let ids = self.node_ids();
+        let mut out = String::from("digraph patterns {\n    rankdir=LR;\n    node [shape=box];\n");
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            out.push_str(&format!(
+                "    n{} [label=\"{}\"];\n",
+                i,
+                node.replace('\\', "\\\\").replace('"', "\\\"")
+            ));
+        }
+
+        for edge in &self.edges {
+            out.push_str(&format!(
+                "    n{} -> n{} [label=\"{:.0}% ({}x)\"];\n",
+                ids[edge.from.as_str()],
+                ids[edge.to.as_str()],
+                edge.probability * 100.0,
+                edge.count
+            ));
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Render as a Mermaid flowchart (pastes straight into markdown)
+    pub fn to_mermaid(&self) -> String {
+        let ids = self.node_ids();
+        let mut out = String::from("flowchart LR\n");
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let label = node
+                .replace('"', "#quot;")
+                .replace('<', "#lt;")
+                .replace('>', "#gt;");
+            out.push_str(&format!("    n{}[\"{}\"]\n", i, label));
+        }
+
+        for edge in &self.edges {
+            out.push_str(&format!(
+                "    n{} -->|{:.0}% ({}x)| n{}\n",
+                ids[edge.from.as_str()],
+                edge.probability * 100.0,
+                edge.count,
+                ids[edge.to.as_str()]
+            ));
+        }
+
+        out
+    }
+
+    /// Map node labels to their index
+    fn node_ids(&self) -> HashMap<&str, usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.as_str(), i))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_graph() -> PatternGraph {
+        let history: Vec<String> = ["git add .", "git commit", "git push", "git add .", "git commit", "ls"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        let pattern = Pattern {
+            pattern_type: PatternType::Sequential,
+            commands: vec![
+                "git add .".to_string(),
+                "git commit".to_string(),
+                "git push".to_string(),
+            ],
+            confidence: 0.8,
+            occurrences: 3,
+            project_path: None,
+        };
+
+        PatternGraph::build(&[pattern], &[history])
+    }
+
+    #[test]
+    fn test_build_probabilities() {
+        let graph = sample_graph();
+
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.edges.len(), 2);
+
+        // commit was followed by push once and by ls once
+        let commit_push = &graph.edges[1];
+        assert_eq!(commit_push.count, 1);
+        assert_eq!(commit_push.probability, 0.5);
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let graph = sample_graph();
+
+        let dot = graph.render(GraphFormat::Dot);
+        assert!(dot.starts_with("digraph patterns {"));
+        assert!(dot.contains("n0 -> n1 [label=\"100% (2x)\"]"));
+
+        let mermaid = graph.render(GraphFormat::Mermaid);
+        assert!(mermaid.starts_with("flowchart LR"));
+        assert!(mermaid.contains("n1 -->|50% (1x)| n2"));
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("dot".parse::<GraphFormat>().unwrap(), GraphFormat::Dot);
+        assert_eq!("Mermaid".parse::<GraphFormat>().unwrap(), GraphFormat::Mermaid);
+        assert!("svg".parse::<GraphFormat>().is_err());
+    }
+}
@@ -0,0 +1,447 @@
+//! Duration regression tracking
+//!
+//! Follows how long a command takes over the weeks and flags when the latest
+//! runs are clearly slower than they used to be, e.g. a `cargo build` that
+//! crept from 40s to a minute.
+
+use crate::db::{parse_timestamp, Database};
+use crate::error::Result;
+use crate::intelligence::PatternDetector;
+use crate::ui::{self, Glyph};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+/// How many weeks the sparkline covers
+pub const WEEKS_SHOWN: usize = 12;
+
+/// Latest runs compared against everything before them
+const RECENT_RUNS: usize = 5;
+
+/// Recent median this much above the baseline counts as a regression
+const SLOWDOWN_THRESHOLD: f64 = 1.25;
+
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The same, lowest to highest, for terminals without block characters
+const ASCII_SPARK_CHARS: [char; 8] = ['_', '.', '-', ':', '=', '+', '*', '#'];
+
+/// An ETA mentions the slow runs when they're this much above the median
+const SLOW_TAIL_FACTOR: f64 = 1.2;
+
+/// Runs needed before a finished command is compared with its median
+const MIN_SUMMARY_RUNS: usize = 3;
+
+/// Closer than this to the median is "about your usual"
+const SAME_SPEED: f64 = 0.05;
+
+/// Duration history for one command
+#[derive(Debug, Clone)]
+pub struct PerfReport {
+    pub command: String,
+    /// Timed runs found
+    pub runs: usize,
+    /// Median duration per week, oldest first (None for weeks without runs)
+    pub weekly_ms: Vec<Option<f64>>,
+    /// Median of all runs before the recent ones
+    pub baseline_ms: Option<f64>,
+    /// Median of the latest runs
+    pub recent_ms: Option<f64>,
+    /// Median of every run
+    pub typical_ms: Option<f64>,
+    /// 90th percentile, once there are enough runs for it to mean something
+    pub slow_ms: Option<f64>,
+}
+
+impl PerfReport {
+    /// Build a report from timed runs
+    ///
+    /// # Arguments
+    /// * `command` - Normalized command the runs belong to
+    /// * `runs` - (when it ran, duration in ms), oldest first
+    /// * `now` - Reference point for the weekly buckets
+    pub fn build(command: &str, runs: &[(DateTime<Utc>, i32)], now: DateTime<Utc>) -> Self {
+        let mut weeks: Vec<Vec<f64>> = vec![Vec::new(); WEEKS_SHOWN];
+        for (at, ms) in runs {
+            let weeks_ago = (now - *at).num_days().max(0) as usize / 7;
+            if weeks_ago < WEEKS_SHOWN {
+                weeks[WEEKS_SHOWN - 1 - weeks_ago].push(*ms as f64);
+            }
+        }
+
+        let durations: Vec<f64> = runs.iter().map(|(_, ms)| *ms as f64).collect();
+        let (baseline, recent) = if durations.len() >= RECENT_RUNS * 2 {
+            let split = durations.len() - RECENT_RUNS;
+            (median(&durations[..split]), median(&durations[split..]))
+        } else {
+            (None, None)
+        };
+
+        Self {
+            command: command.to_string(),
+            runs: runs.len(),
+            weekly_ms: weeks.iter().map(|w| median(w)).collect(),
+            baseline_ms: baseline,
+            recent_ms: recent,
+            typical_ms: median(&durations),
+            slow_ms: if durations.len() >= RECENT_RUNS {
+                percentile(&durations, 0.9)
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Recent median divided by baseline median
+    pub fn slowdown(&self) -> Option<f64> {
+        match (self.baseline_ms, self.recent_ms) {
+            (Some(base), Some(recent)) if base > 0.0 => Some(recent / base),
+            _ => None,
+        }
+    }
+
+    /// Did it get significantly slower?
+    pub fn is_regression(&self) -> bool {
+        self.slowdown().is_some_and(|s| s >= SLOWDOWN_THRESHOLD)
+    }
+
+    /// How long the next run will probably take: "usually ~4m 30s (up to 6m 10s)"
+    pub fn eta(&self) -> Option<String> {
+        let typical = self.typical_ms?;
+        let mut eta = format!("usually ~{}", format_duration(typical as u64));
+
+        if let Some(slow) = self.slow_ms.filter(|slow| *slow > typical * SLOW_TAIL_FACTOR) {
+            eta.push_str(&format!(" (up to {})", format_duration(slow as u64)));
+        }
+
+        Some(eta)
+    }
+
+    /// Notification text for a run that just finished
+    ///
+    /// Only for commands that usually take at least `threshold_ms`, the ones
+    /// you walk away from.
+    ///
+    /// # Returns
+    /// * `Some((summary, body))` - Worth a notification
+    pub fn finished_notice(
+        &self,
+        took_ms: i32,
+        exit_code: Option<i32>,
+        threshold_ms: i64,
+    ) -> Option<(String, String)> {
+        let typical = self.typical_ms?;
+        if threshold_ms <= 0 || typical < threshold_ms as f64 {
+            return None;
+        }
+
+        let summary = match exit_code {
+            Some(code) if code != 0 => format!("✗ {} failed (exit {})", self.command, code),
+            _ => format!("✓ {} finished", self.command),
+        };
+        let body = format!(
+            "Took {} (usually {})",
+            format_duration(took_ms.max(0) as u64),
+            format_duration(typical as u64)
+        );
+
+        Some((summary, body))
+    }
+
+    /// One line for the terminal after a run finishes:
+    /// "✓ 4m 12s — 15% faster than your median for cargo build"
+    ///
+    /// Just the time until there are a few earlier runs to compare with.
+    pub fn completion_line(&self, took_ms: i32, exit_code: Option<i32>) -> String {
+        let took = format_duration(took_ms.max(0) as u64);
+        let mut line = match exit_code {
+            Some(code) if code != 0 => format!("{} {} (exit {})", ui::fail(), took, code),
+            _ => format!("{} {}", ui::ok(), took),
+        };
+
+        let typical = self
+            .typical_ms
+            .filter(|ms| *ms > 0.0 && self.runs >= MIN_SUMMARY_RUNS);
+        if let Some(typical) = typical {
+            let change = took_ms as f64 / typical - 1.0;
+            let comparison = if change.abs() < SAME_SPEED {
+                "about your median".to_string()
+            } else if change < 0.0 {
+                format!("{:.0}% faster than your median", -change * 100.0)
+            } else {
+                format!("{:.0}% slower than your median", change * 100.0)
+            };
+            line.push_str(&format!(
+                " {} {} for {}",
+                ui::glyph(Glyph::Dash),
+                comparison,
+                self.command
+            ));
+        }
+
+        line
+    }
+
+    /// Weekly medians as a sparkline, blank for weeks without runs
+    pub fn sparkline(&self) -> String {
+        let max = self.weekly_ms.iter().flatten().cloned().fold(0.0, f64::max);
+        let min = self
+            .weekly_ms
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(f64::INFINITY, f64::min);
+
+        let chars = spark_chars();
+        self.weekly_ms
+            .iter()
+            .map(|week| match week {
+                None => ' ',
+                Some(_) if max <= min => chars[chars.len() / 2],
+                Some(ms) => {
+                    let level = (ms - min) / (max - min) * (chars.len() - 1) as f64;
+                    chars[level.round() as usize]
+                }
+            })
+            .collect()
+    }
+}
+
+/// Sparkline levels, lowest to highest, for this terminal
+pub fn spark_chars() -> &'static [char; 8] {
+    if ui::theme().unicode {
+        &SPARK_CHARS
+    } else {
+        &ASCII_SPARK_CHARS
+    }
+}
+
+/// Short human duration: 850ms, 4.2s, 6m 05s
+pub fn format_duration(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else if ms < 60_000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        format!("{}m {:02}s", ms / 60_000, (ms % 60_000) / 1000)
+    }
+}
+
+/// Builds duration reports from the execution log
+pub struct PerfAnalyzer {
+    db: Arc<Database>,
+    pattern_detector: PatternDetector,
+}
+
+impl PerfAnalyzer {
+    /// Create a new performance analyzer
+    pub fn new(db: Arc<Database>) -> Self {
+        let pattern_detector = PatternDetector::new(Arc::clone(&db));
+        Self {
+            db,
+            pattern_detector,
+        }
+    }
+
+    /// Duration history for a command
+    ///
+    /// Runs are grouped by normalized command, so `git commit -m 'a'` and
+    /// `git commit -m 'b'` count as the same thing.
+    pub async fn report(&self, command: &str, project_path: Option<&str>) -> Result<PerfReport> {
+        let target = self.pattern_detector.normalize_command(command);
+
+        let runs: Vec<(DateTime<Utc>, i32)> = self
+            .db
+            .get_timed_executions(project_path)
+            .await?
+            .into_iter()
+            .filter(|e| self.pattern_detector.normalize_command(&e.command) == target)
+            .filter_map(|e| Some((parse_timestamp(&e.executed_at)?, e.execution_time_ms?)))
+            .collect();
+
+        Ok(PerfReport::build(&target, &runs, Utc::now()))
+    }
+}
+
+/// Nearest-rank percentile, `p` between 0.0 and 1.0
+fn percentile(values: &[f64], p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+pub(crate) fn median(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        Some((sorted[mid - 1] + sorted[mid]) / 2.0)
+    } else {
+        Some(sorted[mid])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CommandInput;
+    use chrono::Duration;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(&[4.0, 1.0, 2.0, 3.0]), Some(2.5));
+        assert_eq!(median(&[]), None);
+    }
+
+    #[test]
+    fn test_finished_notice() {
+        let now = Utc::now();
+        let runs: Vec<_> = [300_000, 360_000, 420_000]
+            .iter()
+            .map(|ms| (now, *ms))
+            .collect();
+        let report = PerfReport::build("terraform apply", &runs, now);
+        assert_eq!(report.typical_ms, Some(360_000.0));
+
+        let (summary, body) = report.finished_notice(400_000, Some(0), 5 * 60_000).unwrap();
+        assert_eq!(summary, "✓ terraform apply finished");
+        assert_eq!(body, "Took 6m 40s (usually 6m 00s)");
+
+        let (failed, _) = report.finished_notice(1_000, Some(1), 5 * 60_000).unwrap();
+        assert_eq!(failed, "✗ terraform apply failed (exit 1)");
+
+        // Quick commands and a disabled threshold stay quiet
+        assert!(report.finished_notice(400_000, Some(0), 10 * 60_000).is_none());
+        assert!(report.finished_notice(400_000, Some(0), 0).is_none());
+    }
+
+    #[test]
+    fn test_eta() {
+        let now = Utc::now();
+        let runs = |durations: &[i32]| -> Vec<(DateTime<Utc>, i32)> {
+            durations.iter().map(|ms| (now, *ms)).collect()
+        };
+
+        let steady = PerfReport::build("cargo build", &runs(&[270_000; 6]), now);
+        assert_eq!(steady.eta().as_deref(), Some("usually ~4m 30s"));
+
+        let spiky = PerfReport::build(
+            "cargo build",
+            &runs(&[270_000, 260_000, 280_000, 270_000, 275_000, 265_000, 370_000]),
+            now,
+        );
+        assert_eq!(spiky.eta().as_deref(), Some("usually ~4m 30s (up to 6m 10s)"));
+
+        assert!(PerfReport::build("cargo build", &[], now).eta().is_none());
+    }
+
+    #[test]
+    fn test_completion_line() {
+        let now = Utc::now();
+        let runs: Vec<_> = [280_000, 300_000, 320_000]
+            .iter()
+            .map(|ms| (now, *ms))
+            .collect();
+        let report = PerfReport::build("cargo build", &runs, now);
+
+        assert_eq!(
+            report.completion_line(255_000, Some(0)),
+            "✓ 4m 15s — 15% faster than your median for cargo build"
+        );
+        assert_eq!(
+            report.completion_line(360_000, Some(101)),
+            "✗ 6m 00s (exit 101) — 20% slower than your median for cargo build"
+        );
+        assert_eq!(
+            report.completion_line(305_000, None),
+            "✓ 5m 05s — about your median for cargo build"
+        );
+
+        // Nothing to compare with yet
+        let first = PerfReport::build("cargo build", &runs[..1], now);
+        assert_eq!(first.completion_line(4_200, Some(0)), "✓ 4.2s");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(850), "850ms");
+        assert_eq!(format_duration(4_200), "4.2s");
+        assert_eq!(format_duration(365_000), "6m 05s");
+    }
+
+    #[test]
+    fn test_build_flags_regression() {
+        let now = Utc::now();
+        let mut runs = Vec::new();
+        for week in (1..=10).rev() {
+            runs.push((now - Duration::weeks(week), 40_000));
+        }
+        for day in (0..5).rev() {
+            runs.push((now - Duration::days(day), 60_000));
+        }
+
+        let report = PerfReport::build("cargo build", &runs, now);
+        assert_eq!(report.runs, 15);
+        assert_eq!(report.baseline_ms, Some(40_000.0));
+        assert_eq!(report.recent_ms, Some(60_000.0));
+        assert!(report.is_regression());
+
+        let spark = report.sparkline();
+        assert_eq!(spark.chars().count(), WEEKS_SHOWN);
+        assert!(spark.ends_with('█'));
+        assert!(spark.starts_with(' '));
+    }
+
+    #[test]
+    fn test_build_stable() {
+        let now = Utc::now();
+        let runs: Vec<_> = (0..10)
+            .map(|day| (now - Duration::days(day), 1000 + day as i32))
+            .collect();
+
+        let report = PerfReport::build("npm test", &runs, now);
+        assert!(!report.is_regression());
+    }
+
+    #[tokio::test]
+    async fn test_report_groups_normalized_runs() {
+        let db = Arc::new(Database::new_test().await.unwrap());
+        for (cmd, ms) in [
+            ("git commit -m 'one'", Some(100)),
+            ("git commit -m 'two'", Some(300)),
+            ("git commit -m 'three'", None),
+            ("git push", Some(900)),
+        ] {
+            db.record_command(CommandInput {
+                project_path: "/test".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: ms,
+                exit_code: Some(0),
+                context: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let analyzer = PerfAnalyzer::new(db);
+        let report = analyzer
+            .report("git commit -m 'anything'", Some("/test"))
+            .await
+            .unwrap();
+
+        assert_eq!(report.command, "git commit -m <arg>");
+        assert_eq!(report.runs, 2);
+        assert_eq!(report.weekly_ms[WEEKS_SHOWN - 1], Some(200.0));
+    }
+}
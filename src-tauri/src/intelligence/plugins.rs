@@ -0,0 +1,279 @@
+//! External suggestion plugins
+//!
+//! Anything executable in `~/.berri-recall/plugins/` is treated as a
+//! suggestion provider. Each run it gets one JSON request on stdin:
+//!
+//! ```json
+//! {"version": 1, "method": "suggest", "context": {"working_directory": "/work/api",
+//!  "git_branch": "main", "project_type": "Rust", "time_of_day": "morning", "day_of_week": "Monday"}}
+//! ```
+//!
+//! and answers with one JSON object on stdout:
+//!
+//! ```json
+//! {"suggestions": [{"command": "make lint", "reason": "lint before you push", "confidence": 0.7}]}
+//! ```
+//!
+//! Plugins run in the project directory with a cleared environment, get
+//! `PLUGIN_TIMEOUT` to answer and are killed after that. Output past
+//! `MAX_OUTPUT_BYTES` is ignored. WASM modules are not supported yet and
+//! are skipped.
+
+use crate::core::recorder::INTERNAL_ENV;
+use crate::intelligence::{Context, SmartSuggestion, SuggestionProvider};
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Protocol version sent with every request
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How long a plugin gets to answer
+pub const PLUGIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Anything a plugin prints past this is dropped
+const MAX_OUTPUT_BYTES: u64 = 64 * 1024;
+
+/// Plugins can't outrank everything else
+const MAX_PLUGIN_CONFIDENCE: f64 = 0.9;
+
+/// Environment variables a plugin still gets
+const PASSED_ENV: &[&str] = &["PATH", "HOME", "USERPROFILE", "SYSTEMROOT", "LANG"];
+
+/// Where plugins are discovered
+pub fn plugins_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".berri-recall").join("plugins"))
+}
+
+/// Find every plugin in `dir`, sorted by file name
+///
+/// A missing directory just means no plugins.
+pub fn discover(dir: &Path) -> Vec<Box<dyn SuggestionProvider>> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| is_executable(p))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| Box::new(ExternalProvider::new(path)) as Box<dyn SuggestionProvider>)
+        .collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    path.is_file() && matches!(extension.as_deref(), Some("exe" | "bat" | "cmd"))
+}
+
+#[derive(Debug, Deserialize)]
+struct PluginResponse {
+    #[serde(default)]
+    suggestions: Vec<PluginSuggestion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PluginSuggestion {
+    command: String,
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default)]
+    confidence: Option<f64>,
+}
+
+/// A plugin executable speaking JSON over stdio
+pub struct ExternalProvider {
+    path: PathBuf,
+    name: String,
+}
+
+impl ExternalProvider {
+    pub fn new(path: PathBuf) -> Self {
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("plugin")
+            .to_string();
+
+        Self { path, name }
+    }
+
+    /// The request sent to the plugin
+    fn request(context: &Context) -> serde_json::Value {
+        serde_json::json!({
+            "version": PROTOCOL_VERSION,
+            "method": "suggest",
+            "context": {
+                "working_directory": context.working_directory,
+                "git_branch": context.git_branch,
+                "project_type": context.project_type.as_ref().map(|t| format!("{:?}", t)),
+                "time_of_day": context.time_of_day.to_string(),
+                "day_of_week": context.day_of_week.to_string(),
+            }
+        })
+    }
+
+    /// Run the plugin and return what it printed, or None if it failed or timed out
+    fn call(&self, context: &Context) -> Option<String> {
+        let mut command = Command::new(&self.path);
+        command
+            .env_clear()
+            .env(INTERNAL_ENV, "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        for key in PASSED_ENV {
+            if let Some(value) = std::env::var_os(key) {
+                command.env(key, value);
+            }
+        }
+        if Path::new(&context.working_directory).is_dir() {
+            command.current_dir(&context.working_directory);
+        }
+
+        let mut child = command.spawn().ok()?;
+
+        let request = Self::request(context).to_string();
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = writeln!(stdin, "{}", request);
+        }
+
+        // Read on another thread so a plugin that never answers can't hang us
+        let stdout = child.stdout.take()?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut output = String::new();
+            let _ = stdout.take(MAX_OUTPUT_BYTES).read_to_string(&mut output);
+            let _ = tx.send(output);
+        });
+
+        let output = rx.recv_timeout(PLUGIN_TIMEOUT).ok();
+        let _ = child.kill();
+        let _ = child.wait();
+
+        output
+    }
+
+    /// Turn a plugin's answer into suggestions, ignoring anything malformed
+    fn parse(&self, output: &str) -> Vec<SmartSuggestion> {
+        let Ok(response) = serde_json::from_str::<PluginResponse>(output.trim()) else {
+            return Vec::new();
+        };
+
+        response
+            .suggestions
+            .into_iter()
+            .filter(|s| !s.command.trim().is_empty())
+            .map(|s| SmartSuggestion {
+                id: None,
+                command: s.command,
+                reason: s
+                    .reason
+                    .unwrap_or_else(|| format!("Suggested by the {} plugin", self.name)),
+                confidence: s
+                    .confidence
+                    .unwrap_or(0.5)
+                    .clamp(0.0, MAX_PLUGIN_CONFIDENCE),
+            })
+            .collect()
+    }
+}
+
+impl SuggestionProvider for ExternalProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn applies(&self, _context: &Context) -> bool {
+        self.path.is_file()
+    }
+
+    fn suggest(&self, context: &Context) -> Vec<SmartSuggestion> {
+        self.call(context)
+            .map(|output| self.parse(&output))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intelligence::ContextDetector;
+
+    #[test]
+    fn test_parse_response() {
+        let provider = ExternalProvider::new(PathBuf::from("/plugins/lint.sh"));
+
+        let suggestions = provider.parse(
+            r#"{"suggestions": [
+                {"command": "make lint", "confidence": 1.5},
+                {"command": "  "},
+                {"command": "make test", "reason": "tests are stale", "confidence": 0.4}
+            ]}"#,
+        );
+
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].reason, "Suggested by the lint plugin");
+        assert_eq!(suggestions[0].confidence, MAX_PLUGIN_CONFIDENCE);
+        assert_eq!(suggestions[1].reason, "tests are stale");
+        assert!(provider.parse("not json").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discover_and_run() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let write_plugin = |name: &str, body: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, body).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        };
+
+        write_plugin(
+            "echo.sh",
+            "#!/bin/sh\nread request\necho '{\"suggestions\": [{\"command\": \"make lint\"}]}'\n",
+        );
+        write_plugin("slow.sh", "#!/bin/sh\nsleep 5\n");
+        std::fs::write(dir.path().join("notes.txt"), "not a plugin").unwrap();
+        std::fs::write(dir.path().join("model.wasm"), "").unwrap();
+
+        let plugins = discover(dir.path());
+        let names: Vec<&str> = plugins.iter().map(|p| p.name()).collect();
+        assert_eq!(names, vec!["echo", "slow"]);
+
+        let context = ContextDetector::detect().unwrap();
+        let suggestions = plugins[0].suggest(&context);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].command, "make lint");
+
+        // Killed after the timeout instead of holding everything up
+        let started = std::time::Instant::now();
+        assert!(plugins[1].suggest(&context).is_empty());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
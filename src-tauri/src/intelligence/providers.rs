@@ -0,0 +1,335 @@
+//! Per-tool suggestion providers
+//!
+//! Domain-specific suggestions that patterns can't find on their own, like
+//! commits that never got pushed. Each provider checks something cheap
+//! first (a file exists, the tool is installed) before doing real work.
+
+use crate::intelligence::{Context, SmartSuggestion};
+use chrono::{DateTime, Utc};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+/// How long commits can sit unpushed before the git provider speaks up
+pub const UNPUSHED_AFTER_DAYS: i64 = 2;
+
+/// Compose files the docker provider looks for
+const COMPOSE_FILES: &[&str] = &[
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yml",
+    "docker-compose.yaml",
+];
+
+/// A source of suggestions for one tool
+///
+/// Register extra providers with `SuggestionEngine::register_provider`.
+pub trait SuggestionProvider: Send + Sync {
+    /// Short name shown in debug output
+    fn name(&self) -> &str;
+
+    /// Cheap check run before `suggest`, so tools that aren't in use cost nothing
+    fn applies(&self, context: &Context) -> bool;
+
+    /// Suggestions for the current context
+    fn suggest(&self, context: &Context) -> Vec<SmartSuggestion>;
+}
+
+/// The providers every engine starts with
+pub fn builtin_providers() -> Vec<Box<dyn SuggestionProvider>> {
+    #[cfg_attr(not(feature = "git"), allow(unused_mut))]
+    let mut providers: Vec<Box<dyn SuggestionProvider>> =
+        vec![Box::new(DockerProvider), Box::new(CargoProvider)];
+    #[cfg(feature = "git")]
+    providers.insert(0, Box::new(GitProvider));
+    providers
+}
+
+/// Check whether a program is somewhere on PATH
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .map(|paths| {
+            env::split_paths(&paths).any(|dir| {
+                dir.join(program).is_file() || dir.join(format!("{}.exe", program)).is_file()
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// Commits on the current branch that its upstream doesn't have yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unpushed {
+    pub branch: String,
+    pub commits: usize,
+    /// When the oldest of them was made
+    pub since: DateTime<Utc>,
+}
+
+/// "You committed but haven't pushed"
+#[cfg(feature = "git")]
+pub struct GitProvider;
+
+#[cfg(feature = "git")]
+impl GitProvider {
+    /// Find unpushed commits in the repo containing `dir`
+    ///
+    /// Branches without an upstream are skipped, there's nothing to compare to.
+    pub fn unpushed(dir: &Path) -> Option<Unpushed> {
+        let repo = git2::Repository::discover(dir).ok()?;
+        let head = repo.head().ok()?;
+        if !head.is_branch() {
+            return None;
+        }
+
+        let branch_name = head.shorthand()?.to_string();
+        let branch = repo
+            .find_branch(&branch_name, git2::BranchType::Local)
+            .ok()?;
+        let upstream = branch.upstream().ok()?;
+
+        let local_oid = head.target()?;
+        let upstream_oid = upstream.get().target()?;
+
+        let mut walk = repo.revwalk().ok()?;
+        walk.push(local_oid).ok()?;
+        walk.hide(upstream_oid).ok()?;
+
+        let times: Vec<i64> = walk
+            .filter_map(|oid| oid.ok())
+            .filter_map(|oid| repo.find_commit(oid).ok())
+            .map(|commit| commit.time().seconds())
+            .collect();
+        let oldest = times.iter().min()?;
+
+        Some(Unpushed {
+            branch: branch_name,
+            commits: times.len(),
+            since: DateTime::from_timestamp(*oldest, 0)?,
+        })
+    }
+}
+
+#[cfg(feature = "git")]
+impl SuggestionProvider for GitProvider {
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn applies(&self, context: &Context) -> bool {
+        context.git_branch.is_some()
+    }
+
+    fn suggest(&self, context: &Context) -> Vec<SmartSuggestion> {
+        let Some(unpushed) = Self::unpushed(Path::new(&context.working_directory)) else {
+            return Vec::new();
+        };
+
+        let days = (context.now.with_timezone(&Utc) - unpushed.since).num_days();
+        if days < UNPUSHED_AFTER_DAYS {
+            return Vec::new();
+        }
+
+        vec![SmartSuggestion {
+            id: None,
+            command: "git push".to_string(),
+            reason: format!(
+                "You committed to '{}' {} days ago but haven't pushed ({} commit(s))",
+                unpushed.branch, days, unpushed.commits
+            ),
+            confidence: 0.75,
+        }]
+    }
+}
+
+/// "Containers from this project are still running"
+pub struct DockerProvider;
+
+impl DockerProvider {
+    /// Whether `dir` has a compose file
+    fn has_compose_file(dir: &Path) -> bool {
+        COMPOSE_FILES.iter().any(|f| dir.join(f).exists())
+    }
+
+    /// Names of running containers that compose started from `dir`
+    fn running_containers(dir: &str) -> Vec<String> {
+        Command::new("docker")
+            .args([
+                "ps",
+                "--filter",
+                &format!("label=com.docker.compose.project.working_dir={}", dir),
+                "--format",
+                "{{.Names}}",
+            ])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl SuggestionProvider for DockerProvider {
+    fn name(&self) -> &str {
+        "docker"
+    }
+
+    fn applies(&self, context: &Context) -> bool {
+        Self::has_compose_file(Path::new(&context.working_directory)) && on_path("docker")
+    }
+
+    fn suggest(&self, context: &Context) -> Vec<SmartSuggestion> {
+        let running = Self::running_containers(&context.working_directory);
+        if running.is_empty() {
+            return Vec::new();
+        }
+
+        vec![SmartSuggestion {
+            id: None,
+            command: "docker compose down".to_string(),
+            reason: format!(
+                "Containers from this project are still running: {}",
+                running.join(", ")
+            ),
+            confidence: 0.6,
+        }]
+    }
+}
+
+/// "Cargo.lock changed since last build"
+pub struct CargoProvider;
+
+impl CargoProvider {
+    /// Where `dir` builds to: `CARGO_TARGET_DIR`, relative to it, or `target`
+    pub fn target_dir(dir: &Path) -> PathBuf {
+        env::var_os("CARGO_TARGET_DIR")
+            .map(|t| dir.join(t))
+            .unwrap_or_else(|| dir.join("target"))
+    }
+
+    /// Whether Cargo.lock in `dir` is newer than the last build in `target`
+    ///
+    /// A project that was never built doesn't count, that's not a change.
+    pub fn lockfile_changed(dir: &Path, target: &Path) -> bool {
+        let modified = |path: &Path| -> Option<SystemTime> { path.metadata().ok()?.modified().ok() };
+
+        let Some(lock) = modified(&dir.join("Cargo.lock")) else {
+            return false;
+        };
+
+        let last_build = ["debug", "release"]
+            .iter()
+            .filter_map(|profile| modified(&target.join(profile)))
+            .max();
+
+        last_build.is_some_and(|built| lock > built)
+    }
+}
+
+impl SuggestionProvider for CargoProvider {
+    fn name(&self) -> &str {
+        "cargo"
+    }
+
+    fn applies(&self, context: &Context) -> bool {
+        Path::new(&context.working_directory).join("Cargo.lock").exists()
+    }
+
+    fn suggest(&self, context: &Context) -> Vec<SmartSuggestion> {
+        let dir = Path::new(&context.working_directory);
+        if !Self::lockfile_changed(dir, &Self::target_dir(dir)) {
+            return Vec::new();
+        }
+
+        vec![SmartSuggestion {
+            id: None,
+            command: "cargo build".to_string(),
+            reason: "Cargo.lock changed since the last build".to_string(),
+            confidence: 0.7,
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+
+    #[cfg(feature = "git")]
+    fn days_ago(days: i64) -> git2::Time {
+        git2::Time::new((Utc::now() - chrono::Duration::days(days)).timestamp(), 0)
+    }
+
+    #[test]
+    fn test_cargo_lockfile_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.lock"), "").unwrap();
+
+        // Passed in, so a CARGO_TARGET_DIR set for this test run doesn't count
+        let target = dir.path().join("target");
+
+        // Never built: nothing to rebuild
+        assert!(!CargoProvider::lockfile_changed(dir.path(), &target));
+
+        let debug = target.join("debug");
+        fs::create_dir_all(&debug).unwrap();
+        let an_hour_ago = SystemTime::now() - std::time::Duration::from_secs(3600);
+        File::open(&debug).unwrap().set_modified(an_hour_ago).unwrap();
+        assert!(CargoProvider::lockfile_changed(dir.path(), &target));
+
+        let in_a_minute = SystemTime::now() + std::time::Duration::from_secs(60);
+        File::open(&debug).unwrap().set_modified(in_a_minute).unwrap();
+        assert!(!CargoProvider::lockfile_changed(dir.path(), &target));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_unpushed() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let tree_id = repo.index().unwrap().write_tree().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+
+        let commit = |days: i64, parents: &[&git2::Commit]| {
+            let sig = git2::Signature::new("dev", "dev@example.com", &days_ago(days)).unwrap();
+            repo.commit(None, &sig, &sig, "wip", &tree, parents).unwrap()
+        };
+
+        // Pushed 5 days ago, then two local commits
+        let pushed = commit(5, &[]);
+        let first = commit(3, &[&repo.find_commit(pushed).unwrap()]);
+        let second = commit(1, &[&repo.find_commit(first).unwrap()]);
+
+        repo.reference("refs/heads/main", second, true, "test").unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+
+        // No upstream yet: nothing to compare against
+        assert_eq!(GitProvider::unpushed(dir.path()), None);
+
+        repo.remote("origin", "https://example.com/repo.git").unwrap();
+        repo.reference("refs/remotes/origin/main", pushed, true, "test").unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("branch.main.remote", "origin").unwrap();
+        config.set_str("branch.main.merge", "refs/heads/main").unwrap();
+
+        let unpushed = GitProvider::unpushed(dir.path()).expect("two unpushed commits");
+        assert_eq!(unpushed.branch, "main");
+        assert_eq!(unpushed.commits, 2);
+        assert_eq!((Utc::now() - unpushed.since).num_days(), 3);
+    }
+
+    #[test]
+    fn test_docker_probe_needs_compose_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!DockerProvider::has_compose_file(dir.path()));
+
+        fs::write(dir.path().join("compose.yaml"), "services: {}").unwrap();
+        assert!(DockerProvider::has_compose_file(dir.path()));
+    }
+}
@@ -0,0 +1,118 @@
+//! Flaky command detection
+//!
+//! Finds commands that sometimes pass and sometimes fail in the same project,
+//! like a test suite that breaks one run in three or a script that depends on
+//! the network.
+
+use crate::db::{CommandStats, Database};
+use crate::error::Result;
+use std::sync::Arc;
+
+/// Fewer runs than this and a single failure says nothing
+pub const DEFAULT_MIN_RUNS: i64 = 3;
+
+/// A command with mixed exit codes
+#[derive(Debug, Clone)]
+pub struct FlakyCommand {
+    pub stats: CommandStats,
+    /// Share of runs with a known exit code that failed (0.0-1.0)
+    pub failure_rate: f64,
+    /// 1.0 when it fails half the time, towards 0.0 as it settles either way
+    pub flakiness: f64,
+}
+
+/// Finds unstable commands in the execution log
+pub struct ReliabilityAnalyzer {
+    db: Arc<Database>,
+}
+
+impl ReliabilityAnalyzer {
+    /// Create a new reliability analyzer
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// List commands that both passed and failed at least once
+    ///
+    /// Sorted by flakiness, then by how often they run.
+    ///
+    /// # Arguments
+    /// * `project_path` - Optional project path filter (None for all projects)
+    /// * `min_runs` - Ignore commands with fewer runs than this
+    pub async fn flaky_commands(
+        &self,
+        project_path: Option<&str>,
+        min_runs: i64,
+    ) -> Result<Vec<FlakyCommand>> {
+        let stats = self.db.get_command_stats(project_path, None, -1).await?;
+
+        let mut flaky: Vec<FlakyCommand> = stats
+            .into_iter()
+            .filter(|s| s.successes > 0 && s.failures > 0)
+            .filter(|s| s.successes + s.failures >= min_runs)
+            .map(Self::score)
+            .collect();
+
+        flaky.sort_by(|a, b| {
+            b.flakiness
+                .partial_cmp(&a.flakiness)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.stats.runs.cmp(&a.stats.runs))
+        });
+
+        Ok(flaky)
+    }
+
+    fn score(stats: CommandStats) -> FlakyCommand {
+        let failure_rate = 1.0 - stats.success_rate().unwrap_or(1.0);
+        let flakiness = 1.0 - (2.0 * failure_rate - 1.0).abs();
+
+        FlakyCommand {
+            stats,
+            failure_rate,
+            flakiness,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CommandInput;
+
+    async fn record(db: &Database, command: &str, exit_codes: &[i32]) {
+        for code in exit_codes {
+            db.record_command(CommandInput {
+                project_path: "/test".to_string(),
+                command: command.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(*code),
+                context: None,
+            })
+            .await
+            .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flaky_commands() {
+        let db = Arc::new(Database::new_test().await.unwrap());
+
+        record(&db, "npm test", &[0, 1, 0, 1]).await;
+        record(&db, "./fetch.sh", &[0, 0, 0, 0, 1, 0]).await;
+        record(&db, "cargo build", &[0, 0, 0]).await;
+        record(&db, "make lint", &[1, 1, 1]).await;
+        record(&db, "curl api", &[0, 1]).await;
+
+        let analyzer = ReliabilityAnalyzer::new(db);
+        let flaky = analyzer
+            .flaky_commands(Some("/test"), DEFAULT_MIN_RUNS)
+            .await
+            .unwrap();
+
+        let commands: Vec<_> = flaky.iter().map(|f| f.stats.command.as_str()).collect();
+        assert_eq!(commands, vec!["npm test", "./fetch.sh"]);
+        assert_eq!(flaky[0].failure_rate, 0.5);
+        assert_eq!(flaky[0].flakiness, 1.0);
+    }
+}
@@ -1,6 +1,13 @@
-/// Scoring algorithms for patterns and suggestions
-///
-/// Calculates confidence scores based on multiple factors.
+//! Scoring algorithms for patterns and suggestions
+//!
+//! Calculates confidence scores based on multiple factors.
+
+/// Weight of each factor in the suggestion score (they add up to 1.0)
+pub const FREQUENCY_WEIGHT: f64 = 0.25;
+pub const RECENCY_WEIGHT: f64 = 0.20;
+pub const PATTERN_WEIGHT: f64 = 0.25;
+pub const CONTEXT_WEIGHT: f64 = 0.20;
+pub const ACCEPTANCE_WEIGHT: f64 = 0.10;
 
 /// Scorer for calculating confidence scores
 pub struct Scorer;
@@ -25,15 +32,35 @@ impl Scorer {
         acceptance_rate: f64,
     ) -> f64 {
         // Weighted average
-        let score = frequency * 0.25
-            + recency * 0.20
-            + pattern_confidence * 0.25
-            + context_match * 0.20
-            + acceptance_rate * 0.10;
+        let score = frequency * FREQUENCY_WEIGHT
+            + recency * RECENCY_WEIGHT
+            + pattern_confidence * PATTERN_WEIGHT
+            + context_match * CONTEXT_WEIGHT
+            + acceptance_rate * ACCEPTANCE_WEIGHT;
 
         score.clamp(0.0, 1.0)
     }
 
+    /// Combine independent pieces of evidence for the same suggestion
+    ///
+    /// Noisy-OR: each source that agrees pushes the confidence up, without
+    /// ever going past 1.0. Two sources at 0.6 give 0.84.
+    ///
+    /// # Arguments
+    /// * `confidences` - Confidence from each source (0.0-1.0)
+    pub fn combine_evidence(confidences: &[f64]) -> f64 {
+        let doubt: f64 = confidences
+            .iter()
+            .map(|c| 1.0 - c.clamp(0.0, 1.0))
+            .product();
+
+        if confidences.is_empty() {
+            0.0
+        } else {
+            1.0 - doubt
+        }
+    }
+
     /// Calculate frequency weight
     ///
     /// # Arguments
@@ -107,6 +134,14 @@ mod tests {
         assert_eq!(today, 1.0);
     }
 
+    #[test]
+    fn test_combine_evidence() {
+        assert!((Scorer::combine_evidence(&[0.6, 0.6]) - 0.84).abs() < 1e-9);
+        assert_eq!(Scorer::combine_evidence(&[0.7]), 0.7);
+        assert_eq!(Scorer::combine_evidence(&[1.0, 0.2]), 1.0);
+        assert_eq!(Scorer::combine_evidence(&[]), 0.0);
+    }
+
     #[test]
     fn test_context_match() {
         assert_eq!(Scorer::calculate_context_match(3, 5), 0.6);
@@ -0,0 +1,274 @@
+//! Short names for long commands you type all the time
+//!
+//! A command earns a shortening once it's been run `MIN_USES` times and is
+//! at least `MIN_LENGTH` characters. The name is the first letter of each
+//! word (`docker compose up -d` → `dcud`), never one that's already a program
+//! on your PATH, a fish function or builtin, or one of your aliases.
+
+use crate::db::models::{Alias, Command};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+/// Runs before a command is worth shortening
+pub const MIN_USES: i32 = 5;
+
+/// Shorter than this and there's nothing to save
+pub const MIN_LENGTH: usize = 12;
+
+/// Most shortenings to suggest
+pub const MAX_SHORTENINGS: usize = 20;
+
+/// A name that expands to a command
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shortening {
+    pub name: String,
+    pub command: String,
+    /// Runs across every project, 0 for your own aliases
+    pub uses: i64,
+}
+
+/// Shortenings for the most used long commands
+///
+/// `commands` can come from every project, the same command in two projects
+/// counts once with both usage counts. `taken` holds names that can't be used
+/// (existing aliases), `is_program` says whether a name is already on PATH.
+pub fn learn(
+    commands: &[Command],
+    taken: &HashSet<String>,
+    is_program: impl Fn(&str) -> bool,
+) -> Vec<Shortening> {
+    let mut used_names = taken.clone();
+    let mut shortenings = Vec::new();
+
+    for (command, uses) in worth_shortening(commands) {
+        let Some(base) = initials(command) else {
+            continue;
+        };
+
+        let name = names_for(&base).find(|name| !used_names.contains(name) && !is_program(name));
+        let Some(name) = name else {
+            continue;
+        };
+
+        used_names.insert(name.clone());
+        shortenings.push(Shortening {
+            name,
+            command: command.to_string(),
+            uses,
+        });
+        if shortenings.len() == MAX_SHORTENINGS {
+            break;
+        }
+    }
+
+    shortenings
+}
+
+/// Every name `learn` might give out, to check against the shell up front
+pub fn candidate_names(commands: &[Command]) -> Vec<String> {
+    worth_shortening(commands)
+        .into_iter()
+        .filter_map(|(command, _)| initials(command))
+        .flat_map(|base| names_for(&base).collect::<Vec<_>>())
+        .collect()
+}
+
+/// Long, often run commands with their runs across projects, most run first
+fn worth_shortening(commands: &[Command]) -> Vec<(&str, i64)> {
+    let mut uses: HashMap<&str, i64> = HashMap::new();
+    for command in commands {
+        *uses.entry(command.command.trim()).or_default() += command.usage_count as i64;
+    }
+
+    let mut candidates: Vec<(&str, i64)> = uses
+        .into_iter()
+        .filter(|(command, uses)| {
+            *uses >= MIN_USES as i64 && command.len() >= MIN_LENGTH && !command.contains('\n')
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    candidates
+}
+
+/// `dcud`, then `dcud2`, ... `dcud9`
+fn names_for(base: &str) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(base.to_string()).chain((2..10).map(move |n| format!("{}{}", base, n)))
+}
+
+/// First letter of every word, `None` when that's under two letters
+fn initials(command: &str) -> Option<String> {
+    let name: String = command
+        .split_whitespace()
+        .filter_map(|word| {
+            word.trim_start_matches('-')
+                .chars()
+                .next()
+                .filter(|c| c.is_ascii_alphanumeric())
+        })
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    (name.len() >= 2 && name.starts_with(|c: char| c.is_ascii_alphabetic())).then_some(name)
+}
+
+/// Your global aliases as shortenings
+///
+/// Project aliases stay out (an abbreviation works in every directory), and
+/// so do templates: `{1}` only means something to `!!r`.
+pub fn from_aliases(aliases: &[Alias]) -> Vec<Shortening> {
+    let template = Regex::new(r"\{(\d+|@)\}").expect("placeholder pattern is valid");
+
+    aliases
+        .iter()
+        .filter(|alias| alias.project_path.is_none() && !template.is_match(&alias.command))
+        .map(|alias| Shortening {
+            name: alias.alias.clone(),
+            command: alias.command.clone(),
+            uses: 0,
+        })
+        .collect()
+}
+
+/// Whether `name` is a program somewhere on PATH
+pub fn on_path(name: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+
+    std::env::split_paths(&path).any(|dir| {
+        let candidate = dir.join(name);
+        candidate.is_file() || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
+
+/// Which of `names` fish already has as a function, builtin or program
+///
+/// Asks fish itself (`type -q`), so functions and aliases from config.fish
+/// and conf.d count. Empty when fish isn't installed.
+pub fn known_to_fish(names: &[String]) -> HashSet<String> {
+    if names.is_empty() {
+        return HashSet::new();
+    }
+    let output = std::process::Command::new("fish")
+        .arg("-c")
+        .arg("for name in $argv; type -q -- $name; and echo $name; end")
+        .args(names)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output();
+
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(String::from)
+            .collect(),
+        Err(_) => HashSet::new(),
+    }
+}
+
+/// A fish script defining every shortening as an abbreviation
+///
+/// Abbreviations expand as you type, so what ends up in history (and in
+/// berri-recall) is the full command.
+pub fn to_fish(shortenings: &[Shortening]) -> String {
+    let mut script = String::from(
+        "# Generated by `berri-recall optimize --fish-abbr`, changes get overwritten.\n\
+         # Rerun it to refresh, `berri-recall optimize --remove` to take these out.\n\n",
+    );
+
+    for shortening in shortenings {
+        script.push_str(&format!(
+            "abbr --add --global {} {}\n",
+            fish_quote(&shortening.name),
+            fish_quote(&shortening.command)
+        ));
+    }
+
+    script
+}
+
+/// Single quotes, where fish only treats `\\` and `\'` specially
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(project: &str, text: &str, usage_count: i32) -> Command {
+        Command {
+            id: 0,
+            project_path: project.to_string(),
+            command: text.to_string(),
+            timestamp: Default::default(),
+            is_fav: false,
+            usage_count,
+            execution_time_ms: None,
+            exit_code: None,
+            tags: None,
+            context: None,
+        }
+    }
+
+    #[test]
+    fn test_learn() {
+        let commands = vec![
+            command("/api", "docker compose up -d", 4),
+            command("/web", "docker compose up -d", 3),
+            command("/api", "docker compose down", 9),
+            command("/api", "git status", 50),
+            command("/api", "cargo test --workspace", 2),
+        ];
+        let taken = HashSet::from(["dcd".to_string()]);
+
+        let learned = learn(&commands, &taken, |name| name == "dcud");
+        let names: Vec<(&str, &str)> = learned
+            .iter()
+            .map(|s| (s.name.as_str(), s.command.as_str()))
+            .collect();
+        // Too short, too rare, and the names taken by an alias and a program
+        assert_eq!(
+            names,
+            vec![
+                ("dcd2", "docker compose down"),
+                ("dcud2", "docker compose up -d")
+            ]
+        );
+        assert_eq!(learned[1].uses, 7);
+
+        let candidates = candidate_names(&commands);
+        assert_eq!(candidates.len(), 18);
+        assert_eq!(&candidates[..2], ["dcd", "dcd2"]);
+    }
+
+    #[test]
+    fn test_from_aliases() {
+        let alias = |name: &str, command: &str, project: Option<&str>| Alias {
+            alias: name.to_string(),
+            command: command.to_string(),
+            project_path: project.map(String::from),
+            created_at: String::new(),
+        };
+
+        let shortenings = from_aliases(&[
+            alias("k", "kubectl --context prod", None),
+            alias("deploy", "./deploy.sh {1} --verbose", None),
+            alias("t", "make test", Some("/api")),
+        ]);
+        assert_eq!(shortenings.len(), 1);
+        assert_eq!(shortenings[0].name, "k");
+    }
+
+    #[test]
+    fn test_to_fish() {
+        let script = to_fish(&[Shortening {
+            name: "gl".to_string(),
+            command: r#"git log --format='%h %s' | grep \d"#.to_string(),
+            uses: 8,
+        }]);
+        assert!(
+            script.contains(r#"abbr --add --global 'gl' 'git log --format=\'%h %s\' | grep \\d'"#)
+        );
+    }
+}
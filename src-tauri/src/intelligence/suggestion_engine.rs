@@ -1,15 +1,33 @@
-/// Suggestion engine
-///
-/// Generates smart command suggestions based on patterns and context.
+//! Suggestion engine
+//!
+//! Generates smart command suggestions based on patterns and context.
 
-use crate::db::{Database, Suggestion};
+use crate::core::environment;
+use crate::core::ProjectDetector;
+use crate::db::{parse_timestamp, Database, Suggestion};
 use crate::error::Result;
-use crate::intelligence::{Context, ContextDetector, PatternDetector};
+use crate::intelligence::lockfiles::{self, LOCKFILES};
+use crate::intelligence::providers::{self, SuggestionProvider};
+use crate::intelligence::plugins;
+use crate::intelligence::{
+    Clock, Context, ContextDetector, PatternDetector, Scorer, SuggestionRules, SystemClock,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::sync::Arc;
 
+/// Runs with the same env before it counts as the one you use
+const MIN_CONTEXT_RUNS: i64 = 3;
+
+/// Runs on a branch before it's suggested there
+const MIN_BRANCH_RUNS: i64 = 2;
+
 /// Suggestion with reasoning
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SmartSuggestion {
+    /// Row ID once stored (used by `why` and feedback)
+    pub id: Option<i64>,
     pub command: String,
     pub reason: String,
     pub confidence: f64,
@@ -19,6 +37,8 @@ pub struct SmartSuggestion {
 pub struct SuggestionEngine {
     db: Arc<Database>,
     pattern_detector: PatternDetector,
+    providers: Vec<Box<dyn SuggestionProvider>>,
+    clock: Arc<dyn Clock>,
 }
 
 impl SuggestionEngine {
@@ -26,18 +46,37 @@ impl SuggestionEngine {
     pub fn new(db: Arc<Database>) -> Self {
         let pattern_detector = PatternDetector::new(Arc::clone(&db));
 
+        // Built-in providers, then whatever's dropped into ~/.berri-recall/plugins
+        let mut providers = providers::builtin_providers();
+        if let Some(dir) = plugins::plugins_dir() {
+            providers.extend(plugins::discover(&dir));
+        }
+
         Self {
             db,
             pattern_detector,
+            providers,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Take the time from `clock` instead of the system's
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Add a provider on top of the built-in and discovered ones
+    pub fn register_provider(&mut self, provider: Box<dyn SuggestionProvider>) {
+        self.providers.push(provider);
+    }
+
     /// Generate suggestions for current context
     ///
     /// # Returns
     /// * `Ok(Vec<SmartSuggestion>)` - List of suggestions with reasoning
     pub async fn generate_suggestions(&self) -> Result<Vec<SmartSuggestion>> {
-        let context = ContextDetector::detect()?;
+        let context = ContextDetector::detect_with(self.clock.as_ref())?;
         let mut suggestions = Vec::new();
 
         // Get suggestions from patterns
@@ -48,10 +87,30 @@ impl SuggestionEngine {
         let context_suggestions = self.suggest_from_context(&context).await?;
         suggestions.extend(context_suggestions);
 
+        // The virtualenv or node version this project's commands usually run under
+        suggestions.extend(self.suggest_from_toolchain(&context).await?);
+
+        // A lockfile that moved since the last install
+        suggestions.extend(self.suggest_from_lockfiles(&context).await?);
+
+        // What this branch runs that others don't (migrations on a schema branch)
+        suggestions.extend(self.suggest_from_branch(&context).await?);
+
         // Get time-based suggestions
         let time_suggestions = self.suggest_from_time(&context).await?;
         suggestions.extend(time_suggestions);
 
+        // Get tool-specific suggestions (unpushed commits, running containers...)
+        suggestions.extend(self.suggest_from_providers(&context));
+
+        // Same command from several sources becomes one, stronger suggestion
+        let mut suggestions = Self::merge_duplicates(suggestions);
+
+        // Drop anything the user snoozed or said never to suggest
+        let rules = SuggestionRules::load(&self.db).await?;
+        let now = self.clock.now();
+        suggestions.retain(|s| rules.allows(&s.command, now));
+
         // Sort by confidence
         suggestions.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap());
 
@@ -59,8 +118,8 @@ impl SuggestionEngine {
         suggestions.truncate(5);
 
         // Store suggestions in database
-        for suggestion in &suggestions {
-            let _ = self
+        for suggestion in &mut suggestions {
+            if let Ok(id) = self
                 .db
                 .store_suggestion(
                     context.working_directory.clone(),
@@ -69,12 +128,55 @@ impl SuggestionEngine {
                     Some(suggestion.reason.clone()),
                     suggestion.confidence,
                 )
-                .await;
+                .await
+            {
+                suggestion.id = Some(id);
+            }
         }
 
         Ok(suggestions)
     }
 
+    /// Combine suggestions for the same command
+    ///
+    /// Confidences are combined with `Scorer::combine_evidence` and reasons are
+    /// joined strongest first. Keeps the order commands first appeared in.
+    fn merge_duplicates(suggestions: Vec<SmartSuggestion>) -> Vec<SmartSuggestion> {
+        let mut groups: Vec<Vec<SmartSuggestion>> = Vec::new();
+
+        for suggestion in suggestions {
+            match groups
+                .iter_mut()
+                .find(|g| g[0].command == suggestion.command)
+            {
+                Some(group) => group.push(suggestion),
+                None => groups.push(vec![suggestion]),
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|mut group| {
+                group.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap());
+
+                let confidences: Vec<f64> = group.iter().map(|s| s.confidence).collect();
+                let mut reasons: Vec<String> = Vec::new();
+                for s in &group {
+                    if !reasons.contains(&s.reason) {
+                        reasons.push(s.reason.clone());
+                    }
+                }
+
+                SmartSuggestion {
+                    id: None,
+                    command: group[0].command.clone(),
+                    reason: reasons.join("; "),
+                    confidence: Scorer::combine_evidence(&confidences),
+                }
+            })
+            .collect()
+    }
+
     /// Generate suggestions based on detected patterns
     async fn suggest_from_patterns(&self, context: &Context) -> Result<Vec<SmartSuggestion>> {
         let patterns = self
@@ -93,9 +195,13 @@ impl SuggestionEngine {
                     .await?;
 
                 if let Some(last_cmd) = recent.first() {
+                    // Patterns are stored normalized, so compare like with like
+                    let last_normalized = self.pattern_detector.normalize_command(&last_cmd.command);
+
                     // Check if last command matches start of pattern
-                    if let Some(next_cmd) = self.predict_next_in_sequence(&last_cmd.command, &pattern.commands) {
+                    if let Some(next_cmd) = self.predict_next_in_sequence(&last_normalized, &pattern.commands) {
                         suggestions.push(SmartSuggestion {
+                            id: None,
                             command: next_cmd.clone(),
                             reason: format!(
                                 "You usually run '{}' after '{}'",
@@ -130,11 +236,13 @@ impl SuggestionEngine {
             let type_suggestions = match project_type {
                 crate::intelligence::ProjectType::Node => vec![
                     SmartSuggestion {
+                        id: None,
                         command: "npm install".to_string(),
                         reason: "Node project: install dependencies".to_string(),
                         confidence: 0.7,
                     },
                     SmartSuggestion {
+                        id: None,
                         command: "npm test".to_string(),
                         reason: "Node project: run tests".to_string(),
                         confidence: 0.65,
@@ -142,11 +250,13 @@ impl SuggestionEngine {
                 ],
                 crate::intelligence::ProjectType::Rust => vec![
                     SmartSuggestion {
+                        id: None,
                         command: "cargo build".to_string(),
                         reason: "Rust project: build project".to_string(),
                         confidence: 0.7,
                     },
                     SmartSuggestion {
+                        id: None,
                         command: "cargo test".to_string(),
                         reason: "Rust project: run tests".to_string(),
                         confidence: 0.65,
@@ -154,11 +264,13 @@ impl SuggestionEngine {
                 ],
                 crate::intelligence::ProjectType::Python => vec![
                     SmartSuggestion {
+                        id: None,
                         command: "pip install -r requirements.txt".to_string(),
                         reason: "Python project: install dependencies".to_string(),
                         confidence: 0.7,
                     },
                     SmartSuggestion {
+                        id: None,
                         command: "python -m pytest".to_string(),
                         reason: "Python project: run tests".to_string(),
                         confidence: 0.65,
@@ -174,6 +286,7 @@ impl SuggestionEngine {
         if let Some(branch) = &context.git_branch {
             if branch.contains("feature") || branch.contains("feat") {
                 suggestions.push(SmartSuggestion {
+                    id: None,
                     command: "git push".to_string(),
                     reason: format!("On feature branch '{}': push changes", branch),
                     confidence: 0.6,
@@ -184,6 +297,175 @@ impl SuggestionEngine {
         Ok(suggestions)
     }
 
+    /// Suggest activating the env this project's commands usually run in
+    async fn suggest_from_toolchain(&self, context: &Context) -> Result<Vec<SmartSuggestion>> {
+        let cwd = Path::new(&context.working_directory);
+        let Ok(project) = ProjectDetector::detect(cwd) else {
+            return Ok(Vec::new());
+        };
+        let active = environment::toolchain(cwd, &project);
+        self.toolchain_suggestions(&project, &active).await
+    }
+
+    async fn toolchain_suggestions(
+        &self,
+        project: &Path,
+        active: &BTreeMap<String, String>,
+    ) -> Result<Vec<SmartSuggestion>> {
+        let mut suggestions: Vec<SmartSuggestion> = Vec::new();
+
+        for key in ["venv", "conda", "node"] {
+            let usage = self
+                .db
+                .context_usage(&project.to_string_lossy(), key)
+                .await?;
+
+            // Most used command first, so it's the one named in the reason
+            for (command, value, runs, total) in usage {
+                // Nearly always, not just sometimes
+                if runs < MIN_CONTEXT_RUNS || runs * 5 < total * 4 {
+                    continue;
+                }
+                if active.get(key) == Some(&value) {
+                    continue;
+                }
+
+                let activated =
+                    format!("You usually activate {} before running {} here", value, command);
+                let (activate, reason) = match key {
+                    "venv" => {
+                        // Only one we can point at: a virtualenv inside the project
+                        if !project.join(&value).join("bin").join("activate").exists() {
+                            continue;
+                        }
+                        (format!("source {}/bin/activate", value), activated)
+                    }
+                    "conda" => (format!("conda activate {}", value), activated),
+                    _ => (
+                        format!("nvm use {}", value),
+                        format!("You usually run {} with node {} here", command, value),
+                    ),
+                };
+                if suggestions.iter().any(|s| s.command == activate) {
+                    continue;
+                }
+
+                suggestions.push(SmartSuggestion {
+                    id: None,
+                    command: activate,
+                    reason,
+                    confidence: 0.75,
+                });
+            }
+        }
+
+        Ok(suggestions)
+    }
+
+    /// Suggest reinstalling when a lockfile changed after the last install here
+    ///
+    /// Suggests the install command you actually ran last (`npm ci`, not a
+    /// generic `npm install`). Projects with no recorded install are left alone.
+    async fn suggest_from_lockfiles(&self, context: &Context) -> Result<Vec<SmartSuggestion>> {
+        let Ok(project) = ProjectDetector::detect(&context.working_directory) else {
+            return Ok(Vec::new());
+        };
+        self.lockfile_suggestions(&project).await
+    }
+
+    async fn lockfile_suggestions(&self, project: &Path) -> Result<Vec<SmartSuggestion>> {
+        let mut suggestions = Vec::new();
+
+        for lockfile in LOCKFILES {
+            let path = project.join(lockfile.file);
+            if !path.exists() {
+                continue;
+            }
+            let Some((command, executed_at)) = self
+                .db
+                .last_successful_run(&project.to_string_lossy(), lockfile.installs)
+                .await?
+            else {
+                continue;
+            };
+            let Some(installed) = parse_timestamp(&executed_at) else {
+                continue;
+            };
+
+            if lockfiles::modified_after(&path, installed) {
+                suggestions.push(SmartSuggestion {
+                    id: None,
+                    command,
+                    reason: format!(
+                        "Dependencies changed: {} was updated since you last ran it",
+                        lockfile.file
+                    ),
+                    confidence: 0.9,
+                });
+            }
+        }
+
+        Ok(suggestions)
+    }
+
+    /// Suggest commands that mostly run on the checked-out branch
+    ///
+    /// A command counts when at least half its runs were on this branch and
+    /// it leans on the branch more than the project's runs as a whole do. The
+    /// branch most runs happen on (`main`, usually) gets nothing, everything
+    /// would come back as "usually on main".
+    async fn suggest_from_branch(&self, context: &Context) -> Result<Vec<SmartSuggestion>> {
+        let Some(branch) = context.git_branch.as_deref().filter(|b| *b != "HEAD") else {
+            return Ok(Vec::new());
+        };
+        let Ok(project) = ProjectDetector::detect(&context.working_directory) else {
+            return Ok(Vec::new());
+        };
+        self.branch_suggestions(&project, branch).await
+    }
+
+    async fn branch_suggestions(
+        &self,
+        project: &Path,
+        branch: &str,
+    ) -> Result<Vec<SmartSuggestion>> {
+        let usage = self
+            .db
+            .branch_usage(&project.to_string_lossy(), branch)
+            .await?;
+
+        let on_branch: i64 = usage.iter().map(|(_, on, _)| on).sum();
+        let total: i64 = usage.iter().map(|(_, _, runs)| runs).sum();
+        let baseline = on_branch as f64 / total.max(1) as f64;
+        if on_branch == 0 || baseline >= 0.5 {
+            return Ok(Vec::new());
+        }
+
+        Ok(usage
+            .into_iter()
+            .filter(|(_, on, _)| *on >= MIN_BRANCH_RUNS)
+            .filter_map(|(command, on, runs)| {
+                let share = on as f64 / runs as f64;
+                (share >= 0.5 && share >= baseline * 1.5).then(|| SmartSuggestion {
+                    id: None,
+                    command,
+                    reason: format!("You've run it {} times on {}", on, branch),
+                    confidence: 0.5 + 0.4 * share,
+                })
+            })
+            .take(3)
+            .collect())
+    }
+
+    /// Ask every provider whose probe passes
+    fn suggest_from_providers(&self, context: &Context) -> Vec<SmartSuggestion> {
+        self.providers
+            .iter()
+            .filter(|p| p.applies(context))
+            .flat_map(|p| p.suggest(context))
+            .collect()
+    }
+
     /// Generate time-based suggestions
     async fn suggest_from_time(&self, context: &Context) -> Result<Vec<SmartSuggestion>> {
         let mut suggestions = Vec::new();
@@ -197,6 +479,7 @@ impl SuggestionEngine {
             crate::intelligence::TimeOfDay::Morning
         ) {
             suggestions.push(SmartSuggestion {
+                id: None,
                 command: "git pull".to_string(),
                 reason: "Monday morning: sync with latest changes".to_string(),
                 confidence: 0.65,
@@ -212,6 +495,7 @@ impl SuggestionEngine {
             crate::intelligence::TimeOfDay::Afternoon
         ) {
             suggestions.push(SmartSuggestion {
+                id: None,
                 command: "git status".to_string(),
                 reason: "Friday afternoon: check for uncommitted changes".to_string(),
                 confidence: 0.6,
@@ -235,7 +519,8 @@ impl SuggestionEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::db::CommandInput;
+    use crate::db::{CommandInput, ExecutionMeta};
+    use crate::intelligence::FixedClock;
 
     async fn setup() -> SuggestionEngine {
         let db = Arc::new(Database::new_test().await.unwrap());
@@ -268,6 +553,104 @@ mod tests {
         assert!(!suggestions.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_suppressed_suggestions_are_skipped() {
+        let engine = setup().await;
+
+        let first = engine.generate_suggestions().await.unwrap();
+        for suggestion in &first {
+            SuggestionRules::suppress(&engine.db, &suggestion.command)
+                .await
+                .unwrap();
+        }
+
+        let second = engine.generate_suggestions().await.unwrap();
+        assert!(second
+            .iter()
+            .all(|s| first.iter().all(|f| f.command != s.command)));
+    }
+
+    #[test]
+    fn test_registered_provider() {
+        struct Always;
+        impl SuggestionProvider for Always {
+            fn name(&self) -> &str {
+                "always"
+            }
+            fn applies(&self, context: &Context) -> bool {
+                context.working_directory == "/test"
+            }
+            fn suggest(&self, _context: &Context) -> Vec<SmartSuggestion> {
+                vec![SmartSuggestion {
+                    id: None,
+                    command: "make lint".to_string(),
+                    reason: "always".to_string(),
+                    confidence: 0.9,
+                }]
+            }
+        }
+
+        let db = Arc::new(tokio_test::block_on(Database::new_test()).unwrap());
+        let mut engine = SuggestionEngine::new(db);
+        engine.providers.clear();
+        engine.register_provider(Box::new(Always));
+
+        let mut context = ContextDetector::detect().unwrap();
+        assert!(engine.suggest_from_providers(&context).is_empty());
+
+        context.working_directory = "/test".to_string();
+        let suggestions = engine.suggest_from_providers(&context);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].command, "make lint");
+    }
+
+    async fn suggested_at(at: &str) -> Vec<String> {
+        let engine = setup()
+            .await
+            .with_clock(Arc::new(FixedClock::parse(at).unwrap()));
+        let context = ContextDetector::detect_with(engine.clock.as_ref()).unwrap();
+        engine
+            .suggest_from_time(&context)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.command)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_time_suggestions() {
+        assert_eq!(suggested_at("2026-03-02T09:30:00+01:00").await, vec!["git pull"]);
+        assert_eq!(suggested_at("2026-03-06T15:00:00-08:00").await, vec!["git status"]);
+        // Monday morning in UTC, but still Sunday night where you are
+        assert!(suggested_at("2026-03-01T23:00:00-10:00").await.is_empty());
+    }
+
+    #[test]
+    fn test_merge_duplicates() {
+        let suggestion = |command: &str, reason: &str, confidence: f64| SmartSuggestion {
+            id: None,
+            command: command.to_string(),
+            reason: reason.to_string(),
+            confidence,
+        };
+
+        let merged = SuggestionEngine::merge_duplicates(vec![
+            suggestion("git status", "Friday afternoon", 0.6),
+            suggestion("cargo test", "Rust project", 0.7),
+            suggestion("git status", "You usually run it after 'git add .'", 0.8),
+        ]);
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].command, "git status");
+        assert_eq!(
+            merged[0].reason,
+            "You usually run it after 'git add .'; Friday afternoon"
+        );
+        assert!((merged[0].confidence - 0.92).abs() < 1e-9);
+        assert_eq!(merged[1].confidence, 0.7);
+    }
+
     #[tokio::test]
     async fn test_predict_next_in_sequence() {
         let engine = setup().await;
@@ -280,4 +663,144 @@ mod tests {
         let next2 = engine.predict_next_in_sequence("git commit", &sequence);
         assert_eq!(next2, Some("git push".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_toolchain_suggestions() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".venv/bin")).unwrap();
+        std::fs::write(dir.path().join(".venv/bin/activate"), "").unwrap();
+        let project = dir.path().to_string_lossy().into_owned();
+
+        let db = Arc::new(Database::new_test().await.unwrap());
+        let runs = [
+            ("pytest", Some(r#"{"venv":".venv"}"#)),
+            ("pytest", Some(r#"{"venv":".venv"}"#)),
+            ("pytest", Some(r#"{"venv":".venv"}"#)),
+            ("ls", Some(r#"{"venv":".venv"}"#)),
+            ("make docs", Some(r#"{"conda":"docs"}"#)),
+        ];
+        for (cmd, env) in runs {
+            let meta = ExecutionMeta {
+                env: env.map(String::from),
+                ..Default::default()
+            };
+            let input = CommandInput {
+                project_path: project.clone(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            };
+            db.record_command_with(input, &meta).await.unwrap();
+        }
+        let engine = SuggestionEngine::new(db);
+
+        let suggestions = engine
+            .toolchain_suggestions(dir.path(), &BTreeMap::new())
+            .await
+            .unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].command, "source .venv/bin/activate");
+        assert_eq!(
+            suggestions[0].reason,
+            "You usually activate .venv before running pytest here"
+        );
+
+        // Already active
+        let active = BTreeMap::from([("venv".to_string(), ".venv".to_string())]);
+        let suggestions = engine.toolchain_suggestions(dir.path(), &active).await.unwrap();
+        assert!(suggestions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lockfile_suggestions() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("package-lock.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("poetry.lock"), "").unwrap();
+        let project = dir.path().to_string_lossy().into_owned();
+
+        let db = Arc::new(Database::new_test().await.unwrap());
+        let runs = [
+            ("npm ci", "2024-01-01 09:00:00", Some(0)),
+            ("npm install left-pad", "2024-01-02 09:00:00", Some(1)),
+            ("npm run build", "2024-01-03 09:00:00", Some(0)),
+        ];
+        for (cmd, at, exit_code) in runs {
+            let meta = ExecutionMeta {
+                executed_at: Some(at.to_string()),
+                ..Default::default()
+            };
+            let input = CommandInput {
+                project_path: project.clone(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code,
+                context: None,
+            };
+            db.record_command_with(input, &meta).await.unwrap();
+        }
+        let engine = SuggestionEngine::new(Arc::clone(&db));
+
+        // The failed install doesn't count, and poetry was never installed here
+        let suggestions = engine.lockfile_suggestions(dir.path()).await.unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].command, "npm ci");
+        assert!(suggestions[0].reason.contains("package-lock.json"));
+
+        // Installed since
+        let input = CommandInput {
+            project_path: project.clone(),
+            command: "npm ci".to_string(),
+            execution_time_ms: None,
+            exit_code: Some(0),
+            context: None,
+        };
+        let meta = ExecutionMeta {
+            executed_at: Some("2999-01-01 00:00:00".to_string()),
+            ..Default::default()
+        };
+        db.record_command_with(input, &meta).await.unwrap();
+        assert!(engine.lockfile_suggestions(dir.path()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_branch_suggestions() {
+        let db = Arc::new(Database::new_test().await.unwrap());
+        let runs = [
+            ("cargo test", "main"),
+            ("cargo test", "main"),
+            ("cargo test", "main"),
+            ("cargo test", "feat/schema"),
+            ("git pull", "main"),
+            ("git pull", "main"),
+            ("sqlx migrate run", "feat/schema"),
+            ("sqlx migrate run", "feat/schema"),
+            ("sqlx migrate run", "main"),
+        ];
+        for (cmd, branch) in runs {
+            let meta = ExecutionMeta {
+                branch: Some(branch.to_string()),
+                ..Default::default()
+            };
+            let input = CommandInput {
+                project_path: "/work/api".to_string(),
+                command: cmd.to_string(),
+                execution_time_ms: None,
+                exit_code: Some(0),
+                context: None,
+            };
+            db.record_command_with(input, &meta).await.unwrap();
+        }
+        let engine = SuggestionEngine::new(db);
+        let project = Path::new("/work/api");
+
+        let suggestions = engine.branch_suggestions(project, "feat/schema").await.unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].command, "sqlx migrate run");
+        assert_eq!(suggestions[0].reason, "You've run it 2 times on feat/schema");
+
+        // Most of everything happens on main, nothing stands out there
+        assert!(engine.branch_suggestions(project, "main").await.unwrap().is_empty());
+        assert!(engine.branch_suggestions(project, "other").await.unwrap().is_empty());
+    }
 }
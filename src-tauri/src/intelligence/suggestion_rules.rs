@@ -0,0 +1,141 @@
+//! Snoozed and suppressed suggestions
+//!
+//! A suggestion that keeps coming back after you've ignored it trains you to
+//! ignore all of them. Rules live in the preferences table, keyed by command
+//! text so they outlive the suggestion row that prompted them:
+//! - `suggest.never:<command>` - never suggest this again
+//! - `suggest.snooze:<command>` - RFC 3339 time until which it stays quiet
+
+use crate::db::{parse_timestamp, Database};
+use crate::error::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet};
+
+const NEVER_PREFIX: &str = "suggest.never:";
+const SNOOZE_PREFIX: &str = "suggest.snooze:";
+
+/// Parse a snooze length like `30m`, `12h`, `7d` or `2w`
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let unit = value.chars().last()?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    if amount <= 0 {
+        return None;
+    }
+
+    match unit {
+        'm' => Some(Duration::minutes(amount)),
+        'h' => Some(Duration::hours(amount)),
+        'd' => Some(Duration::days(amount)),
+        'w' => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
+/// Loaded suppression and snooze rules
+#[derive(Debug, Clone, Default)]
+pub struct SuggestionRules {
+    pub suppressed: HashSet<String>,
+    pub snoozed: HashMap<String, DateTime<Utc>>,
+}
+
+impl SuggestionRules {
+    /// Load every rule from the preferences table
+    pub async fn load(db: &Database) -> Result<Self> {
+        let mut rules = Self::default();
+
+        for pref in db.get_preferences_with_prefix(NEVER_PREFIX).await? {
+            rules
+                .suppressed
+                .insert(pref.key[NEVER_PREFIX.len()..].to_string());
+        }
+
+        for pref in db.get_preferences_with_prefix(SNOOZE_PREFIX).await? {
+            if let Some(until) = parse_timestamp(&pref.value) {
+                rules
+                    .snoozed
+                    .insert(pref.key[SNOOZE_PREFIX.len()..].to_string(), until);
+            }
+        }
+
+        Ok(rules)
+    }
+
+    /// Should this command be suggested right now?
+    pub fn allows(&self, command: &str, now: DateTime<Utc>) -> bool {
+        if self.suppressed.contains(command) {
+            return false;
+        }
+
+        self.snoozed.get(command).is_none_or(|until| *until <= now)
+    }
+
+    /// Keep a command out of suggestions until `until`
+    pub async fn snooze(db: &Database, command: &str, until: DateTime<Utc>) -> Result<()> {
+        db.set_preference(format!("{}{}", SNOOZE_PREFIX, command), until.to_rfc3339())
+            .await
+    }
+
+    /// Never suggest a command again
+    pub async fn suppress(db: &Database, command: &str) -> Result<()> {
+        db.set_preference(format!("{}{}", NEVER_PREFIX, command), "true".to_string())
+            .await
+    }
+
+    /// Drop any snooze or suppression for a command
+    ///
+    /// # Returns
+    /// * `Ok(true)` - A rule was removed
+    /// * `Ok(false)` - There was nothing to remove
+    pub async fn clear(db: &Database, command: &str) -> Result<bool> {
+        let never = db
+            .delete_preference(&format!("{}{}", NEVER_PREFIX, command))
+            .await?;
+        let snooze = db
+            .delete_preference(&format!("{}{}", SNOOZE_PREFIX, command))
+            .await?;
+
+        Ok(never || snooze)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("7d"), Some(Duration::days(7)));
+        assert_eq!(parse_duration("12h"), Some(Duration::hours(12)));
+        assert_eq!(parse_duration("2w"), Some(Duration::weeks(2)));
+        assert_eq!(parse_duration("0d"), None);
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_rules_roundtrip() {
+        let db = Database::new_test().await.unwrap();
+        let now = Utc::now();
+
+        SuggestionRules::suppress(&db, "npm install").await.unwrap();
+        SuggestionRules::snooze(&db, "git pull", now + Duration::days(7))
+            .await
+            .unwrap();
+        SuggestionRules::snooze(&db, "git status", now - Duration::hours(1))
+            .await
+            .unwrap();
+
+        let rules = SuggestionRules::load(&db).await.unwrap();
+        assert!(!rules.allows("npm install", now));
+        assert!(!rules.allows("git pull", now));
+        assert!(rules.allows("git pull", now + Duration::days(8)));
+        assert!(rules.allows("git status", now)); // snooze ran out
+        assert!(rules.allows("cargo test", now));
+
+        assert!(SuggestionRules::clear(&db, "npm install").await.unwrap());
+        assert!(!SuggestionRules::clear(&db, "npm install").await.unwrap());
+        let rules = SuggestionRules::load(&db).await.unwrap();
+        assert!(rules.allows("npm install", now));
+    }
+}
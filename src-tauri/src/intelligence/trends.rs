@@ -0,0 +1,202 @@
+//! Tool usage over time
+//!
+//! `stats --tool-trends` counts runs per tool (the first word: `git`,
+//! `docker`, `kubectl`) week by week, so shifts in how you work show up:
+//! `docker-compose` fading out as `kubectl` takes over, say.
+
+use crate::intelligence::performance::spark_chars;
+use crate::intelligence::PatternDetector;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+/// How far back `--tool-trends` looks unless told otherwise
+pub const DEFAULT_MONTHS: u32 = 3;
+
+/// How many tools it shows unless told otherwise
+pub const DEFAULT_TOOLS: usize = 8;
+
+/// A tool needs this many runs before it's called rising or fading
+const MIN_TREND_RUNS: usize = 6;
+
+/// One half of the window with this many times the runs of the other
+const SHIFT_FACTOR: usize = 2;
+
+/// One tool's weekly runs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolTrend {
+    pub tool: String,
+    pub total: usize,
+    /// Runs per week, oldest first
+    pub weekly: Vec<usize>,
+}
+
+/// Which way a tool is going, comparing the later half of the window with
+/// the earlier half
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Rising,
+    Fading,
+    Steady,
+}
+
+impl Direction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::Rising => "rising",
+            Direction::Fading => "fading",
+            Direction::Steady => "steady",
+        }
+    }
+}
+
+impl ToolTrend {
+    pub fn direction(&self) -> Direction {
+        let half = self.weekly.len() / 2;
+        let earlier: usize = self.weekly[..half].iter().sum();
+        let later: usize = self.weekly[half..].iter().sum();
+        if self.total < MIN_TREND_RUNS {
+            Direction::Steady
+        } else if later >= earlier * SHIFT_FACTOR {
+            Direction::Rising
+        } else if earlier >= later * SHIFT_FACTOR {
+            Direction::Fading
+        } else {
+            Direction::Steady
+        }
+    }
+
+    /// Runs per week as a sparkline, scaled from zero to the busiest week;
+    /// blank for weeks without runs
+    pub fn sparkline(&self) -> String {
+        let max = self.weekly.iter().copied().max().unwrap_or(0);
+        let chars = spark_chars();
+        self.weekly
+            .iter()
+            .map(|&runs| match runs {
+                0 => ' ',
+                _ => {
+                    let level = runs as f64 / max as f64 * (chars.len() - 1) as f64;
+                    chars[level.round() as usize]
+                }
+            })
+            .collect()
+    }
+}
+
+/// Every tool's trend over the window, for the JSON output
+#[derive(Debug, Clone, Serialize)]
+pub struct TrendReport {
+    /// Start of the oldest week
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub weeks: usize,
+    pub tools: Vec<ToolTrendEntry>,
+}
+
+/// A tool's trend with its direction spelled out
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolTrendEntry {
+    #[serde(flatten)]
+    pub trend: ToolTrend,
+    pub direction: Direction,
+}
+
+/// Weeks covering `months` months, near enough
+pub fn weeks_in(months: u32) -> usize {
+    ((months as usize * 30).div_ceil(7)).max(1)
+}
+
+/// Count `runs` (when, command) into weekly buckets per tool, busiest tools
+/// first
+///
+/// Runs more than `weeks` weeks before `now` are left out.
+pub fn build(
+    runs: &[(DateTime<Utc>, String)],
+    weeks: usize,
+    now: DateTime<Utc>,
+    limit: usize,
+) -> Vec<ToolTrend> {
+    let mut trends: Vec<ToolTrend> = Vec::new();
+    for (at, command) in runs {
+        let weeks_ago = (now - *at).num_days().max(0) as usize / 7;
+        if weeks_ago >= weeks {
+            continue;
+        }
+        let tool = PatternDetector::extract_category(command);
+        let index = match trends.iter().position(|t| t.tool == tool) {
+            Some(index) => index,
+            None => {
+                trends.push(ToolTrend {
+                    tool,
+                    total: 0,
+                    weekly: vec![0; weeks],
+                });
+                trends.len() - 1
+            }
+        };
+        trends[index].total += 1;
+        trends[index].weekly[weeks - 1 - weeks_ago] += 1;
+    }
+    // Stable, so ties keep the order they were first run in
+    trends.sort_by_key(|t| std::cmp::Reverse(t.total));
+    trends.truncate(limit);
+    trends
+}
+
+/// The report `--json` prints
+pub fn report(trends: Vec<ToolTrend>, weeks: usize, now: DateTime<Utc>) -> TrendReport {
+    TrendReport {
+        from: now - Duration::weeks(weeks as i64),
+        to: now,
+        weeks,
+        tools: trends
+            .into_iter()
+            .map(|trend| ToolTrendEntry {
+                direction: trend.direction(),
+                trend,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let now = Utc::now();
+        let ago = |weeks: i64| now - Duration::weeks(weeks) - Duration::hours(1);
+        let mut runs = Vec::new();
+        // docker-compose early on, kubectl lately, git all along
+        for week in [7, 7, 6, 6, 5, 5, 4] {
+            runs.push((ago(week), "docker-compose up -d".to_string()));
+        }
+        for week in [3, 2, 2, 1, 1, 0, 0, 0] {
+            runs.push((ago(week), "kubectl get pods".to_string()));
+        }
+        for week in 0..8 {
+            runs.push((ago(week), "git status".to_string()));
+        }
+        runs.push((ago(20), "ls".to_string()));
+
+        let trends = build(&runs, 8, now, 10);
+        assert_eq!(
+            trends.iter().map(|t| t.tool.as_str()).collect::<Vec<_>>(),
+            vec!["kubectl", "git", "docker-compose"]
+        );
+        assert_eq!(trends[0].weekly, vec![0, 0, 0, 0, 1, 2, 2, 3]);
+        assert_eq!(trends[0].direction(), Direction::Rising);
+        assert_eq!(trends[1].direction(), Direction::Steady);
+        assert_eq!(trends[2].direction(), Direction::Fading);
+        assert_eq!(trends[0].sparkline(), "    ▃▆▆█");
+
+        assert_eq!(build(&runs, 8, now, 1).len(), 1);
+        assert_eq!(weeks_in(3), 13);
+
+        let json = serde_json::to_value(report(trends, 8, now)).unwrap();
+        assert_eq!(json["tools"][0]["tool"], "kubectl");
+        assert_eq!(json["tools"][2]["direction"], "fading");
+    }
+}
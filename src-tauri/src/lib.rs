@@ -1,13 +1,19 @@
-/// recall-cli library
-///
-/// Core functionality for intelligent command memory system.
+//! recall-cli library
+//!
+//! Core functionality for intelligent command memory system.
 
+pub mod build_info;
 pub mod core;
 pub mod db;
+#[cfg(feature = "intelligence")]
+pub mod editor;
 pub mod error;
 pub mod intelligence;
+pub mod recall;
 pub mod shell;
+pub mod ui;
 
 // Re-exports for convenience
 pub use db::Database;
 pub use error::{RecallError, Result};
+pub use recall::Recall;
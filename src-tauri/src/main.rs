@@ -3,19 +3,111 @@
 // This is the main entry point. Parses CLI args and dispatches to handlers.
 
 use berri_recall_lib::{
-    core::{ProjectDetector, Recorder},
-    intelligence::Analyzer,
-    shell::{HookInstaller, ShellDetector},
-    Database, Result,
+    build_info,
+    core::{
+        cheatsheet,
+        clipboard,
+        demo,
+        environment::{self, EnvFilter},
+        expander::{EXPAND_PREFIX, RECALL_TOKEN},
+        ignore_rules::IgnoreRules,
+        listing::{self, Column, GroupBy, Matches, SortKey, Times},
+        markers::Markers,
+        notifier,
+        orphans,
+        pipeline,
+        project_detector::paths_equal,
+        prompt::{self, PromptCache, SegmentFormat},
+        recorder::Dropped,
+        runner,
+        safety,
+        scriptify::{Script, ScriptFormat},
+        signals::{Signal, Signals},
+        spool::{self, Spool, SpooledCommand},
+        sweep,
+        tasks,
+        timeline::{self, TimelineEntry, TimelineFormat},
+        triggers::{Event, Trigger, Triggers},
+        Expander, ProjectDetector, Recorder, Redactor,
+    },
+    intelligence::{
+        performance::format_duration, trends, AnomalyDetector, PatternDetector, PerfAnalyzer,
+    },
+    shell::{
+        history,
+        hook_args::RecordArgs,
+        mirror::{self, Tail},
+        HookInstaller, InstallOutcome, Shell, ShellDetector,
+    },
+    ui::{self, Glyph, Theme},
+    db::{
+        backup, cold,
+        lock::{self, Lock},
+        parse_timestamp, Archive, CommandInput, Config, ExecutionMeta, PatternType,
+    },
+    recall, Database, Recall, RecallError, Result,
 };
+#[cfg(feature = "intelligence")]
+use berri_recall_lib::{
+    editor::{launcher, EditorServer, LauncherFormat},
+    intelligence::{
+        self, reliability, scorer, shortenings, suggestion_rules, Analyzer, Explainer, GraphFormat,
+        ReliabilityAnalyzer, SuggestionRules,
+    },
+    shell::hook_args::decode_base64,
+};
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-#[tokio::main]
-async fn main() -> Result<()> {
+// Running a suggested command within this long counts as taking the suggestion
+const SUGGESTION_ACCEPT_MINUTES: i64 = 60;
+
+// Runs quicker than this never get a "finished" notification, you didn't walk away
+const MIN_NOTIFY_RUN_MS: i32 = 10_000;
+
+// How often `mirror --watch` looks at the history file when nothing wakes it
+const MIRROR_POLL: std::time::Duration = std::time::Duration::from_secs(2);
+
+// How much history `demo` makes up without --commands
+const DEMO_COMMANDS: usize = 2_000;
+
+// How far down `recent` the rows given to `batch` and `scriptify --rows` can go
+const MAX_ROWS: i64 = 1_000;
+
+// Set by --read-only: open the database without migrating or writing to it
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+fn main() -> Result<()> {
     // Grab whatever the user typed
     let args: Vec<String> = env::args().collect();
 
+    // The hooks record after every command. Fast, that's one small file and
+    // no runtime to start or database to open.
+    if args.get(1).is_some_and(|arg| arg == "record") {
+        if let Some(record) =
+            RecordArgs::parse(&args[2..]).filter(|record| record.fast || spool::fast_record())
+        {
+            return handle_record_fast(record);
+        }
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(start(args))
+}
+
+async fn start(mut args: Vec<String>) -> Result<()> {
+
+    // Global flags go before the command, anything after belongs to it
+    while args.get(1).is_some_and(|arg| arg == "--read-only") {
+        READ_ONLY.store(true, Ordering::Relaxed);
+        args.remove(1);
+    }
+
     if args.len() < 2 {
         print_usage();
         return Ok(());
@@ -23,23 +115,100 @@ async fn main() -> Result<()> {
 
     let command = &args[1];
 
-    match command.as_str() {
+    match run(command, &args).await {
+        Err(e) if backup::is_corruption(&e) && can_offer_restore(command) => {
+            if restore_latest_backup(&e)? {
+                run(command, &args).await
+            } else {
+                Ok(())
+            }
+        }
+        result => result,
+    }
+}
+
+async fn run(command: &str, args: &[String]) -> Result<()> {
+    match command {
         "record" => handle_record(&args[2..]).await,
+        "check" => handle_check(&args[2..]).await,
+        "expand" => handle_expand(&args[2..]).await,
+        "alias" => handle_alias(&args[2..]).await,
         "recent" => handle_recent(&args[2..]).await,
+        "top" => handle_top(&args[2..]).await,
+        "tldr" => handle_tldr(&args[2..]).await,
+        "flags" => handle_flags(&args[2..]).await,
+        "stats" => handle_stats(&args[2..]).await,
+        "hello" => handle_hello(&args[2..]).await,
+        "last" => handle_last(&args[2..]).await,
+        #[cfg(feature = "intelligence")]
+        "flaky" => handle_flaky(&args[2..]).await,
+        #[cfg(feature = "intelligence")]
+        "perf" => handle_perf(&args[2..]).await,
+        #[cfg(feature = "intelligence")]
+        "eta" => handle_eta(&args[2..]).await,
+        "prompt-segment" => handle_prompt_segment(&args[2..]),
+        #[cfg(feature = "intelligence")]
+        "finished" => handle_finished(&args[2..]).await,
+        #[cfg(feature = "intelligence")]
+        "warm" => handle_warm(&args[2..]).await,
         "search" => handle_search(&args[2..]).await,
         "setup" => handle_setup(&args[2..]).await,
         "uninstall" => handle_uninstall(&args[2..]).await,
-        "status" => handle_status().await,
+        "status" => handle_status(&args[2..]).await,
+        #[cfg(feature = "intelligence")]
         "analyze" => handle_analyze(&args[2..]).await,
-        "suggest" => handle_suggest().await,
+        #[cfg(feature = "intelligence")]
+        "suggest" => handle_suggest(&args[2..]).await,
+        #[cfg(feature = "intelligence")]
+        "patterns" => handle_patterns(&args[2..]).await,
+        #[cfg(feature = "intelligence")]
+        "why" => handle_why(&args[2..]).await,
+        #[cfg(feature = "intelligence")]
+        "lsp-like" => handle_lsp_like().await,
+        #[cfg(feature = "intelligence")]
+        "launcher" => handle_launcher(&args[2..]).await,
+        "trigger" => handle_trigger(&args[2..]).await,
+        "sessions" => handle_sessions(&args[2..]).await,
+        "session" => handle_session(&args[2..]).await,
+        "replay" => handle_replay(&args[2..]).await,
+        "timeline" => handle_timeline(&args[2..]).await,
+        "oops" => handle_oops(&args[2..]).await,
+        "redact" => handle_redact(&args[2..]).await,
+        "sweep" => handle_sweep(&args[2..]).await,
+        "orphans" => handle_orphans(&args[2..]).await,
+        "merge" => handle_merge(&args[2..]).await,
+        "export" => handle_export(&args[2..]).await,
+        "import" => handle_import(&args[2..]).await,
+        "archive" => handle_archive(&args[2..]).await,
+        "mirror" => handle_mirror(&args[2..]).await,
+        "demo" => handle_demo(&args[2..]).await,
+        "ignore" => handle_ignore(&args[2..]).await,
+        "config" => handle_config(&args[2..]).await,
+        #[cfg(feature = "intelligence")]
+        "optimize" => handle_optimize(&args[2..]).await,
+        "batch" => handle_batch(&args[2..]).await,
+        "scriptify" => handle_scriptify(&args[2..]).await,
         "version" | "-v" | "--version" => {
-            println!("berri-recall v{}", env!("CARGO_PKG_VERSION"));
+            if args[2..].iter().any(|arg| arg == "--build-info") {
+                print!("{}", build_info::render());
+            } else {
+                println!("berri-recall v{}", env!("CARGO_PKG_VERSION"));
+            }
             Ok(())
         }
         "help" | "-h" | "--help" => {
             print_usage();
             Ok(())
         }
+        #[cfg(not(feature = "intelligence"))]
+        "flaky" | "perf" | "eta" | "analyze" | "suggest" | "patterns" | "why" | "lsp-like"
+        | "launcher" | "optimize" | "warm" | "finished" => {
+            eprintln!(
+                "'{}' isn't in this build (it was built without the intelligence feature)",
+                command
+            );
+            Ok(())
+        }
         _ => {
             eprintln!("Unknown command: {}", command);
             print_usage();
@@ -49,9 +218,201 @@ async fn main() -> Result<()> {
 }
 
 async fn handle_record(args: &[String]) -> Result<()> {
-    // Parse flags and extract the actual command
+    let Some(RecordArgs {
+        command: command_to_record,
+        exit_code,
+        duration_ms,
+        cwd: cwd_override,
+        session_id,
+        interactive,
+        shell_rules,
+        ..
+    }) = RecordArgs::parse(args)
+    else {
+        // Sometimes shell hooks call us with nothing. Just ignore it.
+        return Ok(());
+    };
+
+    // Figure out where the user ran this from
+    let cwd = if let Some(cwd_path) = cwd_override {
+        std::path::PathBuf::from(cwd_path)
+    } else {
+        env::current_dir()?
+    };
+
+    let project_root = ProjectDetector::detect_cached(&cwd)?;
+
+    let project = project_root.to_str().unwrap();
+    let mut meta = ExecutionMeta {
+        session_id,
+        hostname: gethostname::gethostname().into_string().ok(),
+        cwd: cwd.to_str().map(String::from),
+        executed_at: None,
+        env: None,
+        branch: ProjectDetector::git_branch(&cwd),
+        interactive,
+    };
+
+    let db = match get_database().await {
+        Ok(db) => Arc::new(db),
+        Err(e) => {
+            // Busy or being restored: keep the command for the next record
+            if !READ_ONLY.load(Ordering::Relaxed) {
+                spool_for_later(&command_to_record, project, duration_ms, exit_code, &meta);
+            }
+            return Err(e);
+        }
+    };
+    // Read once: the recorder reuses it for every setting it checks
+    let config = Config::load(&db).await.ok();
+    if let Some(config) = &config {
+        meta.env = environment::capture(&config.capture_env(), &cwd, &project_root);
+        sync_markers(config);
+    }
+    let mut ignore_rules = IgnoreRules::load(&db).await.unwrap_or_default();
+    ignore_rules.extend(shell_rules);
+    let mut recorder = Recorder::new(Arc::clone(&db))
+        .with_meta(meta.clone())
+        .with_ignore_rules(ignore_rules);
+    if let Some(config) = config {
+        recorder = recorder.with_config(config);
+    }
+
+    // Skip stuff we don't care about (passwords, env vars, etc)
+    if recorder.should_ignore(&command_to_record) {
+        let _ = recorder.count_dropped(&command_to_record).await;
+        return Ok(());
+    }
+
+    match recorder
+        .record(&command_to_record, project, duration_ms, exit_code, None)
+        .await
+    {
+        Ok(_) => {
+            // worked fine, don't say anything. same goes for the follow-up stuff
+            let _ = after_record(&db, &command_to_record, project, exit_code, duration_ms).await;
+            // Recording runs in the background, a good time for the daily backup
+            let _ = db.backup_if_due().await;
+        }
+        Err(RecallError::Database(_)) if !READ_ONLY.load(Ordering::Relaxed) => {
+            spool_for_later(&command_to_record, project, duration_ms, exit_code, &meta);
+        }
+        Err(_) => {
+            // failed but don't spam the terminal. nobody likes that.
+        }
+    }
+
+    Ok(())
+}
+
+// `record --fast`: straight into the spool, the next command that opens the
+// database records it. The shell's own ignore rules are checked here, yours
+// and everything else `record` does when the spool is drained.
+fn handle_record_fast(record: RecordArgs) -> Result<()> {
+    let cwd = match record.cwd {
+        Some(cwd) => std::path::PathBuf::from(cwd),
+        None => env::current_dir()?,
+    };
+    if record.shell_rules.matching(&record.command).is_some() {
+        return Ok(());
+    }
+    let project_root = ProjectDetector::detect_cached(&cwd)?;
+    let Some(project) = project_root.to_str() else {
+        return Ok(());
+    };
+
+    let meta = ExecutionMeta {
+        session_id: record.session_id,
+        hostname: gethostname::gethostname().into_string().ok(),
+        cwd: cwd.to_str().map(String::from),
+        executed_at: None,
+        env: None,
+        branch: ProjectDetector::git_branch(&cwd),
+        interactive: record.interactive,
+    };
+    spool_for_later(
+        &record.command,
+        project,
+        record.duration_ms,
+        record.exit_code,
+        &meta,
+    );
+    Ok(())
+}
+
+// The database couldn't take the write. Park the command in the spool,
+// unless it's something record would refuse anyway.
+fn spool_for_later(
+    command: &str,
+    project: &str,
+    duration_ms: Option<i32>,
+    exit_code: Option<i32>,
+    meta: &ExecutionMeta,
+) {
+    // A leading space means "don't keep this", not even for a while
+    if command.starts_with(' ') || Recorder::refuses(command) {
+        return;
+    }
+    if let Some(dir) = Spool::default_dir() {
+        let entry = SpooledCommand::new(command, project, duration_ms, exit_code, meta);
+        let _ = Spool::new(dir).push(&entry);
+    }
+}
+
+// Suggestion feedback and user triggers for a command that just got recorded
+async fn after_record(
+    db: &Arc<Database>,
+    command: &str,
+    project: &str,
+    exit_code: Option<i32>,
+    duration_ms: Option<i32>,
+) -> Result<()> {
+    let triggers = Triggers::load(db).await?;
+    let accepted = db
+        .accept_recent_suggestion(project, command, SUGGESTION_ACCEPT_MINUTES)
+        .await?;
+
+    let mut fields = vec![
+        ("command", command.to_string()),
+        ("project", project.to_string()),
+        ("exit_code", exit_code.map(|c| c.to_string()).unwrap_or_default()),
+        ("duration_ms", duration_ms.map(|d| d.to_string()).unwrap_or_default()),
+    ];
+    triggers.fire(Event::CommandRecorded, Some(command), &fields);
+
+    if triggers.listens_for(Event::DangerousCommand) {
+        let detector = AnomalyDetector::new(Arc::clone(db));
+        if let Some(reason) = detector.destructive_reason(command) {
+            fields.push(("reason", reason.to_string()));
+            triggers.fire(Event::DangerousCommand, Some(command), &fields);
+        }
+    }
+
+    if let Some(suggestion) = accepted {
+        drop_from_prompt(project, command);
+        triggers.fire(
+            Event::SuggestionAccepted,
+            Some(command),
+            &[
+                ("command", command.to_string()),
+                ("project", project.to_string()),
+                ("suggestion_id", suggestion.id.to_string()),
+                ("reason", suggestion.reason.unwrap_or_default()),
+            ],
+        );
+    }
+
+    // No notification service (ssh, headless) is fine
+    if let Some(took_ms) = duration_ms.filter(|ms| *ms >= MIN_NOTIFY_RUN_MS) {
+        let _ = notify_if_long(db, command, project, took_ms, exit_code).await;
+    }
+
+    Ok(())
+}
+
+// Called by hooks before a command runs. Exit code 2 means "ask first".
+async fn handle_check(args: &[String]) -> Result<()> {
     let mut command_parts = Vec::new();
-    let mut exit_code: Option<i32> = None;
     let mut cwd_override: Option<String> = None;
 
     let mut i = 0;
@@ -63,12 +424,6 @@ async fn handle_record(args: &[String]) -> Result<()> {
                     command_parts.push(args[i].clone());
                 }
             }
-            "--exit-code" => {
-                i += 1;
-                if i < args.len() {
-                    exit_code = args[i].parse().ok();
-                }
-            }
             "--cwd" => {
                 i += 1;
                 if i < args.len() {
@@ -80,339 +435,5029 @@ async fn handle_record(args: &[String]) -> Result<()> {
         i += 1;
     }
 
-    if command_parts.is_empty() {
-        // Sometimes shell hooks call us with nothing. Just ignore it.
+    let command = command_parts.join(" ");
+    if command.trim().is_empty() {
         return Ok(());
     }
 
-    let command_to_record = command_parts.join(" ");
-
-    // Figure out where the user ran this from
-    let cwd = if let Some(cwd_path) = cwd_override {
-        std::path::PathBuf::from(cwd_path)
-    } else {
-        env::current_dir()?
+    let cwd = match cwd_override {
+        Some(path) => std::path::PathBuf::from(path),
+        None => env::current_dir()?,
     };
-
     let project_root = ProjectDetector::detect(&cwd)?;
 
-    let db = get_database().await?;
-    let recorder = Recorder::new(Arc::new(db));
+    let hostname = gethostname::gethostname().into_string().ok();
+    let detector = AnomalyDetector::new(Arc::new(get_database().await?));
+    if let Some(anomaly) = detector
+        .check(
+            command.trim(),
+            project_root.to_str().unwrap_or("/"),
+            hostname.as_deref(),
+        )
+        .await?
+    {
+        eprintln!(
+            "{} '{}' {} and you've never run it in {}{}{}",
+            ui::warn(),
+            anomaly.command,
+            anomaly.reason,
+            anomaly.project_path,
+            anomaly
+                .hostname
+                .map(|host| format!(" on {}", host))
+                .unwrap_or_default(),
+            if anomaly.seen_elsewhere {
+                " (only in other projects or on other machines)"
+            } else {
+                ""
+            }
+        );
+        std::process::exit(2);
+    }
 
-    // Skip stuff we don't care about (passwords, env vars, etc)
-    if recorder.should_ignore(&command_to_record) {
-        return Ok(());
+    Ok(())
+}
+
+// Called by hooks on Enter for `!!r <alias>` and `!r:3`/`!r:fail` tokens.
+// Prints the expanded line, exit 1 if there's nothing to do.
+async fn handle_expand(args: &[String]) -> Result<()> {
+    let mut line: Option<String> = None;
+    let mut cwd_override: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--command" => {
+                i += 1;
+                line = args.get(i).cloned();
+            }
+            "--cwd" => {
+                i += 1;
+                cwd_override = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
     }
 
-    match recorder
-        .record(
-            &command_to_record,
-            project_root.to_str().unwrap(),
-            None,
-            exit_code,
-            None,
-        )
+    // Bail before opening the database for anything that isn't ours
+    let line = match line {
+        Some(l) if Expander::is_expandable(&l) => l,
+        _ => std::process::exit(1),
+    };
+
+    let cwd = match cwd_override {
+        Some(path) => std::path::PathBuf::from(path),
+        None => env::current_dir()?,
+    };
+    let project_root = ProjectDetector::detect(&cwd).ok();
+
+    let expander = Expander::new(Arc::new(get_database().await?));
+    match expander
+        .expand(&line, project_root.as_ref().and_then(|p| p.to_str()))
         .await
     {
-        Ok(_) => {} // worked fine, don't say anything
-        Err(_) => {
-            // failed but don't spam the terminal. nobody likes that.
+        Ok(Some(expanded)) => {
+            println!("{}", expanded);
+            Ok(())
+        }
+        Ok(None) => std::process::exit(1),
+        Err(RecallError::CommandNotFound(name)) => {
+            if name.starts_with(RECALL_TOKEN) {
+                eprintln!("berri-recall: nothing in this project's history for {}", name);
+            } else {
+                eprintln!("berri-recall: no alias named '{}'", name);
+            }
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("berri-recall: {}", e.user_message());
+            std::process::exit(1);
         }
     }
-
-    Ok(())
 }
 
-async fn handle_recent(args: &[String]) -> Result<()> {
-    let limit = args
-        .get(0)
-        .and_then(|s| s.parse::<i64>().ok())
-        .unwrap_or(10);
-
+async fn handle_alias(args: &[String]) -> Result<()> {
     let db = get_database().await?;
     let cwd = env::current_dir()?;
     let project_root = ProjectDetector::detect(&cwd).ok();
+    let project_path = project_root.as_ref().and_then(|p| p.to_str());
 
-    let commands = db
-        .get_recent_commands(project_root.as_ref().and_then(|p| p.to_str()), limit)
-        .await?;
+    match args.first().map(|s| s.as_str()) {
+        Some("add") => {
+            let global = args.iter().any(|a| a == "--global");
+            let rest: Vec<&String> = args[1..].iter().filter(|a| *a != "--global").collect();
 
-    if commands.is_empty() {
-        println!("No commands found.");
-    } else {
-        println!("\nRecent commands:");
-        println!("{}", "=".repeat(60));
-        for (i, cmd) in commands.iter().enumerate() {
-            let status = if let Some(code) = cmd.exit_code {
-                if code == 0 {
-                    "✓"
-                } else {
-                    "✗"
-                }
+            if rest.len() < 2 {
+                eprintln!("Usage: berri-recall alias add <name> <command...> [--global]");
+                return Ok(());
+            }
+
+            let name = rest[0].clone();
+            let command = rest[1..]
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let scope = if global {
+                None
             } else {
-                " "
+                project_path.map(|p| p.to_string())
             };
+
+            db.create_alias(name.clone(), command.clone(), scope).await?;
             println!(
-                "{:3}. {} {} (used {} times)",
-                i + 1,
-                status,
-                cmd.command,
-                cmd.usage_count
+                "{} {} {} {} {}",
+                ui::ok(),
+                EXPAND_PREFIX,
+                name,
+                ui::glyph(Glyph::Arrow),
+                command
             );
         }
-        println!("{}", "=".repeat(60));
+        Some("rm") | Some("remove") => match args.get(1) {
+            Some(name) => {
+                if db.delete_alias(name).await? {
+                    println!("{} Removed alias '{}'", ui::ok(), name);
+                } else {
+                    eprintln!("No alias named '{}'", name);
+                }
+            }
+            None => eprintln!("Usage: berri-recall alias rm <name>"),
+        },
+        Some("list") | None => {
+            let aliases = db.get_aliases(project_path).await?;
+            if aliases.is_empty() {
+                println!("No aliases yet. Add one with: berri-recall alias add <name> <command>");
+            } else {
+                for alias in aliases {
+                    println!(
+                        "  {:<16} {}{}",
+                        alias.alias,
+                        alias.command,
+                        if alias.project_path.is_none() {
+                            "  (global)"
+                        } else {
+                            ""
+                        }
+                    );
+                }
+            }
+        }
+        Some(other) => {
+            eprintln!("Unknown alias action: {}", other);
+            eprintln!("Usage: berri-recall alias [list|add|rm]");
+        }
     }
 
     Ok(())
 }
 
-async fn handle_search(args: &[String]) -> Result<()> {
-    if args.is_empty() {
-        eprintln!("Error: No search query provided");
+async fn handle_recent(args: &[String]) -> Result<()> {
+    let (args, copy) = take_copy_flag(args);
+    let Some((args, scope)) = take_scope_flags(&args) else {
+        return Ok(());
+    };
+    let Some((args, layout)) = take_layout_flags(&args) else {
+        return Ok(());
+    };
+    let Some((args, branch)) = take_branch_flag(&args) else {
         return Ok(());
+    };
+    let (args, interactive) = take_origin_flag(&args);
+
+    let mut rest = Vec::new();
+    let mut group_by = None;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--group-by" {
+            let Some(value) = args.get(i + 1) else {
+                eprintln!("--group-by needs tool, project or session");
+                return Ok(());
+            };
+            match value.parse::<GroupBy>() {
+                Ok(g) => group_by = Some(g),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(());
+                }
+            }
+            i += 1;
+        } else {
+            rest.push(args[i].clone());
+        }
+        i += 1;
     }
+    let limit = rest
+        .first()
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or(10);
 
-    let query = args.join(" ");
     let db = get_database().await?;
-    let cwd = env::current_dir()?;
-    let project_root = ProjectDetector::detect(&cwd).ok();
+    let Some(project) = scope_filter(&db, &scope).await? else {
+        return Ok(());
+    };
 
-    let results = db
-        .search_commands(&query, project_root.as_ref().and_then(|p| p.to_str()), 20)
-        .await?;
+    let mut commands = if branch.is_none() && interactive.is_none() {
+        db.get_recent_commands(project.as_deref(), limit).await?
+    } else {
+        let mut commands = db.get_recent_commands(project.as_deref(), 1000).await?;
+        if let Some(branch) = &branch {
+            let ids = db.command_ids_on_branch(branch).await?;
+            commands.retain(|c| ids.contains(&c.id));
+        }
+        if let Some(interactive) = interactive {
+            let ids = db.command_ids_by_interactive(interactive).await?;
+            commands.retain(|c| ids.contains(&c.id));
+        }
+        commands.truncate(limit.max(0) as usize);
+        commands
+    };
 
-    if results.is_empty() {
-        println!("No commands found matching '{}'", query);
+    if commands.is_empty() {
+        if !layout.porcelain {
+            println!("No commands found.");
+        }
     } else {
-        println!("\nFound {} command(s) matching '{}':", results.len(), query);
-        println!("{}", "=".repeat(60));
-        for (i, cmd) in results.iter().enumerate() {
-            println!(
-                "{:3}. {} (used {} times)",
-                i + 1,
-                cmd.command,
-                cmd.usage_count
-            );
+        match &branch {
+            _ if layout.porcelain => {}
+            Some(branch) => println!("\nRecent commands on {}:", branch),
+            None => println!("\nRecent commands:"),
         }
-        println!("{}", "=".repeat(60));
+        let defaults = [
+            Column::Exit,
+            Column::Cmd,
+            Column::Count,
+            Column::Duration,
+            Column::Last,
+        ];
+        let sessions = match group_by {
+            Some(GroupBy::Session) => {
+                let ids: Vec<i64> = commands.iter().map(|c| c.id).collect();
+                db.last_sessions(&ids).await?
+            }
+            _ => HashMap::new(),
+        };
+        let key = |c: &berri_recall_lib::db::Command| match group_by {
+            Some(GroupBy::Tool) => PatternDetector::extract_category(&c.command),
+            Some(GroupBy::Project) => c.project_path.clone(),
+            _ => sessions
+                .get(&c.id)
+                .cloned()
+                .unwrap_or_else(|| "no session".to_string()),
+        };
+        print_table(
+            &mut commands,
+            &layout,
+            &defaults,
+            project.is_none(),
+            Matches::default(),
+            group_by.is_some().then_some(&key as &dyn Fn(&_) -> String),
+        );
+    }
+
+    if let Some(n) = copy {
+        copy_result(commands.get(n - 1).map(|c| c.command.as_str()), n);
     }
 
     Ok(())
 }
 
-async fn handle_setup(args: &[String]) -> Result<()> {
-    let installer = HookInstaller::new()?;
-
-    // Check for --all flag
-    let install_all = args.iter().any(|arg| arg == "--all");
-
-    if install_all {
-        println!("Installing hooks for all detected shells...\n");
-        match installer.install_all() {
-            Ok(shells) => {
-                println!("✓ Successfully installed hooks for:");
-                for shell in shells {
-                    println!("  - {}", shell);
-                }
-                println!("\n🎉 Setup complete! Restart your shell or run:");
-                println!("   source ~/.bashrc   (for bash)");
-                println!("   source ~/.zshrc    (for zsh)");
-            }
-            Err(e) => {
-                eprintln!("✗ Setup failed: {}", e);
-                return Err(e);
-            }
-        }
-    } else {
-        // Auto-detect and install for current shell
-        println!("Detecting your shell...\n");
-        match installer.install_auto() {
-            Ok(shell) => {
-                println!("✓ Detected shell: {}", shell);
-                println!("✓ Hook installed successfully!\n");
-                println!("🎉 Setup complete! Restart your shell or run:");
-                use berri_recall_lib::shell::Shell;
-                match shell {
-                    Shell::Bash => println!("   source ~/.bashrc"),
-                    Shell::Zsh => println!("   source ~/.zshrc"),
-                    Shell::Fish => {
-                        println!("   source ~/.config/fish/config.fish")
-                    }
-                    Shell::PowerShell => {
-                        println!("   . $PROFILE")
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!("✗ Setup failed: {}", e);
-                eprintln!("\nTry running with --all flag to install for all shells:");
-                eprintln!("   berri-recall setup --all");
-                return Err(e);
+// `1-3,5 7` → rows 1, 2, 3, 5 and 7, each once, in order. None past the
+// `shown` rows there are to pick from, so `1-99999999` isn't a billion of them.
+fn parse_rows(spec: &[String], shown: usize) -> Option<Vec<usize>> {
+    let mut rows = Vec::new();
+    for part in spec.iter().flat_map(|s| s.split(',')).filter(|p| !p.is_empty()) {
+        let (from, to) = match part.split_once('-') {
+            Some((from, to)) => (from.parse::<usize>().ok()?, to.parse::<usize>().ok()?),
+            None => {
+                let row = part.parse::<usize>().ok()?;
+                (row, row)
             }
+        };
+        if from == 0 || to < from || to > shown {
+            return None;
         }
+        rows.extend(from..=to);
     }
-
-    Ok(())
+    rows.sort_unstable();
+    rows.dedup();
+    (!rows.is_empty()).then_some(rows)
 }
 
-async fn handle_uninstall(_args: &[String]) -> Result<()> {
-    let installer = HookInstaller::new()?;
+// What rows `batch` and `scriptify --rows` can be given here
+fn rows_available(listed: usize) -> String {
+    match listed {
+        0 => "Nothing in `berri-recall recent` here".to_string(),
+        1 => "Only row 1 of `berri-recall recent` here".to_string(),
+        n => format!("Rows 1-{} of `berri-recall recent` here", n),
+    }
+}
 
-    println!("Uninstalling berri-recall hooks...\n");
+// Tag, favorite, delete or export several rows of `recent` in one go
+async fn handle_batch(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall batch <rows> tag <name>\n       \
+                 berri-recall batch <rows> fav|unfav|delete|export\n\n\
+                 Rows are numbers from `berri-recall recent`: 1-3,5 7\n\
+                 (--global or --project <name|path> to match `recent`'s)";
 
-    use berri_recall_lib::shell::Shell;
+    let Some((args, scope)) = take_scope_flags(args) else {
+        return Ok(());
+    };
+    let actions = ["tag", "fav", "unfav", "delete", "export"];
+    let Some(action_at) = args.iter().position(|a| actions.contains(&a.as_str())) else {
+        eprintln!("{}", usage);
+        return Ok(());
+    };
+    if action_at == 0 {
+        eprintln!("{}", usage);
+        return Ok(());
+    }
+
+    let db = get_database().await?;
+    let Some(project) = scope_filter(&db, &scope).await? else {
+        return Ok(());
+    };
+    let listed = db.get_recent_commands(project.as_deref(), MAX_ROWS).await?;
+    let Some(rows) = parse_rows(&args[..action_at], listed.len()) else {
+        eprintln!("{}", usage);
+        eprintln!("\n{}", rows_available(listed.len()));
+        return Ok(());
+    };
+    let picked: Vec<_> = rows.iter().map(|row| &listed[row - 1]).collect();
+    let ids: Vec<i64> = picked.iter().map(|c| c.id).collect();
+
+    match (args[action_at].as_str(), args.get(action_at + 1)) {
+        ("tag", Some(tag)) => {
+            let tagged = db.tag_many(&ids, tag).await?;
+            println!("{} Tagged {} commands '{}'", ui::ok(), tagged, tag);
+        }
+        ("fav", None) | ("unfav", None) => {
+            let favorite = args[action_at] == "fav";
+            let changed = db.set_favorite_many(&ids, favorite).await?;
+            println!(
+                "{} {} {} commands",
+                ui::ok(),
+                if favorite { "Favorited" } else { "Unfavorited" },
+                changed
+            );
+        }
+        ("delete", None) => {
+            for command in &picked {
+                println!("  {}", command.command);
+            }
+            eprint!("Delete these {} commands and all their runs? [y/N] ", picked.len());
+            let mut answer = String::new();
+            std::io::stdin().read_line(&mut answer)?;
+            if !answer.trim().eq_ignore_ascii_case("y") {
+                eprintln!("Nothing deleted");
+                return Ok(());
+            }
+            let deleted = db.delete_many(&ids).await?;
+            println!("{} Deleted {} commands", ui::ok(), deleted);
+        }
+        ("export", None) => {
+            println!("#!/usr/bin/env bash");
+            for command in picked {
+                println!("{}", command.command);
+            }
+        }
+        _ => eprintln!("{}", usage),
+    }
+
+    Ok(())
+}
+
+// A pattern, or rows of `recent`, as a script with the changing bits as parameters
+async fn handle_scriptify(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall scriptify <pattern-id> [options]\n       \
+                 berri-recall scriptify --pick [options]\n       \
+                 berri-recall scriptify --rows <rows> [options]\n\n\
+                 Options: --format sh|just|make  --name <name>  --output <file>\n\
+                 Pattern IDs are in `berri-recall patterns list`, rows in `berri-recall recent`";
+
+    let mut pattern_id = None;
+    let mut pick = false;
+    let mut rows: Option<String> = None;
+    let mut format = ScriptFormat::Shell;
+    let mut name = "workflow".to_string();
+    let mut output: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--pick" => pick = true,
+            "--rows" => {
+                i += 1;
+                rows = args.get(i).cloned();
+                if rows.is_none() {
+                    eprintln!("Error: --rows needs rows like 1-3,5");
+                    return Ok(());
+                }
+            }
+            "--format" => {
+                i += 1;
+                match args.get(i).map(|f| f.parse::<ScriptFormat>()) {
+                    Some(Ok(f)) => format = f,
+                    Some(Err(e)) => {
+                        eprintln!("Error: {}", e);
+                        return Ok(());
+                    }
+                    None => {
+                        eprintln!("Error: --format needs a value (sh, just or make)");
+                        return Ok(());
+                    }
+                }
+            }
+            "--name" => {
+                i += 1;
+                match args.get(i) {
+                    Some(n) if !n.is_empty() => name = n.clone(),
+                    _ => {
+                        eprintln!("Error: --name needs a value");
+                        return Ok(());
+                    }
+                }
+            }
+            "--output" | "-o" => {
+                i += 1;
+                match args.get(i) {
+                    Some(path) => output = Some(path.clone()),
+                    None => {
+                        eprintln!("Error: --output needs a file");
+                        return Ok(());
+                    }
+                }
+            }
+            id => match id.trim_start_matches('#').parse::<i64>() {
+                Ok(id) if pattern_id.is_none() => pattern_id = Some(id),
+                _ => {
+                    eprintln!("{}", usage);
+                    return Ok(());
+                }
+            },
+        }
+        i += 1;
+    }
+
+    let sources = pattern_id.is_some() as u8 + pick as u8 + rows.is_some() as u8;
+    if sources != 1 {
+        eprintln!("{}", usage);
+        return Ok(());
+    }
+
+    let db = get_database().await?;
+    let project_root = ProjectDetector::detect(env::current_dir()?).ok();
+    let project = project_root.as_ref().and_then(|p| p.to_str());
+    let today = chrono::Local::now().format("%Y-%m-%d");
+
+    let (commands, provenance) = if let Some(id) = pattern_id {
+        let Some(pattern) = db.get_pattern_by_id(id).await? else {
+            eprintln!("No pattern #{} (see `berri-recall patterns list`)", id);
+            return Ok(());
+        };
+        if pattern.pattern_type != PatternType::Sequential {
+            eprintln!("Pattern #{} is not a sequence, there's nothing to script", id);
+            return Ok(());
+        }
+        let provenance = format!(
+            "Generated by `berri-recall scriptify` from pattern #{} (seen {} times in {}), {}",
+            id,
+            pattern.occurrences,
+            pattern.project_path.as_deref().unwrap_or("every project"),
+            today
+        );
+        (pattern.get_commands(), provenance)
+    } else {
+        let (rows, listed) = match rows {
+            Some(spec) => {
+                let listed = db.get_recent_commands(project, MAX_ROWS).await?;
+                match parse_rows(std::slice::from_ref(&spec), listed.len()) {
+                    Some(rows) => (rows, listed),
+                    None => {
+                        eprintln!("Error: --rows needs rows like 1-3,5");
+                        eprintln!("{}", rows_available(listed.len()));
+                        return Ok(());
+                    }
+                }
+            }
+            None => {
+                let listed = db.get_recent_commands(project, 20).await?;
+                if listed.is_empty() {
+                    println!("No commands found.");
+                    return Ok(());
+                }
+                for (i, command) in listed.iter().enumerate() {
+                    eprintln!("{:3}. {}", i + 1, command.command);
+                }
+                eprint!("Rows to include (e.g. 1-3,5): ");
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                let spec: Vec<String> = answer.split_whitespace().map(String::from).collect();
+                match parse_rows(&spec, listed.len()) {
+                    Some(rows) => (rows, listed),
+                    None => {
+                        eprintln!("Nothing picked");
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        // `recent` is newest first, the script runs oldest first
+        let mut picked: Vec<String> = rows
+            .iter()
+            .map(|row| listed[row - 1].command.clone())
+            .collect();
+        picked.reverse();
+
+        let provenance = format!(
+            "Generated by `berri-recall scriptify` from {} commands run in {}, {}",
+            picked.len(),
+            project.unwrap_or("this directory"),
+            today
+        );
+        (picked, provenance)
+    };
+
+    if commands.is_empty() {
+        eprintln!("Nothing to script");
+        return Ok(());
+    }
+
+    let script = Script::new(&name, &provenance, &commands).render(format);
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, &script)?;
+            #[cfg(unix)]
+            if format == ScriptFormat::Shell {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+            }
+            println!("{} Wrote {}", ui::ok(), path);
+        }
+        None => print!("{}", script),
+    }
+
+    Ok(())
+}
+
+// Which projects a listing covers
+enum Scope {
+    Here,
+    Global,
+    Project(String),
+}
+
+// `--env NAME=value` (any number of them), `None` after printing an error
+fn take_env_filters(args: &[String]) -> Option<(Vec<String>, Vec<EnvFilter>)> {
+    let mut rest = Vec::new();
+    let mut filters = Vec::new();
+
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--env" {
+            i += 1;
+            match args.get(i).map(|spec| spec.parse::<EnvFilter>()) {
+                Some(Ok(filter)) => filters.push(filter),
+                Some(Err(e)) => {
+                    eprintln!("Error: {}", e);
+                    return None;
+                }
+                None => {
+                    eprintln!("Error: --env needs NAME=value or NAME");
+                    return None;
+                }
+            }
+        } else {
+            rest.push(args[i].clone());
+        }
+        i += 1;
+    }
+
+    Some((rest, filters))
+}
+
+// `--interactive` for commands run from a prompt, `--scripted` for ones a
+// script ran
+fn take_origin_flag(args: &[String]) -> (Vec<String>, Option<bool>) {
+    let mut rest = Vec::new();
+    let mut interactive = None;
+
+    for arg in args {
+        match arg.as_str() {
+            "--interactive" => interactive = Some(true),
+            "--scripted" => interactive = Some(false),
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    (rest, interactive)
+}
+
+// `--branch <name>`, or `--branch .` for the one checked out here
+fn take_branch_flag(args: &[String]) -> Option<(Vec<String>, Option<String>)> {
+    let mut rest = Vec::new();
+    let mut branch = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--branch" {
+            i += 1;
+            match args.get(i).map(String::as_str) {
+                Some(".") => match env::current_dir().ok().and_then(ProjectDetector::git_branch) {
+                    Some(current) => branch = Some(current),
+                    None => {
+                        eprintln!("Error: Not on a git branch here");
+                        return None;
+                    }
+                },
+                Some(name) if !name.is_empty() => branch = Some(name.to_string()),
+                _ => {
+                    eprintln!("Error: --branch needs a branch name (or . for this one)");
+                    return None;
+                }
+            }
+        } else {
+            rest.push(args[i].clone());
+        }
+        i += 1;
+    }
+
+    Some((rest, branch))
+}
+
+// Pulls `--global`/`-g` and `--project <name|path>` out of the args,
+// None (after saying why) when --project has no value
+fn take_scope_flags(args: &[String]) -> Option<(Vec<String>, Scope)> {
+    let mut rest = Vec::new();
+    let mut scope = Scope::Here;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--global" | "-g" => scope = Scope::Global,
+            "--project" => {
+                i += 1;
+                match args.get(i) {
+                    Some(p) => scope = Scope::Project(p.clone()),
+                    None => {
+                        eprintln!("Error: --project needs a name or path");
+                        return None;
+                    }
+                }
+            }
+            _ => rest.push(args[i].clone()),
+        }
+        i += 1;
+    }
+
+    Some((rest, scope))
+}
+
+// The project path to filter on, None for every project. The outer None
+// means --project named nothing we know (already reported).
+async fn scope_filter(db: &Database, scope: &Scope) -> Result<Option<Option<String>>> {
+    match scope {
+        Scope::Global => Ok(Some(None)),
+        Scope::Project(arg) => Ok(resolve_project(db, arg).await?.map(Some)),
+        Scope::Here => Ok(Some(
+            ProjectDetector::detect(env::current_dir()?)
+                .ok()
+                .and_then(|p| p.to_str().map(String::from)),
+        )),
+    }
+}
+
+// `--sort`, `--columns`, `--absolute` and `--porcelain` for recent and search
+struct Layout {
+    sort: Option<SortKey>,
+    columns: Option<Vec<Column>>,
+    absolute: bool,
+    porcelain: bool,
+}
+
+// Pulls `--sort <key>`, `--columns <list>`, `--absolute` and `--porcelain` out
+// of the args, None (after saying why) when a value is missing or wrong
+fn take_layout_flags(args: &[String]) -> Option<(Vec<String>, Layout)> {
+    let mut rest = Vec::new();
+    let mut layout = Layout {
+        sort: None,
+        columns: None,
+        absolute: false,
+        porcelain: false,
+    };
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--sort" => {
+                i += 1;
+                match args.get(i).map(|k| k.parse::<SortKey>()) {
+                    Some(Ok(key)) => layout.sort = Some(key),
+                    Some(Err(e)) => {
+                        eprintln!("Error: {}", e);
+                        return None;
+                    }
+                    None => {
+                        eprintln!("Error: --sort needs recency, usage, duration or exit");
+                        return None;
+                    }
+                }
+            }
+            "--absolute" => layout.absolute = true,
+            "--porcelain" => layout.porcelain = true,
+            "--columns" => {
+                i += 1;
+                match args.get(i).map(|c| listing::parse_columns(c)) {
+                    Some(Ok(columns)) => layout.columns = Some(columns),
+                    Some(Err(e)) => {
+                        eprintln!("Error: {}", e);
+                        return None;
+                    }
+                    None => {
+                        eprintln!("Error: --columns needs a list like cmd,count,last,exit,duration");
+                        return None;
+                    }
+                }
+            }
+            _ => rest.push(args[i].clone()),
+        }
+        i += 1;
+    }
+
+    Some((rest, layout))
+}
+
+// Sorts the rows in place (so --copy N matches what's shown) and prints them,
+// cut to the terminal's width when there is one. With `group` the rows are
+// then gathered under a heading per group.
+fn print_table(
+    commands: &mut [berri_recall_lib::db::Command],
+    layout: &Layout,
+    defaults: &[Column],
+    global: bool,
+    matches: Matches,
+    group: Option<&dyn Fn(&berri_recall_lib::db::Command) -> String>,
+) {
+    if let Some(key) = layout.sort {
+        listing::sort(commands, key);
+    }
+    let groups = group.map(|key| listing::group(commands, key));
+    if layout.porcelain {
+        let columns = layout.columns.as_deref().unwrap_or(listing::PORCELAIN_COLUMNS);
+        print!("{}", listing::render_porcelain(commands, columns));
+        return;
+    }
+
+    let mut columns = layout.columns.clone().unwrap_or_else(|| defaults.to_vec());
+    // Across projects a row means little without where it ran
+    if global && layout.columns.is_none() {
+        columns.push(Column::Project);
+    }
+
+    let terminal = std::io::stdout().is_terminal();
+    let width = terminal
+        .then(terminal_size::terminal_size)
+        .flatten()
+        .map(|(width, _)| width.0 as usize);
+    let times = if layout.absolute {
+        Times::Absolute
+    } else {
+        Times::Relative(chrono::Utc::now())
+    };
+    let matches = Matches {
+        color: matches.color && ui::theme().color,
+        ..matches
+    };
+    let table = match &groups {
+        Some(groups) => listing::render_grouped(commands, groups, &columns, times, width),
+        None => listing::render_matches(commands, &columns, times, width, matches),
+    };
+    print!("{}", table);
+}
+
+// Pulls `--copy [N]` out of the args. N is the 1-based row to copy, default 1.
+fn take_copy_flag(args: &[String]) -> (Vec<String>, Option<usize>) {
+    let mut rest = Vec::new();
+    let mut copy = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--copy" {
+            let row = args.get(i + 1).and_then(|n| n.parse::<usize>().ok());
+            if row.is_some() {
+                i += 1;
+            }
+            copy = Some(row.unwrap_or(1).max(1));
+        } else {
+            rest.push(args[i].clone());
+        }
+        i += 1;
+    }
+
+    (rest, copy)
+}
+
+fn copy_result(command: Option<&str>, row: usize) {
+    let command = match command {
+        Some(c) => c,
+        None => {
+            eprintln!("Nothing at #{} to copy.", row);
+            return;
+        }
+    };
+
+    match clipboard::copy(command) {
+        Ok(_) => println!("Copied to clipboard: {}", command),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+// Paste-ready summary of the last (failing) run, for bug reports and asking for help
+async fn handle_last(args: &[String]) -> Result<()> {
+    let failed_only = args.iter().any(|a| a == "--failed");
+
+    let db = get_database().await?;
+    let cwd = env::current_dir()?;
+    let project_root = ProjectDetector::detect(&cwd).ok();
+    let project_path = project_root.as_ref().and_then(|p| p.to_str());
+
+    let execution = if failed_only {
+        db.get_last_failed_execution(project_path).await?
+    } else {
+        db.get_execution_history(project_path, 1).await?.pop()
+    };
+
+    let execution = match execution {
+        Some(e) => e,
+        None if failed_only => {
+            println!("Nothing has failed in this project. Nice.");
+            return Ok(());
+        }
+        None => {
+            println!("No commands found.");
+            return Ok(());
+        }
+    };
+
+    let ran_at = local_time(&execution.executed_at, "%Y-%m-%d %H:%M:%S %Z");
+
+    println!("$ {}", execution.command);
+    match execution.exit_code {
+        Some(code) => println!("exit code: {}", code),
+        None => println!("exit code: unknown"),
+    }
+    println!("ran at:    {}", ran_at);
+    if let Some(ms) = execution.execution_time_ms {
+        println!("took:      {}", format_duration(ms.max(0) as u64));
+    }
+    println!("in:        {}", execution.project_path);
+
+    Ok(())
+}
+
+// SQLite timestamp in local time, or as stored if it doesn't parse
+fn local_time(timestamp: &str, format: &str) -> String {
+    parse_timestamp(timestamp)
+        .map(|t| t.with_timezone(&chrono::Local).format(format).to_string())
+        .unwrap_or(timestamp.to_string())
+}
+
+async fn handle_sessions(args: &[String]) -> Result<()> {
+    let limit = args
+        .first()
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or(20);
+
+    let db = get_database().await?;
+    let sessions = db.get_sessions(limit).await?;
+
+    if sessions.is_empty() {
+        println!("No sessions yet. Shells pick up session tracking after `berri-recall setup`.");
+        return Ok(());
+    }
+
+    println!("\nShell sessions:");
+    println!("{}", "=".repeat(60));
+    for session in &sessions {
+        let started = local_time(&session.started_at, "%Y-%m-%d %H:%M");
+        // Same day is the usual case, no need to repeat the date
+        let ended = if session.ended_at.get(..10) == session.started_at.get(..10) {
+            local_time(&session.ended_at, "%H:%M")
+        } else {
+            local_time(&session.ended_at, "%Y-%m-%d %H:%M")
+        };
+
+        println!(
+            "{}  {} {} {}  {} ({} commands)",
+            session.session_id,
+            started,
+            ui::glyph(Glyph::Arrow),
+            ended,
+            session.hostname.as_deref().unwrap_or("?"),
+            session.command_count
+        );
+        println!("    {}", session.projects.replace(',', ", "));
+    }
+    println!("{}", "=".repeat(60));
+    println!("berri-recall session show <id> for the full timeline (a unique prefix is enough)");
+
+    Ok(())
+}
+
+// A session from the start of its ID, or None after saying why not
+async fn resolve_session(db: &Database, prefix: &str) -> Result<Option<String>> {
+    let mut matches = db.find_sessions(prefix).await?;
+    match matches.len() {
+        1 => Ok(matches.pop()),
+        0 => {
+            eprintln!("No session starting with '{}'", prefix);
+            Ok(None)
+        }
+        n => {
+            eprintln!("'{}' matches {} sessions, give more of the ID:", prefix, n);
+            for id in &matches {
+                eprintln!("  {}", id);
+            }
+            Ok(None)
+        }
+    }
+}
+
+async fn handle_session(args: &[String]) -> Result<()> {
+    let prefix = match (args.first().map(|s| s.as_str()), args.get(1)) {
+        (Some("show"), Some(id)) => id,
+        _ => {
+            eprintln!("Usage: berri-recall session show <id>");
+            return Ok(());
+        }
+    };
+
+    let db = get_database().await?;
+    let Some(session_id) = resolve_session(&db, prefix).await? else {
+        return Ok(());
+    };
+
+    let executions = db.get_session_executions(&session_id).await?;
+    let (Some(first), Some(last)) = (executions.first(), executions.last()) else {
+        return Ok(());
+    };
+
+    println!(
+        "\nSession {} on {}",
+        session_id,
+        first.hostname.as_deref().unwrap_or("?")
+    );
+    println!(
+        "{} {} {}, {} commands",
+        local_time(&first.executed_at, "%Y-%m-%d %H:%M:%S"),
+        ui::glyph(Glyph::Arrow),
+        local_time(&last.executed_at, "%Y-%m-%d %H:%M:%S"),
+        executions.len()
+    );
+    println!("{}", "=".repeat(60));
+
+    // Only say where we are when it changes, otherwise it's noise on every line
+    let mut current_dir: Option<&str> = None;
+    for execution in &executions {
+        let dir = execution.cwd.as_deref().unwrap_or(&execution.project_path);
+        if current_dir != Some(dir) {
+            println!("  in {}", dir);
+            current_dir = Some(dir);
+        }
+
+        let status = match execution.exit_code {
+            // Padded below, so no color
+            Some(0) => ui::glyph(Glyph::Ok).to_string(),
+            Some(code) => format!("{} {}", ui::glyph(Glyph::Fail), code),
+            None => " ".to_string(),
+        };
+        let took = execution
+            .execution_time_ms
+            .map(|ms| format!("  ({})", format_duration(ms.max(0) as u64)))
+            .unwrap_or_default();
+
+        println!(
+            "{}  {:<5} {}{}",
+            local_time(&execution.executed_at, "%H:%M:%S"),
+            status,
+            execution.command,
+            took
+        );
+    }
+    println!("{}", "=".repeat(60));
+
+    Ok(())
+}
+
+// Step through a past session's commands again, asking before each one
+async fn handle_replay(args: &[String]) -> Result<()> {
+    let Some(prefix) = args.first() else {
+        eprintln!("Usage: berri-recall replay <session-id>\n\nIDs are in `berri-recall sessions`");
+        return Ok(());
+    };
+
+    let db = Arc::new(get_database().await?);
+    let Some(session_id) = resolve_session(&db, prefix).await? else {
+        return Ok(());
+    };
+    let executions = db.get_session_executions(&session_id).await?;
+
+    // Runs from here are a session of their own, this shell's if it has one
+    let meta = ExecutionMeta {
+        session_id: env::var("BERRI_RECALL_SESSION").ok().filter(|s| !s.is_empty()),
+        hostname: gethostname::gethostname().into_string().ok(),
+        cwd: None,
+        executed_at: None,
+        env: None,
+        branch: None,
+        interactive: None,
+    };
+    let config = Config::load(&db).await?;
+    let capture_env = config.capture_env();
+    let ignore_rules = IgnoreRules::load(&db).await.unwrap_or_default();
+    // Up-arrow finds what replay ran, in the shell you're replaying from
+    let history_shell = if config.write_history() {
+        ShellDetector::detect().ok()
+    } else {
+        None
+    };
+
+    // Where the session started may not exist on this machine: start here
+    // and follow the session's `cd`s instead
+    let mut cwd = env::current_dir()?;
+    let total = executions.len();
+    let stdin = std::io::stdin();
+
+    for (n, execution) in executions.iter().enumerate() {
+        let mut command = execution.command.clone();
+
+        loop {
+            eprintln!("\n[{}/{}] {}", n + 1, total, command);
+            let warnings = safety::lint(&command, &safety::Repo::detect(&cwd));
+            for warning in &warnings {
+                eprintln!("  {} {}", ui::warn(), warning);
+            }
+            eprint!("[r]un, [s]kip, [e]dit, [q]uit? ");
+            let mut answer = String::new();
+            // End of input is a quit, not a yes
+            if stdin.read_line(&mut answer)? == 0 {
+                eprintln!();
+                return Ok(());
+            }
+
+            match answer.trim().to_lowercase().as_str() {
+                "r" | "run" if warnings.is_empty() => break,
+                "r" | "run" => {
+                    // A warned command takes more than one keypress
+                    eprint!("Run it anyway? Type `yes`: ");
+                    let mut sure = String::new();
+                    stdin.read_line(&mut sure)?;
+                    if sure.trim() == "yes" {
+                        break;
+                    }
+                }
+                "s" | "skip" => {
+                    command.clear();
+                    break;
+                }
+                "e" | "edit" => {
+                    eprint!("Command: ");
+                    let mut edited = String::new();
+                    stdin.read_line(&mut edited)?;
+                    if !edited.trim().is_empty() {
+                        command = edited.trim().to_string();
+                    }
+                }
+                "q" | "quit" => return Ok(()),
+                _ => {}
+            }
+        }
+
+        if command.is_empty() {
+            continue;
+        }
+
+        if let Some(dir) = runner::cd_target(&command, &cwd) {
+            cwd = dir;
+            eprintln!("  now in {}", cwd.display());
+            continue;
+        }
+
+        let outcome = runner::run(&command, &cwd)?;
+        if outcome.exit_code != 0 {
+            eprintln!("{} exited with {}", ui::fail(), outcome.exit_code);
+        }
+        if let Some(shell) = history_shell {
+            if let Err(e) = history::append(shell, &command, chrono::Utc::now()) {
+                eprintln!("{} Couldn't add it to your {} history: {}", ui::warn(), shell, e);
+            }
+        }
+
+        let Ok(project_root) = ProjectDetector::detect(&cwd) else {
+            continue;
+        };
+        let project = project_root.to_string_lossy().to_string();
+        let recorder = Recorder::new(Arc::clone(&db))
+            .with_meta(ExecutionMeta {
+                cwd: cwd.to_str().map(String::from),
+                env: environment::capture(&capture_env, &cwd, &project_root),
+                branch: ProjectDetector::git_branch(&cwd),
+                ..meta.clone()
+            })
+            .with_ignore_rules(ignore_rules.clone());
+        if recorder.should_ignore(&command) {
+            continue;
+        }
+        let (exit_code, duration_ms) = (Some(outcome.exit_code), Some(outcome.duration_ms));
+        if recorder
+            .record(&command, &project, duration_ms, exit_code, None)
+            .await
+            .is_ok()
+        {
+            let _ = after_record(&db, &command, &project, exit_code, duration_ms).await;
+        }
+    }
+
+    eprintln!("\nEnd of session {}", session_id);
+    Ok(())
+}
+
+// Something sensitive got past the filters: forget the last thing this shell recorded
+async fn handle_oops(args: &[String]) -> Result<()> {
+    let mut session_id = env::var("BERRI_RECALL_SESSION")
+        .ok()
+        .filter(|s| !s.is_empty());
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--session" => {
+                i += 1;
+                session_id = args.get(i).cloned();
+            }
+            other => {
+                eprintln!("Unknown option: {}", other);
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+
+    let db = get_database().await?;
+
+    // Hooks from before sessions existed don't tell us which shell we're in,
+    // so settle for the last thing recorded in this project
+    let project_path = if session_id.is_none() {
+        let cwd = env::current_dir()?;
+        ProjectDetector::detect(&cwd)
+            .ok()
+            .and_then(|p| p.to_str().map(String::from))
+    } else {
+        None
+    };
+
+    match db
+        .undo_last_execution(session_id.as_deref(), project_path.as_deref())
+        .await?
+    {
+        Some(execution) => {
+            drop_from_prompt(&execution.project_path, &execution.command);
+            println!("{} Forgot: {}", ui::ok(), execution.command);
+            println!("  (your shell's own history still has it)");
+        }
+        None => println!("Nothing recorded in this session yet."),
+    }
+
+    Ok(())
+}
+
+// Clean up history recorded before a sensitive pattern was known about
+async fn handle_redact(args: &[String]) -> Result<()> {
+    let mut query: Option<String> = None;
+    let mut delete = false;
+    let mut dry_run = false;
+    let mut yes = false;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--query" => {
+                i += 1;
+                query = args.get(i).cloned();
+            }
+            "--delete" => delete = true,
+            "--dry-run" => dry_run = true,
+            "--yes" | "-y" => yes = true,
+            other => {
+                eprintln!("Unknown option: {}", other);
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+
+    let Some(query) = query else {
+        eprintln!("Usage: berri-recall redact --query <regex> [--delete [--yes]] [--dry-run]");
+        return Ok(());
+    };
+    let matcher = match regex::Regex::new(&query) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Invalid --query regex: {}", e);
+            return Ok(());
+        }
+    };
+
+    let db = get_database().await?;
+    let redactor = Redactor::new();
+    let matching: Vec<_> = db
+        .get_recent_commands(None, i64::MAX)
+        .await?
+        .into_iter()
+        .filter(|c| matcher.is_match(&c.command))
+        .collect();
+
+    if matching.is_empty() {
+        println!("No stored commands match '{}'.", query);
+        return Ok(());
+    }
+
+    if dry_run {
+        for cmd in &matching {
+            if delete {
+                println!("delete  {}", cmd.command);
+            } else {
+                println!(
+                    "{}\n     {} {}",
+                    cmd.command,
+                    ui::glyph(Glyph::Arrow),
+                    redactor.redact(&cmd.command)
+                );
+            }
+        }
+        println!("\n{} commands match (nothing changed, this was a dry run)", matching.len());
+        return Ok(());
+    }
+
+    // Deleting can't be undone, so say how much first
+    if delete && !yes {
+        eprint!(
+            "{} commands match '{}'. Delete them and all their runs? [y/N] ",
+            matching.len(),
+            query
+        );
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            eprintln!("Nothing deleted (--dry-run lists them)");
+            return Ok(());
+        }
+    }
+
+    let mut changed = 0;
+    for cmd in &matching {
+        if delete {
+            db.delete_command(cmd.id).await?;
+            changed += 1;
+        } else {
+            let redacted = redactor.redact(&cmd.command);
+            if redacted != cmd.command {
+                db.rewrite_command(cmd.id, &redacted).await?;
+                changed += 1;
+            }
+        }
+        forget_in_prompt(&cmd.command);
+    }
+    let derived = db.forget_derived(|c| matcher.is_match(c)).await?;
+
+    if delete {
+        println!("{} Deleted {} commands", ui::ok(), changed);
+    } else {
+        println!("{} Redacted {} of {} matching commands", ui::ok(), changed, matching.len());
+        if changed < matching.len() {
+            println!(
+                "  {} had nothing that looks like a secret. Use --delete to remove them instead.",
+                matching.len() - changed
+            );
+        }
+    }
+    if derived > 0 {
+        println!(
+            "  Dropped {} patterns/suggestions built from them (analyze rebuilds them)",
+            derived
+        );
+    }
+
+    Ok(())
+}
+
+// Every table and setting as one JSON archive
+async fn handle_export(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall export --everything [--output <file>]";
+    let mut everything = false;
+    let mut output: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--everything" => everything = true,
+            "--output" | "-o" => {
+                i += 1;
+                output = args.get(i).cloned();
+                if output.is_none() {
+                    eprintln!("{}", usage);
+                    return Ok(());
+                }
+            }
+            other => {
+                eprintln!("Unknown option: {}\n{}", other, usage);
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+    if !everything {
+        eprintln!("{}", usage);
+        eprintln!("(`batch <rows> export` turns rows of `recent` into a script)");
+        return Ok(());
+    }
+
+    let db = get_database().await?;
+    let archive = db.export_everything().await?;
+    let json = serde_json::to_string_pretty(&archive)?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, json + "\n")?;
+            let rows: usize = archive.manifest.tables.values().sum();
+            eprintln!(
+                "{} Exported {} rows from {} tables to {}",
+                ui::ok(),
+                rows,
+                archive.manifest.tables.len(),
+                path
+            );
+        }
+        None => println!("{}", json),
+    }
+
+    Ok(())
+}
+
+// Load an `export --everything` archive, on a fresh install or over this one
+async fn handle_import(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall import --everything <file> [--replace]";
+    let mut everything = false;
+    let mut replace = false;
+    let mut file: Option<String> = None;
+
+    for arg in args {
+        match arg.as_str() {
+            "--everything" => everything = true,
+            "--replace" => replace = true,
+            other if file.is_none() && !other.starts_with('-') => file = Some(other.to_string()),
+            other => {
+                eprintln!("Unknown option: {}\n{}", other, usage);
+                return Ok(());
+            }
+        }
+    }
+    let (true, Some(file)) = (everything, file) else {
+        eprintln!("{}", usage);
+        return Ok(());
+    };
+
+    let archive = match std::fs::read_to_string(&file)
+        .map_err(RecallError::from)
+        .and_then(|text| Archive::parse(&text))
+    {
+        Ok(archive) => archive,
+        Err(e) => {
+            eprintln!("{}: {}", file, e);
+            return Ok(());
+        }
+    };
+
+    let db = get_database().await?;
+    let Some(_lock) = maintenance_lock(db.path(), "importing an archive") else {
+        return Ok(());
+    };
+    if !replace && !db.get_recent_commands(None, 1).await?.is_empty() {
+        eprintln!("There's already history here. Use --replace to swap it for the archive");
+        eprintln!("(a backup is taken first), or `merge` to combine two databases.");
+        return Ok(());
+    }
+    if replace {
+        let path = backup::backup_dir(db.path()).join(format!(
+            "commands-{}.db",
+            chrono::Utc::now().format("%Y%m%d-%H%M%S")
+        ));
+        db.backup_to(&path).await?;
+        println!("Backed up the current database to {}", path.display());
+    }
+
+    let stats = db.import_everything(&archive, replace).await?;
+    println!(
+        "{} Imported the archive from berri-recall v{} ({})",
+        ui::ok(),
+        archive.manifest.app_version, archive.manifest.exported_at
+    );
+    for (table, rows) in &stats.rows {
+        println!("  {:<18} {}", table, rows);
+    }
+    if !stats.skipped_columns.is_empty() {
+        println!(
+            "  Not kept (this version has no such column): {}",
+            stats.skipped_columns.join(", ")
+        );
+    }
+
+    if let Some(text) = &archive.manifest.config_file {
+        match Config::file_path(&db) {
+            Some(path) if !path.exists() => {
+                std::fs::write(&path, text)?;
+                println!("  Restored {}", path.display());
+            }
+            Some(path) => println!("  Left {} as it is (the archive has its own)", path.display()),
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Move old runs out of the database into compressed monthly files
+async fn handle_archive(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall archive [--older-than <months>]";
+    let months = match args {
+        [] => None,
+        [flag, months] if flag == "--older-than" => match months.parse::<u32>() {
+            Ok(months) if months > 0 => Some(months),
+            _ => {
+                eprintln!("{}", usage);
+                return Ok(());
+            }
+        },
+        _ => {
+            eprintln!("{}", usage);
+            return Ok(());
+        }
+    };
+
+    let db = get_database().await?;
+    let months = match months {
+        Some(months) => months,
+        None => Config::load(&db).await?.archive_after_months() as u32,
+    };
+    let Some(before) = chrono::Utc::now().checked_sub_months(chrono::Months::new(months)) else {
+        eprintln!("{}", usage);
+        return Ok(());
+    };
+    let megabytes = |path: &std::path::Path| {
+        std::fs::metadata(path).map(|m| m.len()).unwrap_or(0) as f64 / 1_048_576.0
+    };
+    let size_before = megabytes(db.path());
+
+    let Some(_lock) = maintenance_lock(db.path(), "archiving old runs") else {
+        return Ok(());
+    };
+    let dir = cold::cold_dir(db.path());
+    let stats = db.move_to_cold(&dir, before).await?;
+    if stats.runs == 0 {
+        println!("Nothing older than {} month(s) to archive.", months);
+        return Ok(());
+    }
+
+    println!(
+        "{} Archived {} run(s) older than {} month(s), {} command(s) with them",
+        ui::ok(),
+        stats.runs,
+        months,
+        stats.commands
+    );
+    let (first, last) = (&stats.months[0], &stats.months[stats.months.len() - 1]);
+    if first == last {
+        println!("  {} into {}", first, dir.display());
+    } else {
+        println!("  {} to {} into {}", first, last, dir.display());
+    }
+    println!(
+        "  Database: {:.1} MB -> {:.1} MB",
+        size_before,
+        megabytes(db.path())
+    );
+    println!("  `search --include-archived` still finds them.");
+    Ok(())
+}
+
+// Fill a throwaway database with made-up history to try everything on
+async fn handle_demo(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall demo [--seed <n>] [--commands <n>]";
+    let mut seed: Option<u64> = None;
+    let mut count = DEMO_COMMANDS;
+
+    let mut i = 0;
+    while i < args.len() {
+        let value = args.get(i + 1).and_then(|v| v.parse::<u64>().ok());
+        match (args[i].as_str(), value) {
+            ("--seed", Some(value)) => seed = Some(value),
+            ("--commands", Some(value)) if value > 0 => count = value as usize,
+            ("--seed" | "--commands", _) => {
+                eprintln!("{}", usage);
+                return Ok(());
+            }
+            (other, _) => {
+                eprintln!("Unknown option: {}\n{}", other, usage);
+                return Ok(());
+            }
+        }
+        i += 2;
+    }
+    // No seed: a new history each time, with the seed shown to get it back
+    let seed = seed.unwrap_or_else(|| chrono::Utc::now().timestamp_micros() as u64);
+
+    let dir = env::temp_dir().join(format!("berri-recall-demo-{}", seed));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    let projects = dir.join("projects");
+    demo::create_projects(&projects)?;
+    let path = dir.join("commands.db");
+    let db = Database::new(&path).await?;
+
+    println!("Making up {} commands (seed {})...", count, seed);
+    let runs = demo::generate(seed, count, &projects, chrono::Utc::now());
+    for run in &runs {
+        let project_path = run.project_path.to_string_lossy().into_owned();
+        let input = CommandInput {
+            project_path: project_path.clone(),
+            command: run.command.clone(),
+            execution_time_ms: Some(run.duration_ms),
+            exit_code: Some(run.exit_code),
+            context: None,
+        };
+        let meta = ExecutionMeta {
+            session_id: Some(run.session_id.clone()),
+            hostname: Some("demo-laptop".to_string()),
+            cwd: Some(project_path),
+            executed_at: Some(run.executed_at.format("%Y-%m-%d %H:%M:%S").to_string()),
+            env: None,
+            branch: Some(run.branch.clone()),
+            interactive: Some(true),
+        };
+        db.record_command_with(input, &meta).await?;
+    }
+
+    println!("{} Demo history in {}", ui::ok(), path.display());
+    println!();
+    println!("Point berri-recall at it (this shell only) and look around:");
+    println!("  export {}={}", recall::DB_ENV, path.display());
+    println!("  cd {}", projects.join("ledger").display());
+    println!("  berri-recall recent");
+    println!("  berri-recall analyze && berri-recall suggest");
+    println!("  berri-recall flaky");
+    println!();
+    println!("`unset {}` goes back to your own history.", recall::DB_ENV);
+    Ok(())
+}
+
+// Record from the shell's history file as it grows, for setups without the hooks
+async fn handle_mirror(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall mirror [--watch] [--shell <bash|zsh|fish|powershell>] \
+                 [--file <history file>]";
+    let mut watch = false;
+    let mut shell: Option<Shell> = None;
+    let mut file: Option<std::path::PathBuf> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--watch" => watch = true,
+            "--shell" => {
+                i += 1;
+                match args.get(i).map(|name| name.parse::<Shell>()) {
+                    Some(Ok(parsed)) => shell = Some(parsed),
+                    Some(Err(e)) => {
+                        eprintln!("{}", e);
+                        return Ok(());
+                    }
+                    None => {
+                        eprintln!("{}", usage);
+                        return Ok(());
+                    }
+                }
+            }
+            "--file" => {
+                i += 1;
+                let Some(path) = args.get(i) else {
+                    eprintln!("{}", usage);
+                    return Ok(());
+                };
+                file = Some(env::current_dir()?.join(path));
+            }
+            other => {
+                eprintln!("Unknown option: {}\n{}", other, usage);
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+
+    let shell = match shell.map_or_else(ShellDetector::detect, Ok) {
+        Ok(shell) => shell,
+        Err(e) => {
+            eprintln!("{} (pick one with --shell)", e.user_message());
+            return Ok(());
+        }
+    };
+    let path = match file {
+        Some(path) => path,
+        None => history::path(shell)?,
+    };
+
+    let db = Arc::new(get_database().await?);
+    // Two followers of one file would both record every line
+    let name = format!("mirror-{}", path.file_name().unwrap_or_default().to_string_lossy());
+    let job = format!("mirroring {}", path.display());
+    let _lock = match Lock::try_acquire(&lock::lock_path(db.path(), &name), &job) {
+        Ok(lock) => lock,
+        Err(e) => {
+            eprintln!("{} {}", ui::fail(), e.user_message());
+            return Ok(());
+        }
+    };
+    if HookInstaller::new().is_ok_and(|installer| installer.is_installed(shell)) {
+        eprintln!(
+            "{} The {} hook is recording already, mirroring too records everything twice",
+            ui::warn(),
+            shell
+        );
+    }
+
+    let key = format!("{}{}", mirror::OFFSET_PREFIX, path.display());
+    let offset = db.get_preference(&key).await?.and_then(|o| o.parse().ok());
+    if offset.is_none() {
+        println!(
+            "Mirroring {} from here on: what's in it already isn't imported",
+            path.display()
+        );
+    }
+    let mut tail = Tail::new(path, offset);
+    // Where the file's `cd`s had got to last time, else where a shell is now
+    let cwd_key = format!("{}{}", mirror::CWD_PREFIX, tail.path().display());
+    let followed = db
+        .get_preference(&cwd_key)
+        .await?
+        .map(std::path::PathBuf::from)
+        .filter(|dir| dir.is_dir());
+    let mut cwd = match followed
+        .or_else(|| mirror::guess_cwd(shell))
+        .or_else(dirs::home_dir)
+    {
+        Some(dir) => dir,
+        None => env::current_dir()?,
+    };
+    let mut ignore_rules = IgnoreRules::load(&db).await.unwrap_or_default();
+    let hostname = gethostname::gethostname().into_string().ok();
+
+    #[cfg(feature = "watch")]
+    let mut watching = if watch {
+        Some(mirror::watch(tail.path())?)
+    } else {
+        None
+    };
+    let mut signals = watch.then(Signals::new).transpose()?;
+    if watch {
+        println!("Watching {} (Ctrl-C stops)", tail.path().display());
+    }
+    let started = std::time::Instant::now();
+    let mut total = 0;
+
+    loop {
+        let text = tail.read(shell)?;
+        let format = history::Format::detect(shell, &text);
+        let mut recorded = 0;
+
+        for entry in format.parse(shell, &text) {
+            let command = entry.command;
+            // Where the shell was is a guess, kept up by following its `cd`s
+            if let Some(dir) = runner::cd_target(&command, &cwd) {
+                cwd = dir;
+            }
+            let Ok(project_root) = ProjectDetector::detect(&cwd) else {
+                continue;
+            };
+            let project = project_root.to_string_lossy().to_string();
+            let recorder = Recorder::new(Arc::clone(&db))
+                .with_meta(ExecutionMeta {
+                    session_id: None,
+                    hostname: hostname.clone(),
+                    cwd: cwd.to_str().map(String::from),
+                    executed_at: entry
+                        .at
+                        .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string()),
+                    env: None,
+                    branch: ProjectDetector::git_branch(&cwd),
+                    interactive: Some(true),
+                })
+                .with_ignore_rules(ignore_rules.clone());
+            if recorder.should_ignore(&command) {
+                let _ = recorder.count_dropped(&command).await;
+                continue;
+            }
+            if recorder
+                .record(&command, &project, None, None, None)
+                .await
+                .is_ok()
+            {
+                recorded += 1;
+                let _ = after_record(&db, &command, &project, None, None).await;
+            }
+        }
+        db.set_preference(key.clone(), tail.offset().to_string())
+            .await?;
+        db.set_preference(cwd_key.clone(), cwd.to_string_lossy().to_string())
+            .await?;
+        total += recorded;
+
+        if !watch {
+            println!(
+                "{} Recorded {} new command{} from {}",
+                ui::ok(),
+                recorded,
+                if recorded == 1 { "" } else { "s" },
+                tail.path().display()
+            );
+            return Ok(());
+        }
+
+        // Changes wake it early where the platform reports them
+        let wait = async {
+            #[cfg(feature = "watch")]
+            if let Some((_, changes)) = watching.as_mut() {
+                let _ = tokio::time::timeout(MIRROR_POLL, changes.recv()).await;
+            }
+            #[cfg(not(feature = "watch"))]
+            tokio::time::sleep(MIRROR_POLL).await;
+        };
+        let Some(signals) = signals.as_mut() else {
+            unreachable!("created for --watch");
+        };
+        // Only between reads, nothing is cut off halfway through recording
+        tokio::select! {
+            _ = wait => {}
+            signal = signals.recv() => match signal {
+                Signal::Stop => {
+                    if let Some(dir) = Spool::default_dir() {
+                        let recorder = Recorder::new(Arc::clone(&db))
+                            .with_ignore_rules(ignore_rules.clone());
+                        let _ = Spool::new(dir).drain(&recorder).await;
+                    }
+                    db.close().await;
+                    println!(
+                        "{} Stopped after {}, {} command{} recorded",
+                        ui::ok(),
+                        format_uptime(started.elapsed()),
+                        total,
+                        if total == 1 { "" } else { "s" }
+                    );
+                    return Ok(());
+                }
+                Signal::Reload => {
+                    ignore_rules = IgnoreRules::load(&db).await.unwrap_or_default();
+                    if let Ok(config) = Config::load(&db).await {
+                        ui::set_theme(Theme::from_config(&config));
+                    }
+                    eprintln!("Reloaded the settings and ignore rules");
+                }
+                Signal::Report => {
+                    let spooled = Spool::default_dir()
+                        .map(|dir| Spool::new(dir).pending().len())
+                        .unwrap_or(0);
+                    eprintln!(
+                        "Up {}, {} command{} recorded, {} bytes into {}, {} spooled",
+                        format_uptime(started.elapsed()),
+                        total,
+                        if total == 1 { "" } else { "s" },
+                        tail.offset(),
+                        tail.path().display(),
+                        spooled
+                    );
+                }
+            },
+        }
+    }
+}
+
+// "3h 12m" for how long something has been running
+fn format_uptime(elapsed: std::time::Duration) -> String {
+    let minutes = elapsed.as_secs() / 60;
+    match (minutes / 60 / 24, minutes / 60 % 24, minutes % 60) {
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+// Numbers about your history as a whole. For now that's --tool-trends: runs
+// per tool, week by week.
+async fn handle_stats(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall stats --tool-trends [--months N] [--top N] [--json] \
+                 [--project <name|path>]";
+    let Some((args, scope)) = take_scope_flags(args) else {
+        return Ok(());
+    };
+
+    let mut tool_trends = false;
+    let mut months = trends::DEFAULT_MONTHS;
+    let mut top = trends::DEFAULT_TOOLS;
+    let mut json = false;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--tool-trends" => tool_trends = true,
+            "--json" => json = true,
+            "--months" => {
+                i += 1;
+                match args.get(i).and_then(|n| n.parse::<u32>().ok()) {
+                    Some(n) if n > 0 => months = n,
+                    _ => {
+                        eprintln!("Error: --months needs a number of months");
+                        return Ok(());
+                    }
+                }
+            }
+            "--top" => {
+                i += 1;
+                match args.get(i).and_then(|n| n.parse::<usize>().ok()) {
+                    Some(n) if n > 0 => top = n,
+                    _ => {
+                        eprintln!("Error: --top needs a number of tools");
+                        return Ok(());
+                    }
+                }
+            }
+            _ => {
+                eprintln!("{}", usage);
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+    if !tool_trends {
+        eprintln!("{}", usage);
+        return Ok(());
+    }
+
+    let db = get_database().await?;
+    // Every project unless one is named: workflow shifts cut across repos
+    let project = match scope {
+        Scope::Here => None,
+        scope => match scope_filter(&db, &scope).await? {
+            Some(project) => project,
+            None => return Ok(()),
+        },
+    };
+
+    let now = chrono::Utc::now();
+    let weeks = trends::weeks_in(months);
+    let from = now - chrono::Duration::weeks(weeks as i64);
+    let runs: Vec<(chrono::DateTime<chrono::Utc>, String)> = db
+        .get_executions_in_range(project.as_deref(), from, now)
+        .await?
+        .into_iter()
+        // What scripts run says nothing about how you work
+        .filter(|e| e.interactive != Some(false))
+        .filter_map(|e| Some((parse_timestamp(&e.executed_at)?, e.command)))
+        .collect();
+    let tools = trends::build(&runs, weeks, now, top);
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&trends::report(tools, weeks, now))?
+        );
+        return Ok(());
+    }
+    if tools.is_empty() {
+        println!("Nothing run in the last {} months.", months);
+        return Ok(());
+    }
+
+    let width = tools.iter().map(|t| t.tool.chars().count()).max().unwrap_or(0);
+    println!(
+        "\nRuns per tool, week by week, last {} weeks (oldest {} this week):",
+        weeks,
+        ui::glyph(Glyph::Arrow)
+    );
+    for tool in &tools {
+        let direction = match tool.direction() {
+            trends::Direction::Steady => String::new(),
+            direction => format!("  {}", direction.as_str()),
+        };
+        println!(
+            "  {:<width$}  {}  {:>5}{}",
+            tool.tool,
+            tool.sparkline(),
+            tool.total,
+            direction,
+            width = width
+        );
+    }
+
+    Ok(())
+}
+
+// A tldr-style page for a tool, from how you run it
+async fn handle_tldr(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall tldr <tool> [N] [--project <name|path>] [--no-pages]\n       \
+                 berri-recall tldr <tool> --note <command> [text]";
+    let Some((args, scope)) = take_scope_flags(args) else {
+        return Ok(());
+    };
+
+    let mut tool: Option<String> = None;
+    let mut limit = 8;
+    let mut pages = true;
+    let mut note: Option<(String, Option<String>)> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--no-pages" => pages = false,
+            "--note" => {
+                i += 1;
+                let Some(command) = args.get(i) else {
+                    eprintln!("Error: --note needs the command, then the note (none removes it)");
+                    return Ok(());
+                };
+                let text = args.get(i + 1).filter(|t| !t.is_empty()).cloned();
+                i += text.is_some() as usize;
+                note = Some((command.clone(), text));
+            }
+            other if tool.is_none() && !other.starts_with('-') => tool = Some(other.to_string()),
+            other => match other.parse::<usize>() {
+                Ok(n) if n > 0 => limit = n,
+                _ => {
+                    eprintln!("{}", usage);
+                    return Ok(());
+                }
+            },
+        }
+        i += 1;
+    }
+    let Some(tool) = tool else {
+        eprintln!("{}", usage);
+        return Ok(());
+    };
+
+    let db = get_database().await?;
+
+    if let Some((command, text)) = note {
+        let line = cheatsheet::fold(&command);
+        let key = format!("{}{}", cheatsheet::NOTE_PREFIX, line);
+        match text {
+            Some(text) => {
+                db.set_preference(key, text).await?;
+                println!("{} Noted `{}`", ui::ok(), line);
+            }
+            None if db.delete_preference(&key).await? => {
+                println!("{} Removed the note on `{}`", ui::ok(), line)
+            }
+            None => println!("No note on `{}`", line),
+        }
+        return Ok(());
+    }
+
+    // Every project unless one is named: it's about the tool, not the repo
+    let project = match scope {
+        Scope::Here => None,
+        scope => match scope_filter(&db, &scope).await? {
+            Some(project) => project,
+            None => return Ok(()),
+        },
+    };
+    let commands: Vec<(String, i64)> = db
+        .get_command_stats(project.as_deref(), Some(&tool), i64::MAX)
+        .await?
+        .into_iter()
+        .map(|s| (s.command, s.usage_count as i64))
+        .collect();
+    let mut entries = cheatsheet::collect(&commands, limit);
+    for entry in &mut entries {
+        let key = format!("{}{}", cheatsheet::NOTE_PREFIX, entry.command);
+        entry.note = db.get_preference(&key).await?;
+    }
+
+    let examples = if pages {
+        cheatsheet::find_page(&tool)
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|text| cheatsheet::parse_page(&text))
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    if entries.is_empty() && examples.is_empty() {
+        println!("You haven't run {} yet, and there's no tldr page for it here.", tool);
+        return Ok(());
+    }
+    print!("{}", cheatsheet::render(&tool, &entries, &examples));
+
+    Ok(())
+}
+
+// The flags you give a tool, counting it wherever it ran: on its own or as
+// one stage of a pipeline
+async fn handle_flags(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall flags <tool> [N] [--project <name|path>]";
+    let Some((args, scope)) = take_scope_flags(args) else {
+        return Ok(());
+    };
+
+    let mut tool: Option<String> = None;
+    let mut limit = 15;
+    for arg in &args {
+        match arg.parse::<usize>() {
+            Ok(n) if n > 0 && tool.is_some() => limit = n,
+            _ if tool.is_none() && !arg.starts_with('-') => tool = Some(arg.clone()),
+            _ => {
+                eprintln!("{}", usage);
+                return Ok(());
+            }
+        }
+    }
+    let Some(tool) = tool else {
+        eprintln!("{}", usage);
+        return Ok(());
+    };
+
+    let db = get_database().await?;
+    // Every project unless one is named, like tldr
+    let project = match scope {
+        Scope::Here => None,
+        scope => match scope_filter(&db, &scope).await? {
+            Some(project) => project,
+            None => return Ok(()),
+        },
+    };
+
+    // Compound commands are counted through their stages, not twice
+    let mut commands: Vec<(String, i64)> = db
+        .get_command_stats(project.as_deref(), Some(&tool), i64::MAX)
+        .await?
+        .into_iter()
+        .filter(|s| pipeline::stages(&s.command).len() == 1)
+        .map(|s| (s.command, s.usage_count as i64))
+        .collect();
+    let staged = db.stage_uses(&tool, project.as_deref()).await?;
+    let in_pipelines: i64 = staged.iter().map(|(_, uses)| uses).sum();
+    commands.extend(staged);
+    let runs: i64 = commands.iter().map(|(_, uses)| uses).sum();
+
+    let mut flags = pipeline::flag_uses(&tool, &commands);
+    if flags.is_empty() {
+        match runs {
+            0 => println!("You haven't run {} yet.", tool),
+            _ => println!("You run {} without flags.", tool),
+        }
+        return Ok(());
+    }
+    flags.truncate(limit);
+
+    println!(
+        "\nFlags you give {} ({} run{}, {} in pipelines or chains):",
+        tool,
+        runs,
+        if runs == 1 { "" } else { "s" },
+        in_pipelines
+    );
+    let width = flags.iter().map(|(f, _)| f.chars().count()).max().unwrap_or(0);
+    for (flag, uses) in &flags {
+        println!("  {:<width$}  {:>5}", flag, uses, width = width);
+    }
+
+    Ok(())
+}
+
+// Walk history one entry at a time before syncing or sharing it
+async fn handle_sweep(args: &[String]) -> Result<()> {
+    let Some((args, scope)) = take_scope_flags(args) else {
+        return Ok(());
+    };
+    let mut limit = 50;
+    let mut risky = false;
+    for arg in &args {
+        match arg.as_str() {
+            "--risky" => risky = true,
+            other => match other.parse::<usize>() {
+                Ok(n) if n > 0 => limit = n,
+                _ => {
+                    eprintln!("Usage: berri-recall sweep [N] [--risky] [--global]");
+                    return Ok(());
+                }
+            },
+        }
+    }
+
+    let db = get_database().await?;
+    let Some(project) = scope_filter(&db, &scope).await? else {
+        return Ok(());
+    };
+    let redactor = Redactor::new();
+
+    // Entries kept on an earlier sweep don't come back until their text changes
+    let kept: HashSet<(i64, String)> = db
+        .get_preferences_with_prefix("sweep.kept:")
+        .await?
+        .into_iter()
+        .filter_map(|p| Some((p.key.strip_prefix("sweep.kept:")?.parse().ok()?, p.value)))
+        .collect();
+    let fetch = if risky { i64::MAX } else { limit as i64 };
+    let mut entries: Vec<_> = db
+        .get_recent_commands(project.as_deref(), fetch)
+        .await?
+        .into_iter()
+        .filter(|c| !kept.contains(&(c.id, c.command.clone())))
+        .map(|c| {
+            let risk = sweep::assess(&c.command, &redactor);
+            (c, risk)
+        })
+        .collect();
+    if risky {
+        entries.retain(|(_, risk)| risk.score > 0);
+        // Stable, so equally risky entries stay newest first
+        entries.sort_by_key(|(_, risk)| std::cmp::Reverse(risk.score));
+        entries.truncate(limit);
+    }
+
+    if entries.is_empty() {
+        println!("Nothing to review.");
+        return Ok(());
+    }
+
+    let total = entries.len();
+    let stdin = std::io::stdin();
+    let (mut reviewed, mut redacted, mut deleted) = (0, 0, 0);
+    let mut changed: Vec<String> = Vec::new();
+
+    'entries: for (n, (cmd, risk)) in entries.iter().enumerate() {
+        eprintln!("\n[{}/{}] {}", n + 1, total, cmd.command);
+        if !risk.reasons.is_empty() {
+            eprintln!("  {} {}", ui::warn(), risk.reasons.join(", "));
+        }
+        let masked = redactor.redact(&cmd.command);
+        if masked != cmd.command {
+            eprintln!("  redacted: {}", masked);
+        }
+
+        loop {
+            eprint!("[k]eep, [r]edact, [e]dit, [d]elete, [q]uit? ");
+            let mut answer = String::new();
+            // End of input stops the sweep, leaving the rest as they are
+            if stdin.read_line(&mut answer)? == 0 {
+                eprintln!();
+                break 'entries;
+            }
+
+            match answer.trim().to_lowercase().as_str() {
+                "k" | "keep" => {
+                    db.set_preference(format!("sweep.kept:{}", cmd.id), cmd.command.clone())
+                        .await?;
+                    break;
+                }
+                "r" | "redact" if masked == cmd.command => {
+                    eprintln!("  Nothing here looks like a secret, try [e]dit or [d]elete");
+                }
+                "r" | "redact" => {
+                    db.rewrite_command(cmd.id, &masked).await?;
+                    redacted += 1;
+                    changed.push(cmd.command.clone());
+                    break;
+                }
+                "e" | "edit" => {
+                    eprint!("Command: ");
+                    let mut edited = String::new();
+                    stdin.read_line(&mut edited)?;
+                    let edited = edited.trim();
+                    if !edited.is_empty() && edited != cmd.command {
+                        db.rewrite_command(cmd.id, edited).await?;
+                        redacted += 1;
+                        changed.push(cmd.command.clone());
+                        break;
+                    }
+                }
+                "d" | "delete" => {
+                    db.delete_command(cmd.id).await?;
+                    deleted += 1;
+                    changed.push(cmd.command.clone());
+                    break;
+                }
+                "q" | "quit" => break 'entries,
+                _ => {}
+            }
+        }
+        reviewed += 1;
+    }
+
+    for command in &changed {
+        forget_in_prompt(command);
+    }
+    let derived = if changed.is_empty() {
+        0
+    } else {
+        db.forget_derived(|c| changed.iter().any(|gone| gone == c)).await?
+    };
+
+    println!(
+        "\n{} Reviewed {} of {}: kept {}, rewrote {}, deleted {}",
+        ui::ok(),
+        reviewed,
+        total,
+        reviewed - redacted - deleted,
+        redacted,
+        deleted
+    );
+    if derived > 0 {
+        println!(
+            "  Dropped {} patterns/suggestions built from them (analyze rebuilds them)",
+            derived
+        );
+    }
+
+    Ok(())
+}
+
+// History filed under project paths that are gone, and moving it to a
+// project that's still there
+async fn handle_orphans(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall orphans [--list]\n       \
+                 berri-recall orphans <path> --to <project>";
+    let mut list_only = false;
+    let mut from: Option<String> = None;
+    let mut to: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--list" => list_only = true,
+            "--to" => {
+                i += 1;
+                to = args.get(i).cloned();
+                if to.is_none() {
+                    eprintln!("{}", usage);
+                    return Ok(());
+                }
+            }
+            path if from.is_none() && !path.starts_with('-') => from = Some(path.to_string()),
+            _ => {
+                eprintln!("{}", usage);
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+    if from.is_some() != to.is_some() || (list_only && from.is_some()) {
+        eprintln!("{}", usage);
+        return Ok(());
+    }
+
+    let db = get_database().await?;
+    let activity = db.get_project_activity().await?;
+
+    // Straight to it, for scripts
+    if let (Some(from), Some(to)) = (from, to) {
+        if !activity.iter().any(|(path, _, _)| *path == from) {
+            eprintln!("Nothing is recorded under '{}'", from);
+            return Ok(());
+        }
+        let Some(to) = resolve_project(&db, &to).await? else {
+            return Ok(());
+        };
+        let moved = db.reassign_project(&from, &to).await?;
+        println!("{} Moved {} commands from {} to {}", ui::ok(), moved, from, to);
+        return Ok(());
+    }
+
+    let (lost, live): (Vec<_>, Vec<_>) =
+        activity.into_iter().partition(|(path, _, _)| orphans::is_orphan(path));
+    let live: Vec<String> = live.into_iter().map(|(path, _, _)| path).collect();
+    if lost.is_empty() {
+        println!("Every recorded project is still on disk.");
+        return Ok(());
+    }
+
+    let homes: Vec<Option<&str>> =
+        lost.iter().map(|(path, _, _)| orphans::likely_home(path, &live)).collect();
+    println!("\nRecorded under paths that are gone ({}):\n", lost.len());
+    for ((path, commands, last), home) in lost.iter().zip(&homes) {
+        let last = parse_timestamp(last)
+            .map(|at| at.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| last.clone());
+        println!("  {}  {} commands, last {}", path, commands, last);
+        if let Some(home) = home {
+            println!("    {} {}?", ui::glyph(Glyph::Arrow), home);
+        }
+    }
+
+    let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    if list_only || !interactive {
+        println!("\nMove one with `berri-recall orphans <path> --to <project>`");
+        return Ok(());
+    }
+
+    let stdin = std::io::stdin();
+    let total = lost.len();
+    let mut moved = 0;
+
+    'lost: for (n, ((path, commands, _), home)) in lost.iter().zip(&homes).enumerate() {
+        eprintln!("\n[{}/{}] {} ({} commands)", n + 1, total, path, commands);
+
+        loop {
+            match home {
+                Some(home) => eprint!("[m]ove to {}, [o]ther project, [s]kip, [q]uit? ", home),
+                None => eprint!("[o]ther project, [s]kip, [q]uit? "),
+            }
+            let mut answer = String::new();
+            // End of input stops here, leaving the rest where they are
+            if stdin.read_line(&mut answer)? == 0 {
+                eprintln!();
+                break 'lost;
+            }
+
+            let target = match answer.trim().to_lowercase().as_str() {
+                "m" | "move" if home.is_some() => home.map(str::to_string),
+                "o" | "other" => {
+                    eprint!("Project (name or path): ");
+                    let mut project = String::new();
+                    stdin.read_line(&mut project)?;
+                    match project.trim() {
+                        "" => continue,
+                        project => match resolve_project(&db, project).await? {
+                            Some(target) => Some(target),
+                            None => continue,
+                        },
+                    }
+                }
+                "" | "s" | "skip" => None,
+                "q" | "quit" => break 'lost,
+                _ => continue,
+            };
+
+            if let Some(target) = target {
+                let count = db.reassign_project(path, &target).await?;
+                eprintln!("  {} Moved {} commands to {}", ui::ok(), count, target);
+                moved += count;
+            }
+            break;
+        }
+    }
+
+    println!("\n{} Moved {} commands", ui::ok(), moved);
+    Ok(())
+}
+
+// Fold another machine's database into this one
+async fn handle_merge(args: &[String]) -> Result<()> {
+    let Some(other) = args.first() else {
+        eprintln!("Usage: berri-recall merge <other.db>");
+        return Ok(());
+    };
+    let other = std::path::PathBuf::from(other);
+
+    let db = get_database().await?;
+    let same_file = match (other.canonicalize(), db.path().canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    };
+    if same_file {
+        eprintln!("That's the database berri-recall is already using.");
+        return Ok(());
+    }
+
+    let Some(_lock) = maintenance_lock(db.path(), "merging another database") else {
+        return Ok(());
+    };
+    let stats = match db.merge_from(&other).await {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Ok(());
+        }
+    };
+
+    println!("{} Merged {}", ui::ok(), other.display());
+    println!(
+        "  {} new commands, {} already here (usage counts added up)",
+        stats.commands_added, stats.commands_merged
+    );
+    println!("  {} runs copied", stats.executions_added);
+    if stats.executions_skipped > 0 {
+        println!("  {} runs were already here and skipped", stats.executions_skipped);
+    }
+    if stats.aliases_added > 0 {
+        println!("  {} new aliases", stats.aliases_added);
+    }
+    if stats.edits_added > 0 {
+        println!("  {} favorites, tags and deletes replayed", stats.edits_added);
+    }
+    println!("Run `berri-recall analyze --all` to refresh patterns with the combined history.");
+
+    Ok(())
+}
+
+// Globs for commands that never get recorded
+async fn handle_ignore(args: &[String]) -> Result<()> {
+    let db = get_database().await?;
+    let usage = "Usage: berri-recall ignore add <glob>\n       berri-recall ignore list\n       berri-recall ignore rm <glob>";
+
+    match args.first().map(|s| s.as_str()) {
+        Some("add") => {
+            let glob = args[1..].join(" ");
+            if glob.is_empty() {
+                eprintln!("{}", usage);
+                return Ok(());
+            }
+            if let Err(e) = IgnoreRules::add(&db, &glob).await {
+                eprintln!("{}", e);
+                return Ok(());
+            }
+            println!("{} Commands matching '{}' won't be recorded", ui::ok(), glob);
+        }
+        Some("rm") | Some("remove") => {
+            let glob = args[1..].join(" ");
+            if glob.is_empty() {
+                eprintln!("{}", usage);
+                return Ok(());
+            }
+            if IgnoreRules::remove(&db, &glob).await? {
+                println!("{} Recording commands matching '{}' again", ui::ok(), glob);
+            } else {
+                eprintln!("No ignore rule for '{}'", glob);
+            }
+        }
+        Some("list") | None => {
+            let rules = IgnoreRules::load(&db).await?;
+            let globs: Vec<&str> = rules.globs().collect();
+            if globs.is_empty() {
+                println!("No ignore rules. Add one with: berri-recall ignore add 'man *'");
+                return Ok(());
+            }
+            println!("\nNever recorded:");
+            for glob in globs {
+                println!("  {}", glob);
+            }
+        }
+        Some(other) => {
+            eprintln!("Unknown ignore command: {}", other);
+            eprintln!("{}", usage);
+        }
+    }
+
+    Ok(())
+}
+
+// Settings: config.toml beats `config set`, an environment variable beats both
+async fn handle_config(args: &[String]) -> Result<()> {
+    let db = get_database().await?;
+    let config = Config::load(&db).await?;
+    let usage = "Usage: berri-recall config list\n       berri-recall config get <key>\n       berri-recall config set <key> <value>\n       berri-recall config unset <key>";
+
+    let result = match (args.first().map(|s| s.as_str()), &args[args.len().min(1)..]) {
+        (Some("list") | None, []) => {
+            println!("\nSettings (highest wins: environment, config.toml, config set, default):");
+            for setting in config.list() {
+                println!(
+                    "  {:<22} {:<7} {:<12} {}",
+                    setting.key.name, setting.value, setting.source, setting.key.description
+                );
+            }
+            if let Some(file) = &config.file {
+                let state = if file.exists() { "" } else { " (not created)" };
+                println!("\nFile: {}{}", file.display(), state);
+            }
+            Ok(())
+        }
+        (Some("get"), [key]) => config.get(key).map(|setting| println!("{}", setting.value)),
+        (Some("set"), [key, value]) => config
+            .set(&db, key, value)
+            .await
+            .map(|value| println!("{} {} = {}", ui::ok(), key, value)),
+        (Some("unset"), [key]) => config.unset(&db, key).await.map(|was_set| {
+            let default = config.get(key).map(|s| s.key.default).unwrap_or_default();
+            if was_set {
+                println!("{} {} is back to its default ({})", ui::ok(), key, default);
+            } else {
+                println!("{} was already the default ({})", key, default);
+            }
+        }),
+        _ => {
+            eprintln!("{}", usage);
+            Ok(())
+        }
+    };
+
+    match result {
+        Ok(()) if matches!(args.first().map(|s| s.as_str()), Some("set" | "unset")) => {
+            if let Ok(config) = Config::load(&db).await {
+                sync_markers(&config);
+            }
+        }
+        Ok(()) => {}
+        Err(e) => eprintln!("{}", e),
+    }
+    for warning in &config.warnings {
+        eprintln!("{} {}", ui::warn(), warning);
+    }
+    Ok(())
+}
+
+// The files hooks check before starting us, in step with the settings
+fn sync_markers(config: &Config) {
+    if READ_ONLY.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Some(dir) = Markers::default_dir() {
+        let markers = Markers::new(dir);
+        let _ = markers.set_summary(config.summary_after_seconds() > 0);
+        let _ = markers.set_hello(config.hello());
+    }
+}
+
+// Where `optimize --fish-abbr` keeps its abbreviations: fish loads conf.d
+// on its own, so config.fish is never touched
+#[cfg(feature = "intelligence")]
+fn fish_abbr_path() -> Result<std::path::PathBuf> {
+    let config = berri_recall_lib::shell::Shell::Fish.rc_file_path()?;
+    let dir = config
+        .parent()
+        .map(|dir| dir.to_path_buf())
+        .unwrap_or_default();
+    Ok(dir.join("conf.d").join("berri-recall-abbr.fish"))
+}
+
+// Shortenings for long commands you keep typing
+#[cfg(feature = "intelligence")]
+async fn handle_optimize(args: &[String]) -> Result<()> {
+    let usage = "Usage: berri-recall optimize [--fish-abbr | --remove]";
+
+    match args.first().map(|s| s.as_str()) {
+        Some("--remove") => {
+            let path = fish_abbr_path()?;
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+                println!("{} Removed {}", ui::ok(), path.display());
+                println!("  Open a new fish (abbreviations already loaded stay until then)");
+            } else {
+                println!("No abbreviations installed");
+            }
+            return Ok(());
+        }
+        Some("--fish-abbr") | None => {}
+        Some(other) => {
+            eprintln!("Unknown option: {}", other);
+            eprintln!("{}", usage);
+            return Ok(());
+        }
+    }
+
+    let db = get_database().await?;
+    let aliases = db.get_aliases(None).await?;
+    let taken: HashSet<String> = aliases.iter().map(|a| a.alias.clone()).collect();
+    let commands = db.get_most_used_commands(None, 500).await?;
+
+    // An abbreviation named like a command would hide it, so ask fish once
+    // about every name that could come up
+    let yours = shortenings::from_aliases(&aliases);
+    let mut names: Vec<String> = yours.iter().map(|s| s.name.clone()).collect();
+    names.extend(shortenings::candidate_names(&commands));
+    let in_fish = shortenings::known_to_fish(&names);
+    let shadows = |name: &str| shortenings::on_path(name) || in_fish.contains(name);
+
+    // Your aliases keep their names: one that would shadow something is left out
+    let (mut all, shadowing): (Vec<_>, Vec<_>) =
+        yours.into_iter().partition(|s| !shadows(&s.name));
+    all.extend(shortenings::learn(&commands, &taken, shadows));
+    for alias in &shadowing {
+        eprintln!(
+            "{} Left out your alias '{}': there's already a command by that name",
+            ui::warn(),
+            alias.name
+        );
+    }
+
+    if all.is_empty() {
+        println!(
+            "Nothing to shorten yet: a command needs {}+ runs and {}+ characters",
+            shortenings::MIN_USES,
+            shortenings::MIN_LENGTH
+        );
+        return Ok(());
+    }
+
+    if args.is_empty() {
+        println!("\nShortenings:");
+        for shortening in &all {
+            let uses = match shortening.uses {
+                0 => "your alias".to_string(),
+                n => format!("{} runs", n),
+            };
+            println!("  {:<8} {}  ({})", shortening.name, shortening.command, uses);
+        }
+        println!("\nAs fish abbreviations: berri-recall optimize --fish-abbr");
+        return Ok(());
+    }
+
+    let path = fish_abbr_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, shortenings::to_fish(&all))?;
+    println!("{} {} abbreviations written to {}", ui::ok(), all.len(), path.display());
+    println!("  New fish shells pick them up, or run: source {}", path.display());
+
+    Ok(())
+}
+
+// Everything run in a time window, for incident reviews
+async fn handle_timeline(args: &[String]) -> Result<()> {
+    let mut from_arg: Option<String> = None;
+    let mut to_arg: Option<String> = None;
+    let mut all_projects = false;
+    let mut format = TimelineFormat::Text;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--from" => {
+                i += 1;
+                from_arg = args.get(i).cloned();
+            }
+            "--to" => {
+                i += 1;
+                to_arg = args.get(i).cloned();
+            }
+            "--all-projects" | "--global" => all_projects = true,
+            "--format" => {
+                i += 1;
+                match args.get(i).map(|f| f.parse::<TimelineFormat>()) {
+                    Some(Ok(f)) => format = f,
+                    Some(Err(e)) => {
+                        eprintln!("{}", e);
+                        return Ok(());
+                    }
+                    None => {
+                        eprintln!("Error: --format needs text, md or json");
+                        return Ok(());
+                    }
+                }
+            }
+            other => {
+                eprintln!("Unknown option: {}", other);
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+
+    let Some(from_arg) = from_arg else {
+        eprintln!(
+            "Usage: berri-recall timeline --from <time> [--to <time>] [--all-projects] \
+             [--format text|md|json]"
+        );
+        eprintln!("Times are local: \"2024-05-02 14:00\", \"2024-05-02\" or \"14:00\" (today)");
+        return Ok(());
+    };
+
+    let today = chrono::Local::now().date_naive();
+    let Some(from) = timeline::parse_local_time(&from_arg, today, false) else {
+        eprintln!("Can't read --from '{}' (try \"2024-05-02 14:00\")", from_arg);
+        return Ok(());
+    };
+    // A bare time in --to means the same day as --from
+    let from_date = from.with_timezone(&chrono::Local).date_naive();
+    let to = match &to_arg {
+        Some(arg) => match timeline::parse_local_time(arg, from_date, true) {
+            Some(to) => to,
+            None => {
+                eprintln!("Can't read --to '{}' (try \"16:00\")", arg);
+                return Ok(());
+            }
+        },
+        None => chrono::Utc::now(),
+    };
+    if to < from {
+        eprintln!("--to is before --from");
+        return Ok(());
+    }
+
+    let db = Arc::new(get_database().await?);
+    let project_path = if all_projects {
+        None
+    } else {
+        let cwd = env::current_dir()?;
+        ProjectDetector::detect(&cwd)
+            .ok()
+            .and_then(|p| p.to_str().map(String::from))
+    };
+
+    let detector = AnomalyDetector::new(Arc::clone(&db));
+    let entries: Vec<TimelineEntry> = db
+        .get_executions_in_range(project_path.as_deref(), from, to)
+        .await?
+        .into_iter()
+        .map(|execution| {
+            let mut notes = Vec::new();
+            if matches!(execution.exit_code, Some(code) if code != 0) {
+                notes.push("failed".to_string());
+            }
+            if let Some(reason) = detector.destructive_reason(&execution.command) {
+                notes.push(format!("destructive: {}", reason));
+            }
+            TimelineEntry { execution, notes }
+        })
+        .collect();
+
+    print!("{}", timeline::render(format, from, to, &entries));
+    if format == TimelineFormat::Json {
+        println!();
+    }
+
+    Ok(())
+}
+
+// What a project has to offer before you've run anything in it. The hooks
+// call it with --once when the directory changes.
+async fn handle_hello(args: &[String]) -> Result<()> {
+    let mut cwd: Option<String> = None;
+    let mut once = false;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--once" => once = true,
+            "--cwd" => {
+                i += 1;
+                cwd = args.get(i).cloned();
+            }
+            other => {
+                eprintln!("Unknown option: {}", other);
+                eprintln!("Usage: berri-recall hello [--cwd <dir>] [--once]");
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+
+    // The hook's spelling of the directory, for the file that stops it asking again
+    let hook_cwd = cwd.clone();
+    let cwd = match cwd {
+        Some(path) => std::path::PathBuf::from(path),
+        None => env::current_dir()?,
+    };
+    let project_root = ProjectDetector::detect(&cwd)?;
+    let project = project_root.to_string_lossy().to_string();
+    let db = get_database().await?;
+
+    // From the hook: only a project you haven't run anything in, only once
+    let greeted_key = format!("hello.greeted:{}", project);
+    if once {
+        let config = Config::load(&db).await?;
+        if !config.hello() {
+            sync_markers(&config);
+            return Ok(());
+        }
+        // Whatever happens below, there's nothing to say here next time
+        if let (Some(dir), Some(hook_cwd), false) = (
+            Markers::default_dir(),
+            &hook_cwd,
+            READ_ONLY.load(Ordering::Relaxed),
+        ) {
+            let _ = Markers::new(dir).mark_greeted(hook_cwd);
+        }
+        if db.count_executions(Some(&project)).await? > 0
+            || db.get_preference(&greeted_key).await?.is_some()
+        {
+            return Ok(());
+        }
+        db.set_preference(greeted_key, chrono::Utc::now().to_rfc3339()).await?;
+    }
+
+    let kinds = ProjectDetector::kinds(&project_root);
+    let tasks = tasks::detect(&project_root);
+
+    // What you run in your other projects of the same kind
+    let mut borrowed: Vec<(String, usize, i64)> = Vec::new();
+    if !kinds.is_empty() {
+        for other in db.get_project_paths().await? {
+            if other == project
+                || !ProjectDetector::kinds(&other)
+                    .iter()
+                    .any(|kind| kinds.contains(kind))
+            {
+                continue;
+            }
+            for command in db.get_most_used_commands(Some(&other), 20).await? {
+                if command.command.starts_with("cd ") {
+                    continue;
+                }
+                match borrowed.iter_mut().find(|(c, _, _)| *c == command.command) {
+                    Some((_, projects, uses)) => {
+                        *projects += 1;
+                        *uses += command.usage_count as i64;
+                    }
+                    None => borrowed.push((command.command, 1, command.usage_count as i64)),
+                }
+            }
+        }
+        borrowed.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)));
+        borrowed.truncate(5);
+    }
+
+    if kinds.is_empty() && tasks.is_empty() && borrowed.is_empty() {
+        if !once {
+            println!("Nothing to say about {} yet", project);
+        }
+        return Ok(());
+    }
+
+    let name = ProjectDetector::get_project_name(&project_root).unwrap_or(project);
+    if kinds.is_empty() {
+        println!("{}{}", ui::emoji("👋"), name);
+    } else {
+        println!("{}{} ({})", ui::emoji("👋"), name, kinds.join(", "));
+    }
+    if !tasks.is_empty() {
+        let commands: Vec<&str> = tasks.iter().take(8).map(|t| t.command.as_str()).collect();
+        let more = tasks.len().saturating_sub(commands.len());
+        println!(
+            "   Tasks: {}{}",
+            commands.join(", "),
+            if more > 0 { format!(" (+{} more)", more) } else { String::new() }
+        );
+    }
+    if !borrowed.is_empty() {
+        let commands: Vec<&str> = borrowed.iter().map(|(c, _, _)| c.as_str()).collect();
+        println!(
+            "   In your other {} projects: {}",
+            kinds.join("/"),
+            commands.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+async fn handle_top(args: &[String]) -> Result<()> {
+    let mut limit = 10;
+    let mut global = false;
+    let mut tool = None;
+    let mut porcelain = false;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--global" => global = true,
+            "--project" => global = false,
+            "--porcelain" => porcelain = true,
+            "--tool" => {
+                i += 1;
+                match args.get(i) {
+                    Some(t) => tool = Some(t.clone()),
+                    None => {
+                        eprintln!("Error: --tool needs a value (e.g. --tool git)");
+                        return Ok(());
+                    }
+                }
+            }
+            other => match other.parse::<i64>() {
+                Ok(n) => limit = n,
+                Err(_) => {
+                    eprintln!("Unknown option: {}", other);
+                    eprintln!(
+                        "Usage: berri-recall top [N] [--project|--global] [--tool <name>] \
+                         [--porcelain]"
+                    );
+                    return Ok(());
+                }
+            },
+        }
+        i += 1;
+    }
+
+    let db = get_database().await?;
+    let project_root = if global {
+        None
+    } else {
+        ProjectDetector::detect(&env::current_dir()?).ok()
+    };
+
+    let stats = db
+        .get_command_stats(
+            project_root.as_ref().and_then(|p| p.to_str()),
+            tool.as_deref(),
+            limit,
+        )
+        .await?;
+
+    // uses, runs, successes, failures, average ms, project, command
+    if porcelain {
+        for s in &stats {
+            println!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                s.usage_count,
+                s.runs,
+                s.successes,
+                s.failures,
+                s.avg_execution_time_ms
+                    .map(|ms| format!("{:.0}", ms))
+                    .unwrap_or_default(),
+                listing::field(&s.project_path),
+                listing::field(&s.command)
+            );
+        }
+        return Ok(());
+    }
+
+    if stats.is_empty() {
+        println!("No commands found.");
+        return Ok(());
+    }
+
+    println!("\nMost used commands{}:", if global { " (all projects)" } else { "" });
+    println!("{}", "=".repeat(60));
+    println!("{:>3}  {:>5}  {:>7}  {:>8}  Command", "#", "Uses", "Success", "Avg time");
+    for (i, s) in stats.iter().enumerate() {
+        let success = s
+            .success_rate()
+            .map(|r| format!("{:.0}%", r * 100.0))
+            .unwrap_or_else(|| "-".to_string());
+        let avg = s
+            .avg_execution_time_ms
+            .map(|ms| format_duration(ms as u64))
+            .unwrap_or_else(|| "-".to_string());
+
+        let project = if global {
+            format!(
+                "  ({})",
+                s.project_path.rsplit('/').next().unwrap_or(&s.project_path)
+            )
+        } else {
+            String::new()
+        };
+
+        println!(
+            "{:>3}. {:>5}  {:>7}  {:>8}  {}{}",
+            i + 1,
+            s.usage_count,
+            success,
+            avg,
+            s.command,
+            project
+        );
+    }
+    println!("{}", "=".repeat(60));
+
+    Ok(())
+}
+
+#[cfg(feature = "intelligence")]
+async fn handle_flaky(args: &[String]) -> Result<()> {
+    let mut min_runs = reliability::DEFAULT_MIN_RUNS;
+
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--min-runs" {
+            i += 1;
+            match args.get(i).and_then(|n| n.parse::<i64>().ok()) {
+                Some(n) => min_runs = n,
+                None => {
+                    eprintln!("Error: --min-runs needs a number");
+                    return Ok(());
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let db = Arc::new(get_database().await?);
+    let cwd = env::current_dir()?;
+    let project_root = ProjectDetector::detect(&cwd).ok();
+
+    let analyzer = ReliabilityAnalyzer::new(db);
+    let flaky = analyzer
+        .flaky_commands(project_root.as_ref().and_then(|p| p.to_str()), min_runs)
+        .await?;
+
+    if flaky.is_empty() {
+        println!("No flaky commands here. Everything either passes or fails consistently.");
+        return Ok(());
+    }
+
+    println!("\nFlaky commands:");
+    println!("{}", "=".repeat(60));
+    println!("{:>3}  {:>6}  {:>8}  Command", "#", "Runs", "Failures");
+    for (i, f) in flaky.iter().enumerate() {
+        println!(
+            "{:>3}. {:>5}  {:>7.0}%  {}",
+            i + 1,
+            f.stats.successes + f.stats.failures,
+            f.failure_rate * 100.0,
+            f.stats.command
+        );
+    }
+    println!("{}", "=".repeat(60));
+
+    Ok(())
+}
+
+#[cfg(feature = "intelligence")]
+async fn handle_perf(args: &[String]) -> Result<()> {
+    if args.is_empty() {
+        eprintln!("Usage: berri-recall perf <command>");
+        return Ok(());
+    }
+
+    let db = Arc::new(get_database().await?);
+    let cwd = env::current_dir()?;
+    let project_root = ProjectDetector::detect(&cwd).ok();
+
+    let analyzer = PerfAnalyzer::new(db);
+    let report = analyzer
+        .report(&args.join(" "), project_root.as_ref().and_then(|p| p.to_str()))
+        .await?;
+
+    if report.runs == 0 {
+        println!("No timed runs of '{}' in this project yet.", report.command);
+        return Ok(());
+    }
+
+    println!("\nDuration of '{}' ({} timed runs):", report.command, report.runs);
+    println!("{}", "=".repeat(60));
+    println!(
+        "  Last {} weeks: {}  (oldest {} this week)",
+        intelligence::performance::WEEKS_SHOWN,
+        report.sparkline(),
+        ui::glyph(Glyph::Arrow)
+    );
+
+    if let (Some(base), Some(recent)) = (report.baseline_ms, report.recent_ms) {
+        println!("  Typical:      {}", format_duration(base as u64));
+        println!("  Lately:       {}", format_duration(recent as u64));
+    }
+
+    if report.is_regression() {
+        println!(
+            "\n  {} Got {:.0}% slower than it used to be.",
+            ui::warn(),
+            (report.slowdown().unwrap_or(1.0) - 1.0) * 100.0
+        );
+    }
+    println!("{}", "=".repeat(60));
+
+    Ok(())
+}
+
+// How long a command will probably take. Hooks call this in preexec when
+// BERRI_RECALL_ETA is set. Exit 1 when there's no estimate (or it's too quick to bother).
+#[cfg(feature = "intelligence")]
+async fn handle_eta(args: &[String]) -> Result<()> {
+    let mut command_parts = Vec::new();
+    let mut cwd_override: Option<String> = None;
+    let mut min_seconds: i64 = 0;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--command" => {
+                i += 1;
+                if i < args.len() {
+                    command_parts.push(args[i].clone());
+                }
+            }
+            "--cwd" => {
+                i += 1;
+                if i < args.len() {
+                    cwd_override = Some(args[i].clone());
+                }
+            }
+            "--min-seconds" => {
+                i += 1;
+                min_seconds = args.get(i).and_then(|s| s.parse().ok()).unwrap_or(0);
+            }
+            arg => command_parts.push(arg.to_string()),
+        }
+        i += 1;
+    }
+
+    let command = command_parts.join(" ");
+    if command.trim().is_empty() {
+        eprintln!("Usage: berri-recall eta <command> [--min-seconds N]");
+        std::process::exit(1);
+    }
+
+    let cwd = match cwd_override {
+        Some(path) => std::path::PathBuf::from(path),
+        None => env::current_dir()?,
+    };
+    let project_root = ProjectDetector::detect(&cwd).ok();
+
+    // This project's runs first, then anywhere
+    let analyzer = PerfAnalyzer::new(Arc::new(get_database().await?));
+    let mut report = analyzer
+        .report(command.trim(), project_root.as_ref().and_then(|p| p.to_str()))
+        .await?;
+    if report.runs == 0 && project_root.is_some() {
+        report = analyzer.report(command.trim(), None).await?;
+    }
+
+    let quick = report
+        .typical_ms
+        .is_none_or(|ms| ms < (min_seconds * 1000) as f64);
+    match report.eta() {
+        Some(eta) if !quick => {
+            println!("{}", eta);
+            Ok(())
+        }
+        _ => std::process::exit(1),
+    }
+}
+
+// One line after a long command finishes: how it compares to the usual run.
+// Hooks call it before recording, so the median is of the runs before this one.
+#[cfg(feature = "intelligence")]
+async fn handle_finished(args: &[String]) -> Result<()> {
+    let mut command = String::new();
+    let mut cwd_override: Option<String> = None;
+    let mut duration_ms: Option<i32> = None;
+    let mut exit_code: Option<i32> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--command" => {
+                i += 1;
+                command = args.get(i).cloned().unwrap_or_default();
+            }
+            // PowerShell can't pass quotes to a native program reliably
+            "--command-base64" => {
+                i += 1;
+                command = args.get(i).and_then(|s| decode_base64(s)).unwrap_or_default();
+            }
+            "--cwd" => {
+                i += 1;
+                cwd_override = args.get(i).cloned();
+            }
+            "--duration" => {
+                i += 1;
+                duration_ms = args.get(i).and_then(|s| s.parse().ok());
+            }
+            "--exit-code" => {
+                i += 1;
+                exit_code = args.get(i).and_then(|s| s.parse().ok());
+            }
+            other => {
+                eprintln!("Unknown option: {}", other);
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+    let (Some(took_ms), false) = (duration_ms, command.trim().is_empty()) else {
+        eprintln!("Usage: berri-recall finished --command <cmd> --duration <ms> [--exit-code N]");
+        return Ok(());
+    };
+
+    let db = Arc::new(get_database().await?);
+    let config = Config::load(&db).await?;
+    // A hook that got here on a stale marker won't next time
+    sync_markers(&config);
+    let seconds = config.summary_after_seconds();
+    if seconds <= 0 || i64::from(took_ms) < seconds * 1000 {
+        return Ok(());
+    }
+
+    let cwd = match cwd_override {
+        Some(path) => std::path::PathBuf::from(path),
+        None => env::current_dir()?,
+    };
+    let project = ProjectDetector::detect(&cwd)
+        .ok()
+        .and_then(|p| p.to_str().map(String::from));
+    let report = PerfAnalyzer::new(db)
+        .report(command.trim(), project.as_deref())
+        .await?;
+    println!("{}", report.completion_line(took_ms, exit_code));
+
+    Ok(())
+}
+
+// Called by the prompt on every redraw: reads the cache file only, never the
+// database, so it stays well inside a 5ms budget
+fn handle_prompt_segment(args: &[String]) -> Result<()> {
+    let mut format = SegmentFormat::Top;
+    let mut cwd_override: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--init" => {
+                let framework = args.get(i + 1).map(|s| s.as_str()).unwrap_or("");
+                match prompt::init_snippet(framework) {
+                    Some(snippet) => print!("{}", snippet),
+                    None => eprintln!("Usage: berri-recall prompt-segment --init <starship|p10k>"),
+                }
+                return Ok(());
+            }
+            "--format" => {
+                i += 1;
+                match args.get(i).map(|f| f.parse::<SegmentFormat>()) {
+                    Some(Ok(f)) => format = f,
+                    Some(Err(e)) => {
+                        eprintln!("{}", e);
+                        return Ok(());
+                    }
+                    None => {
+                        eprintln!("Error: --format needs top or count");
+                        return Ok(());
+                    }
+                }
+            }
+            "--cwd" => {
+                i += 1;
+                cwd_override = args.get(i).cloned();
+            }
+            other => {
+                eprintln!("Unknown option: {}", other);
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+
+    let cwd = match cwd_override {
+        Some(path) => std::path::PathBuf::from(path),
+        None => env::current_dir()?,
+    };
+    let (Ok(project_root), Some(cache_path)) =
+        (ProjectDetector::detect_cached(&cwd), PromptCache::path())
+    else {
+        return Ok(());
+    };
+
+    let cache = PromptCache::load(&cache_path);
+    if let Some(segment) = project_root
+        .to_str()
+        .and_then(|p| cache.segment(p, format, chrono::Utc::now().timestamp()))
+    {
+        println!("{}", segment);
+    }
+
+    Ok(())
+}
+
+// The hooks run this in the background when the directory changes, so the
+// project's suggestions are in the prompt cache before the next prompt.
+// Projects warmed in the last few minutes are skipped.
+#[cfg(feature = "intelligence")]
+async fn handle_warm(args: &[String]) -> Result<()> {
+    let mut cwd_override: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--cwd" => {
+                i += 1;
+                cwd_override = args.get(i).cloned();
+            }
+            other => {
+                eprintln!("Unknown option: {}", other);
+                eprintln!("Usage: berri-recall warm [--cwd <dir>]");
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+
+    // Suggestions look at the current directory (project type, git branch)
+    if let Some(dir) = cwd_override {
+        env::set_current_dir(dir)?;
+    }
+    let cwd = env::current_dir()?;
+    let (Ok(project_root), Some(cache_path)) = (ProjectDetector::detect(&cwd), PromptCache::path())
+    else {
+        return Ok(());
+    };
+    let project = project_root.to_string_lossy().to_string();
+
+    let now = chrono::Utc::now().timestamp();
+    if PromptCache::load(&cache_path).is_fresh(&project, prompt::WARM_AFTER_MINUTES, now) {
+        return Ok(());
+    }
+
+    let db = Arc::new(get_database().await?);
+    // Nothing recorded here yet, nothing to suggest
+    if db.count_executions(Some(&project)).await? == 0 {
+        return Ok(());
+    }
+    let report = Analyzer::new(db).analyze(Some(&project)).await?;
+    cache_for_prompt(Some(&project), &report.suggestions);
+
+    Ok(())
+}
+
+// Fresh suggestions for prompt-segment. Best effort, the prompt just shows nothing.
+#[cfg(feature = "intelligence")]
+fn cache_for_prompt(project: Option<&str>, suggestions: &[intelligence::SmartSuggestion]) {
+    let (Some(project), Some(path)) = (project, PromptCache::path()) else {
+        return;
+    };
+
+    let mut cache = PromptCache::load(&path);
+    cache.set(
+        project,
+        suggestions.iter().map(|s| s.command.clone()).collect(),
+        chrono::Utc::now().timestamp(),
+    );
+    let _ = cache.save(&path);
+}
+
+// A suggestion that was taken doesn't belong in the prompt anymore
+fn drop_from_prompt(project: &str, command: &str) {
+    let Some(path) = PromptCache::path() else {
+        return;
+    };
+
+    let mut cache = PromptCache::load(&path);
+    if cache.remove(project, command) {
+        let _ = cache.save(&path);
+    }
+}
+
+// Snoozed or suppressed: gone from every project's prompt
+fn forget_in_prompt(command: &str) {
+    let Some(path) = PromptCache::path() else {
+        return;
+    };
+
+    let mut cache = PromptCache::load(&path);
+    let projects: Vec<String> = cache.projects.keys().cloned().collect();
+    let mut changed = false;
+    for project in projects {
+        changed |= cache.remove(&project, command);
+    }
+    if changed {
+        let _ = cache.save(&path);
+    }
+}
+
+async fn handle_search(args: &[String]) -> Result<()> {
+    let (args, copy) = take_copy_flag(args);
+    let Some((args, scope)) = take_scope_flags(&args) else {
+        return Ok(());
+    };
+    let Some((args, layout)) = take_layout_flags(&args) else {
+        return Ok(());
+    };
+    let Some((args, env_filters)) = take_env_filters(&args) else {
+        return Ok(());
+    };
+    let Some((args, branch)) = take_branch_flag(&args) else {
+        return Ok(());
+    };
+    let (args, interactive) = take_origin_flag(&args);
+    let preview = args.iter().any(|arg| arg == "--preview");
+    let privileged = args.iter().any(|arg| arg == "--privileged");
+    let include_archived = args.iter().any(|arg| arg == "--include-archived");
+    let args: Vec<String> = args
+        .into_iter()
+        .filter(|arg| !matches!(arg.as_str(), "--preview" | "--privileged" | "--include-archived"))
+        .collect();
+    let filtered =
+        !env_filters.is_empty() || branch.is_some() || interactive.is_some() || privileged;
+    if args.is_empty() && !filtered {
+        eprintln!("Error: No search query provided");
+        return Ok(());
+    }
+
+    let query = args.join(" ");
+    let db = get_database().await?;
+    let Some(project) = scope_filter(&db, &scope).await? else {
+        return Ok(());
+    };
+
+    let mut results = if !filtered {
+        db.search_commands(&query, project.as_deref(), 20).await?
+    } else {
+        // Narrow down a wider search, the filters can drop most of it
+        let mut results = db.search_commands(&query, project.as_deref(), 1000).await?;
+        for filter in &env_filters {
+            let ids = db
+                .command_ids_with_env(&filter.name, filter.value.as_deref())
+                .await?;
+            results.retain(|c| ids.contains(&c.id));
+        }
+        if let Some(branch) = &branch {
+            let ids = db.command_ids_on_branch(branch).await?;
+            results.retain(|c| ids.contains(&c.id));
+        }
+        if let Some(interactive) = interactive {
+            let ids = db.command_ids_by_interactive(interactive).await?;
+            results.retain(|c| ids.contains(&c.id));
+        }
+        if privileged {
+            let ids = db.privileged_command_ids().await?;
+            results.retain(|c| ids.contains(&c.id));
+        }
+        results.truncate(20);
+        results
+    };
+
+    // Runs `archive` moved out, under the same filters
+    let mut archived = 0;
+    if include_archived && results.len() < 20 {
+        let keep = |run: &cold::ColdRun| {
+            env_filters.iter().all(|f| f.matches(run.env.as_deref()))
+                && branch.as_ref().is_none_or(|b| run.branch.as_ref() == Some(b))
+                && interactive.is_none_or(|i| run.interactive == Some(i))
+                && (!privileged || run.privileged == Some(true))
+        };
+        let dir = cold::cold_dir(db.path());
+        for command in cold::search_cold(&dir, &query, project.as_deref(), keep)? {
+            if results.len() >= 20 {
+                break;
+            }
+            if !results
+                .iter()
+                .any(|c| c.command == command.command && c.project_path == command.project_path)
+            {
+                results.push(command);
+                archived += 1;
+            }
+        }
+    }
+
+    // matching 'deploy' with AWS_PROFILE=prod
+    let mut wanted = if query.is_empty() {
+        "run".to_string()
+    } else {
+        format!("matching '{}'", query)
+    };
+    let env_specs: Vec<String> = env_filters
+        .iter()
+        .map(|f| match &f.value {
+            Some(value) => format!("{}={}", f.name, value),
+            None => format!("{} set", f.name),
+        })
+        .collect();
+    if !env_specs.is_empty() {
+        wanted.push_str(&format!(" with {}", env_specs.join(", ")));
+    }
+    if let Some(branch) = &branch {
+        wanted.push_str(&format!(" on {}", branch));
+    }
+    match interactive {
+        Some(true) => wanted.push_str(" from a prompt"),
+        Some(false) => wanted.push_str(" from scripts"),
+        None => {}
+    }
+    if privileged {
+        wanted.push_str(" with sudo or doas");
+    }
+
+    if results.is_empty() {
+        if !layout.porcelain {
+            println!("No commands found {}", wanted);
+        }
+    } else {
+        if !layout.porcelain {
+            println!("\nFound {} command(s) {}:", results.len(), wanted);
+            if archived > 0 {
+                println!("({} only in the archive)", archived);
+            }
+        }
+        let defaults = [Column::Cmd, Column::Count, Column::Exit, Column::Last];
+        let matches = Matches {
+            query: (!query.is_empty()).then_some(query.as_str()),
+            preview,
+            color: true,
+        };
+        print_table(
+            &mut results,
+            &layout,
+            &defaults,
+            project.is_none(),
+            matches,
+            None,
+        );
+    }
+
+    if let Some(n) = copy {
+        copy_result(results.get(n - 1).map(|c| c.command.as_str()), n);
+    }
+
+    Ok(())
+}
+
+async fn handle_setup(args: &[String]) -> Result<()> {
+    use berri_recall_lib::shell::Shell;
+
+    let installer = HookInstaller::new()?;
+
+    let mut install_all = false;
+    let mut rc_file: Option<std::path::PathBuf> = None;
+    let mut print_snippet = false;
+    let mut inline = false;
+    let mut no_modify_rc = false;
+    let mut shell: Option<Shell> = None;
+    let mut restore_rc: Option<Shell> = None;
+    let mut non_interactive = false;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--all" => install_all = true,
+            "--non-interactive" => non_interactive = true,
+            "--print-snippet" => print_snippet = true,
+            "--inline" => inline = true,
+            "--no-modify-rc" => no_modify_rc = true,
+            flag @ ("--shell" | "--restore-rc") => {
+                i += 1;
+                let parsed = match args.get(i).map(|name| name.parse::<Shell>()) {
+                    Some(Ok(parsed)) => parsed,
+                    Some(Err(e)) => {
+                        eprintln!("Error: {}", e);
+                        return Ok(());
+                    }
+                    None => {
+                        eprintln!("Error: {} needs a shell name", flag);
+                        return Ok(());
+                    }
+                };
+                if flag == "--shell" {
+                    shell = Some(parsed);
+                } else {
+                    restore_rc = Some(parsed);
+                }
+            }
+            "--rc-file" => {
+                i += 1;
+                match args.get(i) {
+                    Some(path) => rc_file = Some(env::current_dir()?.join(path)),
+                    None => {
+                        eprintln!("Error: --rc-file needs a path");
+                        return Ok(());
+                    }
+                }
+            }
+            other => {
+                eprintln!("Unknown option: {}", other);
+                eprintln!(
+                    "Usage: berri-recall setup [--all | --rc-file <path> | --print-snippet \
+                     [--inline] | --no-modify-rc | --restore-rc <shell> | --non-interactive] \
+                     [--shell <name>]"
+                );
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+
+    // For package-manager postinstall scripts: one `hook.<shell><TAB>outcome<TAB>rc file`
+    // line per shell, exit 1 if none of them ended up with the hook
+    if non_interactive {
+        if args.len() > 1 {
+            eprintln!("--non-interactive goes on its own");
+            std::process::exit(2);
+        }
+        let outcomes = installer.install_existing();
+        for (shell, outcome) in &outcomes {
+            let rc_path = installer
+                .rc_file(*shell)
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            match outcome {
+                InstallOutcome::Failed(reason) => {
+                    println!("hook.{}\t{}\t{}\t{}", shell.name(), outcome.as_str(), rc_path, reason)
+                }
+                _ => println!("hook.{}\t{}\t{}", shell.name(), outcome.as_str(), rc_path),
+            }
+        }
+        if !outcomes.iter().any(|(_, outcome)| outcome.has_hook()) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(shell) = restore_rc {
+        if args.len() > 2 {
+            eprintln!("--restore-rc goes on its own");
+            return Ok(());
+        }
+        match installer.restore_rc(shell) {
+            Ok(rc_path) => {
+                println!("{} Restored {} from its last backup", ui::ok(), rc_path.display());
+                println!("   Run it again to go back one more change");
+            }
+            Err(e) => eprintln!("{} {}", ui::fail(), e),
+        }
+        return Ok(());
+    }
+
+    if install_all && rc_file.is_some() {
+        eprintln!("--rc-file is for the shell you're in, it can't go with --all");
+        return Ok(());
+    }
+    if (print_snippet || no_modify_rc) && (install_all || rc_file.is_some()) {
+        eprintln!("--print-snippet and --no-modify-rc leave rc files alone, drop --all/--rc-file");
+        return Ok(());
+    }
+    if inline && !print_snippet {
+        eprintln!("--inline goes with --print-snippet");
+        return Ok(());
+    }
+
+    // Dotfile managers own the rc file: hand over what to put in it instead
+    if print_snippet || no_modify_rc {
+        let shell = match shell {
+            Some(shell) => shell,
+            None => ShellDetector::detect()?,
+        };
+
+        if print_snippet && inline {
+            // The whole hook, nothing written anywhere
+            print!("{}", installer.hook_content(shell));
+            return Ok(());
+        }
+
+        // The snippet sources the hook file, so that has to exist
+        let hook_path = installer.write_hook(shell)?;
+        if print_snippet {
+            print!("{}", installer.snippet(shell));
+            return Ok(());
+        }
+
+        println!("{} Hook written to {}", ui::ok(), hook_path.display());
+        let rc_path = installer.rc_file(shell)?;
+        if installer.is_installed(shell) {
+            println!("{} {} already loads it", ui::ok(), rc_path.display());
+        } else {
+            println!("\nYour rc file was not touched. Add this to {}:\n", rc_path.display());
+            for line in installer.snippet(shell).lines() {
+                println!("   {}", line);
+            }
+        }
+        return Ok(());
+    }
+
+    // What to run so the hook takes effect without a new terminal
+    let reload = |shell: Shell| match shell {
+        Shell::PowerShell => ". $PROFILE".to_string(),
+        _ => match installer.rc_file(shell) {
+            Ok(path) => format!("source {}", path.display()),
+            Err(_) => "exec $SHELL".to_string(),
+        },
+    };
+
+    if install_all {
+        println!("Installing hooks for all detected shells...\n");
+        match installer.install_all() {
+            Ok(shells) => {
+                println!("{} Successfully installed hooks for:", ui::ok());
+                for shell in &shells {
+                    println!("  - {}", shell);
+                }
+                println!("\n{}Setup complete! Restart your shell or run:", ui::emoji("🎉"));
+                for shell in shells {
+                    println!("   {:<40} (for {})", reload(shell), shell);
+                }
+            }
+            Err(e) => {
+                eprintln!("{} Setup failed: {}", ui::fail(), e);
+                return Err(e);
+            }
+        }
+    } else {
+        // Auto-detect and install for current shell
+        println!("Detecting your shell...\n");
+        let detected = match shell {
+            Some(shell) => Ok(shell),
+            None => ShellDetector::detect(),
+        };
+        let installed = detected.and_then(|shell| {
+            match &rc_file {
+                Some(path) => installer.install_to(shell, path)?,
+                None => installer.install(shell)?,
+            }
+            Ok(shell)
+        });
+        match installed {
+            Ok(shell) => {
+                println!("{} Detected shell: {}", ui::ok(), shell);
+                if let Ok(path) = installer.rc_file(shell) {
+                    println!("{} Hook installed, sourced from {}\n", ui::ok(), path.display());
+                }
+                println!("{}Setup complete! Restart your shell or run:", ui::emoji("🎉"));
+                println!("   {}", reload(shell));
+            }
+            Err(e) => {
+                eprintln!("{} Setup failed: {}", ui::fail(), e);
+                eprintln!("\nTry running with --all flag to install for all shells:");
+                eprintln!("   berri-recall setup --all");
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_uninstall(_args: &[String]) -> Result<()> {
+    let installer = HookInstaller::new()?;
+
+    println!("Uninstalling berri-recall hooks...\n");
+
+    use berri_recall_lib::shell::Shell;
+
+    let shells = vec![
+        Shell::Bash,
+        Shell::Zsh,
+        Shell::Fish,
+        Shell::PowerShell,
+    ];
+
+    for shell in shells {
+        match installer.uninstall(shell) {
+            Ok(()) => println!("{} Uninstalled {} hook", ui::ok(), shell),
+            Err(e) => eprintln!("  (skipped {}: {})", shell, e),
+        }
+    }
+
+    println!("\n{} Uninstall complete!", ui::ok());
+    println!("Note: Database (~/.berri-recall/) was not removed.");
+    println!("To remove all data: rm -rf ~/.berri-recall");
+
+    Ok(())
+}
+
+async fn handle_status(args: &[String]) -> Result<()> {
+    use berri_recall_lib::shell::Shell;
+
+    let mut porcelain = false;
+    match args.first().map(String::as_str) {
+        None => {}
+        Some("--latency") => return print_hook_latency(),
+        Some("--porcelain") => porcelain = true,
+        Some(other) => {
+            eprintln!("Unknown option: {}", other);
+            eprintln!("Usage: berri-recall status [--latency|--porcelain]");
+            return Ok(());
+        }
+    }
+
+    let installer = HookInstaller::new()?;
+    let db = get_database().await?;
+    let stats = db.stats().await?;
+    let dropped_counts = db.dropped_counts().await?;
+    let shells = [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell];
+    let spooled = Spool::default_dir().map(|dir| Spool::new(dir).pending().len()).unwrap_or(0);
+
+    // One `key<TAB>value` per line
+    if porcelain {
+        for shell in &shells {
+            let state = if installer.is_installed(*shell) {
+                "installed"
+            } else {
+                "missing"
+            };
+            println!("hook.{}\t{}", shell.name(), state);
+        }
+        println!("commands\t{}", stats.total_commands);
+        println!("patterns\t{}", stats.total_patterns);
+        println!("suggestions\t{}", stats.total_suggestions);
+        println!("privileged\t{}", stats.privileged_commands);
+        for dropped in Dropped::ALL {
+            let count = dropped_counts.get(dropped.as_str()).copied().unwrap_or(0);
+            println!("dropped.{}\t{}", dropped.as_str(), count);
+        }
+        println!("spooled\t{}", spooled);
+        let shell = ShellDetector::detect().map(|s| s.name().to_string());
+        println!("shell\t{}", shell.unwrap_or_default());
+        return Ok(());
+    }
+
+    println!("\nberri-recall Status");
+    println!("{}", "=".repeat(60));
+
+    // Shell hooks status
+    println!("\nShell Hooks:");
+    for shell in &shells {
+        let status = if installer.is_installed(*shell) {
+            format!("{} Installed", ui::ok())
+        } else {
+            format!("{} Not installed", ui::fail())
+        };
+        println!("  {:<12} {}", format!("{}:", shell), status);
+    }
+
+    // Database stats
+    println!("\nDatabase Statistics:");
+    println!("  Commands:    {}", stats.total_commands);
+    println!("  Patterns:    {}", stats.total_patterns);
+    println!("  Suggestions: {}", stats.total_suggestions);
+    if stats.privileged_commands > 0 {
+        println!("  Privileged:  {} (run with sudo or doas)", stats.privileged_commands);
+    }
+    if spooled > 0 {
+        println!("  Spooled:     {} (recorded with the next command)", spooled);
+    }
+
+    // Counted as they're left out, so you can tell if the filters are too eager
+    let dropped: Vec<(Dropped, i64)> = Dropped::ALL
+        .into_iter()
+        .filter_map(|d| dropped_counts.get(d.as_str()).map(|count| (d, *count)))
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    if !dropped.is_empty() {
+        let total: i64 = dropped.iter().map(|(_, count)| count).sum();
+        println!("\nNot Recorded: {} (counted, the commands aren't kept)", total);
+        for (reason, count) in dropped {
+            println!("  {:<16} {}", format!("{}:", reason.label()), count);
+        }
+    }
+
+    // Current shell
+    println!("\nCurrent Shell:");
+    match ShellDetector::detect() {
+        Ok(shell) => println!("  {}", shell),
+        Err(_) => println!("  Unknown"),
+    }
+
+    println!("{}", "=".repeat(60));
+
+    Ok(())
+}
+
+// Time what the hooks cost each prompt, against a scratch database so the
+// probes don't end up in your history
+fn print_hook_latency() -> Result<()> {
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    const SAMPLES: usize = 10;
+
+    let exe = env::current_exe()?;
+    let scratch = env::temp_dir().join(format!("berri-recall-latency-{}", std::process::id()));
+    std::fs::create_dir_all(&scratch)?;
+
+    let probe = |args: &[&str], home: Option<&std::path::Path>| -> Result<(Duration, Duration)> {
+        let mut command = Command::new(&exe);
+        command
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if let Some(home) = home {
+            command.env("HOME", home).env("USERPROFILE", home);
+        }
+
+        let start = Instant::now();
+        let mut child = command.spawn()?;
+        let started = start.elapsed();
+        child.wait()?;
+        Ok((started, start.elapsed()))
+    };
+
+    let cwd = scratch.to_string_lossy().to_string();
+    let record = ["record", "--cwd", cwd.as_str(), "--command", "latency probe"];
+    // The first run creates the database, which a real prompt never pays for
+    probe(&record, Some(&scratch))?;
+
+    let mut spawn_times = Vec::new();
+    let mut record_times = Vec::new();
+    for _ in 0..SAMPLES {
+        let (started, finished) = probe(&record, Some(&scratch))?;
+        spawn_times.push(started);
+        record_times.push(finished);
+    }
+    let _ = std::fs::remove_dir_all(&scratch);
+
+    let summary = |times: &mut Vec<Duration>| {
+        times.sort();
+        format!(
+            "{:>6.1}ms median, {:>6.1}ms worst",
+            times[times.len() / 2].as_secs_f64() * 1000.0,
+            times[times.len() - 1].as_secs_f64() * 1000.0
+        )
+    };
+
+    println!("\nHook latency ({} runs):", SAMPLES);
+    println!(
+        "  Prompt overhead: {}  starting record in the background",
+        summary(&mut spawn_times)
+    );
+    println!(
+        "  Recording:       {}  off the prompt, you don't wait for it",
+        summary(&mut record_times)
+    );
+
+    // Opt-in lookups run before the command, so they do hold things up
+    if env::var_os("BERRI_RECALL_ETA").is_some() {
+        let here = env::current_dir()?.to_string_lossy().to_string();
+        let eta = ["eta", "--min-seconds", "30", "--command", "make", "--cwd", here.as_str()];
+        let mut eta_times = Vec::new();
+        for _ in 0..SAMPLES {
+            eta_times.push(probe(&eta, None)?.1);
+        }
+        println!(
+            "  ETA lookup:      {}  BERRI_RECALL_ETA is on, runs before each command",
+            summary(&mut eta_times)
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "intelligence")]
+async fn handle_analyze(args: &[String]) -> Result<()> {
+    let db = Arc::new(get_database().await?);
+
+    let mut project_arg = None;
+    let mut all = false;
+    let mut output: Option<String> = None;
+    let mut interactive = false;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--all" => all = true,
+            "--interactive" | "-i" => interactive = true,
+            "--output" | "-o" => {
+                i += 1;
+                match args.get(i) {
+                    Some(path) => output = Some(path.clone()),
+                    None => {
+                        eprintln!("Error: --output needs a file (- for stdout)");
+                        return Ok(());
+                    }
+                }
+            }
+            "--project" => {
+                i += 1;
+                match args.get(i) {
+                    Some(p) => project_arg = Some(p.clone()),
+                    None => {
+                        eprintln!("Error: --project needs a name or path");
+                        return Ok(());
+                    }
+                }
+            }
+            other => {
+                eprintln!("Unknown option: {}", other);
+                eprintln!(
+                    "Usage: berri-recall analyze [--project <name|path>] [--all] \
+                     [--interactive] [--output <file.json>]"
+                );
+                return Ok(());
+            }
+        }
+        i += 1;
+    }
+
+    if all {
+        return analyze_all_projects(Arc::clone(&db)).await;
+    }
+
+    let project_path = match &project_arg {
+        Some(arg) => match resolve_project(&db, arg).await? {
+            Some(path) => Some(path),
+            None => return Ok(()),
+        },
+        None => ProjectDetector::detect(env::current_dir()?)
+            .ok()
+            .and_then(|p| p.to_str().map(|s| s.to_string())),
+    };
+
+    let analyzer = Analyzer::new(Arc::clone(&db));
+
+    // JSON on stdout is all that goes there
+    let to_stdout = output.as_deref() == Some("-");
+    if !to_stdout {
+        println!("\n{}Analyzing command patterns...\n", ui::emoji("🔍"));
+    }
+
+    let report = analyzer.analyze(project_path.as_deref()).await?;
+    // Suggestions come from where you're standing, so only cache them for this project
+    if project_arg.is_none() {
+        cache_for_prompt(project_path.as_deref(), &report.suggestions);
+    }
+
+    if let Some(output) = &output {
+        let export = analyzer.export(&report, project_path.as_deref()).await?;
+        let json = serde_json::to_string_pretty(&export)?;
+        if to_stdout {
+            println!("{}", json);
+            return Ok(());
+        }
+        std::fs::write(output, json + "\n")?;
+    }
+
+    println!("{}", "=".repeat(60));
+    println!("{}Analysis Report", ui::emoji("📊"));
+    println!("{}", "=".repeat(60));
+    println!("\nPatterns Found: {}", report.patterns_found);
+    println!("Suggestions Generated: {}", report.suggestions_generated);
+
+    if interactive && !report.patterns.is_empty() {
+        let export = analyzer.export(&report, project_path.as_deref()).await?;
+        review_patterns(&db, &analyzer, &export.patterns).await?;
+    } else if !report.patterns.is_empty() {
+        println!("\n{}Detected Patterns:", ui::emoji("🔗"));
+        for (i, pattern) in report.patterns.iter().take(5).enumerate() {
+            println!(
+                "\n  {}. {:?} Pattern (confidence: {:.0}%)",
+                i + 1,
+                pattern.pattern_type,
+                pattern.confidence * 100.0
+            );
+            println!(
+                "     Sequence: {}",
+                ui::joined(&pattern.commands, Glyph::Arrow)
+            );
+        }
+    }
+
+    if !report.suggestions.is_empty() {
+        println!("\n{}Smart Suggestions:", ui::emoji("💡"));
+        for (i, suggestion) in report.suggestions.iter().enumerate() {
+            println!(
+                "\n  {}. {} (confidence: {:.0}%){}",
+                i + 1,
+                suggestion.command,
+                suggestion.confidence * 100.0,
+                suggestion
+                    .id
+                    .map(|id| format!(" [#{}]", id))
+                    .unwrap_or_default()
+            );
+            println!("     Reason: {}", suggestion.reason);
+        }
+    }
+
+    if !report.chains.is_empty() {
+        println!("\n{}Worth a one-liner:", ui::emoji("⚡"));
+        for (i, chain) in report.chains.iter().take(3).enumerate() {
+            println!("\n  {}. {}", i + 1, chain.one_liner());
+            let saving = chain.saving_ms().unwrap_or(0.0);
+            println!(
+                "     Saves ~{} a run, ~{} over the {} times you've typed it out",
+                format_duration(saving as u64),
+                format_duration((saving * chain.occurrences as f64) as u64),
+                chain.occurrences
+            );
+            if let Some(parallel) = chain.parallel_saving_ms().filter(|ms| *ms > 0.0) {
+                println!(
+                    "     Independent steps: `{}` runs them side by side ({} of that)",
+                    chain.side_by_side(),
+                    format_duration(parallel as u64)
+                );
+            }
+        }
+    }
+
+    println!("\n{}", "=".repeat(60));
+    if let Some(output) = output {
+        println!("{} Full report written to {}", ui::ok(), output);
+    }
+
+    Ok(())
+}
+
+// `analyze -i`: one pattern at a time, with the runs behind it, to accept as
+// a workflow or dismiss as noise
+#[cfg(feature = "intelligence")]
+async fn review_patterns(
+    db: &Database,
+    analyzer: &Analyzer,
+    patterns: &[intelligence::analyzer::ExportedPattern],
+) -> Result<()> {
+    let accepted_ids = db.accepted_pattern_ids().await?;
+    let stdin = std::io::stdin();
+    let total = patterns.len();
+    let (mut accepted, mut dismissed) = (0, 0);
+
+    'patterns: for (n, exported) in patterns.iter().enumerate() {
+        let pattern = &exported.pattern;
+        eprintln!(
+            "\n[{}/{}] {}",
+            n + 1,
+            total,
+            ui::joined(&pattern.commands, Glyph::Arrow)
+        );
+        eprintln!(
+            "  {:?}, seen {} times, {:.0}% confidence{}",
+            pattern.pattern_type,
+            pattern.occurrences,
+            pattern.confidence * 100.0,
+            match exported.id {
+                Some(id) if accepted_ids.contains(&id) => "  (your workflow)",
+                _ => "",
+            }
+        );
+
+        loop {
+            eprint!("[r]uns, [a]ccept as workflow, [d]ismiss as noise, [n]ext, [q]uit? ");
+            let mut answer = String::new();
+            if stdin.read_line(&mut answer)? == 0 {
+                eprintln!();
+                break 'patterns;
+            }
+
+            match answer.trim().to_lowercase().as_str() {
+                "r" | "runs" => {
+                    let runs = analyzer.supporting_runs(pattern, 5).await?;
+                    if runs.is_empty() {
+                        eprintln!("  None back to back in the recent history");
+                    }
+                    for run in runs {
+                        let when = parse_timestamp(&run[0].executed_at)
+                            .map(|at| {
+                                at.with_timezone(&chrono::Local)
+                                    .format("%Y-%m-%d %H:%M")
+                                    .to_string()
+                            })
+                            .unwrap_or_else(|| run[0].executed_at.clone());
+                        let steps: Vec<&str> = run.iter().map(|e| e.command.as_str()).collect();
+                        eprintln!("  {}  {}", when, ui::joined(&steps, Glyph::Arrow));
+                    }
+                }
+                "a" | "accept" | "d" | "dismiss" if exported.id.is_none() => {
+                    eprintln!("  Not stored yet (too little to go on), so nothing to mark");
+                }
+                "a" | "accept" => {
+                    let id = exported.id.unwrap_or_default();
+                    db.accept_pattern(id).await?;
+                    accepted += 1;
+                    if pattern.commands.len() > 1 {
+                        eprint!(
+                            "Alias to run it with `{} <name>` (Enter for none): ",
+                            EXPAND_PREFIX
+                        );
+                        let mut name = String::new();
+                        stdin.read_line(&mut name)?;
+                        let name = name.trim();
+                        if !name.is_empty() {
+                            let template = intelligence::chains::alias_template(&pattern.commands);
+                            db.create_alias(
+                                name.to_string(),
+                                template.clone(),
+                                pattern.project_path.clone(),
+                            )
+                            .await?;
+                            eprintln!(
+                                "  {} {} {} {} {}",
+                                ui::ok(),
+                                EXPAND_PREFIX,
+                                name,
+                                ui::glyph(Glyph::Arrow),
+                                template
+                            );
+                        }
+                    }
+                    break;
+                }
+                "d" | "dismiss" => {
+                    db.dismiss_pattern(exported.id.unwrap_or_default()).await?;
+                    dismissed += 1;
+                    break;
+                }
+                "" | "n" | "next" => break,
+                "q" | "quit" => break 'patterns,
+                _ => {}
+            }
+        }
+    }
+
+    println!(
+        "\n{} Accepted {} as workflows, dismissed {} as noise",
+        ui::ok(),
+        accepted,
+        dismissed
+    );
+    Ok(())
+}
+
+// Pattern pass over every known project, e.g. from a nightly cron job
+#[cfg(feature = "intelligence")]
+async fn analyze_all_projects(db: Arc<Database>) -> Result<()> {
+    let projects = db.get_project_paths().await?;
+    if projects.is_empty() {
+        println!("No projects recorded yet.");
+        return Ok(());
+    }
+
+    let analyzer = Analyzer::new(db);
+
+    println!(
+        "\n{}Analyzing {} project(s)...\n",
+        ui::emoji("🔍"),
+        projects.len()
+    );
+    println!("{}", "=".repeat(60));
+
+    for project in &projects {
+        match analyzer.analyze_patterns(project).await {
+            Ok(patterns) => println!("  {:>3} pattern(s)  {}", patterns.len(), project),
+            // One broken project shouldn't stop the nightly run
+            Err(e) => println!("  failed       {}  ({})", project, e),
+        }
+    }
+
+    println!("{}", "=".repeat(60));
+
+    Ok(())
+}
+
+// Turns `--project` into a recorded project path. Accepts a path (anything
+// inside the project works) or the project's directory name.
+async fn resolve_project(db: &Database, arg: &str) -> Result<Option<String>> {
+    let as_path = std::path::Path::new(arg);
+    if as_path.exists() {
+        let root = ProjectDetector::detect(as_path)?;
+        return Ok(root.to_str().map(|s| s.to_string()));
+    }
+
+    let projects = db.get_project_paths().await?;
+    let matches: Vec<&String> = projects
+        .iter()
+        .filter(|p| {
+            p.rsplit(['/', '\\'])
+                .next()
+                .is_some_and(|name| paths_equal(name, arg))
+        })
+        .collect();
+
+    match matches.as_slice() {
+        [only] => Ok(Some(only.to_string())),
+        [] => {
+            eprintln!("No recorded project called '{}'", arg);
+            Ok(None)
+        }
+        many => {
+            eprintln!("'{}' matches several projects, use the full path:", arg);
+            for path in many {
+                eprintln!("  {}", path);
+            }
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(feature = "intelligence")]
+async fn handle_suggest(args: &[String]) -> Result<()> {
+    match args.first().map(|s| s.as_str()) {
+        Some("--snooze") | Some("--never") | Some("--allow") | Some("--rules") => {
+            return handle_suggest_rules(args).await
+        }
+        Some("--accept") | Some("--dismiss") => return handle_suggest_feedback(args).await,
+        Some("--pipe") => return handle_suggest_pipe(&args[1..]).await,
+        Some(other) => {
+            eprintln!("Unknown option: {}", other);
+            eprintln!("Usage: berri-recall suggest [--accept <id>] [--dismiss <id>] [--snooze <id|command> <7d>] [--never <id|command>] [--allow <command>] [--rules] [--pipe <command>]");
+            return Ok(());
+        }
+        None => {}
+    }
+
+    let db = Arc::new(get_database().await?);
+    let analyzer = Analyzer::new(db);
+
+    let cwd = env::current_dir()?;
+    let project_root = ProjectDetector::detect(&cwd).ok();
+
+    println!("\n{}Generating suggestions...\n", ui::emoji("💡"));
+
+    // Only this project's patterns, so another repo's workflow doesn't leak in
+    let report = analyzer
+        .analyze(project_root.as_ref().and_then(|p| p.to_str()))
+        .await?;
+    cache_for_prompt(project_root.as_ref().and_then(|p| p.to_str()), &report.suggestions);
+
+    if report.suggestions.is_empty() {
+        println!("No suggestions available yet.");
+        println!("Use berri-recall more to build up command history!");
+    } else {
+        println!("{}", "=".repeat(60));
+        println!("Smart Suggestions");
+        println!("{}", "=".repeat(60));
+
+        for (i, suggestion) in report.suggestions.iter().enumerate() {
+            println!(
+                "\n{}. {} (confidence: {:.0}%){}",
+                i + 1,
+                suggestion.command,
+                suggestion.confidence * 100.0,
+                suggestion
+                    .id
+                    .map(|id| format!(" [#{}]", id))
+                    .unwrap_or_default()
+            );
+            println!("   {}{}", ui::emoji("💭"), suggestion.reason);
+        }
+
+        println!("\n{}", "=".repeat(60));
+        println!("\nTip: Run these commands or ignore them - recall learns from your choices!");
+        println!("Curious about one? berri-recall why <#id>");
+        println!("Taking one? berri-recall suggest --accept <#id> (or --dismiss <#id>)");
+        println!("Tired of one? berri-recall suggest --snooze <#id> 7d (or --never <#id>)");
+    }
+
+    Ok(())
+}
+
+// Finish a pipeline: the ones you've built on this first stage, most used
+// first, one per line so a shell widget can offer them
+#[cfg(feature = "intelligence")]
+async fn handle_suggest_pipe(args: &[String]) -> Result<()> {
+    let Some((args, scope)) = take_scope_flags(args) else {
+        return Ok(());
+    };
+    // `kubectl get pods |` as typed so far works too
+    let line = args.join(" ");
+    let Some(first) = pipeline::stages(&line).into_iter().next() else {
+        eprintln!("Usage: berri-recall suggest --pipe <command> [--global]");
+        return Ok(());
+    };
 
-    let shells = vec![
-        Shell::Bash,
-        Shell::Zsh,
-        Shell::Fish,
-        Shell::PowerShell,
-    ];
+    let db = get_database().await?;
+    let Some(project) = scope_filter(&db, &scope).await? else {
+        return Ok(());
+    };
+    let pipelines = db.pipelines_from(&first.text, project.as_deref(), 10).await?;
+    if pipelines.is_empty() {
+        eprintln!("No pipelines start with `{}` yet", first.text);
+    }
+    for (command, _) in pipelines {
+        println!("{}", command);
+    }
 
-    for shell in shells {
-        match installer.uninstall(shell) {
-            Ok(()) => println!("✓ Uninstalled {} hook", shell),
-            Err(e) => eprintln!("  (skipped {}: {})", shell, e),
+    Ok(())
+}
+
+// Take or turn down one suggestion. Accepting prints just the command, so a
+// shell can put it on the prompt: `print -z "$(berri-recall suggest --accept 12)"`
+#[cfg(feature = "intelligence")]
+async fn handle_suggest_feedback(args: &[String]) -> Result<()> {
+    let id = match args.get(1).map(|id| id.trim_start_matches('#').parse::<i64>()) {
+        Some(Ok(id)) => id,
+        _ => {
+            eprintln!("Usage: berri-recall suggest {} <id>", args[0]);
+            return Ok(());
         }
+    };
+
+    let db = get_database().await?;
+    let Some(suggestion) = db.get_suggestion_by_id(id).await? else {
+        eprintln!("No suggestion #{}", id);
+        return Ok(());
+    };
+
+    let accepted = args[0] == "--accept";
+    // Counted once: running it afterwards doesn't accept it a second time
+    if accepted && suggestion.times_accepted == 0 {
+        db.record_suggestion_feedback(suggestion.id, true).await?;
+    } else if !accepted {
+        db.record_suggestion_feedback(suggestion.id, false).await?;
     }
+    forget_in_prompt(&suggestion.suggested_command);
 
-    println!("\n✓ Uninstall complete!");
-    println!("Note: Database (~/.berri-recall/) was not removed.");
-    println!("To remove all data: rm -rf ~/.berri-recall");
+    if accepted {
+        println!("{}", suggestion.suggested_command);
+    } else {
+        eprintln!(
+            "Dismissed '{}' (--never <#id> keeps it away for good)",
+            suggestion.suggested_command
+        );
+    }
 
     Ok(())
 }
 
-async fn handle_status() -> Result<()> {
-    let installer = HookInstaller::new()?;
+// Snooze / never / allow rules for suggestions
+#[cfg(feature = "intelligence")]
+async fn handle_suggest_rules(args: &[String]) -> Result<()> {
     let db = get_database().await?;
-    let stats = db.stats().await?;
 
-    println!("\nberri-recall Status");
-    println!("{}", "=".repeat(60));
+    if args[0] == "--rules" {
+        let rules = SuggestionRules::load(&db).await?;
+        let now = chrono::Utc::now();
 
-    // Shell hooks status
-    println!("\nShell Hooks:");
-    use berri_recall_lib::shell::Shell;
-    for shell in &[
-        Shell::Bash,
-        Shell::Zsh,
-        Shell::Fish,
-        Shell::PowerShell,
-    ] {
-        let status = if installer.is_installed(*shell) {
-            "✓ Installed"
-        } else {
-            "✗ Not installed"
-        };
-        println!("  {:<12} {}", format!("{}:", shell), status);
+        if rules.suppressed.is_empty() && rules.snoozed.values().all(|until| *until <= now) {
+            println!("No suggestion rules. Everything is fair game.");
+            return Ok(());
+        }
+
+        let mut suppressed: Vec<_> = rules.suppressed.iter().collect();
+        suppressed.sort();
+        for command in suppressed {
+            println!("  never    {}", command);
+        }
+
+        let mut snoozed: Vec<_> = rules.snoozed.iter().filter(|(_, until)| **until > now).collect();
+        snoozed.sort_by_key(|(_, until)| **until);
+        for (command, until) in snoozed {
+            println!(
+                "  snoozed  {}  (until {})",
+                command,
+                until.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+            );
+        }
+        return Ok(());
     }
 
-    // Database stats
-    println!("\nDatabase Statistics:");
-    println!("  Commands:    {}", stats.total_commands);
-    println!("  Patterns:    {}", stats.total_patterns);
-    println!("  Suggestions: {}", stats.total_suggestions);
+    // Everything else names a suggestion: #id, a bare id, or the command itself
+    let (target, duration) = if args[0] == "--snooze" && args.len() > 2 {
+        (&args[1..args.len() - 1], args.last())
+    } else {
+        (&args[1..], None)
+    };
+    if target.is_empty() {
+        eprintln!("Error: {} needs a suggestion ID or command", args[0]);
+        return Ok(());
+    }
 
-    // Current shell
-    println!("\nCurrent Shell:");
-    match ShellDetector::detect() {
-        Ok(shell) => println!("  {}", shell),
-        Err(_) => println!("  Unknown"),
+    let suggestion = match target[0].trim_start_matches('#').parse::<i64>() {
+        Ok(id) if target.len() == 1 => match db.get_suggestion_by_id(id).await? {
+            Some(s) => Some(s),
+            None => {
+                eprintln!("No suggestion #{}", id);
+                return Ok(());
+            }
+        },
+        _ => None,
+    };
+    let command = match &suggestion {
+        Some(s) => s.suggested_command.clone(),
+        None => target.join(" "),
+    };
+
+    match args[0].as_str() {
+        "--snooze" => {
+            let length = match duration.and_then(|d| suggestion_rules::parse_duration(d)) {
+                Some(length) => length,
+                None => {
+                    eprintln!("Usage: berri-recall suggest --snooze <id|command> <30m|12h|7d|2w>");
+                    return Ok(());
+                }
+            };
+            let until = chrono::Utc::now() + length;
+            SuggestionRules::snooze(&db, &command, until).await?;
+            forget_in_prompt(&command);
+            if let Some(s) = &suggestion {
+                db.record_suggestion_feedback(s.id, false).await?;
+            }
+            println!(
+                "Snoozed '{}' until {}",
+                command,
+                until.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+            );
+        }
+        "--never" => {
+            SuggestionRules::suppress(&db, &command).await?;
+            forget_in_prompt(&command);
+            if let Some(s) = &suggestion {
+                db.record_suggestion_feedback(s.id, false).await?;
+            }
+            println!("Won't suggest '{}' again (undo with --allow)", command);
+        }
+        _ => {
+            if SuggestionRules::clear(&db, &command).await? {
+                println!("'{}' can be suggested again", command);
+            } else {
+                println!("No rule for '{}'", command);
+            }
+        }
     }
 
-    println!("{}", "=".repeat(60));
+    Ok(())
+}
+
+#[cfg(feature = "intelligence")]
+async fn handle_patterns(args: &[String]) -> Result<()> {
+    match args.first().map(|s| s.as_str()) {
+        Some("graph") => handle_patterns_graph(&args[1..]).await,
+        Some("list") => handle_patterns_list().await,
+        _ => {
+            eprintln!(
+                "Usage: berri-recall patterns list\n       \
+                 berri-recall patterns graph [--format dot|mermaid]"
+            );
+            Ok(())
+        }
+    }
+}
+
+// Stored sequences with the IDs `scriptify` takes
+#[cfg(feature = "intelligence")]
+async fn handle_patterns_list() -> Result<()> {
+    let db = get_database().await?;
+    let project_root = ProjectDetector::detect(env::current_dir()?).ok();
+
+    let patterns: Vec<_> = db
+        .get_patterns(project_root.as_ref().and_then(|p| p.to_str()))
+        .await?
+        .into_iter()
+        .filter(|p| p.pattern_type == PatternType::Sequential)
+        .collect();
+
+    if patterns.is_empty() {
+        println!("No sequential patterns detected yet. Run `berri-recall analyze` first.");
+        return Ok(());
+    }
+
+    let accepted = db.accepted_pattern_ids().await?;
+    for pattern in patterns {
+        println!(
+            "[#{}] {} ({:.0}% confidence, seen {} times){}",
+            pattern.id,
+            ui::joined(&pattern.get_commands(), Glyph::Arrow),
+            pattern.confidence_score * 100.0,
+            pattern.occurrences,
+            if accepted.contains(&pattern.id) {
+                "  workflow"
+            } else {
+                ""
+            }
+        );
+    }
 
     Ok(())
 }
 
-async fn handle_analyze(_args: &[String]) -> Result<()> {
+#[cfg(feature = "intelligence")]
+async fn handle_patterns_graph(args: &[String]) -> Result<()> {
+    let mut format = GraphFormat::Dot;
+
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--format" {
+            i += 1;
+            match args.get(i).map(|f| f.parse::<GraphFormat>()) {
+                Some(Ok(f)) => format = f,
+                Some(Err(e)) => {
+                    eprintln!("Error: {}", e);
+                    return Ok(());
+                }
+                None => {
+                    eprintln!("Error: --format needs a value (dot or mermaid)");
+                    return Ok(());
+                }
+            }
+        }
+        i += 1;
+    }
+
     let db = Arc::new(get_database().await?);
-    let analyzer = Analyzer::new(db);
+    let detector = PatternDetector::new(db);
 
     let cwd = env::current_dir()?;
     let project_root = ProjectDetector::detect(&cwd).ok();
 
-    println!("\n🔍 Analyzing command patterns...\n");
-
-    let report = analyzer
-        .analyze(project_root.as_ref().and_then(|p| p.to_str()))
+    let graph = detector
+        .pattern_graph(project_root.as_ref().and_then(|p| p.to_str()))
         .await?;
 
+    if graph.is_empty() {
+        // stderr so piping into `dot` doesn't get garbage
+        eprintln!("No sequential patterns detected yet.");
+        return Ok(());
+    }
+
+    print!("{}", graph.render(format));
+
+    Ok(())
+}
+
+#[cfg(feature = "intelligence")]
+async fn handle_why(args: &[String]) -> Result<()> {
+    if args.is_empty() {
+        eprintln!("Usage: berri-recall why <suggestion-id|command>");
+        return Ok(());
+    }
+
+    let db = Arc::new(get_database().await?);
+    let cwd = env::current_dir()?;
+    let project_root = ProjectDetector::detect(&cwd).ok();
+    let project_path = project_root.as_ref().and_then(|p| p.to_str());
+
+    // A bare number (optionally #-prefixed) is a suggestion ID
+    let suggestion = match args[0].trim_start_matches('#').parse::<i64>() {
+        Ok(id) if args.len() == 1 => db.get_suggestion_by_id(id).await?,
+        _ => None,
+    };
+    let command = match &suggestion {
+        Some(s) => s.suggested_command.clone(),
+        None => args.join(" "),
+    };
+
+    let explainer = Explainer::new(Arc::clone(&db));
+    let explanation = explainer.explain(&command, project_path).await?;
+
+    println!("\nWhy '{}'?", explanation.command);
     println!("{}", "=".repeat(60));
-    println!("📊 Analysis Report");
-    println!("{}", "=".repeat(60));
-    println!("\nPatterns Found: {}", report.patterns_found);
-    println!("Suggestions Generated: {}", report.suggestions_generated);
 
-    if !report.patterns.is_empty() {
-        println!("\n🔗 Detected Patterns:");
-        for (i, pattern) in report.patterns.iter().take(5).enumerate() {
-            println!(
-                "\n  {}. {:?} Pattern (confidence: {:.0}%)",
-                i + 1,
-                pattern.pattern_type,
-                pattern.confidence * 100.0
-            );
-            println!("     Sequence: {}", pattern.commands.join(" → "));
-        }
+    if let Some(s) = &suggestion {
+        println!("\nSuggestion #{}: {}", s.id, s.reason.as_deref().unwrap_or("-"));
+        println!("Confidence when suggested: {:.0}%", s.confidence * 100.0);
     }
 
-    if !report.suggestions.is_empty() {
-        println!("\n💡 Smart Suggestions:");
-        for (i, suggestion) in report.suggestions.iter().enumerate() {
+    let last_used = explanation
+        .days_since_last_use
+        .map(|d| format!("{:.1} days ago", d))
+        .unwrap_or_else(|| "never".to_string());
+    let context = explanation
+        .context_factors
+        .iter()
+        .map(|(name, hit)| format!("{} {}", if *hit { ui::ok() } else { ui::fail() }, name))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    println!("\nScore breakdown:");
+    println!("  {:<12} {:>6} {:>7} {:>7}  Based on", "Factor", "Value", "Weight", "Points");
+    let rows = [
+        (
+            "Frequency",
+            explanation.frequency,
+            scorer::FREQUENCY_WEIGHT,
+            format!(
+                "used {} times (top command: {})",
+                explanation.usage_count, explanation.max_usage_count
+            ),
+        ),
+        (
+            "Recency",
+            explanation.recency,
+            scorer::RECENCY_WEIGHT,
+            format!("last used {}", last_used),
+        ),
+        (
+            "Pattern",
+            explanation.pattern_confidence,
+            scorer::PATTERN_WEIGHT,
+            format!("{} supporting pattern(s)", explanation.supporting_patterns.len()),
+        ),
+        (
+            "Context",
+            explanation.context_match,
+            scorer::CONTEXT_WEIGHT,
+            context,
+        ),
+        (
+            "Acceptance",
+            explanation.acceptance_rate,
+            scorer::ACCEPTANCE_WEIGHT,
+            format!(
+                "accepted {} / rejected {}",
+                explanation.times_accepted, explanation.times_rejected
+            ),
+        ),
+    ];
+    for (name, value, weight, basis) in rows {
+        println!(
+            "  {:<12} {:>6.2} {:>6.0}% {:>7.3}  {}",
+            name,
+            value,
+            weight * 100.0,
+            value * weight,
+            basis
+        );
+    }
+    println!("  {:<12} {:>30.3}", "Total", explanation.score);
+
+    if !explanation.supporting_patterns.is_empty() {
+        println!("\nSupporting patterns:");
+        for pattern in &explanation.supporting_patterns {
             println!(
-                "\n  {}. {} (confidence: {:.0}%)",
-                i + 1,
-                suggestion.command,
-                suggestion.confidence * 100.0
+                "  {} (seen {} times, confidence {:.0}%)",
+                ui::joined(&pattern.commands, Glyph::Arrow),
+                pattern.occurrences,
+                pattern.confidence * 100.0
             );
-            println!("     Reason: {}", suggestion.reason);
         }
     }
 
-    println!("\n{}", "=".repeat(60));
+    println!("{}", "=".repeat(60));
 
     Ok(())
 }
 
-async fn handle_suggest() -> Result<()> {
+// Editor backend. Talks JSON-RPC on stdin/stdout until the editor says exit.
+#[cfg(feature = "intelligence")]
+async fn handle_lsp_like() -> Result<()> {
     let db = Arc::new(get_database().await?);
-    let analyzer = Analyzer::new(db);
+    let mut server = EditorServer::new(Arc::clone(&db));
+    let mut signals = Signals::new()?;
+    let started = std::time::Instant::now();
+
+    // Editors stop it with `exit`, by closing stdin, or with SIGTERM
+    let run = server.run(tokio::io::BufReader::new(tokio::io::stdin()), tokio::io::stdout());
+    tokio::pin!(run);
+    let result = loop {
+        tokio::select! {
+            result = &mut run => break result,
+            signal = signals.recv() => match signal {
+                Signal::Stop => break Ok(()),
+                // It reads no settings, there's nothing to reload
+                Signal::Reload => {}
+                Signal::Report => eprintln!("Up {}", format_uptime(started.elapsed())),
+            },
+        }
+    };
+    db.close().await;
+    result
+}
 
-    println!("\n💡 Generating suggestions...\n");
+// Raycast/Alfred/rofi list output. Searches every project, not just this one.
+#[cfg(feature = "intelligence")]
+async fn handle_launcher(args: &[String]) -> Result<()> {
+    let mut format = None;
+    let mut query_parts = Vec::new();
 
-    let report = analyzer.analyze(None).await?;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--format" {
+            i += 1;
+            match args.get(i).map(|f| f.parse::<LauncherFormat>()) {
+                Some(Ok(f)) => format = Some(f),
+                Some(Err(e)) => {
+                    eprintln!("Error: {}", e);
+                    return Ok(());
+                }
+                None => {
+                    eprintln!("Error: --format needs a value (raycast, alfred or rofi)");
+                    return Ok(());
+                }
+            }
+        } else {
+            query_parts.push(args[i].as_str());
+        }
+        i += 1;
+    }
 
-    if report.suggestions.is_empty() {
-        println!("No suggestions available yet.");
-        println!("Use berri-recall more to build up command history!");
+    let format = match format {
+        Some(f) => f,
+        None => {
+            eprintln!("Usage: berri-recall launcher --format raycast|alfred|rofi [query]");
+            return Ok(());
+        }
+    };
+
+    let db = get_database().await?;
+    let query = query_parts.join(" ");
+
+    // Empty query (launcher just opened) shows the most used commands
+    let commands = if query.trim().is_empty() {
+        db.get_most_used_commands(None, 50).await?
     } else {
-        println!("{}", "=".repeat(60));
-        println!("Smart Suggestions");
-        println!("{}", "=".repeat(60));
+        db.search_commands(query.trim(), None, 50).await?
+    };
 
-        for (i, suggestion) in report.suggestions.iter().enumerate() {
+    print!("{}", launcher::render(format, &commands));
+
+    Ok(())
+}
+
+// Desktop notification when something that usually takes ages is done.
+// The notify_after_minutes setting (or BERRI_RECALL_NOTIFY_AFTER) decides how long.
+async fn notify_if_long(
+    db: &Arc<Database>,
+    command: &str,
+    project: &str,
+    took_ms: i32,
+    exit_code: Option<i32>,
+) -> Result<()> {
+    let minutes = Config::load(db).await?.notify_after_minutes();
+    if minutes <= 0 {
+        return Ok(());
+    }
+
+    let report = PerfAnalyzer::new(Arc::clone(db))
+        .report(command, Some(project))
+        .await?;
+    if let Some((summary, body)) = report.finished_notice(took_ms, exit_code, minutes * 60_000) {
+        notifier::notify(&summary, &body)?;
+    }
+
+    Ok(())
+}
+
+// Scripts and webhooks that run on events
+async fn handle_trigger(args: &[String]) -> Result<()> {
+    let db = get_database().await?;
+    let usage = "Usage: berri-recall trigger add <event> <script|url> [--template <payload>] [--match <regex>]\n       berri-recall trigger list\n       berri-recall trigger rm <event> <script|url>";
+
+    match args.first().map(|s| s.as_str()) {
+        Some("add") => {
+            let mut positional = Vec::new();
+            let mut template = None;
+            let mut matches = None;
+
+            let mut i = 1;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--template" => {
+                        i += 1;
+                        template = args.get(i).cloned();
+                    }
+                    "--match" => {
+                        i += 1;
+                        matches = args.get(i).cloned();
+                    }
+                    other => positional.push(other.to_string()),
+                }
+                i += 1;
+            }
+
+            if positional.len() < 2 {
+                eprintln!("{}", usage);
+                return Ok(());
+            }
+
+            let event = match positional[0].parse::<Event>() {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(());
+                }
+            };
+            let trigger = Trigger {
+                event,
+                target: positional[1..].join(" "),
+                template,
+                matches,
+            };
+
+            if let Err(e) = Triggers::add(&db, &trigger).await {
+                eprintln!("{}", e);
+                return Ok(());
+            }
             println!(
-                "\n{}. {} (confidence: {:.0}%)",
-                i + 1,
-                suggestion.command,
-                suggestion.confidence * 100.0
+                "{} On {}: {} {}",
+                ui::ok(),
+                trigger.event,
+                if trigger.is_webhook() { "POST" } else { "run" },
+                trigger.target
             );
-            println!("   💭 {}", suggestion.reason);
         }
+        Some("rm") | Some("remove") => {
+            if args.len() < 3 {
+                eprintln!("{}", usage);
+                return Ok(());
+            }
 
-        println!("\n{}", "=".repeat(60));
-        println!("\nTip: Run these commands or ignore them - recall learns from your choices!");
+            let event = match args[1].parse::<Event>() {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(());
+                }
+            };
+            let target = args[2..].join(" ");
+
+            if Triggers::remove(&db, event, &target).await? {
+                println!("{} Removed trigger on {}: {}", ui::ok(), event, target);
+            } else {
+                eprintln!("No trigger on {} for '{}'", event, target);
+            }
+        }
+        Some("list") | None => {
+            let triggers = Triggers::load(&db).await?;
+            if triggers.triggers.is_empty() {
+                println!("No triggers yet. Add one with: berri-recall trigger add <event> <script|url>");
+                println!(
+                    "Events: {}",
+                    Event::ALL.map(|e| e.as_str()).join(", ")
+                );
+                return Ok(());
+            }
+
+            for trigger in &triggers.triggers {
+                println!(
+                    "  {:<20} {} {}",
+                    trigger.event.as_str(),
+                    if trigger.is_webhook() { "POST" } else { "run " },
+                    trigger.target
+                );
+                if let Some(pattern) = &trigger.matches {
+                    println!("  {:<20} only when matching /{}/", "", pattern);
+                }
+                if let Some(template) = &trigger.template {
+                    println!("  {:<20} payload: {}", "", template);
+                }
+            }
+        }
+        Some(_) => eprintln!("{}", usage),
     }
 
     Ok(())
 }
 
+fn database_path() -> std::path::PathBuf {
+    Recall::default_path().expect("Could not find home directory")
+}
+
 async fn get_database() -> Result<Database> {
-    let home = dirs::home_dir().expect("Could not find home directory");
-    let db_path = home.join(".berri-recall").join("commands.db");
-    Database::new(db_path).await
+    let db = if READ_ONLY.load(Ordering::Relaxed) {
+        Database::open_read_only(database_path()).await?
+    } else {
+        Database::new(database_path()).await?
+    };
+    // Output looks the way the settings say from here on
+    let config = Config::load(&db).await.ok();
+    if let Some(config) = &config {
+        ui::set_theme(Theme::from_config(config));
+    }
+    // What `record --fast` queued, so it shows up in whatever runs now
+    if !READ_ONLY.load(Ordering::Relaxed) {
+        if let Some(dir) = Spool::default_dir().filter(|dir| dir.exists()) {
+            let ignore_rules = IgnoreRules::load(&db).await.unwrap_or_default();
+            let mut recorder =
+                Recorder::new(Arc::new(db.clone())).with_ignore_rules(ignore_rules);
+            if let Some(config) = config {
+                recorder = recorder.with_config(config);
+            }
+            let _ = Spool::new(dir).drain(&recorder).await;
+        }
+    }
+    Ok(db)
+}
+
+// Only one of merge, archive, import and restore at a time. None when another
+// berri-recall has it, after saying so.
+fn maintenance_lock(db_path: &std::path::Path, job: &str) -> Option<Lock> {
+    match Lock::try_acquire(&lock::lock_path(db_path, lock::MAINTENANCE), job) {
+        Ok(lock) => Some(lock),
+        Err(e) => {
+            eprintln!("{} {}", ui::fail(), e.user_message());
+            None
+        }
+    }
+}
+
+// Hooks run in the background and must never stop to ask anything
+fn can_offer_restore(command: &str) -> bool {
+    let from_hook = matches!(
+        command,
+        "record"
+            | "check"
+            | "expand"
+            | "prompt-segment"
+            | "lsp-like"
+            | "hello"
+            | "warm"
+            | "finished"
+    );
+    !from_hook
+        && !READ_ONLY.load(Ordering::Relaxed)
+        && std::io::stdin().is_terminal()
+        && std::io::stderr().is_terminal()
+}
+
+// The database is damaged. Offer to put the latest backup in its place.
+// Returns whether it was restored.
+fn restore_latest_backup(error: &RecallError) -> Result<bool> {
+    let db_path = database_path();
+    eprintln!(
+        "{} The database at {} looks corrupted: {}",
+        ui::fail(),
+        db_path.display(),
+        error
+    );
+
+    let Some(latest) = backup::list_backups(&backup::backup_dir(&db_path)).into_iter().next()
+    else {
+        eprintln!("   There's no backup to restore. Inspect it with --read-only.");
+        return Ok(false);
+    };
+
+    eprint!("   Restore the backup {}? [y/N] ", latest.display());
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        eprintln!("   Left it as is. Inspect it with --read-only.");
+        return Ok(false);
+    }
+
+    let Some(_lock) = maintenance_lock(&db_path, "restoring a backup") else {
+        return Ok(false);
+    };
+    let kept = backup::restore_backup(&db_path, &latest)?;
+    eprintln!("{} Restored. The damaged file was kept as {}", ui::ok(), kept.display());
+    Ok(true)
 }
 
 fn print_usage() {
@@ -420,26 +5465,125 @@ fn print_usage() {
         r#"berri-recall v{} - Your terminal remembers everything
 
 USAGE:
-    berri-recall <COMMAND> [OPTIONS]
+    berri-recall [--read-only] <COMMAND> [OPTIONS]
 
 COMMANDS:
-    record <command>       Record a command
+    record <command>       Record a command (--fast only queues it, see
+                           BERRI_RECALL_FAST_RECORD)
+    mirror [--watch]       Record what's new in your shell's history file, no hooks
+                           needed (--watch keeps following it, --shell, --file)
+    check <command>        Warn about destructive commands new to this project
+    alias [list|add|rm]    Manage aliases (type '!!r <name>' to expand one)
+    expand <line>          Expand '!!r <alias>', '!r:<n>' and '!r:fail' (used by hooks)
     recent [limit]         Show recent commands (default: 10)
-    search <query>         Search for commands
-    setup [--all]          Install shell hooks
+                           (--copy [N] puts row N, default 1, on the clipboard,
+                           --group-by tool|project|session gathers rows under headings)
+    last [--failed]        Last (failing) command with exit code, time and duration
+    top [N]                Most used commands with success rate and avg time
+                           (--global for all projects, --tool git to filter)
+                           recent, search, top and status take --porcelain: tab-separated
+                           fields, no header, stable across versions
+    tldr <tool> [N]        Cheatsheet of how you run a tool, with your notes
+                           (--note <command> <text>), then its tldr page if cached
+    flags <tool> [N]       The flags you give a tool, inside pipelines and && chains too
+    stats --tool-trends    Weekly runs per tool over the last 3 months, as sparklines
+                           (--months N, --top N, --json; shows what's rising or fading)
+    hello                  Project type, task runner targets and what you run in
+                           similar projects (the hook shows it once per new project)
+    sessions [N]           Past shell sessions: when, where, how many commands
+    session show <id>      Everything run in one session, in order
+    replay <id>            Run a session's commands again, one at a time
+                           (run, skip, edit or quit at each; risky ones
+                           like `rm -rf ~` need a typed yes)
+    oops                   Forget the last command this shell recorded
+    merge <other.db>       Merge another machine's history into this one
+    export --everything    Every table and setting as one JSON archive (--output <file>)
+    import --everything <file>
+                           Load such an archive (--replace swaps out existing history)
+    archive                Move runs older than archive_after_months (default: 12) into
+                           compressed monthly files next to the database
+                           (--older-than <months>)
+    demo [--seed N]        Made-up history in a throwaway database, to try things on
+                           (--commands N for more, prints how to point berri-recall at it)
+    redact --query <regex> Mask secrets in matching history (--delete [--yes], --dry-run)
+    sweep [N]              Review the last N (default: 50) entries one by one: keep,
+                           redact, edit or delete (--risky for likely secrets first,
+                           --global/--project like recent)
+    orphans [--list]       Commands under project paths that are gone, and where they
+                           likely belong now (<path> --to <project> moves them)
+    timeline --from <time> Everything run in a time window (--to, --all-projects,
+                           --format text|md|json)
+    flaky [--min-runs N]   Commands that sometimes pass and sometimes fail
+    perf <command>         Duration history sparkline, flags slowdowns
+    eta <command>          How long it usually takes (--min-seconds N to skip quick ones)
+    prompt-segment         Top pending suggestion for your prompt (--format count,
+                           --init starship|p10k prints the config)
+    warm [--cwd <dir>]     Refresh a project's prompt suggestions (the hooks run it
+                           in the background when you cd)
+    finished --command <cmd> --duration <ms>
+                           "✓ 4m 12s — 15% faster than your median" (the hooks print
+                           it after commands over summary_after_seconds)
+    search <query>         Search for commands, matches highlighted (--copy [N] like recent,
+                           --env AWS_PROFILE=prod for runs with a captured variable,
+                           --preview for where, when and how each one last ran,
+                           --privileged for ones run with sudo or doas,
+                           --include-archived to look through `archive`d runs too)
+                           recent and search take --branch <name> (. for this one),
+                           --interactive or --scripted (typed at a prompt, or run by a script),
+                           --sort recency|usage|duration|exit
+                           and --columns cmd,count,last,exit,duration,project
+                           (--absolute shows LAST as a local date and time)
+                           recent, search and batch take --global/-g for every
+                           project or --project <name|path> for another one
+    setup [--all]          Install shell hooks (--rc-file <path> picks the file to edit,
+                           --no-modify-rc / --print-snippet [--inline] for dotfile managers,
+                           --restore-rc <shell> undoes the last rc file edit,
+                           --non-interactive for package postinstall scripts)
     uninstall              Remove shell hooks
-    status                 Show status and stats
-    analyze                Analyze command patterns
+    status [--latency]     Show status and stats (--latency times the hooks)
+    analyze                Analyze command patterns, and sequences worth a one-liner
+                           (--project <name|path> for another repo, --all for every one,
+                           --output <file> writes the whole report as JSON, - for stdout,
+                           -i goes through each pattern: its runs, accept or dismiss)
     suggest                Get smart suggestions
-    version                Show version
+                           (--accept <id>, --dismiss <id>, --snooze <id> 7d,
+                            --never <id>, --allow <cmd>, --rules,
+                            --pipe <cmd> for the pipelines you build on it)
+    patterns list          Detected sequences with their IDs
+    patterns graph         Export workflow graph (--format dot|mermaid)
+    scriptify <id>         Turn a pattern (or --pick / --rows from `recent`) into a
+                           script (--format sh|just|make, --name, --output)
+    why <id|command>       Explain how a suggestion was scored
+    lsp-like               JSON-RPC over stdio for editor extensions
+    launcher [query]       History for desktop launchers (--format raycast|alfred|rofi)
+    trigger [add|list|rm]  Run a script or POST a webhook on events
+    ignore [add|list|rm]   Never record commands matching a glob ('man *', '*--help')
+    config [list]          Settings and where they come from
+                           (get <key>, set <key> <value>, unset <key>)
+    batch <rows> <action>  tag <name>, fav, unfav, delete or export several rows of
+                           `recent` at once (rows like 1-3,5)
+    optimize               Short names for your most typed long commands, never one
+                           that's already a program, function or builtin
+                           (--fish-abbr writes them as fish abbreviations, --remove)
+    version                Show version (--build-info: target, features, linking)
     help                   Show this help
 
+GLOBAL OPTIONS:
+    --read-only            Open the database without migrating or writing to it
+
 EXAMPLES:
     berri-recall record npm test
     berri-recall recent 20
+    berri-recall top 5 --tool git
     berri-recall search docker
+    berri-recall search docker --copy 2
     berri-recall setup
     berri-recall status
+    berri-recall status --latency
+    berri-recall --read-only search docker
+    berri-recall timeline --from "2024-05-02 14:00" --to 16:00 --all-projects --format md
+    berri-recall patterns graph --format dot | dot -Tsvg > flow.svg
+    berri-recall trigger add dangerous_command https://hooks.slack.com/... --match prod
 
 AUTOMATIC RECORDING:
     Run 'berri-recall setup' to automatically record all commands.
@@ -0,0 +1,183 @@
+//! Embedding berri-recall in another program
+//!
+//! [`Recall`] wraps the database and the pieces the CLI wires together
+//! (project detection, ignore rules, fuzzy search, pattern analysis) behind
+//! a handful of calls. Editor plugins and bots can use it instead of copying
+//! `main.rs`; the lower-level modules stay available through
+//! [`Recall::database`] for anything it doesn't cover.
+
+use crate::core::ignore_rules::IgnoreRules;
+use crate::core::{ProjectDetector, Recorder, Searcher};
+use crate::db::{Command, Database, SearchResult};
+use crate::error::{RecallError, Result};
+use crate::intelligence::analyzer::AnalysisReport;
+use crate::intelligence::{Analyzer, SmartSuggestion};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Points the CLI and the hooks at another database (like the one `demo` makes)
+pub const DB_ENV: &str = "BERRI_RECALL_DB";
+
+/// Command history for one database
+///
+/// Every method taking a `project` accepts any directory inside it (the
+/// project root is found the same way the shell hooks find it); `None`
+/// means every project.
+///
+/// # Examples
+/// ```no_run
+/// use berri_recall_lib::Recall;
+///
+/// # async fn example() -> berri_recall_lib::Result<()> {
+/// let recall = Recall::open_default().await?;
+/// recall.record("cargo test", "/work/api".as_ref(), Some(0), Some(4200)).await?;
+///
+/// for result in recall.search("test", Some("/work/api".as_ref()), 5).await? {
+///     println!("{}", result.command.command);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Recall {
+    db: Arc<Database>,
+}
+
+impl Recall {
+    /// Open (creating and migrating if needed) the database at `path`
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::with_database(Database::new(path).await?))
+    }
+
+    /// Open the database the CLI and the shell hooks use
+    pub async fn open_default() -> Result<Self> {
+        let path = Self::default_path()
+            .ok_or_else(|| RecallError::Config("Could not find home directory".to_string()))?;
+        Self::open(path).await
+    }
+
+    /// `$BERRI_RECALL_DB` if set, else `~/.berri-recall/commands.db`; `None`
+    /// without a home directory
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(DB_ENV).filter(|p| !p.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+        dirs::home_dir().map(|home| home.join(".berri-recall").join("commands.db"))
+    }
+
+    /// Use a database that's already open
+    pub fn with_database(db: Database) -> Self {
+        Self { db: Arc::new(db) }
+    }
+
+    /// The database underneath, for everything this doesn't wrap
+    pub fn database(&self) -> &Arc<Database> {
+        &self.db
+    }
+
+    /// Record a command run in `cwd`
+    ///
+    /// Applies the same filters as the hooks (secrets, ignore rules, a
+    /// leading space), returning `None` when the command was left out.
+    pub async fn record(
+        &self,
+        command: &str,
+        cwd: &Path,
+        exit_code: Option<i32>,
+        duration_ms: Option<i32>,
+    ) -> Result<Option<i64>> {
+        let project = ProjectDetector::detect(cwd)?;
+        let recorder = Recorder::new(Arc::clone(&self.db))
+            .with_ignore_rules(IgnoreRules::load(&self.db).await.unwrap_or_default());
+        if recorder.should_ignore(command) {
+            return Ok(None);
+        }
+
+        let id = recorder
+            .record(
+                command,
+                &project.to_string_lossy(),
+                duration_ms,
+                exit_code,
+                None,
+            )
+            .await?;
+        Ok(Some(id))
+    }
+
+    /// Fuzzy search, best match first
+    pub async fn search(
+        &self,
+        query: &str,
+        project: Option<&Path>,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>> {
+        let project = Self::project_key(project)?;
+        Searcher::new(Arc::clone(&self.db))
+            .search(query, project.as_deref(), limit)
+            .await
+    }
+
+    /// Last used first
+    pub async fn recent(&self, project: Option<&Path>, limit: i64) -> Result<Vec<Command>> {
+        let project = Self::project_key(project)?;
+        self.db.get_recent_commands(project.as_deref(), limit).await
+    }
+
+    /// Suggestions for what to run next
+    ///
+    /// Patterns come from `project`, the rest of the context (git state,
+    /// time of day) from the process's current directory.
+    pub async fn suggest(&self, project: Option<&Path>) -> Result<Vec<SmartSuggestion>> {
+        Ok(self.analyze(project).await?.suggestions)
+    }
+
+    /// Detect and store patterns, then generate suggestions
+    pub async fn analyze(&self, project: Option<&Path>) -> Result<AnalysisReport> {
+        let project = Self::project_key(project)?;
+        Analyzer::new(Arc::clone(&self.db))
+            .analyze(project.as_deref())
+            .await
+    }
+
+    /// A directory's project root, as commands are stored under it
+    fn project_key(project: Option<&Path>) -> Result<Option<String>> {
+        project
+            .map(|path| Ok(ProjectDetector::detect(path)?.to_string_lossy().to_string()))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_search_recent() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("api");
+        std::fs::create_dir_all(project.join(".git")).unwrap();
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        let recall = Recall::with_database(Database::new_test().await.unwrap());
+
+        let id = recall
+            .record("cargo test --workspace", &project, Some(0), Some(4200))
+            .await
+            .unwrap();
+        assert!(id.is_some());
+        // Same filters as the hooks
+        let skipped = recall
+            .record(" export TOKEN=abc", &project, Some(0), None)
+            .await
+            .unwrap();
+        assert_eq!(skipped, None);
+
+        let query = if cfg!(feature = "fuzzy") { "cartest" } else { "cargo test" };
+        let found = recall.search(query, Some(&project), 5).await.unwrap();
+        assert_eq!(found[0].command.command, "cargo test --workspace");
+
+        let recent = recall.recent(Some(&project.join("src")), 10).await.unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].execution_time_ms, Some(4200));
+    }
+}
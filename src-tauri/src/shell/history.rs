@@ -0,0 +1,368 @@
+//! The shell's own history file
+//!
+//! Commands `replay` runs never went through your shell, so up-arrow and
+//! Ctrl-R don't know them. With `write_history` on they're appended to the
+//! shell's history file as well, in the format it already uses: bash with
+//! or without `#<time>` lines, zsh with or without `EXTENDED_HISTORY`, fish's
+//! YAML-ish entries, PSReadLine's plain lines.
+//!
+//! Shells that are already open read the file at their own pace: new ones
+//! see the lines straight away, an open bash after `history -n`, zsh after
+//! `fc -RI` (or at once with `SHARE_HISTORY`), fish after `history merge`.
+//!
+//! Reading goes the other way, for `mirror`: [`Format::parse`] turns what a
+//! shell appended back into commands, with their times where the format
+//! keeps them.
+
+use crate::error::{RecallError, Result};
+use crate::shell::Shell;
+use chrono::{DateTime, Utc};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// How much of the end of the file is read to tell its format
+const TAIL_BYTES: u64 = 4096;
+
+/// One command read back from a history file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub command: String,
+    /// When it ran, for formats that keep the time
+    pub at: Option<DateTime<Utc>>,
+}
+
+/// How entries are laid out in the file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// One command per line
+    Plain,
+    /// bash with `HISTTIMEFORMAT`: a `#<unix time>` line before each command
+    Timestamped,
+    /// zsh's `EXTENDED_HISTORY`: `: <unix time>:<seconds>;<command>`
+    Extended,
+    /// `- cmd: <command>` then `  when: <unix time>`
+    Fish,
+}
+
+impl Format {
+    /// What `shell` writes, going by the end of its file; a new file gets
+    /// the shell's default
+    pub fn detect(shell: Shell, tail: &str) -> Self {
+        let recent = || tail.lines().rev().take(20);
+        match shell {
+            Shell::Fish => Format::Fish,
+            Shell::Bash if recent().any(is_bash_stamp) => Format::Timestamped,
+            Shell::Zsh if recent().any(|l| l.starts_with(": ") && l.contains(';')) => {
+                Format::Extended
+            }
+            _ => Format::Plain,
+        }
+    }
+
+    /// `command` as one entry, ending in a newline
+    pub fn entry(self, shell: Shell, command: &str, at: DateTime<Utc>) -> String {
+        let time = at.timestamp();
+        match self {
+            Format::Fish => {
+                let escaped = command.replace('\\', "\\\\").replace('\n', "\\n");
+                format!("- cmd: {}\n  when: {}\n", escaped, time)
+            }
+            Format::Extended => format!(": {}:0;{}\n", time, command.replace('\n', "\\\n")),
+            Format::Timestamped => format!("#{}\n{}\n", time, command),
+            // PSReadLine ends each line but the last with a backtick
+            Format::Plain if shell == Shell::PowerShell => {
+                format!("{}\n", command.replace('\n', "`\n"))
+            }
+            Format::Plain if shell == Shell::Zsh => format!("{}\n", command.replace('\n', "\\\n")),
+            Format::Plain => format!("{}\n", command),
+        }
+    }
+
+    /// The entries in `text`, which starts at the start of one
+    pub fn parse(self, shell: Shell, text: &str) -> Vec<Entry> {
+        let continuation = continuation(shell);
+        let mut entries: Vec<Entry> = Vec::new();
+        // bash's `#<time>` line, for the command after it
+        let mut stamp = None;
+        let mut pending: Option<String> = None;
+
+        for line in text.lines() {
+            let line = match pending.take() {
+                Some(start) => format!("{}\n{}", start, line),
+                None => line.to_string(),
+            };
+            if let Some(rest) = continuation.and_then(|c| line.strip_suffix(c)) {
+                pending = Some(rest.to_string());
+                continue;
+            }
+
+            let (command, at) = match self {
+                Format::Fish => {
+                    if let Some(command) = line.strip_prefix("- cmd: ") {
+                        entries.push(Entry {
+                            command: unescape_fish(command),
+                            at: None,
+                        });
+                    } else if let Some(when) = line.trim_start().strip_prefix("when: ") {
+                        if let Some(entry) = entries.last_mut() {
+                            entry.at = timestamp(when);
+                        }
+                    }
+                    continue;
+                }
+                Format::Timestamped if is_bash_stamp(&line) => {
+                    stamp = timestamp(&line[1..]);
+                    continue;
+                }
+                Format::Extended => match line.strip_prefix(": ").and_then(|l| l.split_once(';')) {
+                    Some((times, command)) => {
+                        let started = times.split(':').next().and_then(timestamp);
+                        (command.to_string(), started)
+                    }
+                    None => (line, None),
+                },
+                _ => (line, stamp.take()),
+            };
+            if !command.trim().is_empty() {
+                entries.push(Entry { command, at });
+            }
+        }
+        entries
+    }
+}
+
+/// How many bytes of `text` are whole entries: up to the last line end,
+/// unless that line carries on to the next
+pub fn complete(shell: Shell, text: &[u8]) -> usize {
+    let continuation = continuation(shell).map(|c| c as u8);
+    let mut end = 0;
+    let mut start = 0;
+    for (i, _) in text.iter().enumerate().filter(|(_, b)| **b == b'\n') {
+        let line = text[start..i]
+            .strip_suffix(b"\r")
+            .unwrap_or(&text[start..i]);
+        if continuation.is_none_or(|c| line.last() != Some(&c)) {
+            end = i + 1;
+        }
+        start = i + 1;
+    }
+    end
+}
+
+/// What ends a line that goes on to the next: zsh escapes the newline,
+/// PSReadLine puts a backtick there
+fn continuation(shell: Shell) -> Option<char> {
+    match shell {
+        Shell::Zsh => Some('\\'),
+        Shell::PowerShell => Some('`'),
+        Shell::Bash | Shell::Fish => None,
+    }
+}
+
+/// Undo what [`Format::entry`] does to a fish command
+fn unescape_fish(text: &str) -> String {
+    let mut command = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => command.push('\n'),
+            ('\\', Some('\\')) => command.push('\\'),
+            _ => {
+                command.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    command
+}
+
+/// Unix time as written in history files
+fn timestamp(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(text.trim().parse().ok()?, 0)
+}
+
+/// `#1700000000`
+fn is_bash_stamp(line: &str) -> bool {
+    line.strip_prefix('#')
+        .is_some_and(|time| !time.is_empty() && time.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Where `shell` keeps its history
+pub fn path(shell: Shell) -> Result<PathBuf> {
+    let home = dirs::home_dir()
+        .ok_or_else(|| RecallError::Config("Could not determine home directory".to_string()))?;
+    Ok(resolve_path(shell, &home, |name| {
+        env::var(name).ok().filter(|value| !value.is_empty())
+    }))
+}
+
+fn resolve_path(shell: Shell, home: &Path, var: impl Fn(&str) -> Option<String>) -> PathBuf {
+    match shell {
+        // Only there if it was exported, which most setups don't do
+        Shell::Bash => var("HISTFILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".bash_history")),
+        Shell::Zsh => var("HISTFILE").map(PathBuf::from).unwrap_or_else(|| {
+            var("ZDOTDIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| home.to_path_buf())
+                .join(".zsh_history")
+        }),
+        Shell::Fish => {
+            let session = var("fish_history").unwrap_or_else(|| "fish".to_string());
+            var("XDG_DATA_HOME")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| home.join(".local/share"))
+                .join("fish")
+                .join(format!("{}_history", session))
+        }
+        Shell::PowerShell => {
+            let base = if cfg!(windows) {
+                var("APPDATA")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| home.join("AppData/Roaming"))
+                    .join("Microsoft/Windows/PowerShell")
+            } else {
+                var("XDG_DATA_HOME")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| home.join(".local/share"))
+                    .join("powershell")
+            };
+            base.join("PSReadLine/ConsoleHost_history.txt")
+        }
+    }
+}
+
+/// Add `command` to `shell`'s history file, creating it if need be
+///
+/// Returns the file written to.
+pub fn append(shell: Shell, command: &str, at: DateTime<Utc>) -> Result<PathBuf> {
+    let path = path(shell)?;
+    let format = Format::detect(shell, &tail(&path));
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    file.write_all(format.entry(shell, command, at).as_bytes())?;
+    Ok(path)
+}
+
+/// The end of the file, empty if there's no file yet
+fn tail(path: &Path) -> String {
+    let Ok(mut file) = File::open(path) else {
+        return String::new();
+    };
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut bytes = Vec::new();
+    if file
+        .seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES)))
+        .is_ok()
+    {
+        let _ = file.read_to_end(&mut bytes);
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_format() {
+        let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+
+        assert_eq!(Format::detect(Shell::Bash, "ls\ncd src\n"), Format::Plain);
+        assert_eq!(
+            Format::detect(Shell::Bash, "#1699999990\nls\n"),
+            Format::Timestamped
+        );
+        assert_eq!(
+            Format::detect(Shell::Zsh, ": 1699999990:0;ls\n"),
+            Format::Extended
+        );
+        assert_eq!(Format::detect(Shell::Zsh, ""), Format::Plain);
+        assert_eq!(Format::detect(Shell::Fish, ""), Format::Fish);
+
+        assert_eq!(
+            Format::Timestamped.entry(Shell::Bash, "make", at),
+            "#1700000000\nmake\n"
+        );
+        assert_eq!(
+            Format::Extended.entry(Shell::Zsh, "for f in *\ndo echo $f\ndone", at),
+            ": 1700000000:0;for f in *\\\ndo echo $f\\\ndone\n"
+        );
+        assert_eq!(
+            Format::Fish.entry(Shell::Fish, "echo 'a\\b'\nls", at),
+            "- cmd: echo 'a\\\\b'\\nls\n  when: 1700000000\n"
+        );
+        assert_eq!(
+            Format::Plain.entry(Shell::PowerShell, "Get-Item x |\nSelect Name", at),
+            "Get-Item x |`\nSelect Name\n"
+        );
+    }
+
+    #[test]
+    fn test_history_parse() {
+        let at = |time| DateTime::from_timestamp(time, 0);
+        let bash = "#1700000000\nmake\nls -la\n";
+        assert_eq!(
+            Format::Timestamped.parse(Shell::Bash, bash),
+            vec![
+                Entry {
+                    command: "make".to_string(),
+                    at: at(1_700_000_000),
+                },
+                Entry {
+                    command: "ls -la".to_string(),
+                    at: None,
+                },
+            ]
+        );
+
+        let zsh = ": 1700000000:3;for f in *\\\ndo echo $f\\\ndone\n: 1700000009:0;ls\n";
+        let entries = Format::Extended.parse(Shell::Zsh, zsh);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].command, "for f in *\ndo echo $f\ndone");
+        assert_eq!(entries[1].at, at(1_700_000_009));
+
+        // What `entry` writes reads back the same
+        let command = "echo 'a\\nb' \\\\\nls";
+        let fish =
+            Format::Fish.entry(Shell::Fish, command, at(5).unwrap()) + "  paths:\n    - src\n";
+        let entries = Format::Fish.parse(Shell::Fish, &fish);
+        assert_eq!(entries[0].command, command);
+        assert_eq!(entries[0].at, at(5));
+
+        // A zsh line that carries on isn't finished yet
+        assert_eq!(complete(Shell::Zsh, b"ls\nfor f in *\\\ndo"), 3);
+        assert_eq!(complete(Shell::Zsh, b"ls\nfor f in *\\\ndone\n"), 20);
+        assert_eq!(complete(Shell::Bash, b"ls\\\nmake"), 4);
+    }
+
+    #[test]
+    fn test_history_path() {
+        let home = Path::new("/home/me");
+        let none = |_: &str| None;
+        assert_eq!(
+            resolve_path(Shell::Bash, home, none),
+            PathBuf::from("/home/me/.bash_history")
+        );
+        assert_eq!(
+            resolve_path(Shell::Fish, home, none),
+            PathBuf::from("/home/me/.local/share/fish/fish_history")
+        );
+        let zdotdir = |name: &str| (name == "ZDOTDIR").then(|| "/home/me/.config/zsh".to_string());
+        assert_eq!(
+            resolve_path(Shell::Zsh, home, zdotdir),
+            PathBuf::from("/home/me/.config/zsh/.zsh_history")
+        );
+        let histfile = |name: &str| (name == "HISTFILE").then(|| "/tmp/h".to_string());
+        assert_eq!(
+            resolve_path(Shell::Zsh, home, histfile),
+            PathBuf::from("/tmp/h")
+        );
+    }
+}
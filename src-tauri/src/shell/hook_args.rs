@@ -0,0 +1,185 @@
+//! What the shell hooks pass to `record`
+//!
+//! The hooks call `berri-recall record --exit-code 0 --duration 120 --cwd
+//! /work/api --session 1716 -- <command>` after every command, so whatever
+//! was typed at the prompt ends up in these arguments. Parsing never fails:
+//! an option missing its value or a value that doesn't parse is dropped,
+//! and anything that isn't an option is part of the command.
+
+use crate::core::ignore_rules::IgnoreRules;
+
+/// A parsed `record` call
+#[derive(Debug, Clone, Default)]
+pub struct RecordArgs {
+    /// The words of the command joined with spaces, with the leading space
+    /// put back if the hook said there was one
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<i32>,
+    pub cwd: Option<String>,
+    pub session_id: Option<String>,
+    /// Whether the hook ran in a prompt or in a script sourcing it
+    pub interactive: Option<bool>,
+    /// What the shell keeps out of its own history
+    pub shell_rules: IgnoreRules,
+    /// Only queue it in the spool, for the next command that opens the database
+    pub fast: bool,
+}
+
+impl RecordArgs {
+    /// `None` when there's no command, which hooks sometimes send
+    pub fn parse(args: &[String]) -> Option<Self> {
+        let mut parsed = Self::default();
+        let mut command_parts = Vec::new();
+        let mut leading_space = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--command" => command_parts.extend(args.next().cloned()),
+                // PowerShell: multi-line commands and quotes survive the trip
+                "--command-base64" => {
+                    command_parts.extend(args.next().and_then(|e| decode_base64(e)));
+                }
+                "--exit-code" => parsed.exit_code = args.next().and_then(|v| v.parse().ok()),
+                "--duration" => parsed.duration_ms = args.next().and_then(|v| v.parse().ok()),
+                "--cwd" => parsed.cwd = args.next().cloned(),
+                "--session" => {
+                    parsed.session_id = args.next().filter(|s| !s.is_empty()).cloned();
+                }
+                "--interactive" => parsed.interactive = Some(true),
+                "--non-interactive" => parsed.interactive = Some(false),
+                "--fast" => parsed.fast = true,
+                // Typed with a leading space, which the shell may have stripped
+                "--leading-space" => leading_space = true,
+                "--histignore" => {
+                    if let Some(value) = args.next() {
+                        parsed
+                            .shell_rules
+                            .extend(IgnoreRules::from_histignore(value));
+                    }
+                }
+                "--history-ignore" => {
+                    if let Some(value) = args.next() {
+                        parsed
+                            .shell_rules
+                            .extend(IgnoreRules::from_history_ignore(value));
+                    }
+                }
+                arg => command_parts.push(arg.to_string()),
+            }
+        }
+
+        if command_parts.is_empty() {
+            return None;
+        }
+        parsed.command = command_parts.join(" ");
+        if leading_space && !parsed.command.starts_with(' ') {
+            parsed.command.insert(0, ' ');
+        }
+        Some(parsed)
+    }
+}
+
+/// UTF-8 text from base64, with PowerShell's CRLF line endings made plain
+pub fn decode_base64(encoded: &str) -> Option<String> {
+    use base64::Engine;
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    String::from_utf8(bytes)
+        .ok()
+        .map(|text| text.replace("\r\n", "\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use proptest::prelude::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let parsed = RecordArgs::parse(&strings(&[
+            "--exit-code",
+            "2",
+            "--duration",
+            "soon",
+            "--session",
+            "",
+            "--leading-space",
+            "--fast",
+            "--histignore",
+            "ls:pwd",
+            "git",
+            "status",
+        ]))
+        .unwrap();
+        assert_eq!(parsed.command, " git status");
+        assert_eq!(parsed.exit_code, Some(2));
+        assert_eq!(parsed.duration_ms, None);
+        assert_eq!(parsed.session_id, None);
+        assert!(parsed.fast);
+        assert!(parsed.shell_rules.matching("pwd").is_some());
+
+        // A flag at the very end has nothing to take
+        assert!(RecordArgs::parse(&strings(&["--cwd"])).is_none());
+        assert!(RecordArgs::parse(&[]).is_none());
+        let encoded = base64::engine::general_purpose::STANDARD.encode("a\r\nb");
+        let parsed = RecordArgs::parse(&strings(&["--command-base64", &encoded])).unwrap();
+        assert_eq!(parsed.command, "a\nb");
+    }
+
+    proptest! {
+        // Whatever the hook hands over, parsing returns
+        #[test]
+        fn parse_never_panics(args in prop::collection::vec(".*", 0..12)) {
+            let _ = RecordArgs::parse(&args);
+        }
+
+        #[test]
+        fn parse_round_trips(
+            command in "\\PC+",
+            exit_code in any::<i32>(),
+            duration_ms in any::<i32>(),
+            cwd in "\\PC+",
+            session in "[0-9a-f]{1,16}",
+        ) {
+            let args = vec![
+                "--exit-code".to_string(),
+                exit_code.to_string(),
+                "--duration".to_string(),
+                duration_ms.to_string(),
+                "--cwd".to_string(),
+                cwd.clone(),
+                "--session".to_string(),
+                session.clone(),
+                "--command".to_string(),
+                command.clone(),
+            ];
+            let parsed = RecordArgs::parse(&args).unwrap();
+            prop_assert!(!parsed.fast);
+            prop_assert_eq!(parsed.command, command);
+            prop_assert_eq!(parsed.exit_code, Some(exit_code));
+            prop_assert_eq!(parsed.duration_ms, Some(duration_ms));
+            prop_assert_eq!(parsed.cwd, Some(cwd));
+            prop_assert_eq!(parsed.session_id, Some(session));
+        }
+
+        #[test]
+        fn base64_round_trips(text in any::<String>()) {
+            let encoded = base64::engine::general_purpose::STANDARD.encode(&text);
+            prop_assert_eq!(decode_base64(&encoded), Some(text.replace("\r\n", "\n")));
+        }
+
+        #[test]
+        fn base64_never_panics(encoded in ".*") {
+            let _ = decode_base64(&encoded);
+        }
+    }
+}
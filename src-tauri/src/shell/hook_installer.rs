@@ -1,11 +1,21 @@
-/// Hook installer
-///
-/// Handles installation and uninstallation of shell hooks.
+//! Hook installer
+//!
+//! Handles installation and uninstallation of shell hooks.
+//!
+//! Everything setup puts in an rc file sits between `# >>> berri-recall >>>`
+//! and `# <<< berri-recall <<<`. Reinstalling rewrites that block where it
+//! is, uninstalling takes out exactly that block, and nothing outside it is
+//! ever touched. Rc files are written to a temporary file and renamed into
+//! place, so a crash mid-write can't leave half a `.zshrc`.
+//!
+//! Before any change the rc file is copied to `~/.berri-recall/rc-backups/`,
+//! which `setup --restore-rc <shell>` puts back.
 
 use crate::error::{RecallError, Result};
 use crate::shell::{Shell, ShellDetector};
+use chrono::Local;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Hook file contents embedded at compile time
 const BASH_HOOK: &str = include_str!("../../../hooks/bash.sh");
@@ -13,9 +23,22 @@ const ZSH_HOOK: &str = include_str!("../../../hooks/zsh.sh");
 const FISH_HOOK: &str = include_str!("../../../hooks/fish.fish");
 const POWERSHELL_HOOK: &str = include_str!("../../../hooks/powershell.ps1");
 
+/// First line of the block setup manages in rc files
+pub const BLOCK_START: &str = "# >>> berri-recall >>>";
+
+/// Last line of the block
+pub const BLOCK_END: &str = "# <<< berri-recall <<<";
+
+/// What older versions put above the source line, before there was a block
+const LEGACY_COMMENT: &str = "# berri-recall hook (auto-generated)";
+
+/// How many rc backups to keep per shell
+pub const KEEP_RC_BACKUPS: usize = 10;
+
 /// Hook installer
 pub struct HookInstaller {
     hooks_dir: PathBuf,
+    backups_dir: PathBuf,
 }
 
 impl HookInstaller {
@@ -29,8 +52,12 @@ impl HookInstaller {
             .ok_or_else(|| RecallError::Config("Could not determine home directory".to_string()))?;
 
         let hooks_dir = home.join(".berri-recall").join("hooks");
+        let backups_dir = home.join(".berri-recall").join("rc-backups");
 
-        Ok(Self { hooks_dir })
+        Ok(Self {
+            hooks_dir,
+            backups_dir,
+        })
     }
 
     /// Install hooks for the detected shell
@@ -53,6 +80,22 @@ impl HookInstaller {
     /// * `Ok(())` - Installation successful
     /// * `Err(RecallError)` - If installation fails
     pub fn install(&self, shell: Shell) -> Result<()> {
+        let hook_path = self.write_hook(shell)?;
+
+        // Add source line to RC file
+        self.update_rc_file(shell, &hook_path)?;
+
+        Ok(())
+    }
+
+    /// Write the hook script for a shell, leaving rc files alone
+    ///
+    /// For dotfile managers (chezmoi, yadm, home-manager) that own the rc
+    /// file: the user adds `snippet()` themselves.
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - Where the hook was written
+    pub fn write_hook(&self, shell: Shell) -> Result<PathBuf> {
         // Create hooks directory if it doesn't exist
         fs::create_dir_all(&self.hooks_dir)?;
 
@@ -71,10 +114,54 @@ impl HookInstaller {
             fs::set_permissions(&hook_path, perms)?;
         }
 
-        // Add source line to RC file
-        self.update_rc_file(shell, &hook_path)?;
+        Ok(hook_path)
+    }
 
-        Ok(())
+    /// What setup adds to the rc file for a shell
+    pub fn snippet(&self, shell: Shell) -> String {
+        let hook_path = self.hooks_dir.join(shell.hook_filename());
+        format!(
+            "{}\n# Managed by `berri-recall setup`, changes in here get replaced\n{}\n{}\n",
+            BLOCK_START,
+            shell.source_command(&hook_path),
+            BLOCK_END
+        )
+    }
+
+    /// The hook script itself, for pasting it in whole
+    pub fn hook_content(&self, shell: Shell) -> &str {
+        self.get_hook_content(shell)
+    }
+
+    /// Install hooks for a shell, sourced from a file of your choosing
+    ///
+    /// For managed dotfiles where the usual rc file isn't the one to edit. The
+    /// choice is remembered, so status and uninstall look in the same place.
+    pub fn install_to(&self, shell: Shell, rc_path: &Path) -> Result<()> {
+        fs::create_dir_all(&self.hooks_dir)?;
+
+        // Don't leave the hook sourced twice
+        let previous = self.rc_file(shell)?;
+        if previous != rc_path {
+            self.remove_from_rc_file(shell, &previous)?;
+        }
+
+        fs::write(self.rc_choice_path(shell), rc_path.to_string_lossy().as_bytes())?;
+        self.install(shell)
+    }
+
+    /// The rc file the hook for `shell` is sourced from
+    ///
+    /// What `install_to` was given, or the shell's usual one.
+    pub fn rc_file(&self, shell: Shell) -> Result<PathBuf> {
+        match fs::read_to_string(self.rc_choice_path(shell)) {
+            Ok(path) if !path.trim().is_empty() => Ok(PathBuf::from(path.trim())),
+            _ => shell.rc_file_path(),
+        }
+    }
+
+    fn rc_choice_path(&self, shell: Shell) -> PathBuf {
+        self.hooks_dir.join(format!("{}.rc-file", shell.name()))
     }
 
     /// Install hooks for all detected shells
@@ -103,33 +190,95 @@ impl HookInstaller {
         Ok(installed)
     }
 
+    /// Install hooks into every rc file that already exists, asking nothing
+    ///
+    /// For package-manager postinstall scripts: rc files are never created,
+    /// and ones that can't be written are left alone, as are the rc files of
+    /// shells that aren't on `PATH` anymore. Every shell gets an outcome, in
+    /// the order bash, zsh, fish, PowerShell.
+    pub fn install_existing(&self) -> Vec<(Shell, InstallOutcome)> {
+        self.install_existing_where(ShellDetector::is_installed)
+    }
+
+    fn install_existing_where(
+        &self,
+        installed: impl Fn(Shell) -> bool,
+    ) -> Vec<(Shell, InstallOutcome)> {
+        [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell]
+            .into_iter()
+            .map(|shell| (shell, self.install_if_present(shell, &installed)))
+            .collect()
+    }
+
+    fn install_if_present(
+        &self,
+        shell: Shell,
+        installed: impl Fn(Shell) -> bool,
+    ) -> InstallOutcome {
+        if !installed(shell) {
+            return InstallOutcome::NotInstalled;
+        }
+        let rc_path = match self.rc_file(shell) {
+            Ok(path) => path,
+            Err(e) => return InstallOutcome::Failed(e.to_string()),
+        };
+        if !rc_path.is_file() {
+            return InstallOutcome::NoRcFile;
+        }
+        if let Err(e) = fs::OpenOptions::new().append(true).open(&rc_path) {
+            return outcome_of(e.into());
+        }
+
+        let already = self.is_installed(shell);
+        match self.install(shell) {
+            Ok(()) if already => InstallOutcome::AlreadyInstalled,
+            Ok(()) => InstallOutcome::Installed,
+            Err(e) => outcome_of(e),
+        }
+    }
+
     /// Uninstall hooks for a specific shell
     ///
     /// # Arguments
     /// * `shell` - The shell to uninstall hooks from
     pub fn uninstall(&self, shell: Shell) -> Result<()> {
         let hook_path = self.hooks_dir.join(shell.hook_filename());
-        let rc_path = shell.rc_file_path()?;
+        let rc_path = self.rc_file(shell)?;
 
         // Remove source line from RC file
-        if rc_path.exists() {
-            let content = fs::read_to_string(&rc_path)?;
-            let source_cmd = shell.source_command(&hook_path);
-
-            let new_content: String = content
-                .lines()
-                .filter(|line| !line.contains(&source_cmd) && !line.contains("recall-cli"))
-                .collect::<Vec<_>>()
-                .join("\n");
-
-            fs::write(&rc_path, new_content)?;
-        }
+        self.remove_from_rc_file(shell, &rc_path)?;
 
         // Remove hook file
         if hook_path.exists() {
             fs::remove_file(&hook_path)?;
         }
 
+        let choice = self.rc_choice_path(shell);
+        if choice.exists() {
+            fs::remove_file(&choice)?;
+        }
+
+        Ok(())
+    }
+
+    /// Take the managed block back out of an rc file
+    fn remove_from_rc_file(&self, shell: Shell, rc_path: &Path) -> Result<()> {
+        if !rc_path.exists() {
+            return Ok(());
+        }
+
+        let hook_path = self.hooks_dir.join(shell.hook_filename());
+        let content = fs::read_to_string(rc_path)?;
+        let legacy = shell.source_command(&hook_path);
+
+        let (kept, _) = split_block(&content, &legacy)?;
+
+        // Nothing of ours in there, leave the file alone
+        if kept != content {
+            self.backup_rc_file(shell, rc_path)?;
+            write_atomically(rc_path, &kept)?;
+        }
+
         Ok(())
     }
 
@@ -141,20 +290,36 @@ impl HookInstaller {
     /// # Returns
     /// * `true` if hooks are installed, `false` otherwise
     pub fn is_installed(&self, shell: Shell) -> bool {
+        self.rc_file(shell)
+            .ok()
+            .and_then(|rc_path| fs::read_to_string(rc_path).ok())
+            .is_some_and(|content| self.rc_loads_hook(shell, &content))
+    }
+
+    /// Whether rc file `content` loads the hook, however it was added
+    ///
+    /// Setup's own line, a hand-written `source ~/.berri-recall/hooks/zsh.sh`
+    /// or the whole script pasted in.
+    fn rc_loads_hook(&self, shell: Shell, content: &str) -> bool {
+        // Pasted in whole (or generated by a dotfile manager)
+        let first_line = self.get_hook_content(shell)
+            .lines()
+            .find(|line| line.starts_with("# ") && line.contains("hook for berri-recall"));
+        if first_line.is_some_and(|line| content.contains(line)) {
+            return true;
+        }
+
         let hook_path = self.hooks_dir.join(shell.hook_filename());
         if !hook_path.exists() {
             return false;
         }
 
-        // Check if RC file contains source line
-        if let Ok(rc_path) = shell.rc_file_path() {
-            if let Ok(content) = fs::read_to_string(&rc_path) {
-                let source_cmd = shell.source_command(&hook_path);
-                return content.contains(&source_cmd);
-            }
-        }
-
-        false
+        let relative = Path::new(".berri-recall")
+            .join("hooks")
+            .join(shell.hook_filename());
+        content.contains(&shell.source_command(&hook_path))
+            || content.contains(&hook_path.to_string_lossy().to_string())
+            || content.contains(&relative.to_string_lossy().to_string())
     }
 
     /// Get hook content for a specific shell
@@ -168,8 +333,8 @@ impl HookInstaller {
     }
 
     /// Update the RC file to source the hook
-    fn update_rc_file(&self, shell: Shell, hook_path: &PathBuf) -> Result<()> {
-        let rc_path = shell.rc_file_path()?;
+    fn update_rc_file(&self, shell: Shell, hook_path: &Path) -> Result<()> {
+        let rc_path = self.rc_file(shell)?;
 
         // Create parent directories if they don't exist
         if let Some(parent) = rc_path.parent() {
@@ -177,32 +342,243 @@ impl HookInstaller {
         }
 
         // Read existing content or create new file
-        let mut content = if rc_path.exists() {
+        let content = if rc_path.exists() {
             fs::read_to_string(&rc_path)?
         } else {
             String::new()
         };
 
-        // Check if already installed
-        let source_cmd = shell.source_command(hook_path);
-        if content.contains(&source_cmd) {
-            return Ok(()); // Already installed
+        let legacy = shell.source_command(hook_path);
+        let updated = with_block(&content, &self.snippet(shell), &legacy)?;
+
+        // Already installed, and up to date
+        if updated == content {
+            return Ok(());
         }
 
-        // Add source line
-        if !content.ends_with('\n') && !content.is_empty() {
-            content.push('\n');
+        self.backup_rc_file(shell, &rc_path)?;
+        write_atomically(&rc_path, &updated)
+    }
+
+    /// Copy an rc file into the backups before changing it
+    ///
+    /// Saved as `<shell>-<time>.bak`, with `<shell>-<time>.path` saying where
+    /// it came from. A file that doesn't exist yet has nothing to save.
+    fn backup_rc_file(&self, shell: Shell, rc_path: &Path) -> Result<()> {
+        if !rc_path.exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.backups_dir)?;
+
+        // The counter keeps two edits in the same millisecond (install_to
+        // moving the block) apart and in order
+        let stamp = Local::now().format("%Y%m%d-%H%M%S%.3f");
+        let mut n = 1;
+        let mut name = format!("{}-{}-{:02}", shell.name(), stamp, n);
+        while self.backups_dir.join(format!("{}.bak", name)).exists() {
+            n += 1;
+            name = format!("{}-{}-{:02}", shell.name(), stamp, n);
         }
 
-        content.push_str("\n# berri-recall hook (auto-generated)\n");
-        content.push_str(&source_cmd);
-        content.push('\n');
+        fs::copy(rc_path, self.backups_dir.join(format!("{}.bak", name)))?;
+        fs::write(
+            self.backups_dir.join(format!("{}.path", name)),
+            rc_path.to_string_lossy().as_bytes(),
+        )?;
 
-        // Write back
-        fs::write(&rc_path, content)?;
+        for old in self.rc_backups(shell).into_iter().skip(KEEP_RC_BACKUPS) {
+            let _ = fs::remove_file(old.with_extension("path"));
+            let _ = fs::remove_file(old);
+        }
 
         Ok(())
     }
+
+    /// Backups of `shell`'s rc file, newest first
+    pub fn rc_backups(&self, shell: Shell) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(&self.backups_dir) else {
+            return Vec::new();
+        };
+
+        let prefix = format!("{}-", shell.name());
+        let mut backups: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "bak"))
+            .filter(|p| {
+                p.file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+            })
+            .collect();
+        // Names carry the time they were taken
+        backups.sort();
+        backups.reverse();
+        backups
+    }
+
+    /// Put the rc file back the way it was before the last change
+    ///
+    /// The backup is used up, so restoring again goes one change further
+    /// back.
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - The rc file that was restored
+    /// * `Err(RecallError)` - If there's no backup for `shell`
+    pub fn restore_rc(&self, shell: Shell) -> Result<PathBuf> {
+        let Some(backup) = self.rc_backups(shell).into_iter().next() else {
+            return Err(RecallError::Config(format!(
+                "No rc file backups for {} in {}",
+                shell,
+                self.backups_dir.display()
+            )));
+        };
+
+        let origin = backup.with_extension("path");
+        let rc_path = match fs::read_to_string(&origin) {
+            Ok(path) if !path.trim().is_empty() => PathBuf::from(path.trim()),
+            _ => self.rc_file(shell)?,
+        };
+
+        write_atomically(&rc_path, &fs::read_to_string(&backup)?)?;
+        let _ = fs::remove_file(&origin);
+        fs::remove_file(&backup)?;
+
+        Ok(rc_path)
+    }
+}
+
+/// What `install_existing` did for one shell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallOutcome {
+    /// The hook was added
+    Installed,
+    /// The rc file loaded it already, the block was brought up to date
+    AlreadyInstalled,
+    /// The shell isn't on `PATH`
+    NotInstalled,
+    /// There is no rc file, so the shell is most likely not used
+    NoRcFile,
+    /// The rc file (or its directory) can't be written to
+    NotWritable,
+    /// Anything else
+    Failed(String),
+}
+
+impl InstallOutcome {
+    /// Name used in `setup --non-interactive` output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Installed => "installed",
+            Self::AlreadyInstalled => "already-installed",
+            Self::NotInstalled => "not-installed",
+            Self::NoRcFile => "no-rc-file",
+            Self::NotWritable => "not-writable",
+            Self::Failed(_) => "failed",
+        }
+    }
+
+    /// Whether the shell loads the hook afterwards
+    pub fn has_hook(&self) -> bool {
+        matches!(self, Self::Installed | Self::AlreadyInstalled)
+    }
+}
+
+fn outcome_of(error: RecallError) -> InstallOutcome {
+    match &error {
+        RecallError::Io(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::ReadOnlyFilesystem
+            ) =>
+        {
+            InstallOutcome::NotWritable
+        }
+        _ => InstallOutcome::Failed(error.to_string()),
+    }
+}
+
+/// Find our block (or a pre-block install) in rc file `content`
+///
+/// # Returns
+/// * `(without, Some(at))` - `content` with it taken out, and the byte offset
+///   in `without` where it was
+/// * `(content, None)` - It isn't there
+fn split_block(content: &str, legacy_line: &str) -> Result<(String, Option<usize>)> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let is = |line: &str, marker: &str| line.trim_end() == marker;
+
+    let (start, end) = if let Some(start) = lines.iter().position(|l| is(l, BLOCK_START)) {
+        let Some(len) = lines[start..].iter().position(|l| is(l, BLOCK_END)) else {
+            return Err(RecallError::Config(format!(
+                "'{}' has no matching '{}', fix the rc file by hand",
+                BLOCK_START, BLOCK_END
+            )));
+        };
+        (start, start + len)
+    } else if let Some(line) = lines.iter().position(|l| l.trim_end() == legacy_line) {
+        // Older installs: the comment and the source line under it
+        match line.checked_sub(1) {
+            Some(comment) if is(lines[comment], LEGACY_COMMENT) => (comment, line),
+            _ => (line, line),
+        }
+    } else {
+        return Ok((content.to_string(), None));
+    };
+
+    // The blank line install put in front of it goes too
+    let start = match start.checked_sub(1) {
+        Some(blank) if lines[blank].trim().is_empty() => blank,
+        _ => start,
+    };
+
+    let before = lines[..start].concat();
+    let at = before.len();
+    Ok((before + &lines[end + 1..].concat(), Some(at)))
+}
+
+/// `content` with `block` in it: in place of the old one if there is one,
+/// at the end otherwise
+fn with_block(content: &str, block: &str, legacy_line: &str) -> Result<String> {
+    let (mut without, at) = split_block(content, legacy_line)?;
+    let at = at.unwrap_or(without.len());
+
+    let mut insert = String::new();
+    if at > 0 && !without[..at].ends_with('\n') {
+        insert.push('\n');
+    }
+    if at > 0 {
+        insert.push('\n');
+    }
+    insert.push_str(block);
+
+    without.insert_str(at, &insert);
+    Ok(without)
+}
+
+/// Replace `path` with `content` in one step
+///
+/// Written next to it under a temporary name, then renamed over it. A
+/// symlinked rc file (stow, home-manager) gets its target rewritten, the link
+/// stays a link.
+fn write_atomically(path: &Path, content: &str) -> Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.berri-recall-{}", name, std::process::id()));
+
+    fs::write(&tmp, content)?;
+    // Keep whatever permissions the original had
+    if let Ok(metadata) = fs::metadata(&path) {
+        let _ = fs::set_permissions(&tmp, metadata.permissions());
+    }
+    if let Err(e) = fs::rename(&tmp, &path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -214,6 +590,7 @@ mod tests {
         let temp = TempDir::new().unwrap();
         let installer = HookInstaller {
             hooks_dir: temp.path().join("hooks"),
+            backups_dir: temp.path().join("rc-backups"),
         };
         (installer, temp)
     }
@@ -230,7 +607,7 @@ mod tests {
 
         let bash_content = installer.get_hook_content(Shell::Bash);
         assert!(bash_content.contains("bash"));
-        assert!(bash_content.contains("__recall_hook"));
+        assert!(bash_content.contains("__berri_hook"));
 
         let zsh_content = installer.get_hook_content(Shell::Zsh);
         assert!(zsh_content.contains("zsh"));
@@ -243,4 +620,138 @@ mod tests {
         // Should not be installed initially
         assert!(!installer.is_installed(Shell::Bash));
     }
+
+    #[test]
+    fn test_detects_hand_added_snippet() {
+        let (installer, _temp) = create_test_installer();
+
+        // Pasted whole, no hook file needed
+        let pasted = format!("export EDITOR=vim\n{}", installer.hook_content(Shell::Zsh));
+        assert!(installer.rc_loads_hook(Shell::Zsh, &pasted));
+        assert!(!installer.rc_loads_hook(Shell::Bash, &pasted));
+
+        let by_hand = "source ~/.berri-recall/hooks/zsh.sh\n";
+        assert!(!installer.rc_loads_hook(Shell::Zsh, by_hand));
+        installer.write_hook(Shell::Zsh).unwrap();
+        assert!(installer.rc_loads_hook(Shell::Zsh, by_hand));
+        assert!(installer.rc_loads_hook(Shell::Zsh, &installer.snippet(Shell::Zsh)));
+        assert!(!installer.rc_loads_hook(Shell::Zsh, "source ~/.zsh_aliases\n"));
+    }
+
+    #[test]
+    fn test_managed_block() {
+        let (installer, temp) = create_test_installer();
+        let rc = temp.path().join(".bashrc");
+        let original = "export PATH=\"$HOME/bin:$PATH\"\nalias recall-cli='echo mine'";
+        fs::write(&rc, original).unwrap();
+
+        installer.install_to(Shell::Bash, &rc).unwrap();
+        installer.install(Shell::Bash).unwrap();
+        let installed = fs::read_to_string(&rc).unwrap();
+        assert_eq!(installed.matches(BLOCK_START).count(), 1);
+        assert!(installed.ends_with(&format!("{}\n", BLOCK_END)));
+
+        // Uninstalling leaves the user's lines exactly as they were
+        installer.uninstall(Shell::Bash).unwrap();
+        assert_eq!(fs::read_to_string(&rc).unwrap(), format!("{}\n", original));
+    }
+
+    #[test]
+    fn test_restore_rc() {
+        let (installer, temp) = create_test_installer();
+        let rc = temp.path().join(".zshrc");
+        fs::write(&rc, "setopt autocd\n").unwrap();
+
+        installer.install_to(Shell::Zsh, &rc).unwrap();
+        assert_eq!(installer.rc_backups(Shell::Zsh).len(), 1);
+        // Nothing changed, nothing saved
+        installer.install(Shell::Zsh).unwrap();
+        assert_eq!(installer.rc_backups(Shell::Zsh).len(), 1);
+        installer.uninstall(Shell::Zsh).unwrap();
+        assert_eq!(installer.rc_backups(Shell::Zsh).len(), 2);
+        assert!(installer.rc_backups(Shell::Bash).is_empty());
+
+        // Undo the uninstall, then the install
+        assert_eq!(installer.restore_rc(Shell::Zsh).unwrap(), rc);
+        assert!(fs::read_to_string(&rc).unwrap().contains(BLOCK_START));
+        installer.restore_rc(Shell::Zsh).unwrap();
+        assert_eq!(fs::read_to_string(&rc).unwrap(), "setopt autocd\n");
+        assert!(installer.restore_rc(Shell::Zsh).is_err());
+    }
+
+    #[test]
+    fn test_block_upgrades_in_place() {
+        let block = format!("{}\nsource new\n{}\n", BLOCK_START, BLOCK_END);
+
+        let old_block = format!("a\n\n{}\nsource old\n{}\nb\n", BLOCK_START, BLOCK_END);
+        assert_eq!(
+            with_block(&old_block, &block, "source old").unwrap(),
+            format!("a\n\n{}b\n", block)
+        );
+
+        // From before there were blocks
+        let legacy = format!("a\n\n{}\nsource old\nb\n", LEGACY_COMMENT);
+        assert_eq!(
+            with_block(&legacy, &block, "source old").unwrap(),
+            format!("a\n\n{}b\n", block)
+        );
+
+        assert_eq!(with_block("", &block, "source old").unwrap(), block);
+
+        let broken = format!("a\n{}\nsource old\n", BLOCK_START);
+        assert!(with_block(&broken, &block, "source old").is_err());
+    }
+
+    #[test]
+    fn test_install_to_custom_rc_file() {
+        let (installer, temp) = create_test_installer();
+        let first = temp.path().join("dotfiles/zsh/zshrc");
+        let second = temp.path().join("dotfiles/zsh/hooks.zsh");
+
+        installer.install_to(Shell::Zsh, &first).unwrap();
+        assert_eq!(installer.rc_file(Shell::Zsh).unwrap(), first);
+        assert!(installer.is_installed(Shell::Zsh));
+        assert!(fs::read_to_string(&first).unwrap().contains("source"));
+
+        // Moving it takes the line out of the old file
+        installer.install_to(Shell::Zsh, &second).unwrap();
+        assert!(!fs::read_to_string(&first).unwrap().contains("source"));
+        assert!(fs::read_to_string(&second).unwrap().contains("source"));
+
+        installer.uninstall(Shell::Zsh).unwrap();
+        assert!(!fs::read_to_string(&second).unwrap().contains("source"));
+        assert!(!installer.is_installed(Shell::Zsh));
+    }
+
+    #[test]
+    fn test_install_existing() {
+        let (installer, temp) = create_test_installer();
+        let rc = |shell: Shell| temp.path().join(format!("{}rc", shell.name()));
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            fs::create_dir_all(&installer.hooks_dir).unwrap();
+            fs::write(installer.rc_choice_path(shell), rc(shell).to_string_lossy().as_bytes())
+                .unwrap();
+        }
+        fs::write(rc(Shell::Bash), "export EDITOR=vim\n").unwrap();
+        fs::write(rc(Shell::Zsh), "").unwrap();
+        installer.install(Shell::Zsh).unwrap();
+        // Left over from a fish that's since been uninstalled
+        fs::create_dir_all(temp.path()).unwrap();
+        fs::write(rc(Shell::Fish), "").unwrap();
+
+        let outcomes = installer.install_existing_where(|shell| shell != Shell::Fish);
+        assert_eq!(
+            outcomes,
+            vec![
+                (Shell::Bash, InstallOutcome::Installed),
+                (Shell::Zsh, InstallOutcome::AlreadyInstalled),
+                (Shell::Fish, InstallOutcome::NotInstalled),
+                (Shell::PowerShell, InstallOutcome::NoRcFile),
+            ]
+        );
+        // Missing rc files stay missing, and so does the hook in a stale one
+        assert!(!rc(Shell::PowerShell).exists());
+        assert!(!installer.is_installed(Shell::Fish));
+        assert!(installer.is_installed(Shell::Bash));
+    }
 }
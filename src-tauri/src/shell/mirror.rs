@@ -0,0 +1,165 @@
+//! Recording from the shell's history file instead of a hook
+//!
+//! `mirror --watch` follows a history file as the shell appends to it and
+//! records each new command, so nothing runs at the prompt. History files
+//! don't say where a command ran: the directory is a guess, starting from
+//! where an open shell of that kind is (on Linux) or your home, and
+//! following `cd`s as they go by. Exit codes and durations aren't known.
+
+use crate::error::Result;
+use crate::shell::{history, Shell};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Where mirroring got to in a file, followed by the file's path
+pub const OFFSET_PREFIX: &str = "mirror.offset:";
+
+/// The directory the file's last `cd` went to, followed by the file's path
+pub const CWD_PREFIX: &str = "mirror.cwd:";
+
+/// A history file, read as it grows
+#[derive(Debug)]
+pub struct Tail {
+    path: PathBuf,
+    offset: u64,
+}
+
+impl Tail {
+    /// Follow `path` from `offset` bytes in, or from its current end
+    pub fn new(path: PathBuf, offset: Option<u64>) -> Self {
+        let offset =
+            offset.unwrap_or_else(|| std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0));
+        Self { path, offset }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// How far it's been read
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Whole entries added since the last read
+    ///
+    /// A file that got shorter was rewritten (zsh and fish trim theirs), so
+    /// reading picks up from its new end instead of importing it all again.
+    pub fn read(&mut self, shell: Shell) -> Result<String> {
+        let len = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(String::new()),
+            Err(e) => return Err(e.into()),
+        };
+        if len <= self.offset {
+            self.offset = len;
+            return Ok(String::new());
+        }
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let whole = history::complete(shell, &bytes);
+        self.offset += whole as u64;
+        Ok(String::from_utf8_lossy(&bytes[..whole]).into_owned())
+    }
+}
+
+/// Where an open `shell` is, the newest one if there are several
+///
+/// Read from `/proc`, so Linux only; `None` elsewhere or with none open.
+pub fn guess_cwd(shell: Shell) -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    {
+        let name = match shell {
+            Shell::PowerShell => "pwsh",
+            _ => shell.name(),
+        };
+        let mut newest: Option<(u32, PathBuf)> = None;
+        for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse().ok()) else {
+                continue;
+            };
+            let comm = std::fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+            if comm.trim() != name {
+                continue;
+            }
+            // Other users' shells can't be read, which is as well
+            let Ok(cwd) = std::fs::read_link(entry.path().join("cwd")) else {
+                continue;
+            };
+            if newest.as_ref().is_none_or(|(newest, _)| pid > *newest) {
+                newest = Some((pid, cwd));
+            }
+        }
+        newest.map(|(_, cwd)| cwd)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = shell;
+        None
+    }
+}
+
+/// Wake up when `path` changes
+///
+/// The directory is watched rather than the file, since shells replace the
+/// file when they trim it. Keep the watcher for as long as you want events.
+#[cfg(feature = "watch")]
+pub fn watch(path: &Path) -> Result<(notify::RecommendedWatcher, tokio::sync::mpsc::Receiver<()>)> {
+    use notify::Watcher;
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(1);
+    let file = path.to_path_buf();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if event.is_ok_and(|e| e.paths.contains(&file)) {
+            // One wake-up already waiting covers this change too
+            let _ = sender.try_send(());
+        }
+    })?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+    Ok((watcher, receiver))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".zsh_history");
+        std::fs::write(&path, ": 1:0;old\n").unwrap();
+
+        // Only what comes after it starts
+        let mut tail = Tail::new(path.clone(), None);
+        assert_eq!(tail.read(Shell::Zsh).unwrap(), "");
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b": 2:0;make\n: 3:0;for f in *\\\n").unwrap();
+        assert_eq!(tail.read(Shell::Zsh).unwrap(), ": 2:0;make\n");
+        file.write_all(b"do echo $f\\\ndone\n").unwrap();
+        assert_eq!(
+            tail.read(Shell::Zsh).unwrap(),
+            ": 3:0;for f in *\\\ndo echo $f\\\ndone\n"
+        );
+        assert_eq!(tail.offset(), std::fs::metadata(&path).unwrap().len());
+
+        // Trimmed by the shell: carry on from the new end
+        std::fs::write(&path, ": 3:0;done\n").unwrap();
+        assert_eq!(tail.read(Shell::Zsh).unwrap(), "");
+        assert_eq!(tail.offset(), 11);
+        assert_eq!(
+            Tail::new(path, Some(0)).read(Shell::Zsh).unwrap(),
+            ": 3:0;done\n"
+        );
+    }
+}
@@ -1,9 +1,12 @@
-/// Shell integration module
-///
-/// Handles shell detection and hook installation for automatic command recording.
+//! Shell integration module
+//!
+//! Handles shell detection and hook installation for automatic command recording.
 
+pub mod history;
+pub mod hook_args;
 pub mod hook_installer;
+pub mod mirror;
 pub mod shell_detector;
 
-pub use hook_installer::HookInstaller;
+pub use hook_installer::{HookInstaller, InstallOutcome};
 pub use shell_detector::{Shell, ShellDetector};
@@ -1,10 +1,11 @@
-/// Shell detection logic
-///
-/// Detects which shell the user is running and provides shell-specific configuration paths.
+//! Shell detection logic
+//!
+//! Detects which shell the user is running and provides shell-specific configuration paths.
 
 use crate::error::{RecallError, Result};
 use std::env;
-use std::path::PathBuf;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 
 /// Supported shells
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -39,36 +40,66 @@ impl Shell {
     /// Get the RC file path for this shell
     ///
     /// Returns the configuration file that should be modified to source the hook.
+    /// Follows `ZDOTDIR` for zsh and `XDG_CONFIG_HOME` for fish, and on macOS
+    /// picks the file bash login shells actually read.
     pub fn rc_file_path(&self) -> Result<PathBuf> {
         let home = dirs::home_dir().ok_or_else(|| {
             RecallError::Config("Could not determine home directory".to_string())
         })?;
 
-        let path = match self {
+        Ok(self.resolve_rc_file(
+            &home,
+            |name| env::var(name).ok().filter(|value| !value.is_empty()),
+            cfg!(target_os = "macos"),
+        ))
+    }
+
+    fn resolve_rc_file(
+        &self,
+        home: &Path,
+        var: impl Fn(&str) -> Option<String>,
+        macos: bool,
+    ) -> PathBuf {
+        match self {
             Shell::Bash => {
-                // Prefer .bashrc, fallback to .bash_profile
                 let bashrc = home.join(".bashrc");
-                if bashrc.exists() {
+                let profile = home.join(".bash_profile");
+                if macos {
+                    // Terminal.app starts login shells, which only read
+                    // .bash_profile. Use .bashrc only if the profile pulls it in.
+                    let sources_bashrc = std::fs::read_to_string(&profile)
+                        .is_ok_and(|content| content.contains(".bashrc"));
+                    if sources_bashrc {
+                        bashrc
+                    } else {
+                        profile
+                    }
+                } else if bashrc.exists() || !profile.exists() {
+                    // Prefer .bashrc, fallback to .bash_profile
                     bashrc
                 } else {
-                    home.join(".bash_profile")
+                    profile
                 }
             }
-            Shell::Zsh => home.join(".zshrc"),
-            Shell::Fish => home.join(".config/fish/config.fish"),
+            Shell::Zsh => var("ZDOTDIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| home.to_path_buf())
+                .join(".zshrc"),
+            Shell::Fish => var("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| home.join(".config"))
+                .join("fish/config.fish"),
             Shell::PowerShell => {
                 // PowerShell profile location
                 home.join("Documents/PowerShell/Microsoft.PowerShell_profile.ps1")
             }
-        };
-
-        Ok(path)
+        }
     }
 
     /// Get the source command for this shell
     ///
     /// Returns the command to add to the RC file to source the hook.
-    pub fn source_command(&self, hook_path: &PathBuf) -> String {
+    pub fn source_command(&self, hook_path: &Path) -> String {
         match self {
             Shell::Bash | Shell::Zsh => {
                 format!("[ -f \"{}\" ] && source \"{}\"", hook_path.display(), hook_path.display())
@@ -83,6 +114,23 @@ impl Shell {
     }
 }
 
+impl std::str::FromStr for Shell {
+    type Err = RecallError;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            "powershell" | "pwsh" => Ok(Shell::PowerShell),
+            _ => Err(RecallError::Config(format!(
+                "Unsupported shell: {} (bash, zsh, fish or powershell)",
+                name
+            ))),
+        }
+    }
+}
+
 impl std::fmt::Display for Shell {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.name())
@@ -104,8 +152,8 @@ impl ShellDetector {
         // Check SHELL environment variable
         if let Ok(shell_path) = env::var("SHELL") {
             let shell_name = shell_path
-                .split('/')
-                .last()
+                .rsplit('/')
+                .next()
                 .unwrap_or("")
                 .to_lowercase();
 
@@ -163,6 +211,23 @@ impl ShellDetector {
         shells
     }
 
+    /// Whether the shell's program is somewhere on `PATH`
+    pub fn is_installed(shell: Shell) -> bool {
+        env::var_os("PATH").is_some_and(|path| Self::find_in(&path, shell))
+    }
+
+    fn find_in(path: &OsStr, shell: Shell) -> bool {
+        let programs: &[&str] = match shell {
+            Shell::PowerShell => &["pwsh", "powershell"],
+            _ => &[shell.name()],
+        };
+        env::split_paths(path).any(|dir| {
+            programs.iter().any(|program| {
+                dir.join(program).is_file() || dir.join(format!("{}.exe", program)).is_file()
+            })
+        })
+    }
+
     /// Check if a process with the given name is running
     #[cfg(target_os = "macos")]
     fn is_process_running(name: &str) -> bool {
@@ -196,6 +261,13 @@ mod tests {
         assert_eq!(Shell::PowerShell.hook_filename(), "powershell.ps1");
     }
 
+    #[test]
+    fn test_shell_from_str() {
+        assert_eq!("zsh".parse::<Shell>().unwrap(), Shell::Zsh);
+        assert_eq!("pwsh".parse::<Shell>().unwrap(), Shell::PowerShell);
+        assert!("tcsh".parse::<Shell>().is_err());
+    }
+
     #[test]
     fn test_shell_display() {
         assert_eq!(Shell::Bash.to_string(), "bash");
@@ -210,6 +282,38 @@ mod tests {
         let _ = Shell::Fish.rc_file_path();
     }
 
+    #[test]
+    fn test_rc_file_follows_environment() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        let none = |_: &str| None;
+        let vars = |name: &str| match name {
+            "ZDOTDIR" => Some("/dots/zsh".to_string()),
+            "XDG_CONFIG_HOME" => Some("/dots/config".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(Shell::Zsh.resolve_rc_file(home, none, false), home.join(".zshrc"));
+        assert_eq!(
+            Shell::Zsh.resolve_rc_file(home, vars, false),
+            PathBuf::from("/dots/zsh/.zshrc")
+        );
+        assert_eq!(
+            Shell::Fish.resolve_rc_file(home, none, false),
+            home.join(".config/fish/config.fish")
+        );
+        assert_eq!(
+            Shell::Fish.resolve_rc_file(home, vars, false),
+            PathBuf::from("/dots/config/fish/config.fish")
+        );
+
+        // Login shells on macOS read .bash_profile
+        assert_eq!(Shell::Bash.resolve_rc_file(home, none, false), home.join(".bashrc"));
+        assert_eq!(Shell::Bash.resolve_rc_file(home, none, true), home.join(".bash_profile"));
+        std::fs::write(home.join(".bash_profile"), "[ -f ~/.bashrc ] && . ~/.bashrc\n").unwrap();
+        assert_eq!(Shell::Bash.resolve_rc_file(home, none, true), home.join(".bashrc"));
+    }
+
     #[test]
     fn test_source_command() {
         let path = PathBuf::from("/home/user/.recall/bash.sh");
@@ -222,6 +326,18 @@ mod tests {
         assert!(fish_cmd.contains("source"));
     }
 
+    #[test]
+    fn test_find_in_path() {
+        let bin = tempfile::tempdir().unwrap();
+        std::fs::write(bin.path().join("zsh"), "").unwrap();
+        std::fs::write(bin.path().join("pwsh.exe"), "").unwrap();
+        let path = env::join_paths(["/nonexistent", bin.path().to_str().unwrap()]).unwrap();
+
+        assert!(ShellDetector::find_in(&path, Shell::Zsh));
+        assert!(ShellDetector::find_in(&path, Shell::PowerShell));
+        assert!(!ShellDetector::find_in(&path, Shell::Fish));
+    }
+
     #[test]
     fn test_detect_all() {
         let shells = ShellDetector::detect_all();
@@ -0,0 +1,286 @@
+//! How output looks: colors, glyphs and emoji
+//!
+//! Everything that prints a ✓, an arrow, an emoji or a color asks here first,
+//! so one place decides what this terminal gets. Three settings steer it:
+//!
+//! * `color` (`auto`, `always`, `never`): on `auto`, only when stdout is a
+//!   terminal and `NO_COLOR` isn't set
+//! * `glyphs` (`auto`, `unicode`, `ascii`): on `auto`, ASCII when `TERM` is
+//!   `dumb` or the locale isn't UTF-8
+//! * `emoji` (`auto`, `always`, `never`): on `auto`, wherever glyphs are
+//!   Unicode
+//!
+//! Until the settings are read (or for commands that never open the
+//! database) everything is `auto`.
+
+use crate::db::Config;
+use std::io::IsTerminal;
+use std::sync::RwLock;
+
+/// What output may use
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub color: bool,
+    pub unicode: bool,
+    pub emoji: bool,
+}
+
+impl Default for Theme {
+    /// Unicode and emoji, no color: what tests and plain files get
+    fn default() -> Self {
+        Self {
+            color: false,
+            unicode: true,
+            emoji: true,
+        }
+    }
+}
+
+/// What `auto` goes by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Terminal {
+    /// stdout is a terminal
+    pub tty: bool,
+    /// `NO_COLOR` is set to something
+    pub no_color: bool,
+    /// The terminal can draw more than ASCII
+    pub unicode: bool,
+}
+
+impl Terminal {
+    pub fn detect() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        // The first of these that's set is the locale in effect
+        let locale = var("LC_ALL").or_else(|| var("LC_CTYPE")).or_else(|| var("LANG"));
+        let utf8 = locale.is_none_or(|l| {
+            let l = l.to_lowercase();
+            l.contains("utf-8") || l.contains("utf8")
+        });
+
+        Self {
+            tty: std::io::stdout().is_terminal(),
+            no_color: var("NO_COLOR").is_some(),
+            unicode: utf8 && var("TERM").as_deref() != Some("dumb"),
+        }
+    }
+}
+
+impl Theme {
+    /// Settle the three settings against the terminal
+    ///
+    /// Unknown values count as `auto`.
+    pub fn resolve(color: &str, glyphs: &str, emoji: &str, terminal: Terminal) -> Self {
+        let unicode = match glyphs {
+            "unicode" => true,
+            "ascii" => false,
+            _ => terminal.unicode,
+        };
+        Self {
+            color: match color {
+                "always" => true,
+                "never" => false,
+                _ => terminal.tty && !terminal.no_color,
+            },
+            unicode,
+            emoji: match emoji {
+                "always" => true,
+                "never" => false,
+                _ => unicode,
+            },
+        }
+    }
+
+    /// From the settings, for this terminal
+    pub fn from_config(config: &Config) -> Self {
+        Self::resolve(
+            config.color(),
+            config.glyphs(),
+            config.emoji(),
+            Terminal::detect(),
+        )
+    }
+}
+
+static THEME: RwLock<Option<Theme>> = RwLock::new(None);
+
+/// The theme in effect
+pub fn theme() -> Theme {
+    if let Some(theme) = *THEME.read().unwrap_or_else(|e| e.into_inner()) {
+        return theme;
+    }
+    // Tests check output whatever terminal runs them
+    if cfg!(test) {
+        Theme::default()
+    } else {
+        Theme::resolve("auto", "auto", "auto", Terminal::detect())
+    }
+}
+
+/// Use `theme` from now on
+pub fn set_theme(theme: Theme) {
+    *THEME.write().unwrap_or_else(|e| e.into_inner()) = Some(theme);
+}
+
+/// Marks that have an ASCII stand-in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Glyph {
+    Ok,
+    Fail,
+    Warn,
+    Arrow,
+    Ellipsis,
+    Dot,
+    Dash,
+}
+
+impl Glyph {
+    pub fn unicode(self) -> &'static str {
+        match self {
+            Glyph::Ok => "✓",
+            Glyph::Fail => "✗",
+            Glyph::Warn => "⚠",
+            Glyph::Arrow => "→",
+            Glyph::Ellipsis => "…",
+            Glyph::Dot => "·",
+            Glyph::Dash => "—",
+        }
+    }
+
+    pub fn ascii(self) -> &'static str {
+        match self {
+            Glyph::Ok => "ok",
+            Glyph::Fail => "x",
+            Glyph::Warn => "!",
+            Glyph::Arrow => "->",
+            Glyph::Ellipsis => "...",
+            Glyph::Dot => "-",
+            Glyph::Dash => "--",
+        }
+    }
+}
+
+/// `glyph` as this terminal should see it
+pub fn glyph(glyph: Glyph) -> &'static str {
+    if theme().unicode {
+        glyph.unicode()
+    } else {
+        glyph.ascii()
+    }
+}
+
+/// `items` with `glyph` between them: `a → b → c`
+pub fn joined<S: AsRef<str>>(items: &[S], glyph: Glyph) -> String {
+    let separator = format!(" {} ", self::glyph(glyph));
+    items
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(&separator)
+}
+
+/// `emoji` and a space, or nothing where emoji are off
+pub fn emoji(emoji: &str) -> String {
+    if theme().emoji {
+        format!("{} ", emoji)
+    } else {
+        String::new()
+    }
+}
+
+/// What a piece of text is, for coloring it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    /// What a search matched
+    Match,
+    /// Context that matters less than the line above it
+    Dim,
+    Good,
+    Bad,
+    Warning,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Match => "1;33",
+            Style::Dim => "2",
+            Style::Good => "32",
+            Style::Bad => "31",
+            Style::Warning => "33",
+        }
+    }
+}
+
+/// `text` in `style`'s color, or as it is without color
+pub fn paint(style: Style, text: &str) -> String {
+    if theme().color {
+        paint_always(style, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// `text` in `style`'s color whatever the theme says
+pub fn paint_always(style: Style, text: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", style.code(), text)
+}
+
+/// A green ✓, for lines saying something worked
+pub fn ok() -> String {
+    paint(Style::Good, glyph(Glyph::Ok))
+}
+
+/// A red ✗, for lines saying something didn't
+pub fn fail() -> String {
+    paint(Style::Bad, glyph(Glyph::Fail))
+}
+
+/// A yellow ⚠, for warnings
+pub fn warn() -> String {
+    paint(Style::Warning, glyph(Glyph::Warn))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let terminal = Terminal {
+            tty: true,
+            no_color: false,
+            unicode: true,
+        };
+        assert_eq!(
+            Theme::resolve("auto", "auto", "auto", terminal),
+            Theme {
+                color: true,
+                unicode: true,
+                emoji: true,
+            }
+        );
+
+        // Piped, NO_COLOR, dumb terminal: plain everything
+        let plain = Terminal {
+            tty: false,
+            no_color: true,
+            unicode: false,
+        };
+        assert_eq!(
+            Theme::resolve("auto", "auto", "auto", plain),
+            Theme {
+                color: false,
+                unicode: false,
+                emoji: false,
+            }
+        );
+
+        // Saying so beats what the terminal looks like
+        let forced = Theme::resolve("always", "unicode", "never", plain);
+        assert!(forced.color && forced.unicode && !forced.emoji);
+        assert!(!Theme::resolve("never", "ascii", "auto", terminal).emoji);
+
+        assert_eq!(Glyph::Arrow.ascii(), "->");
+        assert_eq!(paint_always(Style::Bad, "x"), "\x1b[31mx\x1b[0m");
+    }
+}